@@ -0,0 +1,229 @@
+#ifndef PROBE_RS_H
+#define PROBE_RS_H
+
+/* Generated with cbindgen from probe-rs-capi/src/lib.rs, do not edit manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The state of a core.
+ */
+typedef enum ProbeRsCoreStatus {
+  /**
+   * The state of the core is not known.
+   */
+  PROBE_RS_CORE_STATUS_UNKNOWN = 0,
+  /**
+   * The core is running.
+   */
+  PROBE_RS_CORE_STATUS_RUNNING = 1,
+  /**
+   * The core is halted.
+   */
+  PROBE_RS_CORE_STATUS_HALTED = 2,
+  /**
+   * The core is locked up, e.g. after a double fault.
+   */
+  PROBE_RS_CORE_STATUS_LOCKED_UP = 3,
+  /**
+   * The core is sleeping.
+   */
+  PROBE_RS_CORE_STATUS_SLEEPING = 4,
+} ProbeRsCoreStatus;
+
+/**
+ * The file formats which can be flashed.
+ */
+typedef enum ProbeRsFormat {
+  /**
+   * An ELF file.
+   */
+  PROBE_RS_FORMAT_ELF = 0,
+  /**
+   * An Intel HEX file.
+   */
+  PROBE_RS_FORMAT_HEX = 1,
+} ProbeRsFormat;
+
+/**
+ * The result of a call into the API.
+ */
+typedef enum ProbeRsStatus {
+  /**
+   * The call succeeded.
+   */
+  PROBE_RS_STATUS_OK = 0,
+  /**
+   * An argument was invalid, e.g. a null pointer.
+   */
+  PROBE_RS_STATUS_INVALID_ARGUMENT = -1,
+  /**
+   * No probe was found.
+   */
+  PROBE_RS_STATUS_NO_PROBE = -2,
+  /**
+   * The probe could not be opened, or the communication with it failed.
+   */
+  PROBE_RS_STATUS_PROBE = -3,
+  /**
+   * The operation on the target failed.
+   */
+  PROBE_RS_STATUS_TARGET = -4,
+  /**
+   * Flashing failed.
+   */
+  PROBE_RS_STATUS_FLASH = -5,
+  /**
+   * The operation timed out.
+   */
+  PROBE_RS_STATUS_TIMEOUT = -6,
+  /**
+   * probe-rs panicked. The session should be closed.
+   */
+  PROBE_RS_STATUS_PANIC = -7,
+} ProbeRsStatus;
+
+/**
+ * A debug session with a target, created with [`probe_rs_session_open`].
+ */
+typedef struct ProbeRsSession ProbeRsSession;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns a description of the last error on the calling thread, or an empty string.
+ *
+ * The string stays valid until the next call into the API on the same thread.
+ */
+const char *probe_rs_last_error(void);
+
+/**
+ * Opens a probe and attaches to the chip `chip`, e.g. "nRF52840_xxAA".
+ *
+ * `probe` selects the probe as "VID:PID" or "VID:PID:SERIAL". If it is null, the first probe
+ * found is used. On success, `*session` is set to the new session, which has to be closed with
+ * [`probe_rs_session_close`].
+ *
+ * # Safety
+ *
+ * `probe` has to be null or a nul-terminated string, `chip` a nul-terminated string, and
+ * `session` has to point to writable memory.
+ */
+enum ProbeRsStatus probe_rs_session_open(const char *probe,
+                                         const char *chip,
+                                         struct ProbeRsSession **session);
+
+/**
+ * Detaches from the target and closes the probe.
+ *
+ * # Safety
+ *
+ * `session` has to be null or a session returned by [`probe_rs_session_open`], which was not
+ * closed yet.
+ */
+void probe_rs_session_close(struct ProbeRsSession *session);
+
+/**
+ * Reads `length` bytes of memory at `address` into `data`, using the core `core`.
+ *
+ * # Safety
+ *
+ * `session` has to be a session returned by [`probe_rs_session_open`], and `data` has to point
+ * to at least `length` writable bytes.
+ */
+enum ProbeRsStatus probe_rs_read_memory(struct ProbeRsSession *session,
+                                        size_t core,
+                                        uint64_t address,
+                                        uint8_t *data,
+                                        size_t length);
+
+/**
+ * Writes `length` bytes from `data` to memory at `address`, using the core `core`.
+ *
+ * # Safety
+ *
+ * `session` has to be a session returned by [`probe_rs_session_open`], and `data` has to point
+ * to at least `length` readable bytes.
+ */
+enum ProbeRsStatus probe_rs_write_memory(struct ProbeRsSession *session,
+                                         size_t core,
+                                         uint64_t address,
+                                         const uint8_t *data,
+                                         size_t length);
+
+/**
+ * Flashes the file at `path` to the target.
+ *
+ * # Safety
+ *
+ * `session` has to be a session returned by [`probe_rs_session_open`], and `path` a
+ * nul-terminated string.
+ */
+enum ProbeRsStatus probe_rs_flash_file(struct ProbeRsSession *session,
+                                       const char *path,
+                                       enum ProbeRsFormat format);
+
+/**
+ * Halts the core `core`, waiting up to `timeout_ms` milliseconds for it to halt.
+ *
+ * # Safety
+ *
+ * `session` has to be a session returned by [`probe_rs_session_open`].
+ */
+enum ProbeRsStatus probe_rs_core_halt(struct ProbeRsSession *session,
+                                      size_t core,
+                                      uint32_t timeout_ms);
+
+/**
+ * Resumes the core `core`.
+ *
+ * # Safety
+ *
+ * `session` has to be a session returned by [`probe_rs_session_open`].
+ */
+enum ProbeRsStatus probe_rs_core_run(struct ProbeRsSession *session, size_t core);
+
+/**
+ * Resets the core `core`, and lets it run.
+ *
+ * # Safety
+ *
+ * `session` has to be a session returned by [`probe_rs_session_open`].
+ */
+enum ProbeRsStatus probe_rs_core_reset(struct ProbeRsSession *session, size_t core);
+
+/**
+ * Resets the core `core`, and halts it before the first instruction, waiting up to
+ * `timeout_ms` milliseconds for it to halt.
+ *
+ * # Safety
+ *
+ * `session` has to be a session returned by [`probe_rs_session_open`].
+ */
+enum ProbeRsStatus probe_rs_core_reset_and_halt(struct ProbeRsSession *session,
+                                                size_t core,
+                                                uint32_t timeout_ms);
+
+/**
+ * Stores the state of the core `core` in `*status`.
+ *
+ * # Safety
+ *
+ * `session` has to be a session returned by [`probe_rs_session_open`], and `status` has to
+ * point to writable memory.
+ */
+enum ProbeRsStatus probe_rs_core_status(struct ProbeRsSession *session,
+                                        size_t core,
+                                        enum ProbeRsCoreStatus *status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PROBE_RS_H */
@@ -0,0 +1,491 @@
+//! C bindings for probe-rs.
+//!
+//! The API is declared in `include/probe_rs.h`, which is generated from this file with
+//! cbindgen. Every function returns a [`ProbeRsStatus`]. If a call fails,
+//! [`probe_rs_last_error`] describes what went wrong.
+
+use std::{
+    cell::RefCell,
+    error::Error as StdError,
+    ffi::{c_char, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    time::Duration,
+};
+
+use probe_rs::{
+    flashing::{self, Format},
+    probe::{list::Lister, DebugProbeError, DebugProbeSelector},
+    CoreStatus, Error, MemoryInterface, Permissions, Session,
+};
+
+/// The result of a call into the API.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeRsStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// An argument was invalid, e.g. a null pointer.
+    InvalidArgument = -1,
+    /// No probe was found.
+    NoProbe = -2,
+    /// The probe could not be opened, or the communication with it failed.
+    Probe = -3,
+    /// The operation on the target failed.
+    Target = -4,
+    /// Flashing failed.
+    Flash = -5,
+    /// The operation timed out.
+    Timeout = -6,
+    /// probe-rs panicked. The session should be closed.
+    Panic = -7,
+}
+
+/// The file formats which can be flashed.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeRsFormat {
+    /// An ELF file.
+    Elf = 0,
+    /// An Intel HEX file.
+    Hex = 1,
+}
+
+/// The state of a core.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeRsCoreStatus {
+    /// The state of the core is not known.
+    Unknown = 0,
+    /// The core is running.
+    Running = 1,
+    /// The core is halted.
+    Halted = 2,
+    /// The core is locked up, e.g. after a double fault.
+    LockedUp = 3,
+    /// The core is sleeping.
+    Sleeping = 4,
+}
+
+/// A debug session with a target, created with [`probe_rs_session_open`].
+pub struct ProbeRsSession {
+    session: Session,
+}
+
+struct ApiError {
+    status: ProbeRsStatus,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: ProbeRsStatus, error: &dyn StdError) -> Self {
+        // C callers only get the message, so include the whole chain of causes.
+        let mut message = error.to_string();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            message.push_str(&format!(": {cause}"));
+            source = cause.source();
+        }
+
+        Self { status, message }
+    }
+
+    fn invalid_argument(message: String) -> Self {
+        Self {
+            status: ProbeRsStatus::InvalidArgument,
+            message,
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let status = match error {
+            Error::Probe(_) => ProbeRsStatus::Probe,
+            Error::Timeout => ProbeRsStatus::Timeout,
+            _ => ProbeRsStatus::Target,
+        };
+
+        Self::new(status, &error)
+    }
+}
+
+impl From<DebugProbeError> for ApiError {
+    fn from(error: DebugProbeError) -> Self {
+        Self::new(ProbeRsStatus::Probe, &error)
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Runs `f`, and turns its result into a status.
+///
+/// Panics must not unwind into C code, so they are caught and reported as well.
+fn call(f: impl FnOnce() -> Result<(), ApiError>) -> ProbeRsStatus {
+    let error = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return ProbeRsStatus::Ok,
+        Ok(Err(error)) => error,
+        Err(_) => ApiError {
+            status: ProbeRsStatus::Panic,
+            message: "probe-rs panicked, please report this as a bug".to_string(),
+        },
+    };
+
+    let message = CString::new(error.message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+
+    error.status
+}
+
+/// Converts a C string argument.
+///
+/// # Safety
+///
+/// `ptr` has to be null or point to a nul-terminated string.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, ApiError> {
+    if ptr.is_null() {
+        return Err(ApiError::invalid_argument(format!("`{name}` is null")));
+    }
+
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| ApiError::invalid_argument(format!("`{name}` is not valid UTF-8")))
+}
+
+/// Converts a session argument.
+///
+/// # Safety
+///
+/// `session` has to be null or a session returned by [`probe_rs_session_open`].
+unsafe fn session_arg<'a>(session: *mut ProbeRsSession) -> Result<&'a mut Session, ApiError> {
+    session
+        .as_mut()
+        .map(|session| &mut session.session)
+        .ok_or_else(|| ApiError::invalid_argument("`session` is null".to_string()))
+}
+
+/// Returns a description of the last error on the calling thread, or an empty string.
+///
+/// The string stays valid until the next call into the API on the same thread.
+#[no_mangle]
+pub extern "C" fn probe_rs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Opens a probe and attaches to the chip `chip`, e.g. "nRF52840_xxAA".
+///
+/// `probe` selects the probe as "VID:PID" or "VID:PID:SERIAL". If it is null, the first probe
+/// found is used. On success, `*session` is set to the new session, which has to be closed with
+/// [`probe_rs_session_close`].
+///
+/// # Safety
+///
+/// `probe` has to be null or a nul-terminated string, `chip` a nul-terminated string, and
+/// `session` has to point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_session_open(
+    probe: *const c_char,
+    chip: *const c_char,
+    session: *mut *mut ProbeRsSession,
+) -> ProbeRsStatus {
+    call(|| {
+        if session.is_null() {
+            return Err(ApiError::invalid_argument("`session` is null".to_string()));
+        }
+        let chip = str_arg(chip, "chip")?;
+
+        let lister = Lister::new();
+        let opened = if probe.is_null() {
+            let probes = lister.list_all();
+            let Some(info) = probes.first() else {
+                return Err(ApiError {
+                    status: ProbeRsStatus::NoProbe,
+                    message: "No probe was found".to_string(),
+                });
+            };
+            info.open()?
+        } else {
+            let selector = str_arg(probe, "probe")?
+                .parse::<DebugProbeSelector>()
+                .map_err(|e| ApiError::new(ProbeRsStatus::InvalidArgument, &e))?;
+            lister.open(selector)?
+        };
+
+        let attached = opened.attach(chip, Permissions::default())?;
+        *session = Box::into_raw(Box::new(ProbeRsSession { session: attached }));
+
+        Ok(())
+    })
+}
+
+/// Detaches from the target and closes the probe.
+///
+/// # Safety
+///
+/// `session` has to be null or a session returned by [`probe_rs_session_open`], which was not
+/// closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_session_close(session: *mut ProbeRsSession) {
+    if !session.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(session))));
+    }
+}
+
+/// Reads `length` bytes of memory at `address` into `data`, using the core `core`.
+///
+/// # Safety
+///
+/// `session` has to be a session returned by [`probe_rs_session_open`], and `data` has to point
+/// to at least `length` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_read_memory(
+    session: *mut ProbeRsSession,
+    core: usize,
+    address: u64,
+    data: *mut u8,
+    length: usize,
+) -> ProbeRsStatus {
+    call(|| {
+        let session = session_arg(session)?;
+        if data.is_null() && length > 0 {
+            return Err(ApiError::invalid_argument("`data` is null".to_string()));
+        }
+        if length == 0 {
+            return Ok(());
+        }
+
+        let data = std::slice::from_raw_parts_mut(data, length);
+        session.core(core)?.read(address, data)?;
+
+        Ok(())
+    })
+}
+
+/// Writes `length` bytes from `data` to memory at `address`, using the core `core`.
+///
+/// # Safety
+///
+/// `session` has to be a session returned by [`probe_rs_session_open`], and `data` has to point
+/// to at least `length` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_write_memory(
+    session: *mut ProbeRsSession,
+    core: usize,
+    address: u64,
+    data: *const u8,
+    length: usize,
+) -> ProbeRsStatus {
+    call(|| {
+        let session = session_arg(session)?;
+        if data.is_null() && length > 0 {
+            return Err(ApiError::invalid_argument("`data` is null".to_string()));
+        }
+        if length == 0 {
+            return Ok(());
+        }
+
+        let data = std::slice::from_raw_parts(data, length);
+        session.core(core)?.write(address, data)?;
+
+        Ok(())
+    })
+}
+
+/// Flashes the file at `path` to the target.
+///
+/// # Safety
+///
+/// `session` has to be a session returned by [`probe_rs_session_open`], and `path` a
+/// nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_flash_file(
+    session: *mut ProbeRsSession,
+    path: *const c_char,
+    format: ProbeRsFormat,
+) -> ProbeRsStatus {
+    call(|| {
+        let session = session_arg(session)?;
+        let path = str_arg(path, "path")?;
+        let format = match format {
+            ProbeRsFormat::Elf => Format::Elf,
+            ProbeRsFormat::Hex => Format::Hex,
+        };
+
+        flashing::download_file(session, path, format)
+            .map_err(|e| ApiError::new(ProbeRsStatus::Flash, &e))
+    })
+}
+
+/// Halts the core `core`, waiting up to `timeout_ms` milliseconds for it to halt.
+///
+/// # Safety
+///
+/// `session` has to be a session returned by [`probe_rs_session_open`].
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_core_halt(
+    session: *mut ProbeRsSession,
+    core: usize,
+    timeout_ms: u32,
+) -> ProbeRsStatus {
+    call(|| {
+        let timeout = Duration::from_millis(timeout_ms.into());
+        session_arg(session)?.core(core)?.halt(timeout)?;
+        Ok(())
+    })
+}
+
+/// Resumes the core `core`.
+///
+/// # Safety
+///
+/// `session` has to be a session returned by [`probe_rs_session_open`].
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_core_run(
+    session: *mut ProbeRsSession,
+    core: usize,
+) -> ProbeRsStatus {
+    call(|| Ok(session_arg(session)?.core(core)?.run()?))
+}
+
+/// Resets the core `core`, and lets it run.
+///
+/// # Safety
+///
+/// `session` has to be a session returned by [`probe_rs_session_open`].
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_core_reset(
+    session: *mut ProbeRsSession,
+    core: usize,
+) -> ProbeRsStatus {
+    call(|| Ok(session_arg(session)?.core(core)?.reset()?))
+}
+
+/// Resets the core `core`, and halts it before the first instruction, waiting up to
+/// `timeout_ms` milliseconds for it to halt.
+///
+/// # Safety
+///
+/// `session` has to be a session returned by [`probe_rs_session_open`].
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_core_reset_and_halt(
+    session: *mut ProbeRsSession,
+    core: usize,
+    timeout_ms: u32,
+) -> ProbeRsStatus {
+    call(|| {
+        let timeout = Duration::from_millis(timeout_ms.into());
+        session_arg(session)?.core(core)?.reset_and_halt(timeout)?;
+        Ok(())
+    })
+}
+
+/// Stores the state of the core `core` in `*status`.
+///
+/// # Safety
+///
+/// `session` has to be a session returned by [`probe_rs_session_open`], and `status` has to
+/// point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn probe_rs_core_status(
+    session: *mut ProbeRsSession,
+    core: usize,
+    status: *mut ProbeRsCoreStatus,
+) -> ProbeRsStatus {
+    call(|| {
+        let session = session_arg(session)?;
+        if status.is_null() {
+            return Err(ApiError::invalid_argument("`status` is null".to_string()));
+        }
+
+        *status = match session.core(core)?.status()? {
+            CoreStatus::Running => ProbeRsCoreStatus::Running,
+            CoreStatus::Halted(_) => ProbeRsCoreStatus::Halted,
+            CoreStatus::LockedUp => ProbeRsCoreStatus::LockedUp,
+            CoreStatus::Sleeping => ProbeRsCoreStatus::Sleeping,
+            CoreStatus::Unknown => ProbeRsCoreStatus::Unknown,
+        };
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::ptr;
+
+    use probe_rs::probe::fake_probe::FakeProbe;
+
+    use super::*;
+
+    fn session() -> *mut ProbeRsSession {
+        let session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+
+        Box::into_raw(Box::new(ProbeRsSession { session }))
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(probe_rs_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn memory_and_run_control() {
+        let session = session();
+
+        unsafe {
+            let data = [1u8, 2, 3, 4];
+            let status = probe_rs_write_memory(session, 0, 0x2000_0000, data.as_ptr(), 4);
+            assert_eq!(status, ProbeRsStatus::Ok);
+
+            let mut read = [0u8; 4];
+            let status = probe_rs_read_memory(session, 0, 0x2000_0000, read.as_mut_ptr(), 4);
+            assert_eq!(status, ProbeRsStatus::Ok);
+            assert_eq!(read, data);
+
+            assert_eq!(probe_rs_core_halt(session, 0, 100), ProbeRsStatus::Ok);
+            let mut core_status = ProbeRsCoreStatus::Unknown;
+            let status = probe_rs_core_status(session, 0, &mut core_status);
+            assert_eq!(status, ProbeRsStatus::Ok);
+            assert_eq!(core_status, ProbeRsCoreStatus::Halted);
+
+            probe_rs_session_close(session);
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        unsafe {
+            let status = probe_rs_read_memory(ptr::null_mut(), 0, 0, ptr::null_mut(), 0);
+            assert_eq!(status, ProbeRsStatus::InvalidArgument);
+            assert_eq!(last_error(), "`session` is null");
+
+            let session = session();
+            let status = probe_rs_core_halt(session, 7, 100);
+            assert_eq!(status, ProbeRsStatus::Target);
+            assert!(!last_error().is_empty());
+            probe_rs_session_close(session);
+        }
+    }
+
+    #[test]
+    fn header_declares_all_functions() {
+        let header = include_str!("../include/probe_rs.h");
+        let source = include_str!("lib.rs");
+
+        for line in source.lines() {
+            let Some(rest) = line
+                .strip_prefix("pub unsafe extern \"C\" fn ")
+                .or_else(|| line.strip_prefix("pub extern \"C\" fn "))
+            else {
+                continue;
+            };
+            let name = &rest[..rest.find('(').unwrap()];
+            assert!(
+                header.contains(&format!("{name}(")),
+                "{name} is missing in the header, regenerate it with cbindgen"
+            );
+        }
+    }
+}
@@ -0,0 +1,47 @@
+from os import PathLike
+from types import TracebackType
+from typing import List, Optional, Tuple, Type, Union
+
+class ProbeRsError(Exception): ...
+
+def list_probes() -> List[str]: ...
+
+class Session:
+    @staticmethod
+    def open(chip: str, probe: Optional[str] = None, speed_khz: Optional[int] = None) -> Session: ...
+    def close(self) -> None: ...
+    def __enter__(self) -> Session: ...
+    def __exit__(
+        self,
+        exc_type: Optional[Type[BaseException]],
+        exc_value: Optional[BaseException],
+        traceback: Optional[TracebackType],
+    ) -> None: ...
+    @property
+    def core_count(self) -> int: ...
+    def core(self, index: int = 0) -> Core: ...
+    def download_file(self, path: Union[str, PathLike[str]], format: str = "elf") -> None: ...
+    def rtt(self, core: int = 0) -> Rtt: ...
+
+class Core:
+    @property
+    def index(self) -> int: ...
+    def read(self, address: int, length: int) -> bytes: ...
+    def write(self, address: int, data: bytes) -> None: ...
+    def read_u32(self, address: int) -> int: ...
+    def write_u32(self, address: int, value: int) -> None: ...
+    def halt(self, timeout: float = 1.0) -> int: ...
+    def run(self) -> None: ...
+    def step(self) -> int: ...
+    def reset(self) -> None: ...
+    def reset_and_halt(self, timeout: float = 1.0) -> int: ...
+    def status(self) -> str: ...
+    def read_pc(self) -> int: ...
+
+class Rtt:
+    @property
+    def up_channels(self) -> List[Tuple[int, Optional[str]]]: ...
+    @property
+    def down_channels(self) -> List[Tuple[int, Optional[str]]]: ...
+    def read(self, channel: int = 0) -> bytes: ...
+    def write(self, channel: int, data: bytes) -> int: ...
@@ -0,0 +1,370 @@
+//! Python bindings for probe-rs.
+//!
+//! The bindings are meant for hardware-in-the-loop tests and scripts, which previously had to
+//! run the CLI and parse its output. The module is built with maturin, see `README.md`.
+//!
+//! Operations which talk to the probe release the GIL, so other Python threads keep running
+//! while e.g. a flash download is in progress.
+
+use std::{error::Error as StdError, path::PathBuf, time::Duration};
+
+use probe_rs::{
+    flashing::{self, Format},
+    probe::{list::Lister, DebugProbeSelector},
+    CoreStatus, MemoryInterface, Permissions,
+};
+use pyo3::{create_exception, exceptions::PyException, prelude::*, types::PyBytes};
+
+create_exception!(
+    probe_rs,
+    ProbeRsError,
+    PyException,
+    "An error reported by probe-rs."
+);
+
+/// Converts an error into a `ProbeRsError`, including its causes in the message.
+fn error(error: impl StdError) -> PyErr {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+
+    ProbeRsError::new_err(message)
+}
+
+fn timeout(seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .map_err(|_| ProbeRsError::new_err(format!("Invalid timeout: {seconds}")))
+}
+
+/// Returns the probes connected to this computer, as human readable strings.
+#[pyfunction]
+fn list_probes(py: Python<'_>) -> Vec<String> {
+    py.allow_threads(|| {
+        Lister::new()
+            .list_all()
+            .iter()
+            .map(|probe| probe.to_string())
+            .collect()
+    })
+}
+
+/// A debug session with a target.
+///
+/// The session can be used as a context manager, which closes it at the end of the block.
+#[pyclass(module = "probe_rs")]
+struct Session {
+    inner: Option<probe_rs::Session>,
+}
+
+impl Session {
+    fn session(&mut self) -> PyResult<&mut probe_rs::Session> {
+        self.inner
+            .as_mut()
+            .ok_or_else(|| ProbeRsError::new_err("The session is closed"))
+    }
+}
+
+#[pymethods]
+impl Session {
+    /// Opens a probe and attaches to the chip `chip`.
+    ///
+    /// `probe` selects the probe as "VID:PID" or "VID:PID:SERIAL". If it is not given, the
+    /// first probe found is used.
+    #[staticmethod]
+    #[pyo3(signature = (chip, probe = None, speed_khz = None))]
+    fn open(
+        py: Python<'_>,
+        chip: &str,
+        probe: Option<&str>,
+        speed_khz: Option<u32>,
+    ) -> PyResult<Self> {
+        let selector = probe
+            .map(|probe| probe.parse::<DebugProbeSelector>().map_err(error))
+            .transpose()?;
+
+        py.allow_threads(|| {
+            let lister = Lister::new();
+            let mut probe = match selector {
+                Some(selector) => lister.open(selector).map_err(error)?,
+                None => {
+                    let probes = lister.list_all();
+                    let info = probes
+                        .first()
+                        .ok_or_else(|| ProbeRsError::new_err("No probe was found"))?;
+                    info.open().map_err(error)?
+                }
+            };
+
+            if let Some(speed_khz) = speed_khz {
+                probe.set_speed(speed_khz).map_err(error)?;
+            }
+
+            let session = probe.attach(chip, Permissions::default()).map_err(error)?;
+
+            Ok(Self {
+                inner: Some(session),
+            })
+        })
+    }
+
+    /// Detaches from the target and closes the probe.
+    fn close(&mut self) {
+        self.inner = None;
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) {
+        self.close();
+    }
+
+    /// The number of cores of the target.
+    #[getter]
+    fn core_count(&mut self) -> PyResult<usize> {
+        Ok(self.session()?.list_cores().len())
+    }
+
+    /// Returns the core with index `index`.
+    #[pyo3(signature = (index = 0))]
+    fn core(slf: Bound<'_, Self>, index: usize) -> Core {
+        Core {
+            session: slf.unbind(),
+            index,
+        }
+    }
+
+    /// Flashes the file at `path`. `format` is either "elf" or "hex".
+    #[pyo3(signature = (path, format = "elf"))]
+    fn download_file(&mut self, py: Python<'_>, path: PathBuf, format: &str) -> PyResult<()> {
+        let format = match format {
+            "elf" => Format::Elf,
+            "hex" => Format::Hex,
+            other => return Err(ProbeRsError::new_err(format!("Unknown format: {other}"))),
+        };
+        let session = self.session()?;
+
+        py.allow_threads(|| flashing::download_file(session, path, format))
+            .map_err(error)
+    }
+
+    /// Attaches to the RTT control block of the target, using the core with index `core`.
+    #[pyo3(signature = (core = 0))]
+    fn rtt(slf: Bound<'_, Self>, core: usize) -> PyResult<Rtt> {
+        let py = slf.py();
+        let rtt = {
+            let mut this = slf.borrow_mut();
+            let session = this.session()?;
+            py.allow_threads(|| {
+                let mut core = session.core(core).map_err(error)?;
+                probe_rs::rtt::Rtt::attach(&mut core).map_err(error)
+            })?
+        };
+
+        Ok(Rtt {
+            session: slf.unbind(),
+            core,
+            rtt,
+        })
+    }
+}
+
+/// A core of the target.
+#[pyclass(module = "probe_rs")]
+struct Core {
+    session: Py<Session>,
+    index: usize,
+}
+
+impl Core {
+    /// Runs `f` with the core, without holding the GIL.
+    fn with<T: Send>(
+        &self,
+        py: Python<'_>,
+        f: impl FnOnce(&mut probe_rs::Core<'_>) -> Result<T, probe_rs::Error> + Send,
+    ) -> PyResult<T> {
+        let mut session = self.session.borrow_mut(py);
+        let session = session.session()?;
+        let index = self.index;
+
+        py.allow_threads(|| f(&mut session.core(index)?))
+            .map_err(error)
+    }
+}
+
+#[pymethods]
+impl Core {
+    /// The index of the core.
+    #[getter]
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Reads `length` bytes of memory at `address`.
+    fn read(&self, py: Python<'_>, address: u64, length: usize) -> PyResult<Py<PyBytes>> {
+        let data = self.with(py, |core| {
+            let mut data = vec![0; length];
+            core.read(address, &mut data)?;
+            Ok(data)
+        })?;
+
+        Ok(PyBytes::new_bound(py, &data).unbind())
+    }
+
+    /// Writes `data` to memory at `address`.
+    fn write(&self, py: Python<'_>, address: u64, data: &[u8]) -> PyResult<()> {
+        self.with(py, |core| core.write(address, data))
+    }
+
+    /// Reads a 32 bit word at `address`.
+    fn read_u32(&self, py: Python<'_>, address: u64) -> PyResult<u32> {
+        self.with(py, |core| core.read_word_32(address))
+    }
+
+    /// Writes a 32 bit word to `address`.
+    fn write_u32(&self, py: Python<'_>, address: u64, value: u32) -> PyResult<()> {
+        self.with(py, |core| core.write_word_32(address, value))
+    }
+
+    /// Halts the core, waiting up to `timeout` seconds. Returns the program counter.
+    #[pyo3(signature = (timeout = 1.0))]
+    fn halt(&self, py: Python<'_>, timeout: f64) -> PyResult<u64> {
+        let timeout = self::timeout(timeout)?;
+        self.with(py, |core| Ok(core.halt(timeout)?.pc))
+    }
+
+    /// Resumes the core.
+    fn run(&self, py: Python<'_>) -> PyResult<()> {
+        self.with(py, |core| core.run())
+    }
+
+    /// Executes a single instruction. Returns the program counter.
+    fn step(&self, py: Python<'_>) -> PyResult<u64> {
+        self.with(py, |core| Ok(core.step()?.pc))
+    }
+
+    /// Resets the core, and lets it run.
+    fn reset(&self, py: Python<'_>) -> PyResult<()> {
+        self.with(py, |core| core.reset())
+    }
+
+    /// Resets the core, and halts it before the first instruction. Returns the program counter.
+    #[pyo3(signature = (timeout = 1.0))]
+    fn reset_and_halt(&self, py: Python<'_>, timeout: f64) -> PyResult<u64> {
+        let timeout = self::timeout(timeout)?;
+        self.with(py, |core| Ok(core.reset_and_halt(timeout)?.pc))
+    }
+
+    /// Returns the state of the core: "running", "halted", "locked_up", "sleeping" or
+    /// "unknown".
+    fn status(&self, py: Python<'_>) -> PyResult<&'static str> {
+        self.with(py, |core| {
+            Ok(match core.status()? {
+                CoreStatus::Running => "running",
+                CoreStatus::Halted(_) => "halted",
+                CoreStatus::LockedUp => "locked_up",
+                CoreStatus::Sleeping => "sleeping",
+                CoreStatus::Unknown => "unknown",
+            })
+        })
+    }
+
+    /// Reads the program counter. The core has to be halted.
+    fn read_pc(&self, py: Python<'_>) -> PyResult<u64> {
+        self.with(py, |core| {
+            let pc = core.program_counter();
+            core.read_core_reg(pc)
+        })
+    }
+}
+
+/// The RTT channels of a target, see `Session.rtt`.
+#[pyclass(module = "probe_rs")]
+struct Rtt {
+    session: Py<Session>,
+    core: usize,
+    rtt: probe_rs::rtt::Rtt,
+}
+
+#[pymethods]
+impl Rtt {
+    /// The up channels, which send data to the host, as `(number, name)` tuples.
+    #[getter]
+    fn up_channels(&mut self) -> Vec<(usize, Option<String>)> {
+        self.rtt
+            .up_channels()
+            .iter()
+            .map(|channel| (channel.number(), channel.name().map(str::to_string)))
+            .collect()
+    }
+
+    /// The down channels, which receive data from the host, as `(number, name)` tuples.
+    #[getter]
+    fn down_channels(&mut self) -> Vec<(usize, Option<String>)> {
+        self.rtt
+            .down_channels()
+            .iter()
+            .map(|channel| (channel.number(), channel.name().map(str::to_string)))
+            .collect()
+    }
+
+    /// Reads the data which is available in the up channel `channel`.
+    #[pyo3(signature = (channel = 0))]
+    fn read(&self, py: Python<'_>, channel: usize) -> PyResult<Py<PyBytes>> {
+        let channel = self
+            .rtt
+            .up_channel(channel)
+            .ok_or_else(|| ProbeRsError::new_err(format!("No up channel {channel}")))?;
+        let mut session = self.session.borrow_mut(py);
+        let session = session.session()?;
+        let core_index = self.core;
+
+        let data = py.allow_threads(|| {
+            let mut core = session.core(core_index).map_err(error)?;
+            let mut data = vec![0; channel.buffer_size()];
+            let count = channel.read(&mut core, &mut data).map_err(error)?;
+            data.truncate(count);
+            Ok::<_, PyErr>(data)
+        })?;
+
+        Ok(PyBytes::new_bound(py, &data).unbind())
+    }
+
+    /// Writes `data` to the down channel `channel`. Returns the number of bytes written, which
+    /// is smaller than the length of `data` if the buffer is full.
+    fn write(&self, py: Python<'_>, channel: usize, data: &[u8]) -> PyResult<usize> {
+        let channel = self
+            .rtt
+            .down_channel(channel)
+            .ok_or_else(|| ProbeRsError::new_err(format!("No down channel {channel}")))?;
+        let mut session = self.session.borrow_mut(py);
+        let session = session.session()?;
+        let core_index = self.core;
+
+        py.allow_threads(|| {
+            let mut core = session.core(core_index).map_err(error)?;
+            channel.write(&mut core, data).map_err(error)
+        })
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "probe_rs")]
+fn probe_rs_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("ProbeRsError", m.py().get_type_bound::<ProbeRsError>())?;
+    m.add_function(wrap_pyfunction!(list_probes, m)?)?;
+    m.add_class::<Session>()?;
+    m.add_class::<Core>()?;
+    m.add_class::<Rtt>()?;
+
+    Ok(())
+}
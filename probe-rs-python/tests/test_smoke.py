@@ -0,0 +1,18 @@
+"""Tests which don't need a probe, run in CI after building the module with maturin."""
+
+import probe_rs
+import pytest
+
+
+def test_list_probes_returns_a_list():
+    probes = probe_rs.list_probes()
+
+    assert isinstance(probes, list)
+    assert all(isinstance(probe, str) for probe in probes)
+
+
+def test_errors_are_raised_as_probe_rs_error():
+    assert issubclass(probe_rs.ProbeRsError, Exception)
+
+    with pytest.raises(probe_rs.ProbeRsError):
+        probe_rs.Session.open("nRF52840_xxAA", probe="0000:0000")
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
 use super::memory::MemoryRegion;
+use super::memory_init::MemoryInitSequence;
+use super::provisioning::ProvisioningRegion;
 use crate::{
     serialize::{hex_option, hex_u_int},
     CoreType,
@@ -81,6 +83,14 @@ pub struct Chip {
     // TODO: rename to default_platform
     #[serde(default)]
     pub default_binary_format: Option<String>,
+    /// Regions holding per-device configuration, which are written once during production,
+    /// e.g. with `probe-rs provision`. Regions shared by the whole family are described in
+    /// [`ChipFamily::provisioning_regions`](crate::ChipFamily::provisioning_regions) instead.
+    #[serde(default)]
+    pub provisioning_regions: Vec<ProvisioningRegion>,
+    /// Sequences which initialize external memory, e.g. SDRAM, before it is accessed.
+    #[serde(default)]
+    pub memory_init_sequences: Vec<MemoryInitSequence>,
 }
 
 impl Chip {
@@ -103,6 +113,8 @@ impl Chip {
             rtt_scan_ranges: None,
             jtag: None,
             default_binary_format: None,
+            provisioning_regions: vec![],
+            memory_init_sequences: vec![],
         }
     }
 }
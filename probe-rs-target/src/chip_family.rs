@@ -5,9 +5,11 @@ use crate::{MemoryRange, MemoryRegion};
 
 use super::chip::Chip;
 use super::flash_algorithm::RawFlashAlgorithm;
+use super::provisioning::ProvisioningRegion;
 use jep106::JEP106Code;
 
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
 
 /// Source of a target description.
 ///
@@ -199,6 +201,9 @@ pub struct ChipFamily {
     /// - `None` if this was not generated from a pack file, or has been modified since it was generated.
     #[serde(default)]
     pub pack_file_release: Option<String>,
+    /// Provisioning regions which all variants of the family share, in addition to their own.
+    #[serde(default)]
+    pub provisioning_regions: Vec<ProvisioningRegion>,
     /// This vector holds all the variants of the family.
     pub variants: Vec<Chip>,
     /// This vector holds all available algorithms.
@@ -224,6 +229,8 @@ impl ChipFamily {
         self.reject_incorrect_core_access_options()?;
         self.validate_memory_regions()?;
         self.validate_rtt_scan_regions()?;
+        self.validate_provisioning_regions()?;
+        self.validate_memory_init_sequences()?;
 
         Ok(())
     }
@@ -370,6 +377,48 @@ impl ChipFamily {
         Ok(())
     }
 
+    /// Provisioning regions are only written in units of their write size, so they have to be
+    /// aligned to it, and their names are used to select them.
+    fn validate_provisioning_regions(&self) -> Result<(), String> {
+        for variant in &self.variants {
+            let regions = self.provisioning_regions(variant);
+            for (i, region) in regions.iter().enumerate() {
+                if region.range.is_empty() || !region.is_aligned(&region.range) {
+                    return Err(format!(
+                        "Variant {}, provisioning region {} is empty or not aligned to its write size",
+                        variant.name, region.name
+                    ));
+                }
+
+                if regions[..i].iter().any(|other| other.name == region.name) {
+                    return Err(format!(
+                        "Variant {}, provisioning region {} appears multiple times",
+                        variant.name, region.name
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Memory init sequences are selected by the ranges they cover, so a sequence without
+    /// ranges would never run.
+    fn validate_memory_init_sequences(&self) -> Result<(), String> {
+        for variant in &self.variants {
+            for sequence in &variant.memory_init_sequences {
+                if sequence.ranges.is_empty() || sequence.ranges.iter().any(Range::is_empty) {
+                    return Err(format!(
+                        "Variant {}, memory init sequence {} has no or empty ranges",
+                        variant.name, sequence.name
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn validate_rtt_scan_regions(&self) -> Result<(), String> {
         for variant in &self.variants {
             let Some(rtt_scan_ranges) = &variant.rtt_scan_ranges else {
@@ -421,4 +470,14 @@ impl ChipFamily {
         let name = name.as_ref();
         self.flash_algorithms.iter().find(|elem| elem.name == name)
     }
+
+    /// Get the provisioning regions of a [Chip] in this family, which are the ones shared by the
+    /// family followed by the chip's own.
+    pub fn provisioning_regions(&self, chip: &Chip) -> Vec<ProvisioningRegion> {
+        self.provisioning_regions
+            .iter()
+            .chain(&chip.provisioning_regions)
+            .cloned()
+            .collect()
+    }
 }
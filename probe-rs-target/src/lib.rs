@@ -16,6 +16,8 @@ mod chip_family;
 mod flash_algorithm;
 mod flash_properties;
 mod memory;
+mod memory_init;
+mod provisioning;
 pub(crate) mod serialize;
 
 pub use chip::{
@@ -31,3 +33,5 @@ pub use memory::{
     GenericRegion, MemoryAccess, MemoryRange, MemoryRegion, NvmRegion, PageInfo, RamRegion,
     RegionMergeIterator, SectorDescription, SectorInfo,
 };
+pub use memory_init::{MemoryInitSequence, MemoryInitStep};
+pub use provisioning::ProvisioningRegion;
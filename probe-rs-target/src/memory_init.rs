@@ -0,0 +1,117 @@
+use crate::flash_algorithm::{deserialize, serialize};
+use crate::serialize::{hex_option, hex_u_int};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A sequence which initializes external memory before it can be accessed.
+///
+/// Examples are the SDRAM behind the FMC of STM32F4 and STM32H7 chips, or HyperRAM behind an
+/// OctoSPI controller. The memory controller and its pins depend on the board, so these sequences
+/// are usually part of a custom target description for the board.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryInitSequence {
+    /// The name of the sequence, e.g. `SDRAM`.
+    pub name: String,
+    /// The memory ranges which can only be accessed after the sequence ran.
+    pub ranges: Vec<Range<u64>>,
+    /// The steps of the sequence, in order.
+    pub steps: Vec<MemoryInitStep>,
+}
+
+impl MemoryInitSequence {
+    /// Returns whether the sequence has to run before `range` is accessed.
+    pub fn covers(&self, range: &Range<u64>) -> bool {
+        self.ranges
+            .iter()
+            .any(|own| own.start < range.end && range.start < own.end)
+    }
+}
+
+/// A single step of a [`MemoryInitSequence`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum MemoryInitStep {
+    /// Writes a 32-bit value to a register.
+    Write {
+        /// The address of the register.
+        #[serde(serialize_with = "hex_u_int")]
+        address: u64,
+        /// The value to write.
+        #[serde(serialize_with = "hex_u_int")]
+        value: u32,
+    },
+    /// Changes the bits in `mask` of a 32-bit register to the ones of `value`, keeping the
+    /// other bits.
+    Modify {
+        /// The address of the register.
+        #[serde(serialize_with = "hex_u_int")]
+        address: u64,
+        /// The bits to change.
+        #[serde(serialize_with = "hex_u_int")]
+        mask: u32,
+        /// The new value of the bits.
+        #[serde(serialize_with = "hex_u_int")]
+        value: u32,
+    },
+    /// Waits until the bits in `mask` of a 32-bit register have the ones of `value`, e.g. until
+    /// a PLL is locked.
+    Poll {
+        /// The address of the register.
+        #[serde(serialize_with = "hex_u_int")]
+        address: u64,
+        /// The bits to check.
+        #[serde(serialize_with = "hex_u_int")]
+        mask: u32,
+        /// The expected value of the bits.
+        #[serde(serialize_with = "hex_u_int")]
+        value: u32,
+        /// How long to wait, in milliseconds.
+        #[serde(default = "default_timeout_ms")]
+        timeout_ms: u64,
+    },
+    /// Waits for a time, e.g. for the power-up delay of SDRAM.
+    Delay {
+        /// The time to wait, in microseconds.
+        us: u64,
+    },
+    /// Loads code into RAM and runs it, for initializations which are too complex for register
+    /// writes. The core registers are overwritten.
+    Run {
+        /// The address the code is loaded to, and started at.
+        #[serde(serialize_with = "hex_u_int")]
+        load_address: u64,
+        /// The code, which has to halt the core when it's done, e.g. with a `bkpt` instruction.
+        #[serde(deserialize_with = "deserialize", serialize_with = "serialize")]
+        instructions: Vec<u8>,
+        /// The initial stack pointer of the code. The stack pointer is kept if not set.
+        #[serde(default, serialize_with = "hex_option")]
+        stack_pointer: Option<u64>,
+        /// How long to wait for the code to halt the core, in milliseconds.
+        #[serde(default = "default_timeout_ms")]
+        timeout_ms: u64,
+    },
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sequences_cover_overlapping_ranges() {
+        let sequence = MemoryInitSequence {
+            name: "SDRAM".to_string(),
+            ranges: vec![0xC000_0000..0xC200_0000, 0xD000_0000..0xD080_0000],
+            steps: vec![],
+        };
+
+        assert!(sequence.covers(&(0xBFFF_FFFC..0xC000_0004)));
+        assert!(sequence.covers(&(0xC1FF_FFFC..0xC200_0000)));
+        assert!(!sequence.covers(&(0xC200_0000..0xC200_0004)));
+        assert!(sequence.covers(&(0xD07F_FFFC..0xD080_0000)));
+    }
+}
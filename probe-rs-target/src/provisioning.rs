@@ -0,0 +1,96 @@
+use crate::serialize::{hex_range, hex_u_int};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A region holding per-device configuration, which is written once during production.
+///
+/// Examples are the UICR of nRF chips, the OTP area of STM32 chips, or the eFuses of ESP32 chips.
+/// Writes to these regions are checked against their current contents, because programmed bits
+/// can't be reset, or only by erasing the whole chip.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProvisioningRegion {
+    /// The name of the region, e.g. `UICR`.
+    pub name: String,
+    /// The address range of the region.
+    #[serde(serialize_with = "hex_range")]
+    pub range: Range<u64>,
+    /// Whether the region can never be erased again.
+    #[serde(default)]
+    pub one_time: bool,
+    /// The value of bytes which were not programmed yet.
+    #[serde(default = "default_erased_byte_value", serialize_with = "hex_u_int")]
+    pub erased_byte_value: u8,
+    /// The size of the units in which the region is programmed, in bytes.
+    ///
+    /// Writes have to start and end at a multiple of this size.
+    #[serde(default = "default_write_size")]
+    pub write_size: u32,
+}
+
+fn default_erased_byte_value() -> u8 {
+    0xFF
+}
+
+fn default_write_size() -> u32 {
+    4
+}
+
+impl ProvisioningRegion {
+    /// Returns whether `range` is completely inside of this region.
+    pub fn contains_range(&self, range: &Range<u64>) -> bool {
+        self.range.start <= range.start && range.end <= self.range.end
+    }
+
+    /// Returns whether `range` is aligned to the write size of the region.
+    pub fn is_aligned(&self, range: &Range<u64>) -> bool {
+        let write_size = u64::from(self.write_size.max(1));
+        range.start % write_size == 0 && range.end % write_size == 0
+    }
+
+    /// Returns whether the bytes `current` can be programmed to `data` without erasing, i.e.
+    /// only bits which are still erased are changed.
+    pub fn can_program(&self, current: &[u8], data: &[u8]) -> bool {
+        current.iter().zip(data).all(|(&current, &data)| {
+            // The bits which were already programmed, and have to stay programmed.
+            let programmed = current ^ self.erased_byte_value;
+            (data ^ self.erased_byte_value) & programmed == programmed
+        })
+    }
+
+    /// Returns whether none of the bytes `current` was programmed yet.
+    pub fn is_erased(&self, current: &[u8]) -> bool {
+        current.iter().all(|&byte| byte == self.erased_byte_value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_erased_bits_can_be_programmed() {
+        let region = ProvisioningRegion {
+            name: "OTP".to_string(),
+            range: 0x1FFF_7800..0x1FFF_7A00,
+            one_time: true,
+            erased_byte_value: 0xFF,
+            write_size: 4,
+        };
+
+        assert!(region.can_program(&[0xFF, 0xFF], &[0x12, 0x34]));
+        assert!(region.can_program(&[0xF0, 0xFF], &[0x30, 0xFF]));
+        assert!(!region.can_program(&[0xF0, 0xFF], &[0x0F, 0xFF]));
+
+        let inverted = ProvisioningRegion {
+            erased_byte_value: 0x00,
+            ..region.clone()
+        };
+        assert!(inverted.can_program(&[0x01], &[0x03]));
+        assert!(!inverted.can_program(&[0x01], &[0x02]));
+
+        assert!(region.is_aligned(&(0x1FFF_7800..0x1FFF_7808)));
+        assert!(!region.is_aligned(&(0x1FFF_7802..0x1FFF_7808)));
+        assert!(!region.contains_range(&(0x1FFF_79FC..0x1FFF_7A04)));
+    }
+}
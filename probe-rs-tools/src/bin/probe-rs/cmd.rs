@@ -1,21 +1,43 @@
 pub mod attach;
 pub mod benchmark;
+pub mod bootloader;
+pub mod bridge;
 pub mod cargo_embed;
 pub mod cargo_flash;
 pub mod chip;
 pub mod complete;
+pub mod coverage;
 pub mod dap_server;
 pub mod debug;
+pub mod disasm;
+pub mod doctor;
 pub mod download;
 pub mod erase;
+pub mod flash_farm;
 pub mod gdb;
 pub mod info;
 pub mod itm;
 pub mod list;
+pub mod memtest;
 pub mod mi;
+pub mod pins;
+pub mod poke;
+pub mod power;
 pub mod profile;
+pub mod provision;
 pub mod read;
+pub mod regions;
 pub mod reset;
+pub mod rp2040;
 pub mod run;
+pub mod script;
+pub mod serve;
+pub mod shell;
+pub mod stack_usage;
+pub mod stm8;
+pub mod symbolize;
+pub mod time;
 pub mod trace;
+pub mod tui;
+pub mod watch;
 pub mod write;
@@ -1,18 +1,38 @@
 use std::{
+    cell::RefCell,
     num::ParseIntError,
+    path::PathBuf,
+    rc::Rc,
     time::{Duration, Instant},
 };
 
 use anyhow::Context;
-use probe_rs::{probe::list::Lister, MemoryInterface};
+use probe_rs::{
+    flashing::{DownloadOptions, FlashProgress, ProgressEvent},
+    probe::list::Lister,
+    rtt::Rtt,
+    MemoryInterface, Session,
+};
+use serde::{Deserialize, Serialize};
 
 use crate::util::common_options::LoadedProbeOptions;
 use crate::util::common_options::ProbeOptions;
+use crate::util::output::{print_json, OutputFormat};
 
 const PROBE_SPEEDS: [u32; 10] = [320, 640, 960, 3200, 6400, 9600, 32000, 64000, 96000, 320000];
 const TEST_SIZES: [usize; 5] = [1, 8, 32, 512, 8192];
 
+/// Measure the throughput of the probe
+///
+/// e.g. probe-rs benchmark --chip nRF52840_xxAA --address 0x20000000 --max-speed 32000
+///      Measures reading and writing RAM with 8, 16 and 32-bit accesses at all speeds up to
+///      32 MHz.
+///
+/// The flash and RTT throughput are measured with `--flash-address` and `--rtt`. With
+/// `--output-format json` the results are printed as JSON, which can be passed to `--baseline`
+/// of a later run to compare probe firmware versions or probe-rs releases.
 #[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
 pub struct Cmd {
     #[clap(flatten)]
     common: ProbeOptions,
@@ -38,12 +58,12 @@ pub struct Cmd {
     #[clap(long = "max-speed", value_parser= parse_int, default_value="0")]
     max_speed: u32,
 
-    /// Word size for read/write accesses.
+    /// Word sizes for read/write accesses.
     ///
-    /// Set the read/write word size to 8/32/64bits.
-    /// Note: not all chips/probes support all sizes. 32bit is a safe default
-    #[clap(long = "word-size", value_parser= parse_int, default_value="32")]
-    word_size: u32,
+    /// Set the read/write word sizes to 8/16/32/64bits, separated by commas.
+    /// Note: not all chips/probes support all sizes.
+    #[clap(long = "word-size", value_parser= parse_int, value_delimiter = ',', default_value="8,16,32")]
+    word_sizes: Vec<u32>,
 
     /// Number of times to run each test
     ///
@@ -52,6 +72,30 @@ pub struct Cmd {
     /// both reduce the amount of jitter, and also quantify it (via standard deviation calcs)
     #[clap(long = "iterations", value_parser= parse_usize, default_value="5")]
     iterations: usize,
+
+    /// Measure erasing and programming the flash at this address.
+    ///
+    /// The flash contents at the address are overwritten.
+    #[clap(long = "flash-address", value_parser= parse_hex)]
+    flash_address: Option<u64>,
+
+    /// Number of bytes programmed for the flash test.
+    #[clap(long = "flash-size", value_parser= parse_usize, default_value="4096")]
+    flash_size: usize,
+
+    /// Measure the RTT throughput, by resetting the target and reading the first up channel.
+    ///
+    /// The firmware has to write to the channel continuously.
+    #[clap(long)]
+    rtt: bool,
+
+    /// How long the RTT throughput is measured, in seconds.
+    #[clap(long = "rtt-duration", default_value_t = 5)]
+    rtt_duration: u64,
+
+    /// The JSON output of an earlier run, to print the change of the throughput.
+    #[clap(long)]
+    baseline: Option<PathBuf>,
 }
 
 fn parse_usize(src: &str) -> Result<usize, ParseIntError> {
@@ -66,10 +110,83 @@ fn parse_hex(src: &str) -> Result<u64, ParseIntError> {
     u64::from_str_radix(src.trim_start_matches("0x"), 16)
 }
 
+/// The results of a benchmark run.
+#[derive(Debug, Serialize, Deserialize)]
+struct Report {
+    probe: String,
+    protocol: String,
+    target: String,
+    probe_rs_version: String,
+    measurements: Vec<Measurement>,
+}
+
+/// The JSON output of an earlier run.
+#[derive(Deserialize)]
+struct Baseline {
+    result: Report,
+}
+
+/// What was measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Test {
+    Read,
+    Write,
+    FlashErase,
+    FlashProgram,
+    Rtt,
+}
+
+/// The throughput of one test.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Measurement {
+    test: Test,
+    speed_khz: u32,
+    /// The size of the memory accesses in bits, for reads and writes.
+    word_size: Option<u32>,
+    /// The number of bytes transferred per iteration.
+    bytes: usize,
+    /// The number of iterations the throughput is averaged over.
+    iterations: usize,
+    /// The mean throughput in bytes per second.
+    throughput: f64,
+    /// The standard deviation of the throughput in bytes per second.
+    std_deviation: f64,
+}
+
+impl Measurement {
+    /// Returns if both measurements are of the same test, so they can be compared.
+    fn same_test(&self, other: &Measurement) -> bool {
+        // The bytes of the flash and RTT tests depend on the target and firmware.
+        self.test == other.test
+            && self.speed_khz == other.speed_khz
+            && self.word_size == other.word_size
+            && (self.word_size.is_none() || self.bytes == other.bytes)
+    }
+
+    fn to_text(&self, baseline: Option<&Measurement>) -> String {
+        let access = match self.word_size {
+            Some(word_size) => format!(" {word_size}bit"),
+            None => String::new(),
+        };
+        let mut text = format!(
+            "{:?}{access}, {} bytes @ {} kHz: {:.2} bytes/s Std Dev {:.2}",
+            self.test, self.bytes, self.speed_khz, self.throughput, self.std_deviation
+        );
+        if let Some(baseline) = baseline.filter(|baseline| baseline.throughput > 0.0) {
+            let change = (self.throughput / baseline.throughput - 1.0) * 100.0;
+            text.push_str(&format!(" ({change:+.1}% vs. baseline)"));
+        }
+
+        text
+    }
+}
+
 #[derive(Debug)]
 /// Provide different data arrays for each read/write stride size
 enum DataType {
     U8(Vec<u8>, Vec<u8>),
+    U16(Vec<u16>, Vec<u16>),
     U32(Vec<u32>, Vec<u32>),
     U64(Vec<u64>, Vec<u64>),
 }
@@ -83,9 +200,9 @@ struct TestData {
 }
 
 impl Cmd {
-    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+    pub fn run(self, lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
         let speed = self.common.speed;
-        let common_options = self.common.load()?;
+        let common_options = self.common.clone().load()?;
         let mut max_speed = self.max_speed;
         let mut speeds = vec![];
         // if no max-speed specified, assume the user just wants to use a single speed (as per other cli cmds)
@@ -95,37 +212,59 @@ impl Cmd {
         } else {
             speeds.extend_from_slice(&PROBE_SPEEDS);
         };
+        if let Some(word_size) = self
+            .word_sizes
+            .iter()
+            .find(|word_size| ![8, 16, 32, 64].contains(*word_size))
+        {
+            anyhow::bail!("Invalid word size {word_size}, use 8, 16, 32 or 64.");
+        }
+        let baseline = match &self.baseline {
+            Some(path) => {
+                let baseline: Baseline = serde_json::from_slice(&std::fs::read(path)?)
+                    .with_context(|| format!("Failed to read the baseline {}", path.display()))?;
+                baseline.result.measurements
+            }
+            None => Vec::new(),
+        };
+
         // if we can't print basic info, we're probably not going to succeed with testing so bubble up the error
-        Cmd::print_info(&common_options, lister)?;
+        let mut report = Cmd::probe_info(&common_options, lister)?;
+        if !output_format.is_json() {
+            println!(
+                "Probe: Probe type {}, debug interface {}, target chip {}\n",
+                report.probe, report.protocol, report.target
+            );
+        }
 
         for speed in speeds
             .iter()
             .filter(|speed| (self.min_speed..=max_speed).contains(*speed))
         {
-            for size in TEST_SIZES {
-                let res = Cmd::benchmark(
-                    &common_options,
-                    lister,
-                    *speed,
-                    size,
-                    self.address,
-                    self.word_size,
-                    self.iterations,
-                );
-                if let Err(e) = res {
-                    println!(
-                        "Test failed for speed {} size {} word_size {}bit - {}",
-                        speed, size, self.word_size, e
-                    )
-                }
+            let result =
+                self.benchmark_speed(&common_options, lister, *speed, &mut |measurement| {
+                    if !output_format.is_json() {
+                        let baseline = baseline
+                            .iter()
+                            .find(|baseline| baseline.same_test(&measurement));
+                        println!("{}", measurement.to_text(baseline));
+                    }
+                    report.measurements.push(measurement);
+                });
+            if let Err(e) = result {
+                eprintln!("Tests failed for speed {speed} - {e:#}");
             }
         }
 
+        if output_format.is_json() {
+            print_json("benchmark", &report)?;
+        }
+
         Ok(())
     }
 
-    /// Print probe and target info
-    fn print_info(common_options: &LoadedProbeOptions, lister: &Lister) -> anyhow::Result<()> {
+    /// Returns the probe and target info, without measurements.
+    fn probe_info(common_options: &LoadedProbeOptions, lister: &Lister) -> anyhow::Result<Report> {
         let probe = common_options.attach_probe(lister)?;
         let protocol_name = probe
             .protocol()
@@ -136,81 +275,236 @@ impl Cmd {
         let probe_name = probe.get_name();
         let session = common_options.attach_session(probe, target)?;
         let target_name = session.target().name.clone();
-        println!(
-            "Probe: Probe type {}, debug interface {}, target chip {}\n",
-            probe_name, protocol_name, target_name
-        );
+
+        Ok(Report {
+            probe: probe_name,
+            protocol: protocol_name,
+            target: target_name,
+            probe_rs_version: env!("CARGO_PKG_VERSION").to_string(),
+            measurements: Vec::new(),
+        })
+    }
+
+    /// Run all tests at one speed, and report each measurement.
+    fn benchmark_speed(
+        &self,
+        common_options: &LoadedProbeOptions,
+        lister: &Lister,
+        speed: u32,
+        report: &mut dyn FnMut(Measurement),
+    ) -> anyhow::Result<()> {
+        let mut probe = common_options.attach_probe(lister)?;
+        let target = common_options.get_target_selector()?;
+        probe
+            .set_speed(speed)
+            .with_context(|| format!("failed to set speed {speed}"))?;
+        let mut session = common_options.attach_session(probe, target)?;
+
+        for &word_size in &self.word_sizes {
+            for size in TEST_SIZES {
+                let result = Cmd::benchmark(
+                    &mut session,
+                    speed,
+                    size,
+                    self.address,
+                    word_size,
+                    self.iterations,
+                );
+                match result {
+                    Ok(measurements) => measurements.into_iter().for_each(&mut *report),
+                    Err(e) => eprintln!(
+                        "Test failed for speed {} size {} word_size {}bit - {}",
+                        speed, size, word_size, e
+                    ),
+                }
+            }
+        }
+
+        if let Some(address) = self.flash_address {
+            match flash_throughput(&mut session, speed, address, self.flash_size) {
+                Ok(measurements) => measurements.into_iter().for_each(&mut *report),
+                Err(e) => eprintln!("Flash test failed for speed {speed} - {e:#}"),
+            }
+        }
+
+        if self.rtt {
+            let duration = Duration::from_secs(self.rtt_duration);
+            match rtt_throughput(&mut session, speed, duration) {
+                Ok(measurement) => report(measurement),
+                Err(e) => eprintln!("RTT test failed for speed {speed} - {e:#}"),
+            }
+        }
+
         Ok(())
     }
 
     /// Run a specific benchmark
     fn benchmark(
-        common_options: &LoadedProbeOptions,
-        lister: &Lister,
+        session: &mut Session,
         speed: u32,
         size: usize,
         address: u64,
         word_size: u32,
         iterations: usize,
-    ) -> Result<(), anyhow::Error> {
-        let mut probe = common_options.attach_probe(lister)?;
-        let target = common_options.get_target_selector()?;
-        if probe.set_speed(speed).is_ok() {
-            let mut session = common_options.attach_session(probe, target)?;
-            let mut test = TestData::new(address, word_size, size);
-            println!(
-                "Test: Speed {}, Word size {}bit, Data length {} bytes, Number of iterations {}",
-                speed,
-                word_size,
-                test.data_type.size() * size,
+    ) -> Result<Vec<Measurement>, anyhow::Error> {
+        let mut test = TestData::new(address, word_size, size);
+        let mut core = session.core(0).context("Failed to attach to core")?;
+        core.halt(Duration::from_millis(100))
+            .context("Halting failed")?;
+
+        let mut read_results = Vec::<f64>::with_capacity(iterations);
+        let mut write_results = Vec::<f64>::with_capacity(iterations);
+        'inner: for _ in 0..iterations {
+            let write_throughput = test.block_write(&mut core)?;
+            let read_throughput = test.block_read(&mut core)?;
+            let verify_success = test.block_verify();
+            if verify_success {
+                read_results.push(read_throughput);
+                write_results.push(write_throughput);
+            } else {
+                eprintln!("Verification failed.");
+                break 'inner;
+            }
+        }
+        if read_results.len() != iterations || write_results.len() != iterations {
+            eprintln!(
+                "Warning: {} reads and {} writes successful (out of {} iterations)",
+                read_results.len(),
+                write_results.len(),
                 iterations
-            );
-            let mut core = session.core(0).context("Failed to attach to core")?;
-            core.halt(Duration::from_millis(100))
-                .context("Halting failed")?;
-
-            let mut read_results = Vec::<f64>::with_capacity(iterations);
-            let mut write_results = Vec::<f64>::with_capacity(iterations);
-            'inner: for _ in 0..iterations {
-                let write_throughput = test.block_write(&mut core)?;
-                let read_throughput = test.block_read(&mut core)?;
-                let verify_success = test.block_verify();
-                if verify_success {
-                    read_results.push(read_throughput);
-                    write_results.push(write_throughput);
-                } else {
-                    eprintln!("Verification failed.");
-                    break 'inner;
+            )
+        }
+
+        let measurement = |test, results: &[f64]| {
+            Some(Measurement {
+                test,
+                speed_khz: speed,
+                word_size: Some(word_size),
+                bytes: test_bytes(word_size, size),
+                iterations: results.len(),
+                throughput: mean(results)?,
+                std_deviation: std_deviation(results)?,
+            })
+        };
+
+        Ok([
+            measurement(Test::Read, &read_results),
+            measurement(Test::Write, &write_results),
+        ]
+        .into_iter()
+        .flatten()
+        .collect())
+    }
+}
+
+/// The number of bytes transferred by a test of `size` words.
+fn test_bytes(word_size: u32, size: usize) -> usize {
+    word_size as usize / 8 * size
+}
+
+/// Measures erasing and programming `size` random bytes at the flash address.
+fn flash_throughput(
+    session: &mut Session,
+    speed: u32,
+    address: u64,
+    size: usize,
+) -> anyhow::Result<Vec<Measurement>> {
+    let mut data = vec![0; size];
+    fastrand::Rng::new().fill(&mut data);
+
+    let mut loader = session.target().flash_loader();
+    loader.add_data(address, &data)?;
+
+    // The bytes and the time of erasing and programming.
+    let times = Rc::new(RefCell::new([(0, Duration::ZERO); 2]));
+    let progress = FlashProgress::new({
+        let times = times.clone();
+        move |event| {
+            let mut times = times.borrow_mut();
+            match event {
+                ProgressEvent::SectorErased { size, time } => {
+                    times[0].0 += size;
+                    times[0].1 += time;
                 }
+                ProgressEvent::PageProgrammed { size, time } => {
+                    times[1].0 += u64::from(size);
+                    times[1].1 += time;
+                }
+                _ => {}
             }
-            println!(
-                "Results: Read: {:.2} bytes/s Std Dev {:.2}, Write: {:.2} bytes/s Std Dev {:.2}",
-                mean(&read_results).expect("invalid mean"),
-                std_deviation(&read_results).expect("invalid std deviation"),
-                mean(&write_results).expect("invalid mean"),
-                std_deviation(&write_results).expect("invalid std deviation")
-            );
-            if read_results.len() != iterations || write_results.len() != iterations {
-                println!(
-                    "Warning: {} reads and {} writes successful (out of {} iterations)",
-                    read_results.len(),
-                    write_results.len(),
-                    iterations
-                )
+        }
+    });
+
+    let mut options = DownloadOptions::default();
+    options.progress = Some(progress);
+    options.verify = true;
+    loader.commit(session, options)?;
+
+    let times = *times.borrow();
+    Ok([Test::FlashErase, Test::FlashProgram]
+        .into_iter()
+        .zip(times)
+        .filter(|(_, (bytes, time))| *bytes > 0 && !time.is_zero())
+        .map(|(test, (bytes, time))| Measurement {
+            test,
+            speed_khz: speed,
+            word_size: None,
+            bytes: bytes as usize,
+            iterations: 1,
+            throughput: bytes as f64 / time.as_secs_f64(),
+            std_deviation: 0.0,
+        })
+        .collect())
+}
+
+/// Measures the throughput of the first RTT up channel, after resetting the target.
+fn rtt_throughput(
+    session: &mut Session,
+    speed: u32,
+    duration: Duration,
+) -> anyhow::Result<Measurement> {
+    let regions = session.target().rtt_scan_regions.clone();
+    let mut core = session.core(0)?;
+    core.reset()?;
+
+    // The firmware sets up the control block after the reset.
+    let attach_start = Instant::now();
+    let rtt = loop {
+        match Rtt::attach_region(&mut core, &regions) {
+            Ok(rtt) => break rtt,
+            Err(_) if attach_start.elapsed() < Duration::from_secs(1) => {
+                std::thread::sleep(Duration::from_millis(10))
             }
-            // Insert another blank line to visually seperate results
-            println!();
-        } else {
-            println!("failed to set speed {}", speed);
+            Err(e) => return Err(e).context("Failed to attach to RTT"),
         }
-        Ok(())
+    };
+    let channel = rtt
+        .up_channel(0)
+        .context("The target has no RTT up channel")?;
+
+    let mut buffer = vec![0; 4096];
+    let mut bytes = 0;
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        bytes += channel.read(&mut core, &mut buffer)?;
     }
+
+    Ok(Measurement {
+        test: Test::Rtt,
+        speed_khz: speed,
+        word_size: None,
+        bytes,
+        iterations: 1,
+        throughput: bytes as f64 / start.elapsed().as_secs_f64(),
+        std_deviation: 0.0,
+    })
 }
 
 impl DataType {
     pub fn new(word_size: u32) -> DataType {
         match word_size {
             8 => DataType::U8(Vec::new(), Vec::new()),
+            16 => DataType::U16(Vec::new(), Vec::new()),
             32 => DataType::U32(Vec::new(), Vec::new()),
             64 => DataType::U64(Vec::new(), Vec::new()),
             _ => panic!("Invalid word size"),
@@ -220,6 +514,7 @@ impl DataType {
     pub fn size(&self) -> usize {
         match self {
             DataType::U8(_, _) => 1,
+            DataType::U16(_, _) => 2,
             DataType::U32(_, _) => 4,
             DataType::U64(_, _) => 8,
         }
@@ -233,6 +528,13 @@ impl DataType {
                 *read_data = vec![0u8; data_size_words];
                 rng.fill(&mut test_data[..]);
             }
+            DataType::U16(ref mut test_data, ref mut read_data) => {
+                *test_data = vec![0u16; data_size_words];
+                *read_data = vec![0u16; data_size_words];
+                for out in test_data.iter_mut() {
+                    *out = rng.u16(..);
+                }
+            }
             DataType::U32(ref mut test_data, ref mut read_data) => {
                 *test_data = vec![0u32; data_size_words];
                 *read_data = vec![0u32; data_size_words];
@@ -260,6 +562,7 @@ impl DataType {
         }
         match self {
             DataType::U8(sample_data, read_data) => compare_data_inner(sample_data, read_data),
+            DataType::U16(sample_data, read_data) => compare_data_inner(sample_data, read_data),
             DataType::U32(sample_data, read_data) => compare_data_inner(sample_data, read_data),
             DataType::U64(sample_data, read_data) => compare_data_inner(sample_data, read_data),
         }
@@ -270,6 +573,9 @@ impl DataType {
             DataType::U8(sample_data, read_data) => {
                 (sample_data[offset].into(), read_data[offset].into())
             }
+            DataType::U16(sample_data, read_data) => {
+                (sample_data[offset].into(), read_data[offset].into())
+            }
             DataType::U32(sample_data, read_data) => {
                 (sample_data[offset].into(), read_data[offset].into())
             }
@@ -312,6 +618,9 @@ impl TestData {
             DataType::U8(_, ref mut readback_data) => core
                 .read_8(self.address, readback_data)
                 .expect("Reading the sample data failed"),
+            DataType::U16(_, ref mut readback_data) => core
+                .read_16(self.address, readback_data)
+                .expect("Reading the sample data failed"),
             DataType::U32(_, ref mut readback_data) => core
                 .read_32(self.address, readback_data)
                 .expect("Reading the sample data failed"),
@@ -333,6 +642,9 @@ impl TestData {
             DataType::U8(ref test_data, _) => core
                 .write_8(self.address, test_data)
                 .context("Writing the sample data failed")?,
+            DataType::U16(ref test_data, _) => core
+                .write_16(self.address, test_data)
+                .context("Writing the sample data failed")?,
             DataType::U32(ref test_data, _) => core
                 .write_32(self.address, test_data)
                 .context("Writing the sample data failed")?,
@@ -378,3 +690,36 @@ fn std_deviation(data: &[f64]) -> Option<f64> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn measurements_are_compared_to_the_baseline() {
+        let measurement = Measurement {
+            test: Test::Read,
+            speed_khz: 4000,
+            word_size: Some(32),
+            bytes: 2048,
+            iterations: 5,
+            throughput: 110.0,
+            std_deviation: 1.0,
+        };
+        let baseline = Measurement {
+            throughput: 100.0,
+            ..measurement.clone()
+        };
+        let other_size = Measurement {
+            bytes: 4,
+            ..baseline.clone()
+        };
+
+        assert!(measurement.same_test(&baseline));
+        assert!(!measurement.same_test(&other_size));
+        assert_eq!(
+            measurement.to_text(Some(&baseline)),
+            "Read 32bit, 2048 bytes @ 4000 kHz: 110.00 bytes/s Std Dev 1.00 (+10.0% vs. baseline)"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use probe_rs::probe::list::Lister;
+use probe_rs::{Core, CoreType, MemoryInterface, ResetStrategy};
+
+use crate::util::common_options::ProbeOptions;
+use crate::util::parse_u64;
+
+/// Restart the target in its ROM bootloader, e.g. to continue with a DFU or UF2 based workflow
+///
+/// The method is chosen based on the chip, unless it is given with `--method`.
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// How the bootloader is entered.
+    #[clap(long, value_enum)]
+    method: Option<Method>,
+
+    /// The address of the STM32 system memory, for families where it is not known.
+    #[clap(long, value_parser = parse_u64)]
+    address: Option<u64>,
+
+    /// The value written to GPREGRET, e.g. `0x57` for the UF2 mode of the Adafruit bootloader,
+    /// or `0xb1` for the DFU mode of the Nordic secure bootloader.
+    #[clap(long, value_parser = parse_u64, default_value = "0x57")]
+    gpregret: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Method {
+    /// Start the STM32 system memory bootloader, by jumping to it after a reset.
+    ///
+    /// This does not change the BOOT pins or the option bytes, so the next reset starts the
+    /// application again.
+    Stm32SystemMemory,
+    /// Start the USB bootloader of the RP2040 (BOOTSEL mode), with the `reset_usb_boot` function
+    /// of its boot ROM.
+    Rp2040Bootsel,
+    /// Write a magic value to the GPREGRET register of nRF chips and reset, which the Adafruit
+    /// (UF2) and Nordic bootloaders check.
+    NrfGpregret,
+    /// Start the serial downloader of the i.MX RT boot ROM, with its `runBootloader` function.
+    ///
+    /// This recovers chips whose image in FlexSPI flash crashes or locks up the debug port. The
+    /// RT1050 has no ROM API and needs the boot mode pins instead.
+    ImxrtSerialDownloader,
+}
+
+impl Method {
+    fn for_chip(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name.starts_with("stm32") {
+            Some(Self::Stm32SystemMemory)
+        } else if name.starts_with("rp2040") {
+            Some(Self::Rp2040Bootsel)
+        } else if name.starts_with("nrf52") {
+            Some(Self::NrfGpregret)
+        } else if name.starts_with("mimxrt10") || name.starts_with("mimxrt11") {
+            Some(Self::ImxrtSerialDownloader)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the address of the system memory of STM32 families where it is the same for all
+/// chips, see AN2606.
+fn stm32_system_memory(name: &str) -> Option<u64> {
+    let name = name.to_ascii_lowercase();
+    let family = name.strip_prefix("stm32")?;
+
+    [
+        ("f2", 0x1FFF_0000),
+        ("f3", 0x1FFF_D800),
+        ("f4", 0x1FFF_0000),
+        ("f7", 0x1FF0_0000),
+        ("g0", 0x1FFF_0000),
+        ("g4", 0x1FFF_0000),
+        ("l0", 0x1FF0_0000),
+        ("l1", 0x1FF0_0000),
+        ("l4", 0x1FFF_0000),
+        ("wb", 0x1FFF_0000),
+    ]
+    .into_iter()
+    .find(|(prefix, _)| family.starts_with(prefix))
+    .map(|(_, address)| address)
+}
+
+/// The address of GPREGRET in the POWER peripheral of nRF52 chips.
+const NRF52_GPREGRET: u64 = 0x4000_051C;
+
+/// The argument of `runBootloader` to enter the serial downloader, with automatic selection of
+/// the USB or UART interface.
+const IMXRT_SERIAL_DOWNLOADER: u32 = 0xEB10_0000;
+
+/// The code of the `reset_usb_boot` function in the function table of the RP2040 boot ROM.
+const RP2040_RESET_USB_BOOT: u16 = u16::from_le_bytes(*b"UB");
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let (mut session, _) = self.probe_options.simple_attach(lister)?;
+        let chip = session.target().name.clone();
+
+        let method = match self.method {
+            Some(method) => method,
+            None => Method::for_chip(&chip).with_context(|| {
+                format!("The bootloader of {chip} is not known, select it with --method")
+            })?,
+        };
+
+        let mut core = session.core(0)?;
+        match method {
+            Method::Stm32SystemMemory => {
+                let address = self.address.or_else(|| stm32_system_memory(&chip));
+                let address = address.with_context(|| {
+                    format!("The system memory of {chip} is not known, give it with --address")
+                })?;
+                jump_to(&mut core, address)?;
+            }
+            Method::Rp2040Bootsel => rp2040_reset_usb_boot(&mut core)?,
+            Method::ImxrtSerialDownloader => {
+                anyhow::ensure!(
+                    !chip.starts_with("MIMXRT1050"),
+                    "The RT1050 has no ROM API, select the serial downloader with the boot mode pins"
+                );
+                // The pointer to the ROM API tree is at a different address on the RT11xx.
+                let tree = if chip.starts_with("MIMXRT11") {
+                    0x0021_001C
+                } else {
+                    0x0020_001C
+                };
+                imxrt_run_bootloader(&mut core, tree)?;
+            }
+            Method::NrfGpregret => {
+                let value = u32::try_from(self.gpregret).context("GPREGRET is 32 bits wide")?;
+                core.write_word_32(NRF52_GPREGRET, value)?;
+                // GPREGRET is kept over a soft reset, but cleared by a pin reset.
+                core.reset_with(
+                    ResetStrategy::SysResetReq,
+                    false,
+                    Duration::from_millis(500),
+                )?;
+            }
+        }
+
+        println!("Started the bootloader of {chip}");
+        Ok(())
+    }
+}
+
+/// Resets the core, and starts the program with the vector table at `address` instead.
+fn jump_to(core: &mut Core, address: u64) -> anyhow::Result<()> {
+    core.reset_and_halt(Duration::from_millis(500))?;
+
+    let stack_pointer = core.read_word_32(address)?;
+    let reset_vector = core.read_word_32(address + 4)?;
+    tracing::info!("Jumping to {reset_vector:#010x} with SP {stack_pointer:#010x}");
+
+    // Interrupts have to use the vector table of the bootloader. Cortex-M0 cores have no VTOR,
+    // and rely on the memory remapping of the chip instead, which is done by the BOOT pins.
+    if core.core_type() == CoreType::Armv6m {
+        tracing::warn!(
+            "The vector table can't be moved on Cortex-M0, interrupts in the bootloader may fail"
+        );
+    } else {
+        const VTOR: u64 = 0xE000_ED08;
+        core.write_word_32(VTOR, address as u32)?;
+    }
+
+    core.write_core_reg(core.stack_pointer(), stack_pointer)?;
+    core.write_core_reg(core.program_counter(), reset_vector & !1)?;
+    core.run()?;
+
+    Ok(())
+}
+
+/// Calls `reset_usb_boot(0, 0)` of the RP2040 boot ROM, which reboots into BOOTSEL mode.
+fn rp2040_reset_usb_boot(core: &mut Core) -> anyhow::Result<()> {
+    core.reset_and_halt(Duration::from_millis(500))?;
+
+    // The boot ROM has a pointer to the table of public functions at 0x14, whose entries are
+    // pairs of a 16-bit code and a 16-bit address, ending with a zero code.
+    let mut entry = u64::from(core.read_word_16(0x14)?);
+    let function = loop {
+        let code = core.read_word_16(entry)?;
+        anyhow::ensure!(code != 0, "reset_usb_boot was not found in the boot ROM");
+        if code == RP2040_RESET_USB_BOOT {
+            break core.read_word_16(entry + 2)?;
+        }
+        entry += 4;
+    };
+
+    let registers = core.registers();
+    core.write_core_reg(registers.argument_register(0), 0u32)?;
+    core.write_core_reg(registers.argument_register(1), 0u32)?;
+    core.write_core_reg(core.program_counter(), u32::from(function) & !1)?;
+    core.run()?;
+
+    Ok(())
+}
+
+/// Calls `runBootloader` of the i.MX RT ROM API, whose tree is pointed to from `tree`, to start
+/// the serial downloader.
+fn imxrt_run_bootloader(core: &mut Core, tree: u64) -> anyhow::Result<()> {
+    core.reset_and_halt(Duration::from_millis(500))?;
+
+    // The argument is passed by reference, so it is placed at the start of the DTCM, which all
+    // chips have. The stack goes above it, as the image in flash may not have set up a valid one.
+    const ARGUMENT: u64 = 0x2000_0000;
+    const STACK: u32 = 0x2000_8000;
+    core.write_word_32(ARGUMENT, IMXRT_SERIAL_DOWNLOADER)?;
+
+    // The tree starts with the version and copyright, followed by `runBootloader`.
+    let tree = core.read_word_32(tree)?;
+    let run_bootloader = core.read_word_32(u64::from(tree) + 8)?;
+    tracing::info!("Calling runBootloader at {run_bootloader:#010x}");
+
+    let registers = core.registers();
+    core.write_core_reg(registers.argument_register(0), ARGUMENT as u32)?;
+    core.write_core_reg(core.stack_pointer(), STACK)?;
+    core.write_core_reg(core.program_counter(), run_bootloader & !1)?;
+    core.run()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bootloader_is_chosen_by_chip() {
+        assert_eq!(
+            Method::for_chip("STM32F411CEUx"),
+            Some(Method::Stm32SystemMemory)
+        );
+        assert_eq!(Method::for_chip("RP2040"), Some(Method::Rp2040Bootsel));
+        assert_eq!(Method::for_chip("nRF52840_xxAA"), Some(Method::NrfGpregret));
+        assert_eq!(
+            Method::for_chip("MIMXRT1060"),
+            Some(Method::ImxrtSerialDownloader)
+        );
+        assert_eq!(Method::for_chip("esp32c3"), None);
+
+        assert_eq!(stm32_system_memory("STM32F411CEUx"), Some(0x1FFF_0000));
+        assert_eq!(stm32_system_memory("STM32F767ZITx"), Some(0x1FF0_0000));
+        // The F0 family has a different address for every line.
+        assert_eq!(stm32_system_memory("STM32F030C8Tx"), None);
+        assert_eq!(RP2040_RESET_USB_BOOT, 0x4255);
+    }
+}
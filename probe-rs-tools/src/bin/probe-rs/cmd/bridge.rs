@@ -0,0 +1,222 @@
+use anyhow::Context;
+use probe_rs::probe::{
+    stlink::{BridgeGpioMode, SpiMode, StLinkBridge, BRIDGE_GPIO_COUNT},
+    DebugProbeSelector,
+};
+
+/// Use the bridge interface of an ST-LINK-V3
+///
+/// The bridge gives access to the SPI, I2C and GPIO pins of the probe, e.g. to program an
+/// external EEPROM. It can be used while the probe is debugging the target.
+#[derive(clap::Parser)]
+pub struct Cmd {
+    /// The probe to use, given as 'VID:PID' or 'VID:PID:Serial'.
+    #[clap(long, env = "PROBE_RS_PROBE")]
+    probe: Option<DebugProbeSelector>,
+
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// List the virtual COM ports of the probe
+    Uart,
+    /// Read or write the GPIO pins
+    #[clap(subcommand)]
+    Gpio(GpioCommand),
+    /// Access a device on the I2C bus
+    #[clap(subcommand)]
+    I2c(I2cCommand),
+    /// Access a device on the SPI bus
+    Spi {
+        /// The SPI clock speed in kHz.
+        #[clap(long, default_value = "1000")]
+        speed: u32,
+        /// The SPI mode, from 0 to 3.
+        #[clap(long, default_value = "0", value_parser = parse_spi_mode)]
+        mode: SpiMode,
+        /// The bytes to write, e.g. `0x9f`.
+        #[clap(value_parser = parse_u8)]
+        data: Vec<u8>,
+        /// The number of bytes to read after writing.
+        #[clap(long, default_value = "0")]
+        read: usize,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum GpioCommand {
+    /// Read the level of a pin
+    Read {
+        /// The pin, from 0 to 3.
+        #[clap(value_parser = parse_pin)]
+        pin: u8,
+    },
+    /// Drive a pin high or low
+    Write {
+        /// The pin, from 0 to 3.
+        #[clap(value_parser = parse_pin)]
+        pin: u8,
+        /// The level, 0 or 1.
+        #[clap(value_parser = clap::value_parser!(u8).range(0..=1))]
+        value: u8,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum I2cCommand {
+    /// Read from a device, optionally writing e.g. a register address first
+    Read {
+        /// The 7 bit address of the device.
+        #[clap(long, value_parser = parse_u8)]
+        address: u8,
+        /// The number of bytes to read.
+        #[clap(long)]
+        length: usize,
+        /// Bytes to write before reading.
+        #[clap(long, value_parser = parse_u8, num_args = 1..)]
+        write: Vec<u8>,
+        /// The I2C clock speed in kHz.
+        #[clap(long, default_value = "100")]
+        speed: u32,
+    },
+    /// Write to a device
+    Write {
+        /// The 7 bit address of the device.
+        #[clap(long, value_parser = parse_u8)]
+        address: u8,
+        /// The bytes to write.
+        #[clap(value_parser = parse_u8, required = true)]
+        data: Vec<u8>,
+        /// The I2C clock speed in kHz.
+        #[clap(long, default_value = "100")]
+        speed: u32,
+    },
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        let selector = match self.probe {
+            Some(selector) => selector,
+            None => {
+                let probes = StLinkBridge::list();
+                let probe = probes
+                    .first()
+                    .context("No ST-LINK-V3 probe with a bridge interface was found")?;
+                if probes.len() > 1 {
+                    println!("Multiple probes found, using {probe}");
+                }
+                DebugProbeSelector::from(probe)
+            }
+        };
+
+        let mut bridge = StLinkBridge::open(&selector)?;
+
+        match self.subcommand {
+            Subcommand::Uart => {
+                let uarts = bridge.uarts();
+                if uarts.is_empty() {
+                    println!("The probe has no virtual COM ports.");
+                }
+                for uart in uarts {
+                    match uart.path {
+                        Some(path) => println!("{} ({})", uart.name, path.display()),
+                        None => println!("{}", uart.name),
+                    }
+                }
+            }
+            Subcommand::Gpio(GpioCommand::Read { pin }) => {
+                let mask = 1 << pin;
+                bridge.gpio_init(mask, BridgeGpioMode::Input)?;
+                let value = bridge.gpio_read(mask)? != 0;
+                println!("GPIO{pin}: {}", value as u8);
+            }
+            Subcommand::Gpio(GpioCommand::Write { pin, value }) => {
+                let mask = 1 << pin;
+                bridge.gpio_init(mask, BridgeGpioMode::Output)?;
+                bridge.gpio_write(mask, value << pin)?;
+            }
+            Subcommand::I2c(I2cCommand::Read {
+                address,
+                length,
+                write,
+                speed,
+            }) => {
+                bridge.i2c_init(speed)?;
+                if !write.is_empty() {
+                    bridge.i2c_write(address, &write)?;
+                }
+                let data = bridge.i2c_read(address, length)?;
+                print_bytes(&data);
+            }
+            Subcommand::I2c(I2cCommand::Write {
+                address,
+                data,
+                speed,
+            }) => {
+                bridge.i2c_init(speed)?;
+                bridge.i2c_write(address, &data)?;
+            }
+            Subcommand::Spi {
+                speed,
+                mode,
+                data,
+                read,
+            } => {
+                let actual_speed = bridge.spi_init(mode, speed)?;
+                tracing::info!("SPI clock is {actual_speed} kHz");
+
+                bridge.spi_set_cs(false)?;
+                let result = spi_transfer(&mut bridge, &data, read);
+                bridge.spi_set_cs(true)?;
+
+                print_bytes(&result?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn spi_transfer(bridge: &mut StLinkBridge, data: &[u8], read: usize) -> anyhow::Result<Vec<u8>> {
+    if !data.is_empty() {
+        bridge.spi_write(data)?;
+    }
+    if read == 0 {
+        return Ok(vec![]);
+    }
+    Ok(bridge.spi_read(read)?)
+}
+
+fn print_bytes(data: &[u8]) {
+    for line in data.chunks(16) {
+        let line: Vec<_> = line.iter().map(|byte| format!("{byte:02x}")).collect();
+        println!("{}", line.join(" "));
+    }
+}
+
+fn parse_u8(input: &str) -> Result<u8, std::num::ParseIntError> {
+    parse_int::parse(input)
+}
+
+fn parse_pin(input: &str) -> Result<u8, String> {
+    let pin = parse_u8(input).map_err(|e| e.to_string())?;
+    if usize::from(pin) >= BRIDGE_GPIO_COUNT {
+        return Err(format!(
+            "The bridge has {BRIDGE_GPIO_COUNT} GPIO pins, from 0 to {}",
+            BRIDGE_GPIO_COUNT - 1
+        ));
+    }
+    Ok(pin)
+}
+
+fn parse_spi_mode(input: &str) -> Result<SpiMode, String> {
+    match input {
+        "0" => Ok(SpiMode::Mode0),
+        "1" => Ok(SpiMode::Mode1),
+        "2" => Ok(SpiMode::Mode2),
+        "3" => Ok(SpiMode::Mode3),
+        _ => Err(format!("Invalid SPI mode '{input}', expected 0 to 3")),
+    }
+}
@@ -39,6 +39,8 @@ pub struct Probe {
     pub serial: Option<String>,
     pub protocol: WireProtocol,
     pub speed: Option<u32>,
+    pub adaptive_clocking: bool,
+    pub speed_fallback: bool,
 }
 
 /// The flashing config struct holding all the possible flashing options.
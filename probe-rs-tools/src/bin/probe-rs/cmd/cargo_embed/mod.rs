@@ -1,6 +1,6 @@
 mod config;
 mod error;
-mod rttui;
+pub(crate) mod rttui;
 
 use anyhow::{anyhow, Context, Result};
 use clap::Parser;
@@ -9,7 +9,10 @@ use parking_lot::FairMutex;
 use probe_rs::gdb_server::GdbInstanceConfiguration;
 use probe_rs::probe::list::Lister;
 use probe_rs::rtt::{try_attach_to_rtt_shared, Error, ScanRegion};
-use probe_rs::{probe::DebugProbeSelector, Session};
+use probe_rs::{
+    probe::{bitbang::BitbangProtocol, DebugProbeSelector},
+    Session,
+};
 use std::ffi::OsString;
 use std::{fs, thread};
 use std::{
@@ -118,7 +121,7 @@ fn main_try(args: &[OsString], offset: UtcOffset) -> Result<()> {
     let configs = config::Configs::new(work_dir.clone());
     let config = configs.select_defined(config_name)?;
 
-    let _log_guard = setup_logging(None, config.general.log_level);
+    let _log_guard = setup_logging(None, config.general.log_level, None);
 
     // Make sure we load the config given in the cli parameters.
     for cdp in &config.general.chip_descriptions {
@@ -162,6 +165,8 @@ fn main_try(args: &[OsString], offset: UtcOffset) -> Result<()> {
                 vendor_id: u16::from_str_radix(vid, 16)?,
                 product_id: u16::from_str_radix(pid, 16)?,
                 serial_number: config.probe.serial.clone(),
+                ip_address: None,
+                usb_port: None,
             }),
             (vid, pid) => {
                 if vid.is_some() {
@@ -187,10 +192,20 @@ fn main_try(args: &[OsString], offset: UtcOffset) -> Result<()> {
         protocol: Some(config.probe.protocol),
         non_interactive: false,
         probe: selector,
+        wait_for_probe: false,
+        connect_timeout: None,
         speed: config.probe.speed,
+        adaptive_clocking: config.probe.adaptive_clocking,
+        speed_fallback: config.probe.speed_fallback,
         connect_under_reset: config.general.connect_under_reset,
         dry_run: false,
         allow_erase_all: config.flashing.enabled || config.gdb.enabled,
+        record: None,
+        replay: None,
+        remote: None,
+        bitbang: None,
+        bitbang_protocol: BitbangProtocol::RemoteBitbang,
+        gpio: None,
     };
 
     let lister = Lister::new();
@@ -233,6 +248,8 @@ fn main_try(args: &[OsString], offset: UtcOffset) -> Result<()> {
             restore_unwritten: config.flashing.restore_unwritten_bytes,
             flash_layout_output_path: None,
             verify: config.flashing.verify,
+            metrics: false,
+            patches: Default::default(),
         };
         let format_options = FormatOptions::default();
         let loader = build_loader(&mut session, &path, format_options, image_instr_set)?;
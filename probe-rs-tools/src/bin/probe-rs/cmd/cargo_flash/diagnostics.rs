@@ -77,6 +77,12 @@ pub(crate) fn render_diagnostics(error: OperationError) {
             ],
         ),
         OperationError::FlashingFailed { source, target, target_spec, .. } => generate_flash_error_hints(source, target, target_spec),
+        OperationError::FailedToPatchImage { .. } => (
+            error.to_string(),
+            vec![
+                "Patches are given as <ADDRESS|SYMBOL>=<VALUE>, where the value is hex bytes (0x0011aabb), an integer (u32:1234) or a string (str:SN-0001).".into(),
+            ],
+        ),
         OperationError::ChipDescriptionNotFound{ .. } => (
             error.to_string(),
             vec![],
@@ -142,7 +148,7 @@ pub(crate) fn render_diagnostics(error: OperationError) {
             ),
         },
         OperationError::ChipNotFound { source, .. } => match source {
-            RegistryError::ChipNotFound(_) => (
+            RegistryError::ChipNotFound(..) => (
                 error.to_string(),
                 vec![
                     "Did you spell the name of your chip correctly? Capitalization does not matter."
@@ -166,6 +172,13 @@ pub(crate) fn render_diagnostics(error: OperationError) {
                 format!("Try specifying a speed lower than {speed} kHz")
             ],
         ),
+        OperationError::FailedToEnableAdaptiveClocking(_) => (
+            error.to_string(),
+            vec![
+                "Adaptive clocking requires JTAG and a probe which supports RTCK, e.g. a J-Link or an FT2232H based probe.".into(),
+                "Make sure RTCK is connected, or use a fixed `--speed` instead.".into(),
+            ],
+        ),
         OperationError::AttachingFailed { source, connect_under_reset } => match source {
             ProbeRsError::ChipNotFound(RegistryError::ChipAutodetectFailed) => (
                 error.to_string(),
@@ -76,7 +76,7 @@ fn main_try(args: &[OsString]) -> Result<(), OperationError> {
     let opt = CliOptions::parse_from(args);
 
     // Initialize the logger with the loglevel given on the commandline.
-    let _log_guard = setup_logging(None, opt.log);
+    let _log_guard = setup_logging(None, opt.log, None);
 
     // Change the work dir if the user asked to do so.
     if let Some(ref work_dir) = opt.work_dir {
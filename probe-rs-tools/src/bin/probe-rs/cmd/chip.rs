@@ -1,5 +1,12 @@
+mod validate;
+
 use bytesize::ByteSize;
-use probe_rs::config::MemoryRegion;
+use probe_rs::config::{search_chips_fuzzy, DebugSequence, MemoryRegion};
+use probe_rs::probe::list::Lister;
+use probe_rs_target::{CoreAccessOptions, MemoryAccess};
+use serde::Serialize;
+
+use crate::util::output::{write_json, OutputFormat};
 
 #[derive(clap::Parser)]
 pub struct Cmd {
@@ -9,6 +16,7 @@ pub struct Cmd {
 
 #[derive(clap::Subcommand)]
 /// Inspect internal registry of supported chips
+#[allow(clippy::large_enum_variant)]
 enum Subcommand {
     /// Lists all the available families and their chips with their full.
     #[clap(name = "list")]
@@ -19,15 +27,40 @@ enum Subcommand {
         /// The name of the chip to display.
         name: String,
     },
+    /// Searches for chips with a similar name, best matches first
+    #[clap(name = "search")]
+    Search {
+        /// A part of the chip name, e.g. "stm32f4".
+        query: String,
+        /// The maximum number of chips to show.
+        #[clap(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Checks a target description against the attached hardware
+    ///
+    /// Attaches to the target, resets its cores, accesses the boundaries of its memory regions,
+    /// and erases, programs and verifies a flash sector, to report which parts of the target
+    /// description are wrong.
+    #[clap(name = "validate")]
+    Validate(validate::Cmd),
 }
 
 impl Cmd {
-    pub fn run(self) -> anyhow::Result<()> {
+    pub fn run(self, lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
         let output = std::io::stdout().lock();
 
-        match self.subcommand {
-            Subcommand::List => print_families(output),
-            Subcommand::Info { name } => print_chip_info(output, &name),
+        match (self.subcommand, output_format) {
+            (Subcommand::List, OutputFormat::Text) => print_families(output),
+            (Subcommand::List, OutputFormat::Json) => print_families_json(output),
+            (Subcommand::Info { name }, OutputFormat::Text) => print_chip_info(output, &name),
+            (Subcommand::Info { name }, OutputFormat::Json) => print_chip_info_json(output, &name),
+            (Subcommand::Search { query, limit }, OutputFormat::Text) => {
+                print_search_results(output, &query, limit)
+            }
+            (Subcommand::Search { query, limit }, OutputFormat::Json) => {
+                write_json(output, "chip search", &search_chips_fuzzy(query, limit))
+            }
+            (Subcommand::Validate(cmd), output_format) => cmd.run(lister, output_format),
         }
     }
 }
@@ -46,38 +79,356 @@ pub fn print_families(mut output: impl std::io::Write) -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Print all the available families and their contained chips to the
-/// commandline.
+#[derive(Serialize)]
+struct FamilyEntry<'a> {
+    name: &'a str,
+    variants: Vec<&'a str>,
+}
+
+/// Print all the available families and their contained chips as JSON.
+pub fn print_families_json(output: impl std::io::Write) -> anyhow::Result<()> {
+    let families = probe_rs::config::families();
+    let families: Vec<_> = families
+        .iter()
+        .map(|family| FamilyEntry {
+            name: &family.name,
+            variants: family.variants().iter().map(|v| v.name.as_str()).collect(),
+        })
+        .collect();
+
+    write_json(output, "chip list", &families)
+}
+
+/// Print the chips matching `query` to the commandline.
+pub fn print_search_results(
+    mut output: impl std::io::Write,
+    query: &str,
+    limit: usize,
+) -> anyhow::Result<()> {
+    let matches = search_chips_fuzzy(query, limit);
+    if matches.is_empty() {
+        writeln!(output, "No chips found for '{query}'.")?;
+        return Ok(());
+    }
+
+    for chip in matches {
+        writeln!(output, "{} ({})", chip.name, chip.family)?;
+    }
+    Ok(())
+}
+
+/// Print the properties of a chip to the commandline.
 pub fn print_chip_info(mut output: impl std::io::Write, name: &str) -> anyhow::Result<()> {
     writeln!(output, "{}", name)?;
-    let target = probe_rs::config::get_target_by_name(name)?;
-    writeln!(output, "Cores ({}):", target.cores.len())?;
-    for core in target.cores {
-        writeln!(
+    let info = chip_info(name)?;
+
+    writeln!(output, "Cores ({}):", info.cores.len())?;
+    for core in &info.cores {
+        writeln!(output, "    - {} ({})", core.name, core.core_type)?;
+        let access = match &core.access {
+            CoreAccess::Arm {
+                ap,
+                psel,
+                debug_base,
+                cti_base,
+            } => {
+                let mut access = format!("AP {ap}, PSEL {psel:#x}");
+                if let Some(debug_base) = debug_base {
+                    access.push_str(&format!(", debug base {debug_base:#010x}"));
+                }
+                if let Some(cti_base) = cti_base {
+                    access.push_str(&format!(", CTI base {cti_base:#010x}"));
+                }
+                access
+            }
+            CoreAccess::Riscv { hart_id, jtag_tap } => {
+                format!(
+                    "hart {}, JTAG TAP {}",
+                    optional(hart_id),
+                    optional(jtag_tap)
+                )
+            }
+            CoreAccess::Xtensa { jtag_tap } => format!("JTAG TAP {}", optional(jtag_tap)),
+        };
+        writeln!(output, "        Access: {access}")?;
+    }
+
+    writeln!(output, "Memory map ({}):", info.memory_map.len())?;
+    for memory in &info.memory_map {
+        let size = ByteSize(memory.end - memory.start).to_string_as(true);
+        write!(
             output,
-            "    - {} ({:?})",
-            core.name.to_ascii_lowercase(),
-            core.core_type
+            "    - {}: {:#010x?} ({size})",
+            memory.kind,
+            memory.start..memory.end
         )?;
+        if let Some(name) = &memory.name {
+            write!(output, " {name}")?;
+        }
+        writeln!(output)?;
+
+        let mut access = String::new();
+        for (allowed, flag) in [
+            (memory.access.read, 'r'),
+            (memory.access.write, 'w'),
+            (memory.access.execute, 'x'),
+        ] {
+            access.push(if allowed { flag } else { '-' });
+        }
+        if memory.access.boot {
+            access.push_str(", boot");
+        }
+        if memory.is_alias {
+            access.push_str(", alias");
+        }
+        writeln!(output, "        Access: {access}")?;
+        writeln!(output, "        Cores: {}", memory.cores.join(", "))?;
     }
 
-    fn get_range_len(range: &std::ops::Range<u64>) -> u64 {
-        range.end - range.start
+    writeln!(
+        output,
+        "Flash algorithms ({}):",
+        info.flash_algorithms.len()
+    )?;
+    for algorithm in &info.flash_algorithms {
+        let default = if algorithm.default { " (default)" } else { "" };
+        writeln!(output, "    - {}{default}", algorithm.name)?;
+        if !algorithm.description.is_empty() {
+            writeln!(output, "        Description: {}", algorithm.description)?;
+        }
+        writeln!(
+            output,
+            "        Flash: {:#010x?}, page size {}, erased byte {:#04x}",
+            algorithm.flash_start..algorithm.flash_end,
+            ByteSize(algorithm.page_size.into()).to_string_as(true),
+            algorithm.erased_byte_value
+        )?;
+        for sector in &algorithm.sectors {
+            writeln!(
+                output,
+                "        Sectors: {} from {:#010x}",
+                ByteSize(sector.size).to_string_as(true),
+                sector.address
+            )?;
+        }
+        let load_address = algorithm
+            .load_address
+            .map_or_else(|| "any".to_string(), |address| format!("{address:#010x}"));
+        writeln!(output, "        Load address: {load_address}")?;
+        if let Some(data_load_address) = algorithm.data_load_address {
+            writeln!(
+                output,
+                "        Data load address: {data_load_address:#010x}"
+            )?;
+        }
+        writeln!(
+            output,
+            "        Data section offset: {:#x}",
+            algorithm.data_section_offset
+        )?;
+        let stack_size = algorithm
+            .stack_size
+            .map_or_else(|| "default".to_string(), |size| size.to_string());
+        writeln!(output, "        Stack size: {stack_size}")?;
+        writeln!(output, "        Encoding: {}", algorithm.transfer_encoding)?;
+        writeln!(output, "        Cores: {}", algorithm.cores.join(", "))?;
     }
 
-    for memory in target.memory_map {
-        let range = memory.address_range();
-        let size = ByteSize(get_range_len(&range)).to_string_as(true);
-        let kind = match memory {
-            MemoryRegion::Ram(_) => "RAM",
-            MemoryRegion::Generic(_) => "Generic",
-            MemoryRegion::Nvm(_) => "NVM",
-        };
-        writeln!(output, "{kind}: {range:#010x?} ({size})")?
+    writeln!(output, "Debug sequence: {}", info.debug_sequence)?;
+    if let Some(default_format) = &info.default_format {
+        writeln!(output, "Default format: {default_format}")?;
     }
+
     Ok(())
 }
 
+fn optional(value: &Option<impl std::fmt::Display>) -> String {
+    value
+        .as_ref()
+        .map_or_else(|| "default".to_string(), |value| value.to_string())
+}
+
+fn memory_kind(memory: &MemoryRegion) -> &'static str {
+    match memory {
+        MemoryRegion::Ram(_) => "RAM",
+        MemoryRegion::Generic(_) => "Generic",
+        MemoryRegion::Nvm(_) => "NVM",
+    }
+}
+
+#[derive(Serialize)]
+struct ChipInfo {
+    name: String,
+    cores: Vec<CoreEntry>,
+    memory_map: Vec<MemoryEntry>,
+    flash_algorithms: Vec<FlashAlgorithmEntry>,
+    debug_sequence: String,
+    default_format: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CoreEntry {
+    name: String,
+    core_type: String,
+    access: CoreAccess,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "architecture", rename_all = "snake_case")]
+enum CoreAccess {
+    Arm {
+        ap: u8,
+        psel: u32,
+        debug_base: Option<u64>,
+        cti_base: Option<u64>,
+    },
+    Riscv {
+        hart_id: Option<u32>,
+        jtag_tap: Option<usize>,
+    },
+    Xtensa {
+        jtag_tap: Option<usize>,
+    },
+}
+
+#[derive(Serialize)]
+struct MemoryEntry {
+    kind: &'static str,
+    name: Option<String>,
+    start: u64,
+    end: u64,
+    access: MemoryAccess,
+    is_alias: bool,
+    cores: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct FlashAlgorithmEntry {
+    name: String,
+    description: String,
+    default: bool,
+    flash_start: u64,
+    flash_end: u64,
+    page_size: u32,
+    erased_byte_value: u8,
+    sectors: Vec<SectorEntry>,
+    load_address: Option<u64>,
+    data_load_address: Option<u64>,
+    data_section_offset: u64,
+    stack_size: Option<u32>,
+    transfer_encoding: String,
+    cores: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SectorEntry {
+    size: u64,
+    address: u64,
+}
+
+/// Collects the properties of a chip.
+fn chip_info(name: &str) -> anyhow::Result<ChipInfo> {
+    let target = probe_rs::config::get_target_by_name(name)?;
+
+    let cores = target
+        .cores
+        .iter()
+        .map(|core| CoreEntry {
+            name: core.name.to_ascii_lowercase(),
+            core_type: format!("{:?}", core.core_type),
+            access: match &core.core_access_options {
+                CoreAccessOptions::Arm(options) => CoreAccess::Arm {
+                    ap: options.ap,
+                    psel: options.psel,
+                    debug_base: options.debug_base,
+                    cti_base: options.cti_base,
+                },
+                CoreAccessOptions::Riscv(options) => CoreAccess::Riscv {
+                    hart_id: options.hart_id,
+                    jtag_tap: options.jtag_tap,
+                },
+                CoreAccessOptions::Xtensa(options) => CoreAccess::Xtensa {
+                    jtag_tap: options.jtag_tap,
+                },
+            },
+        })
+        .collect();
+
+    let memory_map = target
+        .memory_map
+        .iter()
+        .map(|memory| {
+            let range = memory.address_range();
+            let (name, access, is_alias) = match memory {
+                MemoryRegion::Ram(region) => (&region.name, region.access(), false),
+                MemoryRegion::Generic(region) => (&region.name, region.access(), false),
+                MemoryRegion::Nvm(region) => (&region.name, region.access(), region.is_alias),
+            };
+            MemoryEntry {
+                kind: memory_kind(memory),
+                name: name.clone(),
+                start: range.start,
+                end: range.end,
+                access,
+                is_alias,
+                cores: memory.cores().to_vec(),
+            }
+        })
+        .collect();
+
+    let flash_algorithms = target
+        .flash_algorithms
+        .iter()
+        .map(|algorithm| {
+            let properties = &algorithm.flash_properties;
+            FlashAlgorithmEntry {
+                name: algorithm.name.clone(),
+                description: algorithm.description.clone(),
+                default: algorithm.default,
+                flash_start: properties.address_range.start,
+                flash_end: properties.address_range.end,
+                page_size: properties.page_size,
+                erased_byte_value: properties.erased_byte_value,
+                sectors: properties
+                    .sectors
+                    .iter()
+                    .map(|sector| SectorEntry {
+                        size: sector.size,
+                        address: sector.address,
+                    })
+                    .collect(),
+                load_address: algorithm.load_address,
+                data_load_address: algorithm.data_load_address,
+                data_section_offset: algorithm.data_section_offset,
+                stack_size: algorithm.stack_size,
+                transfer_encoding: format!("{:?}", algorithm.transfer_encoding.unwrap_or_default())
+                    .to_ascii_lowercase(),
+                cores: algorithm.cores.clone(),
+            }
+        })
+        .collect();
+
+    let debug_sequence = match &target.debug_sequence {
+        DebugSequence::Arm(sequence) => format!("{sequence:?}"),
+        DebugSequence::Riscv(sequence) => format!("{sequence:?}"),
+        DebugSequence::Xtensa(sequence) => format!("{sequence:?}"),
+    };
+
+    Ok(ChipInfo {
+        name: target.name,
+        cores,
+        memory_map,
+        flash_algorithms,
+        debug_sequence,
+        default_format: target.default_format,
+    })
+}
+
+/// Print the properties of a chip as JSON.
+pub fn print_chip_info_json(output: impl std::io::Write, name: &str) -> anyhow::Result<()> {
+    write_json(output, "chip info", &chip_info(name)?)
+}
+
 #[test]
 fn single_chip_output() {
     let mut buff = Vec::new();
@@ -96,3 +447,43 @@ fn multiple_chip_output() {
 
     insta::assert_snapshot!(error.to_string());
 }
+
+#[test]
+fn single_chip_json_output() {
+    let mut buff = Vec::new();
+    print_chip_info_json(&mut buff, "nrf52840_xxaa").unwrap();
+
+    let output = String::from_utf8(buff).unwrap();
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn chip_search_output() {
+    let mut buff = Vec::new();
+    print_search_results(&mut buff, "nrf52840", 5).unwrap();
+
+    let output = String::from_utf8(buff).unwrap();
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn riscv_chip_output() {
+    let mut buff = Vec::new();
+    print_chip_info(&mut buff, "esp32c3").unwrap();
+
+    let output = String::from_utf8(buff).unwrap();
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn xtensa_chip_output() {
+    let mut buff = Vec::new();
+    print_chip_info(&mut buff, "esp32s3").unwrap();
+
+    let output = String::from_utf8(buff).unwrap();
+
+    insta::assert_snapshot!(output);
+}
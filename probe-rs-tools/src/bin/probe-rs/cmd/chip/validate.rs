@@ -0,0 +1,552 @@
+//! Checks a target description against the hardware, to find the parts of it which are wrong
+//! before it is added to probe-rs.
+
+use std::ops::Range;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context};
+use probe_rs::config::MemoryRegion;
+use probe_rs::flashing::DownloadOptions;
+use probe_rs::probe::list::Lister;
+use probe_rs::{MemoryInterface, Session, Target};
+use probe_rs_target::{FlashProperties, RawFlashAlgorithm};
+use serde::Serialize;
+
+use super::memory_kind;
+use crate::util::common_options::ProbeOptions;
+use crate::util::output::{write_json, OutputFormat};
+use crate::util::parse_u64;
+
+/// The timeout for halting the cores.
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The pattern written to RAM to check that it's writable.
+const RAM_PATTERN: u32 = 0xA55A_5AA5;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    /// The name of the target to validate.
+    ///
+    /// Use `--chip-description-path` to validate a target description which is not built into
+    /// probe-rs.
+    target: String,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The address of the flash sector which is erased, programmed and verified to test the
+    /// flash algorithm. Its contents are restored afterwards.
+    ///
+    /// Defaults to the last sector of the first flash region.
+    #[clap(long, value_parser = parse_u64)]
+    scratch_address: Option<u64>,
+
+    /// Don't erase or program the flash.
+    #[clap(long, conflicts_with = "scratch_address")]
+    skip_flash: bool,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
+        let mut report = Report {
+            target: self.target.clone(),
+            checks: vec![],
+        };
+
+        let mut common = self.common;
+        common.chip = Some(self.target.clone());
+        let session = report.check(
+            "Attach to the target",
+            "the debug sequence, or `cores[].core_access_options`",
+            common.simple_attach(lister).map_err(Into::into),
+        );
+
+        if let Some((mut session, _probe_options)) = session {
+            validate_cores(&mut session, &mut report);
+            validate_memory(&mut session, &mut report);
+            if self.skip_flash {
+                report.skip("Erase, program and verify a flash sector", "--skip-flash");
+            } else {
+                validate_flash(&mut session, self.scratch_address, &mut report);
+            }
+        }
+
+        let output = std::io::stdout().lock();
+        match output_format {
+            OutputFormat::Text => report.print(output)?,
+            OutputFormat::Json => write_json(output, "chip validation", &report)?,
+        }
+
+        let failures = report.failures();
+        if failures > 0 {
+            bail!("{failures} checks of {} failed.", self.target);
+        }
+
+        Ok(())
+    }
+}
+
+/// The outcome of the checks of a target.
+#[derive(Serialize)]
+struct Report {
+    target: String,
+    checks: Vec<Check>,
+}
+
+#[derive(Serialize)]
+struct Check {
+    name: String,
+    outcome: Outcome,
+    /// The error, or why the check was skipped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+    /// The part of the target description which is probably wrong if the check failed.
+    suspect: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+impl Report {
+    /// Records the result of a check, and returns its value if it passed.
+    fn check<T>(
+        &mut self,
+        name: impl Into<String>,
+        suspect: impl Into<String>,
+        result: anyhow::Result<T>,
+    ) -> Option<T> {
+        let (outcome, details, value) = match result {
+            Ok(value) => (Outcome::Passed, None, Some(value)),
+            Err(error) => (Outcome::Failed, Some(format!("{error:#}")), None),
+        };
+        self.checks.push(Check {
+            name: name.into(),
+            outcome,
+            details,
+            suspect: suspect.into(),
+        });
+
+        value
+    }
+
+    fn skip(&mut self, name: impl Into<String>, reason: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.into(),
+            outcome: Outcome::Skipped,
+            details: Some(reason.into()),
+            suspect: String::new(),
+        });
+    }
+
+    fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.outcome == Outcome::Failed)
+            .count()
+    }
+
+    fn print(&self, mut output: impl std::io::Write) -> anyhow::Result<()> {
+        writeln!(output, "Validating {}", self.target)?;
+        for check in &self.checks {
+            let outcome = match check.outcome {
+                Outcome::Passed => " ok ",
+                Outcome::Failed => "FAIL",
+                Outcome::Skipped => "skip",
+            };
+            writeln!(output, "[{outcome}] {}", check.name)?;
+            if let Some(details) = &check.details {
+                writeln!(output, "       {details}")?;
+            }
+            if check.outcome == Outcome::Failed {
+                writeln!(output, "       Check {} of the target.", check.suspect)?;
+            }
+        }
+
+        let failures = self.failures();
+        if failures == 0 {
+            writeln!(output, "The target description matches the hardware.")?;
+        } else {
+            writeln!(output, "{failures} of {} checks failed.", self.checks.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Halts and resets all cores.
+fn validate_cores(session: &mut Session, report: &mut Report) {
+    let cores: Vec<_> = session
+        .target()
+        .cores
+        .iter()
+        .map(|core| core.name.clone())
+        .collect();
+
+    for (index, name) in cores.iter().enumerate() {
+        let access = format!("`cores[{index}].core_access_options`");
+        let halted = report.check(format!("Halt core {name}"), &access, halt(session, index));
+        if halted.is_none() {
+            report.skip(format!("Reset core {name}"), "The core can't be halted");
+            continue;
+        }
+
+        let reset_sequence = format!("the reset of the debug sequence, or {access}");
+        report.check(
+            format!("Reset core {name}"),
+            &reset_sequence,
+            reset(session, index),
+        );
+        report.check(
+            format!("Reset and halt core {name}"),
+            &reset_sequence,
+            reset_and_halt(session, index),
+        );
+    }
+}
+
+fn halt(session: &mut Session, core: usize) -> anyhow::Result<()> {
+    session.core(core)?.halt(TIMEOUT)?;
+    Ok(())
+}
+
+fn reset(session: &mut Session, core: usize) -> anyhow::Result<()> {
+    let mut core = session.core(core)?;
+    core.reset()?;
+    thread::sleep(Duration::from_millis(100));
+
+    let status = core.status()?;
+    core.halt(TIMEOUT)?;
+    ensure!(
+        !status.is_halted(),
+        "The core is halted after the reset instead of running: {status:?}"
+    );
+
+    Ok(())
+}
+
+fn reset_and_halt(session: &mut Session, core: usize) -> anyhow::Result<()> {
+    let mut core = session.core(core)?;
+    core.reset_and_halt(TIMEOUT)?;
+    ensure!(
+        core.core_halted()?,
+        "The core is not halted after the reset."
+    );
+
+    Ok(())
+}
+
+/// Reads the first and the last word of all memory regions, and writes them in RAM.
+fn validate_memory(session: &mut Session, report: &mut Report) {
+    let regions = session.target().memory_map.clone();
+
+    for (index, region) in regions.iter().enumerate() {
+        let range = region.address_range();
+        let description = format!("{} region {range:#010x?}", memory_kind(region));
+        let (access, is_ram) = match region {
+            MemoryRegion::Ram(region) => (region.access(), true),
+            MemoryRegion::Generic(region) => (region.access(), false),
+            MemoryRegion::Nvm(region) => (region.access(), false),
+        };
+        if !access.read {
+            report.skip(format!("Read {description}"), "The region is not readable");
+            continue;
+        }
+
+        let core = region.cores().first().and_then(|name| {
+            session
+                .target()
+                .cores
+                .iter()
+                .position(|core| &core.name == name)
+        });
+        let Some(core) = core else {
+            report.check::<()>(
+                format!("Read {description}"),
+                format!("`memory_map[{index}].cores`"),
+                Err(anyhow::anyhow!(
+                    "No core of the target can access the region"
+                )),
+            );
+            continue;
+        };
+
+        let last_word = range.end.saturating_sub(4).max(range.start) & !3;
+        report.check(
+            format!("Read the start of the {description}"),
+            format!("`memory_map[{index}].range.start`"),
+            read_word(session, core, range.start),
+        );
+        report.check(
+            format!("Read the end of the {description}"),
+            format!("`memory_map[{index}].range.end`"),
+            read_word(session, core, last_word),
+        );
+
+        if is_ram && access.write {
+            report.check(
+                format!("Write the start and the end of the {description}"),
+                format!("`memory_map[{index}]`, which may not be RAM"),
+                write_word(session, core, range.start)
+                    .and_then(|_| write_word(session, core, last_word)),
+            );
+        }
+    }
+}
+
+fn read_word(session: &mut Session, core: usize, address: u64) -> anyhow::Result<()> {
+    session
+        .core(core)?
+        .read_word_32(address & !3)
+        .with_context(|| format!("Failed to read {address:#010x}"))?;
+    Ok(())
+}
+
+/// Writes a word of RAM and reads it back, then restores it.
+fn write_word(session: &mut Session, core: usize, address: u64) -> anyhow::Result<()> {
+    let mut core = session.core(core)?;
+    let address = address & !3;
+
+    let original = core.read_word_32(address)?;
+    core.write_word_32(address, RAM_PATTERN)?;
+    let read_back = core.read_word_32(address)?;
+    core.write_word_32(address, original)?;
+
+    ensure!(
+        read_back == RAM_PATTERN,
+        "Wrote {RAM_PATTERN:#010x} to {address:#010x}, but read back {read_back:#010x}"
+    );
+
+    Ok(())
+}
+
+/// Erases, programs and verifies a flash sector, and restores its contents.
+fn validate_flash(session: &mut Session, scratch_address: Option<u64>, report: &mut Report) {
+    let scratch = report.check(
+        "Find the scratch flash sector",
+        "`memory_map` and `flash_algorithms[].flash_properties.sectors`",
+        scratch_sector(session.target(), scratch_address),
+    );
+    let Some((algorithm, core, sector)) = scratch else {
+        return;
+    };
+
+    report.check(
+        format!("Erase, program and verify the flash sector {sector:#010x?}"),
+        format!("the flash algorithm `{algorithm}` and its page size and sectors"),
+        program_and_verify(session, core, sector),
+    );
+}
+
+/// Returns the flash algorithm, the core and the sector to test the flash with.
+fn scratch_sector(
+    target: &Target,
+    address: Option<u64>,
+) -> anyhow::Result<(String, usize, Range<u64>)> {
+    let mut regions = target
+        .memory_map
+        .iter()
+        .filter_map(MemoryRegion::as_nvm_region)
+        .filter(|region| !region.is_alias);
+    let region = match address {
+        Some(address) => regions
+            .find(|region| region.range.contains(&address))
+            .with_context(|| format!("{address:#010x} is not in a flash region"))?,
+        None => regions.next().context("The target has no flash region")?,
+    };
+
+    let algorithm = flash_algorithm_for(target, &region.range)?;
+    let address = address.unwrap_or(region.range.end - 1);
+    let sector = sector_at(&algorithm.flash_properties, address).with_context(|| {
+        format!(
+            "The sectors of the flash algorithm `{}` don't cover {address:#010x}",
+            algorithm.name
+        )
+    })?;
+    ensure!(
+        region.range.start <= sector.start && sector.end <= region.range.end,
+        "The sector {sector:#010x?} is not in the flash region {:#010x?}",
+        region.range
+    );
+
+    let core = region
+        .cores
+        .first()
+        .and_then(|name| target.cores.iter().position(|core| &core.name == name))
+        .context("No core of the target can access the flash region")?;
+
+    Ok((algorithm.name.clone(), core, sector))
+}
+
+/// Returns the flash algorithm which covers `range`, preferring the default algorithms.
+fn flash_algorithm_for<'a>(
+    target: &'a Target,
+    range: &Range<u64>,
+) -> anyhow::Result<&'a RawFlashAlgorithm> {
+    let mut algorithms = target.flash_algorithms.iter().filter(|algorithm| {
+        let flash = &algorithm.flash_properties.address_range;
+        flash.start <= range.start && range.end <= flash.end
+    });
+    let first = algorithms
+        .next()
+        .with_context(|| format!("No flash algorithm covers the flash region {range:#010x?}"))?;
+
+    Ok(if first.default {
+        first
+    } else {
+        algorithms
+            .find(|algorithm| algorithm.default)
+            .unwrap_or(first)
+    })
+}
+
+/// Returns the flash sector which contains `address`.
+fn sector_at(properties: &FlashProperties, address: u64) -> Option<Range<u64>> {
+    let flash = &properties.address_range;
+    if !flash.contains(&address) {
+        return None;
+    }
+
+    // The sizes apply from the offset of a description up to the next one.
+    let offset = address - flash.start;
+    let description = properties
+        .sectors
+        .iter()
+        .rev()
+        .find(|sector| sector.address <= offset && sector.size > 0)?;
+    let index = (offset - description.address) / description.size;
+    let start = flash.start + description.address + index * description.size;
+
+    Some(start..start + description.size)
+}
+
+/// Programs a pattern into the sector, verifies it, and restores the sector.
+fn program_and_verify(
+    session: &mut Session,
+    core: usize,
+    sector: Range<u64>,
+) -> anyhow::Result<()> {
+    let size = (sector.end - sector.start) as usize;
+    let mut original = vec![0; size];
+    session
+        .core(core)?
+        .read(sector.start, &mut original)
+        .context("Failed to read the sector")?;
+
+    // The length of the pattern is prime, so that it doesn't repeat with the pages.
+    let pattern: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+    let result = program(session, sector.start, &pattern).and_then(|_| {
+        let mut read_back = vec![0; size];
+        session.core(core)?.read(sector.start, &mut read_back)?;
+        if let Some(offset) = read_back.iter().zip(&pattern).position(|(a, b)| a != b) {
+            bail!(
+                "The byte at {:#010x} is {:#04x} instead of {:#04x} after programming",
+                sector.start + offset as u64,
+                read_back[offset],
+                pattern[offset]
+            );
+        }
+        Ok(())
+    });
+
+    // Restore the sector even if the test failed.
+    let restored =
+        program(session, sector.start, &original).context("Failed to restore the sector");
+
+    result.and(restored)
+}
+
+fn program(session: &mut Session, address: u64, data: &[u8]) -> anyhow::Result<()> {
+    let mut loader = session.target().flash_loader();
+    loader.add_data(address, data)?;
+
+    let mut options = DownloadOptions::default();
+    options.verify = true;
+    loader.commit(session, options)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use probe_rs_target::SectorDescription;
+
+    use super::*;
+
+    fn properties() -> FlashProperties {
+        FlashProperties {
+            address_range: 0x0800_0000..0x0810_0000,
+            sectors: vec![
+                SectorDescription {
+                    address: 0,
+                    size: 0x4000,
+                },
+                SectorDescription {
+                    address: 0x1_0000,
+                    size: 0x1_0000,
+                },
+                SectorDescription {
+                    address: 0x2_0000,
+                    size: 0x2_0000,
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sectors_are_found_in_each_description() {
+        let properties = properties();
+
+        assert_eq!(
+            sector_at(&properties, 0x0800_4001),
+            Some(0x0800_4000..0x0800_8000)
+        );
+        assert_eq!(
+            sector_at(&properties, 0x0801_ffff),
+            Some(0x0801_0000..0x0802_0000)
+        );
+        assert_eq!(
+            sector_at(&properties, 0x080f_ffff),
+            Some(0x080e_0000..0x0810_0000)
+        );
+        assert_eq!(sector_at(&properties, 0x0810_0000), None);
+    }
+
+    #[test]
+    fn failed_checks_name_the_suspect() {
+        let mut report = Report {
+            target: "nRF52840_xxAA".to_string(),
+            checks: vec![],
+        };
+        report.check("Halt core main", "`cores[0].core_access_options`", Ok(()));
+        report.check::<()>(
+            "Read the end of the RAM region",
+            "`memory_map[1].range.end`",
+            Err(anyhow::anyhow!("Failed to read 0x2004fffc")),
+        );
+        report.skip("Erase, program and verify a flash sector", "--skip-flash");
+
+        let mut output = vec![];
+        report.print(&mut output).unwrap();
+
+        assert_eq!(report.failures(), 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Validating nRF52840_xxAA
+[ ok ] Halt core main
+[FAIL] Read the end of the RAM region
+       Failed to read 0x2004fffc
+       Check `memory_map[1].range.end` of the target.
+[skip] Erase, program and verify a flash sector
+       --skip-flash
+1 of 3 checks failed.
+"
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use addr2line::Loader;
+use anyhow::{anyhow, Context};
+use probe_rs::architecture::arm::{
+    component::{find_component, MicroTraceBuffer},
+    memory::PeripheralType,
+    DpAddress,
+};
+use probe_rs::probe::list::Lister;
+use probe_rs::{MemoryInterface, Session};
+use signal_hook::consts::signal;
+
+use crate::util::{common_options::ProbeOptions, parse_u32, parse_u64, resolve_location};
+use crate::CoreOptions;
+
+/// The executed lines of each source file, with how often they were executed.
+type LineHits = BTreeMap<String, BTreeMap<u32, u64>>;
+
+/// The size of an MTB packet, the source and the destination address of a branch.
+const MTB_PACKET_SIZE: u32 = 8;
+
+/// Longer ranges between two branches are treated as corrupted trace.
+const MAX_TRACED_RANGE: u64 = 0x1_0000;
+
+/// Record the code coverage of a run of the firmware
+///
+/// e.g. probe-rs coverage --chip nRF52840_xxAA --elf firmware.elf --until test_done
+///      Resets the target, single-steps the firmware until it reaches `test_done`, and writes
+///      the executed lines to `lcov.info`.
+///
+/// Every executed instruction is recorded, so the run is about a thousand times slower than
+/// on the target. It is bounded with `--until` and `--max-steps`, or can be stopped with Ctrl-C.
+/// The coverage is written in the LCOV format, e.g. for `genhtml`.
+///
+/// e.g. probe-rs coverage --chip ATSAMD21G18A --elf firmware.elf --mtb 0x20007c00 --until test_done
+///      Records the run with the Micro Trace Buffer of a Cortex-M0+ core instead. The firmware
+///      runs at full speed, and is only halted when the trace buffer has to be read. The buffer
+///      is in the SRAM of the target, so the firmware must not use it.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// The ELF file with the debug info of the firmware.
+    #[clap(long)]
+    elf: PathBuf,
+
+    /// Stop when the core reaches this address or symbol.
+    #[clap(long)]
+    until: Option<String>,
+
+    /// Stop after this many instructions, when single-stepping.
+    #[clap(long, default_value_t = 1_000_000)]
+    max_steps: u64,
+
+    /// Record the run with the Micro Trace Buffer, which writes to the SRAM at this address.
+    #[clap(long, value_parser = parse_u64)]
+    mtb: Option<u64>,
+
+    /// The size of the MTB trace buffer in bytes, a power of two.
+    #[clap(long, value_parser = parse_u32, default_value = "512")]
+    mtb_size: u32,
+
+    /// Record the running firmware from where it is, instead of resetting it.
+    #[clap(long)]
+    no_reset: bool,
+
+    /// The LCOV file the coverage is written to.
+    #[clap(long, default_value = "lcov.info")]
+    output: PathBuf,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let elf = std::fs::read(&self.elf)?;
+        let until = self
+            .until
+            .as_deref()
+            .map(|until| resolve_location(until, Some(&elf)))
+            .transpose()?;
+        let loader = Loader::new(&self.elf).map_err(|error| {
+            anyhow!(
+                "Failed to read the debug info of {}: {error}",
+                self.elf.display()
+            )
+        })?;
+
+        let core_index = self.shared.core;
+        let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
+        let mut core = session.core(core_index)?;
+        let timeout = Duration::from_millis(500);
+
+        let pc = if self.no_reset {
+            core.halt(timeout)?.pc
+        } else {
+            core.reset_and_halt(timeout)?.pc
+        };
+        drop(core);
+
+        // Stop on Ctrl-C, so the coverage so far is written.
+        let stop = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal::SIGINT, stop.clone())?;
+
+        let (executed, recorded) = match self.mtb {
+            Some(buffer) => {
+                let (executed, branches) = trace_with_mtb(
+                    &mut session,
+                    core_index,
+                    buffer,
+                    self.mtb_size,
+                    pc,
+                    until,
+                    &stop,
+                )?;
+                (executed, format!("{branches} branches"))
+            }
+            None => {
+                let mut core = session.core(core_index)?;
+                let mut pc = pc;
+                let mut executed = BTreeMap::new();
+                let mut steps = 0;
+                while steps < self.max_steps && Some(pc) != until && !stop.load(Ordering::Relaxed) {
+                    *executed.entry(pc).or_insert(0) += 1;
+                    pc = core.step()?.pc;
+                    steps += 1;
+                }
+                core.run()?;
+                (executed, format!("{steps} steps"))
+            }
+        };
+
+        let rows = loader
+            .find_location_range(0, u64::MAX)
+            .map_err(|error| anyhow!("Failed to read the line table: {error}"))?
+            .filter_map(|(address, size, location)| {
+                Some((address, size, location.file?, location.line?))
+            });
+        let hits = line_hits(rows, &executed);
+        std::fs::write(&self.output, lcov(&hits))?;
+
+        let lines = hits.values().map(BTreeMap::len).sum::<usize>();
+        let covered = hits
+            .values()
+            .flat_map(BTreeMap::values)
+            .filter(|&&count| count > 0)
+            .count();
+        println!(
+            "Covered {covered} of {lines} lines ({:.1}%) in {recorded}, written to {}",
+            covered as f64 * 100.0 / lines.max(1) as f64,
+            self.output.display()
+        );
+
+        Ok(())
+    }
+}
+
+/// Runs the firmware from `pc` with the MTB enabled, and returns the executed addresses and
+/// the number of recorded branches.
+///
+/// The MTB halts the core before the trace buffer is full. The trace is then read, and the
+/// core resumed, until it halts for another reason, reaches `until`, or `stop` is set.
+fn trace_with_mtb(
+    session: &mut Session,
+    core_index: usize,
+    buffer: u64,
+    size: u32,
+    mut pc: u64,
+    until: Option<u64>,
+    stop: &AtomicBool,
+) -> anyhow::Result<(BTreeMap<u64, u64>, usize)> {
+    anyhow::ensure!(size >= 64, "The MTB buffer has to be at least 64 bytes");
+
+    let components = session.get_arm_components(DpAddress::Default)?;
+    let component = find_component(&components, PeripheralType::Mtb)
+        .context("The target has no Micro Trace Buffer")?;
+
+    let mut mtb = MicroTraceBuffer::new(session.get_arm_interface()?, component);
+    let offset = buffer
+        .checked_sub(u64::from(mtb.sram_base()?))
+        .and_then(|offset| u32::try_from(offset).ok())
+        .context("The MTB buffer is not in the SRAM of the target")?;
+    // Leave room for the branches which are recorded while the core halts.
+    let watermark = offset + size - 2 * MTB_PACKET_SIZE;
+    mtb.enable(offset, size, Some(watermark))?;
+
+    if let Some(until) = until {
+        session.core(core_index)?.set_hw_breakpoint(until)?;
+    }
+
+    let mut executed = BTreeMap::new();
+    let mut branches = 0;
+    loop {
+        let start = pc;
+        let mut core = session.core(core_index)?;
+        core.run()?;
+        while !core.core_halted()? {
+            if stop.load(Ordering::Relaxed) {
+                core.halt(Duration::from_millis(500))?;
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let program_counter = core.program_counter();
+        pc = core.read_core_reg(program_counter)?;
+        drop(core);
+
+        let mut mtb = MicroTraceBuffer::new(session.get_arm_interface()?, component);
+        let (position, wrapped) = mtb.position()?;
+        mtb.set_position(offset)?;
+
+        let mut core = session.core(core_index)?;
+        let mut words = vec![0; (size / 4) as usize];
+        core.read_32(buffer, &mut words)?;
+        let packets = if wrapped {
+            tracing::warn!("The MTB trace buffer overflowed, some executed code is missing");
+            words.rotate_left(((position - offset) / 4) as usize);
+            &words[..]
+        } else {
+            &words[..((position - offset) / 4) as usize]
+        };
+        branches += packets.len() / 2;
+        add_mtb_trace(packets, (!wrapped).then_some(start), pc, &mut executed);
+
+        let full = wrapped || position >= watermark;
+        if !full || Some(pc) == until || stop.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+
+    if let Some(until) = until {
+        session.core(core_index)?.clear_hw_breakpoint(until)?;
+    }
+    MicroTraceBuffer::new(session.get_arm_interface()?, component).disable()?;
+    session.core(core_index)?.run()?;
+
+    Ok((executed, branches))
+}
+
+/// Adds the code executed between `start`, where the core was resumed, and `end`, where it
+/// halted, to `executed`. `packets` are the words of the MTB packets recorded in between.
+///
+/// Every halfword of the executed ranges is counted, so each instruction is counted at its
+/// address. `start` is `None` if the beginning of the trace was lost.
+fn add_mtb_trace(packets: &[u32], start: Option<u64>, end: u64, executed: &mut BTreeMap<u64, u64>) {
+    let mut add_range = |from: Option<u64>, to: u64| {
+        let Some(from) = from else {
+            return;
+        };
+        if to <= from || to - from > MAX_TRACED_RANGE {
+            return;
+        }
+        for address in (from..to).step_by(2) {
+            *executed.entry(address).or_insert(0) += 1;
+        }
+    };
+
+    let mut from = start;
+    for packet in packets.chunks_exact(2) {
+        let source = u64::from(packet[0] & !1);
+        let destination = u64::from(packet[1] & !1);
+
+        // The branch instruction at the source was executed, unless the packet is for an
+        // exception entry (A-bit), where the source is the next instruction to execute.
+        let exception = packet[0] & 1 == 1;
+        add_range(from, if exception { source } else { source + 2 });
+
+        from = Some(destination);
+    }
+    add_range(from, end);
+}
+
+/// Counts how often the lines of the line table rows were executed.
+///
+/// A line is executed as often as the most executed of its instructions. Lines without
+/// executed instructions are included with zero hits.
+fn line_hits<'a>(
+    rows: impl Iterator<Item = (u64, u64, &'a str, u32)>,
+    executed: &BTreeMap<u64, u64>,
+) -> LineHits {
+    let mut hits = LineHits::new();
+    for (address, size, file, line) in rows {
+        let count = executed
+            .range(address..address + size)
+            .map(|(_, count)| *count)
+            .max()
+            .unwrap_or_default();
+
+        let line_count = hits
+            .entry(file.to_string())
+            .or_default()
+            .entry(line)
+            .or_default();
+        *line_count = (*line_count).max(count);
+    }
+
+    hits
+}
+
+/// Formats the line hits as LCOV tracefile.
+fn lcov(hits: &LineHits) -> String {
+    let mut output = String::from("TN:\n");
+    for (file, lines) in hits {
+        let covered = lines.values().filter(|&&count| count > 0).count();
+
+        // Writing to a string can't fail.
+        let _ = writeln!(output, "SF:{file}");
+        for (line, count) in lines {
+            let _ = writeln!(output, "DA:{line},{count}");
+        }
+        let _ = writeln!(output, "LF:{}", lines.len());
+        let _ = writeln!(output, "LH:{covered}");
+        output.push_str("end_of_record\n");
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn executed_lines_are_written_as_lcov() {
+        let rows = [
+            (0x100, 4, "main.rs", 3),
+            (0x104, 2, "main.rs", 4),
+            (0x106, 2, "main.rs", 3),
+            (0x200, 2, "lib.rs", 10),
+        ];
+        let executed = BTreeMap::from([(0x100, 1), (0x102, 1), (0x106, 2)]);
+
+        let hits = line_hits(rows.into_iter(), &executed);
+
+        assert_eq!(
+            lcov(&hits),
+            "TN:\n\
+             SF:lib.rs\nDA:10,0\nLF:1\nLH:0\nend_of_record\n\
+             SF:main.rs\nDA:3,2\nDA:4,0\nLF:2\nLH:1\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn mtb_trace_is_mapped_to_executed_ranges() {
+        // A branch from 0x104 to 0x200, with the S-bit of the first packet, an exception entry
+        // at 0x204, with the A-bit, and the return from the handler at 0x300.
+        let packets = [0x104, 0x201, 0x205, 0x300, 0x302, 0x204];
+        let mut executed = BTreeMap::new();
+
+        add_mtb_trace(&packets, Some(0x100), 0x208, &mut executed);
+
+        assert_eq!(
+            executed.keys().copied().collect::<Vec<_>>(),
+            [0x100, 0x102, 0x104, 0x200, 0x202, 0x204, 0x206, 0x300, 0x302]
+        );
+        assert_eq!(executed[&0x204], 1);
+    }
+
+    #[test]
+    fn mtb_trace_without_start_skips_first_range() {
+        let packets = [0x104, 0x200];
+        let mut executed = BTreeMap::new();
+
+        add_mtb_trace(&packets, None, 0x204, &mut executed);
+
+        assert_eq!(executed.keys().copied().collect::<Vec<_>>(), [0x200, 0x202]);
+    }
+}
@@ -4,7 +4,8 @@ use super::{
     repl_commands_helpers::{build_expanded_commands, command_completions},
     request_helpers::{
         disassemble_target_memory, get_dap_source, get_svd_variable_reference,
-        get_variable_reference, set_instruction_breakpoint,
+        get_variable_reference, parse_register_value, register_value_string,
+        set_instruction_breakpoint,
     },
 };
 use crate::cmd::dap_server::{
@@ -31,12 +32,12 @@ use probe_rs::{
         VariableName, VerifiedBreakpoint,
     },
     Architecture::Riscv,
-    CoreStatus, Error, HaltReason, MemoryInterface, RegisterValue,
+    CoreStatus, Error, HaltReason, InterruptMasking, MemoryInterface, RegisterValue,
 };
 use serde::{de::DeserializeOwned, Serialize};
 use typed_path::NativePathBuf;
 
-use std::{fmt::Display, str, time::Duration};
+use std::{collections::VecDeque, fmt::Display, str, time::Duration};
 
 /// Progress ID used for progress reporting when the debug adapter protocol is used.
 type ProgressId = i64;
@@ -45,6 +46,8 @@ type ProgressId = i64;
 /// see <https://microsoft.github.io/debug-adapter-protocol/overview>
 pub struct DebugAdapter<P: ProtocolAdapter> {
     pub(crate) halt_after_reset: bool,
+    /// Whether interrupts are taken while single stepping.
+    pub(crate) step_interrupts: InterruptMasking,
     /// NOTE: VSCode sends a 'threads' request when it receives the response from the `ConfigurationDone` request, irrespective of target state.
     /// This can lead to duplicate `threads->stacktrace->etc.` sequences if & when the target halts and sends a 'stopped' event.
     /// See <https://github.com/golang/vscode-go/issues/940> for more info.
@@ -73,6 +76,9 @@ pub struct DebugAdapter<P: ProtocolAdapter> {
     /// Flag to indicate that workarounds for VSCode-specific spec deviations etc. should be
     /// enabled.
     pub(crate) vscode_quirks: bool,
+    /// Requests which were received while checking for cancellation of a long-running operation,
+    /// and which are handled after the operation is done.
+    deferred_requests: VecDeque<Request>,
     adapter: P,
 }
 
@@ -81,12 +87,14 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         DebugAdapter {
             vscode_quirks: false,
             halt_after_reset: false,
+            step_interrupts: InterruptMasking::Masked,
             configuration_done: false,
             all_cores_halted: true,
             progress_id: 0,
             supports_progress_reporting: false,
             lines_start_at_1: true,
             columns_start_at_1: true,
+            deferred_requests: VecDeque::new(),
             adapter,
         }
     }
@@ -159,7 +167,7 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         let arguments: ReadMemoryArguments = get_arguments(self, request)?;
 
         let memory_offset = arguments.offset.unwrap_or(0);
-        let mut address: u64 = match parse::<u64>(arguments.memory_reference.as_ref()) {
+        let start_address: u64 = match parse::<u64>(arguments.memory_reference.as_ref()) {
             Ok(address) => address + memory_offset as u64,
             Err(err) => {
                 return self.send_response::<()>(
@@ -171,6 +179,7 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
                 );
             }
         };
+        let mut address = start_address;
         let mut num_bytes_unread = arguments.count as usize;
         // The probe-rs API does not return partially read data.
         // It either succeeds for the whole buffer or not. However, doing single byte reads is slow, so we will
@@ -206,7 +215,7 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
             self.send_response(
                 request,
                 Ok(Some(ReadMemoryResponseBody {
-                    address: format!("{address:#010x}"),
+                    address: format!("{start_address:#010x}"),
                     data: Some(response),
                     unreadable_bytes: if num_bytes_unread == 0 {
                         None
@@ -432,13 +441,13 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
                     }
                 {
                     // Always search the registers first, because we don't have a VariableCache for them.
-                    if let Some(register_value) = stack_frame
+                    if let Some(register) = stack_frame
                         .registers
                         .get_register_by_name(expression.as_str())
-                        .and_then(|reg| reg.value)
+                        .filter(|reg| reg.value.is_some())
                     {
                         response_body.type_ = Some(format!("{}", VariableName::RegistersRoot));
-                        response_body.result = format!("{register_value}");
+                        response_body.result = register_value_string(&register);
                     } else {
                         // If the expression wasn't pointing to a register, then check if is a local or static variable in our stack_frame
                         let mut variable: Option<probe_rs::debug::Variable> = None;
@@ -561,7 +570,7 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         };
 
         // The arguments.variables_reference contains the reference of the variable container. This can be:
-        // - The `StackFrame.id` for register variables - we will warn the user that updating these are not yet supported.
+        // - The `StackFrame.id` for register variables - only the registers of the current frame can be updated.
         // - The `Variable.parent_key` for a local or static variable - If these are base data types, we will attempt to update their value, otherwise we will warn the user that updating complex / structure variables are not yet supported.
         let parent_key: ObjectRef = arguments.variables_reference.into();
         let new_value = &arguments.value;
@@ -572,22 +581,44 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
             .core_data
             .stack_frames
             .iter_mut()
-            .find(|stack_frame| stack_frame.id == parent_key)
+            .position(|stack_frame| stack_frame.id == parent_key)
         {
-            Some(stack_frame) => {
-                // The variable is a register value in this StackFrame
-                if let Some(_register_value) = stack_frame
-                    .registers
-                    .get_register_by_name(arguments.name.as_str())
-                    .and_then(|reg| reg.value)
-                {
-                    // TODO: Does it make sense for us to consider implementing an update of platform registers?
-                    return self.send_response::<SetVariableResponseBody>(
-                        request,
-                        Err(&DebuggerError::Other(anyhow!(
-                            "Set Register values is not yet supported."
-                        ))),
-                    );
+            Some(frame_index) => {
+                // The variable is a register value in this StackFrame. The registers of the
+                // other frames are unwound, so only the ones of the current frame can be written.
+                let stack_frame = &mut target_core.core_data.stack_frames[frame_index];
+                if let Some(register) = stack_frame.registers.0.iter_mut().find(|register| {
+                    register.get_register_name() == arguments.name
+                        || register.core_register.name() == arguments.name
+                }) {
+                    if frame_index != 0 {
+                        return self.send_response::<SetVariableResponseBody>(
+                            request,
+                            Err(&DebuggerError::UserMessage(
+                                "Only the registers of the current frame can be set.".to_string(),
+                            )),
+                        );
+                    }
+
+                    let result =
+                        parse_register_value(register.core_register.data_type(), new_value)
+                            .and_then(|value| {
+                                target_core
+                                    .core
+                                    .write_core_reg(register.core_register.id(), value)?;
+                                Ok(value)
+                            });
+                    match result {
+                        Ok(value) => {
+                            register.value = Some(value);
+                            response_body.value = register_value_string(register);
+                            response_body.type_ = Some(format!("{}", VariableName::RegistersRoot));
+                        }
+                        Err(error) => {
+                            return self
+                                .send_response::<SetVariableResponseBody>(request, Err(&error));
+                        }
+                    }
                 }
             }
             None => {
@@ -967,6 +998,106 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         self.send_response(request, Ok(Some(instruction_breakpoint_body)))
     }
 
+    /// Find the statement at the requested source location, that the program counter can be moved to.
+    /// - The address of the statement is used as the goto target `id`, so that the `goto` request can validate it again.
+    /// - Only statements in the current function are valid targets. See [`probe_rs::debug::DebugInfo::validate_goto_target`].
+    pub(crate) fn goto_targets(
+        &mut self,
+        target_core: &mut CoreHandle,
+        request: &Request,
+    ) -> Result<()> {
+        let arguments: GotoTargetsArguments = get_arguments(self, request)?;
+
+        let Some(source_path) = arguments.source.path.as_ref() else {
+            return self.send_response::<()>(
+                request,
+                Err(&DebuggerError::Other(anyhow!(
+                    "Could not get a valid source path from arguments: {arguments:?}"
+                ))),
+            );
+        };
+        // Assume that the path is native to the current OS
+        let source_path = NativePathBuf::from(source_path).to_typed_path_buf();
+
+        let requested_line = if self.lines_start_at_1 {
+            arguments.line as u64
+        } else {
+            arguments.line as u64 + 1
+        };
+        let requested_column = arguments.column.map(|column| {
+            if self.columns_start_at_1 {
+                column as u64
+            } else {
+                column as u64 + 1
+            }
+        });
+
+        let program_counter: u64 = target_core
+            .core
+            .read_core_reg(target_core.core.program_counter())?;
+
+        match target_core.core_data.debug_info.get_goto_location(
+            program_counter,
+            &source_path,
+            requested_line,
+            requested_column,
+        ) {
+            Ok(VerifiedBreakpoint {
+                address,
+                source_location,
+            }) => {
+                let line = source_location.line.unwrap_or(requested_line) as i64;
+                self.send_response(
+                    request,
+                    Ok(Some(GotoTargetsResponseBody {
+                        targets: vec![GotoTarget {
+                            id: address as i64,
+                            label: format!("Line {line} ({address:#010X})"),
+                            line,
+                            column: source_location.column.map(|col| match col {
+                                ColumnType::LeftEdge => 0_i64,
+                                ColumnType::Column(c) => c as i64,
+                            }),
+                            end_line: None,
+                            end_column: None,
+                            instruction_pointer_reference: Some(format!("{address:#010X}")),
+                        }],
+                    })),
+                )
+            }
+            Err(error) => self.send_response::<()>(
+                request,
+                Err(&DebuggerError::UserMessage(format!(
+                    "Cannot jump to this location: {error}"
+                ))),
+            ),
+        }
+    }
+
+    /// Move the program counter to a target previously returned by [`DebugAdapter::goto_targets`].
+    pub(crate) fn goto(&mut self, target_core: &mut CoreHandle, request: &Request) -> Result<()> {
+        let arguments: GotoArguments = get_arguments(self, request)?;
+
+        let address = arguments.target_id as u64;
+        if let Err(error) = target_core.goto(address) {
+            return self.send_response::<()>(request, Err(&error));
+        }
+
+        self.send_response::<()>(request, Ok(None))?;
+
+        // The core stays halted, but the client has to refresh the stack frames and variables.
+        let event_body = Some(StoppedEventBody {
+            reason: "goto".to_string(),
+            description: Some(format!("Jumped to {address:#010X}")),
+            thread_id: Some(target_core.core.id() as i64),
+            preserve_focus_hint: None,
+            text: None,
+            all_threads_stopped: Some(self.all_cores_halted),
+            hit_breakpoint_ids: None,
+        });
+        self.send_event("stopped", event_body)
+    }
+
     pub(crate) fn threads(
         &mut self,
         target_core: &mut CoreHandle,
@@ -1455,7 +1586,7 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
                             named_variables: None,
                             presentation_hint: None, // TODO: Implement hint as Hex for registers
                             type_: Some(format!("{}", VariableName::RegistersRoot)),
-                            value: register.value.unwrap_or_default().to_string(),
+                            value: register_value_string(register),
                             variables_reference: 0,
                         })
                         .collect();
@@ -1650,9 +1781,11 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         request: &Request,
     ) -> Result<(), anyhow::Error> {
         target_core.reset_core_status(self);
-        let (new_status, program_counter) = match stepping_granularity
-            .step(&mut target_core.core, &target_core.core_data.debug_info)
-        {
+        let (new_status, program_counter) = match stepping_granularity.step_with(
+            &mut target_core.core,
+            &target_core.core_data.debug_info,
+            self.step_interrupts,
+        ) {
             Ok((new_status, program_counter)) => (new_status, program_counter),
             Err(error) => match &error {
                 probe_rs::debug::DebugError::WarnAndContinue { message } => {
@@ -1703,9 +1836,55 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
     /// Returns one of the standard DAP Requests if all goes well, or a "error" request, which should indicate that the calling function should return.
     /// When preparing to return an "error" request, we will send a Response containing the DebuggerError encountered.
     pub fn listen_for_request(&mut self) -> anyhow::Result<Option<Request>> {
+        if let Some(request) = self.deferred_requests.pop_front() {
+            return Ok(Some(request));
+        }
+
         self.adapter.listen_for_request()
     }
 
+    /// Checks, without blocking, if the client asked to stop the operation which reports progress
+    /// with `progress_id`, and which was started by the request with sequence number `request_id`.
+    ///
+    /// A `cancel` request for the progress or the request is answered here. `disconnect` and
+    /// `terminate` requests also stop the operation, but like all other requests they are kept,
+    /// and returned by [`Self::listen_for_request`] afterwards.
+    pub(crate) fn cancellation_requested(
+        &mut self,
+        progress_id: ProgressId,
+        request_id: i64,
+    ) -> bool {
+        let request = match self.adapter.listen_for_request() {
+            Ok(Some(request)) => request,
+            Ok(None) => return false,
+            Err(error) => {
+                tracing::warn!("Failed to check for cancellation: {error:?}");
+                return false;
+            }
+        };
+
+        match request.command.as_str() {
+            "cancel" => {
+                let arguments: CancelArguments = match get_arguments(self, &request) {
+                    Ok(arguments) => arguments,
+                    Err(_) => return false,
+                };
+                let cancelled = arguments.progress_id == Some(progress_id.to_string())
+                    || arguments.request_id == Some(request_id);
+                self.send_response::<()>(&request, Ok(None)).ok();
+                cancelled
+            }
+            "disconnect" | "terminate" => {
+                self.deferred_requests.push_back(request);
+                true
+            }
+            _ => {
+                self.deferred_requests.push_back(request);
+                false
+            }
+        }
+    }
+
     /// Sends either the success response or an error response if passed a
     /// DebuggerError. For the DAP Client, it forwards the response, while for
     /// the CLI, it will print the body for success, or the message for
@@ -1810,10 +1989,13 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         id
     }
 
+    /// Reports the start of an operation to the client. If `cancellable` is set, the client
+    /// offers to cancel it, see [`Self::cancellation_requested`].
     pub fn start_progress(
         &mut self,
         title: &str,
         request_id: Option<ProgressId>,
+        cancellable: bool,
     ) -> Result<ProgressId> {
         anyhow::ensure!(
             self.supports_progress_reporting,
@@ -1825,7 +2007,7 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         self.send_event(
             "progressStart",
             Some(ProgressStartEventBody {
-                cancellable: Some(false),
+                cancellable: Some(cancellable),
                 message: None,
                 percentage: None,
                 progress_id: progress_id.to_string(),
@@ -11,7 +11,7 @@ use crate::cmd::dap_server::{server::core_data::CoreHandle, DebuggerError};
 use itertools::Itertools;
 use probe_rs::{
     debug::{ObjectRef, VariableName},
-    CoreDump, CoreStatus, HaltReason,
+    CoreDump, CoreSnapshot, CoreStatus, HaltReason,
 };
 use std::{fmt::Display, ops::Range, path::Path, str::FromStr, time::Duration};
 
@@ -130,6 +130,180 @@ pub(crate) static REPL_COMMANDS: &[ReplCommand<ReplHandler>] = &[
             })
         },
     },
+    ReplCommand {
+        command: "snapshot",
+        help_text: "Save the registers and RAM of the core, so you can `rewind` to this point later.",
+        sub_commands: None,
+        args: Some(&[ReplCommandArgs::Optional("name")]),
+        handler: |target_core, command_arguments, _| {
+            let name = match command_arguments.trim() {
+                "" => format!("{}", target_core.core_data.snapshots.len() + 1),
+                name => name.to_string(),
+            };
+
+            let snapshot = CoreSnapshot::take(&mut target_core.core)?;
+            let size: u64 = snapshot
+                .memory_ranges()
+                .map(|range| range.end - range.start)
+                .sum();
+
+            let snapshots = &mut target_core.core_data.snapshots;
+            snapshots.retain(|(existing, _)| *existing != name);
+            snapshots.push((name.clone(), snapshot));
+
+            Ok(Response {
+                command: "snapshot".to_string(),
+                success: true,
+                message: Some(format!(
+                    "Saved snapshot {name:?} with the registers and {size} bytes of RAM."
+                )),
+                type_: "response".to_string(),
+                request_seq: 0,
+                seq: 0,
+                body: None,
+            })
+        },
+    },
+    ReplCommand {
+        command: "rewind",
+        help_text: "Restore the registers and RAM saved by `snapshot`, the most recent snapshot if no name is given. Peripherals are not restored.",
+        sub_commands: None,
+        args: Some(&[ReplCommandArgs::Optional("name")]),
+        handler: |target_core, command_arguments, _| {
+            let name = command_arguments.trim();
+            let snapshots = &target_core.core_data.snapshots;
+            let Some((name, snapshot)) = (if name.is_empty() {
+                snapshots.last()
+            } else {
+                snapshots.iter().find(|(existing, _)| existing == name)
+            }) else {
+                return Err(DebuggerError::UserMessage(format!(
+                    "No snapshot {name:?}. Use the `snapshot` command to save one first."
+                )));
+            };
+
+            let written = snapshot.restore(&mut target_core.core)?;
+            let message = format!("Rewound to snapshot {name:?}, restored {written} bytes of RAM.");
+
+            // The registers changed, so let the debugger synch the client status, like after a step.
+            target_core.core_data.last_known_status = CoreStatus::Running;
+
+            Ok(Response {
+                command: "rewind".to_string(),
+                success: true,
+                message: Some(message),
+                type_: "response".to_string(),
+                request_seq: 0,
+                seq: 0,
+                body: None,
+            })
+        },
+    },
+    ReplCommand {
+        command: "call",
+        help_text: "Call a function on the target, and print the value it returns. Registers are restored afterwards, but memory changes are kept.",
+        sub_commands: None,
+        args: Some(&[
+            ReplCommandArgs::Required("<function name or address>"),
+            ReplCommandArgs::Optional("arguments"),
+        ]),
+        handler: |target_core, command_arguments, _| {
+            let mut input_arguments = command_arguments.split_whitespace();
+            let Some(function) = input_arguments.next() else {
+                return Err(DebuggerError::UserMessage(
+                    "Please provide the name or address of the function to call.".to_string(),
+                ));
+            };
+
+            let address = match parse_int::parse::<u64>(function) {
+                Ok(address) => address,
+                Err(_) => target_core
+                    .core_data
+                    .debug_info
+                    .function_address(function)
+                    .ok_or_else(|| {
+                        DebuggerError::UserMessage(format!(
+                            "No function called {function:?} in the symbol table."
+                        ))
+                    })?,
+            };
+            let arguments = input_arguments
+                .map(|argument| {
+                    parse_int::parse::<u64>(argument).map_err(|error| {
+                        DebuggerError::UserMessage(format!("Invalid argument {argument:?}: {error}"))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let result =
+                target_core
+                    .core
+                    .call_function(address, &arguments, Duration::from_secs(5))?;
+
+            // The function may have changed memory, so let the debugger refresh the client.
+            target_core.core_data.last_known_status = CoreStatus::Running;
+
+            Ok(Response {
+                command: "call".to_string(),
+                success: true,
+                message: Some(format!("{function} returned {result} ({result:#x})")),
+                type_: "response".to_string(),
+                request_seq: 0,
+                seq: 0,
+                body: None,
+            })
+        },
+    },
+    ReplCommand {
+        command: "jump",
+        help_text: "Move the program counter to a line in the current source file, or to an address in the current function. Unlike gdb, the core stays halted; use `c` to resume.",
+        sub_commands: None,
+        args: Some(&[ReplCommandArgs::Required("<line> or *address")]),
+        handler: |target_core, command_arguments, _| {
+            let location = command_arguments.trim();
+            let address = if let Some(address_str) = location.strip_prefix('*') {
+                let MemoryAddress(address) = address_str.try_into()?;
+                address
+            } else {
+                let line = location.parse::<u64>().map_err(|error| {
+                    DebuggerError::UserMessage(format!(
+                        "Invalid line number {location:?}: {error}. See the `help` command for more information."
+                    ))
+                })?;
+                let program_counter: u64 = target_core
+                    .core
+                    .read_core_reg(target_core.core.program_counter())?;
+                let debug_info = &target_core.core_data.debug_info;
+                let Some(source_path) = debug_info
+                    .get_source_location(program_counter)
+                    .and_then(|source_location| source_location.combined_typed_path())
+                else {
+                    return Err(DebuggerError::UserMessage(format!(
+                        "No source information for the program counter {program_counter:#010X}."
+                    )));
+                };
+                debug_info
+                    .get_goto_location(program_counter, &source_path, line, None)
+                    .map_err(|error| DebuggerError::UserMessage(error.to_string()))?
+                    .address
+            };
+
+            target_core.goto(address)?;
+
+            // The registers changed, so let the debugger synch the client status, like after a step.
+            target_core.core_data.last_known_status = CoreStatus::Running;
+
+            Ok(Response {
+                command: "jump".to_string(),
+                success: true,
+                message: Some(format!("Program counter moved to {address:#010X}.")),
+                type_: "response".to_string(),
+                request_seq: 0,
+                seq: 0,
+                body: None,
+            })
+        },
+    },
     ReplCommand {
         command: "break",
         // Stricly speaking, gdb refers to this as an expression, but we only support variables.
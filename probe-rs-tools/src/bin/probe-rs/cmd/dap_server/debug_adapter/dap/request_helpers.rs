@@ -11,8 +11,8 @@ use capstone::{
 };
 use once_cell::sync::Lazy;
 use probe_rs::{
-    debug::{ColumnType, ObjectRef, SourceLocation},
-    CoreType, InstructionSet, MemoryInterface,
+    debug::{ColumnType, DebugRegister, ObjectRef, SourceLocation},
+    CoreType, InstructionSet, MemoryInterface, RegisterDataType, RegisterValue,
 };
 use std::{fmt::Write, time::Duration};
 use typed_path::TypedPathBuf;
@@ -551,3 +551,86 @@ pub(crate) fn set_instruction_breakpoint(
     };
     breakpoint_response
 }
+
+/// Formats the value of a register. Floating point registers are shown as numbers, followed by
+/// their bits.
+pub(crate) fn register_value_string(register: &DebugRegister) -> String {
+    let value = register.value.unwrap_or_default();
+    match (register.core_register.data_type(), value) {
+        (RegisterDataType::FloatingPoint(32), RegisterValue::U32(bits)) => {
+            format!("{} ({value})", f32::from_bits(bits))
+        }
+        (RegisterDataType::FloatingPoint(64), RegisterValue::U64(bits)) => {
+            format!("{} ({value})", f64::from_bits(bits))
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Parses a new value for a register of the type, as an integer like `0x3f800000`. Floating
+/// point registers also accept numbers like `1.5`.
+pub(crate) fn parse_register_value(
+    data_type: RegisterDataType,
+    input: &str,
+) -> Result<RegisterValue, DebuggerError> {
+    let input = input.trim();
+    let (size, float_value) = match data_type {
+        RegisterDataType::FloatingPoint(32) => (
+            32,
+            input
+                .parse::<f32>()
+                .map(|value| value.to_bits().into())
+                .ok(),
+        ),
+        RegisterDataType::FloatingPoint(64) => (
+            64,
+            input
+                .parse::<f64>()
+                .map(|value| value.to_bits().into())
+                .ok(),
+        ),
+        RegisterDataType::FloatingPoint(size) | RegisterDataType::UnsignedInteger(size) => {
+            (size, None)
+        }
+    };
+    if let Some(value) = float_value {
+        return Ok(value);
+    }
+
+    let invalid = || DebuggerError::UserMessage(format!("Invalid register value {input:?}."));
+    let value = parse_int::parse::<u128>(input).map_err(|_| invalid())?;
+    match size {
+        32 => u32::try_from(value)
+            .map(RegisterValue::U32)
+            .map_err(|_| invalid()),
+        64 => u64::try_from(value)
+            .map(RegisterValue::U64)
+            .map_err(|_| invalid()),
+        _ => Ok(RegisterValue::U128(value)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn register_values_are_parsed() {
+        let float = RegisterDataType::FloatingPoint(32);
+        assert_eq!(
+            parse_register_value(float.clone(), "-1.5").unwrap(),
+            RegisterValue::U32(0xbfc0_0000)
+        );
+        assert_eq!(
+            parse_register_value(float.clone(), "0x7fc00000").unwrap(),
+            RegisterValue::U32(0x7fc0_0000)
+        );
+        assert!(parse_register_value(float, "0x1_0000_0000").is_err());
+        assert_eq!(
+            parse_register_value(RegisterDataType::UnsignedInteger(64), "42").unwrap(),
+            RegisterValue::U64(42)
+        );
+    }
+}
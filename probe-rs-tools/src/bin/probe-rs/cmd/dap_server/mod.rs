@@ -15,14 +15,17 @@ use probe_rs::{
     probe::{list::Lister, DebugProbeError},
     CoreDumpError, Error,
 };
-use server::startup::debug;
+use server::startup::{debug, DapEndpoint};
 use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
-    path::Path,
+    path::{Path, PathBuf},
 };
 use time::UtcOffset;
 
-use crate::util::common_options::OperationError;
+use crate::util::{
+    common_options::OperationError,
+    server_address::{check_connection_string, check_listen_address},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum DebuggerError {
@@ -74,8 +77,8 @@ pub enum DebuggerError {
 #[derive(clap::Parser)]
 pub struct Cmd {
     /// IP port number to listen for incoming DAP connections, e.g. "50000"
-    #[clap(long)]
-    port: u16,
+    #[clap(long, required_unless_present = "unix_socket")]
+    port: Option<u16>,
 
     /// IP address to listen for incoming DAP connections, e.g. "127.0.0.1"
     #[clap(long, default_value_t = Ipv4Addr::LOCALHOST.into())]
@@ -89,6 +92,31 @@ pub struct Cmd {
     /// adapter process.
     #[clap(long, alias("vscode"))]
     single_session: bool,
+
+    /// Also start a GDB server for each debug session, on the same probe and target, e.g.
+    /// "localhost:1337".
+    ///
+    /// GDB requests are served while the DAP client is idle, and both clients share the halted
+    /// or running state of the cores.
+    #[clap(long)]
+    gdb_connection_string: Option<String>,
+
+    /// Listen for incoming DAP connections on this Unix domain socket, instead of on an IP port.
+    ///
+    /// Only the current user can connect to the socket. This is only supported on Unix.
+    #[clap(long, conflicts_with_all = ["port", "ip"])]
+    unix_socket: Option<PathBuf>,
+
+    /// Allow listening on addresses which can be reached from other machines.
+    ///
+    /// Without this, only loopback addresses like "127.0.0.1" are accepted, for the DAP server and
+    /// the GDB server.
+    #[clap(long)]
+    allow_remote: bool,
+
+    /// Require DAP clients to send this token as `authToken` in their launch or attach request.
+    #[clap(long, env = "PROBE_RS_DAP_AUTH_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
 }
 
 pub fn run(
@@ -97,6 +125,27 @@ pub fn run(
     time_offset: UtcOffset,
     log_file: Option<&Path>,
 ) -> Result<()> {
-    let addr = SocketAddr::new(cmd.ip, cmd.port);
-    debug(lister, addr, cmd.single_session, log_file, time_offset)
+    let endpoint = match (cmd.unix_socket, cmd.port) {
+        (Some(path), _) => DapEndpoint::Unix(path),
+        (None, Some(port)) => {
+            let addr = SocketAddr::new(cmd.ip, port);
+            check_listen_address(addr, cmd.allow_remote)?;
+            DapEndpoint::Tcp(addr)
+        }
+        (None, None) => anyhow::bail!("Either `--port` or `--unix-socket` is required."),
+    };
+
+    if let Some(gdb_connection_string) = &cmd.gdb_connection_string {
+        check_connection_string(gdb_connection_string, cmd.allow_remote)?;
+    }
+
+    debug(
+        lister,
+        endpoint,
+        cmd.single_session,
+        cmd.gdb_connection_string,
+        cmd.auth_token,
+        log_file,
+        time_offset,
+    )
 }
@@ -31,6 +31,7 @@ impl SvdCache {
         let progress_id = debug_adapter.start_progress(
             format!("Loading SVD file: {}", svd_file.display()).as_str(),
             Some(dap_request_id),
+            false,
         )?;
 
         let _ = svd_opened_file.read_to_string(svd_xml)?;
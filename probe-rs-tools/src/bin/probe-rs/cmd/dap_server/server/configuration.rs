@@ -2,9 +2,15 @@ use crate::util::common_options::ProbeOptions;
 use crate::util::rtt;
 use crate::{cmd::dap_server::DebuggerError, FormatOptions};
 use anyhow::{anyhow, Result};
-use probe_rs::probe::{DebugProbeSelector, WireProtocol};
+use probe_rs::{
+    probe::{bitbang::BitbangProtocol, DebugProbeSelector, WireProtocol},
+    InterruptMasking, VectorCatchCondition,
+};
 use serde::{Deserialize, Serialize};
-use std::{env::current_dir, path::PathBuf};
+use std::{
+    env::current_dir,
+    path::{Path, PathBuf},
+};
 
 use super::startup::TargetSessionType;
 
@@ -35,6 +41,14 @@ pub struct SessionConfig {
     /// Protocol speed in kHz
     pub(crate) speed: Option<u32>,
 
+    /// Let the JTAG clock follow the RTCK signal of the target
+    #[serde(default)]
+    pub(crate) adaptive_clocking: bool,
+
+    /// Lower the protocol speed when transfers keep failing
+    #[serde(default)]
+    pub(crate) speed_fallback: bool,
+
     /// Protocol to use for target connection
     pub(crate) wire_protocol: Option<WireProtocol>,
 
@@ -46,10 +60,17 @@ pub struct SessionConfig {
     #[serde(default)]
     pub(crate) flashing_config: FlashingConfig,
 
+    /// Whether interrupts are `masked` (the default) or `unmasked` while single stepping.
+    #[serde(default)]
+    pub(crate) step_interrupts: InterruptMasking,
+
     /// Every core on the target has certain configuration.
     ///
     /// NOTE: Although we allow specifying multiple core configurations, this is a work in progress, and probe-rs-debugger currently only supports debugging a single core.
     pub(crate) core_configs: Vec<CoreConfig>,
+
+    /// The token to authenticate with, if the debug server was started with `--auth-token`.
+    pub(crate) auth_token: Option<String>,
 }
 
 impl SessionConfig {
@@ -97,6 +118,17 @@ impl SessionConfig {
                         )));
                 }
             };
+            // Update the `symbols_file` and validate that the file exists.
+            if let Some(symbols_file) = &target_core_config.symbols_file {
+                let symbols_file = get_absolute_path(self.cwd.as_ref(), Some(symbols_file))?;
+                if !symbols_file.is_file() {
+                    return Err(DebuggerError::Other(anyhow!(
+                        "Invalid symbols file specified '{}'",
+                        symbols_file.display()
+                    )));
+                }
+                target_core_config.symbols_file = Some(symbols_file);
+            }
             // Update the `svd_file` and validate that the file exists, or else warn the user and continue.
             target_core_config.svd_file =
                 match get_absolute_path(self.cwd.as_ref(), target_core_config.svd_file.as_ref()) {
@@ -161,10 +193,20 @@ impl SessionConfig {
             protocol: self.wire_protocol,
             non_interactive: true,
             probe: self.probe.clone(),
+            wait_for_probe: false,
+            connect_timeout: None,
             speed: self.speed,
+            adaptive_clocking: self.adaptive_clocking,
+            speed_fallback: self.speed_fallback,
             connect_under_reset: self.connect_under_reset,
             dry_run: false,
             allow_erase_all: self.allow_erase_all,
+            record: None,
+            replay: None,
+            remote: None,
+            bitbang: None,
+            bitbang_protocol: BitbangProtocol::RemoteBitbang,
+            gpio: None,
         }
     }
 }
@@ -230,13 +272,31 @@ pub struct CoreConfig {
     /// Binary to debug as a path. Relative to `cwd`, or fully qualified.
     pub(crate) program_binary: Option<PathBuf>,
 
+    /// ELF file with the symbols and debug info of the `program_binary`, if they were stripped
+    /// from it. Relative to `cwd`, or fully qualified.
+    pub(crate) symbols_file: Option<PathBuf>,
+
     /// CMSIS-SVD file for the target. Relative to `cwd`, or fully qualified.
     pub(crate) svd_file: Option<PathBuf>,
 
+    /// Halt the core on these exceptions, e.g. `["hard-fault", "bus-fault"]`.
+    #[serde(default)]
+    pub(crate) vector_catch: Vec<VectorCatchCondition>,
+
     #[serde(flatten)]
     pub(crate) rtt_config: rtt::RttConfig,
 }
 
+impl CoreConfig {
+    /// The ELF file with the symbols of the program, which is the `program_binary`, unless a
+    /// separate `symbols_file` is given.
+    pub(crate) fn symbols(&self) -> Option<&Path> {
+        self.symbols_file
+            .as_deref()
+            .or(self.program_binary.as_deref())
+    }
+}
+
 fn default_console_log() -> Option<ConsoleLog> {
     Some(ConsoleLog::Console)
 }
@@ -263,3 +323,68 @@ impl std::str::FromStr for ConsoleLog {
         }
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+
+    /// A directory with ELF files to use as program binaries and symbol files.
+    const ELF_DIR: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../probe-rs/tests/debug-unwind-tests"
+    );
+
+    fn session_config(symbols_file: &str) -> SessionConfig {
+        serde_json::from_value(serde_json::json!({
+            "cwd": ELF_DIR,
+            "coreConfigs": [{
+                "programBinary": "nRF52833_xxAA_svcall.elf",
+                "symbolsFile": symbols_file,
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn symbols_file_is_resolved_relative_to_cwd() {
+        let mut config = session_config("RP2040_full_unwind.elf");
+        config.validate_config_files().unwrap();
+
+        let core_config = &config.core_configs[0];
+        assert_eq!(
+            core_config.symbols(),
+            Some(Path::new(ELF_DIR).join("RP2040_full_unwind.elf").as_path())
+        );
+    }
+
+    #[test]
+    fn missing_symbols_file_is_an_error() {
+        let mut config = session_config("missing.elf");
+        let error = config.validate_config_files().unwrap_err();
+
+        assert!(error.to_string().contains("Invalid symbols file"));
+    }
+
+    #[test]
+    fn step_interrupts_default_to_masked() {
+        let config = session_config("RP2040_full_unwind.elf");
+        assert_eq!(config.step_interrupts, InterruptMasking::Masked);
+
+        let config: SessionConfig = serde_json::from_value(serde_json::json!({
+            "stepInterrupts": "unmasked",
+            "coreConfigs": [],
+        }))
+        .unwrap();
+        assert_eq!(config.step_interrupts, InterruptMasking::Unmasked);
+    }
+
+    #[test]
+    fn symbols_default_to_the_program_binary() {
+        let core_config = CoreConfig {
+            program_binary: Some(PathBuf::from("firmware.elf")),
+            ..Default::default()
+        };
+        assert_eq!(core_config.symbols(), Some(Path::new("firmware.elf")));
+    }
+}
@@ -25,7 +25,7 @@ use probe_rs::{
         debug_info::DebugInfo, stack_frame::StackFrameInfo, ColumnType, ObjectRef, VariableCache,
     },
     rtt::{Rtt, ScanRegion},
-    Core, CoreStatus, HaltReason,
+    Core, CoreSnapshot, CoreStatus, HaltReason,
 };
 use time::UtcOffset;
 use typed_path::TypedPathBuf;
@@ -49,6 +49,8 @@ pub struct CoreData {
     pub stack_frames: Vec<probe_rs::debug::stack_frame::StackFrame>,
     pub breakpoints: Vec<session_data::ActiveBreakpoint>,
     pub rtt_connection: Option<debug_rtt::RttConnection>,
+    /// Snapshots saved with the `snapshot` REPL command, in the order they were taken.
+    pub snapshots: Vec<(String, CoreSnapshot)>,
 }
 
 /// [CoreHandle] provides handles to various data structures required to debug a single instance of a core. The actual state is stored in [session_data::SessionData].
@@ -325,6 +327,26 @@ impl<'p> CoreHandle<'p> {
         })
     }
 
+    /// Move the program counter of the halted core to `address`, after validating that it is a statement
+    /// in the current function.
+    pub(crate) fn goto(&mut self, address: u64) -> Result<(), DebuggerError> {
+        if !self.core.core_halted()? {
+            return Err(DebuggerError::UserMessage(
+                "The core must be halted before the program counter can be moved.".to_string(),
+            ));
+        }
+
+        let program_counter: u64 = self.core.read_core_reg(self.core.program_counter())?;
+        self.core_data
+            .debug_info
+            .validate_goto_target(program_counter, address)
+            .map_err(|error| DebuggerError::UserMessage(error.to_string()))?;
+
+        self.core
+            .write_core_reg(self.core.program_counter(), address)?;
+        Ok(())
+    }
+
     /// In the case where a new binary is flashed as part of a restart, we need to recompute the breakpoint address,
     /// for a specified source location, of any [`super::session_data::BreakpointType::SourceBreakpoint`].
     /// This is because the address of the breakpoint may have changed based on changes in the source file that created the new binary.
@@ -20,13 +20,13 @@ use crate::{
         peripherals::svd_variables::SvdCache,
         DebuggerError,
     },
-    util::flash::build_loader,
+    util::{flash::build_loader, server_address::token_matches},
 };
 use anyhow::{anyhow, Context};
 use probe_rs::{
     flashing::{DownloadOptions, FileDownloadError, FlashProgress, ProgressEvent},
     probe::list::Lister,
-    Architecture, CoreStatus,
+    Architecture, CancellationToken, CoreStatus,
 };
 use std::{
     cell::RefCell,
@@ -71,6 +71,12 @@ pub struct Debugger {
     /// Used to capture the `tracing` messages that are generated during the DAP sessions,
     /// to be ultimately forwarded to the DAP client's Debug Console, or failing that, stderr.
     pub(crate) debug_logger: DebugLogger,
+
+    /// If set, a GDB server is started on this address once the debug session is attached.
+    pub(crate) gdb_connection_string: Option<String>,
+
+    /// If set, clients have to send this token as `authToken` in their launch or attach request.
+    pub(crate) auth_token: Option<String>,
 }
 
 impl Debugger {
@@ -84,6 +90,8 @@ impl Debugger {
             timestamp_offset,
             binary_timestamp: None,
             debug_logger: DebugLogger::new(log_file)?,
+            gdb_connection_string: None,
+            auth_token: None,
         };
 
         debugger
@@ -109,12 +117,13 @@ impl Debugger {
         match debug_adapter.listen_for_request()? {
             None => {
                 let _poll_span = tracing::trace_span!("Polling for core status").entered();
-                if debug_adapter.all_cores_halted {
+                if debug_adapter.all_cores_halted && !session_data.has_gdb_server() {
                     // Once all cores are halted, then we can skip polling the core for status, and just wait for the next DAP Client request.
+                    // A GDB client can resume the cores behind our back though, so we keep polling while a GDB server is running.
                     tracing::trace!(
                         "Sleeping (all cores are halted) for 100ms to reduce polling overheaads."
                     );
-                    thread::sleep(Duration::from_millis(100)); // Medium delay to reduce fast looping costs.
+                    session_data.wait(Duration::from_millis(100)); // Medium delay to reduce fast looping costs.
                 } else {
                     // Poll ALL target cores for status, which includes synching status with the DAP client, and handling RTT data.
                     let (_, suggest_delay_required) =
@@ -124,9 +133,10 @@ impl Debugger {
                         tracing::trace!(
                             "Sleeping (core is running) for 50ms to reduce polling overheads."
                         );
-                        thread::sleep(Duration::from_millis(50)); // Small delay to reduce fast looping costs.
+                        session_data.wait(Duration::from_millis(50)); // Small delay to reduce fast looping costs.
                     } else {
                         tracing::trace!("Retrieving data from the core, no delay required between iterations of polling the core.");
+                        session_data.yield_session();
                     };
                 }
 
@@ -238,6 +248,8 @@ impl Debugger {
                         }
                         Ok(())
                     }
+                    // Only flashing can be cancelled, and it checks for `cancel` requests itself.
+                    "cancel" => debug_adapter.send_response::<()>(&request, Ok(None)),
                     "disconnect" => {
                         let result = debug_adapter.disconnect(&mut target_core, &request);
                         debug_session = DebugSessionStatus::Terminate;
@@ -277,6 +289,8 @@ impl Debugger {
                         result
                     }
                     "setBreakpoints" => debug_adapter.set_breakpoints(&mut target_core, &request),
+                    "gotoTargets" => debug_adapter.goto_targets(&mut target_core, &request),
+                    "goto" => debug_adapter.goto(&mut target_core, &request),
                     "setInstructionBreakpoints" => {
                         debug_adapter.set_instruction_breakpoints(&mut target_core, &request)
                     }
@@ -432,6 +446,23 @@ impl Debugger {
             }
         };
 
+        if let Some(expected_token) = &self.auth_token {
+            let authenticated = self
+                .config
+                .auth_token
+                .as_deref()
+                .is_some_and(|token| token_matches(expected_token, token));
+
+            if !authenticated {
+                let error = DebuggerError::UserMessage(
+                    "Authentication failed. Please set `authToken` to the token of the debug server."
+                        .to_string(),
+                );
+                debug_adapter.send_response::<()>(launch_attach_request, Err(&error))?;
+                return Err(error);
+            }
+        }
+
         if let Err(bad_config) = self
             .config
             .validate_configuration_option_compatibility(requested_target_session_type)
@@ -458,6 +489,7 @@ impl Debugger {
             };
 
         debug_adapter.halt_after_reset = self.config.flashing_config.halt_after_reset;
+        debug_adapter.step_interrupts = self.config.step_interrupts;
 
         if self.config.flashing_config.flashing_enabled {
             let target_core_config = match self.config.core_configs.first_mut() {
@@ -515,6 +547,16 @@ impl Debugger {
             return Err(error);
         }
 
+        for &condition in &target_core_config.vector_catch {
+            if let Err(error) = target_core.core.enable_vector_catch(condition) {
+                let error = DebuggerError::Other(anyhow!(
+                    "Failed to enable the {condition} vector catch: {error}"
+                ));
+                debug_adapter.send_response::<()>(launch_attach_request, Err(&error))?;
+                return Err(error);
+            }
+        }
+
         // Before we complete, load the (optional) CMSIS-SVD file and its variable cache.
         // Configure the [CorePeripherals].
         if let Some(svd_file) = &target_core_config.svd_file {
@@ -551,6 +593,23 @@ impl Debugger {
 
         drop(target_core);
 
+        if let Some(gdb_connection_string) = &self.gdb_connection_string {
+            match session_data.start_gdb_server(gdb_connection_string) {
+                Ok(instances) => {
+                    for instance in instances.iter() {
+                        self.debug_logger.log_to_console(&format!(
+                            "Firing up GDB stub for {:?} cores at {:?}",
+                            instance.core_type, instance.socket_addrs
+                        ))?;
+                    }
+                }
+                Err(error) => {
+                    debug_adapter.send_response::<()>(launch_attach_request, Err(&error))?;
+                    return Err(error);
+                }
+            }
+        }
+
         debug_adapter.send_response::<()>(launch_attach_request, Ok(None))?;
 
         Ok((debug_adapter, session_data))
@@ -631,12 +690,15 @@ impl Debugger {
             &path_to_elf
         ));
         let progress_id = debug_adapter
-            .start_progress("Flashing device", Some(launch_attach_request.seq))
+            .start_progress("Flashing device", Some(launch_attach_request.seq), true)
             .ok();
 
+        let cancellation = CancellationToken::new();
         let mut download_options = DownloadOptions::default();
         download_options.keep_unwritten_bytes = self.config.flashing_config.restore_unwritten_bytes;
         download_options.do_chip_erase = self.config.flashing_config.full_chip_erase;
+        download_options.cancellation = Some(cancellation.clone());
+        let launch_attach_request_id = launch_attach_request.seq;
 
         let rc_debug_adapter = Rc::new(RefCell::new(debug_adapter));
         let rc_debug_adapter_clone = rc_debug_adapter.clone();
@@ -663,6 +725,9 @@ impl Debugger {
             FlashProgress::new(move |event| {
                 let mut flash_progress = progress_state.borrow_mut();
                 let mut debug_adapter = rc_debug_adapter_clone.borrow_mut();
+                if debug_adapter.cancellation_requested(id, launch_attach_request_id) {
+                    cancellation.cancel();
+                }
                 match event {
                     ProgressEvent::Initialized { phases, .. } => {
                         for phase_layout in phases {
@@ -756,7 +821,9 @@ impl Debugger {
                             .update_progress(Some(1.0), Some("Flashing Pages Complete!"), id)
                             .ok();
                     }
-                    ProgressEvent::DiagnosticMessage { .. } => (),
+                    ProgressEvent::DiagnosticMessage { .. }
+                    | ProgressEvent::AlgorithmCalled { .. }
+                    | ProgressEvent::AlgorithmReturned { .. } => (),
                 }
             })
         });
@@ -884,6 +951,8 @@ impl Debugger {
             supports_instruction_breakpoints: Some(true),
             supports_stepping_granularity: Some(true),
             supports_completions_request: Some(true),
+            supports_goto_targets_request: Some(true),
+            supports_cancel_request: Some(true),
             support_terminate_debuggee: Some(true),
             // supports_value_formatting_options: Some(true),
             // supports_function_breakpoints: Some(true),
@@ -971,9 +1040,11 @@ mod test {
             dap::{
                 adapter::DebugAdapter,
                 dap_types::{
-                    Capabilities, DisconnectArguments, ErrorResponseBody,
-                    InitializeRequestArguments, Message, Request, Response, Thread,
-                    ThreadsResponseBody,
+                    CancelArguments, Capabilities, DisconnectArguments, ErrorResponseBody,
+                    InitializeRequestArguments, MemoryEventBody, Message, PauseArguments,
+                    ReadMemoryArguments, ReadMemoryResponseBody, Request, Response,
+                    StoppedEventBody, Thread, ThreadsResponseBody, WriteMemoryArguments,
+                    WriteMemoryResponseBody,
                 },
             },
             protocol::ProtocolAdapter,
@@ -981,6 +1052,7 @@ mod test {
         server::configuration::{ConsoleLog, CoreConfig, FlashingConfig, SessionConfig},
         test::TestLister,
     };
+    use base64::{engine::general_purpose as base64_engine, Engine as _};
     use probe_rs::{
         architecture::arm::FullyQualifiedApAddress,
         integration::{FakeProbe, Operation},
@@ -1027,6 +1099,8 @@ mod test {
             support_suspend_debuggee: Some(true),
             supports_clipboard_context: Some(true),
             supports_completions_request: Some(true),
+            supports_goto_targets_request: Some(true),
+            supports_cancel_request: Some(true),
             supports_configuration_done_request: Some(true),
             supports_delayed_stack_trace_loading: Some(true),
             supports_disassemble_request: Some(true),
@@ -1497,6 +1571,51 @@ mod test {
         debugger.debug_session(debug_adapter, &lister).unwrap();
     }
 
+    #[test]
+    fn launch_with_wrong_auth_token() {
+        let mut protocol_adapter = MockProtocolAdapter::new();
+
+        protocol_adapter
+            .add_request("initialize")
+            .with_arguments(default_initialize_args())
+            .and_succesful_response()
+            .with_body(expected_capabilites());
+
+        protocol_adapter.expect_output_event("probe-rs-debug: Log output for \"probe_rs=warn\" will be written to the Debug Console.\n");
+        protocol_adapter
+            .expect_output_event("probe-rs-debug: Starting probe-rs as a DAP Protocol server\n");
+
+        let launch_args = SessionConfig {
+            chip: Some("nrf52833_xxaa".to_owned()),
+            core_configs: vec![CoreConfig {
+                core_index: 0,
+                ..CoreConfig::default()
+            }],
+            auth_token: Some("guessed".to_owned()),
+            ..SessionConfig::default()
+        };
+
+        let expected_error =
+            "Authentication failed. Please set `authToken` to the token of the debug server.";
+        protocol_adapter.expect_output_event(&format!("{expected_error}\n"));
+
+        protocol_adapter
+            .add_request("launch")
+            .with_arguments(launch_args)
+            .and_error_response()
+            .with_body(error_response_body(expected_error));
+
+        let debug_adapter = DebugAdapter::new(protocol_adapter);
+
+        let mut debugger = Debugger::new(UtcOffset::UTC, None).unwrap();
+        debugger.auth_token = Some("secret".to_owned());
+
+        // The probe must not be opened before the client is authenticated.
+        let lister = Lister::with_lister(Box::new(TestLister::new()));
+
+        debugger.debug_session(debug_adapter, &lister).unwrap();
+    }
+
     #[test]
     fn wrong_request_after_init() {
         let mut protocol_adapter = MockProtocolAdapter::new();
@@ -1778,4 +1897,180 @@ mod test {
 
         debugger.debug_session(debug_adapter, &lister).unwrap();
     }
+
+    /// Drives a complete debug session against the simulated core of the fake probe.
+    #[test]
+    fn launch_and_access_memory() {
+        let manifest_dir = PathBuf::from(std::env!("CARGO_MANIFEST_DIR"));
+        let debug_info =
+            manifest_dir.join("../probe-rs/tests/debug-unwind-tests/nRF52833_xxAA_full_unwind.elf");
+
+        let mut protocol_adapter = MockProtocolAdapter::new();
+
+        protocol_adapter
+            .add_request("initialize")
+            .with_arguments(default_initialize_args())
+            .and_succesful_response()
+            .with_body(expected_capabilites());
+
+        protocol_adapter.expect_output_event("probe-rs-debug: Log output for \"probe_rs=warn\" will be written to the Debug Console.\n");
+        protocol_adapter
+            .expect_output_event("probe-rs-debug: Starting probe-rs as a DAP Protocol server\n");
+
+        let launch_args = SessionConfig {
+            chip: Some("nRF52833_xxAA".to_owned()),
+            core_configs: vec![CoreConfig {
+                core_index: 0,
+                program_binary: Some(debug_info),
+                ..CoreConfig::default()
+            }],
+            ..SessionConfig::default()
+        };
+
+        protocol_adapter
+            .add_request("launch")
+            .with_arguments(launch_args)
+            .and_succesful_response();
+
+        protocol_adapter.expect_event("initialized", None::<u32>);
+
+        protocol_adapter
+            .add_request("configurationDone")
+            .and_succesful_response();
+
+        protocol_adapter
+            .add_request("pause")
+            .with_arguments(PauseArguments { thread_id: 0 })
+            .and_succesful_response()
+            .with_body("Core stopped at address 0x00000000");
+
+        protocol_adapter.expect_event(
+            "stopped",
+            Some(StoppedEventBody {
+                reason: "pause".to_owned(),
+                description: Some("Core halted due to a user (debugger client) request".to_owned()),
+                thread_id: Some(0),
+                preserve_focus_hint: Some(false),
+                text: None,
+                all_threads_stopped: Some(false),
+                hit_breakpoint_ids: None,
+            }),
+        );
+
+        let data = [0xde, 0xad, 0xbe, 0xef, 0x01, 0x02, 0x03, 0x04];
+        let encoded = base64_engine::STANDARD.encode(data);
+
+        protocol_adapter
+            .add_request("writeMemory")
+            .with_arguments(WriteMemoryArguments {
+                allow_partial: None,
+                data: encoded.clone(),
+                memory_reference: "0x20000100".to_owned(),
+                offset: None,
+            })
+            .and_succesful_response()
+            .with_body(WriteMemoryResponseBody {
+                bytes_written: Some(data.len() as i64),
+                offset: None,
+            });
+
+        protocol_adapter.expect_event(
+            "memory",
+            Some(MemoryEventBody {
+                count: data.len() as i64,
+                memory_reference: "0x20000100".to_owned(),
+                offset: 0,
+            }),
+        );
+
+        protocol_adapter
+            .add_request("readMemory")
+            .with_arguments(ReadMemoryArguments {
+                count: data.len() as i64,
+                memory_reference: "0x20000100".to_owned(),
+                offset: None,
+            })
+            .and_succesful_response()
+            .with_body(ReadMemoryResponseBody {
+                address: "0x20000100".to_owned(),
+                data: Some(encoded),
+                unreadable_bytes: None,
+            });
+
+        protocol_adapter
+            .add_request("disconnect")
+            .with_arguments(DisconnectArguments {
+                restart: Some(false),
+                suspend_debuggee: Some(false),
+                terminate_debuggee: Some(false),
+            })
+            .and_succesful_response();
+
+        let debug_adapter = DebugAdapter::new(protocol_adapter);
+
+        let mut debugger = Debugger::new(UtcOffset::UTC, None).unwrap();
+
+        let lister = TestLister::new();
+
+        let probe_info = DebugProbeInfo::new(
+            "Mock probe",
+            0x12,
+            0x23,
+            Some("mock_serial".to_owned()),
+            &MockProbeFactory,
+            None,
+        );
+
+        let fake_probe = FakeProbe::with_mocked_core();
+
+        // Indicate that the core is unlocked
+        fake_probe.expect_operation(Operation::ReadRawApRegister {
+            ap: FullyQualifiedApAddress::v1_with_default_dp(1),
+            address: 0xC,
+            result: 1,
+        });
+
+        lister.probes.borrow_mut().push((probe_info, fake_probe));
+
+        let lister = Lister::with_lister(Box::new(lister));
+
+        debugger.debug_session(debug_adapter, &lister).unwrap();
+    }
+
+    #[test]
+    fn cancel_progress() {
+        let mut protocol_adapter = MockProtocolAdapter::new();
+
+        protocol_adapter
+            .add_request("cancel")
+            .with_arguments(CancelArguments {
+                progress_id: Some("1".to_owned()),
+                request_id: None,
+            })
+            .and_succesful_response();
+        protocol_adapter
+            .add_request("cancel")
+            .with_arguments(CancelArguments {
+                progress_id: None,
+                request_id: Some(7),
+            })
+            .and_succesful_response();
+        protocol_adapter.add_request("threads");
+        protocol_adapter.add_request("disconnect");
+
+        let mut debug_adapter = DebugAdapter::new(protocol_adapter);
+
+        // The first request cancels another progress.
+        assert!(!debug_adapter.cancellation_requested(0, 7));
+        assert!(debug_adapter.cancellation_requested(1, 7));
+
+        // Other requests are handled after the operation.
+        assert!(!debug_adapter.cancellation_requested(1, 7));
+        assert!(debug_adapter.cancellation_requested(1, 7));
+
+        let deferred = debug_adapter.listen_for_request().unwrap().unwrap();
+        assert_eq!(deferred.command, "threads");
+        let deferred = debug_adapter.listen_for_request().unwrap().unwrap();
+        assert_eq!(deferred.command, "disconnect");
+    }
 }
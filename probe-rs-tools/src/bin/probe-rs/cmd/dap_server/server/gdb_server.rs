@@ -0,0 +1,117 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use anyhow::anyhow;
+use parking_lot::FairMutex;
+use probe_rs::{
+    gdb_server::{GdbInstanceConfiguration, GdbServer},
+    Session,
+};
+
+use crate::cmd::dap_server::DebuggerError;
+
+/// A GDB server for the session of a DAP client, which runs on its own thread.
+///
+/// The GDB server locks the session for each GDB request, so it can only handle them while the
+/// debugger has unlocked the session, see [`super::session_data::SessionData::wait`].
+pub(crate) struct SharedGdbServer {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SharedGdbServer {
+    /// Starts the GDB server, and returns once its sockets are bound.
+    pub(crate) fn start(
+        session: Arc<FairMutex<Session>>,
+        instances: Vec<GdbInstanceConfiguration>,
+    ) -> Result<Self, DebuggerError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (started_sender, started) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name("gdb-server".to_string())
+            .spawn({
+                let stop = stop.clone();
+                move || {
+                    let mut server = match GdbServer::new(&session, instances.iter()) {
+                        Ok(server) => {
+                            _ = started_sender.send(Ok(()));
+                            server
+                        }
+                        Err(error) => {
+                            _ = started_sender.send(Err(error));
+                            return;
+                        }
+                    };
+
+                    if let Err(error) = server.run_until(&stop) {
+                        tracing::error!("The GDB server stopped with an error: {error:?}");
+                    }
+                }
+            })?;
+
+        started
+            .recv()
+            .map_err(|_| anyhow!("The GDB server stopped unexpectedly."))??;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for SharedGdbServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use std::time::Duration;
+
+    use probe_rs::integration::FakeProbe;
+
+    use super::*;
+
+    fn shared_session() -> Arc<FairMutex<Session>> {
+        let session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        Arc::new(FairMutex::new(session))
+    }
+
+    #[test]
+    fn server_shares_the_session_until_dropped() {
+        let session = shared_session();
+        let instances =
+            GdbInstanceConfiguration::from_session(&session.lock(), Some("127.0.0.1:0"));
+        assert_eq!(instances.len(), 1);
+
+        let server = SharedGdbServer::start(session.clone(), instances).unwrap();
+
+        // Without a GDB client, the server doesn't hold on to the session.
+        assert!(session.try_lock_for(Duration::from_secs(1)).is_some());
+
+        drop(server);
+        assert_eq!(Arc::strong_count(&session), 1);
+    }
+
+    #[test]
+    fn server_without_instances_stops() {
+        let session = shared_session();
+
+        let server = SharedGdbServer::start(session.clone(), Vec::new()).unwrap();
+        drop(server);
+
+        assert_eq!(Arc::strong_count(&session), 1);
+    }
+}
@@ -1,6 +1,7 @@
 use super::{
     configuration::{self, CoreConfig, SessionConfig},
     core_data::{CoreData, CoreHandle},
+    gdb_server::SharedGdbServer,
 };
 use crate::{
     cmd::dap_server::{
@@ -13,14 +14,16 @@ use crate::{
     util::common_options::OperationError,
 };
 use anyhow::{anyhow, Result};
+use parking_lot::{ArcMutexGuard, FairMutex, RawFairMutex};
 use probe_rs::{
     config::TargetSelector,
     debug::{debug_info::DebugInfo, DebugRegisters, SourceLocation},
     exception_handler_for_core,
+    gdb_server::GdbInstanceConfiguration,
     probe::list::Lister,
     CoreStatus, Session,
 };
-use std::env::set_current_dir;
+use std::{env::set_current_dir, net::ToSocketAddrs, sync::Arc, thread, time::Duration};
 use time::UtcOffset;
 
 /// The supported breakpoint types
@@ -55,7 +58,8 @@ pub struct ActiveBreakpoint {
 /// To get access to the [CoreHandle] for a specific [probe_rs::Core], the
 /// TODO: Adjust [SessionConfig] to allow multiple cores (and if appropriate, their binaries) to be specified.
 pub(crate) struct SessionData {
-    pub(crate) session: Session,
+    /// The session, which the debugger keeps locked, except while it waits for requests.
+    pub(crate) session: ArcMutexGuard<RawFairMutex, Session>,
     /// [SessionData] will manage one [CoreData] per target core, that is also present in [SessionConfig::core_configs]
     pub(crate) core_data: Vec<CoreData>,
 
@@ -63,6 +67,11 @@ pub(crate) struct SessionData {
     ///
     /// Getting the offset can fail, so it's better to store it.
     timestamp_offset: UtcOffset,
+
+    /// The GDB server sharing the session, if enabled.
+    ///
+    /// This is dropped after `session`, so the session is unlocked while the server stops.
+    gdb_server: Option<SharedGdbServer>,
 }
 
 impl SessionData {
@@ -142,16 +151,56 @@ impl SessionData {
                 stack_frames: vec![],
                 breakpoints: vec![],
                 rtt_connection: None,
+                snapshots: vec![],
             })
         }
 
         Ok(SessionData {
-            session: target_session,
+            session: Arc::new(FairMutex::new(target_session)).lock_arc(),
             core_data: core_data_vec,
             timestamp_offset,
+            gdb_server: None,
         })
     }
 
+    /// Starts a GDB server for all cores of the session, on the addresses of `connection_string`.
+    pub(crate) fn start_gdb_server(
+        &mut self,
+        connection_string: &str,
+    ) -> Result<Vec<GdbInstanceConfiguration>, DebuggerError> {
+        if let Err(error) = connection_string.to_socket_addrs() {
+            return Err(DebuggerError::UserMessage(format!(
+                "Invalid GDB connection string '{connection_string}': {error}"
+            )));
+        }
+
+        let instances =
+            GdbInstanceConfiguration::from_session(&self.session, Some(connection_string));
+        let server = SharedGdbServer::start(
+            ArcMutexGuard::mutex(&self.session).clone(),
+            instances.clone(),
+        )?;
+        self.gdb_server = Some(server);
+
+        Ok(instances)
+    }
+
+    /// Returns whether a GDB server shares the session, so the cores can be halted and resumed
+    /// without the debugger.
+    pub(crate) fn has_gdb_server(&self) -> bool {
+        self.gdb_server.is_some()
+    }
+
+    /// Waits for `duration`, with the session unlocked, so a GDB server can use it.
+    pub(crate) fn wait(&mut self, duration: Duration) {
+        ArcMutexGuard::unlocked_fair(&mut self.session, || thread::sleep(duration));
+    }
+
+    /// Lets a GDB server handle a pending request, if there is one.
+    pub(crate) fn yield_session(&mut self) {
+        ArcMutexGuard::bump(&mut self.session);
+    }
+
     /// Reload the a specific core's debug info from the binary file.
     pub(crate) fn load_debug_info_for_core(
         &mut self,
@@ -251,7 +300,7 @@ impl SessionData {
                     #[allow(clippy::unwrap_used)]
                     match target_core.attach_to_rtt(
                         debug_adapter,
-                        core_config.program_binary.as_ref().unwrap(),
+                        core_config.symbols().unwrap(),
                         &core_config.rtt_config,
                         timestamp_offset,
                     ) {
@@ -319,7 +368,7 @@ impl SessionData {
 }
 
 fn debug_info_from_binary(core_configuration: &CoreConfig) -> anyhow::Result<DebugInfo> {
-    let Some(ref binary_path) = core_configuration.program_binary else {
+    let Some(binary_path) = core_configuration.symbols() else {
         return Err(anyhow!(
             "Please provide a valid `program_binary` for debug core: {}",
             core_configuration.core_index
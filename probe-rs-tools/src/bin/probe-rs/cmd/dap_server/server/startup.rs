@@ -4,9 +4,10 @@ use anyhow::{Context, Result};
 use probe_rs::probe::list::Lister;
 use serde::Deserialize;
 use std::{
-    fs,
-    net::TcpListener,
-    path::Path,
+    fmt, fs,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
     time::{Duration, UNIX_EPOCH},
 };
 use time::UtcOffset;
@@ -31,14 +32,109 @@ impl std::str::FromStr for TargetSessionType {
     }
 }
 
+/// The address that the DAP server listens on.
+pub(crate) enum DapEndpoint {
+    Tcp(SocketAddr),
+    /// A Unix domain socket, which is created when the server starts listening.
+    Unix(PathBuf),
+}
+
+impl fmt::Display for DapEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DapEndpoint::Tcp(addr) => write!(f, "port {}", addr.port()),
+            DapEndpoint::Unix(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+enum DapListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+/// The two halves of an accepted DAP connection.
+type Connection = (Box<dyn Read>, Box<dyn Write>);
+
+impl DapListener {
+    fn bind(endpoint: &DapEndpoint) -> Result<Self> {
+        match endpoint {
+            DapEndpoint::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)?;
+                listener.set_nonblocking(false)?;
+                Ok(DapListener::Tcp(listener))
+            }
+            #[cfg(unix)]
+            DapEndpoint::Unix(path) => {
+                use std::os::unix::{fs::PermissionsExt, net::UnixListener};
+
+                // A socket left behind by a previous session would make binding fail.
+                if path.exists() {
+                    fs::remove_file(path).with_context(|| {
+                        format!("Failed to remove the existing socket {}", path.display())
+                    })?;
+                }
+
+                let listener = UnixListener::bind(path)
+                    .with_context(|| format!("Failed to listen on {}", path.display()))?;
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+                Ok(DapListener::Unix(listener))
+            }
+            #[cfg(not(unix))]
+            DapEndpoint::Unix(_) => {
+                anyhow::bail!("Unix domain sockets are not supported on this platform.")
+            }
+        }
+    }
+
+    /// Wait for the next client, and return the connection and a description of the client.
+    fn accept(&self) -> Result<(Connection, String)> {
+        match self {
+            DapListener::Tcp(listener) => {
+                let (socket, addr) = listener.accept()?;
+                socket.set_nonblocking(true).with_context(|| {
+                    format!("Failed to negotiate non-blocking socket with request from: {addr}")
+                })?;
+
+                let reader = socket
+                    .try_clone()
+                    .context("Failed to establish a bi-directional Tcp connection.")?;
+
+                Ok(((Box::new(reader), Box::new(socket)), addr.to_string()))
+            }
+            #[cfg(unix)]
+            DapListener::Unix(listener) => {
+                let (socket, _) = listener.accept()?;
+                socket
+                    .set_nonblocking(true)
+                    .context("Failed to negotiate non-blocking socket with the client.")?;
+
+                let reader = socket
+                    .try_clone()
+                    .context("Failed to establish a bi-directional Unix socket connection.")?;
+
+                Ok((
+                    (Box::new(reader), Box::new(socket)),
+                    "Unix socket client".to_string(),
+                ))
+            }
+        }
+    }
+}
+
 pub fn debug(
     lister: &Lister,
-    addr: std::net::SocketAddr,
+    endpoint: DapEndpoint,
     single_session: bool,
+    gdb_connection_string: Option<String>,
+    auth_token: Option<String>,
     log_file: Option<&Path>,
     timestamp_offset: UtcOffset,
 ) -> Result<()> {
     let mut debugger = Debugger::new(timestamp_offset, log_file)?;
+    debugger.gdb_connection_string = gdb_connection_string;
+    debugger.auth_token = auth_token;
 
     let old_hook = std::panic::take_hook();
     let logger = debugger.debug_logger.clone();
@@ -49,34 +145,23 @@ pub fn debug(
     }));
 
     loop {
-        let listener = TcpListener::bind(addr)?;
+        let listener = DapListener::bind(&endpoint)?;
 
         debugger
             .debug_logger
-            .log_to_console(&format!("Listening for requests on port {}", addr.port()))?;
+            .log_to_console(&format!("Listening for requests on {endpoint}"))?;
 
         if !single_session {
             // When running as a server from the command line, we want startup logs to go to the stderr.
             debugger.debug_logger.flush()?;
         }
 
-        listener.set_nonblocking(false)?;
-
         match listener.accept() {
-            Ok((socket, addr)) => {
-                socket.set_nonblocking(true).with_context(|| {
-                    format!("Failed to negotiate non-blocking socket with request from: {addr}")
-                })?;
-
+            Ok(((reader, writer), addr)) => {
                 debugger
                     .debug_logger
                     .log_to_console(&format!("Starting debug session from: {addr}"))?;
 
-                let reader = socket
-                    .try_clone()
-                    .context("Failed to establish a bi-directional Tcp connection.")?;
-                let writer = socket;
-
                 let dap_adapter = DapAdapter::new(reader, writer);
                 let mut debug_adapter = DebugAdapter::new(dap_adapter);
 
@@ -107,6 +192,10 @@ pub fn debug(
         debugger.debug_logger.flush()?;
     }
 
+    if let DapEndpoint::Unix(path) = &endpoint {
+        _ = fs::remove_file(path);
+    }
+
     debugger
         .debug_logger
         .log_to_console("DAP Protocol server exiting")?;
@@ -3,10 +3,6 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 use anyhow::anyhow;
-use capstone::{
-    arch::arm::ArchMode as armArchMode, arch::arm64::ArchMode as aarch64ArchMode,
-    arch::riscv::ArchMode as riscvArchMode, prelude::*, Endian,
-};
 use num_traits::Num;
 use parse_int::parse;
 use probe_rs::architecture::arm::ap::AccessPortError;
@@ -20,11 +16,12 @@ use probe_rs::CoreDumpError;
 use probe_rs::CoreInterface;
 use probe_rs::{
     debug::{debug_info::DebugInfo, registers::DebugRegisters, stack_frame::StackFrame},
-    Core, CoreType, InstructionSet, MemoryInterface, RegisterValue,
+    Core, CoreType, InterruptMasking, MemoryInterface, RegisterValue,
 };
 use rustyline::{error::ReadlineError, DefaultEditor};
 
-use crate::{util::common_options::ProbeOptions, CoreOptions};
+use crate::util::{common_options::ProbeOptions, disassembly::disassembler_for};
+use crate::CoreOptions;
 
 #[derive(clap::Parser)]
 pub struct Cmd {
@@ -84,7 +81,7 @@ impl Cmd {
 }
 
 #[derive(Debug, thiserror::Error)]
-enum CliError {
+pub(crate) enum CliError {
     #[error(transparent)]
     DebugProbe(#[from] DebugProbeError),
     #[error(transparent)]
@@ -115,7 +112,7 @@ pub struct DebugCli {
 }
 
 /// Parse the argument at the given index.
-fn get_int_argument<T: Num>(args: &[&str], index: usize) -> Result<T, CliError>
+pub(crate) fn get_int_argument<T: Num>(args: &[&str], index: usize) -> Result<T, CliError>
 where
     <T as Num>::FromStrRadixErr: std::error::Error + Send + Sync + 'static,
 {
@@ -129,17 +126,30 @@ where
 }
 
 impl DebugCli {
-    fn new() -> DebugCli {
+    pub(crate) fn new() -> DebugCli {
         let mut cli = DebugCli {
             commands: Vec::new(),
         };
 
         cli.add_command(Command {
             name: "step",
-            help_text: "Step a single instruction",
+            help_text: "Step a single instruction, 'step unmasked' takes pending interrupts",
 
-            function: |cli_data, _args| {
-                let cpu_info = cli_data.core.step()?;
+            function: |cli_data, args| {
+                let interrupts = match args.first() {
+                    Some(argument) => {
+                        argument
+                            .parse()
+                            .map_err(|e: String| CliError::ArgumentParseError {
+                                argument_index: 0,
+                                argument: argument.to_string(),
+                                source: anyhow!(e),
+                            })?
+                    }
+                    None => InterruptMasking::Masked,
+                };
+
+                let cpu_info = cli_data.core.step_with(interrupts)?;
                 println!("Core stopped at address 0x{:08x}", cpu_info.pc);
 
                 Ok(CliState::Continue)
@@ -158,44 +168,8 @@ impl DebugCli {
 
                 cli_data.core.read(cpu_info.pc, &mut code)?;
 
-                let cs = match cli_data.core.instruction_set()? {
-                    InstructionSet::Thumb2 => Capstone::new()
-                        .arm()
-                        .mode(armArchMode::Thumb)
-                        .endian(Endian::Little)
-                        .build(),
-                    InstructionSet::A32 => {
-                        // We need to inspect the CPSR to determine what mode this is opearting in
-                        Capstone::new()
-                            .arm()
-                            .mode(armArchMode::Arm)
-                            .endian(Endian::Little)
-                            .build()
-                    }
-                    InstructionSet::A64 => {
-                        // We need to inspect the CPSR to determine what mode this is opearting in
-                        Capstone::new()
-                            .arm64()
-                            .mode(aarch64ArchMode::Arm)
-                            .endian(Endian::Little)
-                            .build()
-                    }
-                    InstructionSet::RV32 => Capstone::new()
-                        .riscv()
-                        .mode(riscvArchMode::RiscV32)
-                        .endian(Endian::Little)
-                        .build(),
-                    InstructionSet::RV32C => Capstone::new()
-                        .riscv()
-                        .mode(riscvArchMode::RiscV32)
-                        .endian(Endian::Little)
-                        .extra_mode(std::iter::once(
-                            capstone::arch::riscv::ArchExtraMode::RiscVC,
-                        ))
-                        .build(),
-                    InstructionSet::Xtensa => Err(capstone::Error::UnsupportedArch),
-                }
-                .map_err(|err| anyhow!("Error creating capstone: {:?}", err))?;
+                let cs = disassembler_for(cli_data.core.instruction_set()?)
+                    .map_err(|err| anyhow!("Error creating capstone: {:?}", err))?;
 
                 // Attempt to dissassemble
                 match cs.disasm_all(&code, cpu_info.pc) {
@@ -674,6 +648,39 @@ impl DebugCli {
             },
         });
 
+        cli.add_command(Command {
+            name: "reg",
+            help_text: "Read or write a register: reg <name|pc|sp|lr> [value]",
+
+            function: |cli_data, args| {
+                let name = args.first().ok_or(CliError::MissingArgument)?;
+                let core = &cli_data.core;
+                let register = match name.to_ascii_lowercase().as_str() {
+                    "pc" => Some(core.program_counter()),
+                    "sp" => Some(core.stack_pointer()),
+                    "lr" | "ra" => Some(core.return_address()),
+                    _ => core
+                        .registers()
+                        .all_registers()
+                        .find(|r| r.name().eq_ignore_ascii_case(name)),
+                };
+                let Some(register) = register else {
+                    println!("Unknown register '{name}'");
+                    return Ok(CliState::Continue);
+                };
+
+                if args.len() > 1 {
+                    let value: u64 = get_int_argument(args, 1)?;
+                    cli_data.core.write_core_reg(register, value)?;
+                }
+
+                let value: RegisterValue = cli_data.core.read_core_reg(register)?;
+                println!("{:10}: {:#}", register.name(), value);
+
+                Ok(CliState::Continue)
+            },
+        });
+
         cli.add_command(Command {
             name: "fp_regs",
             help_text: "Show floating point register values",
@@ -918,16 +925,24 @@ impl DebugCli {
         self.commands.push(command)
     }
 
-    fn handle_line(&self, line: &str, cli_data: &mut CliData) -> Result<CliState, CliError> {
+    /// Prints the available commands, with their help text.
+    pub(crate) fn print_commands(&self) {
+        for cmd in &self.commands {
+            println!(" - {:12} {}", cmd.name, cmd.help_text);
+        }
+    }
+
+    pub(crate) fn handle_line(
+        &self,
+        line: &str,
+        cli_data: &mut CliData,
+    ) -> Result<CliState, CliError> {
         let mut command_parts = line.split_whitespace();
 
         match command_parts.next() {
             Some("help") => {
                 println!("The following commands are available:");
-
-                for cmd in &self.commands {
-                    println!(" - {}", cmd.name);
-                }
+                self.print_commands();
 
                 Ok(CliState::Continue)
             }
@@ -988,10 +1003,20 @@ pub struct CliData<'p> {
 
 impl<'p> CliData<'p> {
     fn new(core: Core<'p>, debug_info: Option<DebugInfo>) -> Result<CliData, CliError> {
+        Self::with_state(core, debug_info, DebugState::default())
+    }
+
+    /// Continues with the state of an earlier `CliData` for the same core, see
+    /// [`CliData::into_parts`].
+    pub(crate) fn with_state(
+        core: Core<'p>,
+        debug_info: Option<DebugInfo>,
+        state: DebugState,
+    ) -> Result<CliData, CliError> {
         let mut cli_data = CliData {
             core,
             debug_info,
-            state: DebugState::default(),
+            state,
         };
 
         cli_data.update_debug_status_from_core()?;
@@ -999,6 +1024,11 @@ impl<'p> CliData<'p> {
         Ok(cli_data)
     }
 
+    /// Releases the core, keeping the debug info and the state of the debugger.
+    pub(crate) fn into_parts(self) -> (Option<DebugInfo>, DebugState) {
+        (self.debug_info, self.state)
+    }
+
     /// Fill out DebugStatus for a given core
     fn update_debug_status_from_core(&mut self) -> Result<(), CliError> {
         let status = self.core.status()?;
@@ -1054,13 +1084,13 @@ impl<'p> CliData<'p> {
 }
 
 #[derive(Default)]
-enum DebugState {
+pub(crate) enum DebugState {
     #[default]
     Running,
     Halted(HaltedState),
 }
 
-struct HaltedState {
+pub(crate) struct HaltedState {
     program_counter: u64,
     current_frame: usize,
     frame_indices: Vec<i64>,
@@ -0,0 +1,347 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context};
+use capstone::Capstone;
+use goblin::elf::{
+    header::{EM_AARCH64, EM_ARM, EM_RISCV},
+    program_header::PT_LOAD,
+    sym::STT_FUNC,
+    Elf,
+};
+use probe_rs::{probe::list::Lister, CoreDump, InstructionSet, MemoryInterface};
+
+use crate::util::{common_options::ProbeOptions, disassembly::disassembler_for, resolve_location};
+use crate::CoreOptions;
+
+/// The number of bytes disassembled if neither a size is given, nor the address is a symbol.
+const DEFAULT_SIZE: u64 = 64;
+
+/// Disassemble memory of the target, an ELF file or a core dump
+///
+/// e.g. probe-rs disasm --chip nRF52840_xxAA --elf firmware.elf main
+///      Disassembles the function `main` as it is in the flash of the target.
+///
+/// e.g. probe-rs disasm --elf firmware.elf --from-elf 0x00001000 --size 32
+///      Disassembles 32 bytes of the ELF file, to compare them with the flash.
+///
+/// Instructions at the start of a symbol of the ELF file are labelled with it, and the
+/// instruction at the PC of a halted core or a core dump is marked with `=>`. Bytes which are not
+/// a valid instruction are shown as data.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// The start of the memory to disassemble, as address or symbol of the ELF file.
+    address: String,
+
+    /// The number of bytes to disassemble. Defaults to the size of the symbol, or 64 bytes.
+    #[clap(long)]
+    size: Option<u64>,
+
+    /// The ELF file with the symbols.
+    #[clap(long)]
+    elf: Option<PathBuf>,
+
+    /// Disassemble the loadable segments of the ELF file, instead of the memory of the target.
+    #[clap(long, requires = "elf")]
+    from_elf: bool,
+
+    /// Disassemble the memory of a core dump, instead of the memory of the target.
+    #[clap(long, conflicts_with = "from_elf")]
+    core_dump: Option<PathBuf>,
+
+    /// The instruction set, e.g. `thumb2`, `rv32c` or a target triple. Defaults to the one of
+    /// the core, or the architecture of the ELF file.
+    #[clap(long, value_parser = parse_instruction_set)]
+    instruction_set: Option<InstructionSet>,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let elf = self.elf.as_ref().map(std::fs::read).transpose()?;
+        let symbols = match &elf {
+            Some(elf) => Symbols::from_elf(elf)?,
+            None => Symbols::default(),
+        };
+
+        let address = resolve_location(&self.address, elf.as_deref())?;
+        let size = self
+            .size
+            .or_else(|| symbols.size_at(address))
+            .unwrap_or(DEFAULT_SIZE);
+
+        let mut code = vec![0; size as usize];
+        let (instruction_set, pc) = if let Some(elf) = elf.as_deref().filter(|_| self.from_elf) {
+            read_elf(elf, address, &mut code)?;
+            let instruction_set = match self.instruction_set {
+                Some(instruction_set) => instruction_set,
+                None => instruction_set_of(elf)?,
+            };
+            (instruction_set, None)
+        } else if let Some(path) = &self.core_dump {
+            let mut dump = CoreDump::load(path)?;
+            dump.read(address, &mut code)?;
+            let pc = dump
+                .debug_registers()
+                .get_program_counter()
+                .and_then(|pc| pc.value)
+                .and_then(|pc| pc.try_into().ok());
+            (self.instruction_set.unwrap_or(dump.instruction_set()), pc)
+        } else {
+            let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
+            let mut core = session.core(self.shared.core)?;
+            core.read(address, &mut code)?;
+            let pc = if core.core_halted()? {
+                Some(core.read_core_reg(core.program_counter())?)
+            } else {
+                None
+            };
+            let instruction_set = match self.instruction_set {
+                Some(instruction_set) => instruction_set,
+                None => core.instruction_set()?,
+            };
+            (instruction_set, pc)
+        };
+
+        let disassembler = match disassembler_for(instruction_set) {
+            Ok(disassembler) => Some(disassembler),
+            Err(error) => {
+                eprintln!("Cannot disassemble {instruction_set:?} ({error}), showing the bytes.");
+                None
+            }
+        };
+
+        for line in disassemble(disassembler.as_ref(), &code, address, &symbols, pc) {
+            println!("{line}");
+        }
+
+        Ok(())
+    }
+}
+
+/// The function and object symbols of an ELF file.
+#[derive(Default)]
+struct Symbols {
+    /// The address, size and demangled name of each symbol, sorted by address.
+    symbols: Vec<(u64, u64, String)>,
+}
+
+impl Symbols {
+    fn from_elf(elf: &[u8]) -> anyhow::Result<Self> {
+        let elf = parse_elf(elf)?;
+        let mut symbols: Vec<_> = elf
+            .syms
+            .iter()
+            .filter(|symbol| symbol.st_value != 0 && symbol.st_type() <= STT_FUNC)
+            .filter_map(|symbol| {
+                let name = elf.strtab.get_at(symbol.st_name)?;
+                // Mapping symbols like `$t` and `$d` only mark the kind of code.
+                if name.is_empty() || name.starts_with('$') {
+                    return None;
+                }
+                // The lowest bit of Thumb function symbols is set, but it's not part of the address.
+                let address = if symbol.st_type() == STT_FUNC && elf.header.e_machine == EM_ARM {
+                    symbol.st_value & !1
+                } else {
+                    symbol.st_value
+                };
+                let name = format!("{:#}", rustc_demangle::demangle(name));
+                Some((address, symbol.st_size, name))
+            })
+            .collect();
+        symbols.sort();
+
+        Ok(Self { symbols })
+    }
+
+    /// Returns the size of the symbol starting at the address.
+    fn size_at(&self, address: u64) -> Option<u64> {
+        self.symbols
+            .iter()
+            .find(|(start, size, _)| *start == address && *size > 0)
+            .map(|(_, size, _)| *size)
+    }
+
+    /// Returns the names of the symbols starting at the address.
+    fn names_at(&self, address: u64) -> impl Iterator<Item = &str> {
+        self.symbols
+            .iter()
+            .filter(move |(start, _, _)| *start == address)
+            .map(|(_, _, name)| name.as_str())
+    }
+
+    /// Returns the name of the symbol containing the address, and the offset of the address in it.
+    fn containing(&self, address: u64) -> Option<(&str, u64)> {
+        self.symbols
+            .iter()
+            .rev()
+            .find(|(start, size, _)| *start <= address && address < start + size)
+            .map(|(start, _, name)| (name.as_str(), address - start))
+    }
+}
+
+/// Returns the lines of the disassembly of the code at the address.
+///
+/// Without disassembler, the code is shown as data.
+fn disassemble(
+    disassembler: Option<&Capstone>,
+    code: &[u8],
+    address: u64,
+    symbols: &Symbols,
+    pc: Option<u64>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some((name, offset)) = symbols
+        .containing(address)
+        .filter(|(_, offset)| *offset > 0)
+    {
+        lines.push(format!("<{name}+{offset:#x}>:"));
+    }
+
+    let mut offset = 0;
+    while offset < code.len() {
+        let instruction_address = address + offset as u64;
+        for name in symbols.names_at(instruction_address) {
+            lines.push(format!("<{name}>:"));
+        }
+
+        let instruction = disassembler.and_then(|disassembler| {
+            let instructions = disassembler
+                .disasm_count(&code[offset..], instruction_address, 1)
+                .ok()?;
+            let instruction = instructions.first()?;
+            Some((
+                instruction.bytes().len(),
+                format!(
+                    "{} {}",
+                    instruction.mnemonic().unwrap_or_default(),
+                    instruction.op_str().unwrap_or_default()
+                ),
+            ))
+        });
+        // Skip the smallest instruction size of the common instruction sets, to resync after data.
+        let (length, text) = instruction.unwrap_or_else(|| {
+            let length = (code.len() - offset).min(2);
+            (length, ".data".to_string())
+        });
+
+        let bytes: Vec<_> = code[offset..offset + length]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let marker = if pc == Some(instruction_address) {
+            "=>"
+        } else {
+            "  "
+        };
+        lines.push(
+            format!(
+                "{marker} {instruction_address:#010x}:  {:<12} {text}",
+                bytes.join(" ")
+            )
+            .trim_end()
+            .to_string(),
+        );
+
+        offset += length;
+    }
+
+    lines
+}
+
+fn parse_elf(elf: &[u8]) -> anyhow::Result<Elf<'_>> {
+    Elf::parse(elf).map_err(|error| anyhow!("Failed to parse the ELF file: {error}"))
+}
+
+/// Reads the code at the address from the loadable segments of the ELF file.
+fn read_elf(elf: &[u8], address: u64, code: &mut [u8]) -> anyhow::Result<()> {
+    let parsed = parse_elf(elf)?;
+    let end = address + code.len() as u64;
+    let segment = parsed
+        .program_headers
+        .iter()
+        .filter(|header| header.p_type == PT_LOAD)
+        .find(|header| header.p_vaddr <= address && end <= header.p_vaddr + header.p_filesz)
+        .with_context(|| format!("The ELF file has no data for {address:#010x}..{end:#010x}."))?;
+
+    let start = (segment.p_offset + address - segment.p_vaddr) as usize;
+    code.copy_from_slice(&elf[start..start + code.len()]);
+
+    Ok(())
+}
+
+/// Returns the instruction set of the architecture of the ELF file.
+fn instruction_set_of(elf: &[u8]) -> anyhow::Result<InstructionSet> {
+    /// Missing in goblin.
+    const EM_XTENSA: u16 = 94;
+    /// The ELF flag of RISC-V binaries using compressed instructions.
+    const EF_RISCV_RVC: u32 = 0x1;
+
+    let header = parse_elf(elf)?.header;
+    Ok(match header.e_machine {
+        // Cortex-M is far more common in embedded firmware than Cortex-A.
+        EM_ARM => InstructionSet::Thumb2,
+        EM_AARCH64 => InstructionSet::A64,
+        EM_RISCV if header.e_flags & EF_RISCV_RVC != 0 => InstructionSet::RV32C,
+        EM_RISCV => InstructionSet::RV32,
+        EM_XTENSA => InstructionSet::Xtensa,
+        machine => bail!("Unknown architecture {machine} of the ELF file, use --instruction-set."),
+    })
+}
+
+/// Parses an instruction set, given as name or target triple.
+fn parse_instruction_set(input: &str) -> Result<InstructionSet, String> {
+    match input.to_ascii_lowercase().as_str() {
+        "thumb" | "thumb2" => Ok(InstructionSet::Thumb2),
+        "arm" | "a32" => Ok(InstructionSet::A32),
+        "aarch64" | "a64" => Ok(InstructionSet::A64),
+        "rv32" => Ok(InstructionSet::RV32),
+        "rv32c" => Ok(InstructionSet::RV32C),
+        "xtensa" => Ok(InstructionSet::Xtensa),
+        triple => InstructionSet::from_target_triple(triple)
+            .ok_or_else(|| format!("Unknown instruction set '{input}'.")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disassembly_is_annotated() {
+        let disassembler = disassembler_for(InstructionSet::Thumb2).unwrap();
+        let symbols = Symbols {
+            symbols: vec![(0x100, 6, "main".to_string())],
+        };
+        // push {r7, lr}; nop; nop; the first half of a 32-bit instruction
+        let code = [0x80, 0xb5, 0x00, 0xbf, 0x00, 0xbf, 0xff, 0xf7];
+
+        let lines = disassemble(Some(&disassembler), &code, 0xfe, &symbols, Some(0x102));
+
+        assert_eq!(
+            lines,
+            [
+                "   0x000000fe:  80 b5        push {r7, lr}",
+                "<main>:",
+                "   0x00000100:  00 bf        nop",
+                "=> 0x00000102:  00 bf        nop",
+                "   0x00000104:  ff f7        .data",
+            ]
+        );
+    }
+
+    #[test]
+    fn instruction_sets_are_parsed() {
+        assert_eq!(parse_instruction_set("rv32c"), Ok(InstructionSet::RV32C));
+        assert_eq!(
+            parse_instruction_set("thumbv7em-none-eabihf"),
+            Ok(InstructionSet::Thumb2)
+        );
+        assert!(parse_instruction_set("mips").is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+use anyhow::anyhow;
+use probe_rs::{
+    architecture::arm::{
+        dp::{DebugPortId, DPIDR},
+        sequences::DefaultArmSequence,
+        DpAddress, Register,
+    },
+    probe::{
+        list::Lister, DebugProbeError, DebugProbeInfo, DebugProbeSelector, Probe,
+        ProbeCreationError, WireProtocol,
+    },
+};
+use serde::Serialize;
+
+use crate::util::output::{print_json, OutputFormat};
+
+/// Check the setup for common problems
+///
+/// Checks the USB permissions and drivers, the connected probes and their firmware, the target
+/// power and the SWD connection to the target, and prints what can be done about the problems
+/// found.
+#[derive(clap::Parser)]
+pub struct Cmd {
+    /// Only check the probe matching this selector, given as 'VID:PID' or 'VID:PID:Serial'.
+    #[clap(long, env = "PROBE_RS_PROBE")]
+    probe: Option<DebugProbeSelector>,
+
+    /// Don't connect to the target, only check the host and the probes.
+    #[clap(long)]
+    no_connect: bool,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
+        let mut report = Report {
+            findings: vec![],
+            print: !output_format.is_json(),
+        };
+
+        check_udev_rules(&mut report);
+
+        let probes: Vec<_> = lister
+            .list_all()
+            .into_iter()
+            .filter(|probe| {
+                self.probe
+                    .as_ref()
+                    .map_or(true, |selector| selector.matches_probe(probe))
+            })
+            .collect();
+
+        if probes.is_empty() {
+            report.add(
+                "probes",
+                Severity::Error,
+                "No debug probe was found.",
+                Some(no_probe_hint()),
+            );
+        }
+
+        for info in &probes {
+            check_probe(&mut report, info, !self.no_connect);
+        }
+
+        if output_format.is_json() {
+            print_json("doctor", &report.findings)?;
+        }
+
+        match report.problems() {
+            0 => {
+                if report.print {
+                    println!();
+                    println!("No problems found.");
+                }
+                Ok(())
+            }
+            problems => Err(anyhow!("Found {problems} problem(s).")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// The result of a single check.
+#[derive(Serialize)]
+struct Finding {
+    check: String,
+    severity: Severity,
+    message: String,
+    /// What the user can do to fix the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+/// All findings of a run. In text mode, findings are printed as soon as they are added.
+struct Report {
+    findings: Vec<Finding>,
+    print: bool,
+}
+
+impl Report {
+    fn add(
+        &mut self,
+        check: impl Into<String>,
+        severity: Severity,
+        message: impl Into<String>,
+        hint: Option<String>,
+    ) {
+        let finding = Finding {
+            check: check.into(),
+            severity,
+            message: message.into(),
+            hint,
+        };
+
+        if self.print {
+            let label = match finding.severity {
+                Severity::Ok => "ok",
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            println!("[{label:>7}] {}: {}", finding.check, finding.message);
+            if let Some(hint) = &finding.hint {
+                println!("          hint: {hint}");
+            }
+        }
+
+        self.findings.push(finding);
+    }
+
+    /// The number of findings which are not ok.
+    fn problems(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity != Severity::Ok)
+            .count()
+    }
+}
+
+const UDEV_RULE_DIRECTORIES: [&str; 3] = [
+    "/etc/udev/rules.d",
+    "/usr/lib/udev/rules.d",
+    "/lib/udev/rules.d",
+];
+
+const PROBE_SETUP_URL: &str = "https://probe.rs/docs/getting-started/probe-setup/";
+
+/// Checks that udev rules giving users access to debug probes are installed.
+fn check_udev_rules(report: &mut Report) {
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+
+    let rules = UDEV_RULE_DIRECTORIES
+        .iter()
+        .filter_map(|directory| fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| is_probe_rule_file(path));
+
+    match rules {
+        Some(path) => report.add(
+            "udev",
+            Severity::Ok,
+            format!("Found udev rules for debug probes in {}", path.display()),
+            None,
+        ),
+        None => report.add(
+            "udev",
+            Severity::Warning,
+            "No udev rules for debug probes were found, so probes might only be usable as root.",
+            Some(format!(
+                "Install the udev rules as described in {PROBE_SETUP_URL}"
+            )),
+        ),
+    }
+}
+
+fn is_probe_rule_file(path: &Path) -> bool {
+    if path.extension() != Some("rules".as_ref()) {
+        return false;
+    }
+
+    let named_after_probe_rs = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.contains("probe-rs"));
+
+    named_after_probe_rs
+        || fs::read_to_string(path).is_ok_and(|content| content.contains("probe-rs"))
+}
+
+fn no_probe_hint() -> String {
+    if cfg!(windows) {
+        "Check the USB cable. Some probes need the WinUSB driver, which can be installed with Zadig."
+            .to_string()
+    } else if cfg!(target_os = "linux") {
+        format!(
+            "Check the USB cable, and that the udev rules from {PROBE_SETUP_URL} are installed."
+        )
+    } else {
+        "Check the USB cable.".to_string()
+    }
+}
+
+/// Returns what the user can do about a probe which could not be opened.
+fn open_error_hint(error: &DebugProbeError) -> String {
+    let permission_denied = match error {
+        DebugProbeError::Usb(error)
+        | DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::Usb(error)) => {
+            error.kind() == ErrorKind::PermissionDenied
+        }
+        DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::CouldNotOpen) => true,
+        _ => false,
+    };
+
+    if matches!(error, DebugProbeError::ProbeFirmwareOutdated) {
+        "Update the firmware of the probe with the updater of its vendor.".to_string()
+    } else if permission_denied && cfg!(target_os = "linux") {
+        format!("The probe can't be accessed. Install the udev rules from {PROBE_SETUP_URL}, and reconnect the probe.")
+    } else if permission_denied && cfg!(windows) {
+        "The probe can't be accessed. Make sure no other program uses it, and that it uses the WinUSB driver."
+            .to_string()
+    } else if permission_denied {
+        "The probe can't be accessed. Make sure no other program uses it.".to_string()
+    } else {
+        "Reconnect the probe, and make sure no other program uses it.".to_string()
+    }
+}
+
+fn check_probe(report: &mut Report, info: &DebugProbeInfo, connect: bool) {
+    let check = format!("probe {}", info.identifier);
+
+    let mut probe = match info.open() {
+        Ok(probe) => probe,
+        Err(error) => {
+            let hint = open_error_hint(&error);
+            report.add(
+                check,
+                Severity::Error,
+                format!("Failed to open the probe: {error}"),
+                Some(hint),
+            );
+            return;
+        }
+    };
+
+    let details = probe.details();
+    match &details.firmware_version {
+        Some(version) => report.add(
+            &check,
+            Severity::Ok,
+            format!("Opened {}, firmware {version}", info.probe_type()),
+            None,
+        ),
+        None => report.add(
+            &check,
+            Severity::Ok,
+            format!("Opened {}", info.probe_type()),
+            None,
+        ),
+    }
+
+    match details.target_voltage {
+        None => report.add(
+            &check,
+            Severity::Ok,
+            "The probe can't measure the target voltage.",
+            None,
+        ),
+        Some(voltage) if probe.target_unpowered() => report.add(
+            &check,
+            Severity::Error,
+            format!("The target voltage is {voltage:.2} V, the target does not seem to be powered."),
+            Some("Check the power supply of the target. Probes which can power the target can switch it on with `probe-rs power on`.".to_string()),
+        ),
+        Some(voltage) => report.add(
+            &check,
+            Severity::Ok,
+            format!("The target voltage is {voltage:.2} V."),
+            None,
+        ),
+    }
+
+    if connect {
+        check_connection(report, &check, probe);
+    }
+}
+
+/// Connects to the target, and reads the ID of the ARM debug port if the probe supports ARM.
+fn check_connection(report: &mut Report, check: &str, mut probe: Probe) {
+    let wiring_hint = Some(
+        "Check the wiring between probe and target, and that the target is powered. A lower speed, e.g. `--speed 100`, helps with long cables."
+            .to_string(),
+    );
+
+    // SWD is the most common protocol, but not every probe supports it.
+    let protocol = match probe.select_protocol(WireProtocol::Swd) {
+        Ok(()) => WireProtocol::Swd,
+        Err(_) => match probe.protocol() {
+            Some(protocol) => protocol,
+            None => {
+                report.add(
+                    check,
+                    Severity::Warning,
+                    "The probe does not support SWD, skipping the connection check.",
+                    None,
+                );
+                return;
+            }
+        },
+    };
+
+    if let Err(error) = probe.attach_to_unspecified() {
+        report.add(
+            check,
+            Severity::Error,
+            format!("Failed to connect to the target via {protocol}: {error}"),
+            wiring_hint,
+        );
+        return;
+    }
+
+    if !probe.has_arm_interface() {
+        report.add(
+            check,
+            Severity::Ok,
+            format!("Connected to the target via {protocol}."),
+            None,
+        );
+        return;
+    }
+
+    let result = match probe.try_into_arm_interface() {
+        Ok(interface) => {
+            match interface.initialize(DefaultArmSequence::create(), DpAddress::Default) {
+                Ok(mut interface) => {
+                    let result = interface.read_raw_dp_register(DpAddress::Default, DPIDR::ADDRESS);
+                    interface.close();
+                    result.map_err(|error| anyhow!(error))
+                }
+                Err((_, error)) => Err(anyhow!(error)),
+            }
+        }
+        Err((_, error)) => Err(anyhow!(error)),
+    };
+
+    match result {
+        Ok(dpidr) => {
+            let id = DebugPortId::from(DPIDR(dpidr));
+            report.add(
+                check,
+                Severity::Ok,
+                format!(
+                    "Connected to the target via {protocol}, found an ARM debug port {} by {}.",
+                    id.version,
+                    id.designer.get().unwrap_or("an unknown designer")
+                ),
+                None,
+            );
+        }
+        Err(error) => report.add(
+            check,
+            Severity::Error,
+            format!("Failed to read the ID of the ARM debug port via {protocol}: {error}"),
+            wiring_hint,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn outdated_firmware_hint() {
+        let hint = open_error_hint(&DebugProbeError::ProbeFirmwareOutdated);
+
+        assert!(hint.contains("Update the firmware"));
+    }
+
+    #[test]
+    fn permission_denied_hint() {
+        let error = DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::Usb(
+            std::io::Error::from(ErrorKind::PermissionDenied),
+        ));
+
+        assert!(open_error_hint(&error).contains("can't be accessed"));
+    }
+
+    #[test]
+    fn problems_are_counted() {
+        let mut report = Report {
+            findings: vec![],
+            print: false,
+        };
+
+        report.add("a", Severity::Ok, "fine", None);
+        report.add("b", Severity::Warning, "not quite", None);
+        report.add("c", Severity::Error, "broken", Some("fix it".to_string()));
+
+        assert_eq!(report.problems(), 2);
+    }
+}
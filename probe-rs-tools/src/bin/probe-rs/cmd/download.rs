@@ -4,8 +4,9 @@ use probe_rs::probe::list::Lister;
 
 use crate::util::common_options::BinaryDownloadOptions;
 use crate::util::common_options::ProbeOptions;
-use crate::util::flash::build_loader;
+use crate::util::devices::{for_each_device, DeviceOptions};
 use crate::util::flash::run_flash_download;
+use crate::util::flash::{add_image, build_loader};
 use crate::FormatOptions;
 
 #[derive(clap::Parser)]
@@ -13,9 +14,17 @@ pub struct Cmd {
     #[clap(flatten)]
     probe_options: ProbeOptions,
 
+    #[clap(flatten)]
+    device_options: DeviceOptions,
+
     /// The path to the file to be downloaded to the flash
     path: String,
 
+    /// Further files to download together with the first one, e.g. the firmware of the second
+    /// core of a dual-core chip. They must not overlap with each other.
+    #[clap(long = "additional-image", value_name = "PATH")]
+    additional_images: Vec<String>,
+
     /// Whether to erase the entire chip before downloading
     #[clap(long)]
     chip_erase: bool,
@@ -29,13 +38,37 @@ pub struct Cmd {
 
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
-        let (mut session, probe_options) = self.probe_options.simple_attach(lister)?;
+        let devices = self.device_options.devices()?;
+        if devices.is_empty() {
+            return self.download(lister, self.probe_options.clone(), &self.download_options);
+        }
+
+        // Progress bars of several devices would overwrite each other.
+        let mut download_options = self.download_options.clone();
+        download_options.disable_progressbars = true;
+
+        for_each_device(&devices, &self.probe_options, |probe_options, _| {
+            // The lister can't be shared between threads.
+            self.download(&Lister::new(), probe_options, &download_options)
+        })
+    }
+
+    fn download(
+        &self,
+        lister: &Lister,
+        probe_options: ProbeOptions,
+        download_options: &BinaryDownloadOptions,
+    ) -> anyhow::Result<()> {
+        let (mut session, probe_options) = probe_options.simple_attach(lister)?;
 
-        let loader = build_loader(&mut session, &self.path, self.format_options, None)?;
+        let mut loader = build_loader(&mut session, &self.path, self.format_options.clone(), None)?;
+        for path in &self.additional_images {
+            add_image(&mut session, &mut loader, path, self.format_options.clone())?;
+        }
         run_flash_download(
             &mut session,
             Path::new(&self.path),
-            &self.download_options,
+            download_options,
             &probe_options,
             loader,
             self.chip_erase,
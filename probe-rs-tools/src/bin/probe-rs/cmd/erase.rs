@@ -2,11 +2,18 @@ use std::cell::RefCell;
 
 use indicatif::{MultiProgress, ProgressBar};
 use probe_rs::{
-    flashing::{erase_all, FlashProgress, ProgressEvent},
+    flashing::{erase_all_cancellable, FlashProgress, ProgressEvent},
     probe::list::Lister,
 };
 
-use crate::util::{common_options::ProbeOptions, flash::ProgressBarGroup, logging};
+use serde::Serialize;
+
+use crate::util::{
+    common_options::ProbeOptions,
+    flash::{CtrlCCancellation, ProgressBarGroup},
+    logging,
+    output::{print_json, OutputFormat},
+};
 
 #[derive(clap::Parser)]
 pub struct Cmd {
@@ -18,13 +25,14 @@ pub struct Cmd {
 }
 
 impl Cmd {
-    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+    pub fn run(self, lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
         let (mut session, _probe_options) = self.common.simple_attach(lister)?;
 
         let multi_progress = MultiProgress::new();
         logging::set_progress_bar(multi_progress.clone());
 
-        let progress = if !self.disable_progressbars {
+        // Only the result is printed in JSON mode.
+        let progress = if !self.disable_progressbars && !output_format.is_json() {
             let progress_bars = RefCell::new(ProgressBarGroup::new("Erasing"));
 
             FlashProgress::new(move |event| {
@@ -58,8 +66,23 @@ impl Cmd {
             FlashProgress::empty()
         };
 
-        erase_all(&mut session, progress)?;
+        let ctrl_c = CtrlCCancellation::register()?;
+        erase_all_cancellable(&mut session, progress, ctrl_c.token())?;
+
+        if output_format.is_json() {
+            print_json(
+                "erase",
+                &EraseResult {
+                    target: &session.target().name,
+                },
+            )?;
+        }
 
         Ok(())
     }
 }
+
+#[derive(Serialize)]
+struct EraseResult<'a> {
+    target: &'a str,
+}
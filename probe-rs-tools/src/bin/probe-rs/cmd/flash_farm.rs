@@ -0,0 +1,299 @@
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::Context;
+use itertools::Itertools;
+use probe_rs::flashing::{DownloadOptions, FlashError, FlashLoader};
+use probe_rs::probe::{list::Lister, DebugProbeSelector};
+use probe_rs::MemoryInterface;
+use serde::Serialize;
+
+use crate::util::common_options::ProbeOptions;
+use crate::util::flash::build_loader;
+use crate::util::parse_u64;
+use crate::util::patch::PatchOptions;
+use crate::FormatOptions;
+
+/// Programs many boards with the same image, for production.
+///
+/// The image is loaded once, and then programmed to a board on every probe, or only on the
+/// probe given with `--probe`. With `--count`, the operator is asked to connect the next boards
+/// in between.
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// The path to the file to be programmed.
+    path: PathBuf,
+
+    #[clap(flatten)]
+    format_options: FormatOptions,
+
+    /// Per-unit data, like serial numbers. Unit N uses row `--patch-row` + N - 1 of the
+    /// patch manifest.
+    #[clap(flatten)]
+    patches: PatchOptions,
+
+    /// The number of boards to program on every probe.
+    #[clap(long, default_value_t = 1)]
+    count: usize,
+
+    /// Program the boards on all probes at the same time, instead of one after another.
+    #[clap(long)]
+    parallel: bool,
+
+    /// Verify the flash contents after programming.
+    #[clap(long)]
+    verify: bool,
+
+    /// Erase the entire chip before programming.
+    #[clap(long)]
+    chip_erase: bool,
+
+    /// Read the serial number of every unit from this address, e.g. a unique device ID register.
+    #[clap(long, value_parser = parse_u64)]
+    serial_address: Option<u64>,
+
+    /// The size of the serial number in bytes.
+    #[clap(long, default_value_t = 8, requires = "serial_address")]
+    serial_size: usize,
+
+    /// Write the results of all units to this file, as JSON if its name ends in `.json`,
+    /// and as CSV otherwise.
+    #[clap(long, value_name = "PATH")]
+    results: Option<PathBuf>,
+}
+
+/// The result of programming a single unit.
+#[derive(Debug, Serialize)]
+struct UnitResult {
+    unit: usize,
+    probe: String,
+    serial: Option<String>,
+    duration_ms: u128,
+    success: bool,
+    /// Whether the flash contents were verified, if `--verify` was given.
+    verified: Option<bool>,
+    error: Option<String>,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let probes: Vec<DebugProbeSelector> = match &self.probe_options.probe {
+            Some(probe) => vec![probe.clone()],
+            None => lister.list_all().iter().map(Into::into).collect(),
+        };
+        anyhow::ensure!(!probes.is_empty(), "No connected probes were found.");
+
+        // The image only has to be loaded once, it is the same for all units.
+        let loader = {
+            let (mut session, _) = self.probe_options_for(&probes[0]).simple_attach(lister)?;
+            build_loader(&mut session, &self.path, self.format_options.clone(), None)?
+        };
+
+        let mut results = Vec::new();
+        for round in 0..self.count {
+            if round > 0 {
+                prompt_for_next_boards()?;
+            }
+
+            let first_unit = results.len() + 1;
+            if self.parallel {
+                std::thread::scope(|scope| {
+                    let threads: Vec<_> = probes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, probe)| {
+                            let (cmd, loader) = (&self, &loader);
+                            // The lister can't be shared between threads.
+                            scope.spawn(move || {
+                                cmd.program_unit(&Lister::new(), probe, loader, first_unit + i)
+                            })
+                        })
+                        .collect();
+
+                    for thread in threads {
+                        results.push(thread.join().expect("The programming thread panicked"));
+                    }
+                });
+            } else {
+                for (i, probe) in probes.iter().enumerate() {
+                    results.push(self.program_unit(lister, probe, &loader, first_unit + i));
+                }
+            }
+        }
+
+        if let Some(path) = &self.results {
+            write_results(path, &results)?;
+        }
+
+        let failed = results.iter().filter(|unit| !unit.success).count();
+        println!(
+            "Programmed {} of {} units successfully",
+            results.len() - failed,
+            results.len()
+        );
+        if failed > 0 {
+            anyhow::bail!("{failed} units failed");
+        }
+
+        Ok(())
+    }
+
+    fn probe_options_for(&self, probe: &DebugProbeSelector) -> ProbeOptions {
+        let mut probe_options = self.probe_options.clone();
+        probe_options.probe = Some(probe.clone());
+        probe_options
+    }
+
+    fn program_unit(
+        &self,
+        lister: &Lister,
+        probe: &DebugProbeSelector,
+        loader: &FlashLoader,
+        unit: usize,
+    ) -> UnitResult {
+        let start = Instant::now();
+        let mut serial = None;
+
+        let result = (|| {
+            let (mut session, probe_options) =
+                self.probe_options_for(probe).simple_attach(lister)?;
+
+            if let Some(address) = self.serial_address {
+                let mut data = vec![0; self.serial_size];
+                session.core(0)?.read(address, &mut data)?;
+                serial = Some(data.iter().map(|byte| format!("{byte:02x}")).join(""));
+            }
+
+            let mut options = DownloadOptions::default();
+            options.verify = self.verify;
+            options.do_chip_erase = self.chip_erase;
+            options.dry_run = probe_options.dry_run();
+
+            if self.patches.patches.is_empty() && self.patches.patch_manifest.is_none() {
+                loader.commit(&mut session, options)?;
+            } else {
+                let mut loader = loader.clone();
+                self.patches
+                    .apply(&mut loader, &self.path, self.patches.patch_row + unit - 1)?;
+                loader.commit(&mut session, options)?;
+            }
+
+            anyhow::Ok(())
+        })();
+
+        let unit_result = UnitResult {
+            unit,
+            probe: probe.to_string(),
+            serial,
+            duration_ms: start.elapsed().as_millis(),
+            success: result.is_ok(),
+            verified: self.verify.then(|| match &result {
+                Ok(()) => true,
+                Err(error) => !matches!(error.downcast_ref(), Some(FlashError::Verify)),
+            }),
+            error: result.err().map(|error| format!("{error:#}")),
+        };
+
+        match &unit_result.error {
+            None => eprintln!(
+                "Unit {unit} ({}): programmed in {} ms",
+                unit_result.probe, unit_result.duration_ms
+            ),
+            Some(error) => eprintln!("Unit {unit} ({}): failed: {error}", unit_result.probe),
+        }
+
+        unit_result
+    }
+}
+
+fn prompt_for_next_boards() -> anyhow::Result<()> {
+    print!("Connect the next boards and press Enter to continue...");
+    std::io::stdout().flush()?;
+    std::io::stdin().lock().read_line(&mut String::new())?;
+    Ok(())
+}
+
+fn write_results(path: &Path, results: &[UnitResult]) -> anyhow::Result<()> {
+    let mut file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        serde_json::to_writer_pretty(&mut file, results)?;
+    } else {
+        write_csv(&mut file, results)?;
+    }
+
+    Ok(())
+}
+
+fn write_csv(mut csv: impl Write, results: &[UnitResult]) -> std::io::Result<()> {
+    fn field(value: Option<impl ToString>) -> String {
+        let value = value.map(|value| value.to_string()).unwrap_or_default();
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+
+    writeln!(csv, "unit,probe,serial,duration_ms,success,verified,error")?;
+    for unit in results {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{}",
+            unit.unit,
+            field(Some(&unit.probe)),
+            field(unit.serial.as_ref()),
+            unit.duration_ms,
+            unit.success,
+            unit.verified
+                .map(|verified| verified.to_string())
+                .unwrap_or_default(),
+            field(unit.error.as_ref()),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_results() {
+        let results = [
+            UnitResult {
+                unit: 1,
+                probe: "0483:374b:0001".to_string(),
+                serial: Some("0011aabb".to_string()),
+                duration_ms: 1200,
+                success: true,
+                verified: Some(true),
+                error: None,
+            },
+            UnitResult {
+                unit: 2,
+                probe: "0483:374b:0002".to_string(),
+                serial: None,
+                duration_ms: 50,
+                success: false,
+                verified: None,
+                error: Some("Probe \"2\" is gone".to_string()),
+            },
+        ];
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &results).unwrap();
+
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "unit,probe,serial,duration_ms,success,verified,error\n\
+             1,\"0483:374b:0001\",\"0011aabb\",1200,true,true,\"\"\n\
+             2,\"0483:374b:0002\",\"\",50,false,,\"Probe \"\"2\"\" is gone\"\n"
+        );
+    }
+}
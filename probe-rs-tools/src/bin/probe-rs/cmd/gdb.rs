@@ -1,9 +1,11 @@
 use std::time::Duration;
 
+use anyhow::Context;
+
 use parking_lot::FairMutex;
-use probe_rs::probe::list::Lister;
+use probe_rs::{probe::list::Lister, VectorCatchCondition};
 
-use crate::util::common_options::ProbeOptions;
+use crate::util::{common_options::ProbeOptions, server_address::check_connection_string};
 
 #[derive(clap::Parser)]
 pub struct Cmd {
@@ -20,12 +22,32 @@ pub struct Cmd {
     )]
     reset_halt: bool,
 
+    /// Halt the cores on the given exceptions, e.g. `--catch hard-fault,bus-fault`.
+    ///
+    /// Possible values are hard-fault, core-reset, secure-fault, bus-fault, mem-manage,
+    /// usage-fault and all.
+    #[clap(long, value_delimiter = ',', value_name = "CONDITION")]
+    catch: Vec<VectorCatchCondition>,
+
+    /// Allow listening on addresses which can be reached from other machines.
+    ///
+    /// The GDB protocol has no authentication, so anybody who can reach the server has full
+    /// access to the target.
+    #[clap(long)]
+    allow_remote: bool,
+
     #[clap(flatten)]
     common: ProbeOptions,
 }
 
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let gdb_connection_string = self
+            .gdb_connection_string
+            .unwrap_or_else(|| "localhost:1337".to_string());
+
+        check_connection_string(&gdb_connection_string, self.allow_remote)?;
+
         let (mut session, _probe_options) = self.common.simple_attach(lister)?;
 
         if self.reset_halt {
@@ -34,9 +56,14 @@ impl Cmd {
                 .reset_and_halt(Duration::from_millis(100))?;
         }
 
-        let gdb_connection_string = self
-            .gdb_connection_string
-            .unwrap_or_else(|| "localhost:1337".to_string());
+        for core_index in 0..session.target().cores.len() {
+            let mut core = session.core(core_index)?;
+            for &condition in &self.catch {
+                core.enable_vector_catch(condition).with_context(|| {
+                    format!("Failed to enable the {condition} vector catch on core {core_index}")
+                })?;
+            }
+        }
 
         let instances = probe_rs::gdb_server::GdbInstanceConfiguration::from_session(
             &session,
@@ -24,9 +24,11 @@ use probe_rs::{
     probe::{list::Lister, Probe, WireProtocol},
     MemoryMappedRegister,
 };
+use serde::{Serialize, Serializer};
 use termtree::Tree;
 
 use crate::util::common_options::ProbeOptions;
+use crate::util::output::{print_json, OutputFormat};
 
 const JEP_ARM: JEP106Code = JEP106Code::new(4, 0x3b);
 
@@ -54,7 +56,7 @@ fn parse_hex(src: &str) -> Result<u32, std::num::ParseIntError> {
 }
 
 impl Cmd {
-    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+    pub fn run(self, lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
         let probe_options = self.common.load()?;
         let mut probe = probe_options.attach_probe(lister)?;
 
@@ -64,15 +66,37 @@ impl Cmd {
             vec![WireProtocol::Jtag, WireProtocol::Swd]
         };
 
-        for protocol in protocols {
-            println!("Probing target via {protocol}");
+        let print = !output_format.is_json();
+        let mut infos = vec![];
+
+        if print {
+            println!("Probe: {}", probe.get_name());
+            let details = probe.details();
+            crate::cmd::list::print_details(&details);
+            match details.target_voltage {
+                None => println!("       Target voltage: can't be measured by this probe"),
+                Some(_) if probe.target_unpowered() => {
+                    println!("       The target does not seem to be powered.")
+                }
+                Some(_) => {}
+            }
             println!();
+        }
+
+        for protocol in protocols {
+            if print {
+                println!("Probing target via {protocol}");
+                println!();
+            }
+
+            let mut info = ProtocolInfo::new(protocol, print);
 
             let (new_probe, result) = try_show_info(
                 probe,
                 protocol,
                 probe_options.connect_under_reset(),
                 self.target_sel,
+                &mut info,
             );
 
             probe = new_probe;
@@ -80,16 +104,142 @@ impl Cmd {
             probe.detach()?;
 
             if let Err(e) = result {
-                println!("Error identifying target using protocol {protocol}: {e}");
+                info.message(format!(
+                    "Error identifying target using protocol {protocol}: {e}"
+                ));
             }
 
-            println!();
+            if print {
+                println!();
+            }
+
+            infos.push(info);
+        }
+
+        if output_format.is_json() {
+            print_json("info", &infos)?;
         }
 
         Ok(())
     }
 }
 
+/// Information found about the target using a single protocol.
+///
+/// In text mode, everything is printed as soon as it is added.
+#[derive(Serialize)]
+struct ProtocolInfo {
+    protocol: String,
+    debug_ports: Vec<DebugPortInfo>,
+    riscv: Option<IdCodeInfo>,
+    xtensa: Option<IdCodeInfo>,
+    /// Errors and notes encountered while probing the target.
+    messages: Vec<String>,
+    #[serde(skip)]
+    print: bool,
+}
+
+impl ProtocolInfo {
+    fn new(protocol: WireProtocol, print: bool) -> Self {
+        Self {
+            protocol: protocol.to_string(),
+            debug_ports: vec![],
+            riscv: None,
+            xtensa: None,
+            messages: vec![],
+            print,
+        }
+    }
+
+    fn message(&mut self, message: String) {
+        if self.print {
+            println!("{message}");
+        }
+        self.messages.push(message);
+    }
+
+    fn debug_port(&mut self, debug_port: DebugPortInfo) {
+        if self.print {
+            println!("ARM Chip with debug port {}:", debug_port.address);
+            if debug_port.tree.leaves.is_empty() {
+                println!("No access ports found on this chip.");
+            } else {
+                println!("{}", debug_port.tree);
+            }
+            println!();
+        }
+        self.debug_ports.push(debug_port);
+    }
+}
+
+#[derive(Serialize)]
+struct DebugPortInfo {
+    address: String,
+    /// The debug port, with the access ports and their components as children.
+    #[serde(serialize_with = "serialize_tree")]
+    tree: Tree<String>,
+}
+
+#[derive(Serialize)]
+struct TreeNode<'a> {
+    name: &'a str,
+    children: Vec<TreeNode<'a>>,
+}
+
+impl<'a> From<&'a Tree<String>> for TreeNode<'a> {
+    fn from(tree: &'a Tree<String>) -> Self {
+        Self {
+            name: &tree.root,
+            children: tree.leaves.iter().map(TreeNode::from).collect(),
+        }
+    }
+}
+
+fn serialize_tree<S: Serializer>(tree: &Tree<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    TreeNode::from(tree).serialize(serializer)
+}
+
+#[derive(Serialize)]
+struct IdCodeInfo {
+    idcode: u32,
+    version: u32,
+    part: u32,
+    manufacturer_id: u32,
+    manufacturer: String,
+}
+
+impl IdCodeInfo {
+    fn new(idcode: u32) -> Self {
+        let version = (idcode >> 28) & 0xf;
+        let part = (idcode >> 12) & 0xffff;
+        let manufacturer_id = (idcode >> 1) & 0x7ff;
+
+        let jep_cc = (manufacturer_id >> 7) & 0xf;
+        let jep_id = manufacturer_id & 0x7f;
+
+        let jep_id = jep106::JEP106Code::new(jep_cc as u8, jep_id as u8);
+
+        Self {
+            idcode,
+            version,
+            part,
+            manufacturer_id,
+            manufacturer: jep_id.to_string(),
+        }
+    }
+
+    fn print(&self, architecture: &str) {
+        println!("{architecture} Chip:");
+        println!("  IDCODE: {:010x}", self.idcode);
+        println!("    Version:      {}", self.version);
+        println!("    Part:         {}", self.part);
+        println!(
+            "    Manufacturer: {} ({})",
+            self.manufacturer_id, self.manufacturer
+        );
+    }
+}
+
 const ALTERNATE_DP_ADRESSES: [DpAddress; 2] = [
     DpAddress::Multidrop(0x01002927),
     DpAddress::Multidrop(0x11002927),
@@ -100,6 +250,7 @@ fn try_show_info(
     protocol: WireProtocol,
     connect_under_reset: bool,
     target_sel: Option<u32>,
+    info: &mut ProtocolInfo,
 ) -> (Probe, Result<()>) {
     if let Err(e) = probe.select_protocol(protocol) {
         return (probe, Err(e.into()));
@@ -122,34 +273,36 @@ fn try_show_info(
             DpAddress::Default
         };
 
-        let print_err = |dp_addr, e| {
-            println!(
+        let print_err = |info: &mut ProtocolInfo, dp_addr, e| {
+            info.message(format!(
                 "Error showing ARM chip information for Debug Port {:?}: {:?}",
                 dp_addr, e
-            );
-            println!();
+            ));
+            if info.print {
+                println!();
+            }
         };
-        match try_show_arm_dp_info(probe, dp_addr) {
+        match try_show_arm_dp_info(probe, dp_addr, info) {
             (probe_moved, Ok(_)) => probe = probe_moved,
             (probe_moved, Err(e)) => {
                 probe = probe_moved;
-                print_err(dp_addr, e);
+                print_err(info, dp_addr, e);
 
                 if dp_addr == DpAddress::Default {
-                    println!("Trying alternate multi-drop debug ports");
+                    info.message("Trying alternate multi-drop debug ports".to_string());
 
                     for address in ALTERNATE_DP_ADRESSES {
-                        match try_show_arm_dp_info(probe, address) {
+                        match try_show_arm_dp_info(probe, address, info) {
                             (probe_moved, Ok(dp_version)) => {
                                 probe = probe_moved;
                                 if dp_version < DebugPortVersion::DPv2 {
-                                    println!("Debug port version {} does not support SWD multidrop. Stopping here.", dp_version);
+                                    info.message(format!("Debug port version {} does not support SWD multidrop. Stopping here.", dp_version));
                                     break;
                                 }
                             }
                             (probe_moved, Err(e)) => {
                                 probe = probe_moved;
-                                print_err(address, e);
+                                print_err(info, address, e);
                             }
                         }
                     }
@@ -157,7 +310,7 @@ fn try_show_info(
             }
         }
     } else {
-        println!("No DAP interface was found on the connected probe. ARM-specific information cannot be printed.");
+        info.message("No DAP interface was found on the connected probe. ARM-specific information cannot be printed.".to_string());
     }
 
     // This check is a bit weird, but `try_into_riscv_interface` will try to switch the protocol to JTAG.
@@ -169,25 +322,28 @@ fn try_show_info(
                 let mut state = factory.create_state();
                 match factory.attach(&mut state) {
                     Ok(mut interface) => {
-                        if let Err(e) = show_riscv_info(&mut interface) {
-                            println!("Error showing RISC-V chip information: {:?}", anyhow!(e));
+                        if let Err(e) = show_riscv_info(&mut interface, info) {
+                            info.message(format!(
+                                "Error showing RISC-V chip information: {:?}",
+                                anyhow!(e)
+                            ));
                         }
                     }
-                    Err(e) => println!(
+                    Err(e) => info.message(format!(
                         "Error while attaching to RISC-V interface: {:?}",
                         anyhow!(e)
-                    ),
+                    )),
                 };
             }
-            Err(e) => println!("Error while reading RISC-V info: {:?}", anyhow!(e)),
+            Err(e) => info.message(format!("Error while reading RISC-V info: {:?}", anyhow!(e))),
         }
     } else if protocol == WireProtocol::Swd {
-        println!(
-            "Debugging RISC-V targets over SWD is not supported. For these targets, JTAG is the only supported protocol. RISC-V specific information cannot be printed."
+        info.message(
+            "Debugging RISC-V targets over SWD is not supported. For these targets, JTAG is the only supported protocol. RISC-V specific information cannot be printed.".to_string()
         );
     } else {
-        println!(
-            "Unable to debug RISC-V targets using the current probe. RISC-V specific information cannot be printed."
+        info.message(
+            "Unable to debug RISC-V targets using the current probe. RISC-V specific information cannot be printed.".to_string()
         );
     }
 
@@ -198,28 +354,38 @@ fn try_show_info(
         let mut state = XtensaDebugInterfaceState::default();
         match probe.try_get_xtensa_interface(&mut state) {
             Ok(mut interface) => {
-                if let Err(e) = show_xtensa_info(&mut interface) {
-                    println!("Error showing Xtensa chip information: {:?}", anyhow!(e));
+                if let Err(e) = show_xtensa_info(&mut interface, info) {
+                    info.message(format!(
+                        "Error showing Xtensa chip information: {:?}",
+                        anyhow!(e)
+                    ));
                 }
             }
             Err(e) => {
-                println!("Error showing Xtensa chip information: {:?}", anyhow!(e));
+                info.message(format!(
+                    "Error showing Xtensa chip information: {:?}",
+                    anyhow!(e)
+                ));
             }
         }
     } else if protocol == WireProtocol::Swd {
-        println!(
-            "Debugging Xtensa targets over SWD is not supported. For these targets, JTAG is the only supported protocol. Xtensa specific information cannot be printed."
+        info.message(
+            "Debugging Xtensa targets over SWD is not supported. For these targets, JTAG is the only supported protocol. Xtensa specific information cannot be printed.".to_string()
         );
     } else {
-        println!(
-            "Unable to debug Xtensa targets using the current probe. Xtensa specific information cannot be printed."
+        info.message(
+            "Unable to debug Xtensa targets using the current probe. Xtensa specific information cannot be printed.".to_string()
         );
     }
 
     (probe, Ok(()))
 }
 
-fn try_show_arm_dp_info(probe: Probe, dp_address: DpAddress) -> (Probe, Result<DebugPortVersion>) {
+fn try_show_arm_dp_info(
+    probe: Probe,
+    dp_address: DpAddress,
+    info: &mut ProtocolInfo,
+) -> (Probe, Result<DebugPortVersion>) {
     tracing::debug!("Trying to show ARM chip information");
     match probe
         .try_into_arm_interface()
@@ -230,7 +396,7 @@ fn try_show_arm_dp_info(probe: Probe, dp_address: DpAddress) -> (Probe, Result<D
                 .map_err(|(interface, e)| (interface.close(), anyhow!(e)))
         }) {
         Ok(mut interface) => {
-            let res = show_arm_info(&mut *interface, dp_address);
+            let res = show_arm_info(&mut *interface, dp_address, info);
             (interface.close(), res)
         }
         Err((probe, e)) => (probe, Err(e)),
@@ -240,7 +406,11 @@ fn try_show_arm_dp_info(probe: Probe, dp_address: DpAddress) -> (Probe, Result<D
 /// Try to show information about the ARM chip, connected to a DP at the given address.
 ///
 /// Returns the version of the DP.
-fn show_arm_info(interface: &mut dyn ArmProbeInterface, dp: DpAddress) -> Result<DebugPortVersion> {
+fn show_arm_info(
+    interface: &mut dyn ArmProbeInterface,
+    dp: DpAddress,
+    info: &mut ProtocolInfo,
+) -> Result<DebugPortVersion> {
     let dp_info = interface.read_raw_dp_register(dp, DPIDR::ADDRESS)?;
     let dp_info = DebugPortId::from(DPIDR(dp_info));
 
@@ -292,48 +462,45 @@ fn show_arm_info(interface: &mut dyn ArmProbeInterface, dp: DpAddress) -> Result
     let mut tree = Tree::new(dp_node);
 
     let access_ports = interface.access_ports(dp)?;
-    println!("ARM Chip with debug port {:x?}:", dp);
-    if access_ports.is_empty() {
-        println!("No access ports found on this chip.");
-    } else {
-        for ap_address in access_ports {
-            use probe_rs::architecture::arm::ap::IDR;
-            let idr: IDR = interface
-                .read_raw_ap_register(&ap_address, IDR::ADDRESS)?
-                .try_into()?;
-
-            if idr.CLASS == ApClass::MemAp {
-                let mut ap_nodes =
-                    Tree::new(format!("{} MemoryAP ({:?})", ap_address.ap_v1()?, idr.TYPE));
-                match handle_memory_ap(interface, &ap_address) {
-                    Ok(component_tree) => ap_nodes.push(component_tree),
-                    Err(e) => ap_nodes.push(format!("Error during access: {e}")),
-                };
-                tree.push(ap_nodes);
-            } else {
-                let jep = idr.DESIGNER;
+    for ap_address in access_ports {
+        use probe_rs::architecture::arm::ap::IDR;
+        let idr: IDR = interface
+            .read_raw_ap_register(&ap_address, IDR::ADDRESS)?
+            .try_into()?;
+
+        if idr.CLASS == ApClass::MemAp {
+            let mut ap_nodes =
+                Tree::new(format!("{} MemoryAP ({:?})", ap_address.ap_v1()?, idr.TYPE));
+            match handle_memory_ap(interface, &ap_address) {
+                Ok(component_tree) => ap_nodes.push(component_tree),
+                Err(e) => ap_nodes.push(format!("Error during access: {e}")),
+            };
+            tree.push(ap_nodes);
+        } else {
+            let jep = idr.DESIGNER;
 
-                let ap_type = if idr.DESIGNER == JEP_ARM {
-                    format!("{:?}", idr.TYPE)
-                } else {
-                    format!("{:#x}", idr.TYPE as u8)
-                };
+            let ap_type = if idr.DESIGNER == JEP_ARM {
+                format!("{:?}", idr.TYPE)
+            } else {
+                format!("{:#x}", idr.TYPE as u8)
+            };
 
-                tree.push(format!(
-                    "{} Unknown AP (Designer: {}, Class: {:?}, Type: {}, Variant: {:#x}, Revision: {:#x})",
-                    ap_address.ap_v1()?,
-                    jep.get().unwrap_or("<unknown>"),
-                    idr.CLASS,
-                    ap_type,
-                    idr.VARIANT,
-                    idr.REVISION
-                ));
-            }
+            tree.push(format!(
+                "{} Unknown AP (Designer: {}, Class: {:?}, Type: {}, Variant: {:#x}, Revision: {:#x})",
+                ap_address.ap_v1()?,
+                jep.get().unwrap_or("<unknown>"),
+                idr.CLASS,
+                ap_type,
+                idr.VARIANT,
+                idr.REVISION
+            ));
         }
-
-        println!("{tree}");
     }
-    println!();
+
+    info.debug_port(DebugPortInfo {
+        address: format!("{:x?}", dp),
+        tree,
+    });
 
     Ok(dp_info.version)
 }
@@ -499,45 +666,51 @@ fn cpu_info_tree(scs: &mut Scs) -> Result<Tree<String>> {
     Ok(tree)
 }
 
-fn show_riscv_info(interface: &mut RiscvCommunicationInterface) -> Result<()> {
+fn show_riscv_info(
+    interface: &mut RiscvCommunicationInterface,
+    info: &mut ProtocolInfo,
+) -> Result<()> {
     if let Some(idcode) = interface.read_idcode()? {
-        print_idcode_info("RISC-V", idcode);
+        let idcode = IdCodeInfo::new(idcode);
+        if info.print {
+            idcode.print("RISC-V");
+        }
+        info.riscv = Some(idcode);
     } else {
-        println!("No IDCODE info for this RISC-V chip.")
+        info.message("No IDCODE info for this RISC-V chip.".to_string());
     }
 
     Ok(())
 }
 
-fn show_xtensa_info(interface: &mut XtensaCommunicationInterface) -> Result<()> {
-    let idcode = interface.read_idcode()?;
-
-    print_idcode_info("Xtensa", idcode);
+fn show_xtensa_info(
+    interface: &mut XtensaCommunicationInterface,
+    info: &mut ProtocolInfo,
+) -> Result<()> {
+    let idcode = IdCodeInfo::new(interface.read_idcode()?);
+    if info.print {
+        idcode.print("Xtensa");
+    }
+    info.xtensa = Some(idcode);
 
     Ok(())
 }
 
-fn print_idcode_info(architecture: &str, idcode: u32) {
-    let version = (idcode >> 28) & 0xf;
-    let part_number = (idcode >> 12) & 0xffff;
-    let manufacturer_id = (idcode >> 1) & 0x7ff;
-
-    let jep_cc = (manufacturer_id >> 7) & 0xf;
-    let jep_id = manufacturer_id & 0x7f;
-
-    let jep_id = jep106::JEP106Code::new(jep_cc as u8, jep_id as u8);
-
-    println!("{architecture} Chip:");
-    println!("  IDCODE: {idcode:010x}");
-    println!("    Version:      {version}");
-    println!("    Part:         {part_number}");
-    println!("    Manufacturer: {manufacturer_id} ({jep_id})");
-}
-
 #[cfg(test)]
 mod tests {
     #[test]
     fn jep_arm_is_arm() {
         assert_eq!(super::JEP_ARM.get(), Some("ARM Ltd"))
     }
+
+    #[test]
+    fn idcode_is_decoded() {
+        // IDCODE of the ARM JTAG-DP
+        let info = super::IdCodeInfo::new(0x4ba0_0477);
+
+        assert_eq!(info.version, 4);
+        assert_eq!(info.part, 0xba00);
+        assert_eq!(info.manufacturer_id, 0x23b);
+        assert_eq!(info.manufacturer, "ARM Ltd");
+    }
 }
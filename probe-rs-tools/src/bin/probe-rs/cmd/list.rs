@@ -1,16 +1,63 @@
-use probe_rs::probe::list::Lister;
+use probe_rs::probe::{
+    list::{Lister, ProbeEvent},
+    xds110::{self, Xds110Factory},
+    DebugProbeInfo, ProbeDetails,
+};
+use serde::Serialize;
+
+use crate::util::output::{print_json, OutputFormat};
 
 #[derive(clap::Parser)]
-pub struct Cmd {}
+pub struct Cmd {
+    /// Keep running and print probes as they are connected or disconnected.
+    #[clap(long)]
+    watch: bool,
+    /// Open each probe and show its firmware version, capabilities and the target voltage.
+    #[clap(long, conflicts_with = "watch")]
+    details: bool,
+}
 
 impl Cmd {
-    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+    pub fn run(self, lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
+        if self.watch {
+            return watch(lister, output_format);
+        }
+
         let probes = lister.list_all();
 
+        if output_format.is_json() {
+            let probes: Vec<_> = probes
+                .iter()
+                .map(|probe| {
+                    let mut entry = ProbeEntry::from(probe);
+                    if self.details {
+                        entry.details = probe.open().ok().map(|mut probe| probe.details());
+                    }
+                    entry
+                })
+                .collect();
+            return print_json("list", &probes);
+        }
+
         if !probes.is_empty() {
             println!("The following debug probes were found:");
             for (num, link) in probes.iter().enumerate() {
-                println!("[{num}]: {link}");
+                match &link.usb_port {
+                    Some(port) => println!("[{num}]: {link} at usb:{port}"),
+                    None => println!("[{num}]: {link}"),
+                }
+                if self.details {
+                    match link.open() {
+                        Ok(mut probe) => print_details(&probe.details()),
+                        Err(e) => println!("       Failed to open the probe: {e}"),
+                    }
+                }
+                for uart in uarts(link) {
+                    match uart.path {
+                        Some(path) => println!("       UART: {} ({})", uart.name, path.display()),
+                        None => println!("       UART: {}", uart.name),
+                    }
+                }
             }
         } else {
             println!("No debug probes were found.");
@@ -18,3 +65,126 @@ impl Cmd {
         Ok(())
     }
 }
+
+/// Prints the details of a probe, indented to fit below its entry in the list.
+pub(crate) fn print_details(details: &ProbeDetails) {
+    if let Some(version) = &details.firmware_version {
+        println!("       Firmware: {version}");
+    }
+    if !details.protocols.is_empty() {
+        let protocols: Vec<_> = details.protocols.iter().map(|p| p.to_string()).collect();
+        println!("       Protocols: {}", protocols.join(", "));
+    }
+    if let Some(speed) = details.max_speed_khz {
+        println!("       Max speed: {speed} kHz");
+    }
+    if details.adaptive_clocking {
+        println!("       Adaptive clocking (RTCK): supported");
+    }
+    if let Some(swo) = &details.swo {
+        let mut modes = vec![];
+        if swo.uart {
+            modes.push("UART".to_string());
+        }
+        if swo.manchester {
+            modes.push("Manchester".to_string());
+        }
+        if let Some(baud) = swo.max_baud {
+            modes.push(format!("up to {baud} baud"));
+        }
+        if let Some(size) = swo.buffer_size {
+            modes.push(format!("{size} byte buffer"));
+        }
+        println!("       SWO: {}", modes.join(", "));
+    }
+    match (details.packet_count, details.packet_size) {
+        (Some(count), Some(size)) => println!("       Packets: {count} x {size} bytes"),
+        (None, Some(size)) => println!("       Packet size: {size} bytes"),
+        _ => {}
+    }
+    if let Some(voltage) = details.target_voltage {
+        println!("       Target voltage: {voltage:.2} V");
+    }
+}
+
+fn watch(lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
+    for event in lister.watch() {
+        if output_format.is_json() {
+            let (event, probe) = match &event {
+                ProbeEvent::Connected(probe) => ("connected", probe),
+                ProbeEvent::Disconnected(probe) => ("disconnected", probe),
+            };
+            print_json(
+                "list",
+                &WatchEntry {
+                    event,
+                    probe: ProbeEntry::from(probe),
+                },
+            )?;
+            continue;
+        }
+
+        match event {
+            ProbeEvent::Connected(probe) => println!("Connected: {probe}"),
+            ProbeEvent::Disconnected(probe) => println!("Disconnected: {probe}"),
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct WatchEntry {
+    event: &'static str,
+    #[serde(flatten)]
+    probe: ProbeEntry,
+}
+
+#[derive(Serialize)]
+struct ProbeEntry {
+    identifier: String,
+    vendor_id: u16,
+    product_id: u16,
+    serial_number: Option<String>,
+    usb_port: Option<String>,
+    probe_type: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    uarts: Vec<UartEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<ProbeDetails>,
+}
+
+#[derive(Serialize)]
+struct UartEntry {
+    name: String,
+    path: Option<String>,
+}
+
+/// Returns the UARTs offered by the probe, which can be used to capture logs.
+fn uarts(info: &DebugProbeInfo) -> Vec<xds110::Xds110Uart> {
+    if info.is_probe_type::<Xds110Factory>() {
+        xds110::list_uarts(info)
+    } else {
+        vec![]
+    }
+}
+
+impl From<&DebugProbeInfo> for ProbeEntry {
+    fn from(info: &DebugProbeInfo) -> Self {
+        Self {
+            identifier: info.identifier.clone(),
+            vendor_id: info.vendor_id,
+            product_id: info.product_id,
+            serial_number: info.serial_number.clone(),
+            usb_port: info.usb_port.clone(),
+            probe_type: info.probe_type(),
+            uarts: uarts(info)
+                .into_iter()
+                .map(|uart| UartEntry {
+                    name: uart.name,
+                    path: uart.path.map(|path| path.display().to_string()),
+                })
+                .collect(),
+            details: None,
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::ops::Range;
+use std::time::Duration;
+
+use anyhow::bail;
+use probe_rs::{
+    config::MemoryRegion, memory_init::init_memory, probe::list::Lister, Core, MemoryInterface,
+};
+
+use crate::util::{common_options::ProbeOptions, parse_u64};
+use crate::CoreOptions;
+
+/// The number of words written and verified at once.
+const CHUNK_WORDS: usize = 16 * 1024;
+
+/// Test RAM for faulty data and address lines and cells
+///
+/// e.g. probe-rs memtest --chip STM32H743ZITx --region 0xC0000000..0xC2000000
+///      Tests the 32 MiB of SDRAM at 0xC0000000 with all tests.
+///
+/// The core is halted during the test, and the contents of the tested memory are lost.
+/// Without `--region`, all RAM regions of the target are tested, including the RAM the
+/// firmware uses. External memory is initialized by the memory init sequences of the target,
+/// if it has any, otherwise it has to be initialized before, e.g. by the firmware.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// A memory range to test, as `<start>..<end>`.
+    #[clap(long = "region", value_parser = parse_range)]
+    regions: Vec<Range<u64>>,
+
+    /// The tests to run, in order.
+    #[clap(
+        long = "test",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "walking-ones,address,random"
+    )]
+    tests: Vec<Test>,
+
+    /// The seed of the random test, to repeat a failing run. Defaults to a random seed.
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// The maximum number of failing words printed per test.
+    #[clap(long, default_value_t = 20)]
+    max_errors: usize,
+}
+
+/// A test of the memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Test {
+    /// Writes a single set bit, and a single cleared bit, in every position to the first word
+    /// of the region, to find stuck or shorted data lines.
+    WalkingOnes,
+    /// Writes the address of every word to it, to find faulty address lines.
+    Address,
+    /// Writes random values to every word, to find faulty cells.
+    Random,
+}
+
+/// A word which read back differently than it was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Failure {
+    address: u64,
+    expected: u32,
+    actual: u32,
+}
+
+/// The memory under test, as 32-bit words.
+trait Words {
+    fn write(&mut self, address: u64, data: &[u32]) -> anyhow::Result<()>;
+    fn read(&mut self, address: u64, data: &mut [u32]) -> anyhow::Result<()>;
+}
+
+impl Words for Core<'_> {
+    fn write(&mut self, address: u64, data: &[u32]) -> anyhow::Result<()> {
+        Ok(self.write_32(address, data)?)
+    }
+
+    fn read(&mut self, address: u64, data: &mut [u32]) -> anyhow::Result<()> {
+        Ok(self.read_32(address, data)?)
+    }
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
+        let regions = if self.regions.is_empty() {
+            session
+                .target()
+                .memory_map
+                .iter()
+                .filter_map(MemoryRegion::as_ram_region)
+                .map(|region| region.range.clone())
+                .collect()
+        } else {
+            self.regions.clone()
+        };
+        let seed = self.seed.unwrap_or_else(|| fastrand::u64(..));
+
+        for region in &regions {
+            init_memory(&mut session, region)?;
+        }
+
+        let mut core = session.core(self.shared.core)?;
+        core.halt(Duration::from_millis(100))?;
+
+        let mut failed = false;
+        for region in &regions {
+            // Only whole words are tested.
+            let region = (region.start + 3) & !3..region.end & !3;
+            println!("Testing {:#010x}..{:#010x}", region.start, region.end);
+
+            for test in &self.tests {
+                let failures = match test {
+                    Test::WalkingOnes => walking_ones(&mut core, region.start)?,
+                    Test::Address => fill_and_verify(&mut core, &region, |address| address as u32)?,
+                    Test::Random => {
+                        // Every word gets the same value in every run with the same seed.
+                        fill_and_verify(&mut core, &region, |address| {
+                            fastrand::Rng::with_seed(seed ^ address).u32(..)
+                        })?
+                    }
+                };
+
+                if failures.is_empty() {
+                    println!("  {test:?}: passed");
+                    continue;
+                }
+                failed = true;
+
+                let bits = failures.iter().fold(0, |bits, failure| {
+                    bits | (failure.expected ^ failure.actual)
+                });
+                println!(
+                    "  {test:?}: {} words failed, in bits {bits:#034b}",
+                    failures.len()
+                );
+                for failure in failures.iter().take(self.max_errors) {
+                    println!(
+                        "    {:#010x}: expected {:#010x}, read {:#010x}",
+                        failure.address, failure.expected, failure.actual
+                    );
+                }
+            }
+        }
+
+        if failed {
+            if self.tests.contains(&Test::Random) {
+                println!("The random test used the seed {seed}.");
+            }
+            bail!("The memory test failed.");
+        }
+
+        Ok(())
+    }
+}
+
+/// Tests the data lines by walking a set bit and a cleared bit through the word at the address.
+fn walking_ones(memory: &mut dyn Words, address: u64) -> anyhow::Result<Vec<Failure>> {
+    let mut failures = Vec::new();
+    for bit in 0..32 {
+        for expected in [1 << bit, !(1 << bit)] {
+            let mut actual = [0];
+            memory.write(address, &[expected])?;
+            memory.read(address, &mut actual)?;
+
+            if actual[0] != expected {
+                failures.push(Failure {
+                    address,
+                    expected,
+                    actual: actual[0],
+                });
+            }
+        }
+    }
+
+    Ok(failures)
+}
+
+/// Writes the value of `pattern` for its address to every word of the region, then reads the
+/// region back.
+///
+/// All words are written before any are read, so writes to a wrong address are found.
+fn fill_and_verify(
+    memory: &mut dyn Words,
+    region: &Range<u64>,
+    pattern: impl Fn(u64) -> u32,
+) -> anyhow::Result<Vec<Failure>> {
+    let chunks = || {
+        (region.start..region.end)
+            .step_by(CHUNK_WORDS * 4)
+            .map(|start| start..region.end.min(start + CHUNK_WORDS as u64 * 4))
+    };
+    let words = |chunk: &Range<u64>| (chunk.start..chunk.end).step_by(4);
+
+    for chunk in chunks() {
+        let data: Vec<_> = words(&chunk).map(&pattern).collect();
+        memory.write(chunk.start, &data)?;
+    }
+
+    let mut failures = Vec::new();
+    for chunk in chunks() {
+        let mut data = vec![0; ((chunk.end - chunk.start) / 4) as usize];
+        memory.read(chunk.start, &mut data)?;
+
+        for (address, actual) in words(&chunk).zip(data) {
+            let expected = pattern(address);
+            if actual != expected {
+                failures.push(Failure {
+                    address,
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+
+    Ok(failures)
+}
+
+/// Parses a memory range like `0x20000000..0x20010000`.
+fn parse_range(input: &str) -> Result<Range<u64>, String> {
+    let range = input
+        .split_once("..")
+        .and_then(|(start, end)| Some(parse_u64(start).ok()?..parse_u64(end).ok()?));
+
+    match range {
+        Some(range) if range.start < range.end => Ok(range),
+        _ => Err(format!(
+            "'{input}' is not a range like 0x20000000..0x20010000."
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// RAM at 0x1000 with a stuck data bit, and an address line which is shorted to ground.
+    struct FaultyRam {
+        words: Vec<u32>,
+        stuck_bit: u32,
+        shorted_address_bit: u64,
+    }
+
+    impl FaultyRam {
+        fn index(&self, address: u64) -> usize {
+            ((address & !self.shorted_address_bit) - 0x1000) as usize / 4
+        }
+    }
+
+    impl Words for FaultyRam {
+        fn write(&mut self, address: u64, data: &[u32]) -> anyhow::Result<()> {
+            for (offset, word) in data.iter().enumerate() {
+                let index = self.index(address + offset as u64 * 4);
+                self.words[index] = word | self.stuck_bit;
+            }
+            Ok(())
+        }
+
+        fn read(&mut self, address: u64, data: &mut [u32]) -> anyhow::Result<()> {
+            for (offset, word) in data.iter_mut().enumerate() {
+                *word = self.words[self.index(address + offset as u64 * 4)];
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn faults_are_found() {
+        let region = 0x1000..0x1100;
+        let mut ram = FaultyRam {
+            words: vec![0; 64],
+            stuck_bit: 0,
+            shorted_address_bit: 0,
+        };
+        assert_eq!(walking_ones(&mut ram, 0x1000).unwrap(), []);
+        assert_eq!(
+            fill_and_verify(&mut ram, &region, |address| address as u32).unwrap(),
+            []
+        );
+
+        ram.stuck_bit = 1 << 7;
+        let failures = walking_ones(&mut ram, 0x1000).unwrap();
+        // Every pattern with a zero in the stuck bit fails: 31 walking ones and one walking zero.
+        assert_eq!(failures.len(), 32);
+        assert!(failures
+            .iter()
+            .all(|failure| failure.expected ^ failure.actual == 1 << 7));
+
+        ram.stuck_bit = 0;
+        ram.shorted_address_bit = 0x10;
+        let failures = fill_and_verify(&mut ram, &region, |address| address as u32).unwrap();
+        // The words with the address bit set overwrote the ones without it.
+        assert_eq!(failures.len(), 32);
+        assert_eq!(
+            failures[0],
+            Failure {
+                address: 0x1000,
+                expected: 0x1000,
+                actual: 0x1010,
+            }
+        );
+    }
+
+    #[test]
+    fn ranges_are_parsed() {
+        assert_eq!(parse_range("0x1000..0x2000"), Ok(0x1000..0x2000));
+        assert!(parse_range("0x2000..0x1000").is_err());
+        assert!(parse_range("0x1000").is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Context;
+use probe_rs::architecture::arm::Pins;
+use probe_rs::probe::{list::Lister, DebugProbeSelector, Probe};
+
+/// Read and drive the reset and SWJ pins of the probe, e.g. for recovery procedures or board
+/// automation
+///
+/// Driving the data pins needs a probe supporting `DAP_SWJ_Pins`, like CMSIS-DAP probes. Other
+/// probes may only support the nRESET pin.
+#[derive(clap::Parser)]
+pub struct Cmd {
+    /// The probe to use, given as 'VID:PID' or 'VID:PID:Serial'.
+    #[clap(long, env = "PROBE_RS_PROBE")]
+    probe: Option<DebugProbeSelector>,
+
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Print the levels of all pins
+    Read,
+    /// Control the nRESET pin
+    Reset {
+        #[clap(value_enum)]
+        action: ResetAction,
+
+        /// How long the reset is asserted when pulsing it, in milliseconds.
+        #[clap(long, default_value_t = 100)]
+        duration: u64,
+    },
+    /// Drive pins, given as a sequence of `<PIN>=<0|1>` and `wait=<MS>` steps
+    ///
+    /// For example, `tdi=1 nreset=0 wait=100 nreset=1 wait=500 tdi=0` holds the pin wired to
+    /// TDI high while resetting the target. The pins are `swclk` (or `tck`), `swdio` (or `tms`),
+    /// `tdi`, `tdo`, `ntrst` and `nreset`.
+    Drive {
+        #[clap(required = true)]
+        steps: Vec<Step>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum ResetAction {
+    /// Pull nRESET low
+    Assert,
+    /// Release nRESET
+    Deassert,
+    /// Pull nRESET low, and release it again
+    Pulse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pin {
+    Swclk,
+    Swdio,
+    Tdi,
+    Tdo,
+    Ntrst,
+    Nreset,
+}
+
+impl Pin {
+    const ALL: [Pin; 6] = [
+        Pin::Swclk,
+        Pin::Swdio,
+        Pin::Tdi,
+        Pin::Tdo,
+        Pin::Ntrst,
+        Pin::Nreset,
+    ];
+
+    fn mask(self) -> u32 {
+        let mut pins = Pins(0);
+        match self {
+            Pin::Swclk => pins.set_swclk_tck(true),
+            Pin::Swdio => pins.set_swdio_tms(true),
+            Pin::Tdi => pins.set_tdi(true),
+            Pin::Tdo => pins.set_tdo(true),
+            Pin::Ntrst => pins.set_ntrst(true),
+            Pin::Nreset => pins.set_nreset(true),
+        }
+        pins.0 as u32
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Pin::Swclk => "SWCLK/TCK",
+            Pin::Swdio => "SWDIO/TMS",
+            Pin::Tdi => "TDI",
+            Pin::Tdo => "TDO",
+            Pin::Ntrst => "nTRST",
+            Pin::Nreset => "nRESET",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Drive { pin: Pin, high: bool },
+    Wait(u64),
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <PIN>=<0|1> or wait=<MS>, got '{s}'"))?;
+
+        let pin = match name.to_ascii_lowercase().as_str() {
+            "wait" => {
+                return value
+                    .parse()
+                    .map(Step::Wait)
+                    .map_err(|_| format!("Invalid wait time '{value}'"))
+            }
+            "swclk" | "tck" => Pin::Swclk,
+            "swdio" | "tms" => Pin::Swdio,
+            "tdi" => Pin::Tdi,
+            "tdo" => Pin::Tdo,
+            "ntrst" => Pin::Ntrst,
+            "nreset" => Pin::Nreset,
+            _ => return Err(format!("Unknown pin '{name}'")),
+        };
+        let high = match value {
+            "0" => false,
+            "1" => true,
+            _ => return Err(format!("Invalid level '{value}', expected 0 or 1")),
+        };
+
+        Ok(Step::Drive { pin, high })
+    }
+}
+
+/// A `DAP_SWJ_Pins` command, or a delay.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    SetPins { pin_out: u32, pin_select: u32 },
+    Wait(u64),
+}
+
+/// Merges consecutive pin steps, so pins which are given together change at the same time.
+fn merge_steps(steps: &[Step]) -> Vec<Action> {
+    let mut actions = Vec::new();
+
+    for step in steps {
+        match *step {
+            Step::Wait(ms) => actions.push(Action::Wait(ms)),
+            Step::Drive { pin, high } => {
+                let out = if high { pin.mask() } else { 0 };
+                match actions.last_mut() {
+                    Some(Action::SetPins {
+                        pin_out,
+                        pin_select,
+                    }) => {
+                        *pin_out = (*pin_out & !pin.mask()) | out;
+                        *pin_select |= pin.mask();
+                    }
+                    _ => actions.push(Action::SetPins {
+                        pin_out: out,
+                        pin_select: pin.mask(),
+                    }),
+                }
+            }
+        }
+    }
+
+    actions
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let mut probe = self.open_probe(lister)?;
+
+        match self.subcommand {
+            Subcommand::Read => {
+                let levels = probe.swj_pins(0, 0, 0)?;
+                for pin in Pin::ALL {
+                    let level = if levels & pin.mask() != 0 { 1 } else { 0 };
+                    println!("{:<10} {level}", pin.name());
+                }
+            }
+            Subcommand::Reset { action, duration } => match action {
+                ResetAction::Assert => probe.target_reset_assert()?,
+                ResetAction::Deassert => probe.target_reset_deassert()?,
+                ResetAction::Pulse => {
+                    probe.target_reset_assert()?;
+                    std::thread::sleep(Duration::from_millis(duration));
+                    probe.target_reset_deassert()?;
+                }
+            },
+            Subcommand::Drive { steps } => {
+                for action in merge_steps(&steps) {
+                    match action {
+                        Action::SetPins {
+                            pin_out,
+                            pin_select,
+                        } => {
+                            probe.swj_pins(pin_out, pin_select, 0)?;
+                        }
+                        Action::Wait(ms) => std::thread::sleep(Duration::from_millis(ms)),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn open_probe(&self, lister: &Lister) -> anyhow::Result<Probe> {
+        if let Some(selector) = &self.probe {
+            return Ok(lister.open(selector)?);
+        }
+
+        let probes = lister.list_all();
+        let probe = probes.first().context("No debug probe was found")?;
+        if probes.len() > 1 {
+            println!("Multiple probes found, using {probe}");
+        }
+        Ok(probe.open()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn steps_are_merged() {
+        let steps: Vec<Step> = ["tdi=1", "NRESET=0", "wait=100", "nreset=1", "tdi=0"]
+            .iter()
+            .map(|step| step.parse().unwrap())
+            .collect();
+
+        assert_eq!(
+            merge_steps(&steps),
+            [
+                Action::SetPins {
+                    pin_out: 0x04,
+                    pin_select: 0x84
+                },
+                Action::Wait(100),
+                Action::SetPins {
+                    pin_out: 0x80,
+                    pin_select: 0x84
+                },
+            ]
+        );
+        assert!("boot0=1".parse::<Step>().is_err());
+        assert!("tdi=2".parse::<Step>().is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use std::ops::Range;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use probe_rs::{
+    config::MemoryRegion,
+    debug::{
+        stack_frame::StackFrameInfo, DebugInfo, DebugRegisters, Variable, VariableCache,
+        VariableLocation, VariableName,
+    },
+    probe::list::Lister,
+    Core,
+};
+
+use crate::util::common_options::ProbeOptions;
+use crate::CoreOptions;
+
+/// Change a global variable of the running firmware
+///
+/// e.g. probe-rs poke --chip nRF52840_xxAA --elf firmware.elf CONFIG.gain 1.5
+///      Sets the field `gain` of the static `CONFIG` to 1.5.
+///
+/// The variable is looked up in the debug info of the ELF file, and the value is encoded
+/// according to its type. Only variables of primitive types in RAM can be changed. The core
+/// keeps running, so the firmware sees the new value the next time it reads the variable.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// The ELF file with the debug info of the firmware.
+    #[clap(long)]
+    elf: PathBuf,
+
+    /// The variable, as path of the static and its fields, e.g. `firmware::CONFIG.gain`.
+    variable: String,
+
+    /// The new value, e.g. `1.5`, `-3` or `true`.
+    value: String,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let debug_info = DebugInfo::from_file(&self.elf)
+            .with_context(|| format!("Failed to read the debug info of {}", self.elf.display()))?;
+
+        let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
+        let ram: Vec<_> = session
+            .target()
+            .memory_map
+            .iter()
+            .filter_map(MemoryRegion::as_ram_region)
+            .map(|region| region.range.clone())
+            .collect();
+        let mut core = session.core(self.shared.core)?;
+
+        let (old_value, new_value) =
+            poke(&debug_info, &mut core, &ram, &self.variable, &self.value)?;
+        println!("{}: {old_value} -> {new_value}", self.variable);
+
+        Ok(())
+    }
+}
+
+/// Writes `value` to the variable at `path`, which has to lie in one of the `ram` ranges.
+///
+/// Returns the old and the new value of the variable.
+fn poke(
+    debug_info: &DebugInfo,
+    core: &mut Core,
+    ram: &[Range<u64>],
+    path: &str,
+    value: &str,
+) -> anyhow::Result<(String, String)> {
+    let mut cache = debug_info.create_static_scope_cache();
+    let variable = find_variable(debug_info, &mut cache, core, path)?;
+
+    let VariableLocation::Address(address) = variable.memory_location else {
+        bail!("'{path}' has no address in memory.");
+    };
+    let size = variable.byte_size.unwrap_or(1);
+    if !ram
+        .iter()
+        .any(|range| range.start <= address && address + size <= range.end)
+    {
+        bail!("'{path}' at {address:#010x} is not in RAM.");
+    }
+
+    let old_value = variable.to_string(&cache);
+    variable.update_value(core, &mut cache, value.to_string())?;
+    let new_value = cache
+        .get_variable_by_key(variable.variable_key())
+        .map(|variable| variable.to_string(&cache))
+        .unwrap_or_else(|| value.to_string());
+
+    Ok((old_value, new_value))
+}
+
+/// Looks up a variable given as the path of a static and its fields, e.g. `module::STATIC.field`.
+fn find_variable(
+    debug_info: &DebugInfo,
+    cache: &mut VariableCache,
+    core: &mut Core,
+    path: &str,
+) -> anyhow::Result<Variable> {
+    // Statics don't depend on any registers.
+    let registers = DebugRegisters::default();
+    let frame_info = StackFrameInfo {
+        registers: &registers,
+        frame_base: None,
+        canonical_frame_address: None,
+    };
+
+    let mut root = cache.root_variable().clone();
+    debug_info.cache_deferred_variables(cache, core, &mut root, frame_info)?;
+
+    let mut segments = path.split('.');
+    let static_path = segments.next().unwrap_or_default();
+    let mut variable = match static_path.rsplit_once("::") {
+        Some((namespaces, name)) => {
+            let mut parent = root.variable_key();
+            for namespace in namespaces.split("::") {
+                parent = cache
+                    .get_variable_by_name_and_parent(
+                        &VariableName::Namespace(namespace.to_string()),
+                        parent,
+                    )
+                    .with_context(|| format!("The module '{namespace}' was not found."))?
+                    .variable_key();
+            }
+            cache.get_variable_by_name_and_parent(&VariableName::Named(name.to_string()), parent)
+        }
+        None => cache.get_variable_by_name(&VariableName::Named(static_path.to_string())),
+    }
+    .with_context(|| format!("The static '{static_path}' was not found."))?;
+
+    for field in segments {
+        debug_info.cache_deferred_variables(cache, core, &mut variable, frame_info)?;
+        variable = cache
+            .get_variable_by_name_and_parent(
+                &VariableName::Named(field.to_string()),
+                variable.variable_key(),
+            )
+            .with_context(|| format!("The field '{field}' was not found."))?;
+    }
+
+    Ok(variable)
+}
+
+#[cfg(test)]
+mod test {
+    use probe_rs::{integration::FakeProbe, MemoryInterface};
+
+    use super::*;
+
+    /// The address of `probe_rs_debugger_test::I32` in the test ELF file.
+    const I32_ADDRESS: u64 = 0x2000_000c;
+
+    fn debug_info() -> DebugInfo {
+        DebugInfo::from_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../probe-rs/tests/debug-unwind-tests/nRF52833_xxAA_svcall.elf"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn poke_changes_a_static() {
+        let debug_info = debug_info();
+        let mut session = FakeProbe::attach_mocked_core("STM32F407VGTx").unwrap();
+        let mut core = session.core(0).unwrap();
+        core.write_word_32(I32_ADDRESS, 7).unwrap();
+
+        let ram = [0x1000_0000..0x1001_0000, 0x2000_0000..0x2002_0000];
+        let (old_value, new_value) = poke(
+            &debug_info,
+            &mut core,
+            &ram,
+            "probe_rs_debugger_test::I32",
+            "-3",
+        )
+        .unwrap();
+
+        assert_eq!(old_value, "7");
+        assert_eq!(new_value, "-3");
+        assert_eq!(core.read_word_32(I32_ADDRESS).unwrap() as i32, -3);
+    }
+
+    #[test]
+    fn poke_refuses_statics_outside_ram() {
+        let debug_info = debug_info();
+        let mut session = FakeProbe::attach_mocked_core("STM32F407VGTx").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        let ram = [0x1000_0000..0x1001_0000, 0x2000_1000..0x2002_0000];
+        let error = poke(
+            &debug_info,
+            &mut core,
+            &ram,
+            "probe_rs_debugger_test::I32",
+            "1",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("is not in RAM"));
+        assert_eq!(core.read_word_32(I32_ADDRESS).unwrap(), 0);
+
+        assert!(poke(&debug_info, &mut core, &ram, "MISSING", "1").is_err());
+    }
+}
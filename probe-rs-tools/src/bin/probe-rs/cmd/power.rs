@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use probe_rs::probe::{list::Lister, DebugProbeSelector, Probe};
+
+/// Switch the power supply of the target, for probes which can power the target
+///
+/// This is supported by J-Link probes with a 5V supply on pin 19.
+#[derive(clap::Parser)]
+pub struct Cmd {
+    /// The probe to use, given as 'VID:PID' or 'VID:PID:Serial'.
+    #[clap(long, env = "PROBE_RS_PROBE")]
+    probe: Option<DebugProbeSelector>,
+
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Switch the power on
+    On,
+    /// Switch the power off
+    Off,
+    /// Switch the power off, and on again
+    Cycle {
+        /// How long the power stays off, in milliseconds.
+        #[clap(long, default_value = "500")]
+        off_time: u64,
+    },
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let mut probe = self.open_probe(lister)?;
+
+        match self.subcommand {
+            Subcommand::On => probe.set_target_power(true)?,
+            Subcommand::Off => probe.set_target_power(false)?,
+            Subcommand::Cycle { off_time } => {
+                probe.set_target_power(false)?;
+                std::thread::sleep(Duration::from_millis(off_time));
+                probe.set_target_power(true)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn open_probe(&self, lister: &Lister) -> anyhow::Result<Probe> {
+        if let Some(selector) = &self.probe {
+            return Ok(lister.open(selector)?);
+        }
+
+        let probes = lister.list_all();
+        let probe = probes.first().context("No debug probe was found")?;
+        if probes.len() > 1 {
+            println!("Multiple probes found, using {probe}");
+        }
+        Ok(probe.open()?)
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use itertools::Itertools;
+use probe_rs::probe::list::Lister;
+use probe_rs::provisioning::{self, ProvisioningState};
+use probe_rs::MemoryInterface;
+
+use crate::util::common_options::ProbeOptions;
+use crate::util::parse_u64;
+
+/// Write per-device configuration to one-time-programmable regions, like the nRF UICR or
+/// the STM32 OTP area
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// List the provisioning regions of the target, and whether they were programmed
+    List,
+    /// Write data to a provisioning region
+    Write {
+        /// The address to write to.
+        #[clap(value_parser = parse_u64)]
+        address: u64,
+
+        /// The data to write, as hex bytes, e.g. `0011aabb`.
+        #[clap(long, required_unless_present = "file", conflicts_with = "file")]
+        hex: Option<String>,
+
+        /// A binary file with the data to write.
+        #[clap(long)]
+        file: Option<PathBuf>,
+
+        /// Only check whether the data can be written, without writing it.
+        #[clap(long)]
+        check: bool,
+
+        /// Confirm writing to a region which can never be erased again.
+        #[clap(long)]
+        yes: bool,
+    },
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let (mut session, _) = self.probe_options.simple_attach(lister)?;
+
+        match self.subcommand {
+            Subcommand::List => {
+                let regions = session.target().provisioning_regions.clone();
+                if regions.is_empty() {
+                    println!("The target has no provisioning regions.");
+                }
+
+                for region in regions {
+                    let mut contents = vec![0; (region.range.end - region.range.start) as usize];
+                    session.core(0)?.read(region.range.start, &mut contents)?;
+
+                    let programmed = contents
+                        .iter()
+                        .filter(|&&byte| byte != region.erased_byte_value)
+                        .count();
+                    println!(
+                        "{}: {:#010x}..{:#010x}{}, {programmed} of {} bytes programmed",
+                        region.name,
+                        region.range.start,
+                        region.range.end,
+                        if region.one_time { " (one-time)" } else { "" },
+                        contents.len(),
+                    );
+                }
+            }
+            Subcommand::Write {
+                address,
+                hex,
+                file,
+                check,
+                yes,
+            } => {
+                let data = match (hex, file) {
+                    (Some(hex), _) => parse_hex_bytes(&hex)?,
+                    (None, Some(file)) => std::fs::read(&file)
+                        .with_context(|| format!("Failed to read {}", file.display()))?,
+                    (None, None) => unreachable!("clap requires one of them"),
+                };
+
+                let plan = provisioning::plan(&mut session, address, &data)?;
+                println!(
+                    "Region {}: {:#010x}..{:#010x} is {}",
+                    plan.region.name,
+                    address,
+                    address + data.len() as u64,
+                    match plan.state {
+                        ProvisioningState::Erased => "erased",
+                        ProvisioningState::AlreadyProgrammed => "already programmed with this data",
+                        ProvisioningState::Programmable =>
+                            "partially programmed, the data can be written",
+                        ProvisioningState::Conflict => "programmed with different data",
+                    }
+                );
+
+                if check || plan.state == ProvisioningState::AlreadyProgrammed {
+                    anyhow::ensure!(
+                        plan.state != ProvisioningState::Conflict,
+                        "The data can't be written"
+                    );
+                    return Ok(());
+                }
+
+                anyhow::ensure!(
+                    !plan.region.one_time || yes,
+                    "Region {} can never be erased again. Pass --yes to write it.",
+                    plan.region.name
+                );
+
+                provisioning::provision(&mut session, &plan)?;
+                println!("Written and verified {} bytes", data.len());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_hex_bytes(hex: &str) -> anyhow::Result<Vec<u8>> {
+    let hex = hex.trim_start_matches("0x");
+    anyhow::ensure!(hex.len() % 2 == 0, "Expected an even number of hex digits");
+
+    hex.as_bytes()
+        .chunks(2)
+        .map(|byte| {
+            let byte = std::str::from_utf8(byte)?;
+            u8::from_str_radix(byte, 16).with_context(|| format!("Invalid hex byte `{byte}`"))
+        })
+        .try_collect()
+}
@@ -1,6 +1,9 @@
-use probe_rs::{probe::list::Lister, MemoryInterface};
+use probe_rs::{memory_init::init_memory, probe::list::Lister, MemoryInterface};
+
+use serde::Serialize;
 
 use crate::util::common_options::{ProbeOptions, ReadWriteBitWidth, ReadWriteOptions};
+use crate::util::output::{print_json, OutputFormat};
 use crate::CoreOptions;
 
 /// Read from target memory address
@@ -32,38 +35,61 @@ pub struct Cmd {
 }
 
 impl Cmd {
-    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+    pub fn run(self, lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
         let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
-        let mut core = session.core(self.shared.core)?;
         let words = self.words as usize;
+        let address = self.read_write_options.address;
+        let width = self.read_write_options.width;
 
-        match self.read_write_options.width {
+        init_memory(
+            &mut session,
+            &(address..address + self.words * (width as u64 / 8)),
+        )?;
+        let mut core = session.core(self.shared.core)?;
+
+        let values: Vec<u64> = match width {
             ReadWriteBitWidth::B8 => {
                 let mut values = vec![0; words];
-                core.read_8(self.read_write_options.address, &mut values)?;
-                for val in values {
-                    print!("{:02x} ", val);
-                }
-                println!();
+                core.read_8(address, &mut values)?;
+                values.into_iter().map(u64::from).collect()
             }
             ReadWriteBitWidth::B32 => {
                 let mut values = vec![0; words];
-                core.read_32(self.read_write_options.address, &mut values)?;
-                for val in values {
-                    print!("{:08x} ", val);
-                }
-                println!();
+                core.read_32(address, &mut values)?;
+                values.into_iter().map(u64::from).collect()
             }
             ReadWriteBitWidth::B64 => {
                 let mut values = vec![0; words];
-                core.read_64(self.read_write_options.address, &mut values)?;
-                for val in values {
-                    print!("{:016x} ", val);
-                }
-                println!();
+                core.read_64(address, &mut values)?;
+                values
             }
+        };
+
+        if output_format.is_json() {
+            return print_json(
+                "read",
+                &ReadResult {
+                    address,
+                    width: width as u8,
+                    values,
+                },
+            );
         }
 
+        // Pad each value to the number of hex digits of the word width.
+        let digits = width as usize / 4;
+        for val in values {
+            print!("{val:0digits$x} ");
+        }
+        println!();
+
         Ok(())
     }
 }
+
+#[derive(Serialize)]
+struct ReadResult {
+    address: u64,
+    width: u8,
+    values: Vec<u64>,
+}
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use probe_rs::architecture::arm::mpu::{
+    read_mpu, read_sau, MpuConfiguration, MpuRegion, SauConfiguration, SauRegion,
+};
+use probe_rs::probe::list::Lister;
+use serde::Serialize;
+
+use crate::util::common_options::ProbeOptions;
+use crate::util::output::{print_json, OutputFormat};
+use crate::CoreOptions;
+
+/// Show the MPU and SAU regions of a Cortex-M core
+///
+/// e.g. probe-rs regions --chip STM32H743ZITx
+///      Prints the MPU regions with their addresses, access permissions and memory attributes.
+///
+/// The core is halted while the regions are read, and resumed afterwards if it was running.
+/// The SAU regions are only shown for cores with the Security Extension.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// Also show the disabled regions.
+    #[clap(long)]
+    all: bool,
+}
+
+#[derive(Serialize)]
+struct Regions {
+    mpu: Option<MpuConfiguration>,
+    sau: Option<SauConfiguration>,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister, output_format: OutputFormat) -> anyhow::Result<()> {
+        let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
+        let mut core = session.core(self.shared.core)?;
+
+        let was_halted = core.core_halted()?;
+        if !was_halted {
+            core.halt(Duration::from_millis(100))?;
+        }
+        let regions = Regions {
+            mpu: read_mpu(&mut core)?,
+            sau: read_sau(&mut core)?,
+        };
+        if !was_halted {
+            core.run()?;
+        }
+
+        if output_format.is_json() {
+            return print_json("regions", &regions);
+        }
+
+        match &regions.mpu {
+            Some(mpu) => {
+                println!(
+                    "MPU: {}{}{}",
+                    enabled(mpu.enabled),
+                    if mpu.privileged_default_map {
+                        ", default map for privileged code"
+                    } else {
+                        ""
+                    },
+                    if mpu.enabled_in_fault_handlers {
+                        ", enabled in fault handlers"
+                    } else {
+                        ""
+                    }
+                );
+                for region in mpu.regions.iter().filter(|r| self.all || r.enabled) {
+                    println!("  {}", format_mpu_region(region));
+                }
+            }
+            None => println!("MPU: not present"),
+        }
+
+        if let Some(sau) = &regions.sau {
+            println!(
+                "SAU: {}{}",
+                enabled(sau.enabled),
+                if !sau.enabled && sau.all_non_secure {
+                    ", all memory non-secure"
+                } else {
+                    ""
+                }
+            );
+            for region in sau.regions.iter().filter(|r| self.all || r.enabled) {
+                println!("  {}", format_sau_region(region));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn enabled(enabled: bool) -> &'static str {
+    if enabled {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
+
+fn format_mpu_region(region: &MpuRegion) -> String {
+    let mut line = format!(
+        "{:>2}: {:#010x}..{:#010x} {:<18} {}",
+        region.number,
+        region.range.start,
+        region.range.end,
+        region.access.to_string(),
+        region.attributes
+    );
+
+    if region.execute_never {
+        line.push_str(", XN");
+    }
+    if region.shareable {
+        line.push_str(", shareable");
+    }
+    if region.disabled_subregions != 0 {
+        line.push_str(&format!(
+            ", subregions {:#010b} disabled",
+            region.disabled_subregions
+        ));
+    }
+    if !region.enabled {
+        line.push_str(" (disabled)");
+    }
+
+    line
+}
+
+fn format_sau_region(region: &SauRegion) -> String {
+    format!(
+        "{:>2}: {:#010x}..{:#010x} {}{}",
+        region.number,
+        region.range.start,
+        region.range.end,
+        if region.non_secure_callable {
+            "non-secure callable"
+        } else {
+            "non-secure"
+        },
+        if region.enabled { "" } else { " (disabled)" }
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use probe_rs::architecture::arm::mpu::{CachePolicy, MemoryAttributes, MpuAccess};
+
+    use super::*;
+
+    #[test]
+    fn regions_are_formatted() {
+        let policy = CachePolicy::WriteBack {
+            read_allocate: true,
+            write_allocate: true,
+        };
+        let region = MpuRegion {
+            number: 2,
+            enabled: true,
+            range: 0x2000_0000..0x2001_0000,
+            disabled_subregions: 0x80,
+            access: MpuAccess::ReadWrite,
+            execute_never: true,
+            shareable: false,
+            attributes: MemoryAttributes::Normal {
+                inner: policy,
+                outer: policy,
+            },
+        };
+
+        assert_eq!(
+            format_mpu_region(&region),
+            " 2: 0x20000000..0x20010000 RW                 Normal WB-RWA, XN, subregions 0b10000000 disabled"
+        );
+    }
+}
@@ -1,4 +1,7 @@
+use std::time::Duration;
+
 use probe_rs::probe::list::Lister;
+use probe_rs::ResetStrategy;
 
 use crate::{util::common_options::ProbeOptions, CoreOptions};
 
@@ -9,13 +12,42 @@ pub struct Cmd {
 
     #[clap(flatten)]
     common: ProbeOptions,
+
+    /// How the target is reset: `default` uses the reset of the target's debug sequence, which
+    /// may be a vendor-specific soft reset, `sysresetreq` and `vectreset` write the Cortex-M
+    /// AIRCR register, and `hardware` pulses the nRESET pin of the probe.
+    #[clap(long, default_value_t = ResetStrategy::Default)]
+    strategy: ResetStrategy,
+
+    /// Halt the core after the reset, before it executes the first instruction.
+    #[clap(long)]
+    halt: bool,
+
+    /// Wait this many milliseconds after the reset, e.g. to give a bootloader time to start.
+    #[clap(long, value_name = "MS", default_value_t = 0)]
+    delay: u64,
 }
 
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
         let (mut session, _probe_options) = self.common.simple_attach(lister)?;
 
-        session.core(self.shared.core)?.reset()?;
+        session.reset_with(
+            self.shared.core,
+            self.strategy,
+            self.halt,
+            Duration::from_millis(500),
+        )?;
+
+        if self.delay > 0 {
+            std::thread::sleep(Duration::from_millis(self.delay));
+        }
+
+        if self.halt {
+            let mut core = session.core(self.shared.core)?;
+            let pc: u64 = core.read_core_reg(core.program_counter())?;
+            println!("Core {} halted at {pc:#010x}", self.shared.core);
+        }
 
         Ok(())
     }
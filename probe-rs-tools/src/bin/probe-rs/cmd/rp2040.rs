@@ -0,0 +1,61 @@
+use probe_rs::probe::list::Lister;
+use probe_rs::vendor::raspberrypi::{rp2040_core1_launched, rp2040_rescue};
+use probe_rs::CoreStatus;
+
+use crate::util::common_options::ProbeOptions;
+
+/// Recover and inspect RP2040 targets
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Recover a chip which can't be attached to, with the rescue debug port
+    ///
+    /// The chip is reset and stops in the boot ROM, so it can be flashed with a working firmware.
+    Rescue,
+    /// Print the state of both cores, and whether core 1 was launched by the firmware
+    Cores,
+}
+
+impl Cmd {
+    pub fn run(mut self, lister: &Lister) -> anyhow::Result<()> {
+        if self.probe_options.chip.is_none() {
+            self.probe_options.chip = Some("RP2040".to_string());
+        }
+
+        match self.subcommand {
+            Subcommand::Rescue => {
+                let mut probe = self.probe_options.load()?.attach_probe(lister)?;
+                rp2040_rescue(&mut probe)?;
+                println!("The chip was reset and waits in the boot ROM, it can be flashed now.");
+            }
+            Subcommand::Cores => {
+                let (mut session, _) = self.probe_options.simple_attach(lister)?;
+
+                for core_index in 0..2 {
+                    let status = session.core(core_index)?.status()?;
+                    let state = match status {
+                        CoreStatus::Halted(reason) => format!("halted ({reason:?})"),
+                        other => format!("{other:?}").to_lowercase(),
+                    };
+                    println!("Core {core_index}: {state}");
+                }
+
+                if rp2040_core1_launched(&mut session)? {
+                    println!("Core 1 was launched by the firmware.");
+                } else {
+                    println!("Core 1 was not launched, it waits in the boot ROM.");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
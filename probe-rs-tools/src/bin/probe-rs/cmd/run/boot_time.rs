@@ -0,0 +1,167 @@
+use std::cell::Cell;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use probe_rs::Core;
+
+use crate::util::{cycle_counter::CycleCounter, resolve_location};
+
+/// How long after the reset the target is polled often, to catch the boot events.
+const BOOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Measures the boot time of the firmware, from the release of the reset to the first RTT
+/// output and to a symbol, e.g. `main`.
+///
+/// The symbol is caught with a hardware breakpoint, and the cycles until it is reached are read
+/// from the cycle counter of the core, if it has one. The times are measured on the host, so
+/// they include the latency of the probe.
+pub(crate) struct BootTimer {
+    symbol: String,
+    address: u64,
+    /// The cycle counter and its value at the reset.
+    counter: Option<(CycleCounter, u32)>,
+    released: Cell<Option<Instant>>,
+    first_output: Cell<bool>,
+    reached: Cell<bool>,
+}
+
+impl BootTimer {
+    /// Resets and halts the core, and sets the breakpoint at the symbol.
+    pub fn arm(core: &mut Core, elf: &Path, symbol: &str) -> anyhow::Result<Self> {
+        let elf = std::fs::read(elf)?;
+        let address = resolve_location(symbol, Some(&elf))?;
+
+        core.reset_and_halt(Duration::from_millis(100))?;
+        let counter = match CycleCounter::enable(core) {
+            Ok(counter) => Some((counter, counter.read(core)?)),
+            Err(error) => {
+                tracing::warn!("Measuring the boot time without cycles: {error}");
+                None
+            }
+        };
+        core.set_hw_breakpoint(address)
+            .with_context(|| format!("Failed to set a breakpoint at '{symbol}'."))?;
+
+        Ok(Self {
+            symbol: symbol.to_string(),
+            address,
+            counter,
+            released: Cell::new(None),
+            first_output: Cell::new(false),
+            reached: Cell::new(false),
+        })
+    }
+
+    /// Records that the core was released from the reset.
+    pub fn released(&self) {
+        if self.released.get().is_none() {
+            self.released.set(Some(Instant::now()));
+        }
+    }
+
+    /// Records RTT output of the target, and prints the time of the first one.
+    pub fn output(&self) {
+        if !self.first_output.replace(true) {
+            eprintln!("Boot time: first RTT output after {}", self.elapsed());
+        }
+    }
+
+    /// Returns if the boot timer still waits for an event, so the target should be polled often.
+    pub fn waiting(&self) -> bool {
+        let booting = self
+            .released
+            .get()
+            .map_or(true, |released| released.elapsed() < BOOT_TIMEOUT);
+
+        booting && (!self.first_output.get() || !self.reached.get())
+    }
+
+    /// Checks if the core halted at the symbol. If so, prints the time and removes the
+    /// breakpoint, so the core can continue.
+    pub fn halted(&self, core: &mut Core) -> anyhow::Result<bool> {
+        let pc: u64 = core.read_core_reg(core.program_counter())?;
+        if self.reached.get() || pc != self.address {
+            return Ok(false);
+        }
+        self.reached.set(true);
+
+        let elapsed = self.elapsed();
+        core.clear_hw_breakpoint(self.address)?;
+        match self.counter {
+            Some((counter, start)) => {
+                let cycles = counter.read(core)?.wrapping_sub(start);
+                eprintln!(
+                    "Boot time: reached '{}' after {cycles} cycles ({elapsed})",
+                    self.symbol
+                );
+            }
+            None => eprintln!("Boot time: reached '{}' after {elapsed}", self.symbol),
+        }
+
+        Ok(true)
+    }
+
+    /// Formats the time since the release of the reset.
+    fn elapsed(&self) -> String {
+        let elapsed = self.released.get().map(|released| released.elapsed());
+        format!(
+            "{:.3} ms",
+            elapsed.unwrap_or_default().as_secs_f64() * 1000.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use probe_rs::{integration::FakeProbe, MemoryInterface};
+
+    use super::*;
+
+    /// The test ELF file, in which `main` is at 0x3ac.
+    const ELF: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../probe-rs/tests/debug-unwind-tests/nRF52833_xxAA_svcall.elf"
+    );
+
+    /// Returns if one of the FPB comparators of the core is enabled.
+    fn breakpoint_set(core: &mut Core) -> bool {
+        const FP_COMP0: u64 = 0xE000_2008;
+        (0..6).any(|index| core.read_word_32(FP_COMP0 + 4 * index).unwrap() & 1 != 0)
+    }
+
+    #[test]
+    fn boot_timer_catches_the_symbol_once() {
+        let mut session = FakeProbe::attach_mocked_core("STM32F407VGTx").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        let boot_timer = BootTimer::arm(&mut core, Path::new(ELF), "main").unwrap();
+        assert_eq!(boot_timer.address, 0x3ac);
+        assert!(boot_timer.counter.is_some());
+        assert!(boot_timer.waiting());
+        assert!(breakpoint_set(&mut core));
+
+        boot_timer.released();
+        let pc = core.program_counter();
+        core.write_core_reg(pc, 0x100u32).unwrap();
+        assert!(!boot_timer.halted(&mut core).unwrap());
+
+        core.write_core_reg(pc, 0x3acu32).unwrap();
+        assert!(boot_timer.halted(&mut core).unwrap());
+        assert!(!breakpoint_set(&mut core));
+        assert!(!boot_timer.halted(&mut core).unwrap());
+
+        // Still waiting for the first RTT output.
+        assert!(boot_timer.waiting());
+        boot_timer.output();
+        assert!(!boot_timer.waiting());
+    }
+
+    #[test]
+    fn unknown_symbol_is_an_error() {
+        let mut session = FakeProbe::attach_mocked_core("STM32F407VGTx").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        assert!(BootTimer::arm(&mut core, Path::new(ELF), "missing_symbol").is_err());
+    }
+}
@@ -0,0 +1,401 @@
+//! A file server which lets the firmware access files on the host over RTT.
+//!
+//! Unlike semihosting, requests don't halt the core, so the throughput is only limited by the
+//! RTT buffers and the probe.
+//!
+//! The firmware sends requests on an RTT up channel named `probe-rs-fs`, and receives the
+//! responses on the down channel with the same name. Requests and responses use the same frame:
+//!
+//! * 1 byte: the command of a request, or the status of a response.
+//! * 1 byte: the handle of the file.
+//! * 4 bytes: the length of the payload, little endian.
+//! * The payload.
+//!
+//! The commands are:
+//!
+//! * `1` open: the payload is the mode (`0` read, `1` write, `2` append) followed by the UTF-8
+//!   path, relative to the served directory. The response contains the handle of the file.
+//! * `2` close: closes the file with the handle.
+//! * `3` read: the payload is the maximum number of bytes to read, as `u32`. The response
+//!   contains the data, which is empty at the end of the file.
+//! * `4` write: writes the payload to the file.
+//! * `5` seek: the payload is the offset from the start of the file, as `u32`.
+//!
+//! The status of the response is `0` on success, or one of the [`Status`] error codes.
+//! Requests are answered in order.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Context;
+use probe_rs::Core;
+
+use crate::util::rtt::{RttActiveDownChannel, RttActiveTarget, RttActiveUpChannel};
+
+/// The name of the RTT channels used by the file server.
+pub(crate) const CHANNEL_NAME: &str = "probe-rs-fs";
+
+/// The size of the frame header, before the payload.
+const HEADER_SIZE: usize = 6;
+
+/// The maximum number of bytes returned by a single read request.
+const MAX_READ_SIZE: usize = 64 * 1024;
+
+/// The maximum payload size of a request. Larger requests are considered corrupt.
+const MAX_REQUEST_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Command {
+    Open = 1,
+    Close = 2,
+    Read = 3,
+    Write = 4,
+    Seek = 5,
+}
+
+impl TryFrom<u8> for Command {
+    type Error = Status;
+
+    fn try_from(value: u8) -> Result<Self, Status> {
+        match value {
+            1 => Ok(Command::Open),
+            2 => Ok(Command::Close),
+            3 => Ok(Command::Read),
+            4 => Ok(Command::Write),
+            5 => Ok(Command::Seek),
+            _ => Err(Status::InvalidRequest),
+        }
+    }
+}
+
+/// The status of a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Status {
+    Ok = 0,
+    NotFound = 1,
+    PermissionDenied = 2,
+    InvalidHandle = 3,
+    InvalidRequest = 4,
+    Io = 5,
+}
+
+impl From<io::Error> for Status {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Status::NotFound,
+            io::ErrorKind::PermissionDenied => Status::PermissionDenied,
+            _ => Status::Io,
+        }
+    }
+}
+
+/// A request or a response.
+#[derive(Debug, PartialEq)]
+struct Frame {
+    /// The command of a request, or the status of a response.
+    code: u8,
+    handle: u8,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Removes the first complete frame from the buffer.
+    ///
+    /// Returns `Ok(None)` if the buffer doesn't contain a complete frame yet.
+    fn decode(buffer: &mut Vec<u8>) -> anyhow::Result<Option<Self>> {
+        let Some(header) = buffer.get(..HEADER_SIZE) else {
+            return Ok(None);
+        };
+
+        let length = u32::from_le_bytes(header[2..].try_into().unwrap()) as usize;
+        anyhow::ensure!(
+            length <= MAX_REQUEST_SIZE,
+            "The file server received a request with a payload of {length} bytes, the RTT data is probably corrupt."
+        );
+        if buffer.len() < HEADER_SIZE + length {
+            return Ok(None);
+        }
+
+        let frame = Frame {
+            code: header[0],
+            handle: header[1],
+            payload: buffer[HEADER_SIZE..HEADER_SIZE + length].to_vec(),
+        };
+        buffer.drain(..HEADER_SIZE + length);
+
+        Ok(Some(frame))
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(self.code);
+        buffer.push(self.handle);
+        buffer.extend_from_slice(&(self.payload.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(&self.payload);
+    }
+
+    fn response(status: Status, handle: u8, payload: Vec<u8>) -> Self {
+        Frame {
+            code: status as u8,
+            handle,
+            payload,
+        }
+    }
+}
+
+/// Serves the files of a directory. Paths outside of the directory can't be accessed.
+struct Files {
+    root: PathBuf,
+    open: BTreeMap<u8, File>,
+}
+
+impl Files {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            open: BTreeMap::new(),
+        }
+    }
+
+    fn handle(&mut self, request: Frame) -> Frame {
+        let handle = request.handle;
+        match self.handle_inner(request) {
+            Ok((handle, payload)) => Frame::response(Status::Ok, handle, payload),
+            Err(status) => Frame::response(status, handle, vec![]),
+        }
+    }
+
+    fn handle_inner(&mut self, request: Frame) -> Result<(u8, Vec<u8>), Status> {
+        let handle = request.handle;
+        let payload = request.payload;
+
+        match Command::try_from(request.code)? {
+            Command::Open => {
+                let (&mode, path) = payload.split_first().ok_or(Status::InvalidRequest)?;
+                let path = std::str::from_utf8(path).map_err(|_| Status::InvalidRequest)?;
+                let file = self.open_file(mode, path)?;
+
+                let handle = (1..=u8::MAX)
+                    .find(|handle| !self.open.contains_key(handle))
+                    .ok_or(Status::Io)?;
+                self.open.insert(handle, file);
+                Ok((handle, vec![]))
+            }
+            Command::Close => {
+                self.open.remove(&handle).ok_or(Status::InvalidHandle)?;
+                Ok((handle, vec![]))
+            }
+            Command::Read => {
+                let length = u32::from_le_bytes(
+                    payload
+                        .as_slice()
+                        .try_into()
+                        .map_err(|_| Status::InvalidRequest)?,
+                ) as usize;
+                let file = self.open.get_mut(&handle).ok_or(Status::InvalidHandle)?;
+
+                let mut data = vec![];
+                file.take(length.min(MAX_READ_SIZE) as u64)
+                    .read_to_end(&mut data)?;
+                Ok((handle, data))
+            }
+            Command::Write => {
+                let file = self.open.get_mut(&handle).ok_or(Status::InvalidHandle)?;
+                file.write_all(&payload)?;
+                Ok((handle, vec![]))
+            }
+            Command::Seek => {
+                let offset = u32::from_le_bytes(
+                    payload
+                        .as_slice()
+                        .try_into()
+                        .map_err(|_| Status::InvalidRequest)?,
+                );
+                let file = self.open.get_mut(&handle).ok_or(Status::InvalidHandle)?;
+                file.seek(SeekFrom::Start(offset.into()))?;
+                Ok((handle, vec![]))
+            }
+        }
+    }
+
+    fn open_file(&self, mode: u8, path: &str) -> Result<File, Status> {
+        let path = Path::new(path);
+        // Only allow plain relative paths, so the firmware can't leave the served directory.
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(Status::PermissionDenied);
+        }
+        let path = self.root.join(path);
+
+        let mut options = OpenOptions::new();
+        match mode {
+            0 => options.read(true),
+            1 => options.write(true).create(true).truncate(true),
+            2 => options.append(true).create(true),
+            _ => return Err(Status::InvalidRequest),
+        };
+
+        tracing::debug!("Opening {} for the target", path.display());
+        Ok(options.open(path)?)
+    }
+}
+
+/// Answers the file requests of the firmware.
+pub(crate) struct FileServer {
+    up_channel: RttActiveUpChannel,
+    down_channel: RttActiveDownChannel,
+    files: Files,
+    /// Received data which doesn't form a complete request yet.
+    received: Vec<u8>,
+    /// Responses which didn't fit into the down channel yet.
+    pending: Vec<u8>,
+    read_buffer: Vec<u8>,
+}
+
+impl FileServer {
+    /// Takes the file server channels from the RTT target, so they are not printed.
+    ///
+    /// Returns `None` if the firmware doesn't have both channels.
+    pub fn take_channels(root: &Path, rtta: &mut RttActiveTarget) -> Option<Self> {
+        let up = rtta
+            .active_up_channels
+            .iter()
+            .position(|channel| channel.up_channel.name() == Some(CHANNEL_NAME))?;
+        let down = rtta
+            .active_down_channels
+            .iter()
+            .position(|channel| channel.down_channel.name() == Some(CHANNEL_NAME))?;
+
+        let up_channel = rtta.active_up_channels.remove(up);
+        let down_channel = rtta.active_down_channels.remove(down);
+
+        Some(Self {
+            read_buffer: vec![0; up_channel.up_channel.buffer_size().max(1)],
+            up_channel,
+            down_channel,
+            files: Files::new(root.to_path_buf()),
+            received: vec![],
+            pending: vec![],
+        })
+    }
+
+    /// Handles the requests received since the last poll.
+    ///
+    /// Returns whether there was any traffic.
+    pub fn poll(&mut self, core: &mut Core) -> anyhow::Result<bool> {
+        let read = self
+            .up_channel
+            .up_channel
+            .read(core, &mut self.read_buffer)
+            .context("Failed to read file server requests")?;
+        self.received.extend_from_slice(&self.read_buffer[..read]);
+
+        while let Some(request) = Frame::decode(&mut self.received)? {
+            self.files.handle(request).encode(&mut self.pending);
+        }
+
+        let mut written = 0;
+        if !self.pending.is_empty() {
+            written = self
+                .down_channel
+                .down_channel
+                .write(core, &self.pending)
+                .context("Failed to write file server responses")?;
+            self.pending.drain(..written);
+        }
+
+        Ok(read > 0 || written > 0)
+    }
+
+    /// Clean up temporary changes made to the channels.
+    pub fn clean_up(&mut self, core: &mut Core) -> anyhow::Result<()> {
+        self.up_channel.clean_up(core)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(command: Command, handle: u8, payload: &[u8]) -> Frame {
+        Frame {
+            code: command as u8,
+            handle,
+            payload: payload.to_vec(),
+        }
+    }
+
+    fn open(files: &mut Files, mode: u8, path: &str) -> Frame {
+        let mut payload = vec![mode];
+        payload.extend_from_slice(path.as_bytes());
+        files.handle(request(Command::Open, 0, &payload))
+    }
+
+    #[test]
+    fn frames_are_decoded_once_complete() {
+        let mut encoded = vec![];
+        request(Command::Write, 3, b"hello").encode(&mut encoded);
+
+        let mut buffer = encoded[..4].to_vec();
+        assert_eq!(Frame::decode(&mut buffer).unwrap(), None);
+
+        buffer.extend_from_slice(&encoded[4..]);
+        buffer.push(0xFF);
+        assert_eq!(
+            Frame::decode(&mut buffer).unwrap(),
+            Some(request(Command::Write, 3, b"hello"))
+        );
+        assert_eq!(buffer, [0xFF]);
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let root = std::env::temp_dir().join(format!("probe-rs-fs-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut files = Files::new(root.clone());
+
+        let opened = open(&mut files, 1, "data.bin");
+        assert_eq!(opened.code, Status::Ok as u8);
+        let written = files.handle(request(Command::Write, opened.handle, b"0123456789"));
+        assert_eq!(written.code, Status::Ok as u8);
+        files.handle(request(Command::Close, opened.handle, &[]));
+
+        let opened = open(&mut files, 0, "data.bin");
+        files.handle(request(Command::Seek, opened.handle, &4u32.to_le_bytes()));
+        let read = files.handle(request(Command::Read, opened.handle, &4u32.to_le_bytes()));
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(read.code, Status::Ok as u8);
+        assert_eq!(read.payload, b"4567");
+    }
+
+    #[test]
+    fn paths_outside_of_the_root_are_rejected() {
+        let mut files = Files::new(std::env::temp_dir());
+
+        assert_eq!(
+            open(&mut files, 0, "../etc/passwd").code,
+            Status::PermissionDenied as u8
+        );
+        assert_eq!(
+            open(&mut files, 0, "/etc/passwd").code,
+            Status::PermissionDenied as u8
+        );
+    }
+
+    #[test]
+    fn unknown_handles_are_rejected() {
+        let mut files = Files::new(std::env::temp_dir());
+
+        let response = files.handle(request(Command::Close, 7, &[]));
+
+        assert_eq!(response.code, Status::InvalidHandle as u8);
+        assert_eq!(response.handle, 7);
+    }
+}
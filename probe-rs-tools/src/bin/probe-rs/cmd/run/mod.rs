@@ -1,3 +1,9 @@
+mod boot_time;
+use boot_time::BootTimer;
+mod file_server;
+use file_server::FileServer;
+mod task_trace;
+use task_trace::TaskTrace;
 mod normal_run_mode;
 use normal_run_mode::*;
 mod test_run_mode;
@@ -12,6 +18,7 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Result};
+use probe_rs::architecture::arm::{component::TraceSink, swo::SwoConfig};
 use probe_rs::debug::{DebugInfo, DebugRegisters};
 use probe_rs::flashing::FileDownloadError;
 use probe_rs::{
@@ -24,13 +31,18 @@ use signal_hook::consts::signal;
 use time::UtcOffset;
 
 use crate::util::common_options::{BinaryDownloadOptions, ProbeOptions};
-use crate::util::flash::{build_loader, run_flash_download};
+use crate::util::cycle_counter;
+use crate::util::devices::{for_each_device, Device, DeviceOptions, PrefixedWriter};
+use crate::util::flash::{build_loader, run_flash_download, take_over_ctrl_c};
+use crate::util::itm::{ItmFormat, ItmLog};
+use crate::util::measurement::CurrentRecorder;
+use crate::util::project_config::ProjectConfig;
 use crate::util::rtt::{
     self, ChannelDataCallbacks, DefmtState, RttActiveTarget, RttChannelConfig, RttConfig,
 };
 use crate::FormatOptions;
 
-#[derive(clap::Parser)]
+#[derive(Clone, clap::Parser)]
 pub struct Cmd {
     /// Options only used when in normal run mode
     #[clap(flatten)]
@@ -45,11 +57,14 @@ pub struct Cmd {
     pub(crate) shared_options: SharedOptions,
 }
 
-#[derive(Debug, clap::Parser)]
+#[derive(Debug, Clone, clap::Parser)]
 pub struct SharedOptions {
     #[clap(flatten)]
     pub(crate) probe_options: ProbeOptions,
 
+    #[clap(flatten)]
+    pub(crate) device_options: DeviceOptions,
+
     #[clap(flatten)]
     pub(crate) download_options: BinaryDownloadOptions,
 
@@ -62,6 +77,13 @@ pub struct SharedOptions {
     )]
     pub(crate) path: PathBuf,
 
+    /// The ELF file with the symbols and debug info of the firmware, if they were stripped from
+    /// the flashed binary.
+    ///
+    /// Stack traces, RTT and defmt use this file instead of the flashed one.
+    #[clap(long, value_name = "PATH")]
+    pub(crate) symbols: Option<PathBuf>,
+
     /// Always print the stacktrace on ctrl + c.
     #[clap(long)]
     pub(crate) always_print_stacktrace: bool,
@@ -84,6 +106,62 @@ pub struct SharedOptions {
     /// Scan the memory to find the RTT control block
     #[clap(long)]
     pub(crate) rtt_scan_memory: bool,
+
+    /// Print the data received on the UART of the probe with the given baud rate.
+    ///
+    /// This requires a CMSIS-DAP probe which implements the DAP_UART commands.
+    #[clap(long)]
+    pub(crate) uart_baud: Option<u32>,
+
+    /// Print the log output written to ITM stimulus port 0, received over SWO.
+    ///
+    /// This is an alternative to RTT for firmware which can't spare the RAM for the RTT buffers.
+    /// `text` prints the data as it is, e.g. from `iprintln!`, and `defmt` decodes the data
+    /// written by `defmt-itm`.
+    #[clap(long, value_name = "FORMAT")]
+    pub(crate) itm: Option<ItmFormat>,
+
+    /// The frequency of the clock feeding the TPIU/SWO module of the target in Hz, usually the
+    /// core clock.
+    ///
+    /// If it is not given, the core clock is measured with the cycle counter of the core. For
+    /// this, the firmware is started a moment before the log output is received.
+    #[clap(long, value_name = "HZ")]
+    pub(crate) swo_clock: Option<u32>,
+
+    /// The baud rate of the SWO output.
+    #[clap(long, value_name = "BAUD", default_value_t = 1_000_000)]
+    pub(crate) swo_baud: u32,
+
+    /// Measure the current consumption of the target with a Nordic PPK2 in ampere meter mode,
+    /// and write it to the given CSV file.
+    ///
+    /// The output of the target is added to the file as markers.
+    #[clap(long, value_name = "PATH")]
+    pub(crate) current_csv: Option<PathBuf>,
+
+    /// Serve the files of the given directory to the firmware, over the `probe-rs-fs` RTT
+    /// channels.
+    ///
+    /// This is a faster alternative to semihosting for firmware which reads or writes a lot of
+    /// data on the host.
+    #[clap(long, value_name = "DIR")]
+    pub(crate) rtt_fileserver: Option<PathBuf>,
+
+    /// Record the scheduler events the firmware sends on the `probe-rs-trace` RTT channel, and
+    /// write them to the given file.
+    ///
+    /// The file can be opened with Perfetto (https://ui.perfetto.dev) to see which task or ISR
+    /// ran when.
+    #[clap(long, value_name = "PATH")]
+    pub(crate) task_trace: Option<PathBuf>,
+}
+
+impl SharedOptions {
+    /// The ELF file with the symbols of the firmware.
+    fn symbols_path(&self) -> &Path {
+        self.symbols.as_deref().unwrap_or(&self.path)
+    }
 }
 
 impl Cmd {
@@ -92,12 +170,62 @@ impl Cmd {
         lister: &Lister,
         run_download: bool,
         timestamp_offset: UtcOffset,
+    ) -> Result<()> {
+        let devices = self.shared_options.device_options.devices()?;
+        if !devices.is_empty() {
+            return self.run_on_devices(&devices, run_download, timestamp_offset);
+        }
+
+        self.run_on_device(lister, run_download, timestamp_offset, None)
+    }
+
+    /// Runs the program on all devices concurrently, prefixing their output with their label.
+    fn run_on_devices(
+        &self,
+        devices: &[Device],
+        run_download: bool,
+        timestamp_offset: UtcOffset,
+    ) -> Result<()> {
+        for_each_device(
+            devices,
+            &self.shared_options.probe_options,
+            |probe_options, label| {
+                let mut cmd = self.clone();
+                cmd.shared_options.probe_options = probe_options;
+                // Progress bars of several devices would overwrite each other.
+                cmd.shared_options.download_options.disable_progressbars = true;
+
+                // The lister can't be shared between threads.
+                cmd.run_on_device(
+                    &Lister::new(),
+                    run_download,
+                    timestamp_offset,
+                    Some(label.to_string()),
+                )
+            },
+        )
+    }
+
+    fn run_on_device(
+        self,
+        lister: &Lister,
+        run_download: bool,
+        timestamp_offset: UtcOffset,
+        output_prefix: Option<String>,
     ) -> Result<()> {
         let run_mode = detect_run_mode(&self)?;
+        let symbols = self.shared_options.symbols_path().to_path_buf();
+
+        let rtt = &ProjectConfig::get().rtt;
+        let no_location = self.shared_options.no_location || rtt.no_location;
+        let log_format = self
+            .shared_options
+            .log_format
+            .or_else(|| rtt.log_format.clone());
 
         let (mut session, probe_options) =
             self.shared_options.probe_options.simple_attach(lister)?;
-        let core_id = rtt::get_target_core_id(&mut session, &self.shared_options.path);
+        let core_id = rtt::get_target_core_id(&mut session, &symbols);
 
         if run_download {
             let loader = build_loader(
@@ -121,7 +249,27 @@ impl Cmd {
                 .reset_and_halt(Duration::from_millis(100))?;
         }
 
-        let rtt_scan_regions = match self.shared_options.rtt_scan_memory {
+        if self.shared_options.itm.is_some() {
+            let clock = match self.shared_options.swo_clock {
+                Some(clock) => clock,
+                None => cycle_counter::measure_core_clock(&mut session.core(core_id)?)
+                    .context("Failed to measure the core clock, please pass --swo-clock")?,
+            };
+
+            let config = SwoConfig::new(clock).set_baud(self.shared_options.swo_baud);
+            if config.baud_error() > 3.0 {
+                tracing::warn!(
+                    "The SWO baud rate of {} Bd can't be derived from the {clock} Hz clock, the target sends {} Bd. Choose a --swo-baud which divides the clock.",
+                    config.baud(),
+                    config.actual_baud()
+                );
+            }
+            session
+                .setup_tracing(core_id, TraceSink::Swo(config))
+                .context("Failed to set up SWO")?;
+        }
+
+        let rtt_scan_regions = match self.shared_options.rtt_scan_memory || rtt.scan_memory {
             true => session.target().rtt_scan_regions.clone(),
             false => ScanRegion::Ranges(vec![]),
         };
@@ -132,10 +280,17 @@ impl Cmd {
                 core_id,
                 rtt_scan_regions,
                 timestamp_offset,
-                path: self.shared_options.path,
+                path: symbols,
                 always_print_stacktrace: self.shared_options.always_print_stacktrace,
-                no_location: self.shared_options.no_location,
-                log_format: self.shared_options.log_format,
+                no_location,
+                log_format,
+                uart_baud: self.shared_options.uart_baud,
+                itm_format: self.shared_options.itm,
+                current_csv: self.shared_options.current_csv,
+                file_server_root: self.shared_options.rtt_fileserver,
+                task_trace_path: self.shared_options.task_trace,
+                output_prefix,
+                boot_timer: None,
             },
         )?;
 
@@ -148,7 +303,7 @@ trait RunMode {
 }
 
 fn detect_run_mode(cmd: &Cmd) -> anyhow::Result<Box<dyn RunMode>> {
-    if elf_contains_test(&cmd.shared_options.path)? {
+    if elf_contains_test(cmd.shared_options.symbols_path())? {
         // We tolerate the run options, even in test mode so that you can set
         // `probe-rs run --catch-hardfault` as cargo runner (used for both unit tests and normal binaries)
         tracing::info!("Detected embedded-test in ELF file. Running as test");
@@ -168,6 +323,9 @@ fn detect_run_mode(cmd: &Cmd) -> anyhow::Result<Box<dyn RunMode>> {
     }
 }
 
+/// Runs the core for a moment, so the firmware sets up its clocks, and measures the core clock.
+///
+/// The core keeps running afterwards.
 fn elf_contains_test(path: &Path) -> anyhow::Result<bool> {
     let mut file = File::open(path).map_err(FileDownloadError::IO)?;
 
@@ -195,11 +353,24 @@ fn elf_contains_test(path: &Path) -> anyhow::Result<bool> {
 struct RunLoop {
     core_id: usize,
     rtt_scan_regions: ScanRegion,
+    /// The ELF file with the symbols of the firmware.
     path: PathBuf,
     timestamp_offset: UtcOffset,
     always_print_stacktrace: bool,
     no_location: bool,
     log_format: Option<String>,
+    uart_baud: Option<u32>,
+    /// The format of the log output on ITM stimulus port 0, if it is printed.
+    itm_format: Option<ItmFormat>,
+    current_csv: Option<PathBuf>,
+    /// The directory served to the firmware, if any.
+    file_server_root: Option<PathBuf>,
+    /// The file to write the task trace to, if any.
+    task_trace_path: Option<PathBuf>,
+    /// Prefixes the output lines, when running on several devices.
+    output_prefix: Option<String>,
+    /// Measures the boot time, if requested.
+    boot_timer: Option<BootTimer>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -230,8 +401,7 @@ impl RunLoop {
     fn run_until<F, R>(
         &self,
         core: &mut Core,
-        catch_hardfault: bool,
-        catch_reset: bool,
+        vector_catch: &[VectorCatchCondition],
         output_stream: OutputStream,
         timeout: Option<Duration>,
         mut predicate: F,
@@ -239,19 +409,13 @@ impl RunLoop {
     where
         F: FnMut(HaltReason, &mut Core) -> Result<Option<R>>,
     {
-        if catch_hardfault || catch_reset {
+        if !vector_catch.is_empty() {
             if !core.core_halted()? {
                 core.halt(Duration::from_millis(100))?;
             }
 
-            if catch_hardfault {
-                match core.enable_vector_catch(VectorCatchCondition::HardFault) {
-                    Ok(_) | Err(Error::NotImplemented(_)) => {} // Don't output an error if vector_catch hasn't been implemented
-                    Err(e) => tracing::error!("Failed to enable_vector_catch: {:?}", e),
-                }
-            }
-            if catch_reset {
-                match core.enable_vector_catch(VectorCatchCondition::CoreReset) {
+            for &condition in vector_catch {
+                match core.enable_vector_catch(condition) {
                     Ok(_) | Err(Error::NotImplemented(_)) => {} // Don't output an error if vector_catch hasn't been implemented
                     Err(e) => tracing::error!("Failed to enable_vector_catch: {:?}", e),
                 }
@@ -262,14 +426,31 @@ impl RunLoop {
             core.run()?;
         }
         let start = Instant::now();
+        if let Some(boot_timer) = &self.boot_timer {
+            boot_timer.released();
+        }
 
         let mut rtt_config = RttConfig::default();
-        rtt_config.channels.push(RttChannelConfig {
-            channel_number: Some(0),
-            show_location: !self.no_location,
-            log_format: self.log_format.clone(),
-            ..Default::default()
-        });
+        let channels = &ProjectConfig::get().rtt.channels;
+        if channels.is_empty() {
+            rtt_config.channels.push(RttChannelConfig {
+                channel_number: Some(0),
+                show_location: !self.no_location,
+                log_format: self.log_format.clone(),
+                ..Default::default()
+            });
+        } else {
+            for channel in channels {
+                rtt_config.channels.push(RttChannelConfig {
+                    show_location: channel.show_location && !self.no_location,
+                    log_format: channel
+                        .log_format
+                        .clone()
+                        .or_else(|| self.log_format.clone()),
+                    ..channel.clone()
+                });
+            }
+        }
 
         let mut rtta = attach_to_rtt(
             core,
@@ -281,14 +462,90 @@ impl RunLoop {
         )
         .context("Failed to attach to RTT")?;
 
-        let result = self.do_run_until(
-            core,
-            &mut rtta,
-            output_stream,
-            timeout,
-            start,
-            &mut predicate,
-        );
+        let mut services = match rtta.as_mut() {
+            Some(rtta) => self.take_rtt_services(rtta)?,
+            None => RttServices::default(),
+        };
+
+        if let Some(baud_rate) = self.uart_baud {
+            let uart = core
+                .uart_interface_mut()
+                .context("The probe does not support UART communication")?;
+            let actual_baud_rate = uart.enable_uart(baud_rate)?;
+            tracing::info!("Receiving UART data with a baud rate of {actual_baud_rate}");
+        }
+
+        let mut itm_log = match self.itm_format {
+            Some(format) => Some(ItmLog::new(
+                format,
+                Some(&self.path),
+                &RttChannelConfig {
+                    show_location: !self.no_location,
+                    log_format: self.log_format.clone(),
+                    ..Default::default()
+                },
+                self.timestamp_offset,
+            )?),
+            None => None,
+        };
+
+        let mut recorder = match &self.current_csv {
+            Some(path) => Some(CurrentRecorder::start(path)?),
+            None => None,
+        };
+
+        let mut stdout;
+        let mut stderr;
+        let output_stream: &mut dyn Write = match output_stream {
+            OutputStream::Stdout => {
+                stdout = std::io::stdout();
+                &mut stdout
+            }
+            OutputStream::Stderr => {
+                stderr = std::io::stderr();
+                &mut stderr
+            }
+        };
+        let mut prefixed;
+        let output_stream: &mut dyn Write = match &self.output_prefix {
+            Some(label) => {
+                prefixed = PrefixedWriter::new(output_stream, label);
+                &mut prefixed
+            }
+            None => output_stream,
+        };
+
+        let result = match recorder.as_mut() {
+            Some(recorder) => self.do_run_until(
+                core,
+                &mut rtta,
+                &mut services,
+                &mut itm_log,
+                &mut MarkerOutput {
+                    inner: output_stream,
+                    recorder,
+                },
+                timeout,
+                start,
+                &mut predicate,
+            ),
+            None => self.do_run_until(
+                core,
+                &mut rtta,
+                &mut services,
+                &mut itm_log,
+                output_stream,
+                timeout,
+                start,
+                &mut predicate,
+            ),
+        };
+
+        if let Some(recorder) = recorder {
+            if let Err(e) = recorder.finish() {
+                tracing::warn!("Failed to measure the current: {e}");
+            }
+        }
 
         // Always clean up after RTT but don't overwrite the original result.
         let cleanup_result = if let Some(mut rtta) = rtta {
@@ -296,6 +553,23 @@ impl RunLoop {
         } else {
             Ok(())
         };
+        let cleanup_result = cleanup_result.and(services.finish(core));
+
+        if self.uart_baud.is_some() {
+            if let Some(uart) = core.uart_interface_mut() {
+                if let Err(e) = uart.disable_uart() {
+                    tracing::warn!("Failed to disable the UART: {e}");
+                }
+            }
+        }
+
+        if itm_log.is_some() {
+            if let Some(swo) = core.swo_interface_mut() {
+                if let Err(e) = swo.disable_swo() {
+                    tracing::warn!("Failed to disable SWO: {e}");
+                }
+            }
+        }
 
         if result.is_ok() {
             // If the result is Ok, we return the potential error during cleanup.
@@ -305,11 +579,41 @@ impl RunLoop {
         result
     }
 
+    /// Takes the RTT channels which are handled by probe-rs instead of being printed.
+    fn take_rtt_services(&self, rtta: &mut RttActiveTarget) -> Result<RttServices> {
+        let mut services = RttServices::default();
+
+        if let Some(root) = &self.file_server_root {
+            services.file_server = FileServer::take_channels(root, rtta);
+            if services.file_server.is_none() {
+                tracing::warn!(
+                    "The firmware has no `{}` RTT channels, the files are not served.",
+                    file_server::CHANNEL_NAME
+                );
+            }
+        }
+
+        if let Some(path) = &self.task_trace_path {
+            services.task_trace = TaskTrace::take_channel(path, rtta)?;
+            if services.task_trace.is_none() {
+                tracing::warn!(
+                    "The firmware has no `{}` RTT channel, no task trace is recorded.",
+                    task_trace::CHANNEL_NAME
+                );
+            }
+        }
+
+        Ok(services)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn do_run_until<F, R>(
         &self,
         core: &mut Core,
         rtta: &mut Option<RttActiveTarget>,
-        output_stream: OutputStream,
+        services: &mut RttServices,
+        itm_log: &mut Option<ItmLog>,
+        output_stream: &mut dyn Write,
         timeout: Option<Duration>,
         start: Instant,
         predicate: &mut F,
@@ -318,21 +622,10 @@ impl RunLoop {
         F: FnMut(HaltReason, &mut Core) -> Result<Option<R>>,
     {
         let exit = Arc::new(AtomicBool::new(false));
+        // Stop on Ctrl-C, instead of terminating like after flashing.
+        take_over_ctrl_c();
         let sig_id = signal_hook::flag::register(signal::SIGINT, exit.clone())?;
 
-        let mut stdout;
-        let mut stderr;
-        let output_stream: &mut dyn Write = match output_stream {
-            OutputStream::Stdout => {
-                stdout = std::io::stdout();
-                &mut stdout
-            }
-            OutputStream::Stderr => {
-                stderr = std::io::stderr();
-                &mut stderr
-            }
-        };
-
         let return_reason = loop {
             // check for halt first, poll rtt after.
             // this is important so we do one last poll after halt, so we flush all messages
@@ -356,6 +649,15 @@ impl RunLoop {
             }
 
             let had_rtt_data = poll_rtt(rtta, core, output_stream)?;
+            if let Some(boot_timer) = self.boot_timer.as_ref().filter(|_| had_rtt_data) {
+                boot_timer.output();
+            }
+            let had_uart_data = self.uart_baud.is_some() && poll_uart(core, output_stream)?;
+            let had_service_data = services.poll(core)?;
+            let had_itm_data = match itm_log.as_mut() {
+                Some(itm_log) => poll_itm(itm_log, core, output_stream)?,
+                None => false,
+            };
 
             if return_reason.is_none() {
                 if exit.load(Ordering::Relaxed) {
@@ -378,7 +680,14 @@ impl RunLoop {
             //
             // If the polling frequency is too high, the USB connection to the probe
             // can become unstable. Hence we only pull as little as necessary.
-            if had_rtt_data {
+            //
+            // While measuring the boot time, we poll at 1kHz for a better resolution.
+            if had_rtt_data
+                || had_uart_data
+                || had_service_data
+                || had_itm_data
+                || self.boot_timer.as_ref().is_some_and(BootTimer::waiting)
+            {
                 thread::sleep(Duration::from_millis(1));
             } else {
                 thread::sleep(Duration::from_millis(100));
@@ -402,6 +711,59 @@ impl RunLoop {
     }
 }
 
+/// The RTT channels which are handled by probe-rs, instead of being printed.
+#[derive(Default)]
+struct RttServices {
+    file_server: Option<FileServer>,
+    task_trace: Option<TaskTrace>,
+}
+
+impl RttServices {
+    /// Handles the data received since the last poll.
+    ///
+    /// Returns whether there was any traffic.
+    fn poll(&mut self, core: &mut Core) -> Result<bool> {
+        let mut had_data = false;
+        if let Some(file_server) = &mut self.file_server {
+            had_data |= file_server.poll(core)?;
+        }
+        if let Some(task_trace) = &mut self.task_trace {
+            had_data |= task_trace.poll(core)?;
+        }
+        Ok(had_data)
+    }
+
+    /// Finishes the outputs, and cleans up temporary changes made to the channels.
+    fn finish(self, core: &mut Core) -> Result<()> {
+        let mut result = Ok(());
+        if let Some(mut file_server) = self.file_server {
+            result = result.and(file_server.clean_up(core));
+        }
+        if let Some(task_trace) = self.task_trace {
+            result = result.and(task_trace.finish(core));
+        }
+        result
+    }
+}
+
+/// Prints the output of the target, and adds it as markers to the current measurement.
+struct MarkerOutput<'a> {
+    inner: &'a mut dyn Write,
+    recorder: &'a mut CurrentRecorder,
+}
+
+impl Write for MarkerOutput<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.recorder.record_output(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 /// Prints the stacktrace of the current execution state.
 fn print_stacktrace<S: Write + ?Sized>(
     core: &mut impl CoreInterface,
@@ -471,7 +833,7 @@ fn print_stacktrace<S: Write + ?Sized>(
 }
 
 /// Poll RTT and print the received buffer.
-fn poll_rtt<S: Write + ?Sized>(
+pub(crate) fn poll_rtt<S: Write + ?Sized>(
     rtta: &mut Option<RttActiveTarget>,
     core: &mut Core<'_>,
     out_stream: &mut S,
@@ -510,7 +872,37 @@ fn poll_rtt<S: Write + ?Sized>(
     Ok(had_data)
 }
 
-fn attach_to_rtt(
+/// Prints the data received on the UART of the probe.
+///
+/// Returns whether data was received.
+fn poll_uart<S: Write + ?Sized>(core: &mut Core<'_>, out_stream: &mut S) -> Result<bool> {
+    let Some(uart) = core.uart_interface_mut() else {
+        return Ok(false);
+    };
+
+    let data = uart.read_uart()?;
+    out_stream.write_all(&data)?;
+    out_stream.flush()?;
+
+    Ok(!data.is_empty())
+}
+
+/// Reads the SWO data received since the last poll, and prints the log output in it.
+fn poll_itm<S: Write + ?Sized>(
+    itm_log: &mut ItmLog,
+    core: &mut Core<'_>,
+    out_stream: &mut S,
+) -> Result<bool> {
+    let swo = core
+        .swo_interface_mut()
+        .context("The probe does not support reading SWO data")?;
+    let data = swo.read_swo()?;
+    itm_log.process(&data, out_stream)?;
+
+    Ok(!data.is_empty())
+}
+
+pub(crate) fn attach_to_rtt(
     core: &mut Core<'_>,
     timeout: Duration,
     rtt_region: &ScanRegion,
@@ -539,3 +931,32 @@ fn attach_to_rtt(
     let defmt_state = DefmtState::try_from_bytes(&elf)?;
     RttActiveTarget::new(core, rtt, defmt_state, rtt_config, timestamp_offset).map(Some)
 }
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn symbols_default_to_the_flashed_file() {
+        let cmd = Cmd::parse_from(["run", "firmware.elf"]);
+        assert_eq!(cmd.shared_options.symbols_path(), Path::new("firmware.elf"));
+
+        let cmd = Cmd::parse_from(["run", "firmware.bin", "--symbols", "firmware.elf"]);
+        assert_eq!(cmd.shared_options.path, Path::new("firmware.bin"));
+        assert_eq!(cmd.shared_options.symbols_path(), Path::new("firmware.elf"));
+    }
+
+    #[test]
+    fn boot_time_defaults_to_main() {
+        let cmd = Cmd::parse_from(["run", "firmware.elf"]);
+        assert_eq!(cmd.run_options.boot_time, None);
+
+        let cmd = Cmd::parse_from(["run", "firmware.elf", "--boot-time"]);
+        assert_eq!(cmd.run_options.boot_time.as_deref(), Some("main"));
+
+        let cmd = Cmd::parse_from(["run", "--boot-time=init", "firmware.elf"]);
+        assert_eq!(cmd.run_options.boot_time.as_deref(), Some("init"));
+    }
+}
@@ -1,6 +1,8 @@
-use crate::cmd::run::{OutputStream, RunLoop, RunMode};
+use crate::cmd::run::{BootTimer, OutputStream, RunLoop, RunMode};
 use anyhow::anyhow;
-use probe_rs::{BreakpointCause, Core, HaltReason, SemihostingCommand, Session};
+use probe_rs::{
+    BreakpointCause, Core, HaltReason, SemihostingCommand, Session, VectorCatchCondition,
+};
 
 /// Options only used in normal run mode
 #[derive(Debug, clap::Parser, Clone)]
@@ -11,6 +13,28 @@ pub struct NormalRunOptions {
     /// Enable hardfault vector catch if its supported on the target.
     #[clap(long, help_heading = "RUN OPTIONS")]
     pub catch_hardfault: bool,
+    /// Halt the core on the given exceptions, e.g. `--catch bus-fault,usage-fault`.
+    ///
+    /// Possible values are hard-fault, core-reset, secure-fault, bus-fault, mem-manage,
+    /// usage-fault and all.
+    #[clap(
+        long,
+        value_delimiter = ',',
+        value_name = "CONDITION",
+        help_heading = "RUN OPTIONS"
+    )]
+    pub catch: Vec<VectorCatchCondition>,
+    /// Reset the target and print the time until the first RTT output, and until the core
+    /// reaches the given symbol, e.g. `--boot-time=init`. Without symbol, `main` is used.
+    #[clap(
+        long,
+        value_name = "SYMBOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "main",
+        help_heading = "RUN OPTIONS"
+    )]
+    pub boot_time: Option<String>,
 }
 
 /// Normal run mode (non-test)
@@ -24,10 +48,23 @@ impl NormalRunMode {
     }
 }
 impl RunMode for NormalRunMode {
-    fn run(&self, mut session: Session, run_loop: RunLoop) -> anyhow::Result<()> {
+    fn run(&self, mut session: Session, mut run_loop: RunLoop) -> anyhow::Result<()> {
         let mut core = session.core(run_loop.core_id)?;
 
-        let halt_handler = |halt_reason: HaltReason, _core: &mut Core| {
+        if let Some(symbol) = &self.run_options.boot_time {
+            run_loop.boot_timer = Some(BootTimer::arm(&mut core, &run_loop.path, symbol)?);
+        }
+        let boot_timer = run_loop.boot_timer.as_ref();
+
+        let halt_handler = |halt_reason: HaltReason, core: &mut Core| {
+            if let (HaltReason::Breakpoint(BreakpointCause::Hardware), Some(boot_timer)) =
+                (&halt_reason, boot_timer)
+            {
+                if boot_timer.halted(core)? {
+                    return Ok(None);
+                }
+            }
+
             let HaltReason::Breakpoint(BreakpointCause::Semihosting(cmd)) = halt_reason else {
                 anyhow::bail!("CPU halted unexpectedly.");
             };
@@ -53,10 +90,17 @@ impl RunMode for NormalRunMode {
             }
         };
 
+        let mut vector_catch = self.run_options.catch.clone();
+        if self.run_options.catch_hardfault {
+            vector_catch.push(VectorCatchCondition::HardFault);
+        }
+        if self.run_options.catch_reset {
+            vector_catch.push(VectorCatchCondition::CoreReset);
+        }
+
         run_loop.run_until(
             &mut core,
-            self.run_options.catch_hardfault,
-            self.run_options.catch_reset,
+            &vector_catch,
             OutputStream::Stdout,
             None,
             halt_handler,
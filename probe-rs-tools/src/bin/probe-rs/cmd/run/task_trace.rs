@@ -0,0 +1,378 @@
+//! Records the scheduler events of an RTOS, streamed by the firmware over RTT, as a trace which
+//! can be opened with [Perfetto](https://ui.perfetto.dev) or `chrome://tracing`.
+//!
+//! The firmware writes the events from its scheduler hooks to an RTT up channel named
+//! `probe-rs-trace`. Every event starts with its type, followed by a `u32` timestamp in ticks,
+//! and the fields of the event. All integers are little endian.
+//!
+//! * `0` start: the tick frequency in Hz, as `u32`. Must be sent before any other event.
+//! * `1` task created: the task id (`u8`), the length of the name (`u8`) and the UTF-8 name.
+//! * `2` task switched in: the id of the task which runs from now on.
+//! * `3` idle: no task runs from now on.
+//! * `4` ISR entered: the interrupt number, as `u16`.
+//! * `5` ISR exited: the interrupt number, as `u16`.
+//! * `6` marker: the length of the text (`u8`) and the UTF-8 text.
+//!
+//! The timestamps may wrap around, as long as no two events are more than `u32::MAX` ticks
+//! apart.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Context;
+use probe_rs::Core;
+use serde_json::{json, Value};
+
+use crate::util::rtt::{RttActiveTarget, RttActiveUpChannel};
+
+/// The name of the RTT channel used for the scheduler events.
+pub(crate) const CHANNEL_NAME: &str = "probe-rs-trace";
+
+/// The size of the event type and the timestamp, before the fields of an event.
+const HEADER_SIZE: usize = 5;
+
+/// The thread id of the ISR track. Tasks use their id as thread id.
+const ISR_TRACK: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+enum EventKind {
+    Start { frequency: u32 },
+    TaskCreated { id: u8, name: String },
+    TaskSwitchedIn { id: u8 },
+    Idle,
+    IsrEntered { irq: u16 },
+    IsrExited { irq: u16 },
+    Marker { text: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Event {
+    /// The timestamp in ticks, extended to 64 bits.
+    ticks: u64,
+    kind: EventKind,
+}
+
+/// Splits the byte stream of the firmware into events.
+#[derive(Default)]
+struct Decoder {
+    buffer: Vec<u8>,
+    /// The last timestamp, used to extend the 32 bit timestamps of the target.
+    last_ticks: Option<u64>,
+}
+
+impl Decoder {
+    fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete event, if there is one.
+    fn next_event(&mut self) -> anyhow::Result<Option<Event>> {
+        let Some(header) = self.buffer.get(..HEADER_SIZE) else {
+            return Ok(None);
+        };
+        let event_type = header[0];
+        let timestamp = u32::from_le_bytes(header[1..].try_into().unwrap());
+        let fields = &self.buffer[HEADER_SIZE..];
+
+        let decoded = match event_type {
+            0 => read_u32(fields).map(|(frequency, len)| (EventKind::Start { frequency }, len)),
+            1 => fields.split_first().and_then(|(&id, rest)| {
+                read_string(rest).map(|(name, len)| (EventKind::TaskCreated { id, name }, 1 + len))
+            }),
+            2 => fields
+                .first()
+                .map(|&id| (EventKind::TaskSwitchedIn { id }, 1)),
+            3 => Some((EventKind::Idle, 0)),
+            4 => read_u16(fields).map(|(irq, len)| (EventKind::IsrEntered { irq }, len)),
+            5 => read_u16(fields).map(|(irq, len)| (EventKind::IsrExited { irq }, len)),
+            6 => read_string(fields).map(|(text, len)| (EventKind::Marker { text }, len)),
+            other => anyhow::bail!(
+                "Received the unknown task trace event {other:#04x}, the RTT data is probably corrupt."
+            ),
+        };
+
+        let Some((kind, fields_len)) = decoded else {
+            return Ok(None);
+        };
+        self.buffer.drain(..HEADER_SIZE + fields_len);
+
+        let ticks = match self.last_ticks {
+            Some(last) => {
+                let delta = timestamp.wrapping_sub(last as u32);
+                last + u64::from(delta)
+            }
+            None => u64::from(timestamp),
+        };
+        self.last_ticks = Some(ticks);
+
+        Ok(Some(Event { ticks, kind }))
+    }
+}
+
+fn read_u16(data: &[u8]) -> Option<(u16, usize)> {
+    let bytes = data.get(..2)?;
+    Some((u16::from_le_bytes(bytes.try_into().unwrap()), 2))
+}
+
+fn read_u32(data: &[u8]) -> Option<(u32, usize)> {
+    let bytes = data.get(..4)?;
+    Some((u32::from_le_bytes(bytes.try_into().unwrap()), 4))
+}
+
+/// Reads a string prefixed with its length.
+fn read_string(data: &[u8]) -> Option<(String, usize)> {
+    let (&len, rest) = data.split_first()?;
+    let bytes = rest.get(..usize::from(len))?;
+    Some((
+        String::from_utf8_lossy(bytes).into_owned(),
+        1 + usize::from(len),
+    ))
+}
+
+/// Turns the events into trace events in the Chrome trace event format.
+#[derive(Default)]
+struct Exporter {
+    frequency: Option<u32>,
+    /// The task which runs, and since when.
+    running: Option<(u8, f64)>,
+    /// The ISRs which were entered but not exited yet.
+    isrs: BTreeMap<u16, f64>,
+    /// Whether the warning about events before the start was printed.
+    warned_missing_start: bool,
+}
+
+impl Exporter {
+    fn timestamp_us(&self, ticks: u64) -> Option<f64> {
+        self.frequency
+            .map(|frequency| ticks as f64 * 1_000_000.0 / f64::from(frequency))
+    }
+
+    fn export(&mut self, event: Event, output: &mut Vec<Value>) {
+        if let EventKind::Start { frequency } = event.kind {
+            if frequency == 0 {
+                tracing::warn!(
+                    "The task trace started with a tick frequency of 0 Hz, ignoring it."
+                );
+                return;
+            }
+            self.frequency = Some(frequency);
+            output.push(metadata("process_name", 0, "Target"));
+            output.push(metadata("thread_name", ISR_TRACK, "ISRs"));
+            return;
+        }
+
+        let Some(ts) = self.timestamp_us(event.ticks) else {
+            if !self.warned_missing_start {
+                tracing::warn!("Dropping task trace events received before the start event.");
+                self.warned_missing_start = true;
+            }
+            return;
+        };
+
+        match event.kind {
+            EventKind::Start { .. } => unreachable!(),
+            EventKind::TaskCreated { id, name } => {
+                output.push(metadata("thread_name", u32::from(id), &name));
+            }
+            EventKind::TaskSwitchedIn { id } => {
+                self.end_running_task(ts, output);
+                self.running = Some((id, ts));
+            }
+            EventKind::Idle => self.end_running_task(ts, output),
+            EventKind::IsrEntered { irq } => {
+                self.isrs.insert(irq, ts);
+            }
+            EventKind::IsrExited { irq } => {
+                if let Some(entered) = self.isrs.remove(&irq) {
+                    output.push(complete(&format!("IRQ {irq}"), ISR_TRACK, entered, ts));
+                }
+            }
+            EventKind::Marker { text } => output.push(json!({
+                "name": text,
+                "ph": "i",
+                "s": "g",
+                "ts": ts,
+                "pid": 0,
+                "tid": 0,
+            })),
+        }
+    }
+
+    fn end_running_task(&mut self, ts: f64, output: &mut Vec<Value>) {
+        if let Some((id, since)) = self.running.take() {
+            output.push(complete("running", u32::from(id), since, ts));
+        }
+    }
+}
+
+fn metadata(name: &str, tid: u32, value: &str) -> Value {
+    json!({
+        "name": name,
+        "ph": "M",
+        "pid": 0,
+        "tid": tid,
+        "args": { "name": value },
+    })
+}
+
+fn complete(name: &str, tid: u32, start: f64, end: f64) -> Value {
+    json!({
+        "name": name,
+        "ph": "X",
+        "ts": start,
+        "dur": end - start,
+        "pid": 0,
+        "tid": tid,
+    })
+}
+
+/// Writes the scheduler events of the firmware to a trace file.
+pub(crate) struct TaskTrace {
+    up_channel: RttActiveUpChannel,
+    read_buffer: Vec<u8>,
+    decoder: Decoder,
+    exporter: Exporter,
+    writer: BufWriter<File>,
+    empty: bool,
+}
+
+impl TaskTrace {
+    /// Takes the trace channel from the RTT target, so it is not printed, and creates the trace
+    /// file.
+    ///
+    /// Returns `None` if the firmware doesn't have the channel.
+    pub fn take_channel(path: &Path, rtta: &mut RttActiveTarget) -> anyhow::Result<Option<Self>> {
+        let Some(index) = rtta
+            .active_up_channels
+            .iter()
+            .position(|channel| channel.up_channel.name() == Some(CHANNEL_NAME))
+        else {
+            return Ok(None);
+        };
+
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create the task trace {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(b"[\n")?;
+
+        let up_channel = rtta.active_up_channels.remove(index);
+        Ok(Some(Self {
+            read_buffer: vec![0; up_channel.up_channel.buffer_size().max(1)],
+            up_channel,
+            decoder: Decoder::default(),
+            exporter: Exporter::default(),
+            writer,
+            empty: true,
+        }))
+    }
+
+    /// Reads and records the events received since the last poll.
+    ///
+    /// Returns whether events were received.
+    pub fn poll(&mut self, core: &mut Core) -> anyhow::Result<bool> {
+        let read = self
+            .up_channel
+            .up_channel
+            .read(core, &mut self.read_buffer)
+            .context("Failed to read the task trace")?;
+        self.decoder.push(&self.read_buffer[..read]);
+
+        let mut trace_events = vec![];
+        while let Some(event) = self.decoder.next_event()? {
+            self.exporter.export(event, &mut trace_events);
+        }
+
+        for trace_event in trace_events {
+            let separator = if self.empty { "" } else { ",\n" };
+            self.empty = false;
+            write!(self.writer, "{separator}{trace_event}")?;
+        }
+
+        Ok(read > 0)
+    }
+
+    /// Finishes the trace file, and cleans up temporary changes made to the channel.
+    pub fn finish(mut self, core: &mut Core) -> anyhow::Result<()> {
+        self.writer.write_all(b"\n]\n")?;
+        self.writer.flush()?;
+        self.up_channel.clean_up(core)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(event_type: u8, timestamp: u32, fields: &[u8]) -> Vec<u8> {
+        let mut data = vec![event_type];
+        data.extend_from_slice(&timestamp.to_le_bytes());
+        data.extend_from_slice(fields);
+        data
+    }
+
+    #[test]
+    fn events_are_decoded_once_complete() {
+        let mut decoder = Decoder::default();
+        let data = event(1, 10, b"\x02\x04idle");
+
+        decoder.push(&data[..7]);
+        assert_eq!(decoder.next_event().unwrap(), None);
+
+        decoder.push(&data[7..]);
+        assert_eq!(
+            decoder.next_event().unwrap(),
+            Some(Event {
+                ticks: 10,
+                kind: EventKind::TaskCreated {
+                    id: 2,
+                    name: "idle".to_string()
+                }
+            })
+        );
+        assert!(decoder.buffer.is_empty());
+    }
+
+    #[test]
+    fn timestamps_are_extended_on_wrap_around() {
+        let mut decoder = Decoder::default();
+        decoder.push(&event(3, u32::MAX - 1, &[]));
+        decoder.push(&event(3, 2, &[]));
+
+        assert_eq!(decoder.next_event().unwrap().unwrap().ticks, 0xFFFF_FFFE);
+        assert_eq!(decoder.next_event().unwrap().unwrap().ticks, 0x1_0000_0002);
+    }
+
+    #[test]
+    fn unknown_events_are_an_error() {
+        let mut decoder = Decoder::default();
+        decoder.push(&event(0x42, 0, &[]));
+
+        assert!(decoder.next_event().is_err());
+    }
+
+    #[test]
+    fn task_switches_become_slices() {
+        let mut exporter = Exporter::default();
+        let mut output = vec![];
+
+        for (ticks, kind) in [
+            (0, EventKind::Start { frequency: 1000 }),
+            (1, EventKind::TaskSwitchedIn { id: 1 }),
+            (2, EventKind::IsrEntered { irq: 5 }),
+            (3, EventKind::IsrExited { irq: 5 }),
+            (4, EventKind::TaskSwitchedIn { id: 2 }),
+        ] {
+            exporter.export(Event { ticks, kind }, &mut output);
+        }
+
+        let slices: Vec<_> = output.iter().filter(|event| event["ph"] == "X").collect();
+        assert_eq!(slices.len(), 2);
+        assert_eq!(slices[0]["tid"], ISR_TRACK);
+        assert_eq!(slices[0]["dur"], 1000.0);
+        assert_eq!(slices[1]["tid"], 1);
+        assert_eq!(slices[1]["ts"], 1000.0);
+        assert_eq!(slices[1]["dur"], 3000.0);
+    }
+}
@@ -1,13 +1,15 @@
 use crate::cmd::run::{print_stacktrace, OutputStream, ReturnReason, RunLoop, RunMode};
 use anyhow::Result;
 use libtest_mimic::{Arguments, Failed, FormatSetting, Trial};
-use probe_rs::{BreakpointCause, Core, HaltReason, SemihostingCommand, Session};
+use probe_rs::{
+    BreakpointCause, Core, HaltReason, SemihostingCommand, Session, VectorCatchCondition,
+};
 use serde::Deserialize;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 /// Options only used when in test run mode
-#[derive(Debug, clap::Parser)]
+#[derive(Debug, Clone, clap::Parser)]
 pub struct TestOptions {
     /// Filter string. Only tests which contain this string are run.
     #[clap(
@@ -56,7 +58,7 @@ pub struct TestOptions {
 }
 
 /// Options which are ignored, but exist for compatibility with libtest.
-#[derive(Debug, clap::Parser)]
+#[derive(Debug, Clone, clap::Parser)]
 struct NoOpTestOptions {
     // No-op, ignored (libtest-mimic always runs in no-capture mode)
     #[clap(long = "nocapture", hide = true)]
@@ -166,8 +168,10 @@ impl TestRunMode {
 
         match session_and_runloop.run_loop.run_until(
             &mut core,
-            true,
-            true,
+            &[
+                VectorCatchCondition::HardFault,
+                VectorCatchCondition::CoreReset,
+            ],
             OutputStream::Stderr,
             Some(Duration::from_secs(5)),
             halt_handler,
@@ -230,8 +234,10 @@ impl TestRunMode {
 
         match session_and_runloop.run_loop.run_until(
             core,
-            true,
-            true,
+            &[
+                VectorCatchCondition::HardFault,
+                VectorCatchCondition::CoreReset,
+            ],
             OutputStream::Stderr,
             Some(timeout),
             halt_handler,
@@ -0,0 +1,311 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use probe_rs::flashing::{erase_all, FlashProgress};
+use probe_rs::probe::list::Lister;
+use probe_rs::{Core, MemoryInterface, Session};
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, Scope, INT};
+
+use crate::util::common_options::{BinaryDownloadOptions, LoadedProbeOptions, ProbeOptions};
+use crate::util::flash::{build_loader, run_flash_download};
+use crate::CoreOptions;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Run a Rhai script against the attached target
+///
+/// The script can use the following functions, addresses and values are integers:
+///
+///     halt()  run()  step()  reset()  reset_halt()  core(index)
+///     read8(address)  read32(address)  read_bytes(address, length)
+///     write8(address, value)  write32(address, value)  write_bytes(address, blob)
+///     read_reg(name)  write_reg(name, value)
+///     erase_all()  flash(path)  verify(path)  sleep_ms(milliseconds)
+///
+/// Arguments following the script path are available in the `ARGS` array.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    #[clap(flatten)]
+    download_options: BinaryDownloadOptions,
+
+    /// The path to the script to run.
+    script: PathBuf,
+
+    /// Arguments passed to the script.
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+    script_args: Vec<String>,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let source = std::fs::read_to_string(&self.script)
+            .with_context(|| format!("Failed to read script {}", self.script.display()))?;
+
+        let mut engine = Engine::new();
+        let ast = engine
+            .compile(&source)
+            .map_err(|e| anyhow!("Failed to parse {}: {e}", self.script.display()))?;
+
+        let (session, probe_options) = self.common.simple_attach(lister)?;
+
+        let context = Rc::new(RefCell::new(ScriptContext {
+            session,
+            probe_options,
+            download_options: self.download_options,
+            core: self.shared.core,
+        }));
+        register_functions(&mut engine, &context);
+
+        let mut scope = Scope::new();
+        let args: Array = self.script_args.into_iter().map(Dynamic::from).collect();
+        scope.push_constant("ARGS", args);
+
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| anyhow!("Script {} failed: {e}", self.script.display()))
+    }
+}
+
+struct ScriptContext {
+    session: Session,
+    probe_options: LoadedProbeOptions,
+    download_options: BinaryDownloadOptions,
+    core: usize,
+}
+
+impl ScriptContext {
+    fn with_core<T>(
+        &mut self,
+        f: impl FnOnce(&mut Core) -> Result<T, probe_rs::Error>,
+    ) -> ScriptResult<T> {
+        let mut core = self.session.core(self.core).map_err(script_error)?;
+        f(&mut core).map_err(script_error)
+    }
+
+    fn flash(&mut self, path: &str) -> anyhow::Result<()> {
+        let loader = build_loader(&mut self.session, path, Default::default(), None)?;
+        run_flash_download(
+            &mut self.session,
+            path,
+            &self.download_options,
+            &self.probe_options,
+            loader,
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// Compares the contents of the binary with the target memory, without programming it.
+    fn verify(&mut self, path: &str) -> anyhow::Result<bool> {
+        let loader = build_loader(&mut self.session, path, Default::default(), None)?;
+        let mut core = self.session.core(self.core)?;
+
+        for (address, data) in loader.data() {
+            let mut read_back = vec![0; data.len()];
+            core.read(address, &mut read_back)?;
+            if read_back != data {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+fn script_error(error: impl std::fmt::Display) -> Box<EvalAltResult> {
+    error.to_string().into()
+}
+
+/// Converts a script integer into the given type, failing for out of range values.
+fn convert<T: TryFrom<INT>>(value: INT, what: &str) -> ScriptResult<T> {
+    T::try_from(value).map_err(|_| script_error(format!("{what} {value:#x} is out of range")))
+}
+
+fn register_functions(engine: &mut Engine, context: &Rc<RefCell<ScriptContext>>) {
+    let ctx = context.clone();
+    engine.register_fn("halt", move || -> ScriptResult<INT> {
+        let info = ctx
+            .borrow_mut()
+            .with_core(|core| core.halt(Duration::from_millis(100)))?;
+        Ok(info.pc as INT)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("run", move || -> ScriptResult<()> {
+        ctx.borrow_mut().with_core(|core| core.run())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("step", move || -> ScriptResult<INT> {
+        let info = ctx.borrow_mut().with_core(|core| core.step())?;
+        Ok(info.pc as INT)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("reset", move || -> ScriptResult<()> {
+        ctx.borrow_mut().with_core(|core| core.reset())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("reset_halt", move || -> ScriptResult<INT> {
+        let info = ctx
+            .borrow_mut()
+            .with_core(|core| core.reset_and_halt(Duration::from_millis(100)))?;
+        Ok(info.pc as INT)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("core", move |index: INT| -> ScriptResult<()> {
+        let index: usize = convert(index, "Core index")?;
+        let mut ctx = ctx.borrow_mut();
+        let cores = ctx.session.list_cores().len();
+        if index >= cores {
+            return Err(script_error(format!(
+                "Core {index} does not exist, the target has {cores} core(s)"
+            )));
+        }
+        ctx.core = index;
+        Ok(())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("read8", move |address: INT| -> ScriptResult<INT> {
+        let address = convert(address, "Address")?;
+        let value = ctx
+            .borrow_mut()
+            .with_core(|core| core.read_word_8(address))?;
+        Ok(value.into())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("read32", move |address: INT| -> ScriptResult<INT> {
+        let address = convert(address, "Address")?;
+        let value = ctx
+            .borrow_mut()
+            .with_core(|core| core.read_word_32(address))?;
+        Ok(value.into())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "read_bytes",
+        move |address: INT, length: INT| -> ScriptResult<Blob> {
+            let address = convert(address, "Address")?;
+            let mut data = vec![0; convert(length, "Length")?];
+            ctx.borrow_mut()
+                .with_core(|core| core.read(address, &mut data))?;
+            Ok(data)
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "write8",
+        move |address: INT, value: INT| -> ScriptResult<()> {
+            let address = convert(address, "Address")?;
+            let value = convert(value, "Value")?;
+            ctx.borrow_mut()
+                .with_core(|core| core.write_word_8(address, value))
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "write32",
+        move |address: INT, value: INT| -> ScriptResult<()> {
+            let address = convert(address, "Address")?;
+            let value = convert(value, "Value")?;
+            ctx.borrow_mut()
+                .with_core(|core| core.write_word_32(address, value))
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "write_bytes",
+        move |address: INT, data: Blob| -> ScriptResult<()> {
+            let address = convert(address, "Address")?;
+            ctx.borrow_mut()
+                .with_core(|core| core.write(address, &data))
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn("read_reg", move |name: &str| -> ScriptResult<INT> {
+        ctx.borrow_mut().with_core(|core| {
+            let register = find_register(core, name)?;
+            let value: u64 = core.read_core_reg(register)?;
+            Ok(value as INT)
+        })
+    });
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "write_reg",
+        move |name: &str, value: INT| -> ScriptResult<()> {
+            ctx.borrow_mut().with_core(|core| {
+                let register = find_register(core, name)?;
+                core.write_core_reg(register, value as u64)
+            })
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn("erase_all", move || -> ScriptResult<()> {
+        erase_all(&mut ctx.borrow_mut().session, FlashProgress::empty()).map_err(script_error)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("flash", move |path: &str| -> ScriptResult<()> {
+        ctx.borrow_mut()
+            .flash(path)
+            .map_err(|e| script_error(format!("{e:#}")))
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("verify", move |path: &str| -> ScriptResult<bool> {
+        ctx.borrow_mut()
+            .verify(path)
+            .map_err(|e| script_error(format!("{e:#}")))
+    });
+
+    engine.register_fn("sleep_ms", |milliseconds: INT| -> ScriptResult<()> {
+        std::thread::sleep(Duration::from_millis(convert(milliseconds, "Duration")?));
+        Ok(())
+    });
+}
+
+fn find_register(
+    core: &Core,
+    name: &str,
+) -> Result<&'static probe_rs::CoreRegister, probe_rs::Error> {
+    core.registers()
+        .all_registers()
+        .find(|r| r.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| probe_rs::Error::Other(format!("Unknown register '{name}'")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::convert;
+
+    #[test]
+    fn convert_rejects_out_of_range_values() {
+        assert_eq!(convert::<u64>(0x2000_0000, "Address").unwrap(), 0x2000_0000);
+        assert_eq!(convert::<u8>(0xff, "Value").unwrap(), 0xff);
+
+        assert!(convert::<u64>(-1, "Address").is_err());
+        assert!(convert::<u8>(0x100, "Value").is_err());
+        assert!(convert::<u32>(0x1_0000_0000, "Value").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use std::net::{SocketAddr, TcpListener};
+
+use anyhow::Context;
+use probe_rs::probe::{list::Lister, remote::RemoteServer};
+
+use crate::util::common_options::ProbeOptions;
+
+/// Serve the selected probe over the network
+///
+/// Other probe-rs instances can use the probe by passing `--remote <ADDRESS>`, or by
+/// setting the `PROBE_RS_REMOTE` environment variable. Multiple instances can use the
+/// probe at the same time, e.g. `probe-rs attach` receiving RTT messages while
+/// `probe-rs read` inspects memory.
+///
+/// The connection is neither authenticated nor encrypted, so the server should only be
+/// reachable from trusted networks.
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The address to listen on. Use `0.0.0.0:4242` to accept connections from other machines.
+    #[clap(long, default_value = "127.0.0.1:4242")]
+    address: SocketAddr,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let probe = self.common.load()?.attach_probe(lister)?;
+        let server = RemoteServer::new(probe)?;
+
+        let listener = TcpListener::bind(self.address)
+            .with_context(|| format!("Failed to listen on {}", self.address))?;
+        println!("Serving {} on {}", server.probe_name(), self.address);
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    tracing::warn!("Failed to accept connection: {error}");
+                    continue;
+                }
+            };
+
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "unknown".to_string(), |peer| peer.to_string());
+            println!("Client {peer} connected");
+
+            let server = server.clone();
+            std::thread::spawn(move || match server.handle_connection(stream) {
+                Ok(()) => println!("Client {peer} disconnected"),
+                Err(error) => println!("Client {peer} disconnected: {error}"),
+            });
+        }
+
+        Ok(())
+    }
+}
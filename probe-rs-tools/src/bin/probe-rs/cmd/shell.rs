@@ -0,0 +1,498 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use probe_rs::debug::debug_info::DebugInfo;
+use probe_rs::probe::list::Lister;
+use probe_rs::Session;
+use rustyline::{error::ReadlineError, DefaultEditor, ExternalPrinter};
+use time::UtcOffset;
+
+use crate::cmd::debug::{get_int_argument, CliData, CliError, CliState, DebugCli, DebugState};
+use crate::cmd::run::{attach_to_rtt, poll_rtt};
+use crate::util::common_options::{BinaryDownloadOptions, LoadedProbeOptions, ProbeOptions};
+use crate::util::flash::{build_loader, run_flash_download};
+use crate::util::rtt::{RttActiveTarget, RttConfig};
+use crate::CoreOptions;
+
+/// Interval in which the RTT thread polls the target.
+const RTT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Interactive shell keeping one session open across commands
+///
+/// Unlike the single-shot commands, the probe and target stay attached
+/// between commands, so repeated operations don't pay the attach cost.
+/// The shell offers the commands of `probe-rs debug`, plus commands
+/// which work on the whole session, like flashing and RTT.
+/// Enter `help` at the prompt for a list of commands.
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    #[clap(flatten)]
+    download_options: BinaryDownloadOptions,
+
+    /// Binary used by `flash` and `rtt` when no path is given, and to
+    /// show stack frames and variables
+    #[clap(long, value_parser)]
+    exe: Option<PathBuf>,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister, timestamp_offset: UtcOffset) -> anyhow::Result<()> {
+        let (session, probe_options) = self.common.simple_attach(lister)?;
+
+        let debug_info = self.exe.as_deref().map(DebugInfo::from_file).transpose()?;
+
+        let mut shell_data = ShellData {
+            target: Arc::new(Mutex::new(Target {
+                session,
+                core: self.shared.core,
+                rtt: None,
+            })),
+            probe_options,
+            download_options: self.download_options,
+            exe: self.exe,
+            debug_info,
+            debug_state: DebugState::default(),
+            timestamp_offset,
+        };
+
+        let shell = Shell::new();
+        let mut rl = DefaultEditor::new()?;
+
+        // RTT output is printed from a separate thread while the prompt waits for input. The
+        // external printer keeps the output from garbling the line being edited.
+        let printer: Box<dyn ExternalPrinter + Send> = match rl.create_external_printer() {
+            Ok(printer) => Box::new(printer),
+            Err(_) => Box::new(StdoutPrinter),
+        };
+        let rtt_thread = RttThread::spawn(shell_data.target.clone(), printer);
+
+        loop {
+            let core = shell_data.target().core;
+
+            match rl.readline(&format!("core{core}> ")) {
+                Ok(line) => {
+                    rl.add_history_entry(line.as_str())?;
+                    if shell.handle_line(&line, &mut shell_data) == CliState::Stop {
+                        break;
+                    }
+                }
+                // For end of file and ctrl-c, we just quit
+                Err(ReadlineError::Eof | ReadlineError::Interrupted) => break,
+                Err(actual_error) => {
+                    println!("Error handling input: {actual_error:?}");
+                    break;
+                }
+            }
+        }
+
+        rtt_thread.stop();
+        shell_data.target().stop_rtt()?;
+
+        Ok(())
+    }
+}
+
+/// The session, and the RTT connection which is polled in the background.
+struct Target {
+    session: Session,
+    core: usize,
+    rtt: Option<RttActiveTarget>,
+}
+
+impl Target {
+    /// Polls RTT, if it is enabled, and writes the received data to `output`.
+    fn poll_rtt(&mut self, output: &mut Vec<u8>) {
+        if self.rtt.is_none() {
+            return;
+        }
+
+        let result = self
+            .session
+            .core(self.core)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut core| poll_rtt(&mut self.rtt, &mut core, output));
+
+        if let Err(error) = result {
+            output.extend(format!("Failed to poll RTT, disabling it: {error}\n").bytes());
+            self.rtt = None;
+        }
+    }
+
+    fn stop_rtt(&mut self) -> Result<(), CliError> {
+        if let Some(mut rtt) = self.rtt.take() {
+            rtt.clean_up(&mut self.session.core(self.core)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints to stdout, when the terminal doesn't support an external printer.
+struct StdoutPrinter;
+
+impl ExternalPrinter for StdoutPrinter {
+    fn print(&mut self, msg: String) -> rustyline::Result<()> {
+        print!("{msg}");
+        Ok(())
+    }
+}
+
+/// A thread printing the RTT output while the shell waits for input.
+struct RttThread {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl RttThread {
+    fn spawn(target: Arc<Mutex<Target>>, mut printer: Box<dyn ExternalPrinter + Send>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_requested = stop.clone();
+
+        let handle = thread::spawn(move || {
+            let mut output = Vec::new();
+
+            while !stop_requested.load(Ordering::Relaxed) {
+                target
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .poll_rtt(&mut output);
+
+                if !output.is_empty() {
+                    let text = String::from_utf8_lossy(&output).into_owned();
+                    output.clear();
+                    if printer.print(text).is_err() {
+                        break;
+                    }
+                }
+
+                thread::sleep(RTT_POLL_INTERVAL);
+            }
+        });
+
+        Self { stop, handle }
+    }
+
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if self.handle.join().is_err() {
+            tracing::warn!("The RTT thread panicked");
+        }
+    }
+}
+
+struct ShellData {
+    target: Arc<Mutex<Target>>,
+    probe_options: LoadedProbeOptions,
+    download_options: BinaryDownloadOptions,
+    exe: Option<PathBuf>,
+    debug_info: Option<DebugInfo>,
+    /// The state of the debugger commands, e.g. the selected stack frame.
+    debug_state: DebugState,
+    timestamp_offset: UtcOffset,
+}
+
+impl ShellData {
+    fn target(&self) -> MutexGuard<'_, Target> {
+        // The RTT thread doesn't leave the target in an inconsistent state when it panics.
+        self.target
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns the path given as argument, or the default binary.
+    fn binary(&self, args: &[&str]) -> Result<PathBuf, CliError> {
+        args.first()
+            .map(PathBuf::from)
+            .or_else(|| self.exe.clone())
+            .ok_or(CliError::MissingArgument)
+    }
+
+    /// Runs a command of the debugger, on the selected core.
+    fn debug_command(&mut self, cli: &DebugCli, line: &str) -> Result<CliState, CliError> {
+        let target = self.target.clone();
+        let mut target = target
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Target { session, core, .. } = &mut *target;
+
+        let mut cli_data = CliData::with_state(
+            session.core(*core)?,
+            self.debug_info.take(),
+            std::mem::take(&mut self.debug_state),
+        )?;
+        let result = cli.handle_line(line, &mut cli_data);
+        (self.debug_info, self.debug_state) = cli_data.into_parts();
+
+        result
+    }
+}
+
+/// A command which works on the whole session, in addition to the ones of the debugger.
+struct ShellCommand {
+    name: &'static str,
+    help_text: &'static str,
+    function: fn(&mut ShellData, args: &[&str]) -> Result<CliState, CliError>,
+}
+
+struct Shell {
+    commands: Vec<ShellCommand>,
+    debug_cli: DebugCli,
+}
+
+impl Shell {
+    fn new() -> Self {
+        let mut shell = Shell {
+            commands: Vec::new(),
+            debug_cli: DebugCli::new(),
+        };
+
+        shell.add_command(ShellCommand {
+            name: "core",
+            help_text: "Select the core used by all other commands: core <index>",
+            function: |data, args| {
+                let index: usize = get_int_argument(args, 0)?;
+                let mut target = data.target();
+                let cores = target.session.list_cores().len();
+                if index >= cores {
+                    println!("Core {index} does not exist, the target has {cores} core(s).");
+                    return Ok(CliState::Continue);
+                }
+                // RTT is attached through a specific core.
+                target.stop_rtt()?;
+                target.core = index;
+                drop(target);
+
+                data.debug_state = DebugState::default();
+                Ok(CliState::Continue)
+            },
+        });
+
+        shell.add_command(ShellCommand {
+            name: "flash",
+            help_text: "Flash a binary and reset the core: flash [path]",
+            function: |data, args| {
+                let path = data.binary(args)?;
+                // Images without debug info, e.g. hex files, can still be flashed.
+                let debug_info = DebugInfo::from_file(&path).ok();
+
+                let mut target = data.target();
+                // The flash loader may reset the target, which invalidates the RTT control block.
+                target.stop_rtt()?;
+
+                let session = &mut target.session;
+                let loader = build_loader(session, &path, Default::default(), None)?;
+                run_flash_download(
+                    session,
+                    &path,
+                    &data.download_options,
+                    &data.probe_options,
+                    loader,
+                    false,
+                )
+                .map_err(anyhow::Error::from)?;
+
+                let core = target.core;
+                target
+                    .session
+                    .core(core)?
+                    .reset_and_halt(Duration::from_millis(100))?;
+                drop(target);
+
+                data.exe = Some(path);
+                data.debug_info = debug_info;
+                data.debug_state = DebugState::default();
+                Ok(CliState::Continue)
+            },
+        });
+
+        shell.add_command(ShellCommand {
+            name: "rtt",
+            help_text: "Enable or disable RTT output: rtt on [path] | rtt off",
+            function: |data, args| {
+                match args.first() {
+                    Some(&"on") => {
+                        let path = data.binary(&args[1..])?;
+                        let timestamp_offset = data.timestamp_offset;
+
+                        let mut target = data.target();
+                        target.stop_rtt()?;
+
+                        let scan_regions = target.session.target().rtt_scan_regions.clone();
+                        let core = target.core;
+                        let rtt = attach_to_rtt(
+                            &mut target.session.core(core)?,
+                            Duration::from_secs(1),
+                            &scan_regions,
+                            &path,
+                            &RttConfig::default(),
+                            timestamp_offset,
+                        )?;
+
+                        if rtt.is_some() {
+                            println!("RTT enabled");
+                        } else {
+                            println!("No RTT control block found");
+                        }
+                        target.rtt = rtt;
+                    }
+                    Some(&"off") => data.target().stop_rtt()?,
+                    _ => return Err(CliError::MissingArgument),
+                }
+                Ok(CliState::Continue)
+            },
+        });
+
+        shell
+    }
+
+    fn add_command(&mut self, command: ShellCommand) {
+        self.commands.push(command)
+    }
+
+    /// Runs the command in `line`. Errors are reported, but don't end the session.
+    fn handle_line(&self, line: &str, data: &mut ShellData) -> CliState {
+        let mut command_parts = line.split_whitespace();
+
+        let Some(command) = command_parts.next() else {
+            return CliState::Continue;
+        };
+
+        if command == "help" {
+            println!("The following commands are available:");
+            for cmd in &self.commands {
+                println!(" - {:12} {}", cmd.name, cmd.help_text);
+            }
+            self.debug_cli.print_commands();
+            return CliState::Continue;
+        }
+
+        let result = match self.commands.iter().find(|c| c.name == command) {
+            Some(cmd) => {
+                let args: Vec<&str> = command_parts.collect();
+
+                match (cmd.function)(data, &args) {
+                    Err(CliError::MissingArgument) => {
+                        println!("Error: Missing argument\n\n{}", cmd.help_text);
+                        Ok(CliState::Continue)
+                    }
+                    Err(CliError::ArgumentParseError {
+                        argument, source, ..
+                    }) => {
+                        println!(
+                            "Error parsing argument '{}': {}\n\n{}",
+                            argument, source, cmd.help_text
+                        );
+                        Ok(CliState::Continue)
+                    }
+                    other => other,
+                }
+            }
+            // The debugger reports unknown commands and argument errors itself.
+            None => data.debug_command(&self.debug_cli, line),
+        };
+
+        match result {
+            Ok(state) => state,
+            Err(error) => {
+                println!("Error: {:?}", anyhow::Error::from(error));
+                CliState::Continue
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use clap::Parser;
+    use probe_rs::{integration::FakeProbe, MemoryInterface};
+    use time::UtcOffset;
+
+    use super::{Shell, ShellData, Target};
+    use crate::cmd::debug::{CliState, DebugState};
+    use crate::util::common_options::{BinaryDownloadOptions, LoadedProbeOptions, ProbeOptions};
+
+    fn shell_data() -> ShellData {
+        let session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+
+        ShellData {
+            target: Arc::new(Mutex::new(Target {
+                session,
+                core: 0,
+                rtt: None,
+            })),
+            probe_options: LoadedProbeOptions::new(ProbeOptions::parse_from(["shell"])).unwrap(),
+            download_options: BinaryDownloadOptions::parse_from(["shell"]),
+            exe: None,
+            debug_info: None,
+            debug_state: DebugState::default(),
+            timestamp_offset: UtcOffset::UTC,
+        }
+    }
+
+    #[test]
+    fn command_names_are_unique() {
+        let shell = Shell::new();
+        let mut names: Vec<_> = shell.commands.iter().map(|c| c.name).collect();
+        names.push("help");
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), shell.commands.len() + 1);
+    }
+
+    #[test]
+    fn memory_commands_use_the_session() {
+        let shell = Shell::new();
+        let mut data = shell_data();
+
+        let state = shell.handle_line("write32 0x20000000 0xdeadbeef", &mut data);
+        assert!(state == CliState::Continue);
+
+        let value = data
+            .target()
+            .session
+            .core(0)
+            .unwrap()
+            .read_word_32(0x2000_0000)
+            .unwrap();
+        assert_eq!(value, 0xdead_beef);
+    }
+
+    #[test]
+    fn register_commands_keep_the_core_halted() {
+        let shell = Shell::new();
+        let mut data = shell_data();
+
+        shell.handle_line("halt", &mut data);
+        shell.handle_line("reg pc 0x1234", &mut data);
+
+        let mut target = data.target();
+        let mut core = target.session.core(0).unwrap();
+        assert!(core.core_halted().unwrap());
+        let pc = core.program_counter();
+        let value: u32 = core.read_core_reg(pc).unwrap();
+        assert_eq!(value, 0x1234);
+    }
+
+    #[test]
+    fn errors_do_not_end_the_session() {
+        let shell = Shell::new();
+        let mut data = shell_data();
+
+        for line in ["", "nonsense", "write32", "read32 foo", "core 1", "rtt on"] {
+            assert!(shell.handle_line(line, &mut data) == CliState::Continue);
+        }
+        assert_eq!(data.target().core, 0);
+
+        assert!(shell.handle_line("quit", &mut data) == CliState::Stop);
+    }
+}
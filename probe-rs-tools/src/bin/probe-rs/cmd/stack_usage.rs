@@ -0,0 +1,201 @@
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use probe_rs::{probe::list::Lister, Core, MemoryInterface};
+
+use crate::util::{common_options::ProbeOptions, parse_u32, patch::find_symbol, resolve_location};
+use crate::CoreOptions;
+
+/// The symbols of the bottom of the main stack, in order of preference.
+///
+/// `cortex-m-rt` defines `_stack_end` since 0.7.4. Before that the stack ends where the
+/// statically allocated RAM ends.
+const STACK_END_SYMBOLS: &[&str] = &["_stack_end", "__euninit", "__ebss"];
+
+/// Measure the peak stack usage of the firmware
+///
+/// e.g. probe-rs stack-usage --chip nRF52840_xxAA --elf firmware.elf --duration 10
+///      Resets the target, fills the main stack with a pattern, runs the firmware for 10 seconds
+///      and reports how much of the stack was overwritten.
+///
+/// The main stack is found with the `_stack_start` and `_stack_end` symbols of `cortex-m-rt`.
+/// Other stacks, e.g. those of RTOS tasks, can be given with `--stack <start>..<end>`.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// The ELF file the stack symbols are looked up in.
+    #[clap(long)]
+    elf: Option<PathBuf>,
+
+    /// A stack to measure, as `<start>..<end>` with addresses or symbols of the ELF file.
+    ///
+    /// Replaces the main stack found in the ELF file.
+    #[clap(long = "stack")]
+    stacks: Vec<String>,
+
+    /// How long the firmware runs, in seconds.
+    #[clap(long, default_value_t = 10)]
+    duration: u64,
+
+    /// Stop the measurement when the core reaches this address or symbol, instead of after the
+    /// duration.
+    #[clap(long)]
+    until: Option<String>,
+
+    /// Fill the stacks of the running firmware instead of resetting it.
+    ///
+    /// Only the unused part of the stack the core currently runs on is filled, other stacks
+    /// are only scanned.
+    #[clap(long)]
+    no_reset: bool,
+
+    /// The pattern the stacks are filled with.
+    #[clap(long, value_parser = parse_u32, default_value = "0xCCCCCCCC")]
+    pattern: u32,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let elf = self.elf.as_ref().map(std::fs::read).transpose()?;
+        let stacks = self.stacks(elf.as_deref())?;
+        let until = self
+            .until
+            .as_deref()
+            .map(|until| resolve_location(until, elf.as_deref()))
+            .transpose()?;
+
+        let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
+        let mut core = session.core(self.shared.core)?;
+        let timeout = Duration::from_millis(500);
+
+        let painted = if self.no_reset {
+            core.halt(timeout)?;
+            let sp: u64 = core.read_core_reg(core.stack_pointer())?;
+            let Some(stack) = stacks.iter().find(|stack| stack.contains(&sp)) else {
+                bail!("The stack pointer {sp:#010x} is not in any of the stacks.");
+            };
+
+            // Everything above the stack pointer is in use.
+            let unused = stack.start..sp & !3;
+            fill(&mut core, &unused, self.pattern)?;
+            vec![unused]
+        } else {
+            core.reset_and_halt(timeout)?;
+            for stack in &stacks {
+                fill(&mut core, stack, self.pattern)?;
+            }
+            stacks.clone()
+        };
+
+        if let Some(until) = until {
+            core.set_hw_breakpoint(until)?;
+            core.run()?;
+            let halted = core.wait_for_core_halted(Duration::from_secs(self.duration));
+            core.clear_hw_breakpoint(until)?;
+            halted.context("The firmware didn't reach the end of the measurement.")?;
+        } else {
+            core.run()?;
+            std::thread::sleep(Duration::from_secs(self.duration));
+            core.halt(timeout)?;
+        }
+
+        for stack in &stacks {
+            let size = stack.end - stack.start;
+            if !painted
+                .iter()
+                .any(|painted| stack.start <= painted.start && painted.end <= stack.end)
+            {
+                println!("{:#010x}..{:#010x}: not filled", stack.start, stack.end);
+                continue;
+            }
+
+            let mut data = vec![0; (size / 4) as usize];
+            core.read_32(stack.start, &mut data)?;
+            let used = size - 4 * untouched_words(&data, self.pattern) as u64;
+
+            println!(
+                "{:#010x}..{:#010x}: {used} of {size} bytes used ({}%)",
+                stack.start,
+                stack.end,
+                used * 100 / size.max(1),
+            );
+        }
+
+        core.run()?;
+
+        Ok(())
+    }
+
+    /// Returns the stacks to measure, as word-aligned address ranges.
+    fn stacks(&self, elf: Option<&[u8]>) -> anyhow::Result<Vec<Range<u64>>> {
+        let stacks = if self.stacks.is_empty() {
+            let Some(elf) = elf else {
+                bail!("Either an ELF file or a stack has to be given.");
+            };
+            let (start, _) = find_symbol(elf, "_stack_start")?;
+            let end = STACK_END_SYMBOLS
+                .iter()
+                .find_map(|symbol| find_symbol(elf, symbol).ok())
+                .map(|(address, _)| address)
+                .context("The ELF file has no symbol for the end of the stack.")?;
+
+            // The stack grows down from `_stack_start`.
+            vec![Range {
+                start: end,
+                end: start,
+            }]
+        } else {
+            self.stacks
+                .iter()
+                .map(|stack| {
+                    let Some((start, end)) = stack.split_once("..") else {
+                        bail!("'{stack}' is not a range like <start>..<end>.");
+                    };
+                    Ok(resolve_location(start, elf)?..resolve_location(end, elf)?)
+                })
+                .collect::<anyhow::Result<_>>()?
+        };
+
+        Ok(stacks
+            .into_iter()
+            .map(|stack| (stack.start + 3) & !3..stack.end & !3)
+            .collect())
+    }
+}
+
+/// Fills the `stack` with the `pattern`.
+fn fill(core: &mut Core, stack: &Range<u64>, pattern: u32) -> anyhow::Result<()> {
+    let words = ((stack.end - stack.start) / 4) as usize;
+    core.write_32(stack.start, &vec![pattern; words])?;
+
+    Ok(())
+}
+
+/// Returns how many words at the bottom of a stack still contain the `pattern`.
+///
+/// Stacks grow down, so the bottom is the start of the `data`.
+fn untouched_words(data: &[u32], pattern: u32) -> usize {
+    data.iter().take_while(|&&word| word == pattern).count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn untouched_words_are_counted_from_the_bottom() {
+        let pattern = 0xCCCC_CCCC;
+
+        assert_eq!(untouched_words(&[pattern; 4], pattern), 4);
+        assert_eq!(untouched_words(&[pattern, pattern, 0, pattern], pattern), 2);
+        assert_eq!(untouched_words(&[0, pattern], pattern), 0);
+    }
+}
@@ -0,0 +1,131 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use probe_rs::probe::{
+    list::Lister,
+    stlink::{StLinkFactory, StLinkSwim, Stm8Family},
+    DebugProbeSelector,
+};
+
+use crate::util::parse_u32;
+
+/// Flash and inspect STM8 targets using the SWIM interface of an ST-LINK
+///
+/// Connecting resets the target and halts it. The target is resumed when the command exits.
+#[derive(clap::Parser)]
+pub struct Cmd {
+    /// The probe to use, given as 'VID:PID' or 'VID:PID:Serial'.
+    #[clap(long, env = "PROBE_RS_PROBE")]
+    probe: Option<DebugProbeSelector>,
+
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Print the CPU registers
+    Registers,
+    /// Read memory
+    Read {
+        /// The address to read from.
+        #[clap(value_parser = parse_u32)]
+        address: u32,
+        /// The number of bytes to read.
+        length: usize,
+    },
+    /// Program a binary file into the flash
+    Download {
+        /// The binary file to program.
+        path: PathBuf,
+        /// The address to program the file to.
+        #[clap(long, default_value = "0x8000", value_parser = parse_u32)]
+        address: u32,
+        /// The product line of the target.
+        #[clap(long, value_enum, default_value = "stm8s")]
+        family: Family,
+        /// The size of a flash block in bytes, see the reference manual of the device.
+        #[clap(long, default_value = "64")]
+        block_size: usize,
+    },
+    /// Reset the target
+    Reset {
+        /// Keep the target halted after the reset.
+        #[clap(long)]
+        halt: bool,
+    },
+}
+
+#[derive(Copy, Clone, clap::ValueEnum)]
+enum Family {
+    /// STM8S and STM8AF devices
+    Stm8s,
+    /// STM8L and STM8AL devices
+    Stm8l,
+}
+
+impl From<Family> for Stm8Family {
+    fn from(family: Family) -> Self {
+        match family {
+            Family::Stm8s => Stm8Family::Stm8s,
+            Family::Stm8l => Stm8Family::Stm8l,
+        }
+    }
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let selector = match self.probe {
+            Some(selector) => selector,
+            None => {
+                let probes: Vec<_> = lister
+                    .list_all()
+                    .into_iter()
+                    .filter(|probe| probe.is_probe_type::<StLinkFactory>())
+                    .collect();
+                let probe = probes.first().context("No ST-LINK probe was found")?;
+                if probes.len() > 1 {
+                    println!("Multiple probes found, using {probe}");
+                }
+                DebugProbeSelector::from(probe)
+            }
+        };
+
+        let mut swim = StLinkSwim::open(&selector)?;
+
+        match self.subcommand {
+            Subcommand::Registers => {
+                let registers = swim.read_registers()?;
+                println!("A:  {:#04x}", registers.a);
+                println!("PC: {:#08x}", registers.pc);
+                println!("X:  {:#06x}", registers.x);
+                println!("Y:  {:#06x}", registers.y);
+                println!("SP: {:#06x}", registers.sp);
+                println!("CC: {:#04x}", registers.cc);
+            }
+            Subcommand::Read { address, length } => {
+                let mut data = vec![0; length];
+                swim.read_memory(address, &mut data)?;
+
+                for (index, line) in data.chunks(16).enumerate() {
+                    let line: Vec<_> = line.iter().map(|byte| format!("{byte:02x}")).collect();
+                    println!("{:06x}: {}", address as usize + index * 16, line.join(" "));
+                }
+            }
+            Subcommand::Download {
+                path,
+                address,
+                family,
+                block_size,
+            } => {
+                let data = std::fs::read(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                swim.write_flash(family.into(), block_size, address, &data)?;
+                println!("Programmed {} bytes at {address:#06x}", data.len());
+            }
+            Subcommand::Reset { halt } => swim.reset(halt)?,
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use anyhow::Context;
+use probe_rs::debug::{ColumnType, DebugInfo, SourceFrame};
+
+use crate::util::parse_u64;
+
+/// Map addresses to functions and source locations
+///
+/// e.g. probe-rs symbolize --elf firmware.elf 0x08001234 0x0800567a
+///      Prints the function and source location of the two addresses.
+///
+/// e.g. probe-rs symbolize --elf firmware.elf < crash.log
+///      Prints the crash log, with the function and source location after each line which
+///      contains addresses in the firmware, e.g. the PC and LR of a hard fault.
+///
+/// Functions inlined at an address are listed from the innermost one out to the function they
+/// are inlined into. No target is needed.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    /// The ELF file with the debug info of the firmware.
+    #[clap(long)]
+    elf: PathBuf,
+
+    /// The addresses to look up. Without addresses, they are read from the hexadecimal numbers
+    /// in the standard input.
+    #[clap(value_parser = parse_u64)]
+    addresses: Vec<u64>,
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        let debug_info = DebugInfo::from_file(&self.elf)
+            .with_context(|| format!("Failed to read the debug info of {}", self.elf.display()))?;
+        let mut output = std::io::stdout().lock();
+
+        if !self.addresses.is_empty() {
+            for address in self.addresses {
+                let frames = debug_info.get_source_frames(address);
+                if frames.is_empty() {
+                    writeln!(output, "{address:#010x}: ??")?;
+                } else {
+                    write_frames(&mut output, address, &frames)?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        for line in std::io::stdin().lock().lines() {
+            let line = line?;
+            writeln!(output, "{line}")?;
+
+            for address in addresses_in(&line) {
+                let frames = debug_info.get_source_frames(address);
+                if !frames.is_empty() {
+                    write!(output, "    ")?;
+                    write_frames(&mut output, address, &frames)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the functions at the address, one per line.
+fn write_frames(
+    output: &mut impl Write,
+    address: u64,
+    frames: &[SourceFrame],
+) -> std::io::Result<()> {
+    for (index, frame) in frames.iter().enumerate() {
+        if index == 0 {
+            write!(output, "{address:#010x}: ")?;
+        } else {
+            write!(output, "    (inlined by) ")?;
+        }
+
+        let function = frame.function_name.as_deref().unwrap_or("??");
+        writeln!(output, "{function} at {}", location(frame))?;
+    }
+
+    Ok(())
+}
+
+/// Formats the source location of the frame as `<path>:<line>:<column>`.
+fn location(frame: &SourceFrame) -> String {
+    let Some(location) = &frame.source_location else {
+        return "??".to_string();
+    };
+
+    let mut text = match (location.combined_typed_path(), &location.file) {
+        (Some(path), _) => path.to_path().display().to_string(),
+        (None, Some(file)) => file.clone(),
+        (None, None) => "??".to_string(),
+    };
+    if let Some(line) = location.line {
+        text.push_str(&format!(":{line}"));
+
+        if let Some(ColumnType::Column(column)) = location.column {
+            text.push_str(&format!(":{column}"));
+        }
+    }
+
+    text
+}
+
+/// Returns the hexadecimal numbers in the line, e.g. the registers of a fault dump.
+///
+/// Numbers without `0x` prefix need 8 or 16 digits, so words like `add` aren't taken for
+/// addresses.
+fn addresses_in(line: &str) -> Vec<u64> {
+    line.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter_map(|word| {
+            let digits = match word.strip_prefix("0x") {
+                Some(digits) => digits.replace('_', ""),
+                None => Some(word.replace('_', ""))
+                    .filter(|digits| digits.len() == 8 || digits.len() == 16)?,
+            };
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            u64::from_str_radix(&digits, 16).ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn addresses_are_found_in_lines() {
+        assert_eq!(
+            addresses_in("PC: 0x08001234, LR = 0800_567B"),
+            vec![0x0800_1234, 0x0800_567B]
+        );
+        assert_eq!(addresses_in("HardFault at line 42: 0xcafe"), vec![0xcafe]);
+        assert_eq!(addresses_in("added deadbeef"), vec![0xdeadbeef]);
+    }
+}
@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use probe_rs::{probe::list::Lister, Core};
+
+use crate::util::{common_options::ProbeOptions, cycle_counter::CycleCounter, resolve_location};
+use crate::CoreOptions;
+
+/// Measure the cycles and time the target takes from one point in the code to another
+///
+/// e.g. probe-rs time --chip nRF52840_xxAA --elf firmware.elf handle_irq handle_irq_done
+///      Measures the code between the two functions 10 times.
+///
+/// A hardware breakpoint is set at both points, and the cycle counter of the core
+/// (the DWT on Cortex-M, `mcycle` on RISC-V) is read whenever the core halts there.
+/// The cycle counter is 32 bits wide, so only intervals shorter than 2^32 cycles are measured
+/// correctly. The wall time includes the latency of the probe, so it is only a rough estimate.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// The start of the measured code, as an address or as a symbol of the ELF file.
+    start: String,
+
+    /// The end of the measured code, as an address or as a symbol of the ELF file.
+    end: String,
+
+    /// The ELF file the symbols are looked up in.
+    #[clap(long)]
+    elf: Option<PathBuf>,
+
+    /// How often the code is measured.
+    #[clap(long, default_value_t = 10)]
+    iterations: usize,
+
+    /// How long to wait for the target to reach the start or the end, in milliseconds.
+    #[clap(long, default_value_t = 10_000)]
+    timeout: u64,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let elf = self.elf.as_ref().map(std::fs::read).transpose()?;
+        let start = resolve_location(&self.start, elf.as_deref())?;
+        let end = resolve_location(&self.end, elf.as_deref())?;
+        if start == end {
+            bail!("The start and the end of the measured code have to differ.");
+        }
+
+        let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
+        let mut core = session.core(self.shared.core)?;
+        let timeout = Duration::from_millis(self.timeout);
+
+        core.halt(timeout)?;
+        let counter = CycleCounter::enable(&mut core)?;
+
+        core.set_hw_breakpoint(start)?;
+        if let Err(error) = core.set_hw_breakpoint(end) {
+            core.clear_hw_breakpoint(start)?;
+            return Err(error.into());
+        }
+
+        let samples = measure(&mut core, counter, start, end, self.iterations, timeout);
+
+        core.clear_hw_breakpoint(start)?;
+        core.clear_hw_breakpoint(end)?;
+        core.run()?;
+
+        print_statistics(std::io::stdout(), &samples?)
+    }
+}
+
+/// One pass from the start to the end.
+struct Sample {
+    cycles: u32,
+    duration: Duration,
+}
+
+/// Runs the halted core from `start` to `end` `iterations` times.
+fn measure(
+    core: &mut Core,
+    counter: CycleCounter,
+    start: u64,
+    end: u64,
+    iterations: usize,
+    timeout: Duration,
+) -> anyhow::Result<Vec<Sample>> {
+    let mut samples = Vec::with_capacity(iterations);
+
+    while samples.len() < iterations {
+        run_to(core, start, timeout).context("The start was not reached.")?;
+        let start_cycles = counter.read(core)?;
+        let start_time = Instant::now();
+
+        run_to(core, end, timeout).context("The end was not reached.")?;
+        let end_time = Instant::now();
+        let end_cycles = counter.read(core)?;
+
+        samples.push(Sample {
+            cycles: end_cycles.wrapping_sub(start_cycles),
+            duration: end_time - start_time,
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Runs the core until it halts at `address`.
+///
+/// Halts at other breakpoints, e.g. at the end while waiting for the start, are skipped.
+fn run_to(core: &mut Core, address: u64, timeout: Duration) -> anyhow::Result<()> {
+    loop {
+        core.run()?;
+        core.wait_for_core_halted(timeout)?;
+
+        let pc: u64 = core.read_core_reg(core.program_counter())?;
+        if pc == address {
+            return Ok(());
+        }
+    }
+}
+
+fn print_statistics(mut output: impl std::io::Write, samples: &[Sample]) -> anyhow::Result<()> {
+    if samples.is_empty() {
+        return Ok(());
+    }
+
+    let count = samples.len() as u64;
+    let cycles = samples.iter().map(|sample| u64::from(sample.cycles));
+    let durations = samples.iter().map(|sample| sample.duration);
+
+    writeln!(output, "Measured {count} iterations:")?;
+    writeln!(
+        output,
+        "  cycles: min {}, avg {}, max {}",
+        cycles.clone().min().unwrap_or_default(),
+        cycles.clone().sum::<u64>() / count,
+        cycles.max().unwrap_or_default(),
+    )?;
+    writeln!(
+        output,
+        "  time:   min {:?}, avg {:?}, max {:?}",
+        durations.clone().min().unwrap_or_default(),
+        durations.clone().sum::<Duration>() / count as u32,
+        durations.max().unwrap_or_default(),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn statistics_output() {
+        let samples = [(100, 2), (300, 4), (200, 3)].map(|(cycles, millis)| Sample {
+            cycles,
+            duration: Duration::from_millis(millis),
+        });
+
+        let mut buff = Vec::new();
+        print_statistics(&mut buff, &samples).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buff).unwrap(),
+            "Measured 3 iterations:\n  cycles: min 100, avg 200, max 300\n  time:   min 2ms, avg 3ms, max 4ms\n"
+        );
+    }
+}
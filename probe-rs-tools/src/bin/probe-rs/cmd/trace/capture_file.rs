@@ -0,0 +1,344 @@
+//! The file format written by `probe-rs trace capture`.
+//!
+//! A capture starts with a magic number, and a JSON header which describes the trace source.
+//! The raw trace data follows in records, as it was received from the probe. Each record starts
+//! with the time since the start of the capture in microseconds (`u64`), and the length of the
+//! data (`u32`), both little endian.
+//!
+//! To decode only a part of a long capture, the file offsets of the records are written to an
+//! index next to the capture, at most every [`INDEX_INTERVAL`]. An entry consists of the time
+//! of the record in microseconds, and its offset in the capture file (both `u64`, little endian).
+
+use std::ffi::OsString;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+/// The magic number at the start of a capture.
+const MAGIC: &[u8; 8] = b"PRTRACE\0";
+
+/// The version of the file format.
+const VERSION: u32 = 1;
+
+/// The capture time between two index entries.
+pub const INDEX_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Describes the captured trace data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureHeader {
+    /// The name of the chip the data was captured from.
+    pub chip: String,
+    /// Where the data was captured from.
+    pub source: TraceSource,
+}
+
+/// The source of the captured trace data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TraceSource {
+    /// The SWO output of the TPIU, without formatter.
+    Swo {
+        /// The frequency of the clock feeding the TPIU in Hz.
+        clock: u32,
+        /// The baud rate the data was received with.
+        baud: u32,
+    },
+}
+
+/// A chunk of trace data, as it was received from the probe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// The time since the start of the capture.
+    pub timestamp: Duration,
+    pub data: Vec<u8>,
+}
+
+/// An entry of the index of a capture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexEntry {
+    /// The time of the record at `offset`.
+    pub timestamp: Duration,
+    /// The offset of the record in the capture file.
+    pub offset: u64,
+}
+
+/// Returns the path of the index of the capture at `path`, which is the path with `.idx`
+/// appended.
+pub fn index_path(path: &Path) -> PathBuf {
+    let mut index_path = OsString::from(path);
+    index_path.push(".idx");
+    PathBuf::from(index_path)
+}
+
+/// Writes the trace data to a capture.
+pub struct CaptureWriter<W: Write> {
+    out: W,
+    /// The offset of the next record.
+    offset: u64,
+    index: Vec<IndexEntry>,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Starts a capture by writing the header to `out`.
+    pub fn new(mut out: W, header: &CaptureHeader) -> anyhow::Result<Self> {
+        let header = serde_json::to_vec(header)?;
+        let header_len = u32::try_from(header.len())?;
+
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&header_len.to_le_bytes())?;
+        out.write_all(&header)?;
+
+        Ok(Self {
+            out,
+            offset: (MAGIC.len() + 8 + header.len()) as u64,
+            index: vec![],
+        })
+    }
+
+    /// Appends the data received `timestamp` after the start of the capture.
+    pub fn write_record(&mut self, timestamp: Duration, data: &[u8]) -> io::Result<()> {
+        let indexed = self
+            .index
+            .last()
+            .is_some_and(|last| timestamp < last.timestamp + INDEX_INTERVAL);
+        if !indexed {
+            self.index.push(IndexEntry {
+                timestamp,
+                offset: self.offset,
+            });
+        }
+
+        let len = u32::try_from(data.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "The record is too long"))?;
+        self.out
+            .write_all(&(timestamp.as_micros() as u64).to_le_bytes())?;
+        self.out.write_all(&len.to_le_bytes())?;
+        self.out.write_all(data)?;
+        self.offset += 12 + u64::from(len);
+
+        Ok(())
+    }
+
+    /// Flushes the capture, and returns its index.
+    pub fn finish(mut self) -> io::Result<Vec<IndexEntry>> {
+        self.out.flush()?;
+        Ok(self.index)
+    }
+}
+
+/// Writes the index of a capture.
+pub fn write_index(mut out: impl Write, index: &[IndexEntry]) -> io::Result<()> {
+    for entry in index {
+        out.write_all(&(entry.timestamp.as_micros() as u64).to_le_bytes())?;
+        out.write_all(&entry.offset.to_le_bytes())?;
+    }
+    out.flush()
+}
+
+/// Reads the index of a capture.
+pub fn read_index(mut input: impl Read) -> io::Result<Vec<IndexEntry>> {
+    let mut index = vec![];
+    let mut entry = [0; 16];
+    loop {
+        match input.read_exact(&mut entry) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(index),
+            Err(e) => return Err(e),
+        }
+
+        let (timestamp, offset) = entry.split_at(8);
+        index.push(IndexEntry {
+            timestamp: Duration::from_micros(u64::from_le_bytes(timestamp.try_into().unwrap())),
+            offset: u64::from_le_bytes(offset.try_into().unwrap()),
+        });
+    }
+}
+
+/// Reads the trace data of a capture.
+pub struct CaptureReader<R: Read + Seek> {
+    input: R,
+    header: CaptureHeader,
+}
+
+impl<R: Read + Seek> CaptureReader<R> {
+    /// Reads the header of the capture in `input`.
+    pub fn new(mut input: R) -> anyhow::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("The file is not a probe-rs trace capture.");
+        }
+
+        let version = read_u32(&mut input)?;
+        if version != VERSION {
+            bail!("The trace capture has the unsupported version {version}.");
+        }
+
+        let header_len = read_u32(&mut input)?;
+        let mut header = vec![0; header_len as usize];
+        input.read_exact(&mut header)?;
+        let header = serde_json::from_slice(&header).context("The capture header is invalid")?;
+
+        Ok(Self { input, header })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Continues reading at the last record of the index which was received before `timestamp`.
+    ///
+    /// Returns whether the reader skipped any records. Then the trace data is continued in the
+    /// middle of the stream.
+    pub fn seek(&mut self, index: &[IndexEntry], timestamp: Duration) -> anyhow::Result<bool> {
+        let Some(entry) = index
+            .iter()
+            .take_while(|entry| entry.timestamp <= timestamp)
+            .last()
+        else {
+            return Ok(false);
+        };
+
+        let first_record = self.input.stream_position()?;
+        if entry.offset > first_record {
+            self.input.seek(SeekFrom::Start(entry.offset))?;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    /// Reads the next record, or returns `None` at the end of the capture.
+    pub fn next_record(&mut self) -> anyhow::Result<Option<Record>> {
+        let mut timestamp = [0; 8];
+        match self.input.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let len = read_u32(&mut self.input)?;
+        let mut data = vec![0; len as usize];
+        if let Err(e) = self.input.read_exact(&mut data) {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                // The capture was interrupted while the record was written.
+                tracing::warn!("The last record of the trace capture is truncated.");
+                return Ok(None);
+            }
+            return Err(e.into());
+        }
+
+        Ok(Some(Record {
+            timestamp: Duration::from_micros(u64::from_le_bytes(timestamp)),
+            data,
+        }))
+    }
+}
+
+fn read_u32(mut input: impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn header() -> CaptureHeader {
+        CaptureHeader {
+            chip: "nRF52840_xxAA".to_string(),
+            source: TraceSource::Swo {
+                clock: 64_000_000,
+                baud: 2_000_000,
+            },
+        }
+    }
+
+    #[test]
+    fn records_are_read_back() {
+        let records = [
+            (Duration::from_micros(10), vec![0x01, b'a']),
+            (Duration::from_millis(1500), vec![0x01, b'b']),
+        ];
+        let capture = writer_data(&header(), &records);
+
+        let mut reader = CaptureReader::new(Cursor::new(capture)).unwrap();
+        assert_eq!(reader.header(), &header());
+        for (timestamp, data) in records {
+            assert_eq!(
+                reader.next_record().unwrap(),
+                Some(Record { timestamp, data })
+            );
+        }
+        assert_eq!(reader.next_record().unwrap(), None);
+    }
+
+    #[test]
+    fn seeking_uses_the_index() {
+        let records = [
+            (Duration::from_millis(0), vec![1]),
+            (Duration::from_millis(1100), vec![2]),
+            (Duration::from_millis(1500), vec![3]),
+            (Duration::from_millis(2500), vec![4]),
+        ];
+        let capture = writer_data(&header(), &records);
+        let mut index = vec![];
+        write_index(&mut index, &writer_index(&header(), &records)).unwrap();
+        let index = read_index(index.as_slice()).unwrap();
+        assert_eq!(index.len(), 3);
+
+        let mut reader = CaptureReader::new(Cursor::new(capture.clone())).unwrap();
+        assert!(!reader.seek(&index, Duration::from_millis(500)).unwrap());
+        assert_eq!(reader.next_record().unwrap().unwrap().data, vec![1]);
+
+        let mut reader = CaptureReader::new(Cursor::new(capture)).unwrap();
+        assert!(reader.seek(&index, Duration::from_millis(2000)).unwrap());
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(record.timestamp, Duration::from_millis(1100));
+        assert_eq!(record.data, vec![2]);
+        assert_eq!(reader.next_record().unwrap().unwrap().data, vec![3]);
+        assert_eq!(reader.next_record().unwrap().unwrap().data, vec![4]);
+        assert_eq!(reader.next_record().unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_records_end_the_capture() {
+        let mut capture = writer_data(&header(), &[(Duration::ZERO, vec![1, 2, 3])]);
+        capture.pop();
+
+        let mut reader = CaptureReader::new(Cursor::new(capture)).unwrap();
+        assert_eq!(reader.next_record().unwrap(), None);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(
+            CaptureReader::new(Cursor::new(b"\x7fELF\x01\x01\x01\0\0\0\0\0".to_vec())).is_err()
+        );
+    }
+
+    fn writer_data(header: &CaptureHeader, records: &[(Duration, Vec<u8>)]) -> Vec<u8> {
+        let mut capture = vec![];
+        let mut writer = CaptureWriter::new(&mut capture, header).unwrap();
+        for (timestamp, data) in records {
+            writer.write_record(*timestamp, data).unwrap();
+        }
+        writer.finish().unwrap();
+        capture
+    }
+
+    fn writer_index(header: &CaptureHeader, records: &[(Duration, Vec<u8>)]) -> Vec<IndexEntry> {
+        let mut writer = CaptureWriter::new(io::sink(), header).unwrap();
+        for (timestamp, data) in records {
+            writer.write_record(*timestamp, data).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+}
@@ -0,0 +1,287 @@
+mod capture_file;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context;
+use probe_rs::architecture::arm::{component::TraceSink, swo::SwoConfig};
+use probe_rs::probe::list::Lister;
+use probe_rs::MemoryInterface;
+use scroll::{Pwrite, LE};
+use signal_hook::consts::signal;
+use time::UtcOffset;
+
+use crate::util::itm::{ItmFormat, ItmLog};
+use crate::util::rtt::RttChannelConfig;
+use crate::util::{common_options::ProbeOptions, cycle_counter, parse_u64};
+use crate::CoreOptions;
+use capture_file::{
+    index_path, read_index, write_index, CaptureHeader, CaptureReader, CaptureWriter, TraceSource,
+};
+
+#[derive(clap::Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    command: Option<TraceCommand>,
+
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The address of the memory to dump from the target.
+    #[clap(value_parser = parse_u64, required = true)]
+    loc: Option<u64>,
+}
+
+#[derive(clap::Subcommand)]
+#[allow(clippy::large_enum_variant)]
+enum TraceCommand {
+    /// Capture the raw SWO data of the target to a file, until Ctrl+C is pressed.
+    ///
+    /// The data is decoded later with `probe-rs trace decode`, so that the capture keeps up
+    /// with high baud rates.
+    Capture(CaptureCmd),
+    /// Decode the log output in a capture of `probe-rs trace capture`.
+    Decode(DecodeCmd),
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let loc = match self.command {
+            Some(TraceCommand::Capture(cmd)) => return cmd.run(lister),
+            Some(TraceCommand::Decode(cmd)) => return cmd.run(),
+            // Clap requires the location without a subcommand.
+            None => self.loc.unwrap(),
+        };
+
+        let mut xs = vec![];
+        let mut ys = vec![];
+
+        let start = Instant::now();
+
+        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+
+        let mut core = session.core(self.shared.core)?;
+
+        loop {
+            // Prepare read.
+            let elapsed = start.elapsed();
+            let instant = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+
+            // Read data.
+            let value: u32 = core.read_word_32(loc)?;
+
+            xs.push(instant);
+            ys.push(value);
+
+            // Send value to plot.py.
+            let mut buf = [0_u8; 8];
+            // Unwrap is safe!
+            buf.pwrite_with(instant, 0, LE).unwrap();
+            buf.pwrite_with(value, 4, LE).unwrap();
+            std::io::stdout().write_all(&buf)?;
+
+            std::io::stdout().flush()?;
+
+            // Schedule next read.
+            let elapsed = start.elapsed();
+            let instant = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+            let poll_every_ms = 50;
+            let time_to_wait = poll_every_ms - instant % poll_every_ms;
+            sleep(Duration::from_millis(time_to_wait));
+        }
+    }
+}
+
+#[derive(clap::Parser)]
+struct CaptureCmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The file to write the capture to. The index is written next to it, with `.idx` appended.
+    output: PathBuf,
+
+    /// The frequency of the clock feeding the TPIU/SWO module of the target in Hz, usually the
+    /// core clock.
+    ///
+    /// If it is not given, the core clock is measured with the cycle counter of the core.
+    #[clap(long, value_name = "HZ")]
+    swo_clock: Option<u32>,
+
+    /// The baud rate of the SWO output.
+    #[clap(long, value_name = "BAUD", default_value_t = 1_000_000)]
+    swo_baud: u32,
+
+    /// Stop the capture after the given number of seconds.
+    #[clap(long, value_name = "SECONDS")]
+    duration: Option<u64>,
+}
+
+impl CaptureCmd {
+    fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+
+        let clock = match self.swo_clock {
+            Some(clock) => clock,
+            None => cycle_counter::measure_core_clock(&mut session.core(self.shared.core)?)
+                .context("Failed to measure the core clock, please pass --swo-clock")?,
+        };
+        let config = SwoConfig::new(clock).set_baud(self.swo_baud);
+        session
+            .setup_tracing(self.shared.core, TraceSink::Swo(config))
+            .context("Failed to set up SWO")?;
+
+        let header = CaptureHeader {
+            chip: session.target().name.clone(),
+            source: TraceSource::Swo {
+                clock,
+                baud: config.actual_baud(),
+            },
+        };
+        let file = File::create(&self.output)
+            .with_context(|| format!("Failed to create {}", self.output.display()))?;
+        let mut writer = CaptureWriter::new(BufWriter::new(file), &header)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal::SIGINT, stop.clone())?;
+
+        let duration = self.duration.map(Duration::from_secs);
+        eprintln!(
+            "Capturing SWO data to {}, press Ctrl+C to stop.",
+            self.output.display()
+        );
+
+        let start = Instant::now();
+        let mut captured = 0;
+        while !stop.load(Ordering::Relaxed)
+            && !duration.is_some_and(|duration| start.elapsed() >= duration)
+        {
+            let data = session.read_trace_data()?;
+            if !data.is_empty() {
+                writer.write_record(start.elapsed(), &data)?;
+                captured += data.len();
+            }
+        }
+
+        let index = writer.finish()?;
+        let index_path = index_path(&self.output);
+        let index_file = File::create(&index_path)
+            .with_context(|| format!("Failed to create {}", index_path.display()))?;
+        write_index(BufWriter::new(index_file), &index)?;
+
+        session.get_arm_interface()?.disable_swo()?;
+
+        eprintln!(
+            "Captured {captured} bytes in {:.1} s.",
+            start.elapsed().as_secs_f64()
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(clap::Parser)]
+struct DecodeCmd {
+    /// The capture written by `probe-rs trace capture`.
+    capture: PathBuf,
+
+    /// The ELF file of the firmware, with the defmt table to decode the log output.
+    #[clap(long)]
+    elf: Option<PathBuf>,
+
+    /// The format of the log output on ITM stimulus port 0.
+    #[clap(long, value_name = "FORMAT", default_value = "text")]
+    itm: ItmFormat,
+
+    /// Skip the data captured in the given number of seconds after the start of the capture.
+    #[clap(long, value_name = "SECONDS")]
+    from: Option<f64>,
+
+    /// Stop at the data captured the given number of seconds after the start of the capture.
+    #[clap(long, value_name = "SECONDS")]
+    to: Option<f64>,
+
+    /// Show the location of the defmt log messages.
+    #[clap(long)]
+    show_location: bool,
+
+    /// The default format string to use for decoding defmt logs.
+    #[clap(long)]
+    log_format: Option<String>,
+}
+
+impl DecodeCmd {
+    fn run(self) -> anyhow::Result<()> {
+        let file = File::open(&self.capture)
+            .with_context(|| format!("Failed to open {}", self.capture.display()))?;
+        let mut reader = CaptureReader::new(BufReader::new(file))?;
+        let TraceSource::Swo { clock, baud } = reader.header().source;
+        tracing::info!(
+            "Decoding the SWO data of {}, captured with {baud} Bd from a {clock} Hz clock",
+            reader.header().chip
+        );
+
+        let mut itm_log = ItmLog::new(
+            self.itm,
+            self.elf.as_deref(),
+            &RttChannelConfig {
+                // Text lines would be prefixed with the time of decoding instead of the time of
+                // the capture, only defmt has its own timestamps.
+                show_timestamps: self.itm == ItmFormat::Defmt,
+                show_location: self.show_location,
+                log_format: self.log_format.clone(),
+                ..Default::default()
+            },
+            UtcOffset::UTC,
+        )?;
+
+        let from = self.from.map(Duration::from_secs_f64).unwrap_or_default();
+        let to = self.to.map(Duration::from_secs_f64);
+
+        if from > Duration::ZERO {
+            let index_path = index_path(&self.capture);
+            match File::open(&index_path) {
+                Ok(index) => {
+                    let index = read_index(BufReader::new(index))?;
+                    if reader.seek(&index, from)? {
+                        // The data continues in the middle of a packet.
+                        itm_log.skip_to_sync();
+                    }
+                }
+                Err(e) => tracing::warn!(
+                    "Failed to open the index {}, decoding from the start: {e}",
+                    index_path.display()
+                ),
+            }
+        }
+
+        let mut stdout = std::io::stdout();
+        while let Some(record) = reader.next_record()? {
+            if to.is_some_and(|to| record.timestamp > to) {
+                break;
+            }
+            if record.timestamp < from {
+                // The packets may continue in the next record.
+                itm_log.skip_to_sync();
+                continue;
+            }
+
+            itm_log.process(&record.data, &mut stdout)?;
+        }
+
+        Ok(())
+    }
+}
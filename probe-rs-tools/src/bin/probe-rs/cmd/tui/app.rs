@@ -0,0 +1,678 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use crossterm::{
+    event::{KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{enable_raw_mode, EnterAlternateScreen},
+};
+use probe_rs::{
+    debug::{DebugInfo, SourceLocation},
+    rtt::ScanRegion,
+    Core, CoreInterface, CoreStatus, MemoryInterface, RegisterValue,
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph},
+    Frame, Terminal,
+};
+use time::UtcOffset;
+
+use crate::cmd::cargo_embed::rttui::{app::clean_up_terminal, event::Events};
+use crate::cmd::run::attach_to_rtt;
+use crate::util::disassembly::disassembler_for;
+use crate::util::parse_u64;
+use crate::util::rtt::{ChannelDataCallbacks, RttActiveTarget, RttConfig};
+
+/// How often the core status is polled while the core is running.
+const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of bytes shown in the memory pane.
+const MEMORY_WINDOW: usize = 16 * 8;
+
+/// Number of instruction bytes disassembled after the program counter.
+const DISASSEMBLY_WINDOW: usize = 16 * 2;
+
+/// Maximum number of RTT lines kept in the scrollback.
+const MAX_RTT_LINES: usize = 10_000;
+
+/// Everything needed to (re-)attach to the RTT control block of the debugged binary.
+pub struct RttSetup {
+    pub path: PathBuf,
+    pub scan_regions: ScanRegion,
+    pub timestamp_offset: UtcOffset,
+}
+
+/// A command entered on the `:` prompt.
+#[derive(Debug, PartialEq)]
+enum TuiCommand {
+    Halt,
+    Run,
+    Step,
+    Reset,
+    Break(u64),
+    Clear(u64),
+    Memory(u64),
+    Rtt,
+    Help,
+    Quit,
+}
+
+impl TuiCommand {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut parts = line.split_whitespace();
+        let Some(command) = parts.next() else {
+            return Err("Empty command".to_string());
+        };
+
+        let mut address = || {
+            let argument = parts
+                .next()
+                .ok_or_else(|| format!("'{command}' expects an address"))?;
+            parse_u64(argument).map_err(|e| format!("Invalid address '{argument}': {e}"))
+        };
+
+        let command = match command {
+            "halt" => Self::Halt,
+            "run" | "continue" => Self::Run,
+            "step" => Self::Step,
+            "reset" => Self::Reset,
+            "break" => Self::Break(address()?),
+            "clear" => Self::Clear(address()?),
+            "mem" => Self::Memory(address()?),
+            "rtt" => Self::Rtt,
+            "help" => Self::Help,
+            "quit" => Self::Quit,
+            other => return Err(format!("Unknown command '{other}', try ':help'")),
+        };
+
+        Ok(command)
+    }
+}
+
+/// Snapshot of the core state, taken whenever the core halts.
+struct HaltedView {
+    pc: u64,
+    registers: Vec<(&'static str, String)>,
+    disassembly: Vec<(u64, String)>,
+    location: Option<SourceLocation>,
+}
+
+/// Collects RTT output into a line buffer.
+struct RttCollector<'a> {
+    lines: &'a mut Vec<String>,
+    had_data: bool,
+}
+
+impl ChannelDataCallbacks for RttCollector<'_> {
+    fn on_string_data(&mut self, _channel: usize, data: String) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.had_data = true;
+
+        for part in data.split_inclusive('\n') {
+            match self.lines.last_mut() {
+                Some(last) if !last.ends_with('\n') => last.push_str(part),
+                _ => self.lines.push(part.to_string()),
+            }
+        }
+
+        if self.lines.len() > MAX_RTT_LINES {
+            let excess = self.lines.len() - MAX_RTT_LINES;
+            self.lines.drain(..excess);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct App {
+    terminal: Terminal<CrosstermBackend<std::io::Stdout>>,
+    events: Events,
+
+    debug_info: Option<DebugInfo>,
+    source_cache: HashMap<PathBuf, Option<Vec<String>>>,
+
+    rtt_setup: Option<RttSetup>,
+    rtt: Option<RttActiveTarget>,
+    rtt_lines: Vec<String>,
+    rtt_scroll: usize,
+
+    halted: Option<HaltedView>,
+    memory_address: u64,
+    memory: Result<Vec<u8>, String>,
+    breakpoints: Vec<u64>,
+
+    input: Option<String>,
+    message: String,
+}
+
+impl App {
+    pub fn new(debug_info: Option<DebugInfo>, rtt_setup: Option<RttSetup>) -> Result<Self> {
+        let events = Events::new();
+
+        enable_raw_mode().context("Failed to enable 'raw' mode for terminal")?;
+        let mut stdout = std::io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let backend = CrosstermBackend::new(stdout);
+        let mut terminal = Terminal::new(backend)?;
+        let _ = terminal.hide_cursor();
+
+        Ok(Self {
+            terminal,
+            events,
+            debug_info,
+            source_cache: HashMap::new(),
+            rtt_setup,
+            rtt: None,
+            rtt_lines: Vec::new(),
+            rtt_scroll: 0,
+            halted: None,
+            memory_address: 0,
+            memory: Ok(Vec::new()),
+            breakpoints: Vec::new(),
+            input: None,
+            message: "Press ':' to enter a command, 'q' to quit.".to_string(),
+        })
+    }
+
+    /// Tries to attach to the RTT control block, if a binary was given.
+    pub fn attach_rtt(&mut self, core: &mut Core) {
+        let Some(setup) = &self.rtt_setup else {
+            return;
+        };
+
+        if let Some(mut rtt) = self.rtt.take() {
+            if let Err(error) = rtt.clean_up(core) {
+                tracing::warn!("Failed to clean up RTT: {error}");
+            }
+        }
+
+        match attach_to_rtt(
+            core,
+            Duration::from_secs(1),
+            &setup.scan_regions,
+            &setup.path,
+            &RttConfig::default(),
+            setup.timestamp_offset,
+        ) {
+            Ok(Some(rtt)) => {
+                self.rtt = Some(rtt);
+                self.message = "Attached to RTT".to_string();
+            }
+            Ok(None) => self.message = "No RTT control block found".to_string(),
+            Err(error) => self.message = format!("Failed to attach to RTT: {error}"),
+        }
+    }
+
+    /// Runs the UI until the user quits.
+    pub fn run(&mut self, core: &mut Core) -> Result<()> {
+        self.refresh_breakpoints(core);
+        self.refresh_status(core)?;
+        let mut last_status_poll = Instant::now();
+
+        loop {
+            if last_status_poll.elapsed() >= STATUS_POLL_INTERVAL {
+                self.refresh_status(core)?;
+                last_status_poll = Instant::now();
+            }
+
+            let had_rtt_data = self.poll_rtt(core);
+
+            self.render()?;
+
+            if self.handle_event(core) {
+                return Ok(());
+            }
+
+            if had_rtt_data {
+                thread::sleep(Duration::from_millis(1));
+            } else {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    pub fn clean_up(&mut self, core: &mut Core) -> Result<()> {
+        clean_up_terminal();
+        let _ = self.terminal.show_cursor();
+
+        if let Some(rtt) = &mut self.rtt {
+            rtt.clean_up(core)?;
+        }
+
+        Ok(())
+    }
+
+    fn poll_rtt(&mut self, core: &mut Core) -> bool {
+        let Some(rtt) = &mut self.rtt else {
+            return false;
+        };
+
+        let mut collector = RttCollector {
+            lines: &mut self.rtt_lines,
+            had_data: false,
+        };
+
+        if let Err(error) = rtt.poll_rtt_fallible(core, &mut collector) {
+            self.message = format!("Failed to poll RTT: {error}");
+        }
+
+        collector.had_data
+    }
+
+    /// Updates the halted snapshot if the core state changed.
+    fn refresh_status(&mut self, core: &mut Core) -> Result<()> {
+        match core.status()? {
+            CoreStatus::Halted(_) => {
+                let pc: u64 = core.read_core_reg(core.program_counter())?;
+                if self.halted.as_ref().map(|halted| halted.pc) != Some(pc) {
+                    self.halted = Some(self.capture_halted_view(core, pc)?);
+                    self.refresh_memory(core);
+                }
+            }
+            _ => self.halted = None,
+        }
+
+        Ok(())
+    }
+
+    fn capture_halted_view(&self, core: &mut Core, pc: u64) -> Result<HaltedView> {
+        let mut registers = Vec::new();
+        let register_file = core.registers();
+        for register in register_file.core_registers().chain(register_file.psr()) {
+            let value: RegisterValue = core.read_core_reg(register)?;
+            registers.push((register.name(), format!("{value:#}")));
+        }
+
+        let mut disassembly = Vec::new();
+        let mut code = [0u8; DISASSEMBLY_WINDOW];
+        match core.read(pc, &mut code) {
+            Ok(()) => match disassembler_for(core.instruction_set()?) {
+                Ok(cs) => match cs.disasm_all(&code, pc) {
+                    Ok(instructions) => {
+                        for instruction in instructions.iter() {
+                            let text = format!(
+                                "{} {}",
+                                instruction.mnemonic().unwrap_or_default(),
+                                instruction.op_str().unwrap_or_default()
+                            );
+                            disassembly.push((instruction.address(), text));
+                        }
+                    }
+                    Err(error) => disassembly.push((pc, format!("<{error}>"))),
+                },
+                Err(error) => disassembly.push((pc, format!("<{error:?}>"))),
+            },
+            Err(error) => disassembly.push((pc, format!("<{error}>"))),
+        }
+
+        let location = self
+            .debug_info
+            .as_ref()
+            .and_then(|di| di.get_source_location(pc));
+
+        Ok(HaltedView {
+            pc,
+            registers,
+            disassembly,
+            location,
+        })
+    }
+
+    fn refresh_memory(&mut self, core: &mut Core) {
+        let mut buffer = vec![0u8; MEMORY_WINDOW];
+        self.memory = core
+            .read(self.memory_address, &mut buffer)
+            .map(|_| buffer)
+            .map_err(|error| error.to_string());
+    }
+
+    fn refresh_breakpoints(&mut self, core: &mut Core) {
+        match core.hw_breakpoints() {
+            Ok(breakpoints) => self.breakpoints = breakpoints.into_iter().flatten().collect(),
+            Err(error) => self.message = format!("Failed to read breakpoints: {error}"),
+        }
+    }
+
+    /// Returns `true` if the application should exit.
+    fn handle_event(&mut self, core: &mut Core) -> bool {
+        let event = match self.events.next() {
+            // Ignore key release events emitted by Crossterm on Windows
+            Ok(event) if event.kind != KeyEventKind::Press => return false,
+            Ok(event) => event,
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Disconnected) => {
+                tracing::warn!("Unable to receive more input events from terminal, shutting down.");
+                return true;
+            }
+        };
+
+        if event.code == KeyCode::Char('c') && event.modifiers.contains(KeyModifiers::CONTROL) {
+            return true;
+        }
+
+        if let Some(input) = &mut self.input {
+            match event.code {
+                KeyCode::Enter => {
+                    let line = std::mem::take(input);
+                    self.input = None;
+                    return match TuiCommand::parse(&line) {
+                        Ok(command) => self.execute(core, command),
+                        Err(message) => {
+                            self.message = message;
+                            false
+                        }
+                    };
+                }
+                KeyCode::Esc => self.input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return false;
+        }
+
+        let command = match event.code {
+            KeyCode::Char(':') => {
+                self.input = Some(String::new());
+                return false;
+            }
+            KeyCode::PageUp => {
+                self.rtt_scroll = self.rtt_scroll.saturating_add(10);
+                return false;
+            }
+            KeyCode::PageDown => {
+                self.rtt_scroll = self.rtt_scroll.saturating_sub(10);
+                return false;
+            }
+            KeyCode::Char('c') => TuiCommand::Run,
+            KeyCode::Char('h') => TuiCommand::Halt,
+            KeyCode::Char('s') => TuiCommand::Step,
+            KeyCode::Char('r') => TuiCommand::Reset,
+            KeyCode::Char('q') => TuiCommand::Quit,
+            _ => return false,
+        };
+
+        self.execute(core, command)
+    }
+
+    /// Executes a command. Returns `true` if the application should exit.
+    fn execute(&mut self, core: &mut Core, command: TuiCommand) -> bool {
+        let result = match command {
+            TuiCommand::Quit => return true,
+            TuiCommand::Help => {
+                self.message = "Commands: halt, run, step, reset, break <addr>, clear <addr>, \
+                                mem <addr>, rtt, quit"
+                    .to_string();
+                return false;
+            }
+            TuiCommand::Halt => core
+                .halt(Duration::from_millis(100))
+                .map(|info| format!("Core halted at {:#010x}", info.pc)),
+            TuiCommand::Run => core.run().map(|_| "Core running".to_string()),
+            TuiCommand::Step => core
+                .step()
+                .map(|info| format!("Stepped to {:#010x}", info.pc)),
+            TuiCommand::Reset => core
+                .reset_and_halt(Duration::from_millis(100))
+                .map(|info| format!("Core reset, halted at {:#010x}", info.pc)),
+            TuiCommand::Break(address) => core
+                .set_hw_breakpoint(address)
+                .map(|_| format!("Set breakpoint at {address:#010x}")),
+            TuiCommand::Clear(address) => core
+                .clear_hw_breakpoint(address)
+                .map(|_| format!("Cleared breakpoint at {address:#010x}")),
+            TuiCommand::Memory(address) => {
+                self.memory_address = address;
+                self.refresh_memory(core);
+                return false;
+            }
+            TuiCommand::Rtt => {
+                self.attach_rtt(core);
+                return false;
+            }
+        };
+
+        self.message = match result {
+            Ok(message) => message,
+            Err(error) => format!("Error: {error}"),
+        };
+
+        self.refresh_breakpoints(core);
+        if let Err(error) = self.refresh_status(core) {
+            self.message = format!("Error: {error}");
+        }
+
+        false
+    }
+
+    fn render(&mut self) -> Result<()> {
+        self.terminal
+            .draw(|f| {
+                let rows = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Min(10),
+                        Constraint::Length(10),
+                        Constraint::Min(5),
+                        Constraint::Length(1),
+                    ])
+                    .split(f.size());
+                let top = split_horizontal(rows[0]);
+                let middle = split_horizontal(rows[1]);
+
+                let source = self.halted.as_ref().and_then(|halted| {
+                    source_lines(&mut self.source_cache, halted.location.as_ref()?)
+                });
+                render_code(f, top[0], self.halted.as_ref(), source);
+                render_registers(f, top[1], self.halted.as_ref());
+                render_memory(f, middle[0], self.memory_address, &self.memory);
+                render_breakpoints(f, middle[1], &self.breakpoints);
+                render_rtt(f, rows[2], &self.rtt_lines, self.rtt_scroll);
+
+                let status = match &self.input {
+                    Some(input) => Paragraph::new(format!(":{input}"))
+                        .style(Style::default().fg(Color::Yellow).bg(Color::Blue)),
+                    None => {
+                        let state = match &self.halted {
+                            Some(halted) => format!("Halted at {:#010x}", halted.pc),
+                            None => "Running".to_string(),
+                        };
+                        Paragraph::new(format!("{state} | {}", self.message))
+                            .style(Style::default().fg(Color::Black).bg(Color::Yellow))
+                    }
+                };
+                f.render_widget(status, rows[3]);
+            })
+            .context("Failed to render terminal UI")?;
+
+        Ok(())
+    }
+}
+
+fn split_horizontal(area: Rect) -> std::rc::Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+        .split(area)
+}
+
+fn pane(title: impl Into<String>) -> Block<'static> {
+    Block::default().borders(Borders::ALL).title(title.into())
+}
+
+/// Returns the source file and line the location points to, loading the file on first use.
+fn source_lines<'c>(
+    cache: &'c mut HashMap<PathBuf, Option<Vec<String>>>,
+    location: &SourceLocation,
+) -> Option<(String, u64, &'c [String])> {
+    let path = PathBuf::try_from(location.combined_typed_path()?).ok()?;
+    let line = location.line?;
+
+    let lines = cache
+        .entry(path.clone())
+        .or_insert_with(|| {
+            std::fs::read_to_string(&path)
+                .ok()
+                .map(|content| content.lines().map(str::to_string).collect())
+        })
+        .as_deref()?;
+
+    Some((path.display().to_string(), line, lines))
+}
+
+fn render_code(
+    f: &mut Frame,
+    area: Rect,
+    halted: Option<&HaltedView>,
+    source: Option<(String, u64, &[String])>,
+) {
+    let Some(halted) = halted else {
+        f.render_widget(Paragraph::new("Core is running").block(pane("Code")), area);
+        return;
+    };
+
+    let area = if let Some((path, line, lines)) = source {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(area);
+
+        let height = chunks[0].height.saturating_sub(2) as usize;
+        // Line numbers in the debug info are 1-based.
+        let current = line.saturating_sub(1) as usize;
+        let first = current.saturating_sub(height / 2);
+        let text: Vec<Line> = lines
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(height)
+            .map(|(index, content)| {
+                let marker = if index == current { ">" } else { " " };
+                let line = Line::from(format!("{marker}{:5} {content}", index + 1));
+                if index == current {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .collect();
+        f.render_widget(
+            Paragraph::new(text).block(pane(format!("Source: {path}:{line}"))),
+            chunks[0],
+        );
+
+        chunks[1]
+    } else {
+        area
+    };
+
+    let text: Vec<Line> = halted
+        .disassembly
+        .iter()
+        .map(|(address, instruction)| {
+            let line = Line::from(format!("{address:#010x}: {instruction}"));
+            if *address == halted.pc {
+                line.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                line
+            }
+        })
+        .collect();
+    f.render_widget(Paragraph::new(text).block(pane("Disassembly")), area);
+}
+
+fn render_registers(f: &mut Frame, area: Rect, halted: Option<&HaltedView>) {
+    let text: Vec<Line> = halted
+        .map(|halted| {
+            halted
+                .registers
+                .iter()
+                .map(|(name, value)| Line::from(format!("{name:>6}: {value}")))
+                .collect()
+        })
+        .unwrap_or_default();
+    f.render_widget(Paragraph::new(text).block(pane("Registers")), area);
+}
+
+fn render_memory(f: &mut Frame, area: Rect, address: u64, memory: &Result<Vec<u8>, String>) {
+    let text: Vec<Line> = match memory {
+        Ok(data) => data
+            .chunks(16)
+            .enumerate()
+            .map(|(row, bytes)| {
+                let hex = bytes
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let ascii: String = bytes
+                    .iter()
+                    .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                    .collect();
+                Line::from(format!(
+                    "{:#010x}: {hex} {ascii}",
+                    address + (row * 16) as u64
+                ))
+            })
+            .collect(),
+        Err(error) => vec![Line::from(format!("Failed to read memory: {error}"))],
+    };
+    f.render_widget(
+        Paragraph::new(text).block(pane(format!("Memory @ {address:#010x}"))),
+        area,
+    );
+}
+
+fn render_breakpoints(f: &mut Frame, area: Rect, breakpoints: &[u64]) {
+    let text: Vec<Line> = breakpoints
+        .iter()
+        .enumerate()
+        .map(|(index, address)| Line::from(format!("{index}: {address:#010x}")))
+        .collect();
+    f.render_widget(Paragraph::new(text).block(pane("Breakpoints")), area);
+}
+
+fn render_rtt(f: &mut Frame, area: Rect, lines: &[String], scroll: usize) {
+    let height = area.height.saturating_sub(2) as usize;
+    let end = lines.len().saturating_sub(scroll);
+    let start = end.saturating_sub(height);
+    let text: Vec<Line> = lines[start..end]
+        .iter()
+        .map(|line| Line::from(line.trim_end_matches(['\r', '\n']).to_string()))
+        .collect();
+    f.render_widget(Paragraph::new(text).block(pane("RTT")), area);
+}
+
+#[cfg(test)]
+mod test {
+    use super::TuiCommand;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(TuiCommand::parse("step"), Ok(TuiCommand::Step));
+        assert_eq!(TuiCommand::parse("continue"), Ok(TuiCommand::Run));
+        assert_eq!(
+            TuiCommand::parse("break 0x08000100"),
+            Ok(TuiCommand::Break(0x0800_0100))
+        );
+        assert_eq!(
+            TuiCommand::parse("mem 536870912"),
+            Ok(TuiCommand::Memory(0x2000_0000))
+        );
+        assert!(TuiCommand::parse("break").is_err());
+        assert!(TuiCommand::parse("clear zzz").is_err());
+        assert!(TuiCommand::parse("frobnicate").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+mod app;
+
+use std::path::PathBuf;
+
+use probe_rs::{debug::DebugInfo, probe::list::Lister, rtt::ScanRegion};
+use time::UtcOffset;
+
+use crate::{util::common_options::ProbeOptions, CoreOptions};
+
+use app::{App, RttSetup};
+
+/// Terminal based debugger with source, register, memory, breakpoint and RTT panes
+///
+/// Key bindings:
+///     c    resume the core          h    halt the core
+///     s    single step              r    reset and halt
+///     :    enter a command          q    quit
+///     PageUp/PageDown scroll the RTT pane
+///
+/// Type `:help` for the list of commands.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// Binary to debug. Used for source lookup and to locate the RTT control block.
+    #[clap(long, value_parser)]
+    exe: Option<PathBuf>,
+
+    /// Scan the memory to find the RTT control block
+    #[clap(long)]
+    rtt_scan_memory: bool,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister, timestamp_offset: UtcOffset) -> anyhow::Result<()> {
+        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+
+        let debug_info = self.exe.as_deref().map(DebugInfo::from_file).transpose()?;
+
+        let scan_regions = match self.rtt_scan_memory {
+            true => session.target().rtt_scan_regions.clone(),
+            false => ScanRegion::Ranges(vec![]),
+        };
+        let rtt_setup = self.exe.map(|path| RttSetup {
+            path,
+            scan_regions,
+            timestamp_offset,
+        });
+
+        let mut core = session.core(self.shared.core)?;
+
+        let mut app = App::new(debug_info, rtt_setup)?;
+        app.attach_rtt(&mut core);
+
+        let result = app.run(&mut core);
+
+        // Always restore the terminal, but report the error of the run loop first.
+        let cleanup_result = app.clean_up(&mut core);
+        result?;
+        cleanup_result
+    }
+}
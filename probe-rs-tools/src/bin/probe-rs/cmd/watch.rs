@@ -0,0 +1,198 @@
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use probe_rs::{probe::list::Lister, MemoryInterface};
+use signal_hook::consts::signal;
+
+use crate::util::{common_options::ProbeOptions, patch::find_symbol, value::ScalarType};
+use crate::CoreOptions;
+
+/// Sample variables of the running firmware
+///
+/// e.g. probe-rs watch --chip nRF52840_xxAA --elf firmware.elf SPEED TEMPERATURE:f32 --interval 10ms
+///      Prints the values of the two variables every 10 milliseconds.
+///
+/// The variables are read while the core runs, so the firmware is not disturbed. A variable is
+/// given as `<symbol>` or `<symbol>:<type>`, with the types u8..u64, i8..i64, f32, f64 and bool.
+/// Without a type, the variable is read as unsigned integer of the size of the symbol.
+#[derive(clap::Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    probe_options: ProbeOptions,
+
+    /// The ELF file the symbols are looked up in.
+    #[clap(long)]
+    elf: PathBuf,
+
+    /// The variables to sample.
+    #[clap(required = true)]
+    variables: Vec<String>,
+
+    /// The time between two samples, e.g. `10ms`, `500us` or `1s`.
+    #[clap(long, value_parser = parse_interval, default_value = "100ms")]
+    interval: Duration,
+
+    /// Stop after this many seconds, instead of running until interrupted with Ctrl-C.
+    #[clap(long)]
+    duration: Option<u64>,
+
+    /// Write the samples as CSV to this file, instead of printing them.
+    #[clap(long, conflicts_with = "tcp")]
+    csv: Option<PathBuf>,
+
+    /// Stream the samples as CSV to the first client connecting to this address, e.g. for a
+    /// live plot.
+    #[clap(long)]
+    tcp: Option<SocketAddr>,
+}
+
+/// A variable to sample.
+struct Variable {
+    name: String,
+    address: u64,
+    ty: ScalarType,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let elf = std::fs::read(&self.elf)?;
+        let variables = self
+            .variables
+            .iter()
+            .map(|variable| resolve_variable(&elf, variable))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let (mut output, csv): (Box<dyn Write>, bool) = if let Some(path) = &self.csv {
+            (Box::new(std::fs::File::create(path)?), true)
+        } else if let Some(address) = self.tcp {
+            let listener = TcpListener::bind(address)?;
+            println!("Waiting for a client on {address}");
+            let (stream, client) = listener.accept()?;
+            println!("Streaming to {client}");
+            (Box::new(stream), true)
+        } else {
+            (Box::new(std::io::stdout()), false)
+        };
+
+        let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
+        let mut core = session.core(self.shared.core)?;
+
+        if csv {
+            let names = variables.iter().map(|variable| variable.name.as_str());
+            writeln!(output, "time,{}", names.collect::<Vec<_>>().join(","))?;
+        }
+
+        // Stop on Ctrl-C, so the output is flushed.
+        let stop = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal::SIGINT, stop.clone())?;
+
+        let start = Instant::now();
+        let mut next = start;
+        let mut buffer = [0; 8];
+        loop {
+            let time = start.elapsed().as_secs_f64();
+            let mut values = Vec::with_capacity(variables.len());
+            for variable in &variables {
+                let bytes = &mut buffer[..variable.ty.size()];
+                core.read(variable.address, bytes)?;
+                values.push(variable.ty.decode(bytes));
+            }
+
+            if csv {
+                writeln!(output, "{time:.6},{}", values.join(","))?;
+            } else {
+                let values = variables
+                    .iter()
+                    .zip(&values)
+                    .map(|(variable, value)| format!("{} = {value}", variable.name));
+                writeln!(
+                    output,
+                    "{time:>10.3}s  {}",
+                    values.collect::<Vec<_>>().join(", ")
+                )?;
+            }
+
+            if stop.load(Ordering::Relaxed)
+                || self
+                    .duration
+                    .is_some_and(|duration| start.elapsed() >= Duration::from_secs(duration))
+            {
+                break;
+            }
+
+            // Keep the sample rate, even if reading the variables took a while.
+            next += self.interval;
+            if let Some(wait) = next.checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+        }
+
+        output.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Looks up a variable given as `<symbol>` or `<symbol>:<type>` in the ELF file.
+fn resolve_variable(elf: &[u8], variable: &str) -> anyhow::Result<Variable> {
+    // Paths like `config::GAIN` contain colons too.
+    let (name, ty) = match variable.rsplit_once(':') {
+        Some((name, ty)) if !name.ends_with(':') => (name, Some(ty.parse::<ScalarType>()?)),
+        _ => (variable, None),
+    };
+
+    let (address, size) = find_symbol(elf, name)?;
+    let ty = match ty {
+        Some(ty) => ty,
+        None => ScalarType::unsigned(size).with_context(|| {
+            format!("The symbol '{name}' has {size} bytes, so it needs a type like '{name}:u32'.")
+        })?,
+    };
+
+    Ok(Variable {
+        name: name.to_string(),
+        address,
+        ty,
+    })
+}
+
+/// Parses an interval like `10ms`, `500us` or `1s`. Plain numbers are milliseconds.
+fn parse_interval(input: &str) -> Result<Duration, String> {
+    let (number, unit) = input
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or((input, "ms"), |index| input.split_at(index));
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("'{input}' is not an interval like '10ms'."))?;
+
+    match unit {
+        "us" => Ok(Duration::from_micros(number)),
+        "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        _ => Err(format!("Unknown unit '{unit}', use 'us', 'ms' or 's'.")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intervals_are_parsed() {
+        assert_eq!(parse_interval("10ms"), Ok(Duration::from_millis(10)));
+        assert_eq!(parse_interval("500us"), Ok(Duration::from_micros(500)));
+        assert_eq!(parse_interval("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_interval("25"), Ok(Duration::from_millis(25)));
+        assert!(parse_interval("1h").is_err());
+        assert!(parse_interval("ms").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use probe_rs::{probe::list::Lister, MemoryInterface};
+use probe_rs::{memory_init::init_memory, probe::list::Lister, MemoryInterface};
 
 use crate::util::common_options::{ProbeOptions, ReadWriteBitWidth, ReadWriteOptions};
 use crate::util::parse_u64;
@@ -31,6 +31,12 @@ pub struct Cmd {
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
         let (mut session, _probe_options) = self.probe_options.simple_attach(lister)?;
+        let address = self.read_write_options.address;
+        let width = self.read_write_options.width as u64 / 8;
+        init_memory(
+            &mut session,
+            &(address..address + self.values.len() as u64 * width),
+        )?;
         let mut core = session.core(self.shared.core)?;
 
         match self.read_write_options.width {
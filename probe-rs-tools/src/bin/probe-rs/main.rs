@@ -9,7 +9,7 @@ use std::str::FromStr;
 use std::{ffi::OsString, path::PathBuf};
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use colored::Colorize;
 use itertools::Itertools;
 use probe_rs::flashing::{BinOptions, Format, FormatKind, IdfOptions};
@@ -19,9 +19,12 @@ use serde::Deserialize;
 use serde::Serialize;
 use time::{OffsetDateTime, UtcOffset};
 
+use crate::util::error_report::ErrorReport;
 use crate::util::logging::setup_logging;
+use crate::util::output::{print_json_error, OutputFormat};
 use crate::util::parse_u32;
 use crate::util::parse_u64;
+use crate::util::project_config::ProjectConfig;
 
 const MAX_LOG_FILES: usize = 20;
 
@@ -41,6 +44,17 @@ struct Cli {
     /// Enable logging to the default folder. This option is ignored if `--log-file` is specified.
     #[clap(long, global = true, help_heading = "LOG CONFIGURATION")]
     log_to_folder: bool,
+    /// Write the timing of probe, flash and debugger operations to a file in the Chrome trace
+    /// event format, which can be opened with Perfetto (https://ui.perfetto.dev).
+    ///
+    /// This is not supported by the DAP server.
+    #[clap(
+        long,
+        global = true,
+        help_heading = "LOG CONFIGURATION",
+        value_name = "PATH"
+    )]
+    trace_output: Option<PathBuf>,
     #[clap(
         long,
         short,
@@ -52,6 +66,15 @@ struct Cli {
         default_missing_value = "./report.zip"
     )]
     report: Option<PathBuf>,
+    /// Format of the command output. JSON is supported by `list`, `info`, `doctor`, `erase`,
+    /// `regions`, `chip`, `benchmark` and `read`, which also print their errors as JSON, with a
+    /// stable error code. Other commands reject the JSON format.
+    #[clap(long, global = true, value_enum, default_value_t)]
+    output_format: OutputFormat,
+    /// Project configuration file to use instead of the `probe-rs.toml` and
+    /// `probe-rs.local.toml` files found in the current directory or its parents.
+    #[clap(long, global = true, value_name = "PATH", env = "PROBE_RS_CONFIG")]
+    config: Option<PathBuf>,
     #[clap(subcommand)]
     subcommand: Subcommand,
 }
@@ -64,31 +87,80 @@ enum Subcommand {
     List(cmd::list::Cmd),
     /// Gets info about the selected debug probe and connected target
     Info(cmd::info::Cmd),
+    /// Check the setup for common problems, like missing USB permissions or an unpowered target
+    Doctor(cmd::doctor::Cmd),
     /// Resets the target attached to the selected debug probe
     Reset(cmd::reset::Cmd),
     /// Run a GDB server
     Gdb(cmd::gdb::Cmd),
     /// Basic command line debugger
     Debug(cmd::debug::Cmd),
+    /// Terminal UI debugger
+    Tui(cmd::tui::Cmd),
+    /// Interactive shell keeping a session open across commands
+    Shell(cmd::shell::Cmd),
+    /// Run a script automating a sequence of target operations
+    Script(cmd::script::Cmd),
+    /// Share a probe over the network or between processes, for use with `--remote`
+    Serve(cmd::serve::Cmd),
     /// Download memory to attached target
     Download(cmd::download::Cmd),
     /// Erase all nonvolatile memory of attached target
     Erase(cmd::erase::Cmd),
+    /// Program many boards with the same image, for production
+    FlashFarm(cmd::flash_farm::Cmd),
     /// Flash and run an ELF program
     #[clap(name = "run")]
     Run(cmd::run::Cmd),
     /// Attach to rtt logging
     #[clap(name = "attach")]
     Attach(cmd::attach::Cmd),
-    /// Trace a memory location on the target
+    /// Trace a memory location on the target, or capture and decode its SWO output
     #[clap(name = "trace")]
     Trace(cmd::trace::Cmd),
+    /// Measure the peak stack usage of the firmware
+    #[clap(name = "stack-usage")]
+    StackUsage(cmd::stack_usage::Cmd),
+    /// Measure the cycles between two points in the code
+    #[clap(name = "time")]
+    Time(cmd::time::Cmd),
+    /// Record the code coverage of a run of the firmware
+    #[clap(name = "coverage")]
+    Coverage(cmd::coverage::Cmd),
+    /// Change a global variable of the running firmware
+    #[clap(name = "poke")]
+    Poke(cmd::poke::Cmd),
+    /// Sample variables of the running firmware
+    #[clap(name = "watch")]
+    Watch(cmd::watch::Cmd),
+    /// Disassemble memory of the target, an ELF file or a core dump
+    #[clap(name = "disasm")]
+    Disasm(cmd::disasm::Cmd),
+    /// Map addresses to functions and source locations
+    #[clap(name = "symbolize")]
+    Symbolize(cmd::symbolize::Cmd),
+    /// Test RAM for faulty data and address lines and cells
+    #[clap(name = "memtest")]
+    Memtest(cmd::memtest::Cmd),
+    /// Show the MPU and SAU regions of a Cortex-M core
+    #[clap(name = "regions")]
+    Regions(cmd::regions::Cmd),
     /// Configure and monitor ITM trace packets from the target.
     #[clap(name = "itm")]
     Itm(cmd::itm::Cmd),
     Chip(cmd::chip::Cmd),
     /// Measure the throughput of the selected debug probe
     Benchmark(cmd::benchmark::Cmd),
+    Bootloader(cmd::bootloader::Cmd),
+    /// Use the SPI, I2C and GPIO bridge of an ST-LINK-V3
+    Bridge(cmd::bridge::Cmd),
+    /// Flash and inspect STM8 targets with an ST-LINK
+    Stm8(cmd::stm8::Cmd),
+    Rp2040(cmd::rp2040::Cmd),
+    Pins(cmd::pins::Cmd),
+    /// Switch the target power supply of the probe
+    Power(cmd::power::Cmd),
+    Provision(cmd::provision::Cmd),
     /// Profile on-target runtime performance of target ELF program
     Profile(cmd::profile::ProfileCmd),
     Read(cmd::read::Cmd),
@@ -97,6 +169,23 @@ enum Subcommand {
     Mi(cmd::mi::Cmd),
 }
 
+impl Subcommand {
+    /// Returns whether the command can print its result as JSON.
+    fn supports_json(&self) -> bool {
+        matches!(
+            self,
+            Subcommand::List(_)
+                | Subcommand::Info(_)
+                | Subcommand::Doctor(_)
+                | Subcommand::Erase(_)
+                | Subcommand::Regions(_)
+                | Subcommand::Chip(_)
+                | Subcommand::Benchmark(_)
+                | Subcommand::Read(_)
+        )
+    }
+}
+
 /// Shared options for core selection, shared between commands
 #[derive(clap::Parser)]
 pub(crate) struct CoreOptions {
@@ -273,7 +362,12 @@ fn main() -> Result<()> {
     }
 
     // Parse the commandline options.
-    let matches = Cli::parse_from(args);
+    let arg_matches = Cli::command().get_matches_from(args);
+    let matches = Cli::from_arg_matches(&arg_matches).unwrap_or_else(|error| error.exit());
+    let command = arg_matches
+        .subcommand_name()
+        .unwrap_or_default()
+        .to_string();
 
     // Setup the probe lister, list all probes normally
     let lister = Lister::new();
@@ -301,16 +395,28 @@ fn main() -> Result<()> {
         return cmd::dap_server::run(cmd, &lister, utc_offset, log_path);
     }
 
-    let _logger_guard = setup_logging(log_path, None);
+    let logger_guard = setup_logging(log_path, None, matches.trace_output.as_deref())?;
+
+    ProjectConfig::load(matches.config.as_deref())?;
+
+    let json_unsupported = matches.output_format.is_json() && !matches.subcommand.supports_json();
 
     let mut elf = None;
     let result = match matches.subcommand {
+        _ if json_unsupported => Err(anyhow::anyhow!(
+            "`probe-rs {command}` does not support `--output-format json`"
+        )),
         Subcommand::DapServer { .. } => unreachable!(), // handled above.
-        Subcommand::List(cmd) => cmd.run(&lister),
-        Subcommand::Info(cmd) => cmd.run(&lister),
+        Subcommand::List(cmd) => cmd.run(&lister, matches.output_format),
+        Subcommand::Info(cmd) => cmd.run(&lister, matches.output_format),
+        Subcommand::Doctor(cmd) => cmd.run(&lister, matches.output_format),
         Subcommand::Gdb(cmd) => cmd.run(&lister),
         Subcommand::Reset(cmd) => cmd.run(&lister),
         Subcommand::Debug(cmd) => cmd.run(&lister),
+        Subcommand::Tui(cmd) => cmd.run(&lister, utc_offset),
+        Subcommand::Shell(cmd) => cmd.run(&lister, utc_offset),
+        Subcommand::Script(cmd) => cmd.run(&lister),
+        Subcommand::Serve(cmd) => cmd.run(&lister),
         Subcommand::Download(cmd) => cmd.run(&lister),
         Subcommand::Run(cmd) => {
             elf = Some(cmd.shared_options.path.clone());
@@ -320,19 +426,49 @@ fn main() -> Result<()> {
             elf = Some(cmd.run.shared_options.path.clone());
             cmd.run(&lister, utc_offset)
         }
-        Subcommand::Erase(cmd) => cmd.run(&lister),
+        Subcommand::Erase(cmd) => cmd.run(&lister, matches.output_format),
+        Subcommand::FlashFarm(cmd) => cmd.run(&lister),
         Subcommand::Trace(cmd) => cmd.run(&lister),
+        Subcommand::StackUsage(cmd) => cmd.run(&lister),
+        Subcommand::Time(cmd) => cmd.run(&lister),
+        Subcommand::Coverage(cmd) => cmd.run(&lister),
+        Subcommand::Watch(cmd) => cmd.run(&lister),
+        Subcommand::Poke(cmd) => cmd.run(&lister),
+        Subcommand::Disasm(cmd) => cmd.run(&lister),
+        Subcommand::Symbolize(cmd) => cmd.run(),
+        Subcommand::Memtest(cmd) => cmd.run(&lister),
+        Subcommand::Regions(cmd) => cmd.run(&lister, matches.output_format),
         Subcommand::Itm(cmd) => cmd.run(&lister),
-        Subcommand::Chip(cmd) => cmd.run(),
-        Subcommand::Benchmark(cmd) => cmd.run(&lister),
+        Subcommand::Chip(cmd) => cmd.run(&lister, matches.output_format),
+        Subcommand::Benchmark(cmd) => cmd.run(&lister, matches.output_format),
+        Subcommand::Bootloader(cmd) => cmd.run(&lister),
+        Subcommand::Bridge(cmd) => cmd.run(),
+        Subcommand::Stm8(cmd) => cmd.run(&lister),
+        Subcommand::Rp2040(cmd) => cmd.run(&lister),
+        Subcommand::Pins(cmd) => cmd.run(&lister),
+        Subcommand::Power(cmd) => cmd.run(&lister),
+        Subcommand::Provision(cmd) => cmd.run(&lister),
         Subcommand::Profile(cmd) => cmd.run(&lister),
-        Subcommand::Read(cmd) => cmd.run(&lister),
+        Subcommand::Read(cmd) => cmd.run(&lister, matches.output_format),
         Subcommand::Write(cmd) => cmd.run(&lister),
         Subcommand::Complete(cmd) => cmd.run(&lister),
         Subcommand::Mi(cmd) => cmd.run(),
     };
 
-    compile_report(result, matches.report, elf, log_path)
+    if let Err(error) = compile_report(result, matches.report, elf, log_path) {
+        let report = ErrorReport::new(&error);
+        if matches.output_format.is_json() {
+            print_json_error(&command, &report)?;
+        } else {
+            report.print();
+        }
+
+        // Exiting skips destructors, so the log has to be flushed first.
+        drop(logger_guard);
+        std::process::exit(1);
+    }
+
+    Ok(())
 }
 
 fn compile_report(
@@ -381,7 +517,19 @@ fn compile_report(
 
 #[cfg(test)]
 mod test {
-    use crate::multicall_check;
+    use clap::Parser;
+
+    use crate::{multicall_check, Cli};
+
+    #[test]
+    fn json_output_support() {
+        let cli = Cli::parse_from(["probe-rs", "list", "--output-format", "json"]);
+        assert!(cli.output_format.is_json());
+        assert!(cli.subcommand.supports_json());
+
+        let cli = Cli::parse_from(["probe-rs", "reset", "--output-format", "json"]);
+        assert!(!cli.subcommand.supports_json());
+    }
 
     #[test]
     fn argument_preprocessing() {
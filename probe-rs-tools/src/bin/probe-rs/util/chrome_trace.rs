@@ -0,0 +1,233 @@
+//! A [`tracing`] layer which writes spans in the Chrome trace event format.
+//!
+//! The resulting files can be opened with [Perfetto](https://ui.perfetto.dev) or
+//! `chrome://tracing`, which show how long each operation took, and what it was waiting for.
+
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use parking_lot::Mutex;
+use serde_json::{json, Map, Value};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// Writes every entered span as a complete event ("ph": "X") to a trace file.
+pub struct ChromeTraceLayer {
+    output: Arc<Mutex<TraceOutput>>,
+    start: Instant,
+}
+
+/// Finishes the trace file when dropped.
+pub struct ChromeTraceGuard {
+    output: Arc<Mutex<TraceOutput>>,
+}
+
+struct TraceOutput {
+    writer: BufWriter<File>,
+    empty: bool,
+}
+
+impl TraceOutput {
+    fn write_event(&mut self, event: &Value) {
+        let separator = if self.empty { "" } else { ",\n" };
+        self.empty = false;
+
+        // Tracing must not fail the traced operation, so write errors are ignored.
+        _ = write!(self.writer, "{separator}{event}");
+    }
+}
+
+impl ChromeTraceLayer {
+    /// Creates the trace file at `path`.
+    pub fn new(path: &Path) -> std::io::Result<(Self, ChromeTraceGuard)> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(b"[\n")?;
+
+        let output = Arc::new(Mutex::new(TraceOutput {
+            writer,
+            empty: true,
+        }));
+
+        Ok((
+            Self {
+                output: output.clone(),
+                start: Instant::now(),
+            },
+            ChromeTraceGuard { output },
+        ))
+    }
+
+    fn timestamp_us(&self, instant: Instant) -> f64 {
+        instant.duration_since(self.start).as_secs_f64() * 1_000_000.0
+    }
+}
+
+impl Drop for ChromeTraceGuard {
+    fn drop(&mut self) {
+        let mut output = self.output.lock();
+        _ = output.writer.write_all(b"\n]\n");
+        _ = output.writer.flush();
+    }
+}
+
+/// The fields of a span, and when it was entered last.
+struct SpanData {
+    args: Map<String, Value>,
+    entered: Option<Instant>,
+}
+
+struct FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{value:?}")));
+    }
+}
+
+/// Returns a small number identifying the current thread, as trace viewers expect numeric ids.
+fn thread_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+    thread_local! {
+        static ID: Cell<u64> = const { Cell::new(0) };
+    }
+
+    ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
+impl<S> Layer<S> for ChromeTraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut args = Map::new();
+        attrs.record(&mut FieldVisitor(&mut args));
+
+        span.extensions_mut().insert(SpanData {
+            args,
+            entered: None,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            values.record(&mut FieldVisitor(&mut data.args));
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            data.entered = Some(Instant::now());
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let exited = Instant::now();
+
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>() else {
+            return;
+        };
+        let Some(entered) = data.entered.take() else {
+            return;
+        };
+
+        let event = json!({
+            "name": span.name(),
+            "cat": span.metadata().target(),
+            "ph": "X",
+            "ts": self.timestamp_us(entered),
+            "dur": exited.duration_since(entered).as_secs_f64() * 1_000_000.0,
+            "pid": std::process::id(),
+            "tid": thread_id(),
+            "args": data.args,
+        });
+
+        self.output.lock().write_event(&event);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn spans_are_written_as_complete_events() {
+        let path = std::env::temp_dir().join(format!("probe-rs-trace-{}.json", std::process::id()));
+
+        let (layer, guard) = ChromeTraceLayer::new(&path).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("read_32", address = 0x2000_0000u64, length = 4u64);
+            let _entered = span.enter();
+        });
+        drop(guard);
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let trace: Value = serde_json::from_str(&trace).unwrap();
+        let events = trace.as_array().unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["name"], "read_32");
+        assert_eq!(events[0]["ph"], "X");
+        assert_eq!(events[0]["args"]["address"], 0x2000_0000u64);
+        assert_eq!(events[0]["args"]["length"], 4);
+    }
+}
@@ -1,24 +1,32 @@
 use std::{
     fs::File,
-    io::Write,
+    io::{BufWriter, Write},
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 use super::cargo::ArtifactError;
+use super::patch::{PatchError, PatchOptions};
+use super::project_config::ProjectConfig;
 use crate::util::parse_u64;
 use probe_rs::{
     config::{RegistryError, TargetSelector},
     flashing::{FileDownloadError, FlashError},
     integration::FakeProbe,
     probe::{
-        list::Lister, DebugProbeError, DebugProbeInfo, DebugProbeSelector, Probe, WireProtocol,
+        bitbang::{BitbangProbe, BitbangProtocol},
+        gpio::{GpioPins, GpioProbe},
+        list::Lister,
+        recording::ReplayProbe,
+        remote::RemoteProbe,
+        DebugProbeError, DebugProbeInfo, DebugProbeSelector, Probe, SpeedFallback, WireProtocol,
     },
     Permissions, Session, Target,
 };
 use serde::{Deserialize, Serialize};
 
 /// Common options when flashing a target device.
-#[derive(Debug, clap::Parser)]
+#[derive(Debug, Clone, clap::Parser)]
 pub struct BinaryDownloadOptions {
     #[arg(long, help_heading = "DOWNLOAD CONFIGURATION")]
     pub disable_progressbars: bool,
@@ -39,6 +47,11 @@ pub struct BinaryDownloadOptions {
     /// After flashing, read back all the flashed data to verify it has been written correctly.
     #[arg(long, help_heading = "DOWNLOAD CONFIGURATION")]
     pub verify: bool,
+    /// Print transfer statistics and the time spent in each flashing phase after downloading.
+    #[arg(long, help_heading = "DOWNLOAD CONFIGURATION")]
+    pub metrics: bool,
+    #[clap(flatten)]
+    pub patches: PatchOptions,
 }
 
 /// Supported bit-widths for read/write commands (not every device may support each width).
@@ -65,10 +78,12 @@ pub struct ReadWriteOptions {
 }
 
 /// Common options and logic when interfacing with a [Probe].
-#[derive(clap::Parser, Debug)]
+#[derive(clap::Parser, Debug, Clone)]
 pub struct ProbeOptions {
     #[arg(long, env = "PROBE_RS_CHIP", help_heading = "PROBE CONFIGURATION")]
     pub chip: Option<String>,
+    /// A target description file, or a directory of target description files, with targets
+    /// which are not built into probe-rs.
     #[arg(
         value_name = "chip description file path",
         long,
@@ -92,12 +107,47 @@ pub struct ProbeOptions {
     /// Use this flag to select a specific probe in the list.
     ///
     /// Use '--probe VID:PID' or '--probe VID:PID:Serial' if you have more than one
-    /// probe with the same VID:PID.",
+    /// probe with the same VID:PID. Use '--probe usb:<port>' with the USB port shown by
+    /// `probe-rs list` if the probes also have the same serial number. Network attached
+    /// J-Link probes are selected with '--probe ip:<address>'.
     #[arg(long, env = "PROBE_RS_PROBE", help_heading = "PROBE CONFIGURATION")]
     pub probe: Option<DebugProbeSelector>,
+    /// Wait for a probe to be connected instead of failing when none is found.
+    ///
+    /// If '--probe' is given, wait for a matching probe.
+    #[arg(long, help_heading = "PROBE CONFIGURATION")]
+    pub wait_for_probe: bool,
+    /// Keep retrying to open the probe and attach to the target for the given number of
+    /// seconds, e.g. while the board is power-cycled.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        env = "PROBE_RS_CONNECT_TIMEOUT",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub connect_timeout: Option<u64>,
     /// The protocol speed in kHz.
     #[arg(long, env = "PROBE_RS_SPEED", help_heading = "PROBE CONFIGURATION")]
     pub speed: Option<u32>,
+    /// Let the JTAG clock follow the RTCK signal of the target instead of using a fixed speed.
+    ///
+    /// This helps with slow targets, or targets which change their clock when waking up
+    /// from a low power state. Requires a probe and target with RTCK support.
+    #[arg(
+        long,
+        env = "PROBE_RS_ADAPTIVE_CLOCKING",
+        conflicts_with = "speed",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub adaptive_clocking: bool,
+    /// Lower the protocol speed step by step when transfers keep failing, e.g. because of
+    /// long wires, and raise it again once the connection is stable.
+    #[arg(
+        long,
+        env = "PROBE_RS_SPEED_FALLBACK",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub speed_fallback: bool,
     /// Use this flag to assert the nreset & ntrst pins during attaching the probe to
     /// the chip.
     #[arg(
@@ -116,6 +166,55 @@ pub struct ProbeOptions {
         help_heading = "PROBE CONFIGURATION"
     )]
     pub allow_erase_all: bool,
+    /// Record all probe operations to the given file. The recording can be replayed with `--replay`.
+    #[arg(long, value_name = "PATH", help_heading = "PROBE CONFIGURATION")]
+    pub record: Option<PathBuf>,
+    /// Replay a recording made with `--record` instead of using a connected probe.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["record", "dry_run"],
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub replay: Option<PathBuf>,
+    /// Use the probe served by `probe-rs serve` at the given address, e.g. `lab-pc:4242`.
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        env = "PROBE_RS_REMOTE",
+        conflicts_with_all = ["probe", "replay", "dry_run"],
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub remote: Option<String>,
+    /// Connect to a virtual JTAG server at the given address, e.g. a simulator exposing
+    /// `remote_bitbang` or `jtag_vpi`.
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        conflicts_with_all = ["probe", "replay", "dry_run", "remote"],
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub bitbang: Option<String>,
+    /// The protocol spoken by the virtual JTAG server given with `--bitbang`.
+    #[arg(
+        long,
+        value_name = "PROTOCOL",
+        default_value = "remote-bitbang",
+        requires = "bitbang",
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub bitbang_protocol: BitbangProtocol,
+    /// Bit-bang SWD or JTAG using the given GPIO lines (Linux only), e.g. `swclk=25,swdio=24`.
+    ///
+    /// Optional lines are `tdi` and `tdo` for JTAG, and `nreset`. The GPIO chip defaults to
+    /// `/dev/gpiochip0`, and can be set with `chip=<path>`.
+    #[arg(
+        long,
+        value_name = "PINS",
+        conflicts_with_all = ["probe", "replay", "dry_run", "remote", "bitbang"],
+        help_heading = "PROBE CONFIGURATION"
+    )]
+    pub gpio: Option<GpioPins>,
 }
 
 impl ProbeOptions {
@@ -132,8 +231,7 @@ impl ProbeOptions {
         let common_options = self.load()?;
 
         let target = common_options.get_target_selector()?;
-        let probe = common_options.attach_probe(lister)?;
-        let session = common_options.attach_session(probe, target)?;
+        let session = common_options.attach(lister, target)?;
 
         Ok((session, common_options))
     }
@@ -146,7 +244,9 @@ pub struct LoadedProbeOptions(ProbeOptions);
 impl LoadedProbeOptions {
     /// Performs necessary init calls such as loading all chip descriptions
     /// and returns a newtype that ensures initialization.
-    pub(crate) fn new(probe_options: ProbeOptions) -> Result<Self, OperationError> {
+    pub(crate) fn new(mut probe_options: ProbeOptions) -> Result<Self, OperationError> {
+        ProjectConfig::get().probe.apply(&mut probe_options);
+
         let options = Self(probe_options);
         // Load the target description, if given in the cli parameters.
         options.maybe_load_chip_desc()?;
@@ -154,11 +254,22 @@ impl LoadedProbeOptions {
     }
 
     /// Add targets contained in file given by --chip-description-path
-    /// to probe-rs registry.
+    /// to probe-rs registry. If the path is a directory, all target
+    /// description files in it are added.
     ///
     /// Note: should be called before any functions in [ProbeOptions].
     fn maybe_load_chip_desc(&self) -> Result<(), OperationError> {
         if let Some(ref cdp) = self.0.chip_description_path {
+            if cdp.is_dir() {
+                probe_rs::config::add_targets_from_dir(cdp).map_err(|error| {
+                    OperationError::FailedChipDescriptionParsing {
+                        source: error,
+                        path: cdp.clone(),
+                    }
+                })?;
+                return Ok(());
+            }
+
             let file = File::open(Path::new(cdp)).map_err(|error| {
                 OperationError::ChipDescriptionNotFound {
                     source: error,
@@ -234,19 +345,58 @@ impl LoadedProbeOptions {
         selected.and_then(|probe_info| Ok(lister.open(probe_info)?))
     }
 
+    fn wait_for_probe(lister: &Lister, selector: Option<&DebugProbeSelector>) {
+        let found = match selector {
+            Some(selector) => lister.list_all().iter().any(|p| selector.matches_probe(p)),
+            None => !lister.list_all().is_empty(),
+        };
+        if !found {
+            println!("Waiting for a probe to be connected...");
+            lister.wait_for_probe(selector, None);
+        }
+    }
+
     /// Attaches to specified probe and configures it.
     pub fn attach_probe(&self, lister: &Lister) -> Result<Probe, OperationError> {
         let mut probe = if self.0.dry_run {
             Probe::from_specific_probe(Box::new(FakeProbe::with_mocked_core()))
+        } else if let Some(path) = &self.0.replay {
+            Probe::from_specific_probe(Box::new(ReplayProbe::from_file(path)?))
+        } else if let Some(address) = &self.0.remote {
+            Probe::from_specific_probe(Box::new(RemoteProbe::connect(address.as_str())?))
+        } else if let Some(address) = &self.0.bitbang {
+            Probe::from_specific_probe(Box::new(BitbangProbe::connect(
+                address.as_str(),
+                self.0.bitbang_protocol,
+            )?))
+        } else if let Some(pins) = &self.0.gpio {
+            Probe::from_specific_probe(Box::new(GpioProbe::open(pins)?))
         } else {
+            if self.0.wait_for_probe {
+                Self::wait_for_probe(lister, self.0.probe.as_ref());
+            }
+
             // If we got a probe selector as an argument, open the probe
             // matching the selector if possible.
-            match &self.0.probe {
+            let mut probe = match &self.0.probe {
                 Some(selector) => lister.open(selector)?,
                 None => Self::select_probe(lister, self.0.non_interactive)?,
+            };
+
+            if probe.target_unpowered() {
+                tracing::warn!(
+                    "The target voltage is close to 0 V. Make sure the target is powered and the reference voltage pin is connected."
+                );
             }
+
+            probe
         };
 
+        if let Some(path) = &self.0.record {
+            let recording = File::create(path).map_err(OperationError::IOError)?;
+            probe = probe.record(BufWriter::new(recording))?;
+        }
+
         if let Some(protocol) = self.0.protocol {
             // Select protocol and speed
             probe.select_protocol(protocol).map_err(|error| {
@@ -281,6 +431,17 @@ impl LoadedProbeOptions {
             tracing::info!("Protocol speed {} kHz", protocol_speed);
         }
 
+        if self.0.adaptive_clocking {
+            probe
+                .set_adaptive_clocking(true)
+                .map_err(OperationError::FailedToEnableAdaptiveClocking)?;
+            tracing::info!("Using adaptive clocking");
+        }
+
+        if self.0.speed_fallback {
+            probe.set_speed_fallback(Some(SpeedFallback::default()));
+        }
+
         Ok(probe)
     }
 
@@ -309,6 +470,38 @@ impl LoadedProbeOptions {
         Ok(session)
     }
 
+    /// Attaches to the probe and the target, retrying until `--connect-timeout` expires.
+    pub fn attach(
+        &self,
+        lister: &Lister,
+        target: TargetSelector,
+    ) -> Result<Session, OperationError> {
+        const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+        let Some(timeout) = self.0.connect_timeout else {
+            let probe = self.attach_probe(lister)?;
+            return self.attach_session(probe, target);
+        };
+
+        let deadline = Instant::now() + Duration::from_secs(timeout);
+        let mut backoff = Duration::from_millis(100);
+        loop {
+            let result = self
+                .attach_probe(lister)
+                .and_then(|probe| self.attach_session(probe, target.clone()));
+
+            match result {
+                Err(error) if error.is_transient() && Instant::now() < deadline => {
+                    tracing::info!("Failed to connect, retrying: {error}");
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    std::thread::sleep(backoff.min(remaining));
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                result => return result,
+            }
+        }
+    }
+
     pub(crate) fn protocol(&self) -> Option<WireProtocol> {
         self.0.protocol
     }
@@ -476,6 +669,13 @@ pub enum OperationError {
         path: PathBuf,
     },
 
+    #[error("Failed to patch the image '{path}'.")]
+    FailedToPatchImage {
+        #[source]
+        source: PatchError,
+        path: PathBuf,
+    },
+
     #[error("Failed to open the chip description '{path}'.")]
     ChipDescriptionNotFound {
         source: std::io::Error,
@@ -515,6 +715,9 @@ pub enum OperationError {
     #[error("The protocol speed could not be set to '{speed}' kHz.")]
     FailedToSelectProtocolSpeed { source: DebugProbeError, speed: u32 },
 
+    #[error("Adaptive clocking could not be enabled.")]
+    FailedToEnableAdaptiveClocking(#[source] DebugProbeError),
+
     #[error("Connecting to the chip was unsuccessful.")]
     AttachingFailed {
         source: probe_rs::Error,
@@ -539,6 +742,19 @@ pub enum OperationError {
     ParseProbeIndex(#[source] std::num::ParseIntError),
 }
 
+impl OperationError {
+    /// Returns whether retrying could succeed, e.g. because the probe was unplugged or the
+    /// target was not powered.
+    fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            OperationError::NoProbesFound
+                | OperationError::FailedToOpenProbe(_)
+                | OperationError::AttachingFailed { .. }
+        )
+    }
+}
+
 /// Used in errors it can print a list of items.
 fn print_list(list: &[impl std::fmt::Display]) -> String {
     let mut output = String::new();
@@ -558,8 +774,150 @@ impl From<std::io::Error> for OperationError {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use clap::Parser;
+    use probe_rs::{
+        integration::ProbeLister,
+        probe::{DebugProbe, ProbeFactory},
+    };
+
     use super::*;
 
+    #[derive(Debug)]
+    struct MockProbeFactory;
+
+    impl std::fmt::Display for MockProbeFactory {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("Mocked Probe")
+        }
+    }
+
+    impl ProbeFactory for MockProbeFactory {
+        fn open(
+            &self,
+            _selector: &DebugProbeSelector,
+        ) -> Result<Box<dyn DebugProbe>, DebugProbeError> {
+            unimplemented!()
+        }
+
+        fn list_probes(&self) -> Vec<DebugProbeInfo> {
+            unimplemented!()
+        }
+    }
+
+    /// A lister which only finds its probe after it was asked a few times, like a probe which
+    /// is plugged in late.
+    #[derive(Debug)]
+    struct LateLister {
+        polls_until_plugged_in: Cell<usize>,
+        probe: RefCell<Option<FakeProbe>>,
+    }
+
+    impl ProbeLister for LateLister {
+        fn open(&self, _selector: &DebugProbeSelector) -> Result<Probe, DebugProbeError> {
+            let probe =
+                self.probe
+                    .borrow_mut()
+                    .take()
+                    .ok_or(DebugProbeError::ProbeCouldNotBeCreated(
+                        probe_rs::probe::ProbeCreationError::CouldNotOpen,
+                    ))?;
+            Ok(Probe::from_specific_probe(Box::new(probe)))
+        }
+
+        fn list_all(&self) -> Vec<DebugProbeInfo> {
+            let polls = self.polls_until_plugged_in.get();
+            if polls > 0 {
+                self.polls_until_plugged_in.set(polls - 1);
+                return vec![];
+            }
+
+            vec![DebugProbeInfo::new(
+                "Mock probe",
+                0x12,
+                0x23,
+                None,
+                &MockProbeFactory,
+                None,
+            )]
+        }
+    }
+
+    fn late_lister(polls_until_plugged_in: usize) -> Lister {
+        Lister::with_lister(Box::new(LateLister {
+            polls_until_plugged_in: Cell::new(polls_until_plugged_in),
+            probe: RefCell::new(Some(FakeProbe::with_mocked_core())),
+        }))
+    }
+
+    #[test]
+    fn attach_retries_until_connect_timeout() {
+        let options = ProbeOptions::parse_from([
+            "probe-rs",
+            "--chip",
+            "nrf51822_xxAC",
+            "--connect-timeout",
+            "10",
+        ])
+        .load()
+        .unwrap();
+        let target = options.get_target_selector().unwrap();
+
+        options.attach(&late_lister(2), target).unwrap();
+    }
+
+    #[test]
+    fn attach_gives_up_after_connect_timeout() {
+        let options = ProbeOptions::parse_from([
+            "probe-rs",
+            "--chip",
+            "nrf51822_xxAC",
+            "--connect-timeout",
+            "1",
+        ])
+        .load()
+        .unwrap();
+        let target = options.get_target_selector().unwrap();
+
+        let error = options
+            .attach(&late_lister(usize::MAX), target)
+            .unwrap_err();
+        assert!(matches!(error, OperationError::NoProbesFound));
+    }
+
+    #[test]
+    fn adaptive_clocking_conflicts_with_speed() {
+        ProbeOptions::try_parse_from(["probe-rs", "--adaptive-clocking", "--speed", "1000"])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn adaptive_clocking_needs_probe_support() {
+        let options = ProbeOptions::parse_from(["probe-rs", "--dry-run", "--adaptive-clocking"])
+            .load()
+            .unwrap();
+
+        let error = options.attach_probe(&Lister::new()).unwrap_err();
+        assert!(matches!(
+            error,
+            OperationError::FailedToEnableAdaptiveClocking(
+                DebugProbeError::CommandNotSupportedByProbe { .. }
+            )
+        ));
+    }
+
+    #[test]
+    fn attach_fails_without_connect_timeout() {
+        let options = ProbeOptions::parse_from(["probe-rs", "--chip", "nrf51822_xxAC"])
+            .load()
+            .unwrap();
+        let target = options.get_target_selector().unwrap();
+
+        let error = options.attach(&late_lister(2), target).unwrap_err();
+        assert!(matches!(error, OperationError::NoProbesFound));
+    }
+
     #[test]
     fn to_cargo_options() {
         assert_eq!(
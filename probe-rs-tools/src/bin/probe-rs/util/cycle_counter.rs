@@ -0,0 +1,118 @@
+//! The cycle counters of cores, to measure the time the firmware takes exactly.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use probe_rs::{Core, CoreType, MemoryInterface, RegisterId};
+
+/// The Debug Exception and Monitor Control Register of Cortex-M cores.
+const DEMCR: u64 = 0xE000_EDFC;
+/// Enables the DWT and ITM units.
+const DEMCR_TRCENA: u32 = 1 << 24;
+/// The control register of the DWT.
+const DWT_CTRL: u64 = 0xE000_1000;
+/// Set if the DWT has no cycle counter.
+const DWT_CTRL_NOCYCCNT: u32 = 1 << 25;
+/// Enables the cycle counter of the DWT.
+const DWT_CTRL_CYCCNTENA: u32 = 1;
+/// The cycle counter of the DWT.
+const DWT_CYCCNT: u64 = 0xE000_1004;
+/// The `mcycle` CSR of RISC-V cores.
+const MCYCLE: RegisterId = RegisterId(0xB00);
+
+/// The cycle counter of a core.
+#[derive(Clone, Copy)]
+pub enum CycleCounter {
+    /// The `CYCCNT` register of the DWT of a Cortex-M core.
+    Dwt,
+    /// The `mcycle` CSR of a RISC-V core.
+    Mcycle,
+}
+
+impl CycleCounter {
+    /// Enables the cycle counter of the core.
+    pub fn enable(core: &mut Core) -> anyhow::Result<Self> {
+        match core.core_type() {
+            CoreType::Armv7m | CoreType::Armv7em | CoreType::Armv8m => {
+                let demcr = core.read_word_32(DEMCR)?;
+                core.write_word_32(DEMCR, demcr | DEMCR_TRCENA)?;
+
+                let ctrl = core.read_word_32(DWT_CTRL)?;
+                if ctrl & DWT_CTRL_NOCYCCNT != 0 {
+                    bail!("The DWT of the core has no cycle counter.");
+                }
+                core.write_word_32(DWT_CTRL, ctrl | DWT_CTRL_CYCCNTENA)?;
+
+                Ok(Self::Dwt)
+            }
+            CoreType::Riscv => Ok(Self::Mcycle),
+            core_type => bail!("Cores of type {core_type:?} have no supported cycle counter."),
+        }
+    }
+
+    /// Reads the 32-bit cycle count.
+    pub fn read(self, core: &mut Core) -> anyhow::Result<u32> {
+        let cycles = match self {
+            Self::Dwt => core.read_word_32(DWT_CYCCNT)?,
+            Self::Mcycle => core.read_core_reg(MCYCLE)?,
+        };
+
+        Ok(cycles)
+    }
+
+    /// Measures the frequency of the cycle counter, which is the core clock, over `duration`.
+    ///
+    /// The core has to be running, and the result is too low if it sleeps in the meantime.
+    pub fn measure_frequency(self, core: &mut Core, duration: Duration) -> anyhow::Result<u32> {
+        let start_cycles = self.read(core)?;
+        let start = Instant::now();
+        thread::sleep(duration);
+        let cycles = self.read(core)?.wrapping_sub(start_cycles);
+        let elapsed = start.elapsed();
+
+        if cycles == 0 {
+            bail!("The cycle counter did not count, the core is halted or sleeping.");
+        }
+
+        Ok((f64::from(cycles) / elapsed.as_secs_f64()) as u32)
+    }
+}
+
+/// Measures the core clock with the cycle counter, e.g. to derive the SWO prescaler from it.
+///
+/// The core is started, if it is halted, and given a moment to configure its clocks.
+pub fn measure_core_clock(core: &mut Core) -> anyhow::Result<u32> {
+    let counter = CycleCounter::enable(core)?;
+    if core.core_halted()? {
+        core.run()?;
+    }
+    thread::sleep(Duration::from_millis(500));
+
+    let clock = counter.measure_frequency(core, Duration::from_millis(200))?;
+    tracing::info!("Measured a core clock of {clock} Hz for SWO");
+    Ok(clock)
+}
+
+#[cfg(test)]
+mod test {
+    use probe_rs::integration::FakeProbe;
+
+    use super::*;
+
+    #[test]
+    fn dwt_cycle_counter_is_enabled() {
+        let mut session = FakeProbe::attach_mocked_core("STM32F407VGTx").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        assert!(matches!(
+            CycleCounter::enable(&mut core).unwrap(),
+            CycleCounter::Dwt
+        ));
+        assert_ne!(core.read_word_32(DEMCR).unwrap() & DEMCR_TRCENA, 0);
+        assert_ne!(core.read_word_32(DWT_CTRL).unwrap() & DWT_CTRL_CYCCNTENA, 0);
+
+        core.write_word_32(DWT_CTRL, DWT_CTRL_NOCYCCNT).unwrap();
+        assert!(CycleCounter::enable(&mut core).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+//! Running a command on several devices at once.
+//!
+//! Products with several MCUs, and small production batches, need the same firmware flashed to
+//! many devices. Instead of starting probe-rs once per device, `run` and `download` accept
+//! several `--device <CHIP>@<PROBE>` options, or a manifest listing the devices:
+//!
+//! ```toml
+//! [[device]]
+//! name = "application core"
+//! chip = "nRF5340_xxAA"
+//! probe = "1366:1051:001050012345"
+//!
+//! [[device]]
+//! chip = "nRF52840_xxAA"
+//! probe = "1366:1015:000683201234"
+//! ```
+
+use std::{io::Write, path::PathBuf, str::FromStr};
+
+use anyhow::Context;
+use figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use probe_rs::probe::DebugProbeSelector;
+use serde::Deserialize;
+
+use super::common_options::ProbeOptions;
+
+/// A device, consisting of a chip and the probe it is connected to.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Device {
+    /// The name used to prefix the output of the device. Defaults to the probe selector.
+    pub name: Option<String>,
+    pub chip: String,
+    pub probe: DebugProbeSelector,
+}
+
+impl Device {
+    /// The name used to prefix the output of the device.
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.probe.to_string(),
+        }
+    }
+}
+
+impl FromStr for Device {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (chip, probe) = s
+            .split_once('@')
+            .context("Devices have to be given as <CHIP>@<PROBE>")?;
+
+        Ok(Self {
+            name: None,
+            chip: chip.to_string(),
+            probe: probe.parse()?,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    device: Vec<Device>,
+}
+
+/// Options to select several devices.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct DeviceOptions {
+    /// Use the given device, e.g. `nRF52840_xxAA@1366:1015:000683201234`. Can be given several
+    /// times, to work with the devices concurrently.
+    #[arg(
+        long = "device",
+        value_name = "CHIP@PROBE",
+        conflicts_with_all = ["chip", "probe"],
+        help_heading = "MULTIPLE DEVICES"
+    )]
+    pub devices: Vec<Device>,
+
+    /// Read the devices from a TOML file with `[[device]]` entries, which have a `chip`, a
+    /// `probe` and optionally a `name`.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["chip", "probe", "devices"],
+        help_heading = "MULTIPLE DEVICES"
+    )]
+    pub device_manifest: Option<PathBuf>,
+}
+
+impl DeviceOptions {
+    /// Returns the selected devices, or an empty list if none was selected.
+    pub fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        let Some(path) = &self.device_manifest else {
+            return Ok(self.devices.clone());
+        };
+
+        let manifest: Manifest = Figment::from(Toml::file_exact(path))
+            .extract()
+            .with_context(|| format!("Failed to read the device manifest {}", path.display()))?;
+
+        Ok(manifest.device)
+    }
+}
+
+/// Runs `f` concurrently for every device, with `probe_options` selecting the device.
+///
+/// The second argument of `f` is the label of the device. Returns an error if any of the
+/// devices failed, after all of them have finished.
+pub fn for_each_device<F>(
+    devices: &[Device],
+    probe_options: &ProbeOptions,
+    f: F,
+) -> anyhow::Result<()>
+where
+    F: Fn(ProbeOptions, &str) -> anyhow::Result<()> + Sync,
+{
+    let results = std::thread::scope(|scope| {
+        let threads: Vec<_> = devices
+            .iter()
+            .map(|device| {
+                let mut probe_options = probe_options.clone();
+                probe_options.chip = Some(device.chip.clone());
+                probe_options.probe = Some(device.probe.clone());
+
+                let f = &f;
+                scope.spawn(move || f(probe_options, &device.label()))
+            })
+            .collect();
+
+        threads
+            .into_iter()
+            .map(|thread| match thread.join() {
+                Ok(result) => result,
+                Err(_) => Err(anyhow::anyhow!("The thread panicked")),
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut failed = 0;
+    for (device, result) in devices.iter().zip(results) {
+        match result {
+            Ok(()) => eprintln!("[{}] Done", device.label()),
+            Err(error) => {
+                failed += 1;
+                eprintln!("[{}] Failed: {error:#}", device.label());
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} devices failed", devices.len());
+    }
+
+    Ok(())
+}
+
+/// Prefixes every line written to the inner writer, to tell apart the output of devices.
+///
+/// Only complete lines are written, each with a single write, so lines of different devices
+/// sharing e.g. stdout are not mixed up.
+pub struct PrefixedWriter<W: Write> {
+    inner: W,
+    prefix: String,
+    line: Vec<u8>,
+}
+
+impl<W: Write> PrefixedWriter<W> {
+    pub fn new(inner: W, label: &str) -> Self {
+        let prefix = format!("[{label}] ");
+        Self {
+            inner,
+            line: prefix.clone().into_bytes(),
+            prefix,
+        }
+    }
+
+    fn write_line(&mut self) -> std::io::Result<()> {
+        self.inner.write_all(&self.line)?;
+        self.line.clear();
+        self.line.extend_from_slice(self.prefix.as_bytes());
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for PrefixedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for line in buf.split_inclusive(|&byte| byte == b'\n') {
+            self.line.extend_from_slice(line);
+            if line.ends_with(b"\n") {
+                self.write_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for PrefixedWriter<W> {
+    fn drop(&mut self) {
+        if self.line.len() > self.prefix.len() {
+            self.line.push(b'\n');
+            let _ = self.write_line();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_devices() {
+        let device: Device = "nRF52840_xxAA@1366:1015:000683201234".parse().unwrap();
+        assert_eq!(device.chip, "nRF52840_xxAA");
+        assert_eq!(device.label(), "1366:1015:000683201234");
+
+        let manifest: Manifest = Figment::from(Toml::string(
+            r#"
+            [[device]]
+            name = "app"
+            chip = "nRF5340_xxAA"
+            probe = "1366:1051"
+            "#,
+        ))
+        .extract()
+        .unwrap();
+        assert_eq!(manifest.device[0].label(), "app");
+
+        assert!("nRF52840_xxAA".parse::<Device>().is_err());
+    }
+
+    #[test]
+    fn output_is_prefixed_per_line() {
+        let mut output = Vec::new();
+
+        let mut writer = PrefixedWriter::new(&mut output, "a");
+        writer.write_all(b"one\ntw").unwrap();
+        writer.write_all(b"o\nthree").unwrap();
+        drop(writer);
+
+        assert_eq!(output, b"[a] one\n[a] two\n[a] three\n");
+    }
+}
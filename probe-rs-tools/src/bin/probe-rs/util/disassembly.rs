@@ -0,0 +1,48 @@
+use capstone::{
+    arch::arm::ArchMode as armArchMode, arch::arm64::ArchMode as aarch64ArchMode,
+    arch::riscv::ArchMode as riscvArchMode, prelude::*, Endian,
+};
+use probe_rs::InstructionSet;
+
+/// Creates a disassembler matching the given instruction set.
+///
+/// Returns [`capstone::Error::UnsupportedArch`] for instruction sets capstone can't handle.
+pub fn disassembler_for(instruction_set: InstructionSet) -> Result<Capstone, capstone::Error> {
+    match instruction_set {
+        InstructionSet::Thumb2 => Capstone::new()
+            .arm()
+            .mode(armArchMode::Thumb)
+            .endian(Endian::Little)
+            .build(),
+        InstructionSet::A32 => {
+            // We need to inspect the CPSR to determine what mode this is opearting in
+            Capstone::new()
+                .arm()
+                .mode(armArchMode::Arm)
+                .endian(Endian::Little)
+                .build()
+        }
+        InstructionSet::A64 => {
+            // We need to inspect the CPSR to determine what mode this is opearting in
+            Capstone::new()
+                .arm64()
+                .mode(aarch64ArchMode::Arm)
+                .endian(Endian::Little)
+                .build()
+        }
+        InstructionSet::RV32 => Capstone::new()
+            .riscv()
+            .mode(riscvArchMode::RiscV32)
+            .endian(Endian::Little)
+            .build(),
+        InstructionSet::RV32C => Capstone::new()
+            .riscv()
+            .mode(riscvArchMode::RiscV32)
+            .endian(Endian::Little)
+            .extra_mode(std::iter::once(
+                capstone::arch::riscv::ArchExtraMode::RiscVC,
+            ))
+            .build(),
+        InstructionSet::Xtensa => Err(capstone::Error::UnsupportedArch),
+    }
+}
@@ -0,0 +1,345 @@
+//! Classification of the errors of the CLI.
+//!
+//! Errors are assigned a stable code and a category, based on the most specific known error in
+//! their cause chain, so users and IDEs can react to them without parsing messages.
+
+use std::{error::Error, io::ErrorKind};
+
+use colored::Colorize;
+use probe_rs::{
+    architecture::arm::{ArmError, DapError},
+    config::RegistryError,
+    flashing::FlashError,
+    probe::{DebugProbeError, ProbeCreationError},
+};
+use serde::Serialize;
+
+use crate::util::common_options::OperationError;
+
+/// The broad category of a failure, which tells where to look for the problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCategory {
+    /// The probe could not be found or accessed by the host.
+    ProbeAccess,
+    /// The probe could not communicate with the target.
+    WireLevel,
+    /// The target refuses debug access until it is unlocked.
+    TargetLocked,
+    /// The chip is unknown, or doesn't match the firmware.
+    WrongChip,
+    /// Flashing failed while the flash algorithm was running.
+    FlashAlgorithm,
+    /// Anything else.
+    Other,
+}
+
+/// A specific failure, with a code which doesn't change between releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    NoProbeFound,
+    MultipleProbesFound,
+    ProbeAccessDenied,
+    ProbeOpenFailed,
+    ProbeFirmwareOutdated,
+    TargetNotFound,
+    WireProtocol,
+    Timeout,
+    TargetLocked,
+    ChipNotFound,
+    ChipAutodetectFailed,
+    NoSuitableNvm,
+    FlashAlgorithmFailed,
+    EraseFailed,
+    VerifyFailed,
+    Other,
+}
+
+impl ErrorCode {
+    /// The stable code of the error, e.g. `PRS0101`.
+    ///
+    /// The hundreds identify the category. Codes are never reused for a different error.
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorCode::NoProbeFound => "PRS0101",
+            ErrorCode::MultipleProbesFound => "PRS0102",
+            ErrorCode::ProbeAccessDenied => "PRS0103",
+            ErrorCode::ProbeOpenFailed => "PRS0104",
+            ErrorCode::ProbeFirmwareOutdated => "PRS0105",
+            ErrorCode::TargetNotFound => "PRS0201",
+            ErrorCode::WireProtocol => "PRS0202",
+            ErrorCode::Timeout => "PRS0203",
+            ErrorCode::TargetLocked => "PRS0301",
+            ErrorCode::ChipNotFound => "PRS0401",
+            ErrorCode::ChipAutodetectFailed => "PRS0402",
+            ErrorCode::NoSuitableNvm => "PRS0403",
+            ErrorCode::FlashAlgorithmFailed => "PRS0501",
+            ErrorCode::EraseFailed => "PRS0502",
+            ErrorCode::VerifyFailed => "PRS0503",
+            ErrorCode::Other => "PRS0000",
+        }
+    }
+
+    pub fn category(self) -> ErrorCategory {
+        match self {
+            ErrorCode::NoProbeFound
+            | ErrorCode::MultipleProbesFound
+            | ErrorCode::ProbeAccessDenied
+            | ErrorCode::ProbeOpenFailed
+            | ErrorCode::ProbeFirmwareOutdated => ErrorCategory::ProbeAccess,
+            ErrorCode::TargetNotFound | ErrorCode::WireProtocol | ErrorCode::Timeout => {
+                ErrorCategory::WireLevel
+            }
+            ErrorCode::TargetLocked => ErrorCategory::TargetLocked,
+            ErrorCode::ChipNotFound
+            | ErrorCode::ChipAutodetectFailed
+            | ErrorCode::NoSuitableNvm => ErrorCategory::WrongChip,
+            ErrorCode::FlashAlgorithmFailed | ErrorCode::EraseFailed | ErrorCode::VerifyFailed => {
+                ErrorCategory::FlashAlgorithm
+            }
+            ErrorCode::Other => ErrorCategory::Other,
+        }
+    }
+
+    /// What the user can do about the error.
+    fn hints(self) -> &'static [&'static str] {
+        match self {
+            ErrorCode::NoProbeFound => &[
+                "Check the USB cable, and run `probe-rs doctor` to check the probe setup.",
+                "For a guide on how to set up your probes, see https://probe.rs/docs/getting-started/probe-setup",
+            ],
+            ErrorCode::MultipleProbesFound => {
+                &["You can select a probe with the `--probe` argument. See `--help` for how to use it."]
+            }
+            ErrorCode::ProbeAccessDenied => &[
+                "If you are on Linux, you most likely need to install the udev rules for your probe, see https://probe.rs/docs/getting-started/probe-setup/#udev-rules",
+                "If you are on Windows, make sure the probe uses the WinUSB driver, e.g. installed with https://zadig.akeo.ie/",
+            ],
+            ErrorCode::ProbeOpenFailed => &[
+                "Make sure no other program uses the probe, and reconnect it.",
+                "Run `probe-rs doctor` to check the probe setup.",
+            ],
+            ErrorCode::ProbeFirmwareOutdated => {
+                &["Update the firmware of the probe with the updater of its vendor."]
+            }
+            ErrorCode::TargetNotFound | ErrorCode::WireProtocol => &[
+                "Check the wiring between probe and target, and that the target is powered.",
+                "A lower speed, e.g. `--speed 100`, helps with long cables.",
+                "A hard reset during attaching might help, run with `--connect-under-reset`.",
+            ],
+            ErrorCode::Timeout => &[
+                "The target might be in a low power mode, or the speed might be too high. Try `--connect-under-reset`, or a lower `--speed`.",
+            ],
+            ErrorCode::TargetLocked => &[
+                "The chip has to be erased completely to unlock it. Run with `--allow-erase-all` to allow this.",
+            ],
+            ErrorCode::ChipNotFound => &[
+                "Did you spell the name of your chip correctly? Capitalization does not matter.",
+                "You can list all the available chips by running `probe-rs chip list`.",
+            ],
+            ErrorCode::ChipAutodetectFailed => &[
+                "Try specifying your chip with the `--chip` argument.",
+                "You can list all the available chips by running `probe-rs chip list`.",
+            ],
+            ErrorCode::NoSuitableNvm => &[
+                "Make sure the flash region specified in the linker script matches the one of the selected chip.",
+            ],
+            ErrorCode::FlashAlgorithmFailed => &[
+                "Make sure the right chip is selected, as the flash algorithm depends on it.",
+                "Try `--connect-under-reset`, so the firmware can't interfere with flashing.",
+            ],
+            ErrorCode::EraseFailed => &[
+                "Perhaps your chip has write protected sectors that need to be cleared?",
+            ],
+            ErrorCode::VerifyFailed => &[
+                "Make sure the right chip is selected, and that the flash is not write protected.",
+            ],
+            ErrorCode::Other => &[],
+        }
+    }
+}
+
+/// A classified error, which can be printed for humans or serialized for programs.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub code: &'static str,
+    pub category: ErrorCategory,
+    pub message: String,
+    /// The messages of the cause chain, from the outermost to the innermost cause.
+    pub causes: Vec<String>,
+    pub hints: Vec<&'static str>,
+}
+
+impl ErrorReport {
+    pub fn new(error: &anyhow::Error) -> Self {
+        let code = classify(error);
+
+        Self {
+            code: code.code(),
+            category: code.category(),
+            message: error.to_string(),
+            causes: error
+                .chain()
+                .skip(1)
+                .map(|cause| cause.to_string())
+                .collect(),
+            hints: code.hints().to_vec(),
+        }
+    }
+
+    /// Prints the error, its causes and the hints to stderr.
+    pub fn print(&self) {
+        eprintln!(
+            "{} {}",
+            format!("Error[{}]:", self.code).red().bold(),
+            self.message
+        );
+
+        if !self.causes.is_empty() {
+            eprintln!();
+            eprintln!("{}", "Caused by:".bold());
+            for (i, cause) in self.causes.iter().enumerate() {
+                eprintln!("    {i}: {cause}");
+            }
+        }
+
+        for hint in &self.hints {
+            eprintln!();
+            eprintln!("{} {hint}", "Hint:".blue().bold());
+        }
+    }
+}
+
+/// Returns the code of the innermost error in the chain which is known.
+fn classify(error: &anyhow::Error) -> ErrorCode {
+    error
+        .chain()
+        .filter_map(classify_cause)
+        .last()
+        .unwrap_or(ErrorCode::Other)
+}
+
+fn classify_cause(cause: &(dyn Error + 'static)) -> Option<ErrorCode> {
+    if let Some(error) = cause.downcast_ref::<OperationError>() {
+        return match error {
+            OperationError::NoProbesFound => Some(ErrorCode::NoProbeFound),
+            OperationError::MultipleProbesFound { .. } => Some(ErrorCode::MultipleProbesFound),
+            OperationError::FailedToOpenProbe(_) => Some(ErrorCode::ProbeOpenFailed),
+            OperationError::ChipNotFound { .. } => Some(ErrorCode::ChipNotFound),
+            OperationError::FailedToSelectProtocol { .. }
+            | OperationError::FailedToSelectProtocolSpeed { .. } => Some(ErrorCode::WireProtocol),
+            OperationError::AttachingFailed { .. } => Some(ErrorCode::TargetNotFound),
+            OperationError::FlashingFailed { .. } => Some(ErrorCode::FlashAlgorithmFailed),
+            _ => None,
+        };
+    }
+
+    if let Some(error) = cause.downcast_ref::<DebugProbeError>() {
+        return match error {
+            DebugProbeError::Usb(error) if error.kind() == ErrorKind::PermissionDenied => {
+                Some(ErrorCode::ProbeAccessDenied)
+            }
+            DebugProbeError::ProbeCouldNotBeCreated(_) => Some(ErrorCode::ProbeOpenFailed),
+            DebugProbeError::ProbeFirmwareOutdated => Some(ErrorCode::ProbeFirmwareOutdated),
+            DebugProbeError::TargetNotFound => Some(ErrorCode::TargetNotFound),
+            DebugProbeError::Timeout => Some(ErrorCode::Timeout),
+            _ => None,
+        };
+    }
+
+    if let Some(error) = cause.downcast_ref::<ProbeCreationError>() {
+        return match error {
+            ProbeCreationError::Usb(error) if error.kind() == ErrorKind::PermissionDenied => {
+                Some(ErrorCode::ProbeAccessDenied)
+            }
+            ProbeCreationError::CouldNotOpen => Some(ErrorCode::ProbeAccessDenied),
+            _ => Some(ErrorCode::ProbeOpenFailed),
+        };
+    }
+
+    if let Some(error) = cause.downcast_ref::<probe_rs::Error>() {
+        return match error {
+            probe_rs::Error::MissingPermissions(_) => Some(ErrorCode::TargetLocked),
+            probe_rs::Error::Timeout => Some(ErrorCode::Timeout),
+            _ => None,
+        };
+    }
+
+    if let Some(error) = cause.downcast_ref::<ArmError>() {
+        return match error {
+            ArmError::MissingPermissions(_) => Some(ErrorCode::TargetLocked),
+            ArmError::Timeout => Some(ErrorCode::Timeout),
+            _ => None,
+        };
+    }
+
+    if cause.is::<DapError>() {
+        return Some(ErrorCode::WireProtocol);
+    }
+
+    if let Some(error) = cause.downcast_ref::<RegistryError>() {
+        return match error {
+            RegistryError::ChipNotFound(..) => Some(ErrorCode::ChipNotFound),
+            RegistryError::ChipAutodetectFailed => Some(ErrorCode::ChipAutodetectFailed),
+            _ => None,
+        };
+    }
+
+    if let Some(error) = cause.downcast_ref::<FlashError>() {
+        return match error {
+            FlashError::NoSuitableNvm { .. } => Some(ErrorCode::NoSuitableNvm),
+            FlashError::EraseFailed { .. } | FlashError::ChipEraseFailed { .. } => {
+                Some(ErrorCode::EraseFailed)
+            }
+            FlashError::Verify => Some(ErrorCode::VerifyFailed),
+            FlashError::Init(_)
+            | FlashError::Uninit(_)
+            | FlashError::RoutineCallFailed { .. }
+            | FlashError::PageWrite { .. }
+            | FlashError::StackOverflowDetected { .. } => Some(ErrorCode::FlashAlgorithmFailed),
+            _ => None,
+        };
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn innermost_known_error_wins() {
+        let error = anyhow::Error::from(OperationError::AttachingFailed {
+            source: probe_rs::Error::Arm(ArmError::MissingPermissions("locked".to_string())),
+            connect_under_reset: false,
+        });
+
+        let report = ErrorReport::new(&error);
+
+        assert_eq!(report.code, "PRS0301");
+        assert_eq!(report.category, ErrorCategory::TargetLocked);
+        assert_eq!(report.causes.len(), 2);
+    }
+
+    #[test]
+    fn permission_denied_is_probe_access() {
+        let error = anyhow::Error::from(OperationError::FailedToOpenProbe(
+            DebugProbeError::ProbeCouldNotBeCreated(ProbeCreationError::Usb(std::io::Error::from(
+                ErrorKind::PermissionDenied,
+            ))),
+        ));
+
+        assert_eq!(classify(&error), ErrorCode::ProbeAccessDenied);
+    }
+
+    #[test]
+    fn unknown_errors_are_other() {
+        let error = anyhow::anyhow!("something went wrong");
+
+        let report = ErrorReport::new(&error);
+
+        assert_eq!(report.code, "PRS0000");
+        assert!(report.hints.is_empty());
+    }
+}
@@ -2,19 +2,106 @@ use crate::FormatOptions;
 
 use super::common_options::{BinaryDownloadOptions, LoadedProbeOptions, OperationError};
 use super::logging;
+use super::project_config::ProjectConfig;
 
 use std::cell::RefCell;
+use std::sync::{atomic::AtomicBool, Arc, Mutex, PoisonError};
 use std::time::Duration;
-use std::{path::Path, time::Instant};
+use std::{fs::File, path::Path, time::Instant};
 
 use colored::Colorize;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use probe_rs::flashing::FlashLayout;
+use probe_rs::vendor::nxp::boot_header::{check_boot_header, FlexspiBootLayout};
 use probe_rs::InstructionSet;
 use probe_rs::{
     flashing::{DownloadOptions, FileDownloadError, FlashLoader, FlashProgress, ProgressEvent},
-    Session,
+    CancellationToken, Session,
 };
+use signal_hook::{consts::signal, SigId};
+
+/// Cancels flash operations when Ctrl-C is pressed, until it is dropped.
+///
+/// Flashing then stops after the current sector or page, which leaves the flash algorithm in a
+/// consistent state. A second Ctrl-C terminates the process immediately.
+pub struct CtrlCCancellation {
+    token: CancellationToken,
+    signal_ids: [SigId; 2],
+}
+
+impl CtrlCCancellation {
+    pub fn register() -> std::io::Result<Self> {
+        let mut state = ctrl_c_state();
+        // Ctrl-C cancels the operation now, instead of terminating the process.
+        if let Some(id) = state.default_action.take() {
+            signal_hook::low_level::unregister(id);
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        // The conditional shutdown has to be registered first, so it only triggers on the
+        // second Ctrl-C.
+        let shutdown = signal_hook::flag::register_conditional_shutdown(
+            signal::SIGINT,
+            130,
+            cancelled.clone(),
+        )?;
+        let cancel = signal_hook::flag::register(signal::SIGINT, cancelled.clone())?;
+        state.active += 1;
+
+        Ok(Self {
+            token: cancelled.into(),
+            signal_ids: [shutdown, cancel],
+        })
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for CtrlCCancellation {
+    fn drop(&mut self) {
+        for id in self.signal_ids {
+            signal_hook::low_level::unregister(id);
+        }
+
+        let mut state = ctrl_c_state();
+        state.active -= 1;
+        if state.active == 0 && state.default_action.is_none() {
+            // Unregistering doesn't restore the default handler, so Ctrl-C would be ignored.
+            state.default_action = signal_hook::flag::register_conditional_default(
+                signal::SIGINT,
+                Arc::new(AtomicBool::new(true)),
+            )
+            .ok();
+        }
+    }
+}
+
+/// How Ctrl-C is handled between cancellable operations.
+struct CtrlCState {
+    /// The number of registered [`CtrlCCancellation`]s.
+    active: usize,
+    /// The action terminating the process on Ctrl-C once no operation is cancellable anymore.
+    default_action: Option<SigId>,
+}
+
+static CTRL_C_STATE: Mutex<CtrlCState> = Mutex::new(CtrlCState {
+    active: 0,
+    default_action: None,
+});
+
+fn ctrl_c_state() -> std::sync::MutexGuard<'static, CtrlCState> {
+    CTRL_C_STATE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Stops Ctrl-C from terminating the process after a cancellable operation, for callers which
+/// handle it themselves.
+pub fn take_over_ctrl_c() {
+    if let Some(id) = ctrl_c_state().default_action.take() {
+        signal_hook::low_level::unregister(id);
+    }
+}
 
 /// Performs the flash download with the given loader. Ensure that the loader has the data to load already stored.
 /// This function also manages the update and display of progress bars.
@@ -23,15 +110,33 @@ pub fn run_flash_download(
     path: impl AsRef<Path>,
     download_options: &BinaryDownloadOptions,
     probe_options: &LoadedProbeOptions,
-    loader: FlashLoader,
+    mut loader: FlashLoader,
     do_chip_erase: bool,
 ) -> Result<(), OperationError> {
+    let flashing = &ProjectConfig::get().flashing;
+
+    let patches = &download_options.patches;
+    patches
+        .apply(&mut loader, path.as_ref(), patches.patch_row)
+        .map_err(|source| OperationError::FailedToPatchImage {
+            source,
+            path: path.as_ref().to_path_buf(),
+        })?;
+
+    // A flashless i.MX RT only boots images with boot headers, which is easy to miss.
+    if let Some(layout) = FlexspiBootLayout::for_chip(&session.target().name) {
+        if let Err(error) = check_boot_header(&layout, &loader) {
+            tracing::warn!("{error}");
+        }
+    }
+
     let mut options = DownloadOptions::default();
-    options.keep_unwritten_bytes = download_options.restore_unwritten;
+    options.keep_unwritten_bytes = download_options.restore_unwritten || flashing.restore_unwritten;
     options.dry_run = probe_options.dry_run();
-    options.do_chip_erase = do_chip_erase;
-    options.disable_double_buffering = download_options.disable_double_buffering;
-    options.verify = download_options.verify;
+    options.do_chip_erase = do_chip_erase || flashing.chip_erase;
+    options.disable_double_buffering =
+        download_options.disable_double_buffering || flashing.disable_double_buffering;
+    options.verify = download_options.verify || flashing.verify;
 
     if !download_options.disable_progressbars {
         // Create progress bars.
@@ -120,13 +225,20 @@ pub fn run_flash_download(
                 ProgressEvent::FinishedProgramming => progress_bars.program.finish(),
                 ProgressEvent::FailedFilling => progress_bars.fill.abandon(),
                 ProgressEvent::FinishedFilling => progress_bars.fill.finish(),
-                ProgressEvent::DiagnosticMessage { .. } => {}
+                ProgressEvent::DiagnosticMessage { .. }
+                | ProgressEvent::AlgorithmCalled { .. }
+                | ProgressEvent::AlgorithmReturned { .. } => {}
             }
         });
 
         options.progress = Some(progress);
     }
 
+    let ctrl_c = CtrlCCancellation::register()
+        .inspect_err(|error| tracing::warn!("Flashing can't be cancelled with Ctrl-C: {error}"))
+        .ok();
+    options.cancellation = ctrl_c.as_ref().map(CtrlCCancellation::token);
+
     // Start timer.
     let flash_timer = Instant::now();
 
@@ -148,6 +260,10 @@ pub fn run_flash_download(
         flash_timer.elapsed().as_secs_f32(),
     ));
 
+    if download_options.metrics {
+        logging::eprintln(session.metrics().to_string());
+    }
+
     Ok(())
 }
 
@@ -165,6 +281,21 @@ pub fn build_loader(
     probe_rs::flashing::build_loader(session, path, format, image_instruction_set)
 }
 
+/// Adds another image to a loader, e.g. the firmware of the second core of a dual-core chip.
+pub fn add_image(
+    session: &mut Session,
+    loader: &mut FlashLoader,
+    path: impl AsRef<Path>,
+    format_options: FormatOptions,
+) -> Result<(), FileDownloadError> {
+    let format = format_options.into_format(session.target());
+    let mut file = File::open(path).map_err(FileDownloadError::IO)?;
+
+    loader.load_image(session, &mut file, format, None)?;
+
+    Ok(())
+}
+
 struct ProgressBars {
     erase: ProgressBarGroup,
     fill: ProgressBarGroup,
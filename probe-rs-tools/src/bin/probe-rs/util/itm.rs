@@ -0,0 +1,412 @@
+//! A streaming decoder for the ITM packets received over SWO.
+//!
+//! Unlike [`itm::Decoder`], which reads from a blocking source, this decoder is fed with the
+//! chunks of data returned by polling the probe, and only returns complete packets.
+//!
+//! When the SWO clock or baud rate is slightly off, single bytes are received wrong, and the
+//! rest of the stream is misinterpreted. The decoder detects packets which can't be valid, and
+//! then skips the data up to the next synchronization packet, which the ITM sends periodically.
+//!
+//! [`ItmLog`] prints the log output which the firmware writes to ITM stimulus port 0, either as
+//! plain text, as written by `iprintln!`, or as a defmt stream, as written by `defmt-itm`.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use time::UtcOffset;
+
+use crate::util::rtt::{ChannelDataCallbacks, ChannelDataFormat, DefmtState, RttChannelConfig};
+
+/// The number of zero bytes which, followed by `0x80`, form a synchronization packet.
+const SYNC_ZEROS: usize = 5;
+
+/// A synchronization packet.
+const SYNC_PACKET: [u8; SYNC_ZEROS + 1] = [0, 0, 0, 0, 0, 0x80];
+
+/// The maximum length of a timestamp or extension packet, including the header.
+const MAX_CONTINUATION_LEN: usize = 7;
+
+/// The stimulus port used for the log output.
+const LOG_PORT: u8 = 0;
+
+/// An ITM packet, as far as probe-rs cares about it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItmPacket {
+    /// The data written by the software to a stimulus port.
+    Instrumentation { port: u8, payload: Vec<u8> },
+    /// The ITM dropped packets because its FIFO was full.
+    Overflow,
+    /// A synchronization packet.
+    Sync,
+    /// Any other packet, e.g. a timestamp or a hardware source packet of the DWT.
+    Other,
+    /// The stream is corrupt, the data up to the next synchronization packet is skipped.
+    Malformed,
+}
+
+/// Splits the ITM byte stream into packets.
+#[derive(Default)]
+pub struct ItmDecoder {
+    buffer: Vec<u8>,
+    /// Whether the data is skipped until the next synchronization packet.
+    resynchronizing: bool,
+}
+
+impl ItmDecoder {
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Skips the data up to the next synchronization packet, e.g. when the stream is not decoded
+    /// from its start.
+    pub fn skip_to_sync(&mut self) {
+        self.resynchronizing = true;
+    }
+
+    /// Returns the next complete packet, if there is one.
+    pub fn next_packet(&mut self) -> Option<ItmPacket> {
+        if self.resynchronizing {
+            return self.resynchronize();
+        }
+
+        let &header = self.buffer.first()?;
+
+        let (packet, len) = match header {
+            0x00 => {
+                let zeros = self.buffer.iter().take_while(|&&byte| byte == 0).count();
+                match self.buffer.get(zeros) {
+                    None => return None,
+                    Some(0x80) if zeros >= SYNC_ZEROS => (ItmPacket::Sync, zeros + 1),
+                    // Not a valid synchronization packet, skip the zeros.
+                    Some(_) => (ItmPacket::Other, zeros),
+                }
+            }
+            0x70 => (ItmPacket::Overflow, 1),
+            // Source packets, with a payload of 1, 2 or 4 bytes.
+            header if header & 0x03 != 0 => {
+                let size = match header & 0x03 {
+                    1 => 1,
+                    2 => 2,
+                    _ => 4,
+                };
+                let payload = self.buffer.get(1..1 + size)?;
+                let packet = if header & 0x04 == 0 {
+                    ItmPacket::Instrumentation {
+                        port: header >> 3,
+                        payload: payload.to_vec(),
+                    }
+                } else if is_valid_hardware_source(header >> 3, size) {
+                    ItmPacket::Other
+                } else {
+                    return Some(self.lose_sync());
+                };
+                (packet, 1 + size)
+            }
+            // Local and global timestamps, and extension packets, which are continued while the
+            // top bit of the bytes is set.
+            header if header & 0x0F == 0x00 || header & 0x0B == 0x08 || header & 0xDF == 0x94 => {
+                if header & 0x80 == 0 {
+                    (ItmPacket::Other, 1)
+                } else {
+                    let end = self.buffer[1..]
+                        .iter()
+                        .take(MAX_CONTINUATION_LEN - 1)
+                        .position(|byte| byte & 0x80 == 0);
+                    match end {
+                        Some(end) => (ItmPacket::Other, end + 2),
+                        None if self.buffer.len() < MAX_CONTINUATION_LEN => return None,
+                        None => return Some(self.lose_sync()),
+                    }
+                }
+            }
+            // Reserved headers.
+            _ => return Some(self.lose_sync()),
+        };
+
+        self.buffer.drain(..len);
+        Some(packet)
+    }
+
+    fn lose_sync(&mut self) -> ItmPacket {
+        self.skip_to_sync();
+        ItmPacket::Malformed
+    }
+
+    /// Skips the data up to the next synchronization packet.
+    fn resynchronize(&mut self) -> Option<ItmPacket> {
+        match self
+            .buffer
+            .windows(SYNC_PACKET.len())
+            .position(|window| window == SYNC_PACKET)
+        {
+            Some(start) => {
+                self.buffer.drain(..start + SYNC_PACKET.len());
+                self.resynchronizing = false;
+                Some(ItmPacket::Sync)
+            }
+            None => {
+                // Keep the end, which may be the start of a synchronization packet.
+                let keep = self.buffer.len().min(SYNC_ZEROS);
+                self.buffer.drain(..self.buffer.len() - keep);
+                None
+            }
+        }
+    }
+}
+
+/// Whether a hardware source packet with the discriminator `id` can have a payload of `size`
+/// bytes.
+fn is_valid_hardware_source(id: u8, size: usize) -> bool {
+    match id {
+        // Event counter
+        0 => size == 1,
+        // Exception trace
+        1 => size == 2,
+        // Periodic PC sample, 1 byte when the core sleeps.
+        2 => size == 1 || size == 4,
+        // Data trace
+        8..=23 => true,
+        _ => false,
+    }
+}
+
+/// The format of the log output on ITM stimulus port 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ItmFormat {
+    /// UTF-8 text.
+    Text,
+    /// A defmt stream, e.g. from `defmt-itm`.
+    Defmt,
+}
+
+/// Decodes and prints the log output in the ITM data.
+pub struct ItmLog {
+    decoder: ItmDecoder,
+    data_format: ChannelDataFormat,
+    defmt_state: Option<DefmtState>,
+    /// The data of the stimulus port which has not been printed yet.
+    pending: Vec<u8>,
+    /// Whether the pending data has to be split at the end of a defmt frame.
+    split_frames: bool,
+    /// Whether the warning about corrupt data was printed.
+    warned_malformed: bool,
+}
+
+impl ItmLog {
+    /// Creates the decoder for the given format, with the defmt table of the ELF file `elf`.
+    ///
+    /// The ELF file is only required for the defmt format.
+    pub fn new(
+        format: ItmFormat,
+        elf: Option<&Path>,
+        channel_config: &RttChannelConfig,
+        timestamp_offset: UtcOffset,
+    ) -> anyhow::Result<Self> {
+        let (data_format, defmt_state) = match format {
+            ItmFormat::Text => (
+                ChannelDataFormat::String {
+                    timestamp_offset: channel_config.show_timestamps.then_some(timestamp_offset),
+                    last_line_done: true,
+                },
+                None,
+            ),
+            ItmFormat::Defmt => {
+                let elf = elf.context("Decoding defmt output requires the ELF file")?;
+                let elf = std::fs::read(elf)
+                    .with_context(|| format!("Failed to read {}", elf.display()))?;
+                let defmt_state = DefmtState::try_from_bytes(&elf)?;
+                (
+                    ChannelDataFormat::defmt(channel_config, defmt_state.as_ref()),
+                    defmt_state,
+                )
+            }
+        };
+
+        // Only the rzCOBS encoding marks the end of the frames, the raw encoding relies on the
+        // data arriving in complete frames, like on RTT.
+        let split_frames = defmt_state
+            .as_ref()
+            .is_some_and(|defmt| defmt.table.encoding().can_recover());
+
+        Ok(Self {
+            decoder: ItmDecoder::default(),
+            data_format,
+            defmt_state,
+            pending: vec![],
+            split_frames,
+            warned_malformed: false,
+        })
+    }
+
+    /// Skips the data up to the next synchronization packet, see [`ItmDecoder::skip_to_sync`].
+    pub fn skip_to_sync(&mut self) {
+        self.decoder.skip_to_sync();
+        self.pending.clear();
+    }
+
+    /// Decodes the next chunk of ITM data, and prints the log output in it.
+    pub fn process<S: Write + ?Sized>(
+        &mut self,
+        data: &[u8],
+        out_stream: &mut S,
+    ) -> anyhow::Result<()> {
+        self.decoder.push(data);
+
+        while let Some(packet) = self.decoder.next_packet() {
+            match packet {
+                ItmPacket::Instrumentation { port, payload } if port == LOG_PORT => {
+                    self.pending.extend_from_slice(&payload);
+                }
+                ItmPacket::Overflow => {
+                    tracing::warn!("The ITM overflowed, some of the log output was lost.");
+                }
+                ItmPacket::Malformed => {
+                    if !self.warned_malformed {
+                        tracing::warn!("The SWO data is corrupt, the log output is skipped until the ITM is in sync again. If this happens often, the SWO clock is probably wrong.");
+                        self.warned_malformed = true;
+                    }
+                    // The last frame is incomplete, and would corrupt the next one.
+                    self.pending.clear();
+                }
+                _ => {}
+            }
+        }
+
+        self.print_pending(out_stream)
+    }
+
+    fn print_pending<S: Write + ?Sized>(&mut self, out_stream: &mut S) -> anyhow::Result<()> {
+        let complete = if self.split_frames {
+            // rzCOBS frames end with a zero byte.
+            match self.pending.iter().rposition(|&byte| byte == 0) {
+                Some(end) => end + 1,
+                None => return Ok(()),
+            }
+        } else {
+            self.pending.len()
+        };
+        if complete == 0 {
+            return Ok(());
+        }
+
+        let data = self.pending.drain(..complete).collect::<Vec<_>>();
+        self.data_format.process(
+            usize::from(LOG_PORT),
+            &data,
+            self.defmt_state.as_ref(),
+            &mut Printer { out_stream },
+        )
+    }
+}
+
+struct Printer<'a, S: Write + ?Sized> {
+    out_stream: &'a mut S,
+}
+
+impl<S: Write + ?Sized> ChannelDataCallbacks for Printer<'_, S> {
+    fn on_string_data(&mut self, _channel: usize, data: String) -> anyhow::Result<()> {
+        self.out_stream.write_all(data.as_bytes())?;
+        self.out_stream.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode_all(decoder: &mut ItmDecoder) -> Vec<ItmPacket> {
+        std::iter::from_fn(|| decoder.next_packet()).collect()
+    }
+
+    #[test]
+    fn stimulus_packets_are_decoded_once_complete() {
+        let mut decoder = ItmDecoder::default();
+        // A 32 bit write of "abcd" to stimulus port 0.
+        decoder.push(&[0x03, b'a', b'b']);
+        assert_eq!(decoder.next_packet(), None);
+
+        decoder.push(&[b'c', b'd', 0x09, b'x']);
+        assert_eq!(
+            decode_all(&mut decoder),
+            vec![
+                ItmPacket::Instrumentation {
+                    port: 0,
+                    payload: b"abcd".to_vec()
+                },
+                ItmPacket::Instrumentation {
+                    port: 1,
+                    payload: b"x".to_vec()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn other_packets_are_skipped() {
+        let mut decoder = ItmDecoder::default();
+        decoder.push(&[
+            0, 0, 0, 0, 0, 0x80, // Sync
+            0xC0, 0x81, 0x01, // Local timestamp
+            0x0E, 0x12, 0x00, // Exception trace
+            0x70, // Overflow
+            0x01, b'!',
+        ]);
+
+        assert_eq!(
+            decode_all(&mut decoder),
+            vec![
+                ItmPacket::Sync,
+                ItmPacket::Other,
+                ItmPacket::Other,
+                ItmPacket::Overflow,
+                ItmPacket::Instrumentation {
+                    port: 0,
+                    payload: b"!".to_vec()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn corrupt_data_is_skipped_until_sync() {
+        let mut decoder = ItmDecoder::default();
+        decoder.push(&[
+            0x2D, 0x42, // Hardware source packet with an invalid discriminator
+            0x01, b'x', 0, 0, 0,
+        ]);
+        assert_eq!(decode_all(&mut decoder), vec![ItmPacket::Malformed]);
+
+        decoder.push(&[0, 0, 0x80, 0x01, b'y']);
+        assert_eq!(
+            decode_all(&mut decoder),
+            vec![
+                ItmPacket::Sync,
+                ItmPacket::Instrumentation {
+                    port: 0,
+                    payload: b"y".to_vec()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn skipping_to_sync_drops_partial_packets() {
+        let mut decoder = ItmDecoder::default();
+        decoder.skip_to_sync();
+        // The end of a 32 bit write, which looks like a valid packet.
+        decoder.push(&[b'c', 0x01, b'd', 0, 0, 0, 0, 0, 0x80, 0x01, b'e']);
+
+        assert_eq!(
+            decode_all(&mut decoder),
+            vec![
+                ItmPacket::Sync,
+                ItmPacket::Instrumentation {
+                    port: 0,
+                    payload: b"e".to_vec()
+                },
+            ]
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::Context;
 use indicatif::MultiProgress;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
@@ -8,6 +9,8 @@ use tracing_subscriber::{
     fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
 };
 
+use crate::util::chrome_trace::{ChromeTraceGuard, ChromeTraceLayer};
+
 /// Stores the progress bar for the logging facility.
 static PROGRESS_BAR: Lazy<Mutex<Option<MultiProgress>>> = Lazy::new(|| Mutex::new(None));
 
@@ -86,16 +89,24 @@ impl std::io::Write for ProgressBarWriter {
     }
 }
 
+/// Keeps the log file and the trace file open, and finishes them when dropped.
+pub struct LoggingGuard<'a> {
+    _file_logger: Option<FileLoggerGuard<'a>>,
+    _trace: Option<ChromeTraceGuard>,
+}
+
 /// Configures tracing and sets up the logging facility.
 ///
 /// # Arguments
 ///
 /// * `log_path` - The path to the log file. If `None`, log messages will not be stored in a file.
 /// * `default` - The default log level to use. If `None`, falls back to `RUST_LOG` in the environment.
-pub fn setup_logging(
-    log_path: Option<&Path>,
+/// * `trace_path` - The path to write spans to in the Chrome trace event format, for viewing in Perfetto.
+pub fn setup_logging<'a>(
+    log_path: Option<&'a Path>,
     default: Option<LevelFilter>,
-) -> anyhow::Result<Option<FileLoggerGuard<'_>>> {
+    trace_path: Option<&Path>,
+) -> anyhow::Result<LoggingGuard<'a>> {
     let stdout_subscriber = tracing_subscriber::fmt::layer()
         .compact()
         .without_time()
@@ -115,34 +126,48 @@ pub fn setup_logging(
             }
         });
 
-    let Some(log_path) = log_path else {
-        tracing_subscriber::registry()
-            .with(stdout_subscriber)
-            .init();
+    let (file_subscriber, file_guard) = match log_path {
+        Some(log_path) => {
+            let log_file = File::create(log_path)?;
 
-        return Ok(None);
-    };
+            let (file_appender, guard) =
+                tracing_appender::non_blocking::NonBlockingBuilder::default()
+                    .lossy(false)
+                    .buffered_lines_limit(128 * 1024)
+                    .finish(log_file);
 
-    let log_file = File::create(log_path)?;
+            let file_subscriber = tracing_subscriber::fmt::layer()
+                .json()
+                .with_file(true)
+                .with_line_number(true)
+                .with_span_events(FmtSpan::FULL)
+                .with_writer(file_appender);
 
-    let (file_appender, guard) = tracing_appender::non_blocking::NonBlockingBuilder::default()
-        .lossy(false)
-        .buffered_lines_limit(128 * 1024)
-        .finish(log_file);
+            (Some(file_subscriber), Some((guard, log_path)))
+        }
+        None => (None, None),
+    };
 
-    let file_subscriber = tracing_subscriber::fmt::layer()
-        .json()
-        .with_file(true)
-        .with_line_number(true)
-        .with_span_events(FmtSpan::FULL)
-        .with_writer(file_appender);
+    let (trace_subscriber, trace_guard) = match trace_path {
+        Some(trace_path) => {
+            let (layer, guard) = ChromeTraceLayer::new(trace_path).with_context(|| {
+                format!("Failed to create the trace file {}", trace_path.display())
+            })?;
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
 
     tracing_subscriber::registry()
         .with(stdout_subscriber)
         .with(file_subscriber)
+        .with(trace_subscriber)
         .init();
 
-    Ok(Some(FileLoggerGuard::new(guard, log_path)))
+    Ok(LoggingGuard {
+        _file_logger: file_guard.map(|(guard, log_path)| FileLoggerGuard::new(guard, log_path)),
+        _trace: trace_guard,
+    })
 }
 
 /// Sets the currently displayed progress bar of the CLI.
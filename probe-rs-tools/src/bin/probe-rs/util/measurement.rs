@@ -0,0 +1,149 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use probe_rs::measurement::{self, CurrentMeter, CurrentStatistics};
+
+use crate::util::logging;
+
+/// The number of rows written to the CSV file per second.
+const ROWS_PER_SECOND: u32 = 1000;
+
+/// How often the current is logged while measuring.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Records the current measured by a current meter to a CSV file.
+///
+/// Each row contains the average current over one millisecond. Lines printed by the target
+/// are added as markers, so that the current can be correlated with the firmware.
+pub struct CurrentRecorder {
+    markers: Sender<String>,
+    /// The incomplete line printed by the target.
+    line: Vec<u8>,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<anyhow::Result<CurrentStatistics>>,
+    sample_rate: u32,
+}
+
+impl CurrentRecorder {
+    /// Opens the first current meter and starts writing its samples to the given file.
+    pub fn start(path: &Path) -> anyhow::Result<Self> {
+        let mut meter =
+            measurement::open_current_meter().context("Failed to open a current meter")?;
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+
+        let sample_rate = meter.sample_rate();
+        let (markers, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        meter.start()?;
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || record(meter, BufWriter::new(file), receiver, &stop)
+        });
+
+        Ok(Self {
+            markers,
+            line: Vec::new(),
+            stop,
+            thread,
+            sample_rate,
+        })
+    }
+
+    /// Adds the complete lines of the output of the target as markers.
+    pub fn record_output(&mut self, data: &[u8]) {
+        self.line.extend_from_slice(data);
+        while let Some(end) = self.line.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.line.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            if !line.is_empty() {
+                // The thread only stops when the recorder is finished.
+                let _ = self.markers.send(line);
+            }
+        }
+    }
+
+    /// Stops the measurement and prints a summary.
+    pub fn finish(self) -> anyhow::Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        let statistics = self
+            .thread
+            .join()
+            .map_err(|_| anyhow::anyhow!("The measurement thread panicked"))??;
+
+        logging::eprintln(format!(
+            "Current: {:.3} mA average, {:.3} mA min, {:.3} mA max, {:.3} mC over {:.1} s",
+            statistics.average() * 1000.0,
+            statistics.min * 1000.0,
+            statistics.max * 1000.0,
+            statistics.charge(self.sample_rate) * 1000.0,
+            statistics.count as f64 / f64::from(self.sample_rate),
+        ));
+
+        Ok(())
+    }
+}
+
+fn record(
+    mut meter: Box<dyn CurrentMeter>,
+    mut csv: impl Write,
+    markers: Receiver<String>,
+    stop: &AtomicBool,
+) -> anyhow::Result<CurrentStatistics> {
+    let start = Instant::now();
+    let sample_rate = meter.sample_rate();
+    let samples_per_row = (sample_rate / ROWS_PER_SECOND).max(1) as usize;
+
+    let mut total = CurrentStatistics::default();
+    let mut window = CurrentStatistics::default();
+    let mut last_report = Instant::now();
+    let mut row = Vec::with_capacity(samples_per_row);
+    let mut row_start = 0u64;
+
+    writeln!(csv, "time_s,current_a,marker")?;
+
+    while !stop.load(Ordering::Relaxed) {
+        let samples = meter.read_samples()?;
+        total.add(&samples);
+        window.add(&samples);
+
+        for sample in samples {
+            row.push(sample);
+            if row.len() == samples_per_row {
+                let average = row.iter().sum::<f32>() / row.len() as f32;
+                let time = row_start as f64 / f64::from(sample_rate);
+                writeln!(csv, "{time:.6},{average:e},")?;
+                row_start += row.len() as u64;
+                row.clear();
+            }
+        }
+
+        while let Ok(marker) = markers.try_recv() {
+            let time = start.elapsed().as_secs_f64();
+            writeln!(csv, "{time:.6},,\"{}\"", marker.replace('"', "\"\""))?;
+        }
+
+        if last_report.elapsed() >= REPORT_INTERVAL {
+            tracing::info!(
+                "Current: {:.3} mA average, {:.3} mA max",
+                window.average() * 1000.0,
+                window.max * 1000.0
+            );
+            window = CurrentStatistics::default();
+            last_report = Instant::now();
+        }
+    }
+
+    meter.stop()?;
+    csv.flush()?;
+
+    Ok(total)
+}
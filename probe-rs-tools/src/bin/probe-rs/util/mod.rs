@@ -1,9 +1,21 @@
 pub mod cargo;
+pub mod chrome_trace;
 pub mod common_options;
+pub mod cycle_counter;
+pub mod devices;
+pub mod disassembly;
+pub mod error_report;
 pub mod flash;
+pub mod itm;
 pub mod logging;
+pub mod measurement;
 pub mod meta;
+pub mod output;
+pub mod patch;
+pub mod project_config;
 pub mod rtt;
+pub mod server_address;
+pub mod value;
 
 use std::num::ParseIntError;
 
@@ -14,3 +26,38 @@ pub fn parse_u32(input: &str) -> Result<u32, ParseIntError> {
 pub fn parse_u64(input: &str) -> Result<u64, ParseIntError> {
     parse_int::parse(input)
 }
+
+/// Returns the address of `location`, which is either a number or a symbol of the ELF file.
+pub fn resolve_location(location: &str, elf: Option<&[u8]>) -> anyhow::Result<u64> {
+    if let Ok(address) = parse_u64(location) {
+        return Ok(address);
+    }
+
+    let Some(elf) = elf else {
+        anyhow::bail!("'{location}' is not an address, and no ELF file is given to look it up in.");
+    };
+    let (address, _size) = patch::find_symbol(elf, location)?;
+
+    // The lowest bit of Thumb function symbols is set, but it's not part of the address.
+    Ok(address & !1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve_addresses_and_symbols() {
+        assert_eq!(resolve_location("0x2000", None).unwrap(), 0x2000);
+        assert!(resolve_location("main", None).is_err());
+
+        let elf = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../probe-rs/tests/debug-unwind-tests/nRF52833_xxAA_svcall.elf"
+        ))
+        .unwrap();
+        // The Thumb bit of the symbol is not part of the address.
+        assert_eq!(resolve_location("main", Some(&elf)).unwrap(), 0x3ac);
+        assert!(resolve_location("missing_symbol", Some(&elf)).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+/// Version of the JSON output.
+///
+/// This is incremented whenever the structure of the emitted JSON changes in an
+/// incompatible way, so consumers can detect output they don't understand.
+pub const JSON_OUTPUT_VERSION: u32 = 1;
+
+/// Format of the results printed by a command.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// A single JSON object, intended to be consumed by other programs
+    Json,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        self == OutputFormat::Json
+    }
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a, T> {
+    version: u32,
+    command: &'a str,
+    result: &'a T,
+}
+
+/// Writes the result of `command` as a versioned JSON object, followed by a newline.
+pub fn write_json<T: Serialize>(
+    mut output: impl Write,
+    command: &str,
+    result: &T,
+) -> anyhow::Result<()> {
+    let json = JsonOutput {
+        version: JSON_OUTPUT_VERSION,
+        command,
+        result,
+    };
+    serde_json::to_writer_pretty(&mut output, &json)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Prints the result of `command` as a versioned JSON object to stdout.
+pub fn print_json<T: Serialize>(command: &str, result: &T) -> anyhow::Result<()> {
+    write_json(std::io::stdout().lock(), command, result)
+}
+
+#[derive(Serialize)]
+struct JsonError<'a, T> {
+    version: u32,
+    command: &'a str,
+    error: &'a T,
+}
+
+/// Writes the error which made `command` fail as a versioned JSON object, followed by a newline.
+///
+/// The object has the same `version` and `command` fields as the result of a command, with an
+/// `error` field in place of `result`.
+pub fn write_json_error<T: Serialize>(
+    mut output: impl Write,
+    command: &str,
+    error: &T,
+) -> anyhow::Result<()> {
+    let json = JsonError {
+        version: JSON_OUTPUT_VERSION,
+        command,
+        error,
+    };
+    serde_json::to_writer_pretty(&mut output, &json)?;
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Prints the error which made `command` fail as a versioned JSON object to stdout.
+pub fn print_json_error<T: Serialize>(command: &str, error: &T) -> anyhow::Result<()> {
+    write_json_error(std::io::stdout().lock(), command, error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{write_json, write_json_error};
+
+    #[test]
+    fn json_output_is_versioned() {
+        let mut buff = Vec::new();
+        write_json(&mut buff, "erase", &serde_json::json!({ "erased": true })).unwrap();
+
+        let output: serde_json::Value = serde_json::from_slice(&buff).unwrap();
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "version": 1,
+                "command": "erase",
+                "result": { "erased": true },
+            })
+        );
+    }
+
+    #[test]
+    fn json_error_is_versioned() {
+        let mut buff = Vec::new();
+        write_json_error(&mut buff, "read", &serde_json::json!({ "code": "E0001" })).unwrap();
+
+        let output: serde_json::Value = serde_json::from_slice(&buff).unwrap();
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "version": 1,
+                "command": "read",
+                "error": { "code": "E0001" },
+            })
+        );
+    }
+}
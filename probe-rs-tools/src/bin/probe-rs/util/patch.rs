@@ -0,0 +1,374 @@
+//! Patching per-device data, like serial numbers, MAC addresses or keys, into the image while
+//! flashing, so every device gets a unique identity without rebuilding the firmware.
+//!
+//! Patches are given as `<LOCATION>=<VALUE>`. The location is either an address, or the name of
+//! a symbol in the ELF file, which has to be placed in flash. The value is one of
+//!
+//! - hex bytes with a `0x` prefix, e.g. `0x0011aabb`,
+//! - a little-endian integer, e.g. `u32:1234`, with `u8`, `u16`, `u32` and `u64`,
+//! - a string, e.g. `str:SN-0001`.
+//!
+//! A CSV manifest can hold the values for many devices. Its header names the locations, and
+//! every following row holds the values for one device. Values can't contain commas.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use probe_rs::flashing::{FlashError, FlashLoader};
+
+use super::parse_u64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PatchError {
+    #[error("Patches have to be given as <LOCATION>=<VALUE>, got '{0}'.")]
+    InvalidPatch(String),
+    #[error(
+        "Invalid patch value '{0}'. Values are hex bytes (`0x0011aabb`), an integer (`u32:1234`) or a string (`str:SN-0001`)."
+    )]
+    InvalidValue(String),
+    #[error("Failed to read the patch manifest '{path}'.")]
+    ManifestNotFound {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("The patch manifest '{path}' has no row {row}.")]
+    RowNotFound { path: PathBuf, row: usize },
+    #[error("Row {row} of the patch manifest '{path}' has {found} values, but the header names {expected} locations.")]
+    RowLength {
+        path: PathBuf,
+        row: usize,
+        found: usize,
+        expected: usize,
+    },
+    #[error("Failed to read the image to look up symbols.")]
+    ImageNotReadable(#[source] std::io::Error),
+    #[error("Symbols can only be patched in ELF files: {0}")]
+    NotAnElf(String),
+    #[error("The symbol '{0}' was not found in the ELF file.")]
+    SymbolNotFound(String),
+    #[error(
+        "The value for symbol '{symbol}' has {len} bytes, but the symbol only has {size} bytes."
+    )]
+    ValueTooLarge {
+        symbol: String,
+        size: u64,
+        len: usize,
+    },
+    #[error("Failed to patch the image.")]
+    Flash(#[from] FlashError),
+}
+
+/// Where a patch is written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchLocation {
+    Address(u64),
+    Symbol(String),
+}
+
+impl FromStr for PatchLocation {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match parse_u64(s) {
+            Ok(address) => Self::Address(address),
+            Err(_) => Self::Symbol(s.to_string()),
+        })
+    }
+}
+
+/// Data to write into the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub location: PatchLocation,
+    pub value: Vec<u8>,
+}
+
+impl FromStr for Patch {
+    type Err = PatchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (location, value) = s
+            .split_once('=')
+            .ok_or_else(|| PatchError::InvalidPatch(s.to_string()))?;
+
+        Ok(Self {
+            location: location.trim().parse().unwrap(),
+            value: parse_value(value.trim())?,
+        })
+    }
+}
+
+fn parse_value(value: &str) -> Result<Vec<u8>, PatchError> {
+    let invalid = || PatchError::InvalidValue(value.to_string());
+
+    if let Some(string) = value.strip_prefix("str:") {
+        return Ok(string.as_bytes().to_vec());
+    }
+
+    if let Some((kind, number)) = value.split_once(':') {
+        let number = parse_u64(number).map_err(|_| invalid())?;
+        let bytes = number.to_le_bytes();
+        let size = match kind {
+            "u8" => 1,
+            "u16" => 2,
+            "u32" => 4,
+            "u64" => 8,
+            _ => return Err(invalid()),
+        };
+        if size < 8 && number >> (size * 8) != 0 {
+            return Err(invalid());
+        }
+        return Ok(bytes[..size].to_vec());
+    }
+
+    // Bare values are rejected instead of guessing between hex bytes and an integer.
+    let hex = value.strip_prefix("0x").ok_or_else(invalid)?;
+    if hex.is_empty() || hex.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// Options to patch per-device data into the image.
+#[derive(Debug, Clone, Default, clap::Parser)]
+pub struct PatchOptions {
+    /// Patch per-device data into the image, given as `<ADDRESS|SYMBOL>=<VALUE>`. The value is
+    /// hex bytes (`0x0011aabb`), an integer (`u32:1234`) or a string (`str:SN-0001`).
+    #[arg(
+        long = "patch",
+        value_name = "LOCATION=VALUE",
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub patches: Vec<Patch>,
+
+    /// Read patches from a CSV file. The header names the locations, and every following row
+    /// holds the values for one device.
+    #[arg(long, value_name = "PATH", help_heading = "DOWNLOAD CONFIGURATION")]
+    pub patch_manifest: Option<PathBuf>,
+
+    /// The row of the patch manifest to use, starting at 1.
+    #[arg(
+        long,
+        default_value_t = 1,
+        requires = "patch_manifest",
+        help_heading = "DOWNLOAD CONFIGURATION"
+    )]
+    pub patch_row: usize,
+}
+
+impl PatchOptions {
+    /// Returns the patches for a device, using row `row` of the manifest.
+    pub fn patches(&self, row: usize) -> Result<Vec<Patch>, PatchError> {
+        let mut patches = self.patches.clone();
+
+        if let Some(path) = &self.patch_manifest {
+            let manifest =
+                std::fs::read_to_string(path).map_err(|source| PatchError::ManifestNotFound {
+                    source,
+                    path: path.clone(),
+                })?;
+            patches.extend(
+                parse_manifest_row(&manifest, row).map_err(|error| match error {
+                    ManifestError::Patch(error) => error,
+                    ManifestError::RowNotFound => PatchError::RowNotFound {
+                        path: path.clone(),
+                        row,
+                    },
+                    ManifestError::RowLength { found, expected } => PatchError::RowLength {
+                        path: path.clone(),
+                        row,
+                        found,
+                        expected,
+                    },
+                })?,
+            );
+        }
+
+        Ok(patches)
+    }
+
+    /// Applies the patches for a device to the loader, resolving symbols in `image`.
+    pub fn apply(
+        &self,
+        loader: &mut FlashLoader,
+        image: &Path,
+        row: usize,
+    ) -> Result<(), PatchError> {
+        let patches = self.patches(row)?;
+        if patches.is_empty() {
+            return Ok(());
+        }
+
+        let mut elf = None;
+        for patch in patches {
+            let address = match &patch.location {
+                PatchLocation::Address(address) => *address,
+                PatchLocation::Symbol(name) => {
+                    if elf.is_none() {
+                        elf = Some(std::fs::read(image).map_err(PatchError::ImageNotReadable)?);
+                    }
+                    let (address, size) = find_symbol(elf.as_deref().unwrap(), name)?;
+                    if size > 0 && patch.value.len() as u64 > size {
+                        return Err(PatchError::ValueTooLarge {
+                            symbol: name.clone(),
+                            size,
+                            len: patch.value.len(),
+                        });
+                    }
+                    address
+                }
+            };
+
+            tracing::info!("Patching {} bytes at {address:#010x}", patch.value.len());
+            loader.patch_data(address, &patch.value)?;
+        }
+
+        Ok(())
+    }
+}
+
+enum ManifestError {
+    Patch(PatchError),
+    RowNotFound,
+    RowLength { found: usize, expected: usize },
+}
+
+fn parse_manifest_row(manifest: &str, row: usize) -> Result<Vec<Patch>, ManifestError> {
+    let mut lines = manifest.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<PatchLocation> = lines
+        .next()
+        .ok_or(ManifestError::RowNotFound)?
+        .split(',')
+        .map(|location| location.trim().parse().unwrap())
+        .collect();
+
+    let values: Vec<&str> = lines
+        .nth(row.checked_sub(1).ok_or(ManifestError::RowNotFound)?)
+        .ok_or(ManifestError::RowNotFound)?
+        .split(',')
+        .map(str::trim)
+        .collect();
+    if values.len() != header.len() {
+        return Err(ManifestError::RowLength {
+            found: values.len(),
+            expected: header.len(),
+        });
+    }
+
+    header
+        .into_iter()
+        .zip(values)
+        .map(|(location, value)| {
+            Ok(Patch {
+                location,
+                value: parse_value(value).map_err(ManifestError::Patch)?,
+            })
+        })
+        .collect()
+}
+
+/// Returns the address and size of the symbol `name` in the ELF file `elf`.
+///
+/// Mangled Rust symbols are found by their path, e.g. `firmware::config::GAIN`, or by the end
+/// of it, e.g. `GAIN`.
+pub(crate) fn find_symbol(elf: &[u8], name: &str) -> Result<(u64, u64), PatchError> {
+    let elf =
+        goblin::elf::Elf::parse(elf).map_err(|error| PatchError::NotAnElf(error.to_string()))?;
+
+    let symbol = |matches: &dyn Fn(&str) -> bool| {
+        elf.syms
+            .iter()
+            .find(|symbol| elf.strtab.get_at(symbol.st_name).is_some_and(matches))
+            .map(|symbol| (symbol.st_value, symbol.st_size))
+    };
+
+    symbol(&|symbol| symbol == name)
+        .or_else(|| symbol(&|symbol| demangled_path_matches(symbol, name)))
+        .ok_or_else(|| PatchError::SymbolNotFound(name.to_string()))
+}
+
+/// Returns if the demangled path of `symbol`, without the hash, is `name` or ends with it.
+fn demangled_path_matches(symbol: &str, name: &str) -> bool {
+    let Ok(demangled) = rustc_demangle::try_demangle(symbol) else {
+        return false;
+    };
+    let path = format!("{demangled:#}");
+
+    path == name || path.ends_with(&format!("::{name}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mangled_symbols_match_their_path() {
+        let symbol = "_ZN8firmware6config4GAIN17h0123456789abcdefE";
+
+        assert!(demangled_path_matches(symbol, "firmware::config::GAIN"));
+        assert!(demangled_path_matches(symbol, "GAIN"));
+        assert!(!demangled_path_matches(symbol, "AIN"));
+        assert!(!demangled_path_matches("GAIN", "GAIN"));
+    }
+
+    #[test]
+    fn parse_patches() {
+        assert_eq!(
+            "0x1000=0x0011aabb".parse::<Patch>().unwrap(),
+            Patch {
+                location: PatchLocation::Address(0x1000),
+                value: vec![0x00, 0x11, 0xaa, 0xbb],
+            }
+        );
+        assert_eq!(
+            "SERIAL=u32:0x12345678".parse::<Patch>().unwrap(),
+            Patch {
+                location: PatchLocation::Symbol("SERIAL".to_string()),
+                value: vec![0x78, 0x56, 0x34, 0x12],
+            }
+        );
+        assert_eq!("NAME=str:SN-1".parse::<Patch>().unwrap().value, b"SN-1");
+        assert!("SERIAL".parse::<Patch>().is_err());
+        assert!("SERIAL=u8:256".parse::<Patch>().is_err());
+        assert!("SERIAL=0x123".parse::<Patch>().is_err());
+        assert!("SERIAL=1234".parse::<Patch>().is_err());
+        assert!("SERIAL=0x0x12".parse::<Patch>().is_err());
+    }
+
+    #[test]
+    fn parse_manifest() {
+        let manifest = "SERIAL, 0x7f000\nu32:1, 0xaabb\n\nu32:2, 0xccdd\n";
+
+        let patches = parse_manifest_row(manifest, 2).ok().unwrap();
+        assert_eq!(
+            patches,
+            [
+                Patch {
+                    location: PatchLocation::Symbol("SERIAL".to_string()),
+                    value: vec![2, 0, 0, 0],
+                },
+                Patch {
+                    location: PatchLocation::Address(0x7f000),
+                    value: vec![0xcc, 0xdd],
+                },
+            ]
+        );
+        assert!(matches!(
+            parse_manifest_row(manifest, 3),
+            Err(ManifestError::RowNotFound)
+        ));
+        assert!(matches!(
+            parse_manifest_row("A,B\n1", 1),
+            Err(ManifestError::RowLength { .. })
+        ));
+    }
+}
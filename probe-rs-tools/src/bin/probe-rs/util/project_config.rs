@@ -0,0 +1,249 @@
+//! Project configuration, read from `probe-rs.toml`.
+//!
+//! Teams can commit a `probe-rs.toml` with the debug configuration of their project, so it
+//! doesn't have to be repeated on every command line. Personal settings go into
+//! `probe-rs.local.toml` next to it, which takes precedence. The files are searched in the
+//! current directory and its parents.
+//!
+//! Environment variables and command line flags take precedence over the files: values from the
+//! files are only used for options which were not given otherwise. Flags which are switched on in
+//! a file can't be switched off on the command line.
+//!
+//! ```toml
+//! [probe]
+//! chip = "nRF52840_xxAA"
+//! probe = "1366:1015"
+//! speed = 4000
+//!
+//! [flashing]
+//! verify = true
+//!
+//! [rtt]
+//! log-format = "{t} {L} {s}"
+//! channels = [{ channelNumber = 0, dataFormat = "Defmt" }]
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::Context;
+use figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use probe_rs::probe::{DebugProbeSelector, WireProtocol};
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use super::{common_options::ProbeOptions, rtt::RttChannelConfig};
+
+/// The name of the project configuration file.
+pub const CONFIG_FILE: &str = "probe-rs.toml";
+/// The name of the file with personal settings, which overrides [`CONFIG_FILE`].
+pub const LOCAL_CONFIG_FILE: &str = "probe-rs.local.toml";
+
+static PROJECT_CONFIG: OnceLock<ProjectConfig> = OnceLock::new();
+
+/// The contents of the project configuration files.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub probe: ProbeConfig,
+    pub flashing: FlashingConfig,
+    pub rtt: RttConfig,
+}
+
+/// The `[probe]` section, see [`ProbeOptions`](super::common_options::ProbeOptions).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ProbeConfig {
+    pub chip: Option<String>,
+    pub chip_description_path: Option<PathBuf>,
+    pub probe: Option<DebugProbeSelector>,
+    #[serde(deserialize_with = "deserialize_protocol")]
+    pub protocol: Option<WireProtocol>,
+    pub speed: Option<u32>,
+    pub connect_timeout: Option<u64>,
+    pub connect_under_reset: bool,
+    pub allow_erase_all: bool,
+    pub non_interactive: bool,
+}
+
+/// The `[flashing]` section.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FlashingConfig {
+    pub verify: bool,
+    pub restore_unwritten: bool,
+    pub disable_double_buffering: bool,
+    pub chip_erase: bool,
+}
+
+/// The `[rtt]` section, used by `run` and `attach`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RttConfig {
+    /// The default format string for defmt logs.
+    pub log_format: Option<String>,
+    pub no_location: bool,
+    pub scan_memory: bool,
+    /// The configuration of the channels. If it is empty, channel 0 is shown.
+    pub channels: Vec<RttChannelConfig>,
+}
+
+impl ProbeConfig {
+    /// Fills the options which were not given on the command line or in the environment.
+    pub fn apply(&self, options: &mut ProbeOptions) {
+        fill(&mut options.chip, &self.chip);
+        fill(
+            &mut options.chip_description_path,
+            &self.chip_description_path,
+        );
+        fill(&mut options.probe, &self.probe);
+        fill(&mut options.protocol, &self.protocol);
+        fill(&mut options.speed, &self.speed);
+        fill(&mut options.connect_timeout, &self.connect_timeout);
+        options.connect_under_reset |= self.connect_under_reset;
+        options.allow_erase_all |= self.allow_erase_all;
+        options.non_interactive |= self.non_interactive;
+    }
+}
+
+fn fill<T: Clone>(option: &mut Option<T>, default: &Option<T>) {
+    if option.is_none() {
+        option.clone_from(default);
+    }
+}
+
+fn deserialize_protocol<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<WireProtocol>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|protocol| protocol.parse().map_err(D::Error::custom))
+        .transpose()
+}
+
+impl ProjectConfig {
+    /// Loads the configuration, either from `path`, or from the configuration files found in
+    /// the current directory or its parents. Must be called once, before [`ProjectConfig::get`].
+    pub fn load(path: Option<&Path>) -> anyhow::Result<()> {
+        let files = match path {
+            Some(path) => vec![path.to_path_buf()],
+            None => std::env::current_dir()
+                .ok()
+                .and_then(|directory| find_config_dir(&directory))
+                .map(|directory| {
+                    vec![
+                        directory.join(CONFIG_FILE),
+                        directory.join(LOCAL_CONFIG_FILE),
+                    ]
+                })
+                .unwrap_or_default(),
+        };
+
+        let mut figment = Figment::new();
+        for file in &files {
+            tracing::debug!("Loading the project configuration from {}", file.display());
+            figment = figment.merge(Toml::file(file));
+        }
+
+        let config: ProjectConfig = figment.extract().with_context(|| {
+            format!(
+                "Failed to read the project configuration from {}",
+                files[0].display()
+            )
+        })?;
+
+        PROJECT_CONFIG
+            .set(config)
+            .map_err(|_| anyhow::anyhow!("The project configuration was already loaded"))
+    }
+
+    /// Returns the loaded configuration, or the default configuration if none was loaded.
+    pub fn get() -> &'static ProjectConfig {
+        PROJECT_CONFIG.get_or_init(ProjectConfig::default)
+    }
+}
+
+/// Returns the nearest directory, starting at `directory`, which contains a configuration file.
+fn find_config_dir(directory: &Path) -> Option<PathBuf> {
+    directory
+        .ancestors()
+        .find(|directory| {
+            directory.join(CONFIG_FILE).is_file() || directory.join(LOCAL_CONFIG_FILE).is_file()
+        })
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn local_config_overrides_project_config() {
+        let config: ProjectConfig = Figment::new()
+            .merge(Toml::string(
+                r#"
+                [probe]
+                chip = "nRF52840_xxAA"
+                protocol = "swd"
+                speed = 4000
+
+                [flashing]
+                verify = true
+
+                [rtt]
+                channels = [{ channelNumber = 1, dataFormat = "Defmt" }]
+                "#,
+            ))
+            .merge(Toml::string(
+                r#"
+                [probe]
+                probe = "1366:1015"
+                speed = 1000
+                "#,
+            ))
+            .extract()
+            .unwrap();
+
+        assert_eq!(config.probe.chip.as_deref(), Some("nRF52840_xxAA"));
+        assert_eq!(config.probe.protocol, Some(WireProtocol::Swd));
+        assert_eq!(config.probe.speed, Some(1000));
+        assert!(config.probe.probe.is_some());
+        assert!(config.flashing.verify);
+        assert_eq!(config.rtt.channels[0].channel_number, Some(1));
+    }
+
+    #[test]
+    fn command_line_takes_precedence() {
+        let config: ProjectConfig = Figment::new()
+            .merge(Toml::string(
+                r#"
+                [probe]
+                chip = "nRF52840_xxAA"
+                speed = 4000
+                connect-under-reset = true
+                "#,
+            ))
+            .extract()
+            .unwrap();
+
+        let mut options = ProbeOptions::parse_from(["probe-rs", "--speed", "100"]);
+        config.probe.apply(&mut options);
+
+        assert_eq!(options.chip.as_deref(), Some("nRF52840_xxAA"));
+        assert_eq!(options.speed, Some(100));
+        assert!(options.connect_under_reset);
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        let result = Figment::new()
+            .merge(Toml::string("[probe]\nchipp = \"nRF52840_xxAA\""))
+            .extract::<ProjectConfig>();
+
+        assert!(result.is_err());
+    }
+}
@@ -174,7 +174,38 @@ impl ChannelDataFormat {
         matches!(self, ChannelDataFormat::BinaryLE)
     }
 
-    fn process(
+    /// Creates the format of a defmt channel, with the location and timestamp options of
+    /// `channel_config`.
+    pub fn defmt(channel_config: &RttChannelConfig, defmt_state: Option<&DefmtState>) -> Self {
+        let has_timestamp = if let Some(defmt) = defmt_state {
+            defmt.table.has_timestamp()
+        } else {
+            tracing::warn!("No `Table` definition in DWARF info; compile your program with `debug = 2` to enable location info.");
+            false
+        };
+
+        // Format options:
+        // 1. Custom format for the channel
+        // 2. Default with optional timestamp and location
+        let format = if let Some(format) = channel_config.log_format.as_deref() {
+            FormatterFormat::Custom(format)
+        } else {
+            FormatterFormat::Default {
+                with_location: channel_config.show_location,
+            }
+        };
+
+        ChannelDataFormat::Defmt {
+            formatter: Formatter::new(FormatterConfig {
+                format,
+                is_timestamp_available: has_timestamp && channel_config.show_timestamps,
+            }),
+            cwd: std::env::current_dir().unwrap(),
+        }
+    }
+
+    /// Formats the data received on channel `number`, and passes it to `collector`.
+    pub fn process(
         &mut self,
         number: usize,
         buffer: &[u8],
@@ -335,33 +366,7 @@ impl RttActiveUpChannel {
             DataFormat::BinaryLE if !is_defmt_channel => ChannelDataFormat::BinaryLE,
 
             // either DataFormat::Defmt is configured, or defmt_enabled is true
-            _ => {
-                let has_timestamp = if let Some(defmt) = defmt_state {
-                    defmt.table.has_timestamp()
-                } else {
-                    tracing::warn!("No `Table` definition in DWARF info; compile your program with `debug = 2` to enable location info.");
-                    false
-                };
-
-                // Format options:
-                // 1. Custom format for the channel
-                // 2. Default with optional timestamp and location
-                let format = if let Some(format) = channel_config.log_format.as_deref() {
-                    FormatterFormat::Custom(format)
-                } else {
-                    FormatterFormat::Default {
-                        with_location: channel_config.show_location,
-                    }
-                };
-
-                ChannelDataFormat::Defmt {
-                    formatter: Formatter::new(FormatterConfig {
-                        format,
-                        is_timestamp_available: has_timestamp && channel_config.show_timestamps,
-                    }),
-                    cwd: std::env::current_dir().unwrap(),
-                }
-            }
+            _ => ChannelDataFormat::defmt(channel_config, defmt_state),
         };
 
         let channel_name = up_channel
@@ -0,0 +1,77 @@
+//! Access control for the servers which give clients full access to the target, like the GDB and
+//! DAP servers.
+
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use anyhow::Context;
+
+/// Checks that a server only listens on other interfaces than loopback if `allow_remote` is set.
+///
+/// Neither the GDB nor the DAP protocol encrypt their traffic, so anybody who can reach the
+/// server can read and write all memory of the target.
+pub fn check_listen_address(address: SocketAddr, allow_remote: bool) -> anyhow::Result<()> {
+    if address.ip().is_loopback() || allow_remote {
+        return Ok(());
+    }
+
+    anyhow::bail!(
+        "Refusing to listen on {address}, which can be reached from other machines. \
+        Use `--allow-remote` if this is intended."
+    )
+}
+
+/// Resolves `connection_string`, and checks all of its addresses with [`check_listen_address`].
+pub fn check_connection_string(connection_string: &str, allow_remote: bool) -> anyhow::Result<()> {
+    let addresses = connection_string
+        .to_socket_addrs()
+        .with_context(|| format!("Invalid connection string '{connection_string}'"))?;
+
+    for address in addresses {
+        check_listen_address(address, allow_remote)?;
+    }
+
+    Ok(())
+}
+
+/// Compares an authentication token received from a client with the expected one.
+///
+/// The comparison takes the same time for all tokens of the same length, so the expected token
+/// can't be guessed byte by byte.
+pub fn token_matches(expected: &str, received: &str) -> bool {
+    if expected.len() != received.len() {
+        return false;
+    }
+
+    expected
+        .bytes()
+        .zip(received.bytes())
+        .fold(0, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn loopback_is_always_allowed() {
+        check_listen_address("127.0.0.1:1337".parse().unwrap(), false).unwrap();
+        check_listen_address("[::1]:1337".parse().unwrap(), false).unwrap();
+    }
+
+    #[test]
+    fn remote_needs_to_be_allowed() {
+        let address = "0.0.0.0:1337".parse().unwrap();
+
+        assert!(check_listen_address(address, false).is_err());
+        check_listen_address(address, true).unwrap();
+    }
+
+    #[test]
+    fn tokens() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secret", "secreT"));
+        assert!(!token_matches("secret", "secret2"));
+        assert!(!token_matches("secret", ""));
+    }
+}
@@ -0,0 +1,98 @@
+//! Scalar values of target variables, which are stored little-endian in the target memory.
+
+use std::str::FromStr;
+
+/// The type of a scalar variable on the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    Bool,
+}
+
+impl ScalarType {
+    /// Returns the unsigned integer type with `size` bytes.
+    pub fn unsigned(size: u64) -> Option<Self> {
+        match size {
+            1 => Some(Self::U8),
+            2 => Some(Self::U16),
+            4 => Some(Self::U32),
+            8 => Some(Self::U64),
+            _ => None,
+        }
+    }
+
+    /// The size of the type in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 | Self::Bool => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::U64 | Self::I64 | Self::F64 => 8,
+        }
+    }
+
+    /// Formats the value in `bytes`, which has to be [`size`](Self::size) bytes long.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let mut buffer = [0; 8];
+        buffer[..self.size()].copy_from_slice(&bytes[..self.size()]);
+        let raw = u64::from_le_bytes(buffer);
+
+        match self {
+            Self::U8 | Self::U16 | Self::U32 | Self::U64 => raw.to_string(),
+            Self::I8 => (raw as i8).to_string(),
+            Self::I16 => (raw as i16).to_string(),
+            Self::I32 => (raw as i32).to_string(),
+            Self::I64 => (raw as i64).to_string(),
+            Self::F32 => f32::from_bits(raw as u32).to_string(),
+            Self::F64 => f64::from_bits(raw).to_string(),
+            Self::Bool => (raw != 0).to_string(),
+        }
+    }
+}
+
+impl FromStr for ScalarType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ty = match s {
+            "u8" => Self::U8,
+            "u16" => Self::U16,
+            "u32" => Self::U32,
+            "u64" => Self::U64,
+            "i8" => Self::I8,
+            "i16" => Self::I16,
+            "i32" => Self::I32,
+            "i64" => Self::I64,
+            "f32" => Self::F32,
+            "f64" => Self::F64,
+            "bool" => Self::Bool,
+            _ => anyhow::bail!("Unsupported type '{s}'."),
+        };
+
+        Ok(ty)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn values_are_decoded() {
+        assert_eq!(ScalarType::U16.decode(&[0x34, 0x12]), "4660");
+        assert_eq!(ScalarType::I8.decode(&[0xFF]), "-1");
+        assert_eq!(ScalarType::F32.decode(&1.5f32.to_le_bytes()), "1.5");
+        assert_eq!(ScalarType::Bool.decode(&[1]), "true");
+        assert_eq!(ScalarType::unsigned(3), None);
+        assert_eq!("i32".parse::<ScalarType>().unwrap(), ScalarType::I32);
+    }
+}
@@ -11,9 +11,16 @@ use std::collections::HashMap;
 #[derive(Debug)]
 pub struct MockMemoryAp {
     pub memory: Vec<u8>,
+    /// The values written to TAR, in order.
+    pub tar_writes: Vec<u32>,
     store: HashMap<u8, u32>,
 }
 
+/// Increments `address` by `offset` like a MEM-AP, which only increments the 10 lowest bits.
+fn autoincrement(address: u32, offset: u32) -> u32 {
+    address & !0x3FF | (address + offset) & 0x3FF
+}
+
 impl MockMemoryAp {
     /// Creates a MockMemoryAp with the memory filled with a pattern where each byte is equal to its
     /// own address plus one (to avoid zeros). The pattern can be used as a canary pattern to ensure
@@ -45,6 +52,7 @@ impl MockMemoryAp {
         store.insert(DRW::ADDRESS, 0);
         Self {
             memory: std::iter::repeat(1..=255).flatten().take(size).collect(),
+            tar_writes: Vec::new(),
             store,
         }
     }
@@ -123,7 +131,8 @@ impl DapAccess for MockMemoryAp {
 
             match csw.AddrInc {
                 AddressIncrement::Single => {
-                    self.store.insert(TAR::ADDRESS, address + offset);
+                    self.store
+                        .insert(TAR::ADDRESS, autoincrement(address, offset));
                 }
                 AddressIncrement::Off => (),
                 AddressIncrement::Packed => {
@@ -188,7 +197,8 @@ impl DapAccess for MockMemoryAp {
                 }
                 .map(|offset| match csw.AddrInc {
                     AddressIncrement::Single => {
-                        self.store.insert(TAR::ADDRESS, address + offset);
+                        self.store
+                            .insert(TAR::ADDRESS, autoincrement(address, offset));
                     }
                     AddressIncrement::Off => (),
                     AddressIncrement::Packed => {
@@ -202,6 +212,7 @@ impl DapAccess for MockMemoryAp {
             }
             TAR::ADDRESS => {
                 self.store.insert(TAR::ADDRESS, value);
+                self.tar_writes.push(value);
                 Ok(())
             }
             _ => panic!("MockMemoryAp: unknown register"),
@@ -6,11 +6,17 @@ use crate::{
             BASEPTR1, DPIDR, DPIDR1,
         },
         memory::{adi_v5_memory_interface::ADIMemoryInterface, ArmMemoryInterface, Component},
+        queue::{DapQueue, QueuedPort},
+        register_cache::ApRegisterCache,
         sequences::{ArmDebugSequence, DefaultArmSequence},
-        ArmError, DapAccess, DpAddress, FullyQualifiedApAddress, PortType, RawDapAccess, SwoAccess,
-        SwoConfig,
+        ArmError, BlockTransfer, DapAccess, DpAddress, FullyQualifiedApAddress, Pins, PortType,
+        RawDapAccess, RawDapTransfer, SwoAccess, SwoConfig,
+    },
+    metrics::Metrics,
+    probe::{
+        speed_fallback::{SpeedFallback, SpeedFallbackState},
+        DebugProbe, DebugProbeError, Probe, UartAccess,
     },
-    probe::{DebugProbe, DebugProbeError, Probe},
     CoreStatus, Error,
 };
 use jep106::JEP106Code;
@@ -24,7 +30,9 @@ use std::{
 
 /// An error in the communication with an access port or
 /// debug port.
-#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq, Copy)]
+#[derive(
+    Debug, thiserror::Error, Clone, PartialEq, Eq, Copy, serde::Serialize, serde::Deserialize,
+)]
 pub enum DapError {
     /// An error occurred during SWD communication.
     #[error("An error occurred in the SWD communication between probe and device.")]
@@ -124,12 +132,25 @@ pub trait UninitializedArmProbe: SwdSequence + Debug {
         self.initialize(DefaultArmSequence::create(), dp)
     }
 
+    /// Lower the protocol speed when transfers keep failing, see [`SpeedFallback`].
+    ///
+    /// Interfaces which don't have access to the individual transfers ignore this.
+    fn set_speed_fallback(&mut self, _fallback: SpeedFallback) {}
+
+    /// Sets the handle which DAP transactions and memory accesses are counted in.
+    ///
+    /// Interfaces which don't have access to the individual transfers ignore this.
+    fn set_metrics(&mut self, _metrics: Metrics) {}
+
     /// Closes the interface and returns back the generic probe it consumed.
     fn close(self: Box<Self>) -> Probe;
 }
 
 pub trait ArmDebugState {
     fn disconnect(&mut self, _probe: &mut dyn DapProbe) {}
+
+    /// Forgets the cached DP and AP registers, if there are any.
+    fn invalidate_register_cache(&mut self) {}
 }
 
 #[derive(Debug)]
@@ -183,6 +204,12 @@ impl ArmDebugState for Initialized {
         probe.raw_flush().ok();
         drop(stop_span);
     }
+
+    fn invalidate_register_cache(&mut self) {
+        for state in self.dps.values_mut() {
+            state.invalidate_cache();
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -194,9 +221,15 @@ pub(crate) struct DpState {
     pub current_apsel: u8,
     pub current_apbanksel: u8,
 
+    /// Whether the `current_*` fields match the SELECT register of the target.
+    pub select_valid: bool,
+
     /// Information about the APs of the target.
     /// APs are identified by a number, starting from zero.
     pub access_ports: BTreeSet<FullyQualifiedApAddress>,
+
+    /// Cached registers of the APs.
+    ap_registers: HashMap<FullyQualifiedApAddress, ApRegisterCache>,
 }
 
 impl DpState {
@@ -206,7 +239,17 @@ impl DpState {
             current_dpbanksel: 0,
             current_apsel: 0,
             current_apbanksel: 0,
+            select_valid: false,
             access_ports: BTreeSet::new(),
+            ap_registers: HashMap::new(),
+        }
+    }
+
+    /// Forgets the cached SELECT value and AP registers.
+    fn invalidate_cache(&mut self) {
+        self.select_valid = false;
+        for cache in self.ap_registers.values_mut() {
+            cache.invalidate();
         }
     }
 }
@@ -218,6 +261,8 @@ impl DpState {
 pub struct ArmCommunicationInterface<S: ArmDebugState> {
     probe: Option<Box<dyn DapProbe>>,
     state: S,
+    speed_fallback: Option<SpeedFallbackState>,
+    metrics: Metrics,
 }
 
 impl<S: ArmDebugState> Drop for ArmCommunicationInterface<S> {
@@ -241,13 +286,58 @@ impl<S: ArmDebugState> ArmCommunicationInterface<S> {
 
         Probe::from_attached_probe(RawDapAccess::into_probe(probe))
     }
+
+    /// Counts `transactions` DAP transactions, and passes the result of the transfer to the
+    /// speed fallback, which may change the speed.
+    fn track_transfer<T>(
+        &mut self,
+        transactions: usize,
+        result: Result<T, ArmError>,
+    ) -> Result<T, ArmError> {
+        self.metrics.record_dap_transactions(transactions);
+
+        if let (Some(probe), Some(fallback)) =
+            (self.probe.as_deref_mut(), self.speed_fallback.as_mut())
+        {
+            if let Some(speed_khz) = fallback.record(&result) {
+                change_speed(probe, fallback, speed_khz);
+            }
+        }
+
+        result
+    }
+}
+
+/// Sets the speed chosen by the speed fallback, and reports it.
+fn change_speed(probe: &mut dyn DapProbe, fallback: &mut SpeedFallbackState, speed_khz: u32) {
+    let previous_speed_khz = probe.speed_khz();
+
+    match probe.set_speed(speed_khz) {
+        Ok(actual_speed_khz) => {
+            fallback.set_current_speed(actual_speed_khz);
+            if actual_speed_khz < previous_speed_khz {
+                tracing::warn!(
+                    "Lowered the protocol speed to {actual_speed_khz} kHz after repeated transfer errors"
+                );
+            } else {
+                tracing::info!("Raised the protocol speed to {actual_speed_khz} kHz");
+            }
+        }
+        Err(error) => tracing::warn!("Failed to change the protocol speed: {error}"),
+    }
 }
 
 /// Helper trait for probes which offer access to ARM DAP (Debug Access Port).
 ///
 /// This is used to combine the traits, because it cannot be done in the ArmCommunicationInterface
 /// struct itself.
-pub trait DapProbe: RawDapAccess + DebugProbe {}
+pub trait DapProbe: RawDapAccess + DebugProbe {
+    /// Whether the ARM communication interface should enable overrun detection
+    /// in the debug port for this probe.
+    fn use_overrun_detect(&self) -> bool {
+        false
+    }
+}
 
 impl ArmProbeInterface for ArmCommunicationInterface<Initialized> {
     fn memory_interface(
@@ -282,6 +372,8 @@ impl ArmProbeInterface for ArmCommunicationInterface<Initialized> {
 
 impl<S: ArmDebugState> SwdSequence for ArmCommunicationInterface<S> {
     fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        // SWJ sequences reset the debug port, e.g. a line reset or a protocol switch.
+        self.state.invalidate_register_cache();
         self.probe_mut().swj_sequence(bit_len, bits)?;
 
         Ok(())
@@ -293,6 +385,10 @@ impl<S: ArmDebugState> SwdSequence for ArmCommunicationInterface<S> {
         pin_select: u32,
         pin_wait: u32,
     ) -> Result<u32, DebugProbeError> {
+        // A hardware reset may reset the APs as well.
+        if Pins(pin_select as u8).nreset() {
+            self.state.invalidate_register_cache();
+        }
         self.probe_mut().swj_pins(pin_out, pin_select, pin_wait)
     }
 }
@@ -304,6 +400,8 @@ impl ArmCommunicationInterface<Uninitialized> {
         Self {
             probe: Some(probe),
             state,
+            speed_fallback: None,
+            metrics: Metrics::default(),
         }
     }
 }
@@ -315,22 +413,53 @@ impl UninitializedArmProbe for ArmCommunicationInterface<Uninitialized> {
         dp: DpAddress,
     ) -> Result<Box<dyn ArmProbeInterface>, (Box<dyn UninitializedArmProbe>, Error)> {
         let use_overrun_detect = self.state.use_overrun_detect;
-        let probe = self.probe.take().expect("ArmCommunicationInterface is in an inconsistent state. This is a bug, please report it.");
+        let mut speed_fallback = self.speed_fallback.take();
+        let metrics = self.metrics.clone();
+        let mut probe = self.probe.take().expect("ArmCommunicationInterface is in an inconsistent state. This is a bug, please report it.");
 
-        match ArmCommunicationInterface::<Initialized>::try_setup(
-            probe,
-            sequence,
-            dp,
-            use_overrun_detect,
-        ) {
-            Ok(initialized) => Ok(Box::new(initialized)),
-            Err((probe, err)) => Err((
-                Box::new(ArmCommunicationInterface::new(probe, use_overrun_detect)),
-                Error::Arm(err),
-            )),
+        loop {
+            match ArmCommunicationInterface::<Initialized>::try_setup(
+                probe,
+                sequence.clone(),
+                dp,
+                use_overrun_detect,
+                metrics.clone(),
+            ) {
+                Ok(mut initialized) => {
+                    initialized.speed_fallback = speed_fallback;
+                    return Ok(Box::new(initialized));
+                }
+                Err((mut failed_probe, err)) => {
+                    // Retry at a lower speed if the connection failed because of transfer errors.
+                    if let Some(fallback) = speed_fallback.as_mut() {
+                        if SpeedFallbackState::is_signal_error(&err) {
+                            if let Some(speed_khz) = fallback.step_down() {
+                                change_speed(&mut *failed_probe, fallback, speed_khz);
+                                probe = failed_probe;
+                                continue;
+                            }
+                        }
+                    }
+
+                    let mut uninitialized =
+                        ArmCommunicationInterface::new(failed_probe, use_overrun_detect);
+                    uninitialized.speed_fallback = speed_fallback;
+                    uninitialized.metrics = metrics;
+                    return Err((Box::new(uninitialized), Error::Arm(err)));
+                }
+            }
         }
     }
 
+    fn set_speed_fallback(&mut self, fallback: SpeedFallback) {
+        let speed_khz = self.probe_mut().speed_khz();
+        self.speed_fallback = Some(SpeedFallbackState::new(fallback, speed_khz));
+    }
+
+    fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = metrics;
+    }
+
     fn close(self: Box<Self>) -> Probe {
         ArmCommunicationInterface::close(*self)
     }
@@ -343,6 +472,7 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         sequence: Arc<dyn ArmDebugSequence>,
         dp: DpAddress,
         use_overrun_detect: bool,
+        metrics: Metrics,
     ) -> Result<Self, (Box<dyn DapProbe>, ArmError)> {
         if let Err(err) = tracing::debug_span!("debug_port_setup")
             .in_scope(|| sequence.debug_port_setup(&mut *probe, dp))
@@ -353,6 +483,8 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         let mut initializing = Self {
             probe: Some(probe),
             state: Initialized::new(sequence, dp, use_overrun_detect),
+            speed_fallback: None,
+            metrics,
         };
 
         if let Err(err) = initializing.select_dp(dp) {
@@ -384,8 +516,10 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
             self.state.sequence.clone(),
             self.current_debug_port(),
             self.state.use_overrun_detect,
+            self.metrics.clone(),
         ) {
-            Ok(reinitialized) => {
+            Ok(mut reinitialized) => {
+                reinitialized.speed_fallback = self.speed_fallback.take();
                 let _ = std::mem::replace(self, reinitialized);
                 // Dropping the original self. Since we've taken the probe, we've ensured
                 // that the drop effects don't happen again.
@@ -498,6 +632,11 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
         } else if switched_dp {
             let sequence = self.state.sequence.clone();
 
+            // The debug port may have been powered down since it was used last.
+            if let Some(state) = self.state.dps.get_mut(&dp) {
+                state.invalidate_cache();
+            }
+
             let start_span = tracing::debug_span!("debug_port_start").entered();
             sequence.debug_port_start(self, dp)?;
             drop(start_span);
@@ -526,7 +665,7 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
             return Ok(());
         }
 
-        if bank != dp_state.current_dpbanksel {
+        if bank != dp_state.current_dpbanksel || !dp_state.select_valid {
             dp_state.current_dpbanksel = bank;
 
             let mut select = Select(0);
@@ -565,7 +704,7 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
             cache_changed = true;
         }
 
-        if cache_changed {
+        if cache_changed || !dp_state.select_valid {
             let mut select = Select(0);
 
             tracing::debug!(
@@ -585,6 +724,57 @@ impl<'interface> ArmCommunicationInterface<Initialized> {
     }
 }
 
+impl ArmCommunicationInterface<Initialized> {
+    /// Forgets all cached DP and AP registers.
+    ///
+    /// The interface caches SELECT, as well as CSW and TAR of memory APs, to skip writes which
+    /// wouldn't change them. Call this after anything which may have changed these registers
+    /// behind the interface's back, like a reset of the debug logic. The cache is invalidated
+    /// automatically after transfer errors, SWJ sequences, driving the reset pin, and resets of
+    /// the cores.
+    pub fn invalidate_register_cache(&mut self) {
+        self.state.invalidate_register_cache();
+    }
+
+    /// The register cache of an AP, if its debug port was set up already.
+    fn ap_cache(&mut self, ap: &FullyQualifiedApAddress) -> Option<&mut ApRegisterCache> {
+        self.state
+            .dps
+            .get_mut(&ap.dp())
+            .map(|state| state.ap_registers.entry(ap.clone()).or_default())
+    }
+
+    /// Keeps the cached SELECT value up to date with writes to the DP.
+    fn record_dp_write(&mut self, dp: DpAddress, address: u8, value: u32) {
+        if address & 0xf != Select::ADDRESS {
+            return;
+        }
+
+        if let Some(state) = self.state.dps.get_mut(&dp) {
+            let select = Select(value);
+            state.current_apsel = select.ap_sel();
+            state.current_apbanksel = select.ap_bank_sel();
+            state.current_dpbanksel = select.dp_bank_sel();
+            state.select_valid = true;
+        }
+    }
+
+    /// Like `track_transfer`, but also invalidates the register cache on errors, because the
+    /// state of the target is unknown after a failed transfer.
+    fn finish_transfer<T>(
+        &mut self,
+        transactions: usize,
+        result: Result<T, ArmError>,
+    ) -> Result<T, ArmError> {
+        let result = self.track_transfer(transactions, result);
+        if result.is_err() {
+            self.invalidate_register_cache();
+        }
+
+        result
+    }
+}
+
 impl FlushableArmAccess for ArmCommunicationInterface<Initialized> {
     fn flush(&mut self) -> Result<(), ArmError> {
         self.probe_mut().raw_flush()
@@ -619,8 +809,8 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         self.select_dp_and_dp_bank(dp, address)?;
         let result = self
             .probe_mut()
-            .raw_read_register(PortType::DebugPort, address & 0xf)?;
-        Ok(result)
+            .raw_read_register(PortType::DebugPort, address & 0xf);
+        self.finish_transfer(1, result)
     }
 
     fn write_raw_dp_register(
@@ -630,8 +820,11 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         value: u32,
     ) -> Result<(), ArmError> {
         self.select_dp_and_dp_bank(dp, address)?;
-        self.probe_mut()
-            .raw_write_register(PortType::DebugPort, address, value)?;
+        let result = self
+            .probe_mut()
+            .raw_write_register(PortType::DebugPort, address, value);
+        self.finish_transfer(1, result)?;
+        self.record_dp_write(dp, address, value);
         Ok(())
     }
 
@@ -640,15 +833,25 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         ap: &FullyQualifiedApAddress,
         address: u8,
     ) -> Result<u32, ArmError> {
+        if let Some(value) = self.ap_cache(ap).and_then(|cache| cache.read(address)) {
+            return Ok(value);
+        }
+
         self.select_ap_and_ap_bank(ap, address)?;
 
         let result = self
             .probe_mut()
-            .raw_read_register(PortType::AccessPort, address & 0xf)?;
+            .raw_read_register(PortType::AccessPort, address & 0xf);
+        let value = self.finish_transfer(1, result)?;
+
+        if let Some(cache) = self.ap_cache(ap) {
+            cache.record_read(address, value);
+        }
 
-        Ok(result)
+        Ok(value)
     }
 
+    #[tracing::instrument(level = "trace", skip(self, values), fields(length = values.len()))]
     fn read_raw_ap_register_repeated(
         &mut self,
         ap: &FullyQualifiedApAddress,
@@ -657,8 +860,15 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
     ) -> Result<(), ArmError> {
         self.select_ap_and_ap_bank(ap, address)?;
 
-        self.probe_mut()
-            .raw_read_block(PortType::AccessPort, address, values)?;
+        let result = self
+            .probe_mut()
+            .raw_read_block(PortType::AccessPort, address, values);
+        self.finish_transfer(values.len(), result)?;
+
+        if let Some(cache) = self.ap_cache(ap) {
+            cache.record_repeated_read(address, values);
+        }
+
         Ok(())
     }
 
@@ -668,14 +878,28 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
         address: u8,
         value: u32,
     ) -> Result<(), ArmError> {
+        if self
+            .ap_cache(ap)
+            .is_some_and(|cache| cache.write_is_redundant(address, value))
+        {
+            return Ok(());
+        }
+
         self.select_ap_and_ap_bank(ap, address)?;
 
-        self.probe_mut()
-            .raw_write_register(PortType::AccessPort, address, value)?;
+        let result = self
+            .probe_mut()
+            .raw_write_register(PortType::AccessPort, address, value);
+        self.finish_transfer(1, result)?;
+
+        if let Some(cache) = self.ap_cache(ap) {
+            cache.record_write(address, value);
+        }
 
         Ok(())
     }
 
+    #[tracing::instrument(level = "trace", skip(self, values), fields(length = values.len()))]
     fn write_raw_ap_register_repeated(
         &mut self,
         ap: &FullyQualifiedApAddress,
@@ -684,10 +908,108 @@ impl DapAccess for ArmCommunicationInterface<Initialized> {
     ) -> Result<(), ArmError> {
         self.select_ap_and_ap_bank(ap, address)?;
 
-        self.probe_mut()
-            .raw_write_block(PortType::AccessPort, address, values)?;
+        let result = self
+            .probe_mut()
+            .raw_write_block(PortType::AccessPort, address, values);
+        self.finish_transfer(values.len(), result)?;
+
+        if let Some(cache) = self.ap_cache(ap) {
+            cache.record_repeated_write(address, values);
+        }
+
         Ok(())
     }
+
+    fn ap_block_sequence(
+        &mut self,
+        ap: &FullyQualifiedApAddress,
+        steps: &mut [BlockTransfer<'_>],
+    ) -> Result<(), ArmError> {
+        let Some(first) = steps.first() else {
+            return Ok(());
+        };
+        let bank = first.addr() >> 4;
+        if steps.iter().any(|step| step.addr() >> 4 != bank) {
+            return Err(ArmError::Other(
+                "All registers of a block sequence have to be in the same bank".to_string(),
+            ));
+        }
+
+        self.select_ap_and_ap_bank(ap, first.addr())?;
+
+        let transactions = steps
+            .iter()
+            .map(|step| match step {
+                BlockTransfer::Write { .. } => 1,
+                BlockTransfer::ReadRepeated { values, .. } => values.len(),
+                BlockTransfer::WriteRepeated { values, .. } => values.len(),
+            })
+            .sum();
+        let result = self
+            .probe_mut()
+            .raw_block_sequence(PortType::AccessPort, steps);
+        self.finish_transfer(transactions, result)?;
+
+        if let Some(cache) = self.ap_cache(ap) {
+            for step in steps.iter() {
+                match step {
+                    BlockTransfer::Write { addr, value } => cache.record_write(*addr, *value),
+                    BlockTransfer::ReadRepeated { addr, values } => {
+                        cache.record_repeated_read(*addr, values)
+                    }
+                    BlockTransfer::WriteRepeated { addr, values } => {
+                        cache.record_repeated_write(*addr, values)
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_queue(&mut self, queue: &mut DapQueue) -> Result<(), ArmError> {
+        for run in queue.runs_mut() {
+            match &run[0].port {
+                QueuedPort::Dp(dp) => self.select_dp_and_dp_bank(*dp, run[0].addr)?,
+                QueuedPort::Ap(ap) => {
+                    let ap = ap.clone();
+                    self.select_ap_and_ap_bank(&ap, run[0].addr)?
+                }
+            }
+
+            let mut transfers: Vec<_> = run.iter().map(|operation| operation.transfer).collect();
+            let result = self.probe_mut().raw_transfer_batch(&mut transfers);
+            self.finish_transfer(transfers.len(), result)?;
+
+            for (operation, transfer) in run.iter_mut().zip(transfers) {
+                operation.transfer = transfer;
+
+                match (&operation.port, transfer) {
+                    (QueuedPort::Dp(dp), RawDapTransfer::Write { value, .. }) => {
+                        self.record_dp_write(*dp, operation.addr, value)
+                    }
+                    (QueuedPort::Dp(_), RawDapTransfer::Read { .. }) => {}
+                    (QueuedPort::Ap(ap), RawDapTransfer::Read { value, .. }) => {
+                        if let Some(cache) = self.ap_cache(ap) {
+                            cache.record_read(operation.addr, value);
+                        }
+                    }
+                    (QueuedPort::Ap(ap), RawDapTransfer::Write { value, .. }) => {
+                        if let Some(cache) = self.ap_cache(ap) {
+                            cache.record_write(operation.addr, value);
+                        }
+                    }
+                }
+            }
+        }
+        queue.set_executed();
+
+        Ok(())
+    }
+
+    fn metrics(&self) -> Option<&Metrics> {
+        Some(&self.metrics)
+    }
 }
 
 /// Information about the chip target we are currently attached to.
@@ -705,6 +1027,11 @@ pub struct ArmChipInfo {
 }
 
 impl ArmCommunicationInterface<Initialized> {
+    /// Gets the UART interface of the debug probe, if it has one.
+    pub fn uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        self.probe_mut().get_uart_interface_mut()
+    }
+
     /// Reads the chip info from the romtable of given debug port.
     pub fn read_chip_info_from_rom_table(
         &mut self,
@@ -766,3 +1093,65 @@ pub trait FlushableArmAccess {
     /// Flush all remaining commands if the target driver implements batching.
     fn flush(&mut self) -> Result<(), ArmError>;
 }
+
+#[cfg(test)]
+impl ArmCommunicationInterface<Initialized> {
+    /// Creates an interface on a replayed probe, with the default debug port already started.
+    pub(crate) fn from_replay(probe: crate::probe::recording::ReplayProbe) -> Self {
+        let mut state = Initialized::new(DefaultArmSequence::create(), DpAddress::Default, false);
+        state.dps.insert(DpAddress::Default, DpState::new());
+
+        ArmCommunicationInterface {
+            probe: Some(Box::new(probe)),
+            state,
+            speed_fallback: None,
+            metrics: Metrics::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::probe::recording::ReplayProbe;
+
+    // Reads IDR of AP 0, writes CSW twice, asserts the reset pin and writes CSW again.
+    // The second write is skipped by the cache, the third one has to reach the AP.
+    const RESET_RECORDING: &str = r#"{"version":1,"probe_name":"Test probe","speed_khz":1000,"protocol":"Swd","use_overrun_detect":false}
+{"op":"write_register","port":"DebugPort","addr":8,"value":240,"response":"done"}
+{"op":"read_register","port":"AccessPort","addr":12,"response":{"value":611778577}}
+{"op":"write_register","port":"DebugPort","addr":8,"value":0,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":0,"value":587202578,"response":"done"}
+{"op":"swj_pins","pin_out":0,"pin_select":128,"pin_wait":0,"response":{"value":0}}
+{"op":"write_register","port":"DebugPort","addr":8,"value":0,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":0,"value":587202578,"response":"done"}
+{"op":"read_register","port":"DebugPort","addr":0,"response":{"value":731911287}}
+"#;
+
+    #[test]
+    fn reset_invalidates_register_cache() {
+        let probe = ReplayProbe::new(RESET_RECORDING.as_bytes()).unwrap();
+        let mut interface = ArmCommunicationInterface::from_replay(probe);
+
+        let ap = FullyQualifiedApAddress::v1_with_default_dp(0);
+        const CSW: u32 = 0x2300_0012;
+
+        interface.read_raw_ap_register(&ap, 0xfc).unwrap();
+        interface.write_raw_ap_register(&ap, 0x00, CSW).unwrap();
+        interface.write_raw_ap_register(&ap, 0x00, CSW).unwrap();
+
+        let mut n_reset = Pins(0);
+        n_reset.set_nreset(true);
+        interface.swj_pins(0, n_reset.0 as u32, 0).unwrap();
+
+        interface.write_raw_ap_register(&ap, 0x00, CSW).unwrap();
+
+        // Diverges from the recording if the last CSW write was skipped.
+        assert_eq!(
+            interface
+                .read_raw_dp_register(DpAddress::Default, 0)
+                .unwrap(),
+            0x2ba0_1477
+        );
+    }
+}
@@ -2,6 +2,7 @@
 
 mod dwt;
 mod itm;
+mod mtb;
 mod scs;
 mod swo;
 mod tmc;
@@ -19,6 +20,7 @@ use crate::{
 
 pub use self::itm::Itm;
 pub use dwt::Dwt;
+pub use mtb::MicroTraceBuffer;
 pub use scs::Scs;
 pub use swo::Swo;
 pub use tmc::TraceMemoryController;
@@ -171,8 +173,7 @@ fn configure_tpiu(
     let mut tpiu = Tpiu::new(interface, component);
 
     tpiu.set_port_size(1)?;
-    let prescaler = (config.tpiu_clk() / config.baud()) - 1;
-    tpiu.set_prescaler(prescaler)?;
+    tpiu.set_prescaler(config.prescaler())?;
     match config.mode() {
         SwoMode::Manchester => tpiu.set_pin_protocol(1)?,
         SwoMode::Uart => tpiu.set_pin_protocol(2)?,
@@ -223,8 +224,7 @@ pub(crate) fn setup_tracing(
                 let mut swo = Swo::new(interface, peripheral);
                 swo.unlock()?;
 
-                let prescaler = (config.tpiu_clk() / config.baud()) - 1;
-                swo.set_prescaler(prescaler)?;
+                swo.set_prescaler(config.prescaler())?;
 
                 match config.mode() {
                     SwoMode::Manchester => swo.set_pin_protocol(1)?,
@@ -0,0 +1,138 @@
+//! Module for interacting with the Micro Trace Buffer (MTB) of Cortex-M0+ cores.
+//!
+//! The MTB records the taken branches of the core into a region of the on-chip SRAM, from where
+//! they can be read through the debug interface. Each packet is two words: the source address of
+//! the branch, with the A-bit in bit 0, and the destination address, with the S-bit in bit 0.
+
+use crate::{
+    architecture::arm::{
+        component::DebugComponentInterface, memory::CoresightComponent, ArmError, ArmProbeInterface,
+    },
+    memory_mapped_bitfield_register, Error,
+};
+
+/// The Micro Trace Buffer.
+pub struct MicroTraceBuffer<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut dyn ArmProbeInterface,
+}
+
+impl<'a> MicroTraceBuffer<'a> {
+    /// Construct a new Micro Trace Buffer.
+    pub fn new(
+        interface: &'a mut dyn ArmProbeInterface,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Self {
+            component,
+            interface,
+        }
+    }
+
+    /// Get the address of the SRAM the trace is written to.
+    ///
+    /// Positions in the trace buffer are offsets from this address.
+    pub fn sram_base(&mut self) -> Result<u32, ArmError> {
+        Ok(Base::load(self.component, self.interface)?.0)
+    }
+
+    /// Enable tracing into the `size` bytes at `offset` from the SRAM base.
+    ///
+    /// # Args
+    /// * `offset` - The start of the trace buffer, aligned to `size`.
+    /// * `size` - The size of the trace buffer, a power of two of at least 16 bytes.
+    /// * `watermark` - When the trace reaches this offset, the core is halted, so the trace can
+    ///   be read before the buffer wraps.
+    pub fn enable(&mut self, offset: u32, size: u32, watermark: Option<u32>) -> Result<(), Error> {
+        if !size.is_power_of_two() || size < 16 || offset % size != 0 {
+            return Err(Error::Other(format!(
+                "Invalid MTB buffer of {size} bytes at offset {offset:#x}"
+            )));
+        }
+
+        self.set_position(offset)?;
+
+        let mut flow = Flow(0);
+        if let Some(watermark) = watermark {
+            flow.set_watermark(watermark >> 3);
+            flow.set_autohalt(true);
+        }
+        flow.store(self.component, self.interface)?;
+
+        let mut master = Master(0);
+        master.set_mask(size.trailing_zeros() - 4);
+        master.set_en(true);
+        master.store(self.component, self.interface)?;
+
+        Ok(())
+    }
+
+    /// Disable tracing.
+    pub fn disable(&mut self) -> Result<(), Error> {
+        let mut master = Master::load(self.component, self.interface)?;
+        master.set_en(false);
+        master.store(self.component, self.interface)?;
+
+        Flow(0).store(self.component, self.interface)?;
+
+        Ok(())
+    }
+
+    /// Get the offset the next packet is written to, and if the trace buffer wrapped around.
+    pub fn position(&mut self) -> Result<(u32, bool), ArmError> {
+        let position = Position::load(self.component, self.interface)?;
+        Ok((position.pointer() << 3, position.wrap()))
+    }
+
+    /// Set the offset the next packet is written to, and clear the wrap flag.
+    pub fn set_position(&mut self, offset: u32) -> Result<(), ArmError> {
+        let mut position = Position(0);
+        position.set_pointer(offset >> 3);
+        position.store(self.component, self.interface)
+    }
+}
+
+memory_mapped_bitfield_register! {
+    pub struct Position(u32);
+    0x000, "MTB_POSITION",
+    impl From;
+
+    pub pointer, set_pointer: 31, 3;
+    pub wrap, set_wrap: 2;
+}
+
+impl DebugComponentInterface for Position {}
+
+memory_mapped_bitfield_register! {
+    pub struct Master(u32);
+    0x004, "MTB_MASTER",
+    impl From;
+
+    pub en, set_en: 31;
+    pub haltreq, set_haltreq: 9;
+    pub tstopen, set_tstopen: 6;
+    pub tstarten, set_tstarten: 5;
+    pub mask, set_mask: 4, 0;
+}
+
+impl DebugComponentInterface for Master {}
+
+memory_mapped_bitfield_register! {
+    pub struct Flow(u32);
+    0x008, "MTB_FLOW",
+    impl From;
+
+    pub watermark, set_watermark: 31, 3;
+    pub autohalt, set_autohalt: 1;
+    pub autostop, set_autostop: 0;
+}
+
+impl DebugComponentInterface for Flow {}
+
+memory_mapped_bitfield_register! {
+    pub struct Base(u32);
+    0x00C, "MTB_BASE",
+    impl From;
+}
+
+impl DebugComponentInterface for Base {}
@@ -2,10 +2,13 @@
 
 use super::{registers::cortex_m::*, CortexMState, Dfsr};
 use crate::{
-    architecture::arm::{memory::ArmMemoryInterface, sequences::ArmDebugSequence, ArmError},
-    core::{CoreRegisters, RegisterId, RegisterValue, VectorCatchCondition},
+    architecture::arm::{
+        memory::ArmMemoryInterface, sequences::ArmDebugSequence, ArmError, SwoAccess,
+    },
+    core::{CoreRegisters, InterruptMasking, RegisterId, RegisterValue, VectorCatchCondition},
     error::Error,
     memory::{valid_32bit_address, CoreMemoryInterface},
+    probe::UartAccess,
     Architecture, BreakpointCause, CoreInformation, CoreInterface, CoreRegister, CoreStatus,
     CoreType, HaltReason, InstructionSet, MemoryInterface, MemoryMappedRegister,
 };
@@ -608,6 +611,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv6m, None)?;
+        self.memory.invalidate_register_cache();
         Ok(())
     }
 
@@ -616,6 +620,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
 
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv6m, None)?;
+        self.memory.invalidate_register_cache();
 
         // Update core status
         let _ = self.status()?;
@@ -639,6 +644,10 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
     }
 
     fn step(&mut self) -> Result<CoreInformation, Error> {
+        self.step_with(InterruptMasking::Masked)
+    }
+
+    fn step_with(&mut self, interrupts: InterruptMasking) -> Result<CoreInformation, Error> {
         // First check if we stopped on a breakpoint, because this requires special handling before we can continue.
         let breakpoint_at_pc = if matches!(
             self.state.current_state,
@@ -657,7 +666,7 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         value.set_c_step(true);
         value.set_c_halt(false);
         value.set_c_debugen(true);
-        value.set_c_maskints(true);
+        value.set_c_maskints(interrupts == InterruptMasking::Masked);
         value.enable_write();
 
         self.memory
@@ -701,6 +710,15 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         }
     }
 
+    fn read_core_regs(&mut self, addresses: &[RegisterId]) -> Result<Vec<RegisterValue>, Error> {
+        if self.state.current_state.is_halted() {
+            let values = super::cortex_m::read_core_regs(&mut *self.memory, addresses)?;
+            Ok(values.into_iter().map(RegisterValue::from).collect())
+        } else {
+            Err(Error::Arm(ArmError::CoreNotHalted))
+        }
+    }
+
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
         if self.state.current_state.is_halted() {
             super::cortex_m::write_core_reg(&mut *self.memory, address, value.try_into()?)?;
@@ -828,6 +846,18 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
         Ok(InstructionSet::Thumb2)
     }
 
+    fn uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        self.memory
+            .get_arm_communication_interface()
+            .ok()?
+            .uart_interface_mut()
+    }
+
+    fn swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        let interface = self.memory.get_arm_communication_interface().ok()?;
+        Some(interface)
+    }
+
     fn fpu_support(&mut self) -> Result<bool, Error> {
         Ok(false)
     }
@@ -875,6 +905,13 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
             VectorCatchCondition::SecureFault => {
                 return Err(Error::Arm(ArmError::ArchitectureRequired(&["ARMv8"])));
             }
+            VectorCatchCondition::BusFault
+            | VectorCatchCondition::MemManage
+            | VectorCatchCondition::UsageFault => {
+                return Err(Error::Arm(ArmError::ArchitectureRequired(&[
+                    "ARMv7", "ARMv8",
+                ])));
+            }
             VectorCatchCondition::All => {
                 demcr.set_vc_harderr(true);
                 demcr.set_vc_corereset(true);
@@ -894,6 +931,13 @@ impl<'probe> CoreInterface for Armv6m<'probe> {
             VectorCatchCondition::SecureFault => {
                 return Err(Error::Arm(ArmError::ArchitectureRequired(&["ARMv8"])));
             }
+            VectorCatchCondition::BusFault
+            | VectorCatchCondition::MemManage
+            | VectorCatchCondition::UsageFault => {
+                return Err(Error::Arm(ArmError::ArchitectureRequired(&[
+                    "ARMv7", "ARMv8",
+                ])));
+            }
             VectorCatchCondition::All => {
                 demcr.set_vc_harderr(false);
                 demcr.set_vc_corereset(false);
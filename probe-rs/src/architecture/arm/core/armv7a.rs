@@ -407,6 +407,7 @@ impl<'probe> CoreInterface for Armv7a<'probe> {
             crate::CoreType::Armv7a,
             Some(self.base_address),
         )?;
+        self.memory.invalidate_register_cache();
 
         // Reset our cached values
         self.reset_register_cache();
@@ -425,6 +426,7 @@ impl<'probe> CoreInterface for Armv7a<'probe> {
             crate::CoreType::Armv7a,
             Some(self.base_address),
         )?;
+        self.memory.invalidate_register_cache();
 
         // Request halt
         let address = Dbgdrcr::get_mmio_address_from_base(self.base_address)?;
@@ -1,6 +1,7 @@
 //! Register types and the core interface for armv7-M
 
 use super::{
+    cache::{Caches, CoherentMemory},
     cortex_m::Mvfr0,
     registers::cortex_m::{
         CORTEX_M_CORE_REGISTERS, CORTEX_M_WITH_FP_CORE_REGISTERS, FP, PC, RA, SP,
@@ -10,19 +11,21 @@ use super::{
 use crate::{
     architecture::arm::{
         core::registers::cortex_m::XPSR, memory::ArmMemoryInterface, sequences::ArmDebugSequence,
-        ArmError,
+        ArmError, SwoAccess,
     },
     core::{
         Architecture, CoreInformation, CoreInterface, CoreRegisters, CoreStatus, HaltReason,
-        MemoryMappedRegister, RegisterId, RegisterValue, VectorCatchCondition,
+        InterruptMasking, MemoryMappedRegister, RegisterId, RegisterValue, VectorCatchCondition,
     },
     error::Error,
     memory::{valid_32bit_address, CoreMemoryInterface},
+    probe::UartAccess,
     BreakpointCause, CoreRegister, CoreType, InstructionSet, MemoryInterface,
 };
 use bitfield::bitfield;
 use std::{
     mem::size_of,
+    ops::Range,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -587,7 +590,7 @@ impl FpRev2CompX {
 
 /// The state of a core that can be used to persist core state across calls to multiple different cores.
 pub struct Armv7m<'probe> {
-    memory: Box<dyn ArmMemoryInterface + 'probe>,
+    memory: CoherentMemory<'probe>,
 
     state: &'probe mut CortexMState,
 
@@ -626,12 +629,13 @@ impl<'probe> Armv7m<'probe> {
 
             state.current_state = core_state;
             state.fp_present = Mvfr0(memory.read_word_32(Mvfr0::get_mmio_address())?).fp_present();
+            state.caches = Caches::detect(&mut *memory)?;
 
             state.initialize();
         }
 
         Ok(Self {
-            memory,
+            memory: CoherentMemory::new(memory, state.caches),
             state,
             sequence,
         })
@@ -787,6 +791,9 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
             .write_word_32(Dhcsr::get_mmio_address(), dhcsr.into())?;
         self.memory.flush()?;
 
+        // The core may change CCR while it runs.
+        self.memory.forget_ccr();
+
         // We assume that the core is running now
         self.set_core_status(CoreStatus::Running);
 
@@ -798,6 +805,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv7m, None)?;
+        self.memory.invalidate_register_cache();
         Ok(())
     }
 
@@ -808,6 +816,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
 
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv7m, None)?;
+        self.memory.invalidate_register_cache();
 
         // Update core status
         let _ = self.status()?;
@@ -831,6 +840,10 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
     }
 
     fn step(&mut self) -> Result<CoreInformation, Error> {
+        self.step_with(InterruptMasking::Masked)
+    }
+
+    fn step_with(&mut self, interrupts: InterruptMasking) -> Result<CoreInformation, Error> {
         // First check if we stopped on a breakpoint, because this requires special handling before we can continue.
         let breakpoint_at_pc = if matches!(
             self.state.current_state,
@@ -849,8 +862,9 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         if !dhcsr.c_debugen() {
             tracing::warn!("Attempting to STEP while DHCSR->C_DEBUGEN is false");
         }
-        if !dhcsr.c_maskints() {
-            dhcsr.set_c_maskints(true); // This must be reset to false when we run() again.
+        let mask_interrupts = interrupts == InterruptMasking::Masked;
+        if dhcsr.c_maskints() != mask_interrupts {
+            dhcsr.set_c_maskints(mask_interrupts); // This must be reset to false when we run() again.
             dhcsr.enable_write();
             self.memory
                 .write_word_32(Dhcsr::get_mmio_address(), dhcsr.into())?;
@@ -866,6 +880,7 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
             .write_word_32(Dhcsr::get_mmio_address(), dhcsr.into())?;
         self.memory.flush()?;
 
+        self.memory.forget_ccr();
         self.wait_for_core_halted(Duration::from_millis(100))?;
 
         // Try to read the new program counter.
@@ -903,6 +918,15 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         }
     }
 
+    fn read_core_regs(&mut self, addresses: &[RegisterId]) -> Result<Vec<RegisterValue>, Error> {
+        if self.state.current_state.is_halted() {
+            let values = super::cortex_m::read_core_regs(&mut *self.memory, addresses)?;
+            Ok(values.into_iter().map(RegisterValue::from).collect())
+        } else {
+            Err(Error::Arm(ArmError::CoreNotHalted))
+        }
+    }
+
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
         if self.state.current_state.is_halted() {
             super::cortex_m::write_core_reg(&mut *self.memory, address, value.try_into()?)?;
@@ -1046,6 +1070,11 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         self.state.hw_breakpoints_enabled
     }
 
+    fn invalidate_caches(&mut self, range: Range<u64>) -> Result<(), Error> {
+        self.memory.invalidate(range)?;
+        Ok(())
+    }
+
     fn architecture(&self) -> Architecture {
         Architecture::Arm
     }
@@ -1058,6 +1087,18 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         Ok(InstructionSet::Thumb2)
     }
 
+    fn uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        self.memory
+            .get_arm_communication_interface()
+            .ok()?
+            .uart_interface_mut()
+    }
+
+    fn swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        let interface = self.memory.get_arm_communication_interface().ok()?;
+        Some(interface)
+    }
+
     fn fpu_support(&mut self) -> Result<bool, Error> {
         Ok(self.state.fp_present)
     }
@@ -1100,12 +1141,24 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         match condition {
             VectorCatchCondition::HardFault => demcr.set_vc_harderr(true),
             VectorCatchCondition::CoreReset => demcr.set_vc_corereset(true),
+            VectorCatchCondition::BusFault => demcr.set_vc_buserr(true),
+            VectorCatchCondition::MemManage => demcr.set_vc_mmerr(true),
+            VectorCatchCondition::UsageFault => {
+                demcr.set_vc_staterr(true);
+                demcr.set_vc_chkerr(true);
+                demcr.set_vc_nocperr(true);
+            }
             VectorCatchCondition::SecureFault => {
                 return Err(Error::Arm(ArmError::ArchitectureRequired(&["ARMv8"])));
             }
             VectorCatchCondition::All => {
                 demcr.set_vc_harderr(true);
                 demcr.set_vc_corereset(true);
+                demcr.set_vc_buserr(true);
+                demcr.set_vc_mmerr(true);
+                demcr.set_vc_staterr(true);
+                demcr.set_vc_chkerr(true);
+                demcr.set_vc_nocperr(true);
             }
         };
 
@@ -1119,12 +1172,24 @@ impl<'probe> CoreInterface for Armv7m<'probe> {
         match condition {
             VectorCatchCondition::HardFault => demcr.set_vc_harderr(false),
             VectorCatchCondition::CoreReset => demcr.set_vc_corereset(false),
+            VectorCatchCondition::BusFault => demcr.set_vc_buserr(false),
+            VectorCatchCondition::MemManage => demcr.set_vc_mmerr(false),
+            VectorCatchCondition::UsageFault => {
+                demcr.set_vc_staterr(false);
+                demcr.set_vc_chkerr(false);
+                demcr.set_vc_nocperr(false);
+            }
             VectorCatchCondition::SecureFault => {
                 return Err(Error::Arm(ArmError::ArchitectureRequired(&["ARMv8"])));
             }
             VectorCatchCondition::All => {
                 demcr.set_vc_harderr(false);
                 demcr.set_vc_corereset(false);
+                demcr.set_vc_buserr(false);
+                demcr.set_vc_mmerr(false);
+                demcr.set_vc_staterr(false);
+                demcr.set_vc_chkerr(false);
+                demcr.set_vc_nocperr(false);
             }
         };
 
@@ -1138,10 +1203,10 @@ impl CoreMemoryInterface for Armv7m<'_> {
     type ErrorType = ArmError;
 
     fn memory(&self) -> &dyn MemoryInterface<Self::ErrorType> {
-        self.memory.as_memory_interface()
+        &self.memory
     }
     fn memory_mut(&mut self) -> &mut dyn MemoryInterface<Self::ErrorType> {
-        self.memory.as_memory_interface_mut()
+        &mut self.memory
     }
 }
 
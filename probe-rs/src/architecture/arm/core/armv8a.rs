@@ -1164,6 +1164,7 @@ impl<'probe> CoreInterface for Armv8a<'probe> {
             crate::CoreType::Armv8a,
             Some(self.base_address),
         )?;
+        self.memory.invalidate_register_cache();
 
         // Reset our cached values
         self.reset_register_cache();
@@ -1182,6 +1183,7 @@ impl<'probe> CoreInterface for Armv8a<'probe> {
             crate::CoreType::Armv8a,
             Some(self.base_address),
         )?;
+        self.memory.invalidate_register_cache();
 
         // Release from reset
         self.sequence.reset_catch_clear(
@@ -1,6 +1,7 @@
 //! Register types and the core interface for armv8-M
 
 use super::{
+    cache::{Caches, CoherentMemory},
     cortex_m::{IdPfr1, Mvfr0},
     registers::cortex_m::{
         CORTEX_M_CORE_REGISTERS, CORTEX_M_WITH_FP_CORE_REGISTERS, FP, PC, RA, SP,
@@ -10,24 +11,26 @@ use super::{
 use crate::{
     architecture::arm::{
         core::registers::cortex_m::XPSR, memory::ArmMemoryInterface, sequences::ArmDebugSequence,
-        ArmError,
+        ArmError, SwoAccess,
     },
-    core::{CoreRegisters, RegisterId, RegisterValue, VectorCatchCondition},
+    core::{CoreRegisters, InterruptMasking, RegisterId, RegisterValue, VectorCatchCondition},
     error::Error,
     memory::{valid_32bit_address, CoreMemoryInterface},
+    probe::UartAccess,
     Architecture, BreakpointCause, CoreInformation, CoreInterface, CoreRegister, CoreStatus,
     CoreType, HaltReason, InstructionSet, MemoryInterface, MemoryMappedRegister,
 };
 use bitfield::bitfield;
 use std::{
     mem::size_of,
+    ops::Range,
     sync::Arc,
     time::{Duration, Instant},
 };
 
 /// The state of a core that can be used to persist core state across calls to multiple different cores.
 pub struct Armv8m<'probe> {
-    memory: Box<dyn ArmMemoryInterface + 'probe>,
+    memory: CoherentMemory<'probe>,
 
     state: &'probe mut CortexMState,
 
@@ -68,12 +71,13 @@ impl<'probe> Armv8m<'probe> {
 
             state.current_state = core_state;
             state.fp_present = Mvfr0(memory.read_word_32(Mvfr0::get_mmio_address())?).fp_present();
+            state.caches = Caches::detect(&mut *memory)?;
 
             state.initialize();
         }
 
         Ok(Self {
-            memory,
+            memory: CoherentMemory::new(memory, state.caches),
             state,
             sequence,
         })
@@ -222,6 +226,9 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
             .write_word_32(Dhcsr::get_mmio_address(), value.into())?;
         self.memory.flush()?;
 
+        // The core may change CCR while it runs.
+        self.memory.forget_ccr();
+
         // We assume that the core is running now
         self.set_core_status(CoreStatus::Running);
 
@@ -233,6 +240,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv8m, None)?;
+        self.memory.invalidate_register_cache();
         Ok(())
     }
 
@@ -243,6 +251,7 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
 
         self.sequence
             .reset_system(&mut *self.memory, crate::CoreType::Armv8m, None)?;
+        self.memory.invalidate_register_cache();
 
         // Update core status
         let _ = self.status()?;
@@ -266,6 +275,10 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
     }
 
     fn step(&mut self) -> Result<CoreInformation, Error> {
+        self.step_with(InterruptMasking::Masked)
+    }
+
+    fn step_with(&mut self, interrupts: InterruptMasking) -> Result<CoreInformation, Error> {
         // First check if we stopped on a breakpoint, because this requires special handling before we can continue.
         let breakpoint_at_pc = if matches!(
             self.state.current_state,
@@ -284,13 +297,14 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         value.set_c_step(true);
         value.set_c_halt(false);
         value.set_c_debugen(true);
-        value.set_c_maskints(true);
+        value.set_c_maskints(interrupts == InterruptMasking::Masked);
         value.enable_write();
 
         self.memory
             .write_word_32(Dhcsr::get_mmio_address(), value.into())?;
         self.memory.flush()?;
 
+        self.memory.forget_ccr();
         self.wait_for_core_halted(Duration::from_millis(100))?;
 
         // Try to read the new program counter.
@@ -328,6 +342,15 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         }
     }
 
+    fn read_core_regs(&mut self, addresses: &[RegisterId]) -> Result<Vec<RegisterValue>, Error> {
+        if self.state.current_state.is_halted() {
+            let values = super::cortex_m::read_core_regs(&mut *self.memory, addresses)?;
+            Ok(values.into_iter().map(RegisterValue::from).collect())
+        } else {
+            Err(Error::Arm(ArmError::CoreNotHalted))
+        }
+    }
+
     fn write_core_reg(&mut self, address: RegisterId, value: RegisterValue) -> Result<(), Error> {
         if self.state.current_state.is_halted() {
             super::cortex_m::write_core_reg(&mut *self.memory, address, value.try_into()?)?;
@@ -437,6 +460,11 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         self.state.hw_breakpoints_enabled
     }
 
+    fn invalidate_caches(&mut self, range: Range<u64>) -> Result<(), Error> {
+        self.memory.invalidate(range)?;
+        Ok(())
+    }
+
     fn architecture(&self) -> Architecture {
         Architecture::Arm
     }
@@ -449,6 +477,18 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         Ok(InstructionSet::Thumb2)
     }
 
+    fn uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        self.memory
+            .get_arm_communication_interface()
+            .ok()?
+            .uart_interface_mut()
+    }
+
+    fn swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        let interface = self.memory.get_arm_communication_interface().ok()?;
+        Some(interface)
+    }
+
     fn fpu_support(&mut self) -> Result<bool, Error> {
         Ok(self.state.fp_present)
     }
@@ -493,6 +533,13 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         match condition {
             VectorCatchCondition::HardFault => demcr.set_vc_harderr(true),
             VectorCatchCondition::CoreReset => demcr.set_vc_corereset(true),
+            VectorCatchCondition::BusFault => demcr.set_vc_buserr(true),
+            VectorCatchCondition::MemManage => demcr.set_vc_mmerr(true),
+            VectorCatchCondition::UsageFault => {
+                demcr.set_vc_staterr(true);
+                demcr.set_vc_chkerr(true);
+                demcr.set_vc_nocperr(true);
+            }
             VectorCatchCondition::SecureFault => {
                 if !idpfr1.security_present() {
                     return Err(Error::Arm(ArmError::ExtensionRequired(&["Security"])));
@@ -502,6 +549,11 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
             VectorCatchCondition::All => {
                 demcr.set_vc_harderr(true);
                 demcr.set_vc_corereset(true);
+                demcr.set_vc_buserr(true);
+                demcr.set_vc_mmerr(true);
+                demcr.set_vc_staterr(true);
+                demcr.set_vc_chkerr(true);
+                demcr.set_vc_nocperr(true);
                 if idpfr1.security_present() {
                     demcr.set_vc_sferr(true);
                 }
@@ -519,6 +571,13 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
         match condition {
             VectorCatchCondition::HardFault => demcr.set_vc_harderr(false),
             VectorCatchCondition::CoreReset => demcr.set_vc_corereset(false),
+            VectorCatchCondition::BusFault => demcr.set_vc_buserr(false),
+            VectorCatchCondition::MemManage => demcr.set_vc_mmerr(false),
+            VectorCatchCondition::UsageFault => {
+                demcr.set_vc_staterr(false);
+                demcr.set_vc_chkerr(false);
+                demcr.set_vc_nocperr(false);
+            }
             VectorCatchCondition::SecureFault => {
                 if !idpfr1.security_present() {
                     return Err(Error::Arm(ArmError::ExtensionRequired(&["Security"])));
@@ -528,6 +587,11 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
             VectorCatchCondition::All => {
                 demcr.set_vc_harderr(false);
                 demcr.set_vc_corereset(false);
+                demcr.set_vc_buserr(false);
+                demcr.set_vc_mmerr(false);
+                demcr.set_vc_staterr(false);
+                demcr.set_vc_chkerr(false);
+                demcr.set_vc_nocperr(false);
                 if idpfr1.security_present() {
                     demcr.set_vc_sferr(false);
                 }
@@ -544,10 +608,10 @@ impl CoreMemoryInterface for Armv8m<'_> {
     type ErrorType = ArmError;
 
     fn memory(&self) -> &dyn MemoryInterface<Self::ErrorType> {
-        self.memory.as_memory_interface()
+        &self.memory
     }
     fn memory_mut(&mut self) -> &mut dyn MemoryInterface<Self::ErrorType> {
-        self.memory.as_memory_interface_mut()
+        &mut self.memory
     }
 }
 
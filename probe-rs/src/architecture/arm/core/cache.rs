@@ -0,0 +1,435 @@
+//! Cache maintenance for Cortex-M cores with caches, like the Cortex-M7 and Cortex-M55.
+//!
+//! The debugger accesses memory past the caches of these cores. Without maintenance, it reads
+//! stale data where the core has dirty cache lines, and the core keeps executing and reading
+//! stale cache lines after the debugger wrote to memory, e.g. to set a software breakpoint.
+
+use std::ops::{Deref, DerefMut, Range};
+
+use crate::{
+    architecture::arm::{memory::ArmMemoryInterface, ArmError},
+    memory_mapped_bitfield_register, MemoryInterface, MemoryMappedRegister,
+};
+
+/// Invalidate the whole instruction cache.
+const ICIALLU: u64 = 0xE000_EF50;
+/// Invalidate a data cache line by address.
+const DCIMVAC: u64 = 0xE000_EF5C;
+/// Clean a data cache line by address.
+const DCCMVAC: u64 = 0xE000_EF68;
+
+/// The start of the system address space, which is never cached.
+const SYSTEM_SPACE: u64 = 0xE000_0000;
+
+memory_mapped_bitfield_register! {
+    /// CPUID Base Register
+    pub struct Cpuid(u32);
+    0xE000_ED00, "CPUID",
+    impl From;
+    pub implementer, _: 31, 24;
+    pub partno, _: 15, 4;
+}
+
+memory_mapped_bitfield_register! {
+    /// Configuration and Control Register
+    pub struct Ccr(u32);
+    0xE000_ED14, "CCR",
+    impl From;
+    /// Whether the instruction cache is enabled.
+    pub ic, _: 17;
+    /// Whether the data cache is enabled.
+    pub dc, _: 16;
+}
+
+memory_mapped_bitfield_register! {
+    /// Cache Level ID Register
+    pub struct Clidr(u32);
+    0xE000_ED78, "CLIDR",
+    impl From;
+    /// The caches of level 1: `0b001` instruction, `0b010` data, `0b011` both.
+    pub ctype1, _: 2, 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// Cache Type Register
+    pub struct Ctr(u32);
+    0xE000_ED7C, "CTR",
+    impl From;
+    /// Log2 of the number of words in the smallest data cache line.
+    pub dminline, _: 19, 16;
+}
+
+/// The caches of a core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Caches {
+    instruction: bool,
+    data: bool,
+    line_size: u64,
+}
+
+impl Caches {
+    /// Detects the caches of the core. Returns `None` if it has none.
+    pub(crate) fn detect(memory: &mut dyn ArmMemoryInterface) -> Result<Option<Self>, ArmError> {
+        /// The Cortex-M cores which can have caches: Cortex-M7, Cortex-M55 and Cortex-M85.
+        const PARTS_WITH_CACHES: [u32; 3] = [0xC27, 0xD22, 0xD23];
+
+        // The cache identification registers are reserved on other cores.
+        let cpuid = Cpuid(memory.read_word_32(Cpuid::get_mmio_address())?);
+        if cpuid.implementer() != 0x41 || !PARTS_WITH_CACHES.contains(&cpuid.partno()) {
+            return Ok(None);
+        }
+
+        let clidr = Clidr(memory.read_word_32(Clidr::get_mmio_address())?);
+        let ctr = Ctr(memory.read_word_32(Ctr::get_mmio_address())?);
+        let caches = Self {
+            instruction: clidr.ctype1() & 0b001 != 0,
+            data: clidr.ctype1() & 0b010 != 0,
+            line_size: 4 << ctr.dminline(),
+        };
+        tracing::debug!("Detected caches: {:?}", caches);
+
+        Ok((caches.instruction || caches.data).then_some(caches))
+    }
+
+    /// Returns the addresses of the data cache lines covering the range.
+    fn lines(&self, range: Range<u64>) -> impl Iterator<Item = u64> {
+        (range.start & !(self.line_size - 1)..range.end).step_by(self.line_size as usize)
+    }
+}
+
+/// The memory interface of a Cortex-M core, which keeps the caches of the core coherent with
+/// the memory accesses of the debugger.
+///
+/// Before a read or write, the dirty data cache lines of the range are cleaned. After a write, the
+/// data cache lines of the range are invalidated, and the instruction cache, if the caches are
+/// enabled.
+///
+/// CCR is read once and cached until it is written, the core is reset or the core resumes, so
+/// not every access has to read it first.
+pub(crate) struct CoherentMemory<'probe> {
+    memory: Box<dyn ArmMemoryInterface + 'probe>,
+    caches: Option<Caches>,
+    ccr: Option<Ccr>,
+}
+
+impl<'probe> CoherentMemory<'probe> {
+    pub(crate) fn new(
+        memory: Box<dyn ArmMemoryInterface + 'probe>,
+        caches: Option<Caches>,
+    ) -> Self {
+        Self {
+            memory,
+            caches,
+            ccr: None,
+        }
+    }
+
+    /// Forgets the cached CCR, after the core ran and may have changed it.
+    pub(crate) fn forget_ccr(&mut self) {
+        self.ccr = None;
+    }
+
+    /// Invalidates the register caches, after the state of the target is unknown, e.g. after a
+    /// reset.
+    ///
+    /// See [`ArmMemoryInterface::invalidate_register_cache`].
+    pub(crate) fn invalidate_register_cache(&mut self) {
+        self.forget_ccr();
+        self.memory.invalidate_register_cache();
+    }
+
+    /// Returns CCR, reading it only if it isn't cached.
+    fn ccr(&mut self) -> Result<Ccr, ArmError> {
+        if let Some(ccr) = self.ccr {
+            return Ok(ccr);
+        }
+
+        let ccr = Ccr(self.memory.read_word_32(Ccr::get_mmio_address())?);
+        self.ccr = Some(ccr);
+        Ok(ccr)
+    }
+
+    /// Returns the enabled caches for an access to the address.
+    fn enabled_caches(&mut self, address: u64) -> Result<Option<(bool, bool)>, ArmError> {
+        let Some(caches) = self.caches.filter(|_| address < SYSTEM_SPACE) else {
+            return Ok(None);
+        };
+
+        let ccr = self.ccr()?;
+        let instruction = caches.instruction && ccr.ic();
+        let data = caches.data && ccr.dc();
+
+        Ok((instruction || data).then_some((instruction, data)))
+    }
+
+    /// Runs a data cache maintenance operation on every line of the range.
+    fn maintain_lines(&mut self, operation: u64, range: Range<u64>) -> Result<(), ArmError> {
+        let Some(caches) = self.caches else {
+            return Ok(());
+        };
+
+        for line in caches.lines(range) {
+            self.memory.write_word_32(operation, line as u32)?;
+        }
+
+        Ok(())
+    }
+
+    /// Invalidates the caches for the range, after it was changed other than by a write of the
+    /// debugger, e.g. by programming flash.
+    pub(crate) fn invalidate(&mut self, range: Range<u64>) -> Result<(), ArmError> {
+        let enabled = self.enabled_caches(range.start)?;
+        self.finish_write(range.start, (range.end - range.start) as usize, enabled)
+    }
+
+    /// Cleans the data cache for a read of `size` bytes at the address.
+    fn prepare_read(&mut self, address: u64, size: usize) -> Result<(), ArmError> {
+        if let Some((_, true)) = self.enabled_caches(address)? {
+            self.maintain_lines(DCCMVAC, address..address + size as u64)?;
+        }
+
+        Ok(())
+    }
+
+    /// Cleans the data cache for a write of `size` bytes at the address, and returns the
+    /// enabled caches for [`Self::finish_write`].
+    ///
+    /// Cleaning first keeps the data of the core next to the written data in the same lines,
+    /// which would be lost when the lines are invalidated after the write.
+    fn prepare_write(
+        &mut self,
+        address: u64,
+        size: usize,
+    ) -> Result<Option<(bool, bool)>, ArmError> {
+        let ccr = Ccr::get_mmio_address();
+        if address < ccr + 4 && ccr < address + size as u64 {
+            self.forget_ccr();
+        }
+
+        let enabled = self.enabled_caches(address)?;
+        if let Some((_, true)) = enabled {
+            self.maintain_lines(DCCMVAC, address..address + size as u64)?;
+        }
+
+        Ok(enabled)
+    }
+
+    /// Invalidates the caches after a write of `size` bytes at the address.
+    fn finish_write(
+        &mut self,
+        address: u64,
+        size: usize,
+        enabled: Option<(bool, bool)>,
+    ) -> Result<(), ArmError> {
+        let Some((instruction, data)) = enabled else {
+            return Ok(());
+        };
+
+        if data {
+            self.maintain_lines(DCIMVAC, address..address + size as u64)?;
+        }
+        if instruction {
+            self.memory.write_word_32(ICIALLU, 0)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'probe> Deref for CoherentMemory<'probe> {
+    type Target = dyn ArmMemoryInterface + 'probe;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.memory
+    }
+}
+
+impl DerefMut for CoherentMemory<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.memory
+    }
+}
+
+impl MemoryInterface<ArmError> for CoherentMemory<'_> {
+    fn supports_native_64bit_access(&mut self) -> bool {
+        self.memory.supports_native_64bit_access()
+    }
+
+    fn read_word_64(&mut self, address: u64) -> Result<u64, ArmError> {
+        self.prepare_read(address, 8)?;
+        self.memory.read_word_64(address)
+    }
+
+    fn read_word_32(&mut self, address: u64) -> Result<u32, ArmError> {
+        self.prepare_read(address, 4)?;
+        self.memory.read_word_32(address)
+    }
+
+    fn read_word_16(&mut self, address: u64) -> Result<u16, ArmError> {
+        self.prepare_read(address, 2)?;
+        self.memory.read_word_16(address)
+    }
+
+    fn read_word_8(&mut self, address: u64) -> Result<u8, ArmError> {
+        self.prepare_read(address, 1)?;
+        self.memory.read_word_8(address)
+    }
+
+    fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), ArmError> {
+        self.prepare_read(address, std::mem::size_of_val(data))?;
+        self.memory.read_64(address, data)
+    }
+
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+        self.prepare_read(address, std::mem::size_of_val(data))?;
+        self.memory.read_32(address, data)
+    }
+
+    fn read_16(&mut self, address: u64, data: &mut [u16]) -> Result<(), ArmError> {
+        self.prepare_read(address, std::mem::size_of_val(data))?;
+        self.memory.read_16(address, data)
+    }
+
+    fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), ArmError> {
+        self.prepare_read(address, std::mem::size_of_val(data))?;
+        self.memory.read_8(address, data)
+    }
+
+    fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), ArmError> {
+        self.prepare_read(address, data.len())?;
+        self.memory.read(address, data)
+    }
+
+    fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), ArmError> {
+        let enabled = self.prepare_write(address, 8)?;
+        self.memory.write_word_64(address, data)?;
+        self.finish_write(address, 8, enabled)
+    }
+
+    fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), ArmError> {
+        let enabled = self.prepare_write(address, 4)?;
+        self.memory.write_word_32(address, data)?;
+        self.finish_write(address, 4, enabled)
+    }
+
+    fn write_word_16(&mut self, address: u64, data: u16) -> Result<(), ArmError> {
+        let enabled = self.prepare_write(address, 2)?;
+        self.memory.write_word_16(address, data)?;
+        self.finish_write(address, 2, enabled)
+    }
+
+    fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), ArmError> {
+        let enabled = self.prepare_write(address, 1)?;
+        self.memory.write_word_8(address, data)?;
+        self.finish_write(address, 1, enabled)
+    }
+
+    fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), ArmError> {
+        let size = std::mem::size_of_val(data);
+        let enabled = self.prepare_write(address, size)?;
+        self.memory.write_64(address, data)?;
+        self.finish_write(address, size, enabled)
+    }
+
+    fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ArmError> {
+        let size = std::mem::size_of_val(data);
+        let enabled = self.prepare_write(address, size)?;
+        self.memory.write_32(address, data)?;
+        self.finish_write(address, size, enabled)
+    }
+
+    fn write_16(&mut self, address: u64, data: &[u16]) -> Result<(), ArmError> {
+        let size = std::mem::size_of_val(data);
+        let enabled = self.prepare_write(address, size)?;
+        self.memory.write_16(address, data)?;
+        self.finish_write(address, size, enabled)
+    }
+
+    fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), ArmError> {
+        let size = std::mem::size_of_val(data);
+        let enabled = self.prepare_write(address, size)?;
+        self.memory.write_8(address, data)?;
+        self.finish_write(address, size, enabled)
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), ArmError> {
+        let enabled = self.prepare_write(address, data.len())?;
+        self.memory.write(address, data)?;
+        self.finish_write(address, data.len(), enabled)
+    }
+
+    fn supports_8bit_transfers(&self) -> Result<bool, ArmError> {
+        self.memory.supports_8bit_transfers()
+    }
+
+    fn flush(&mut self) -> Result<(), ArmError> {
+        self.memory.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::probe::fake_probe::MockCore;
+
+    #[test]
+    fn lines_cover_the_range() {
+        let caches = Caches {
+            instruction: true,
+            data: true,
+            line_size: 32,
+        };
+
+        assert_eq!(
+            caches.lines(0x2000_001C..0x2000_0044).collect::<Vec<_>>(),
+            [0x2000_0000, 0x2000_0020, 0x2000_0040]
+        );
+        assert_eq!(caches.lines(0x2000_0020..0x2000_0020).count(), 0);
+    }
+    #[test]
+    fn ccr_is_cached_until_written() {
+        /// CCR with the data cache enabled.
+        const DC: u32 = 1 << 16;
+
+        let caches = Caches {
+            instruction: false,
+            data: true,
+            line_size: 32,
+        };
+        let mut core = MockCore::new();
+        let mut memory = CoherentMemory::new(Box::new(&mut core), Some(caches));
+        memory.write_word_32(Ccr::get_mmio_address(), DC).unwrap();
+        memory.read_word_32(0x2000_0000).unwrap();
+        assert_eq!(
+            memory.deref_mut().read_word_32(DCCMVAC).unwrap(),
+            0x2000_0000
+        );
+
+        // Disabling the data cache behind the back of the cached CCR still cleans the lines.
+        memory
+            .deref_mut()
+            .write_word_32(Ccr::get_mmio_address(), 0)
+            .unwrap();
+        memory.read_word_32(0x2000_0040).unwrap();
+        assert_eq!(
+            memory.deref_mut().read_word_32(DCCMVAC).unwrap(),
+            0x2000_0040
+        );
+
+        // Once the core ran, CCR is read again.
+        memory.forget_ccr();
+        memory.read_word_32(0x2000_0080).unwrap();
+        assert_eq!(
+            memory.deref_mut().read_word_32(DCCMVAC).unwrap(),
+            0x2000_0040
+        );
+
+        // Writing CCR through the interface is noticed.
+        memory.write_word_32(Ccr::get_mmio_address(), DC).unwrap();
+        memory.read_word_32(0x2000_00C0).unwrap();
+        assert_eq!(
+            memory.deref_mut().read_word_32(DCCMVAC).unwrap(),
+            0x2000_00C0
+        );
+    }
+}
@@ -1,7 +1,10 @@
 //! Common functions and data types for Cortex-M core variants
 
 use crate::{
-    architecture::arm::{memory::ArmMemoryInterface, ArmError},
+    architecture::arm::{
+        memory::{ArmMemoryInterface, MemoryTransfer},
+        ArmError,
+    },
     core::RegisterId,
     memory_mapped_bitfield_register,
     semihosting::decode_semihosting_syscall,
@@ -169,6 +172,43 @@ pub(crate) fn read_core_reg(
     Ok(value)
 }
 
+/// Read several core registers with a single batch of memory accesses.
+///
+/// For every register, DCRSR is written, and DHCSR and DCRDR are read back right away. If the
+/// transfer of a register was not finished in time, it is read again using [`read_core_reg`].
+pub(crate) fn read_core_regs(
+    memory: &mut dyn ArmMemoryInterface,
+    addrs: &[RegisterId],
+) -> Result<Vec<u32>, ArmError> {
+    let mut transfers = Vec::with_capacity(addrs.len() * 3);
+    for addr in addrs {
+        let mut dcrsr_val = Dcrsr(0);
+        dcrsr_val.set_regwnr(false); // Perform a read.
+        dcrsr_val.set_regsel((*addr).into()); // The address of the register to read.
+
+        transfers.push(MemoryTransfer::write(
+            Dcrsr::get_mmio_address(),
+            dcrsr_val.into(),
+        ));
+        transfers.push(MemoryTransfer::read(Dhcsr::get_mmio_address()));
+        transfers.push(MemoryTransfer::read(Dcrdr::get_mmio_address()));
+    }
+
+    memory.transfer_batch_32(&mut transfers)?;
+
+    addrs
+        .iter()
+        .zip(transfers.chunks(3))
+        .map(|(addr, transfers)| {
+            if Dhcsr(transfers[1].value()).s_regrdy() {
+                Ok(transfers[2].value())
+            } else {
+                read_core_reg(memory, *addr)
+            }
+        })
+        .collect()
+}
+
 pub(crate) fn write_core_reg(
     memory: &mut dyn ArmMemoryInterface,
     addr: RegisterId,
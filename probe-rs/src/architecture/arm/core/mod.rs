@@ -17,6 +17,7 @@ pub mod armv8m;
 
 pub(crate) mod armv7a_debug_regs;
 pub(crate) mod armv8a_debug_regs;
+pub(crate) mod cache;
 pub(crate) mod cortex_m;
 pub(crate) mod instructions;
 pub(crate) mod registers;
@@ -139,6 +140,9 @@ pub struct CortexMState {
 
     fp_present: bool,
 
+    /// The caches of the core, if it has any.
+    caches: Option<cache::Caches>,
+
     /// The semihosting command that was decoded at the current program counter
     semihosting_command: Option<SemihostingCommand>,
 }
@@ -150,6 +154,7 @@ impl CortexMState {
             hw_breakpoints_enabled: false,
             current_state: CoreStatus::Unknown,
             fp_present: false,
+            caches: None,
             semihosting_command: None,
         }
     }
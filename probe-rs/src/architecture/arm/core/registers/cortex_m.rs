@@ -155,11 +155,13 @@ static CORTEX_M_COMMON_REGS_SET: &[CoreRegister] = &[
         data_type: RegisterDataType::UnsignedInteger(32),
         unwind_rule: UnwindRule::SpecialRule,
     },
+    // Exception handlers don't change PSP, and the unwinder needs it to find the exception
+    // frames of code running on the process stack.
     CoreRegister {
         roles: &[RegisterRole::Core("PSP"), RegisterRole::ProcessStackPointer],
         id: RegisterId(0b10010),
         data_type: RegisterDataType::UnsignedInteger(32),
-        unwind_rule: UnwindRule::SpecialRule,
+        unwind_rule: UnwindRule::Preserve,
     },
     XPSR,
     // CONTROL bits [31:24], FAULTMASK bits [23:16],
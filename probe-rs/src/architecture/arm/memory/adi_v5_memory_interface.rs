@@ -3,13 +3,17 @@ use std::any::Any;
 use crate::{
     architecture::arm::{
         ap::{
-            memory_ap::{DataSize, MemoryAp, MemoryApType},
-            ApAccess,
+            memory_ap::{
+                registers::{DRW, TAR, TAR2},
+                DataSize, MemoryAp, MemoryApType,
+            },
+            AccessPortError, AccessPortType, ApAccess,
         },
         communication_interface::{FlushableArmAccess, Initialized, SwdSequence},
         dp::DpAccess,
-        memory::ArmMemoryInterface,
-        ArmCommunicationInterface, ArmError, DapAccess, FullyQualifiedApAddress,
+        memory::{ArmMemoryInterface, MemoryTransfer},
+        ArmCommunicationInterface, ArmError, BlockTransfer, DapAccess, DapQueue,
+        FullyQualifiedApAddress, Register,
     },
     probe::DebugProbeError,
     MemoryInterface,
@@ -23,6 +27,29 @@ fn autoincr_max_bytes(address: u64) -> usize {
     ((address + 1).next_multiple_of(AUTOINCR_LIMIT as _) - address) as usize
 }
 
+/// Add the steps to set the target address to a block sequence.
+fn push_target_address(
+    steps: &mut Vec<BlockTransfer<'_>>,
+    large_address: bool,
+    address: u64,
+) -> Result<(), ArmError> {
+    if large_address {
+        steps.push(BlockTransfer::Write {
+            addr: TAR2::ADDRESS,
+            value: (address >> 32) as u32,
+        });
+    } else if address >> 32 != 0 {
+        return Err(ArmError::OutOfBounds);
+    }
+
+    steps.push(BlockTransfer::Write {
+        addr: TAR::ADDRESS,
+        value: address as u32,
+    });
+
+    Ok(())
+}
+
 /// A struct to give access to a targets memory using a certain DAP.
 pub(crate) struct ADIMemoryInterface<'interface, APA> {
     interface: &'interface mut APA,
@@ -44,6 +71,20 @@ where
             memory_ap,
         })
     }
+
+    /// Counts bytes read from memory in the transfer metrics.
+    fn record_read(&self, bytes: usize) {
+        if let Some(metrics) = self.interface.metrics() {
+            metrics.record_read(bytes);
+        }
+    }
+
+    /// Counts bytes written to memory in the transfer metrics.
+    fn record_write(&self, bytes: usize) {
+        if let Some(metrics) = self.interface.metrics() {
+            metrics.record_write(bytes);
+        }
+    }
 }
 
 impl<APA> ADIMemoryInterface<'_, APA> where APA: ApAccess {}
@@ -70,14 +111,16 @@ where
 
 impl<AP> MemoryInterface<ArmError> for ADIMemoryInterface<'_, AP>
 where
-    AP: FlushableArmAccess + ApAccess + DpAccess,
+    AP: FlushableArmAccess + ApAccess + DpAccess + DapAccess,
 {
     /// Read a block of 64 bit words at `address`.
     ///
     /// The number of words read is `data.len()`.
     /// The address where the read should be performed at has to be a multiple of 8.
     /// Returns `ArmError::MemoryNotAligned` if this does not hold true.
+    #[tracing::instrument(level = "trace", skip(self, data), fields(ap = ?self.memory_ap.ap_address(), length = data.len()))]
     fn read_64(&mut self, mut address: u64, mut data: &mut [u64]) -> Result<(), ArmError> {
+        let bytes = std::mem::size_of_val(data);
         if data.is_empty() {
             return Ok(());
         }
@@ -120,6 +163,7 @@ where
             data = &mut data[chunk_size..];
         }
 
+        self.record_read(bytes);
         tracing::debug!("Finished reading block");
 
         Ok(())
@@ -130,7 +174,9 @@ where
     /// The number of words read is `data.len()`.
     /// The address where the read should be performed at has to be a multiple of 4.
     /// Returns `ArmError::MemoryNotAligned` if this does not hold true.
+    #[tracing::instrument(level = "trace", skip(self, data), fields(ap = ?self.memory_ap.ap_address(), length = data.len()))]
     fn read_32(&mut self, mut address: u64, mut data: &mut [u32]) -> Result<(), ArmError> {
+        let bytes = std::mem::size_of_val(data);
         if data.is_empty() {
             return Ok(());
         }
@@ -142,6 +188,8 @@ where
         self.memory_ap
             .try_set_datasize(self.interface, DataSize::U32)?;
 
+        let large_address = self.memory_ap.has_large_address_extension();
+        let mut steps = vec![];
         while !data.is_empty() {
             let chunk_size = data.len().min(autoincr_max_bytes(address) / 4);
 
@@ -152,16 +200,28 @@ where
             );
 
             // autoincrement is limited to the 10 lowest bits, so write TAR every time.
-            self.memory_ap.set_target_address(self.interface, address)?;
-            self.memory_ap
-                .read_data(self.interface, &mut data[..chunk_size])?;
+            push_target_address(&mut steps, large_address, address)?;
+            let (chunk, rest) = std::mem::take(&mut data).split_at_mut(chunk_size);
+            steps.push(BlockTransfer::ReadRepeated {
+                addr: DRW::ADDRESS,
+                values: chunk,
+            });
 
             address = address
                 .checked_add(chunk_size as u64 * 4)
                 .ok_or(ArmError::OutOfBounds)?;
-            data = &mut data[chunk_size..];
+            data = rest;
         }
 
+        // All chunks are sent at once, so the probe doesn't have to wait for the host between
+        // chunks.
+        let ap = self.memory_ap.ap_address().clone();
+        self.interface
+            .ap_block_sequence(&ap, &mut steps)
+            .map_err(AccessPortError::register_read_error::<DRW, _>)
+            .map_err(|err| ArmError::from_access_port(err, &ap))?;
+
+        self.record_read(bytes);
         tracing::debug!("Finished reading block");
 
         Ok(())
@@ -172,7 +232,9 @@ where
     /// The number of words read is `data.len()`.
     /// The address where the read should be performed at has to be a multiple of 2.
     /// Returns `ArmError::MemoryNotAligned` if this does not hold true.
+    #[tracing::instrument(level = "trace", skip(self, data), fields(ap = ?self.memory_ap.ap_address(), length = data.len()))]
     fn read_16(&mut self, mut address: u64, mut data: &mut [u16]) -> Result<(), ArmError> {
+        let bytes = std::mem::size_of_val(data);
         if self.memory_ap.supports_only_32bit_data_size() {
             return Err(ArmError::UnsupportedTransferWidth(16));
         }
@@ -218,6 +280,7 @@ where
             data = &mut data[chunk_size..];
         }
 
+        self.record_read(bytes);
         tracing::debug!("Finished reading block");
 
         Ok(())
@@ -226,7 +289,9 @@ where
     /// Read a block of 8 bit words at `address`.
     ///
     /// The number of words read is `data.len()`.
+    #[tracing::instrument(level = "trace", skip(self, data), fields(ap = ?self.memory_ap.ap_address(), length = data.len()))]
     fn read_8(&mut self, mut address: u64, mut data: &mut [u8]) -> Result<(), ArmError> {
+        let bytes = std::mem::size_of_val(data);
         if self.memory_ap.supports_only_32bit_data_size() {
             return Err(ArmError::UnsupportedTransferWidth(8));
         }
@@ -268,6 +333,7 @@ where
             data = &mut data[chunk_size..];
         }
 
+        self.record_read(bytes);
         tracing::debug!("Finished reading block");
 
         Ok(())
@@ -301,7 +367,9 @@ where
     /// The number of words written is `data.len()`.
     /// The address where the write should be performed at has to be a multiple of 8.
     /// Returns `ArmError::MemoryNotAligned` if this does not hold true.
+    #[tracing::instrument(level = "trace", skip(self, data), fields(ap = ?self.memory_ap.ap_address(), length = data.len()))]
     fn write_64(&mut self, mut address: u64, mut data: &[u64]) -> Result<(), ArmError> {
+        let bytes = std::mem::size_of_val(data);
         if (address % 8) != 0 {
             return Err(ArmError::alignment_error(address, 8));
         }
@@ -349,6 +417,7 @@ where
             data = &data[chunk_size..];
         }
 
+        self.record_write(bytes);
         tracing::debug!("Finished writing block");
 
         Ok(())
@@ -359,7 +428,9 @@ where
     /// The number of words written is `data.len()`.
     /// The address where the write should be performed at has to be a multiple of 4.
     /// Returns `ArmError::MemoryNotAligned` if this does not hold true.
+    #[tracing::instrument(level = "trace", skip(self, data), fields(ap = ?self.memory_ap.ap_address(), length = data.len()))]
     fn write_32(&mut self, mut address: u64, mut data: &[u32]) -> Result<(), ArmError> {
+        let bytes = std::mem::size_of_val(data);
         if (address % 4) != 0 {
             return Err(ArmError::alignment_error(address, 4));
         }
@@ -377,6 +448,8 @@ where
         self.memory_ap
             .try_set_datasize(self.interface, DataSize::U32)?;
 
+        let large_address = self.memory_ap.has_large_address_extension();
+        let mut steps = vec![];
         while !data.is_empty() {
             let chunk_size = data.len().min(autoincr_max_bytes(address) / 4);
 
@@ -387,9 +460,11 @@ where
             );
 
             // autoincrement is limited to the 10 lowest bits, so write TAR every time.
-            self.memory_ap.set_target_address(self.interface, address)?;
-            self.memory_ap
-                .write_data(self.interface, &data[..chunk_size])?;
+            push_target_address(&mut steps, large_address, address)?;
+            steps.push(BlockTransfer::WriteRepeated {
+                addr: DRW::ADDRESS,
+                values: &data[..chunk_size],
+            });
 
             address = address
                 .checked_add(chunk_size as u64 * 4)
@@ -397,6 +472,13 @@ where
             data = &data[chunk_size..];
         }
 
+        let ap = self.memory_ap.ap_address().clone();
+        self.interface
+            .ap_block_sequence(&ap, &mut steps)
+            .map_err(AccessPortError::register_write_error::<DRW, _>)
+            .map_err(|err| ArmError::from_access_port(err, &ap))?;
+
+        self.record_write(bytes);
         tracing::debug!("Finished writing block");
 
         Ok(())
@@ -407,7 +489,9 @@ where
     /// The number of words written is `data.len()`.
     /// The address where the write should be performed at has to be a multiple of 2.
     /// Returns `ArmError::MemoryNotAligned` if this does not hold true.
+    #[tracing::instrument(level = "trace", skip(self, data), fields(ap = ?self.memory_ap.ap_address(), length = data.len()))]
     fn write_16(&mut self, mut address: u64, mut data: &[u16]) -> Result<(), ArmError> {
+        let bytes = std::mem::size_of_val(data);
         if self.memory_ap.supports_only_32bit_data_size() {
             return Err(ArmError::UnsupportedTransferWidth(16));
         }
@@ -455,6 +539,7 @@ where
             data = &data[chunk_size..];
         }
 
+        self.record_write(bytes);
         tracing::debug!("Finished writing block");
 
         Ok(())
@@ -463,7 +548,9 @@ where
     /// Write a block of 8 bit words at `address`.
     ///
     /// The number of words written is `data.len()`.
+    #[tracing::instrument(level = "trace", skip(self, data), fields(ap = ?self.memory_ap.ap_address(), length = data.len()))]
     fn write_8(&mut self, mut address: u64, mut data: &[u8]) -> Result<(), ArmError> {
+        let bytes = std::mem::size_of_val(data);
         if self.memory_ap.supports_only_32bit_data_size() {
             return Err(ArmError::UnsupportedTransferWidth(8));
         }
@@ -509,6 +596,7 @@ where
             data = &data[chunk_size..];
         }
 
+        self.record_write(bytes);
         tracing::debug!("Finished writing block");
 
         Ok(())
@@ -532,7 +620,7 @@ where
 
 impl<APA> ArmMemoryInterface for ADIMemoryInterface<'_, APA>
 where
-    APA: std::any::Any + FlushableArmAccess + ApAccess + DpAccess,
+    APA: std::any::Any + FlushableArmAccess + ApAccess + DpAccess + DapAccess,
 {
     fn base_address(&mut self) -> Result<u64, ArmError> {
         self.memory_ap.base_address(self.interface)
@@ -563,6 +651,55 @@ where
             ))
             .map(|iface| (iface, &mut self.memory_ap))
     }
+
+    fn transfer_batch_32(&mut self, transfers: &mut [MemoryTransfer]) -> Result<(), ArmError> {
+        if let Some(transfer) = transfers.iter().find(|t| t.address() % 4 != 0) {
+            return Err(ArmError::alignment_error(transfer.address(), 4));
+        }
+
+        self.memory_ap
+            .try_set_datasize(self.interface, DataSize::U32)?;
+
+        let ap = self.memory_ap.ap_address().clone();
+        let large_address = self.memory_ap.has_large_address_extension();
+
+        let mut queue = DapQueue::new();
+        let mut reads = Vec::with_capacity(transfers.len());
+        for transfer in transfers.iter() {
+            let address = transfer.address();
+            if large_address {
+                queue.write_ap(&ap, TAR2::ADDRESS, (address >> 32) as u32);
+            } else if address >> 32 != 0 {
+                return Err(ArmError::OutOfBounds);
+            }
+            queue.write_ap(&ap, TAR::ADDRESS, address as u32);
+
+            reads.push(match transfer {
+                MemoryTransfer::Read { .. } => Some(queue.read_ap(&ap, DRW::ADDRESS)),
+                MemoryTransfer::Write { value, .. } => {
+                    queue.write_ap(&ap, DRW::ADDRESS, *value);
+                    None
+                }
+            });
+        }
+
+        self.interface.execute_queue(&mut queue)?;
+
+        for (transfer, read) in transfers.iter_mut().zip(reads) {
+            if let (MemoryTransfer::Read { value, .. }, Some(read)) = (transfer, read) {
+                *value = queue.result(read)?;
+            }
+        }
+
+        let read_count = transfers
+            .iter()
+            .filter(|t| matches!(t, MemoryTransfer::Read { .. }))
+            .count();
+        self.record_read(read_count * 4);
+        self.record_write((transfers.len() - read_count) * 4);
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -572,8 +709,11 @@ mod tests {
 
     use crate::{
         architecture::arm::{
-            ap::memory_ap::mock::MockMemoryAp, memory::adi_v5_memory_interface::ADIMemoryInterface,
-            FullyQualifiedApAddress,
+            ap::memory_ap::mock::MockMemoryAp,
+            memory::{
+                adi_v5_memory_interface::ADIMemoryInterface, ArmMemoryInterface, MemoryTransfer,
+            },
+            ArmError, FullyQualifiedApAddress,
         },
         MemoryInterface,
     };
@@ -657,6 +797,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn transfer_batch_32() {
+        let mut mock = MockMemoryAp::with_pattern_and_size(256);
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        let mut transfers = [
+            MemoryTransfer::write(8, DATA32[0]),
+            MemoryTransfer::read(0),
+            MemoryTransfer::read(8),
+        ];
+        mi.transfer_batch_32(&mut transfers).unwrap();
+
+        assert_eq!(transfers[1].value(), 0x04030201);
+        assert_eq!(transfers[2].value(), DATA32[0]);
+    }
+
+    #[test]
+    fn transfer_batch_32_unaligned_should_error() {
+        let mut mock = MockMemoryAp::with_pattern_and_size(256);
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        let mut transfers = [MemoryTransfer::read(0), MemoryTransfer::read(2)];
+        assert!(matches!(
+            mi.transfer_batch_32(&mut transfers),
+            Err(ArmError::MemoryNotAligned { .. })
+        ));
+    }
+
     #[test]
     fn write_word_16() {
         for address in [0, 2, 4, 6] {
@@ -732,6 +900,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_32_across_autoincrement_wrap() {
+        let mut mock = MockMemoryAp::with_pattern_and_size(4096);
+        let expected: Vec<u32> = mock.memory[0x3F8..0x408]
+            .chunks(4)
+            .map(|b| b.pread(0).unwrap())
+            .collect();
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        let mut data = vec![0u32; 4];
+        mi.read_32(0x3F8, &mut data).unwrap();
+
+        assert_eq!(data, expected);
+        // TAR only increments within 1 KiB, so it has to be written again at the boundary.
+        assert_eq!(mock.tar_writes, [0x3F8, 0x400]);
+    }
+
+    #[test]
+    fn write_32_across_autoincrement_wrap() {
+        let mut mock = MockMemoryAp::with_pattern_and_size(4096);
+        let mut expected = mock.memory.clone();
+        expected[0x3F8..0x408].copy_from_slice(DATA8);
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        mi.write_32(0x3F8, DATA32).unwrap();
+
+        assert_eq!(mi.mock_memory(), expected);
+        assert_eq!(mock.tar_writes, [0x3F8, 0x400]);
+    }
+
     #[test]
     fn read_32_unaligned_should_error() {
         let mut mock = MockMemoryAp::with_pattern_and_size(256);
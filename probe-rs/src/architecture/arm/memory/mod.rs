@@ -12,6 +12,51 @@ use super::{
 };
 pub use romtable::{Component, ComponentId, CoresightComponent, PeripheralType};
 
+/// A single 32 bit memory access in a batch, see [`ArmMemoryInterface::transfer_batch_32`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryTransfer {
+    /// Read a word. `value` holds the read word once the batch was executed.
+    Read {
+        /// The address to read from, which has to be a multiple of 4.
+        address: u64,
+        /// The read word.
+        value: u32,
+    },
+    /// Write `value` to memory.
+    Write {
+        /// The address to write to, which has to be a multiple of 4.
+        address: u64,
+        /// The word to write.
+        value: u32,
+    },
+}
+
+impl MemoryTransfer {
+    /// Creates a read of the word at `address`.
+    pub fn read(address: u64) -> Self {
+        Self::Read { address, value: 0 }
+    }
+
+    /// Creates a write of `value` to `address`.
+    pub fn write(address: u64, value: u32) -> Self {
+        Self::Write { address, value }
+    }
+
+    /// The address of the access.
+    pub fn address(&self) -> u64 {
+        match self {
+            Self::Read { address, .. } | Self::Write { address, .. } => *address,
+        }
+    }
+
+    /// The word read or written.
+    pub fn value(&self) -> u32 {
+        match self {
+            Self::Read { value, .. } | Self::Write { value, .. } => *value,
+        }
+    }
+}
+
 /// An ArmMemoryInterface (ArmProbeInterface + MemoryAp)
 pub trait ArmMemoryInterface: SwdSequence + ArmMemoryInterfaceShim {
     /// The underlying MemoryAp.
@@ -31,6 +76,26 @@ pub trait ArmMemoryInterface: SwdSequence + ArmMemoryInterfaceShim {
         &mut self,
     ) -> Result<(&mut ArmCommunicationInterface<Initialized>, &mut MemoryAp), DebugProbeError>;
 
+    /// Perform a batch of 32 bit reads and writes to arbitrary addresses, in order.
+    ///
+    /// Interfaces which support it send the whole batch to the probe at once, which is much
+    /// faster than single accesses when many scattered words are accessed. The default
+    /// implementation performs the accesses one by one.
+    fn transfer_batch_32(&mut self, transfers: &mut [MemoryTransfer]) -> Result<(), ArmError> {
+        for transfer in transfers {
+            match transfer {
+                MemoryTransfer::Read { address, value } => {
+                    *value = self.read_word_32(*address)?;
+                }
+                MemoryTransfer::Write { address, value } => {
+                    self.write_word_32(*address, *value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Inform the probe of the [`CoreStatus`] of the chip/core attached to
     /// the probe.
     //
@@ -41,6 +106,15 @@ pub trait ArmMemoryInterface: SwdSequence + ArmMemoryInterfaceShim {
             .map(|iface| iface.core_status_notification(state))
             .ok();
     }
+
+    /// Forgets the cached AP registers of the underlying interface, e.g. after a reset.
+    ///
+    /// See [`ArmCommunicationInterface::invalidate_register_cache`].
+    fn invalidate_register_cache(&mut self) {
+        self.get_arm_communication_interface()
+            .map(|iface| iface.invalidate_register_cache())
+            .ok();
+    }
 }
 
 /// Implementation detail to allow trait upcasting-like behaviour.
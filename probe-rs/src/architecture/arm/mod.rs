@@ -6,6 +6,9 @@ pub mod component;
 pub(crate) mod core;
 pub mod dp;
 pub mod memory;
+pub mod mpu;
+mod queue;
+mod register_cache;
 pub mod sequences;
 pub mod swo;
 mod traits;
@@ -27,6 +30,7 @@ use crate::{
 pub use communication_interface::{
     ArmChipInfo, ArmCommunicationInterface, ArmProbeInterface, DapError, Register,
 };
+pub use queue::{DapQueue, QueuedRead};
 pub use swo::{SwoAccess, SwoConfig, SwoMode, SwoReader};
 pub use traits::*;
 
@@ -59,6 +63,9 @@ pub enum ArmError {
     /// The core has to be halted for the operation, but was not.
     CoreNotHalted,
 
+    /// The result of a queued read was requested before the queue was executed.
+    QueueNotExecuted,
+
     /// Performing certain operations (e.g device unlock or Chip-Erase) can leave the device in a
     /// state that requires a probe re-attach to resolve.
     ReAttachRequired,
@@ -0,0 +1,655 @@
+//! Decoding of the MPU and SAU region configuration of Cortex-M cores.
+//!
+//! The regions are configured through a region number register, which selects the region shown
+//! in the base and attribute registers. Reading them changes the region number register, so the
+//! core should be halted, and the register is restored afterwards.
+
+use std::fmt;
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::{
+    architecture::arm::core::cortex_m::IdPfr1, memory_mapped_bitfield_register, Core, CoreType,
+    Error, MemoryInterface, MemoryMappedRegister,
+};
+
+memory_mapped_bitfield_register! {
+    /// MPU Type Register
+    struct MpuType(u32);
+    0xE000_ED90, "MPU_TYPE",
+    impl From;
+    dregion, _: 15, 8;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU Control Register
+    struct MpuCtrl(u32);
+    0xE000_ED94, "MPU_CTRL",
+    impl From;
+    privdefena, _: 2;
+    hfnmiena, _: 1;
+    enable, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU Region Number Register
+    struct MpuRnr(u32);
+    0xE000_ED98, "MPU_RNR",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU Region Base Address Register
+    struct MpuRbar(u32);
+    0xE000_ED9C, "MPU_RBAR",
+    impl From;
+    /// ARMv8-M: the shareability.
+    sh, _: 4, 3;
+    /// ARMv8-M: the access permissions.
+    ap, _: 2, 1;
+    /// ARMv8-M: execute never.
+    xn, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU Region Attribute and Size Register (ARMv6-M and ARMv7-M)
+    struct MpuRasr(u32);
+    0xE000_EDA0, "MPU_RASR",
+    impl From;
+    xn, _: 28;
+    ap, _: 26, 24;
+    tex, _: 21, 19;
+    s, _: 18;
+    c, _: 17;
+    b, _: 16;
+    u8, srd, _: 15, 8;
+    size, _: 5, 1;
+    enable, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU Region Limit Address Register (ARMv8-M)
+    struct MpuRlar(u32);
+    0xE000_EDA0, "MPU_RLAR",
+    impl From;
+    attrindx, _: 3, 1;
+    en, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU Memory Attribute Indirection Register 0 (ARMv8-M)
+    struct MpuMair0(u32);
+    0xE000_EDC0, "MPU_MAIR0",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// MPU Memory Attribute Indirection Register 1 (ARMv8-M)
+    struct MpuMair1(u32);
+    0xE000_EDC4, "MPU_MAIR1",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU Control Register
+    struct SauCtrl(u32);
+    0xE000_EDD0, "SAU_CTRL",
+    impl From;
+    allns, _: 1;
+    enable, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU Type Register
+    struct SauType(u32);
+    0xE000_EDD4, "SAU_TYPE",
+    impl From;
+    sregion, _: 7, 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU Region Number Register
+    struct SauRnr(u32);
+    0xE000_EDD8, "SAU_RNR",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU Region Base Address Register
+    struct SauRbar(u32);
+    0xE000_EDDC, "SAU_RBAR",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// SAU Region Limit Address Register
+    struct SauRlar(u32);
+    0xE000_EDE0, "SAU_RLAR",
+    impl From;
+    nsc, _: 1;
+    enable, _: 0;
+}
+
+/// The configuration of the MPU of a core.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MpuConfiguration {
+    /// Whether the MPU is enabled.
+    pub enabled: bool,
+    /// Whether privileged code can access memory outside of the regions with the default
+    /// memory map.
+    pub privileged_default_map: bool,
+    /// Whether the MPU is also enabled in HardFault and NMI handlers.
+    pub enabled_in_fault_handlers: bool,
+    /// The regions of the MPU.
+    pub regions: Vec<MpuRegion>,
+}
+
+/// A region of the MPU.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MpuRegion {
+    /// The number of the region.
+    pub number: u32,
+    /// Whether the region is enabled.
+    pub enabled: bool,
+    /// The addresses of the region.
+    pub range: Range<u64>,
+    /// The disabled subregions, one bit per eighth of the region. Only ARMv6-M and ARMv7-M
+    /// have subregions.
+    pub disabled_subregions: u8,
+    /// Who may read and write the region.
+    pub access: MpuAccess,
+    /// Whether instructions can't be executed from the region.
+    pub execute_never: bool,
+    /// Whether the region is shared between multiple bus masters.
+    pub shareable: bool,
+    /// The memory type and cache policies of the region.
+    pub attributes: MemoryAttributes,
+}
+
+/// The access permissions of an MPU region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MpuAccess {
+    /// Neither privileged nor unprivileged code can access the region.
+    NoAccess,
+    /// Only privileged code can read and write the region.
+    PrivilegedReadWrite,
+    /// Privileged code can read and write the region, unprivileged code can read it.
+    PrivilegedReadWriteUnprivilegedReadOnly,
+    /// All code can read and write the region.
+    ReadWrite,
+    /// Only privileged code can read the region.
+    PrivilegedReadOnly,
+    /// All code can read the region.
+    ReadOnly,
+    /// A reserved encoding.
+    Reserved,
+}
+
+impl fmt::Display for MpuAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MpuAccess::NoAccess => "no access",
+            MpuAccess::PrivilegedReadWrite => "priv RW",
+            MpuAccess::PrivilegedReadWriteUnprivilegedReadOnly => "priv RW, unpriv RO",
+            MpuAccess::ReadWrite => "RW",
+            MpuAccess::PrivilegedReadOnly => "priv RO",
+            MpuAccess::ReadOnly => "RO",
+            MpuAccess::Reserved => "reserved",
+        })
+    }
+}
+
+/// The memory type of an MPU region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MemoryAttributes {
+    /// Device memory. ARMv7-M Strongly-ordered memory is Device-nGnRnE memory.
+    Device(DeviceType),
+    /// Normal memory, with the cache policies of the inner and outer caches.
+    Normal {
+        /// The cache policy of the caches of the core.
+        inner: CachePolicy,
+        /// The cache policy of caches of the system.
+        outer: CachePolicy,
+    },
+    /// A reserved encoding.
+    Reserved,
+}
+
+impl fmt::Display for MemoryAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryAttributes::Device(device) => write!(f, "Device-{device:?}"),
+            MemoryAttributes::Normal { inner, outer } if inner == outer => {
+                write!(f, "Normal {inner}")
+            }
+            MemoryAttributes::Normal { inner, outer } => {
+                write!(f, "Normal inner {inner}, outer {outer}")
+            }
+            MemoryAttributes::Reserved => f.write_str("reserved"),
+        }
+    }
+}
+
+/// The kind of device memory, by whether accesses may be gathered (`G`), reordered (`R`) and
+/// acknowledged early (`E`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[allow(non_camel_case_types)]
+pub enum DeviceType {
+    /// No gathering, reordering or early write acknowledgement.
+    nGnRnE,
+    /// Early write acknowledgement.
+    nGnRE,
+    /// Reordering and early write acknowledgement.
+    nGRE,
+    /// Gathering, reordering and early write acknowledgement.
+    GRE,
+}
+
+/// The cache policy of normal memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CachePolicy {
+    /// The memory is not cached.
+    NonCacheable,
+    /// Writes are written to the cache and the memory.
+    WriteThrough {
+        /// Whether reads allocate cache lines.
+        read_allocate: bool,
+        /// Whether writes allocate cache lines.
+        write_allocate: bool,
+    },
+    /// Writes are only written to the cache, and written back to the memory later.
+    WriteBack {
+        /// Whether reads allocate cache lines.
+        read_allocate: bool,
+        /// Whether writes allocate cache lines.
+        write_allocate: bool,
+    },
+}
+
+impl fmt::Display for CachePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, read_allocate, write_allocate) = match *self {
+            CachePolicy::NonCacheable => return f.write_str("non-cacheable"),
+            CachePolicy::WriteThrough {
+                read_allocate,
+                write_allocate,
+            } => ("WT", read_allocate, write_allocate),
+            CachePolicy::WriteBack {
+                read_allocate,
+                write_allocate,
+            } => ("WB", read_allocate, write_allocate),
+        };
+
+        f.write_str(name)?;
+        if read_allocate || write_allocate {
+            f.write_str("-")?;
+        }
+        if read_allocate {
+            f.write_str("R")?;
+        }
+        if write_allocate {
+            f.write_str("W")?;
+        }
+        if read_allocate || write_allocate {
+            f.write_str("A")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The configuration of the Security Attribution Unit of an ARMv8-M core.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SauConfiguration {
+    /// Whether the SAU is enabled.
+    pub enabled: bool,
+    /// Whether all memory is Non-secure while the SAU is disabled. Otherwise it's Secure.
+    pub all_non_secure: bool,
+    /// The regions of the SAU, which are Non-secure memory.
+    pub regions: Vec<SauRegion>,
+}
+
+/// A region of the SAU.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SauRegion {
+    /// The number of the region.
+    pub number: u32,
+    /// Whether the region is enabled.
+    pub enabled: bool,
+    /// The addresses of the region.
+    pub range: Range<u64>,
+    /// Whether the region is Secure, Non-secure callable memory, instead of Non-secure memory.
+    pub non_secure_callable: bool,
+}
+
+/// Reads the MPU configuration of a Cortex-M core. Returns `None` if the core has no MPU.
+///
+/// The core should be halted, see the [module documentation](self).
+pub fn read_mpu(core: &mut Core) -> Result<Option<MpuConfiguration>, Error> {
+    let armv8m = match core.core_type() {
+        CoreType::Armv6m | CoreType::Armv7m | CoreType::Armv7em => false,
+        CoreType::Armv8m => true,
+        _ => {
+            return Err(Error::NotImplemented(
+                "MPU decoding on cores other than Cortex-M",
+            ))
+        }
+    };
+
+    let count = MpuType(core.read_word_32(MpuType::get_mmio_address())?).dregion();
+    if count == 0 {
+        return Ok(None);
+    }
+
+    let ctrl = MpuCtrl(core.read_word_32(MpuCtrl::get_mmio_address())?);
+    let mair = if armv8m {
+        let mair0 = core.read_word_32(MpuMair0::get_mmio_address())?;
+        let mair1 = core.read_word_32(MpuMair1::get_mmio_address())?;
+        u64::from(mair0) | u64::from(mair1) << 32
+    } else {
+        0
+    };
+
+    let rnr = core.read_word_32(MpuRnr::get_mmio_address())?;
+    let mut regions = Vec::new();
+    for number in 0..count {
+        core.write_word_32(MpuRnr::get_mmio_address(), number)?;
+        let rbar = core.read_word_32(MpuRbar::get_mmio_address())?;
+        // RASR on ARMv7-M and RLAR on ARMv8-M are at the same address.
+        let attributes = core.read_word_32(MpuRasr::get_mmio_address())?;
+
+        regions.push(if armv8m {
+            decode_armv8m_region(number, rbar, attributes, mair)
+        } else {
+            decode_armv7m_region(number, rbar, attributes)
+        });
+    }
+    core.write_word_32(MpuRnr::get_mmio_address(), rnr)?;
+
+    Ok(Some(MpuConfiguration {
+        enabled: ctrl.enable(),
+        privileged_default_map: ctrl.privdefena(),
+        enabled_in_fault_handlers: ctrl.hfnmiena(),
+        regions,
+    }))
+}
+
+/// Reads the SAU configuration of an ARMv8-M core. Returns `None` if the core has no Security
+/// Extension.
+///
+/// The SAU is only visible to a debugger with Secure access. The core should be halted, see the
+/// [module documentation](self).
+pub fn read_sau(core: &mut Core) -> Result<Option<SauConfiguration>, Error> {
+    if core.core_type() != CoreType::Armv8m
+        || !IdPfr1(core.read_word_32(IdPfr1::get_mmio_address())?).security_present()
+    {
+        return Ok(None);
+    }
+
+    let ctrl = SauCtrl(core.read_word_32(SauCtrl::get_mmio_address())?);
+    let count = SauType(core.read_word_32(SauType::get_mmio_address())?).sregion();
+
+    let rnr = core.read_word_32(SauRnr::get_mmio_address())?;
+    let mut regions = Vec::new();
+    for number in 0..count {
+        core.write_word_32(SauRnr::get_mmio_address(), number)?;
+        let rbar = core.read_word_32(SauRbar::get_mmio_address())?;
+        let rlar = SauRlar(core.read_word_32(SauRlar::get_mmio_address())?);
+
+        regions.push(SauRegion {
+            number,
+            enabled: rlar.enable(),
+            range: limit_range(rbar, rlar.0),
+            non_secure_callable: rlar.nsc(),
+        });
+    }
+    if count > 0 {
+        core.write_word_32(SauRnr::get_mmio_address(), rnr)?;
+    }
+
+    Ok(Some(SauConfiguration {
+        enabled: ctrl.enable(),
+        all_non_secure: ctrl.allns(),
+        regions,
+    }))
+}
+
+/// Returns the range from a base and a limit register of ARMv8-M, which hold the first and the
+/// last 32-byte block of the range.
+fn limit_range(base: u32, limit: u32) -> Range<u64> {
+    u64::from(base & !0x1F)..u64::from(limit | 0x1F) + 1
+}
+
+fn decode_armv7m_region(number: u32, rbar: u32, rasr: u32) -> MpuRegion {
+    let rasr = MpuRasr(rasr);
+    // The smallest region has 32 bytes.
+    let size = 1u64 << (rasr.size() + 1).max(5);
+    let start = u64::from(rbar & !0x1F) & !(size - 1);
+
+    let access = match rasr.ap() {
+        0b000 => MpuAccess::NoAccess,
+        0b001 => MpuAccess::PrivilegedReadWrite,
+        0b010 => MpuAccess::PrivilegedReadWriteUnprivilegedReadOnly,
+        0b011 => MpuAccess::ReadWrite,
+        0b101 => MpuAccess::PrivilegedReadOnly,
+        0b110 | 0b111 => MpuAccess::ReadOnly,
+        _ => MpuAccess::Reserved,
+    };
+
+    let (attributes, shareable) = match (rasr.tex(), rasr.c(), rasr.b()) {
+        (0b000, false, false) => (MemoryAttributes::Device(DeviceType::nGnRnE), true),
+        (0b000, false, true) => (MemoryAttributes::Device(DeviceType::nGnRE), true),
+        (0b010, false, false) => (MemoryAttributes::Device(DeviceType::nGnRE), false),
+        (0b000, true, b) => {
+            let policy = if b {
+                CachePolicy::WriteBack {
+                    read_allocate: true,
+                    write_allocate: false,
+                }
+            } else {
+                CachePolicy::WriteThrough {
+                    read_allocate: true,
+                    write_allocate: false,
+                }
+            };
+            (
+                MemoryAttributes::Normal {
+                    inner: policy,
+                    outer: policy,
+                },
+                rasr.s(),
+            )
+        }
+        (0b001, false, false) => (
+            MemoryAttributes::Normal {
+                inner: CachePolicy::NonCacheable,
+                outer: CachePolicy::NonCacheable,
+            },
+            rasr.s(),
+        ),
+        (0b001, true, true) => {
+            let policy = CachePolicy::WriteBack {
+                read_allocate: true,
+                write_allocate: true,
+            };
+            (
+                MemoryAttributes::Normal {
+                    inner: policy,
+                    outer: policy,
+                },
+                rasr.s(),
+            )
+        }
+        (tex, c, b) if tex & 0b100 != 0 => (
+            MemoryAttributes::Normal {
+                inner: armv7m_cache_policy(u32::from(c) << 1 | u32::from(b)),
+                outer: armv7m_cache_policy(tex & 0b11),
+            },
+            rasr.s(),
+        ),
+        _ => (MemoryAttributes::Reserved, rasr.s()),
+    };
+
+    MpuRegion {
+        number,
+        enabled: rasr.enable(),
+        range: start..start + size,
+        disabled_subregions: rasr.srd(),
+        access,
+        execute_never: rasr.xn(),
+        shareable,
+        attributes,
+    }
+}
+
+/// Decodes the cache policy of ARMv7-M cached memory, which is encoded in two bits.
+fn armv7m_cache_policy(bits: u32) -> CachePolicy {
+    match bits {
+        0b00 => CachePolicy::NonCacheable,
+        0b01 => CachePolicy::WriteBack {
+            read_allocate: true,
+            write_allocate: true,
+        },
+        0b10 => CachePolicy::WriteThrough {
+            read_allocate: true,
+            write_allocate: false,
+        },
+        _ => CachePolicy::WriteBack {
+            read_allocate: true,
+            write_allocate: false,
+        },
+    }
+}
+
+fn decode_armv8m_region(number: u32, rbar: u32, rlar: u32, mair: u64) -> MpuRegion {
+    let rbar = MpuRbar(rbar);
+    let rlar = MpuRlar(rlar);
+
+    let access = match rbar.ap() {
+        0b00 => MpuAccess::PrivilegedReadWrite,
+        0b01 => MpuAccess::ReadWrite,
+        0b10 => MpuAccess::PrivilegedReadOnly,
+        _ => MpuAccess::ReadOnly,
+    };
+    let attribute = (mair >> (rlar.attrindx() * 8)) as u8;
+
+    MpuRegion {
+        number,
+        enabled: rlar.en(),
+        range: limit_range(rbar.0, rlar.0),
+        disabled_subregions: 0,
+        access,
+        execute_never: rbar.xn(),
+        // 0b00 is non-shareable, 0b01 is reserved.
+        shareable: rbar.sh() >= 0b10,
+        attributes: decode_mair_attribute(attribute),
+    }
+}
+
+/// Decodes an attribute of the MAIR registers of ARMv8-M.
+fn decode_mair_attribute(attribute: u8) -> MemoryAttributes {
+    let outer = attribute >> 4;
+    let inner = attribute & 0xF;
+
+    if outer == 0 {
+        return match inner {
+            0b0000 => MemoryAttributes::Device(DeviceType::nGnRnE),
+            0b0100 => MemoryAttributes::Device(DeviceType::nGnRE),
+            0b1000 => MemoryAttributes::Device(DeviceType::nGRE),
+            0b1100 => MemoryAttributes::Device(DeviceType::GRE),
+            _ => MemoryAttributes::Reserved,
+        };
+    }
+
+    match (armv8m_cache_policy(outer), armv8m_cache_policy(inner)) {
+        (Some(outer), Some(inner)) => MemoryAttributes::Normal { inner, outer },
+        _ => MemoryAttributes::Reserved,
+    }
+}
+
+/// Decodes a nibble of a normal memory attribute of ARMv8-M.
+fn armv8m_cache_policy(bits: u8) -> Option<CachePolicy> {
+    let read_allocate = bits & 0b10 != 0;
+    let write_allocate = bits & 0b01 != 0;
+
+    Some(match bits >> 2 {
+        _ if bits == 0b0100 => CachePolicy::NonCacheable,
+        // The allocation bits 0b00 are reserved for transient memory.
+        0b00 | 0b01 if bits & 0b11 == 0 => return None,
+        0b00 | 0b10 => CachePolicy::WriteThrough {
+            read_allocate,
+            write_allocate,
+        },
+        _ => CachePolicy::WriteBack {
+            read_allocate,
+            write_allocate,
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn armv7m_regions_are_decoded() {
+        // 64 KiB of SRAM, full access, execute never, WB-RWA, shareable, the top subregion
+        // disabled.
+        let rasr = 1 << 28 | 0b011 << 24 | 0b001 << 19 | 1 << 18 | 1 << 17 | 1 << 16;
+        let region = decode_armv7m_region(2, 0x2000_0012, rasr | 0x80 << 8 | 15 << 1 | 1);
+
+        assert_eq!(
+            region,
+            MpuRegion {
+                number: 2,
+                enabled: true,
+                range: 0x2000_0000..0x2001_0000,
+                disabled_subregions: 0x80,
+                access: MpuAccess::ReadWrite,
+                execute_never: true,
+                shareable: true,
+                attributes: MemoryAttributes::Normal {
+                    inner: CachePolicy::WriteBack {
+                        read_allocate: true,
+                        write_allocate: true
+                    },
+                    outer: CachePolicy::WriteBack {
+                        read_allocate: true,
+                        write_allocate: true
+                    },
+                },
+            }
+        );
+        assert_eq!(region.attributes.to_string(), "Normal WB-RWA");
+
+        // Strongly-ordered peripherals, privileged read-only.
+        let region = decode_armv7m_region(0, 0x4000_0000, 0b101 << 24 | 28 << 1 | 1);
+        assert_eq!(region.range, 0x4000_0000..0x6000_0000);
+        assert_eq!(region.access, MpuAccess::PrivilegedReadOnly);
+        assert_eq!(region.attributes.to_string(), "Device-nGnRnE");
+    }
+
+    #[test]
+    fn armv8m_regions_are_decoded() {
+        // Attribute 1 is Normal, inner WB-RWA and outer non-cacheable.
+        let mair = 0x4F_u64 << 8;
+        let region =
+            decode_armv8m_region(1, 0x2000_0000 | 0b10 << 3 | 0b11 << 1, 0x2000_7FE3, mair);
+
+        assert_eq!(region.range, 0x2000_0000..0x2000_8000);
+        assert_eq!(region.access, MpuAccess::ReadOnly);
+        assert!(region.enabled && region.shareable && !region.execute_never);
+        assert_eq!(
+            region.attributes.to_string(),
+            "Normal inner WB-RWA, outer non-cacheable"
+        );
+
+        let region = decode_armv8m_region(0, 0x4000_0001, 0x4FFF_FFE1, mair);
+        assert!(region.execute_never);
+        assert_eq!(
+            region.attributes,
+            MemoryAttributes::Device(DeviceType::nGnRnE)
+        );
+    }
+}
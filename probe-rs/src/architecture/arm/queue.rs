@@ -0,0 +1,170 @@
+//! Queueing DAP register accesses.
+//!
+//! Every register access on its own costs a round trip to the probe, which dominates the time
+//! needed for most operations. A [`DapQueue`] collects reads and writes, which are then sent
+//! to the probe together with [`DapAccess::execute_queue`](super::DapAccess::execute_queue).
+
+use super::{ArmError, DpAddress, FullyQualifiedApAddress, PortType, RawDapTransfer};
+
+/// The port a queued register access goes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum QueuedPort {
+    Dp(DpAddress),
+    Ap(FullyQualifiedApAddress),
+}
+
+/// A register access in a [`DapQueue`].
+#[derive(Debug, Clone)]
+pub(crate) struct QueuedOperation {
+    pub(crate) port: QueuedPort,
+    /// The full register address, including the bank.
+    pub(crate) addr: u8,
+    pub(crate) transfer: RawDapTransfer,
+}
+
+/// A handle to a read in a [`DapQueue`], used to get the value once the queue was executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuedRead(usize);
+
+/// A queue of DP and AP register accesses.
+///
+/// Accesses are only performed once the queue is passed to
+/// [`DapAccess::execute_queue`](super::DapAccess::execute_queue). The results of reads are
+/// available afterwards, using [`DapQueue::result`].
+#[derive(Debug, Default)]
+pub struct DapQueue {
+    operations: Vec<QueuedOperation>,
+    executed: bool,
+}
+
+impl DapQueue {
+    /// Creates an empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, port: QueuedPort, addr: u8, transfer: RawDapTransfer) -> usize {
+        self.executed = false;
+        self.operations.push(QueuedOperation {
+            port,
+            addr,
+            transfer,
+        });
+        self.operations.len() - 1
+    }
+
+    /// Queues a read of a DP register.
+    pub fn read_dp(&mut self, dp: DpAddress, addr: u8) -> QueuedRead {
+        QueuedRead(self.push(
+            QueuedPort::Dp(dp),
+            addr,
+            RawDapTransfer::read(PortType::DebugPort, addr & 0xf),
+        ))
+    }
+
+    /// Queues a write to a DP register.
+    pub fn write_dp(&mut self, dp: DpAddress, addr: u8, value: u32) {
+        self.push(
+            QueuedPort::Dp(dp),
+            addr,
+            RawDapTransfer::write(PortType::DebugPort, addr & 0xf, value),
+        );
+    }
+
+    /// Queues a read of an AP register.
+    pub fn read_ap(&mut self, ap: &FullyQualifiedApAddress, addr: u8) -> QueuedRead {
+        QueuedRead(self.push(
+            QueuedPort::Ap(ap.clone()),
+            addr,
+            RawDapTransfer::read(PortType::AccessPort, addr & 0xf),
+        ))
+    }
+
+    /// Queues a write to an AP register.
+    pub fn write_ap(&mut self, ap: &FullyQualifiedApAddress, addr: u8, value: u32) {
+        self.push(
+            QueuedPort::Ap(ap.clone()),
+            addr,
+            RawDapTransfer::write(PortType::AccessPort, addr & 0xf, value),
+        );
+    }
+
+    /// The number of queued accesses.
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Returns `true` if nothing was queued.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Returns the value of a queued read.
+    ///
+    /// Fails with [`ArmError::QueueNotExecuted`] if the queue was not executed since the read
+    /// was queued.
+    pub fn result(&self, read: QueuedRead) -> Result<u32, ArmError> {
+        if !self.executed {
+            return Err(ArmError::QueueNotExecuted);
+        }
+
+        Ok(self.operations[read.0].transfer.value())
+    }
+
+    pub(crate) fn operations_mut(&mut self) -> &mut [QueuedOperation] {
+        &mut self.operations
+    }
+
+    /// Marks the queue as executed, which makes the results of reads available.
+    pub(crate) fn set_executed(&mut self) {
+        self.executed = true;
+    }
+
+    /// Splits the queue into runs of accesses to the same port and register bank, which can
+    /// be sent as one batch once the port and bank are selected.
+    pub(crate) fn runs_mut(&mut self) -> impl Iterator<Item = &mut [QueuedOperation]> {
+        self.operations
+            .chunk_by_mut(|a, b| a.port == b.port && a.addr >> 4 == b.addr >> 4)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn results_need_execution() {
+        let ap = FullyQualifiedApAddress::v1_with_default_dp(0);
+        let mut queue = DapQueue::new();
+        queue.write_ap(&ap, 0x04, 0x2000_0000);
+        let read = queue.read_ap(&ap, 0x0C);
+        assert_eq!(queue.len(), 2);
+
+        assert!(matches!(
+            queue.result(read),
+            Err(ArmError::QueueNotExecuted)
+        ));
+
+        if let RawDapTransfer::Read { value, .. } = &mut queue.operations_mut()[1].transfer {
+            *value = 42;
+        }
+        queue.set_executed();
+        assert_eq!(queue.result(read).unwrap(), 42);
+    }
+
+    #[test]
+    fn runs_split_on_port_and_bank() {
+        let ap0 = FullyQualifiedApAddress::v1_with_default_dp(0);
+        let ap1 = FullyQualifiedApAddress::v1_with_default_dp(1);
+        let mut queue = DapQueue::new();
+        queue.write_ap(&ap0, 0x04, 0);
+        queue.read_ap(&ap0, 0x0C);
+        queue.read_ap(&ap0, 0xF8);
+        queue.read_ap(&ap1, 0x0C);
+        queue.read_dp(DpAddress::Default, 0x04);
+        queue.read_dp(DpAddress::Default, 0x0C);
+
+        let lengths: Vec<_> = queue.runs_mut().map(|run| run.len()).collect();
+        assert_eq!(lengths, [2, 1, 1, 2]);
+    }
+}
@@ -0,0 +1,210 @@
+//! Caching of MEM-AP registers, to avoid redundant transfers.
+//!
+//! Most memory accesses start by writing CSW and TAR, which often already hold the right value,
+//! and every new memory interface reads the ID registers of its AP again. The cache remembers
+//! these registers, so the transfers can be skipped.
+//!
+//! Only MEM-APs are cached, because other APs use the same addresses for registers which have
+//! side effects. The cache has to be invalidated whenever the state of the target is unknown,
+//! e.g. after a transfer error or when the debug port is powered up again.
+
+use super::ap::{ApClass, IDR};
+
+const CSW: u8 = 0x00;
+const TAR: u8 = 0x04;
+const DRW: u8 = 0x0C;
+const CFG: u8 = 0xF4;
+const BASE: u8 = 0xF8;
+const IDR_ADDRESS: u8 = 0xFC;
+
+/// TAR auto-increment is only guaranteed for the lowest 10 bits.
+const AUTOINCR_LIMIT: u64 = 0x400;
+
+/// Cached registers of a single AP.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ApRegisterCache {
+    /// Set once IDR shows that this is a MEM-AP.
+    is_mem_ap: bool,
+    /// The value last written to CSW.
+    csw: Option<u32>,
+    /// The current value of TAR.
+    tar: Option<u32>,
+    idr: Option<u32>,
+    cfg: Option<u32>,
+    base: Option<u32>,
+}
+
+impl ApRegisterCache {
+    /// Returns the cached value of a register, if reading it can be skipped.
+    pub(crate) fn read(&self, addr: u8) -> Option<u32> {
+        match addr {
+            IDR_ADDRESS => self.idr,
+            CFG if self.is_mem_ap => self.cfg,
+            BASE if self.is_mem_ap => self.base,
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if writing `value` to the register would not change it.
+    pub(crate) fn write_is_redundant(&self, addr: u8, value: u32) -> bool {
+        if !self.is_mem_ap {
+            return false;
+        }
+
+        match addr {
+            CSW => self.csw == Some(value),
+            TAR => self.tar == Some(value),
+            _ => false,
+        }
+    }
+
+    /// Records the value read from a register.
+    pub(crate) fn record_read(&mut self, addr: u8, value: u32) {
+        match addr {
+            // APs which don't exist, or are not accessible yet, read as zero.
+            IDR_ADDRESS if value != 0 => {
+                self.idr = Some(value);
+                self.is_mem_ap = IDR::try_from(value).is_ok_and(|idr| idr.CLASS == ApClass::MemAp);
+            }
+            CFG => self.cfg = Some(value),
+            BASE => self.base = Some(value),
+            TAR => self.tar = Some(value),
+            DRW => self.record_data_access(1),
+            _ => {}
+        }
+    }
+
+    /// Records a write to a register.
+    pub(crate) fn record_write(&mut self, addr: u8, value: u32) {
+        match addr {
+            CSW => {
+                self.csw = Some(value);
+                // The address increment may have changed, which TAR tracking depends on.
+                self.tar = None;
+            }
+            TAR => self.tar = Some(value),
+            DRW => self.record_data_access(1),
+            _ => {}
+        }
+    }
+
+    /// Records the values read from a register with a repeated read.
+    pub(crate) fn record_repeated_read(&mut self, addr: u8, values: &[u32]) {
+        match (addr, values.last()) {
+            (DRW, _) => self.record_data_access(values.len()),
+            (_, Some(value)) => self.record_read(addr, *value),
+            (_, None) => {}
+        }
+    }
+
+    /// Records the values written to a register with a repeated write.
+    pub(crate) fn record_repeated_write(&mut self, addr: u8, values: &[u32]) {
+        match (addr, values.last()) {
+            (DRW, _) => self.record_data_access(values.len()),
+            (_, Some(value)) => self.record_write(addr, *value),
+            (_, None) => {}
+        }
+    }
+
+    /// Records `count` accesses to DRW, which may advance TAR.
+    pub(crate) fn record_data_access(&mut self, count: usize) {
+        let (Some(csw), Some(tar)) = (self.csw, self.tar) else {
+            self.tar = None;
+            return;
+        };
+
+        let size = match csw & 0b111 {
+            0 => 1,
+            1 => 2,
+            2 => 4,
+            _ => {
+                self.tar = None;
+                return;
+            }
+        };
+
+        self.tar = match (csw >> 4) & 0b11 {
+            // No increment
+            0 => Some(tar),
+            // Single increment, as long as it stays within the auto-increment limit.
+            1 => {
+                let end = u64::from(tar) + size * count as u64;
+                (u64::from(tar) / AUTOINCR_LIMIT == end / AUTOINCR_LIMIT).then_some(end as u32)
+            }
+            _ => None,
+        };
+    }
+
+    /// Forgets all registers which may have changed without us knowing.
+    ///
+    /// The ID registers are constant, so they are kept.
+    pub(crate) fn invalidate(&mut self) {
+        self.csw = None;
+        self.tar = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // IDR of an AHB-AP.
+    const MEM_AP_IDR: u32 = 0x2477_0011;
+    // CSW with 32 bit size and single increment.
+    const CSW_32_SINGLE: u32 = 0x2300_0012;
+
+    fn mem_ap() -> ApRegisterCache {
+        let mut cache = ApRegisterCache::default();
+        cache.record_read(IDR_ADDRESS, MEM_AP_IDR);
+        cache.record_write(CSW, CSW_32_SINGLE);
+        cache
+    }
+
+    #[test]
+    fn only_caches_mem_aps() {
+        let mut cache = ApRegisterCache::default();
+        cache.record_write(TAR, 0x2000_0000);
+        assert!(!cache.write_is_redundant(TAR, 0x2000_0000));
+
+        // A JTAG-AP
+        cache.record_read(IDR_ADDRESS, 0x0476_0000);
+        cache.record_read(CFG, 0);
+        assert_eq!(cache.read(IDR_ADDRESS), Some(0x0476_0000));
+        assert_eq!(cache.read(CFG), None);
+
+        let mut cache = mem_ap();
+        cache.record_read(CFG, 0);
+        assert_eq!(cache.read(CFG), Some(0));
+        assert!(cache.write_is_redundant(CSW, CSW_32_SINGLE));
+
+        let mut cache = ApRegisterCache::default();
+        cache.record_read(IDR_ADDRESS, 0);
+        assert_eq!(cache.read(IDR_ADDRESS), None);
+    }
+
+    #[test]
+    fn tar_follows_auto_increment() {
+        let mut cache = mem_ap();
+        cache.record_write(TAR, 0x2000_0000);
+        assert!(cache.write_is_redundant(TAR, 0x2000_0000));
+
+        cache.record_data_access(4);
+        assert!(cache.write_is_redundant(TAR, 0x2000_0010));
+
+        // Crossing the auto-increment limit leaves TAR unknown.
+        cache.record_write(TAR, 0x2000_03FC);
+        cache.record_data_access(1);
+        assert!(!cache.write_is_redundant(TAR, 0x2000_0400));
+    }
+
+    #[test]
+    fn invalidate_keeps_id_registers() {
+        let mut cache = mem_ap();
+        cache.record_write(TAR, 0x2000_0000);
+        cache.invalidate();
+
+        assert!(!cache.write_is_redundant(CSW, CSW_32_SINGLE));
+        assert!(!cache.write_is_redundant(TAR, 0x2000_0000));
+        assert_eq!(cache.read(IDR_ADDRESS), Some(MEM_AP_IDR));
+    }
+}
@@ -370,13 +370,25 @@ fn cortex_m_reset_catch_set(core: &mut dyn ArmMemoryInterface) -> Result<(), Arm
 }
 
 /// ResetSystem for Cortex-M devices
-fn cortex_m_reset_system(interface: &mut dyn ArmMemoryInterface) -> Result<(), ArmError> {
-    use crate::architecture::arm::core::armv7m::{Aircr, Dhcsr};
+pub(crate) fn cortex_m_reset_system(
+    interface: &mut dyn ArmMemoryInterface,
+) -> Result<(), ArmError> {
+    use crate::architecture::arm::core::armv7m::Aircr;
 
     let mut aircr = Aircr(0);
     aircr.vectkey();
     aircr.set_sysresetreq(true);
 
+    cortex_m_request_reset(interface, aircr)
+}
+
+/// Requests a reset by writing `aircr` to AIRCR, and waits until the core left the reset.
+pub(crate) fn cortex_m_request_reset(
+    interface: &mut dyn ArmMemoryInterface,
+    aircr: crate::architecture::arm::core::armv7m::Aircr,
+) -> Result<(), ArmError> {
+    use crate::architecture::arm::core::armv7m::{Aircr, Dhcsr};
+
     interface.write_word_32(Aircr::get_mmio_address(), aircr.into())?;
 
     let start = Instant::now();
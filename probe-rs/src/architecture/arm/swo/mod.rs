@@ -105,6 +105,28 @@ impl SwoConfig {
     pub fn tpiu_continuous_formatting(&self) -> bool {
         self.tpiu_continuous_formatting
     }
+
+    /// The prescaler which divides the TPIU clock to the baud rate as closely as possible.
+    ///
+    /// The SWO output is divided by `prescaler + 1`.
+    pub fn prescaler(&self) -> u32 {
+        let divider = (self.tpiu_clk + self.baud / 2) / self.baud.max(1);
+        divider.max(1) - 1
+    }
+
+    /// The baud rate the target actually generates with [`SwoConfig::prescaler`].
+    ///
+    /// If this differs from [`SwoConfig::baud`] by more than a few percent, the probe can't
+    /// receive the data reliably.
+    pub fn actual_baud(&self) -> u32 {
+        self.tpiu_clk / (self.prescaler() + 1)
+    }
+
+    /// The deviation of [`SwoConfig::actual_baud`] from [`SwoConfig::baud`], in percent.
+    pub fn baud_error(&self) -> f64 {
+        let baud = f64::from(self.baud.max(1));
+        (f64::from(self.actual_baud()) - baud).abs() * 100.0 / baud
+    }
 }
 
 /// An interface to operate SWO to be implemented on drivers that support SWO.
@@ -209,3 +231,27 @@ impl<'a> std::io::Read for SwoReader<'a> {
         Ok(swo.len())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::SwoConfig;
+
+    #[test]
+    fn prescaler_is_rounded() {
+        let config = SwoConfig::new(64_000_000).set_baud(2_000_000);
+        assert_eq!(config.prescaler(), 31);
+        assert_eq!(config.actual_baud(), 2_000_000);
+
+        // 72 MHz / 10 = 7.2 MBd is the closest to 7 MBd.
+        let config = SwoConfig::new(72_000_000).set_baud(7_000_000);
+        assert_eq!(config.prescaler(), 9);
+        assert!(config.baud_error() < 3.0);
+    }
+
+    #[test]
+    fn baud_above_clock_does_not_underflow() {
+        let config = SwoConfig::new(1_000_000).set_baud(2_000_000);
+        assert_eq!(config.prescaler(), 0);
+        assert_eq!(config.actual_baud(), 1_000_000);
+    }
+}
@@ -1,12 +1,16 @@
 use crate::{
+    metrics::Metrics,
     probe::{DebugProbe, DebugProbeError},
     CoreStatus,
 };
 
-use super::ArmError;
+use super::{
+    queue::{DapQueue, QueuedPort},
+    ArmError,
+};
 
 /// The type of port we are using.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum PortType {
     /// Debug Port (e.g. SWD or JTAG)
     DebugPort,
@@ -14,6 +18,103 @@ pub enum PortType {
     AccessPort,
 }
 
+/// A single register access in a batch of transfers, see [`RawDapAccess::raw_transfer_batch`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RawDapTransfer {
+    /// Read a register. `value` holds the read value once the batch was executed.
+    Read {
+        /// The port of the register.
+        port: PortType,
+        /// The address of the register, only the lowest 4 bits are used.
+        addr: u8,
+        /// The read value.
+        value: u32,
+    },
+    /// Write `value` to a register.
+    Write {
+        /// The port of the register.
+        port: PortType,
+        /// The address of the register, only the lowest 4 bits are used.
+        addr: u8,
+        /// The value to write.
+        value: u32,
+    },
+}
+
+impl RawDapTransfer {
+    /// Creates a read of the given register.
+    pub fn read(port: PortType, addr: u8) -> Self {
+        Self::Read {
+            port,
+            addr,
+            value: 0,
+        }
+    }
+
+    /// Creates a write to the given register.
+    pub fn write(port: PortType, addr: u8, value: u32) -> Self {
+        Self::Write { port, addr, value }
+    }
+
+    /// The port of the register.
+    pub fn port(&self) -> PortType {
+        match self {
+            Self::Read { port, .. } | Self::Write { port, .. } => *port,
+        }
+    }
+
+    /// The address of the register.
+    pub fn addr(&self) -> u8 {
+        match self {
+            Self::Read { addr, .. } | Self::Write { addr, .. } => *addr,
+        }
+    }
+
+    /// The value read or written.
+    pub fn value(&self) -> u32 {
+        match self {
+            Self::Read { value, .. } | Self::Write { value, .. } => *value,
+        }
+    }
+}
+
+/// A step in a sequence of block transfers, see [`RawDapAccess::raw_block_sequence`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlockTransfer<'a> {
+    /// Write `value` to a register once.
+    Write {
+        /// The address of the register.
+        addr: u8,
+        /// The value to write.
+        value: u32,
+    },
+    /// Read a register `values.len()` times.
+    ReadRepeated {
+        /// The address of the register.
+        addr: u8,
+        /// Holds the read values once the sequence was executed.
+        values: &'a mut [u32],
+    },
+    /// Write all `values` to a register.
+    WriteRepeated {
+        /// The address of the register.
+        addr: u8,
+        /// The values to write.
+        values: &'a [u32],
+    },
+}
+
+impl BlockTransfer<'_> {
+    /// The address of the register.
+    pub fn addr(&self) -> u8 {
+        match self {
+            Self::Write { addr, .. }
+            | Self::ReadRepeated { addr, .. }
+            | Self::WriteRepeated { addr, .. } => *addr,
+        }
+    }
+}
+
 bitfield::bitfield! {
     /// A struct to describe the default CMSIS-DAP pins that one can toggle from the host.
     #[derive(Copy, Clone)]
@@ -184,6 +285,57 @@ pub trait RawDapAccess {
         Ok(())
     }
 
+    /// Execute a batch of register reads and writes, in order.
+    ///
+    /// Probes which can queue transfers send the whole batch with as few round trips as
+    /// possible. The default implementation performs the transfers one by one. If a transfer
+    /// fails, the error is returned and the remaining transfers are not executed.
+    ///
+    /// Only the lowest 4 bits of the addresses are used. Bank switching is the caller's
+    /// responsibility.
+    fn raw_transfer_batch(&mut self, transfers: &mut [RawDapTransfer]) -> Result<(), ArmError> {
+        for transfer in transfers {
+            match transfer {
+                RawDapTransfer::Read { port, addr, value } => {
+                    *value = self.raw_read_register(*port, *addr)?;
+                }
+                RawDapTransfer::Write { port, addr, value } => {
+                    self.raw_write_register(*port, *addr, *value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Execute a sequence of single and repeated register accesses on one port, in order.
+    ///
+    /// This is the fast path for large memory transfers: the target address is written, followed
+    /// by a block of data register accesses, as often as needed. Probes which can keep several
+    /// packets in flight send the whole sequence without waiting for each response. The default
+    /// implementation executes the steps one by one.
+    fn raw_block_sequence(
+        &mut self,
+        port: PortType,
+        steps: &mut [BlockTransfer<'_>],
+    ) -> Result<(), ArmError> {
+        for step in steps {
+            match step {
+                BlockTransfer::Write { addr, value } => {
+                    self.raw_write_register(port, *addr, *value)?
+                }
+                BlockTransfer::ReadRepeated { addr, values } => {
+                    self.raw_read_block(port, *addr, values)?
+                }
+                BlockTransfer::WriteRepeated { addr, values } => {
+                    self.raw_write_block(port, *addr, values)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Flush any outstanding writes.
     ///
     /// By default, this does nothing -- but in probes that implement write
@@ -322,4 +474,62 @@ pub trait DapAccess {
         }
         Ok(())
     }
+
+    /// Execute a sequence of single and repeated accesses to registers of an AP, in order.
+    ///
+    /// All registers have to be in the same register bank. See
+    /// [`RawDapAccess::raw_block_sequence`].
+    fn ap_block_sequence(
+        &mut self,
+        ap: &FullyQualifiedApAddress,
+        steps: &mut [BlockTransfer<'_>],
+    ) -> Result<(), ArmError> {
+        for step in steps {
+            match step {
+                BlockTransfer::Write { addr, value } => {
+                    self.write_raw_ap_register(ap, *addr, *value)?
+                }
+                BlockTransfer::ReadRepeated { addr, values } => {
+                    self.read_raw_ap_register_repeated(ap, *addr, values)?
+                }
+                BlockTransfer::WriteRepeated { addr, values } => {
+                    self.write_raw_ap_register_repeated(ap, *addr, values)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Execute all register accesses of `queue`, in order.
+    ///
+    /// Implementations send consecutive accesses to the same port and bank as one batch,
+    /// so the queue is executed with as few round trips as possible. The default
+    /// implementation performs the accesses one by one.
+    fn execute_queue(&mut self, queue: &mut DapQueue) -> Result<(), ArmError> {
+        for operation in queue.operations_mut() {
+            match (&operation.port, &mut operation.transfer) {
+                (QueuedPort::Dp(dp), RawDapTransfer::Read { value, .. }) => {
+                    *value = self.read_raw_dp_register(*dp, operation.addr)?;
+                }
+                (QueuedPort::Dp(dp), RawDapTransfer::Write { value, .. }) => {
+                    self.write_raw_dp_register(*dp, operation.addr, *value)?;
+                }
+                (QueuedPort::Ap(ap), RawDapTransfer::Read { value, .. }) => {
+                    *value = self.read_raw_ap_register(ap, operation.addr)?;
+                }
+                (QueuedPort::Ap(ap), RawDapTransfer::Write { value, .. }) => {
+                    self.write_raw_ap_register(ap, operation.addr, *value)?;
+                }
+            }
+        }
+        queue.set_executed();
+
+        Ok(())
+    }
+
+    /// The transfer counters of the probe, if the interface has access to them.
+    fn metrics(&self) -> Option<&Metrics> {
+        None
+    }
 }
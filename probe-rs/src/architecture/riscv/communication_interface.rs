@@ -682,7 +682,7 @@ impl<'state> RiscvCommunicationInterface<'state> {
         Ok(())
     }
 
-    fn halted_access<R>(
+    pub(super) fn halted_access<R>(
         &mut self,
         op: impl FnOnce(&mut Self) -> Result<R, RiscvError>,
     ) -> Result<R, RiscvError> {
@@ -3,18 +3,19 @@
 use crate::{
     architecture::riscv::sequences::RiscvDebugSequence,
     core::{
-        Architecture, BreakpointCause, CoreInformation, CoreRegisters, RegisterId, RegisterValue,
+        Architecture, BreakpointCause, CoreInformation, CoreRegisters, InterruptMasking,
+        RegisterId, RegisterValue,
     },
     memory::{valid_32bit_address, CoreMemoryInterface},
     memory_mapped_bitfield_register,
     probe::DebugProbeError,
     semihosting::decode_semihosting_syscall,
     CoreInterface, CoreRegister, CoreStatus, CoreType, Error, HaltReason, InstructionSet,
-    MemoryInterface, MemoryMappedRegister, SemihostingCommand,
+    MemoryInterface, MemoryMappedRegister, SemihostingCommand, VectorCatchCondition,
 };
 use bitfield::bitfield;
 use communication_interface::{AbstractCommandErrorKind, RiscvCommunicationInterface, RiscvError};
-use registers::{FP, RA, RISCV_CORE_REGSISTERS, SP};
+use registers::{FP, RA, RISCV_CORE_REGSISTERS, RISCV_WITH_FP_CORE_REGISTERS, SP};
 use std::{
     sync::Arc,
     time::{Duration, Instant},
@@ -42,11 +43,45 @@ impl<'state> Riscv32<'state> {
         state: &'state mut RiscvCoreState,
         sequence: Arc<dyn RiscvDebugSequence>,
     ) -> Result<Self, RiscvError> {
-        Ok(Self {
+        let mut core = Self {
             interface,
             state,
             sequence,
-        })
+        };
+
+        if core.state.fp_present.is_none() {
+            // The register file depends on the extensions, so they are read once per session.
+            match core
+                .interface
+                .halted_access(|interface| interface.read_csr(Misa::get_mmio_address() as u16))
+            {
+                Ok(misa) => core.state.fp_present = Some(Misa(misa).has_fpu()),
+                Err(error) => tracing::debug!("Failed to read misa: {error}"),
+            }
+        }
+
+        Ok(core)
+    }
+
+    /// Runs `op` with the FPU enabled in `mstatus`, because the floating point registers can't
+    /// be accessed while the firmware has it turned off.
+    fn with_fpu_enabled<R>(
+        &mut self,
+        op: impl FnOnce(&mut Self) -> Result<R, RiscvError>,
+    ) -> Result<R, RiscvError> {
+        let mstatus = Mstatus(self.read_csr(Mstatus::get_mmio_address() as u16)?);
+        if mstatus.fs() != 0 {
+            return op(self);
+        }
+
+        let mut enabled = mstatus;
+        enabled.set_fs(0b01);
+        self.write_csr(Mstatus::get_mmio_address() as u16, enabled.0)?;
+
+        let result = op(self);
+
+        self.write_csr(Mstatus::get_mmio_address() as u16, mstatus.0)?;
+        result
     }
 
     fn read_csr(&mut self, address: u16) -> Result<u32, RiscvError> {
@@ -191,8 +226,138 @@ impl<'state> Riscv32<'state> {
 
         Ok(tselect_index)
     }
+
+    /// Finds a trigger which can halt the core on exceptions. The search starts at the last
+    /// trigger, because breakpoints use the first ones.
+    fn find_exception_trigger(&mut self) -> Result<Option<u32>, crate::Error> {
+        let tselect = 0x7a0;
+        let tdata1 = 0x7a1;
+        let tinfo = 0x7a4;
+
+        for index in (0..self.available_breakpoint_units()?).rev() {
+            self.write_csr(tselect, index)?;
+
+            let supported = match self.read_csr(tinfo) {
+                Ok(tinfo_val) => tinfo_val & (1 << ETRIGGER_TYPE) != 0,
+                // Without `tinfo`, only a trigger which already is an exception trigger is known to work.
+                Err(RiscvError::AbstractCommand(AbstractCommandErrorKind::Exception)) => {
+                    Etrigger(self.read_csr(tdata1)?).type_() == ETRIGGER_TYPE
+                }
+                Err(other) => return Err(other.into()),
+            };
+
+            if supported {
+                return Ok(Some(index));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Enables or disables halting on the exceptions with the given causes, which are a bitmask
+    /// of the `mcause` exception codes.
+    fn catch_exceptions(&mut self, causes: u32, enabled: bool) -> Result<(), crate::Error> {
+        let was_running = !self.core_halted()?;
+        if was_running {
+            self.halt(Duration::from_millis(100))?;
+        }
+
+        let result = self.catch_exceptions_halted(causes, enabled);
+
+        if was_running {
+            self.resume_core()?;
+        }
+
+        result
+    }
+
+    fn catch_exceptions_halted(&mut self, causes: u32, enabled: bool) -> Result<(), crate::Error> {
+        let tselect = 0x7a0;
+        let tdata1 = 0x7a1;
+        let tdata2 = 0x7a2;
+
+        let Some(index) = self.find_exception_trigger()? else {
+            return Err(Error::NotImplemented(
+                "vector catch without an exception trigger",
+            ));
+        };
+
+        self.write_csr(tselect, index)?;
+        let mut mask = if Etrigger(self.read_csr(tdata1)?).type_() == ETRIGGER_TYPE {
+            self.read_csr(tdata2)?
+        } else {
+            0
+        };
+        if enabled {
+            mask |= causes;
+        } else {
+            mask &= !causes;
+        }
+
+        self.write_csr(tdata1, 0)?;
+        self.write_csr(tdata2, mask)?;
+
+        if mask != 0 {
+            let mut etrigger = Etrigger(0);
+            etrigger.set_type(ETRIGGER_TYPE);
+            etrigger.set_dmode(true);
+            etrigger.set_m(true);
+            etrigger.set_s(true);
+            etrigger.set_u(true);
+            // Enter debug mode
+            etrigger.set_action(1);
+
+            self.write_csr(tdata1, etrigger.0)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_vector_catch(
+        &mut self,
+        condition: VectorCatchCondition,
+        enabled: bool,
+    ) -> Result<(), crate::Error> {
+        match condition {
+            VectorCatchCondition::CoreReset => {
+                if enabled {
+                    self.sequence.reset_catch_set(&mut self.interface)?;
+                } else {
+                    self.sequence.reset_catch_clear(&mut self.interface)?;
+                }
+                Ok(())
+            }
+            VectorCatchCondition::SecureFault => {
+                Err(Error::NotImplemented("SecureFault vector catch on RISC-V"))
+            }
+            VectorCatchCondition::HardFault => self.catch_exceptions(
+                USAGE_FAULT_CAUSES | BUS_FAULT_CAUSES | PAGE_FAULT_CAUSES,
+                enabled,
+            ),
+            VectorCatchCondition::BusFault => self.catch_exceptions(BUS_FAULT_CAUSES, enabled),
+            VectorCatchCondition::MemManage => self.catch_exceptions(PAGE_FAULT_CAUSES, enabled),
+            VectorCatchCondition::UsageFault => self.catch_exceptions(USAGE_FAULT_CAUSES, enabled),
+            VectorCatchCondition::All => {
+                match self.set_vector_catch(VectorCatchCondition::CoreReset, enabled) {
+                    Ok(()) | Err(Error::Riscv(RiscvError::ResetHaltRequestNotSupported)) => {}
+                    Err(error) => return Err(error),
+                }
+                self.set_vector_catch(VectorCatchCondition::HardFault, enabled)
+            }
+        }
+    }
 }
 
+/// The type of exception triggers in `tdata1`.
+const ETRIGGER_TYPE: u32 = 5;
+
+/// The illegal instruction and misaligned access exceptions.
+const USAGE_FAULT_CAUSES: u32 = (1 << 0) | (1 << 2) | (1 << 4) | (1 << 6);
+/// The instruction, load and store access fault exceptions.
+const BUS_FAULT_CAUSES: u32 = (1 << 1) | (1 << 5) | (1 << 7);
+/// The instruction, load and store page fault exceptions.
+const PAGE_FAULT_CAUSES: u32 = (1 << 12) | (1 << 13) | (1 << 15);
+
 impl<'state> CoreInterface for Riscv32<'state> {
     fn wait_for_core_halted(&mut self, timeout: Duration) -> Result<(), crate::Error> {
         self.interface.wait_for_core_halted(timeout)?;
@@ -281,6 +446,10 @@ impl<'state> CoreInterface for Riscv32<'state> {
     }
 
     fn step(&mut self) -> Result<CoreInformation, crate::Error> {
+        self.step_with(InterruptMasking::Masked)
+    }
+
+    fn step_with(&mut self, interrupts: InterruptMasking) -> Result<CoreInformation, crate::Error> {
         let halt_reason = self.status()?;
         if matches!(
             halt_reason,
@@ -320,8 +489,8 @@ impl<'state> CoreInterface for Riscv32<'state> {
         let mut dcsr = Dcsr(self.read_core_reg(RegisterId(0x7b0))?.try_into()?);
         // Set it up, so that the next `self.run()` will only do a single step
         dcsr.set_step(true);
-        // Disable any interrupts during single step.
-        dcsr.set_stepie(false);
+        // Disable any interrupts during single step, unless they should be taken.
+        dcsr.set_stepie(interrupts == InterruptMasking::Unmasked);
         dcsr.set_stopcount(true);
         self.write_csr(0x7b0, dcsr.0)?;
 
@@ -353,9 +522,13 @@ impl<'state> CoreInterface for Riscv32<'state> {
     }
 
     fn read_core_reg(&mut self, address: RegisterId) -> Result<RegisterValue, crate::Error> {
-        self.read_csr(address.0)
-            .map(|v| v.into())
-            .map_err(|e| e.into())
+        let value = if is_fp_register(address) {
+            self.with_fpu_enabled(|core| core.read_csr(address.0))?
+        } else {
+            self.read_csr(address.0)?
+        };
+
+        Ok(value.into())
     }
 
     fn write_core_reg(
@@ -369,7 +542,13 @@ impl<'state> CoreInterface for Riscv32<'state> {
             self.state.pc_written = true;
         }
 
-        self.write_csr(address.0, value).map_err(|e| e.into())
+        if is_fp_register(address) {
+            self.with_fpu_enabled(|core| core.write_csr(address.0, value))?;
+        } else {
+            self.write_csr(address.0, value)?;
+        }
+
+        Ok(())
     }
 
     fn available_breakpoint_units(&mut self) -> Result<u32, crate::Error> {
@@ -547,7 +726,11 @@ impl<'state> CoreInterface for Riscv32<'state> {
     }
 
     fn registers(&self) -> &'static CoreRegisters {
-        &RISCV_CORE_REGSISTERS
+        if self.state.fp_present == Some(true) {
+            &RISCV_WITH_FP_CORE_REGISTERS
+        } else {
+            &RISCV_CORE_REGSISTERS
+        }
     }
 
     fn program_counter(&self) -> &'static CoreRegister {
@@ -575,6 +758,14 @@ impl<'state> CoreInterface for Riscv32<'state> {
         Ok(())
     }
 
+    fn enable_vector_catch(&mut self, condition: VectorCatchCondition) -> Result<(), Error> {
+        self.set_vector_catch(condition, true)
+    }
+
+    fn disable_vector_catch(&mut self, condition: VectorCatchCondition) -> Result<(), Error> {
+        self.set_vector_catch(condition, false)
+    }
+
     fn architecture(&self) -> Architecture {
         Architecture::Riscv
     }
@@ -605,11 +796,7 @@ impl<'state> CoreInterface for Riscv32<'state> {
 
     fn fpu_support(&mut self) -> Result<bool, crate::error::Error> {
         // Read the extensions from the Machine ISA regiseter.
-        let isa_extensions =
-            Misa::from(self.read_csr(Misa::get_mmio_address() as u16)?).extensions();
-        // Mask for the D(double float), F(single float) and Q(quad float) extension bits.
-        let mask = (1 << 3) | (1 << 5) | (1 << 16);
-        Ok(isa_extensions & mask != 0)
+        Ok(Misa::from(self.read_csr(Misa::get_mmio_address() as u16)?).has_fpu())
     }
 
     fn reset_catch_set(&mut self) -> Result<(), Error> {
@@ -653,6 +840,9 @@ pub struct RiscvCoreState {
 
     /// The semihosting command that was decoded at the current program counter
     semihosting_command: Option<SemihostingCommand>,
+
+    /// Whether the hart has floating point registers, or `None` if `misa` couldn't be read yet.
+    fp_present: Option<bool>,
 }
 
 impl RiscvCoreState {
@@ -662,6 +852,7 @@ impl RiscvCoreState {
             hw_breakpoints: None,
             pc_written: false,
             semihosting_command: None,
+            fp_present: None,
         }
     }
 }
@@ -847,6 +1038,19 @@ bitfield! {
     load, set_load: 0;
 }
 
+bitfield! {
+    /// An exception trigger, see the RISC-V debug specification, 5.2.13.
+    struct Etrigger(u32);
+    impl Debug;
+
+    type_, set_type: 31, 28;
+    dmode, set_dmode: 27;
+    m, set_m: 9;
+    s, set_s: 7;
+    u, set_u: 6;
+    action, set_action: 5, 0;
+}
+
 memory_mapped_bitfield_register! {
     /// Isa and Extensions (see RISC-V Privileged Spec, 3.1.1)
     pub struct Misa(u32);
@@ -858,3 +1062,29 @@ memory_mapped_bitfield_register! {
     /// Standard RISC-V extensions
     extensions, _: 25, 0;
 }
+
+impl Misa {
+    /// Whether the hart implements the F, D or Q extension, which add floating point registers.
+    fn has_fpu(&self) -> bool {
+        // Mask for the D(double float), F(single float) and Q(quad float) extension bits.
+        let mask = (1 << 3) | (1 << 5) | (1 << 16);
+        self.extensions() & mask != 0
+    }
+}
+
+memory_mapped_bitfield_register! {
+    /// Machine Status (see RISC-V Privileged Spec, 3.1.6)
+    pub struct Mstatus(u32);
+    0x300, "mstatus",
+    impl From;
+
+    /// The state of the floating point unit. Floating point instructions trap while it's 0 (Off).
+    fs, set_fs: 14, 13;
+}
+
+/// Returns whether the register is a floating point register or CSR, which can only be accessed
+/// while the FPU is enabled.
+fn is_fp_register(register: RegisterId) -> bool {
+    // fflags, frm and fcsr are CSRs 0x001 to 0x003, f0 to f31 are registers 0x1020 to 0x103F.
+    matches!(register.0, 0x001..=0x003 | 0x1020..=0x103F)
+}
@@ -49,6 +49,15 @@ pub const S1: CoreRegister = CoreRegister {
 pub(crate) static RISCV_CORE_REGSISTERS: Lazy<CoreRegisters> =
     Lazy::new(|| CoreRegisters::new(RISCV_REGISTERS_SET.iter().collect()));
 
+pub(crate) static RISCV_WITH_FP_CORE_REGISTERS: Lazy<CoreRegisters> = Lazy::new(|| {
+    CoreRegisters::new(
+        RISCV_REGISTERS_SET
+            .iter()
+            .chain(RISCV_FP_REGISTERS_SET)
+            .collect(),
+    )
+});
+
 static RISCV_REGISTERS_SET: &[CoreRegister] = &[
     CoreRegister {
         roles: &[RegisterRole::Core("x0"), RegisterRole::Other("zero")],
@@ -231,5 +240,338 @@ static RISCV_REGISTERS_SET: &[CoreRegister] = &[
         unwind_rule: UnwindRule::Clear,
     },
     PC,
-    // TODO: Add FPU registers
+];
+
+/// The registers of the F extension. Only the lower 32 bits of the registers are accessed, so
+/// the values of double precision registers are truncated.
+static RISCV_FP_REGISTERS_SET: &[CoreRegister] = &[
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f0"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft0"),
+        ],
+        id: RegisterId(0x1020),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f1"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft1"),
+        ],
+        id: RegisterId(0x1021),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f2"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft2"),
+        ],
+        id: RegisterId(0x1022),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f3"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft3"),
+        ],
+        id: RegisterId(0x1023),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f4"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft4"),
+        ],
+        id: RegisterId(0x1024),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f5"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft5"),
+        ],
+        id: RegisterId(0x1025),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f6"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft6"),
+        ],
+        id: RegisterId(0x1026),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f7"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft7"),
+        ],
+        id: RegisterId(0x1027),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f8"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs0"),
+        ],
+        id: RegisterId(0x1028),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f9"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs1"),
+        ],
+        id: RegisterId(0x1029),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f10"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fa0"),
+        ],
+        id: RegisterId(0x102A),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f11"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fa1"),
+        ],
+        id: RegisterId(0x102B),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f12"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fa2"),
+        ],
+        id: RegisterId(0x102C),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f13"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fa3"),
+        ],
+        id: RegisterId(0x102D),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f14"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fa4"),
+        ],
+        id: RegisterId(0x102E),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f15"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fa5"),
+        ],
+        id: RegisterId(0x102F),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f16"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fa6"),
+        ],
+        id: RegisterId(0x1030),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f17"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fa7"),
+        ],
+        id: RegisterId(0x1031),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f18"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs2"),
+        ],
+        id: RegisterId(0x1032),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f19"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs3"),
+        ],
+        id: RegisterId(0x1033),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f20"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs4"),
+        ],
+        id: RegisterId(0x1034),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f21"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs5"),
+        ],
+        id: RegisterId(0x1035),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f22"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs6"),
+        ],
+        id: RegisterId(0x1036),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f23"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs7"),
+        ],
+        id: RegisterId(0x1037),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f24"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs8"),
+        ],
+        id: RegisterId(0x1038),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f25"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs9"),
+        ],
+        id: RegisterId(0x1039),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f26"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs10"),
+        ],
+        id: RegisterId(0x103A),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f27"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("fs11"),
+        ],
+        id: RegisterId(0x103B),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f28"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft8"),
+        ],
+        id: RegisterId(0x103C),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f29"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft9"),
+        ],
+        id: RegisterId(0x103D),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f30"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft10"),
+        ],
+        id: RegisterId(0x103E),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("f31"),
+            RegisterRole::FloatingPoint,
+            RegisterRole::Other("ft11"),
+        ],
+        id: RegisterId(0x103F),
+        data_type: RegisterDataType::FloatingPoint(32),
+        unwind_rule: UnwindRule::Clear,
+    },
+    CoreRegister {
+        roles: &[
+            RegisterRole::Core("fcsr"),
+            RegisterRole::FloatingPointStatus,
+        ],
+        id: RegisterId(0x003),
+        data_type: RegisterDataType::UnsignedInteger(32),
+        unwind_rule: UnwindRule::Clear,
+    },
 ];
@@ -0,0 +1,286 @@
+//! An async adapter for [`Session`].
+//!
+//! Probe operations block until the probe answered, and some of them, like flashing, take
+//! seconds. [`AsyncSession`] moves the session to a worker thread, so async applications can
+//! await these operations instead of blocking their executor.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{mpsc, Arc},
+    task::{Context, Poll, Waker},
+    thread,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+
+use crate::{Core, CoreInformation, CoreStatus, Error, MemoryInterface, Session};
+
+type Job = Box<dyn FnOnce(&mut Session) + Send>;
+
+enum Message {
+    Run(Job),
+    Stop(oneshot::Sender<Session>),
+}
+
+/// A [`Session`] which is driven from async code.
+///
+/// The session is owned by a worker thread, which performs the requested operations one after
+/// another. The futures returned by the methods complete once the operation has finished. They
+/// don't depend on a specific async runtime, so they can be used with tokio, async-std or smol.
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// use probe_rs::{flashing, probe::list::Lister, AsyncSession, Permissions};
+///
+/// let lister = Lister::new();
+/// let probe = lister.list_all()[0].open()?;
+/// let session = AsyncSession::new(probe.attach("nrf51822_xxAC", Permissions::default())?)?;
+///
+/// // Any operation can be run on the session with `run`.
+/// session
+///     .run(|session| flashing::download_file(session, "firmware.elf", flashing::Format::Elf))
+///     .await??;
+///
+/// session.reset(0).await?;
+/// let data = session.read(0, 0x2000_0000, 64).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AsyncSession {
+    messages: mpsc::Sender<Message>,
+}
+
+impl AsyncSession {
+    /// Moves `session` to a new worker thread.
+    pub fn new(session: Session) -> Result<Self, Error> {
+        let (messages, receiver) = mpsc::channel();
+
+        thread::Builder::new()
+            .name("probe-rs session".to_string())
+            .spawn(move || worker(session, receiver))
+            .map_err(|e| Error::Other(format!("Failed to start the session thread: {e}")))?;
+
+        Ok(Self { messages })
+    }
+
+    /// Runs `f` with the session on the worker thread, and returns its result.
+    ///
+    /// Operations are performed in the order they were started.
+    pub async fn run<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Session) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.send(Message::Run(Box::new(move |session| {
+            sender.send(f(session))
+        })))?;
+
+        receiver.await.ok_or_else(worker_stopped)
+    }
+
+    /// Runs `f` with the core with index `core_index`, and returns its result.
+    pub async fn run_core<T, F>(&self, core_index: usize, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Core<'_>) -> Result<T, Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.run(move |session| f(&mut session.core(core_index)?))
+            .await?
+    }
+
+    /// Reads `length` bytes of memory at `address`, using the core with index `core_index`.
+    pub async fn read(
+        &self,
+        core_index: usize,
+        address: u64,
+        length: usize,
+    ) -> Result<Vec<u8>, Error> {
+        self.run_core(core_index, move |core| {
+            let mut data = vec![0; length];
+            core.read(address, &mut data)?;
+            Ok(data)
+        })
+        .await
+    }
+
+    /// Writes `data` to memory at `address`, using the core with index `core_index`.
+    pub async fn write(&self, core_index: usize, address: u64, data: Vec<u8>) -> Result<(), Error> {
+        self.run_core(core_index, move |core| core.write(address, &data))
+            .await
+    }
+
+    /// Returns the status of the core with index `core_index`.
+    pub async fn status(&self, core_index: usize) -> Result<CoreStatus, Error> {
+        self.run_core(core_index, |core| core.status()).await
+    }
+
+    /// Halts the core with index `core_index`, waiting up to `timeout` for it to halt.
+    pub async fn halt(
+        &self,
+        core_index: usize,
+        timeout: Duration,
+    ) -> Result<CoreInformation, Error> {
+        self.run_core(core_index, move |core| core.halt(timeout))
+            .await
+    }
+
+    /// Resumes the core with index `core_index`.
+    pub async fn resume(&self, core_index: usize) -> Result<(), Error> {
+        self.run_core(core_index, |core| core.run()).await
+    }
+
+    /// Resets the core with index `core_index`, and lets it run.
+    pub async fn reset(&self, core_index: usize) -> Result<(), Error> {
+        self.run_core(core_index, |core| core.reset()).await
+    }
+
+    /// Resets the core with index `core_index`, and halts it before the first instruction.
+    pub async fn reset_and_halt(
+        &self,
+        core_index: usize,
+        timeout: Duration,
+    ) -> Result<CoreInformation, Error> {
+        self.run_core(core_index, move |core| core.reset_and_halt(timeout))
+            .await
+    }
+
+    /// Waits until the operations started so far have finished, stops the worker thread and
+    /// returns the session.
+    pub async fn into_inner(self) -> Result<Session, Error> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(Message::Stop(sender))?;
+
+        receiver.await.ok_or_else(worker_stopped)
+    }
+
+    fn send(&self, message: Message) -> Result<(), Error> {
+        self.messages.send(message).map_err(|_| worker_stopped())
+    }
+}
+
+fn worker(mut session: Session, messages: mpsc::Receiver<Message>) {
+    // The loop ends once the `AsyncSession` is dropped, which also drops the session.
+    while let Ok(message) = messages.recv() {
+        match message {
+            Message::Run(job) => job(&mut session),
+            Message::Stop(sender) => {
+                sender.send(session);
+                return;
+            }
+        }
+    }
+}
+
+fn worker_stopped() -> Error {
+    Error::Other("The session thread has stopped, because an operation panicked".to_string())
+}
+
+/// A channel for a single value, which can be awaited by the receiver.
+mod oneshot {
+    use super::*;
+
+    struct Shared<T> {
+        value: Option<T>,
+        waker: Option<Waker>,
+        closed: bool,
+    }
+
+    pub(super) struct Sender<T>(Arc<Mutex<Shared<T>>>);
+
+    /// Resolves to the sent value, or `None` if the sender was dropped without sending.
+    pub(super) struct Receiver<T>(Arc<Mutex<Shared<T>>>);
+
+    pub(super) fn channel<T>() -> (Sender<T>, Receiver<T>) {
+        let shared = Arc::new(Mutex::new(Shared {
+            value: None,
+            waker: None,
+            closed: false,
+        }));
+
+        (Sender(shared.clone()), Receiver(shared))
+    }
+
+    impl<T> Sender<T> {
+        pub(super) fn send(self, value: T) {
+            self.0.lock().value = Some(value);
+            // Dropping the sender wakes the receiver.
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            let mut shared = self.0.lock();
+            shared.closed = true;
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    impl<T> Future for Receiver<T> {
+        type Output = Option<T>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let mut shared = self.0.lock();
+            if let Some(value) = shared.value.take() {
+                Poll::Ready(Some(value))
+            } else if shared.closed {
+                Poll::Ready(None)
+            } else {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_io::block_on;
+
+    use super::*;
+    use crate::{probe::fake_probe::FakeProbe, HaltReason};
+
+    fn session() -> AsyncSession {
+        let session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+
+        AsyncSession::new(session).unwrap()
+    }
+
+    #[test]
+    fn memory_and_run_control() {
+        let session = session();
+
+        block_on(async {
+            session
+                .write(0, 0x2000_0000, vec![1, 2, 3, 4])
+                .await
+                .unwrap();
+            assert_eq!(session.read(0, 0x2000_0000, 4).await.unwrap(), [1, 2, 3, 4]);
+
+            session.halt(0, Duration::from_millis(100)).await.unwrap();
+            assert_eq!(
+                session.status(0).await.unwrap(),
+                CoreStatus::Halted(HaltReason::Request)
+            );
+
+            session.resume(0).await.unwrap();
+            assert_eq!(session.status(0).await.unwrap(), CoreStatus::Running);
+        });
+    }
+
+    #[test]
+    fn panicking_operation_stops_worker() {
+        let session = session();
+
+        block_on(async {
+            assert!(session.run(|_| panic!("test")).await.is_err());
+            assert!(session.status(0).await.is_err());
+            assert!(session.into_inner().await.is_err());
+        });
+    }
+}
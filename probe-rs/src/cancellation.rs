@@ -0,0 +1,43 @@
+//! Cancelling long-running operations.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A token to cancel a long-running operation, like flashing, from another thread.
+///
+/// Operations check the token between steps, e.g. after each flash sector or page, so they stop
+/// in a consistent state: the current step is finished and the flash algorithm is
+/// uninitialized before the operation returns a `Cancelled` error. Operations which can't be
+/// split, like a chip erase, are only cancelled before they start.
+///
+/// Clones of a token share their state, so cancelling one cancels all of them.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation of the operations using this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Uses `cancelled` as the state of the token, e.g. to cancel from a signal handler which sets it.
+impl From<Arc<AtomicBool>> for CancellationToken {
+    fn from(cancelled: Arc<AtomicBool>) -> Self {
+        Self { cancelled }
+    }
+}
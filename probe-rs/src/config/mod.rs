@@ -17,23 +17,34 @@
 //! ## Adding targets at runtime
 //!
 //! To add a target at runtime, the [add_target_from_yaml] function can
-//! be used to read targets from a YAML file.
+//! be used to read targets from a YAML file, and [add_targets_from_dir] reads
+//! all target files in a directory.
+//!
+//! Crates which ship support for chips that are not built into probe-rs can
+//! add their families with [add_families], or implement a
+//! [`Vendor`](crate::vendor::Vendor) which provides both the families and
+//! their debug sequences, and register it with
+//! [`register_plugin`](crate::vendor::register_plugin).
 //!
 
 mod chip_info;
 pub(crate) mod registry;
+mod search;
 mod target;
 
 pub use probe_rs_target::{
-    Chip, ChipFamily, Core, CoreType, FlashProperties, GenericRegion, InstructionSet, MemoryRange,
-    MemoryRegion, NvmRegion, PageInfo, RamRegion, RawFlashAlgorithm, ScanChainElement,
-    SectorDescription, SectorInfo, TargetDescriptionSource,
+    Chip, ChipFamily, Core, CoreType, FlashProperties, GenericRegion, InstructionSet,
+    MemoryInitSequence, MemoryInitStep, MemoryRange, MemoryRegion, NvmRegion, PageInfo,
+    ProvisioningRegion, RamRegion, RawFlashAlgorithm, ScanChainElement, SectorDescription,
+    SectorInfo, TargetDescriptionSource,
 };
 
 pub use registry::{
-    add_target_from_yaml, families, get_target_and_family_by_name, get_target_by_name,
-    get_targets_by_family_name, search_chips, RegistryError,
+    add_families, add_target_from_yaml, add_targets_from_dir, families,
+    get_target_and_family_by_name, get_target_by_name, get_targets_by_family_name, search_chips,
+    search_chips_fuzzy, RegistryError,
 };
+pub use search::{ChipMatch, ChipSuggestions, MatchKind};
 pub use target::{DebugSequence, Target, TargetSelector};
 
 // Crate-internal API
@@ -1,13 +1,15 @@
 //! Internal target registry
 
+use super::search::{ChipMatch, ChipSuggestions, SearchIndex};
 use super::{Chip, ChipFamily, ChipInfo, Core, Target, TargetDescriptionSource};
 use crate::config::CoreType;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use parking_lot::{RwLock, RwLockReadGuard};
 use probe_rs_target::{CoreAccessOptions, RiscvCoreAccessOptions};
 use std::collections::HashMap;
 use std::io::Read;
 use std::ops::Deref;
+use std::path::Path;
 
 static REGISTRY: Lazy<RwLock<Registry>> =
     Lazy::new(|| RwLock::new(Registry::from_builtin_families()));
@@ -16,8 +18,8 @@ static REGISTRY: Lazy<RwLock<Registry>> =
 /// with the internal registry of targets.
 #[derive(Debug, thiserror::Error, docsplay::Display)]
 pub enum RegistryError {
-    /// The requested chip '{0}' was not found in the list of known targets.
-    ChipNotFound(String),
+    /// The requested chip '{0}' was not found in the list of known targets.{1}
+    ChipNotFound(String, ChipSuggestions),
     /// Found multiple chips matching '{0}', unable to select a single chip. ({1})
     ChipNotUnique(String, String),
     /// The connected chip could not automatically be determined.
@@ -39,6 +41,7 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
             manufacturer: None,
             generated_from_pack: false,
             pack_file_release: None,
+            provisioning_regions: vec![],
             chip_detection: vec![],
             variants: vec![
                 Chip::generic_arm("Cortex-M0", CoreType::Armv6m),
@@ -54,6 +57,7 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
             manufacturer: None,
             generated_from_pack: false,
             pack_file_release: None,
+            provisioning_regions: vec![],
             chip_detection: vec![],
             variants: vec![Chip::generic_arm("Cortex-M3", CoreType::Armv7m)],
             flash_algorithms: vec![],
@@ -64,6 +68,7 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
             manufacturer: None,
             generated_from_pack: false,
             pack_file_release: None,
+            provisioning_regions: vec![],
             chip_detection: vec![],
             variants: vec![
                 Chip::generic_arm("Cortex-M4", CoreType::Armv7em),
@@ -77,6 +82,7 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
             manufacturer: None,
             generated_from_pack: false,
             pack_file_release: None,
+            provisioning_regions: vec![],
             chip_detection: vec![],
             variants: vec![
                 Chip::generic_arm("Cortex-M23", CoreType::Armv8m),
@@ -91,6 +97,7 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
             name: "Generic RISC-V".to_owned(),
             manufacturer: None,
             pack_file_release: None,
+            provisioning_regions: vec![],
             generated_from_pack: false,
             chip_detection: vec![],
             variants: vec![Chip {
@@ -111,6 +118,8 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 rtt_scan_ranges: None,
                 jtag: None,
                 default_binary_format: None,
+                provisioning_regions: vec![],
+                memory_init_sequences: vec![],
             }],
             flash_algorithms: vec![],
             source: TargetDescriptionSource::Generic,
@@ -118,10 +127,15 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
     ]);
 }
 
+/// The number of similar chips suggested when a chip is not found.
+const SUGGESTION_COUNT: usize = 5;
+
 /// Registry of all available targets.
 struct Registry {
     /// All the available chips.
     families: Vec<ChipFamily>,
+    /// The index for fuzzy searches, built on the first search after the families changed.
+    search_index: OnceCell<SearchIndex>,
 }
 
 #[cfg(feature = "builtin-targets")]
@@ -147,7 +161,10 @@ impl Registry {
         // Additionally, validation for existing targets is done in the tests `validate_generic_targets` and
         // `validate_builtin` as well, to ensure we do not ship broken target definitions.
 
-        Self { families }
+        Self {
+            families,
+            search_index: OnceCell::new(),
+        }
     }
 
     fn get_target_by_name(&self, name: impl AsRef<str>) -> Result<Target, RegistryError> {
@@ -185,7 +202,15 @@ impl Registry {
         }
 
         let Some((family, chip)) = selected_family_and_chip else {
-            return Err(RegistryError::ChipNotFound(name.to_string()));
+            let suggestions = self
+                .search_chips_fuzzy(name, SUGGESTION_COUNT)
+                .into_iter()
+                .map(|chip| chip.name)
+                .collect();
+            return Err(RegistryError::ChipNotFound(
+                name.to_string(),
+                ChipSuggestions(suggestions),
+            ));
         };
 
         if exact_matches == 0 {
@@ -251,7 +276,10 @@ impl Registry {
             }
         }
         let Some(family) = found_family else {
-            return Err(RegistryError::ChipNotFound(name.to_string()));
+            return Err(RegistryError::ChipNotFound(
+                name.to_string(),
+                ChipSuggestions::default(),
+            ));
         };
 
         Ok(family.variants.iter().map(|v| v.name.to_string()).collect())
@@ -273,6 +301,12 @@ impl Registry {
         targets
     }
 
+    fn search_chips_fuzzy(&self, query: &str, limit: usize) -> Vec<ChipMatch> {
+        self.search_index
+            .get_or_init(|| SearchIndex::new(&self.families))
+            .search(query, limit)
+    }
+
     fn get_target_by_chip_info(&self, chip_info: ChipInfo) -> Result<Target, RegistryError> {
         let (family, chip) = match chip_info {
             ChipInfo::Arm(chip_info) => {
@@ -324,18 +358,33 @@ impl Registry {
     {
         let family: ChipFamily = serde_yaml::from_reader(yaml_reader)?;
 
-        validate_family(&family).map_err(|error| {
-            RegistryError::InvalidChipFamilyDefinition(Box::new(family.clone()), error)
-        })?;
+        let mut names = self.add_families(vec![family])?;
+        Ok(names.remove(0))
+    }
+
+    /// Adds `families`, replacing existing families with the same name.
+    ///
+    /// If any family is invalid, none of them are added.
+    fn add_families(&mut self, families: Vec<ChipFamily>) -> Result<Vec<String>, RegistryError> {
+        for family in &families {
+            validate_family(family).map_err(|error| {
+                RegistryError::InvalidChipFamilyDefinition(Box::new(family.clone()), error)
+            })?;
+        }
 
-        let family_name = family.name.clone();
+        let mut names = Vec::with_capacity(families.len());
+        for family in families {
+            let family_name = family.name.clone();
 
-        self.families
-            .retain(|old_family| !old_family.name.eq_ignore_ascii_case(&family_name));
+            self.families
+                .retain(|old_family| !old_family.name.eq_ignore_ascii_case(&family_name));
 
-        self.families.push(family);
+            self.families.push(family);
+            names.push(family_name);
+        }
+        self.search_index = OnceCell::new();
 
-        Ok(family_name)
+        Ok(names)
     }
 }
 
@@ -367,6 +416,17 @@ pub fn search_chips(name: impl AsRef<str>) -> Result<Vec<String>, RegistryError>
     Ok(REGISTRY.read_recursive().search_chips(name.as_ref()))
 }
 
+/// Returns up to `limit` chips from the internal registry which are similar to `query`, the best
+/// matches first.
+///
+/// Unlike [`search_chips`], the query doesn't have to be the start of the chip name, and
+/// separators, case and typos are tolerated. This is meant for suggesting chips to users.
+pub fn search_chips_fuzzy(query: impl AsRef<str>, limit: usize) -> Vec<ChipMatch> {
+    REGISTRY
+        .read_recursive()
+        .search_chips_fuzzy(query.as_ref(), limit)
+}
+
 /// Try to retrieve a target based on [ChipInfo] read from a target.
 pub(crate) fn get_target_by_chip_info(chip_info: ChipInfo) -> Result<Target, RegistryError> {
     REGISTRY.read_recursive().get_target_by_chip_info(chip_info)
@@ -402,6 +462,43 @@ where
     REGISTRY.write().add_target_from_yaml(yaml_reader)
 }
 
+/// Add chip families to the internal target registry.
+///
+/// Families with the same name as an existing family replace it. The families are validated
+/// first, and if any of them is invalid, none are added.
+///
+/// This is meant for crates which ship support for chips that are not built into probe-rs.
+/// Debug sequences for these chips can be provided with
+/// [`register_plugin`](crate::vendor::register_plugin).
+pub fn add_families(families: Vec<ChipFamily>) -> Result<Vec<String>, RegistryError> {
+    REGISTRY.write().add_families(families)
+}
+
+/// Add the targets of all target description files in the directory `path` to the internal
+/// target registry.
+///
+/// All files ending with `.yaml` or `.yml` are read, in alphabetical order. Returns the names
+/// of the added families. If any file is invalid, no family is added.
+pub fn add_targets_from_dir(path: impl AsRef<Path>) -> Result<Vec<String>, RegistryError> {
+    let mut files = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|file| {
+        file.is_file()
+            && file
+                .extension()
+                .is_some_and(|extension| extension == "yaml" || extension == "yml")
+    });
+    files.sort();
+
+    let families = files
+        .iter()
+        .map(|file| Ok(serde_yaml::from_reader(std::fs::File::open(file)?)?))
+        .collect::<Result<Vec<ChipFamily>, RegistryError>>()?;
+
+    add_families(families)
+}
+
 /// Get a list of all families which are contained in the internal
 /// registry.
 ///
@@ -474,10 +571,22 @@ mod tests {
         let registry = Registry::from_builtin_families();
         assert!(matches!(
             registry.get_target_by_name("not_a_real_chip"),
-            Err(RegistryError::ChipNotFound(_))
+            Err(RegistryError::ChipNotFound(_, _))
         ));
     }
 
+    #[cfg(feature = "builtin-targets")]
+    #[test]
+    fn not_found_suggests_similar_chips() {
+        let registry = Registry::from_builtin_families();
+        let Err(RegistryError::ChipNotFound(_, suggestions)) =
+            registry.get_target_by_name("nrf52840_xxab")
+        else {
+            panic!("chip should not be found");
+        };
+        assert!(suggestions.0.contains(&"nRF52840_xxAA".to_string()));
+    }
+
     #[cfg(feature = "builtin-targets")]
     #[test]
     fn try_fetch2() {
@@ -546,6 +655,65 @@ mod tests {
             });
     }
 
+    #[test]
+    fn add_targets_from_directory() -> TestResult {
+        let families = add_targets_from_dir("tests")?;
+        assert_eq!(families, ["TEMP_FAM"]);
+        assert!(get_target_by_name("FULL_SCAN_CHAIN").is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn plugin_families_use_their_sequences() {
+        use crate::{
+            architecture::arm::sequences::ArmDebugSequence,
+            config::DebugSequence,
+            vendor::{register_plugin, Vendor},
+        };
+        use std::sync::Arc;
+
+        #[derive(Debug)]
+        struct PluginSequence;
+
+        impl ArmDebugSequence for PluginSequence {}
+
+        #[derive(docsplay::Display)]
+        /// Plugin vendor
+        struct PluginVendor;
+
+        impl Vendor for PluginVendor {
+            fn try_create_debug_sequence(&self, chip: &Chip) -> Option<DebugSequence> {
+                (chip.name == "PLUGIN_CHIP").then(|| DebugSequence::Arm(Arc::new(PluginSequence)))
+            }
+
+            fn chip_families(&self) -> Vec<ChipFamily> {
+                vec![ChipFamily {
+                    name: "Plugin family".to_owned(),
+                    manufacturer: None,
+                    generated_from_pack: false,
+                    pack_file_release: None,
+                    provisioning_regions: vec![],
+                    chip_detection: vec![],
+                    variants: vec![Chip::generic_arm("PLUGIN_CHIP", CoreType::Armv7em)],
+                    flash_algorithms: vec![],
+                    source: TargetDescriptionSource::External,
+                }]
+            }
+        }
+
+        assert_eq!(
+            register_plugin(Box::new(PluginVendor)).unwrap(),
+            ["Plugin family"]
+        );
+
+        let target = get_target_by_name("PLUGIN_CHIP").unwrap();
+        assert!(matches!(
+            target.debug_sequence,
+            DebugSequence::Arm(sequence) if format!("{sequence:?}") == "PluginSequence"
+        ));
+    }
+
     #[test]
     fn add_targets_with_and_without_scanchain() -> TestResult {
         let file = File::open("tests/scan_chain_test.yaml")?;
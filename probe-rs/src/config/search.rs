@@ -0,0 +1,228 @@
+//! Fuzzy search over the chips in the registry.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use super::ChipFamily;
+
+/// How well a chip matched a search, see [`search_chips_fuzzy`](super::search_chips_fuzzy).
+///
+/// Better matches compare as smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    /// The name matches the chip name exactly.
+    Exact,
+    /// The name is the start of the chip name.
+    Prefix,
+    /// The name is contained in the chip name.
+    Substring,
+    /// The name is similar to the chip name, e.g. because of a typo.
+    Similar,
+}
+
+/// A chip found by a fuzzy search.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChipMatch {
+    /// The name of the chip.
+    pub name: String,
+    /// The name of the family the chip belongs to.
+    pub family: String,
+    /// How well the chip matched.
+    pub kind: MatchKind,
+}
+
+/// Chips which are similar to a chip name which was not found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChipSuggestions(pub Vec<String>);
+
+impl fmt::Display for ChipSuggestions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.0.is_empty() {
+            write!(f, " Did you mean {}?", self.0.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+struct Entry {
+    name: String,
+    family: String,
+    /// The name in lower case, without separators.
+    normalized: Vec<u8>,
+}
+
+/// An index over the chip names of the registry.
+pub(super) struct SearchIndex {
+    entries: Vec<Entry>,
+}
+
+impl SearchIndex {
+    pub(super) fn new(families: &[ChipFamily]) -> Self {
+        let entries = families
+            .iter()
+            .flat_map(|family| {
+                family.variants.iter().map(|chip| Entry {
+                    name: chip.name.clone(),
+                    family: family.name.clone(),
+                    normalized: normalize(&chip.name),
+                })
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Returns up to `limit` chips matching `query`, best matches first.
+    pub(super) fn search(&self, query: &str, limit: usize) -> Vec<ChipMatch> {
+        let query = normalize(query);
+        if query.is_empty() {
+            return vec![];
+        }
+
+        let mut matches: Vec<_> = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let (kind, distance) = rank(&query, &entry.normalized)?;
+                Some(((kind, distance, entry.normalized.len()), entry))
+            })
+            .collect();
+        matches
+            .sort_by(|(a, a_entry), (b, b_entry)| a.cmp(b).then(a_entry.name.cmp(&b_entry.name)));
+
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|((kind, _, _), entry)| ChipMatch {
+                name: entry.name.clone(),
+                family: entry.family.clone(),
+                kind,
+            })
+            .collect()
+    }
+}
+
+/// Converts `name` to lower case, and removes everything which is not a letter or a digit.
+fn normalize(name: &str) -> Vec<u8> {
+    name.bytes()
+        .filter(u8::is_ascii_alphanumeric)
+        .map(|byte| byte.to_ascii_lowercase())
+        .collect()
+}
+
+/// Whether the query byte `q` matches the chip name byte `c`. An `x` in a chip name is a
+/// wildcard, e.g. in `STM32F407VGTx`.
+fn matches(q: u8, c: u8) -> bool {
+    q == c || c == b'x'
+}
+
+/// Ranks how well `query` matches `name`. Returns `None` if they are not similar.
+fn rank(query: &[u8], name: &[u8]) -> Option<(MatchKind, usize)> {
+    let starts_with = |name: &[u8]| {
+        name.len() >= query.len() && query.iter().zip(name).all(|(&q, &c)| matches(q, c))
+    };
+
+    if query.len() == name.len() && starts_with(name) {
+        return Some((MatchKind::Exact, 0));
+    }
+    if starts_with(name) {
+        return Some((MatchKind::Prefix, 0));
+    }
+    if let Some(position) = (1..name.len()).find(|&start| starts_with(&name[start..])) {
+        return Some((MatchKind::Substring, position));
+    }
+
+    // Compare with the start of the name, so that queries for a family also find its chips.
+    let max_distance = (query.len() / 4).max(1);
+    let distance = (query.len().saturating_sub(max_distance)..=query.len() + max_distance)
+        .filter(|&length| length > 0 && length <= name.len())
+        .map(|length| edit_distance(query, &name[..length]))
+        .min()?;
+
+    (distance <= max_distance).then_some((MatchKind::Similar, distance))
+}
+
+/// The Levenshtein distance between `a` and `b`, treating wildcards in `b` as matching.
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, &a_byte) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &b_byte) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(!matches(a_byte, b_byte));
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{Chip, CoreType, TargetDescriptionSource};
+
+    fn index() -> SearchIndex {
+        let family = ChipFamily {
+            name: "Test".to_owned(),
+            manufacturer: None,
+            generated_from_pack: false,
+            pack_file_release: None,
+            provisioning_regions: vec![],
+            chip_detection: vec![],
+            variants: [
+                "nRF52832_xxAA",
+                "nRF52840_xxAA",
+                "STM32F401CBUx",
+                "STM32F407VGTx",
+            ]
+            .into_iter()
+            .map(|name| Chip::generic_arm(name, CoreType::Armv7em))
+            .collect(),
+            flash_algorithms: vec![],
+            source: TargetDescriptionSource::Generic,
+        };
+
+        SearchIndex::new(&[family])
+    }
+
+    fn names(matches: Vec<ChipMatch>) -> Vec<(String, MatchKind)> {
+        matches
+            .into_iter()
+            .map(|chip| (chip.name, chip.kind))
+            .collect()
+    }
+
+    #[test]
+    fn matches_are_ranked() {
+        let index = index();
+
+        assert_eq!(
+            names(index.search("nrf52840-xxaa", 10)),
+            [
+                ("nRF52840_xxAA".to_string(), MatchKind::Exact),
+                ("nRF52832_xxAA".to_string(), MatchKind::Similar),
+            ]
+        );
+        assert_eq!(
+            names(index.search("stm32f4", 10)),
+            [
+                ("STM32F401CBUx".to_string(), MatchKind::Prefix),
+                ("STM32F407VGTx".to_string(), MatchKind::Prefix),
+            ]
+        );
+        assert_eq!(
+            names(index.search("F407", 10)),
+            [("STM32F407VGTx".to_string(), MatchKind::Substring)]
+        );
+        assert_eq!(
+            names(index.search("nrf52841", 1)),
+            [("nRF52840_xxAA".to_string(), MatchKind::Similar)]
+        );
+        assert!(index.search("esp32c3", 10).is_empty());
+    }
+}
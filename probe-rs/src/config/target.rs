@@ -1,4 +1,7 @@
-use super::{Core, MemoryRegion, RawFlashAlgorithm, TargetDescriptionSource};
+use super::{
+    Core, MemoryInitSequence, MemoryRegion, ProvisioningRegion, RawFlashAlgorithm,
+    TargetDescriptionSource,
+};
 use crate::flashing::FlashLoader;
 use crate::{
     architecture::{
@@ -42,6 +45,10 @@ pub struct Target {
     pub jtag: Option<Jtag>,
     /// The default executable format for the target.
     pub default_format: Option<String>,
+    /// The regions holding per-device configuration, see [`crate::provisioning`].
+    pub provisioning_regions: Vec<ProvisioningRegion>,
+    /// The sequences initializing external memory, see [`crate::memory_init`].
+    pub memory_init_sequences: Vec<MemoryInitSequence>,
 }
 
 impl std::fmt::Debug for Target {
@@ -109,6 +116,8 @@ impl Target {
             rtt_scan_regions,
             jtag: chip.jtag.clone(),
             default_format: chip.default_binary_format.clone(),
+            provisioning_regions: family.provisioning_regions(chip),
+            memory_init_sequences: chip.memory_init_sequences.clone(),
         }
     }
 
@@ -158,6 +167,19 @@ impl Target {
         self.cores.iter().position(|c| c.name == name)
     }
 
+    /// Returns the indices of the cores which can access `address`, or of all cores if it is not
+    /// in the memory map.
+    pub(crate) fn cores_accessing(&self, address: u64) -> Vec<usize> {
+        match self.get_memory_region_by_address(address) {
+            Some(region) => region
+                .cores()
+                .iter()
+                .filter_map(|name| self.core_index_by_name(name))
+                .collect(),
+            None => (0..self.cores.len()).collect(),
+        }
+    }
+
     /// Gets the first found [MemoryRegion] that contains the given address
     pub(crate) fn get_memory_region_by_address(&self, address: u64) -> Option<&MemoryRegion> {
         self.memory_map
@@ -168,6 +190,7 @@ impl Target {
 
 /// Selector for the debug target.
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum TargetSelector {
     /// Specify the name of a target, which will
     /// be used to search the internal list of
@@ -1,31 +1,39 @@
 use crate::{
     architecture::{
-        arm::sequences::ArmDebugSequence, riscv::sequences::RiscvDebugSequence,
+        arm::{sequences::ArmDebugSequence, SwoAccess},
+        riscv::sequences::RiscvDebugSequence,
         xtensa::sequences::XtensaDebugSequence,
     },
     config::DebugSequence,
     error::Error,
+    events::Events,
     memory::CoreMemoryInterface,
-    CoreType, InstructionSet, MemoryInterface, Target,
+    probe::UartAccess,
+    CancellationToken, CoreType, InstructionSet, MemoryInterface, Target,
 };
 pub use probe_rs_target::{Architecture, CoreAccessOptions};
 use probe_rs_target::{
     ArmCoreAccessOptions, MemoryRegion, RiscvCoreAccessOptions, XtensaCoreAccessOptions,
 };
-use std::{sync::Arc, time::Duration};
+use std::{ops::Range, sync::Arc, time::Duration};
 
 pub mod core_state;
 pub mod core_status;
 #[cfg(feature = "debug")]
 pub(crate) mod dump;
+mod function_call;
 pub mod memory_mapped_registers;
 pub mod registers;
+pub(crate) mod snapshot;
 
 pub use core_state::*;
 pub use core_status::*;
 pub use memory_mapped_registers::MemoryMappedRegister;
 pub use registers::*;
 
+/// The size of the chunks in which cancellable memory transfers are performed.
+const CANCELLABLE_CHUNK_SIZE: usize = 16 * 1024;
+
 /// An struct for storing the current state of a core.
 #[derive(Debug, Clone)]
 pub struct CoreInformation {
@@ -33,6 +41,78 @@ pub struct CoreInformation {
     pub pc: u64,
 }
 
+/// How a core is reset, see [`Core::reset_with`] and [`Session::reset_with`](crate::Session::reset_with).
+///
+/// Bootloaders react differently to the kinds of resets, e.g. some only start the application
+/// after a power-on or pin reset, so the strategy can be chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResetStrategy {
+    /// The reset of the debug sequence of the target, which may be a vendor-specific soft reset.
+    #[default]
+    Default,
+    /// A system reset requested with `AIRCR.SYSRESETREQ`. Only supported on Cortex-M cores.
+    SysResetReq,
+    /// A reset of only the core with `AIRCR.VECTRESET`, which keeps the peripherals running.
+    /// Only supported on ARMv7-M cores.
+    VectReset,
+    /// A reset with the nRESET pin of the probe. Only supported through a [`Session`](crate::Session),
+    /// and only for ARM targets.
+    Hardware,
+}
+
+impl std::str::FromStr for ResetStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(Self::Default),
+            "sysresetreq" => Ok(Self::SysResetReq),
+            "vectreset" => Ok(Self::VectReset),
+            "hardware" | "nreset" => Ok(Self::Hardware),
+            _ => Err(format!(
+                "'{s}' is not a reset strategy, expected one of default, sysresetreq, vectreset and hardware."
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ResetStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Default => "default",
+            Self::SysResetReq => "sysresetreq",
+            Self::VectReset => "vectreset",
+            Self::Hardware => "hardware",
+        })
+    }
+}
+
+/// Whether interrupts are taken while stepping, see [`Core::step_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterruptMasking {
+    /// Interrupts are masked during the step, so a step never ends up in an interrupt handler.
+    #[default]
+    Masked,
+    /// Pending interrupts are taken during the step, so interrupt handlers keep running, but the
+    /// step may end at the start of an interrupt handler.
+    Unmasked,
+}
+
+impl std::str::FromStr for InterruptMasking {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "masked" => Ok(Self::Masked),
+            "unmasked" => Ok(Self::Unmasked),
+            _ => Err(format!(
+                "'{s}' is not an interrupt masking, expected masked or unmasked."
+            )),
+        }
+    }
+}
+
 /// A generic interface to control a MCU core.
 pub trait CoreInterface: MemoryInterface + CoreMemoryInterfaceShim {
     /// Wait until the core is halted. If the core does not halt on its own,
@@ -68,12 +148,37 @@ pub trait CoreInterface: MemoryInterface + CoreMemoryInterfaceShim {
     /// Steps one instruction and then enters halted state again.
     fn step(&mut self) -> Result<CoreInformation, Error>;
 
+    /// Steps one instruction with the given interrupt masking, and then enters halted state
+    /// again.
+    ///
+    /// [`step`](CoreInterface::step) masks interrupts.
+    fn step_with(&mut self, interrupts: InterruptMasking) -> Result<CoreInformation, Error> {
+        match interrupts {
+            InterruptMasking::Masked => self.step(),
+            InterruptMasking::Unmasked => Err(Error::NotImplemented("stepping with interrupts")),
+        }
+    }
+
     /// Read the value of a core register.
     fn read_core_reg(
         &mut self,
         address: registers::RegisterId,
     ) -> Result<registers::RegisterValue, Error>;
 
+    /// Read the values of several core registers.
+    ///
+    /// Cores which can read registers in a single batch override this. The default
+    /// implementation reads the registers one by one.
+    fn read_core_regs(
+        &mut self,
+        addresses: &[registers::RegisterId],
+    ) -> Result<Vec<registers::RegisterValue>, Error> {
+        addresses
+            .iter()
+            .map(|address| self.read_core_reg(*address))
+            .collect()
+    }
+
     /// Write the value of a core register.
     fn write_core_reg(
         &mut self,
@@ -122,6 +227,15 @@ pub trait CoreInterface: MemoryInterface + CoreMemoryInterfaceShim {
         Ok(())
     }
 
+    /// Invalidates the caches of the core for the memory range, after it was changed other than
+    /// by a memory write through this interface, e.g. by programming flash.
+    ///
+    /// Memory writes through this interface already keep the caches coherent.
+    fn invalidate_caches(&mut self, _range: Range<u64>) -> Result<(), Error> {
+        // Only cores with caches, e.g. the Cortex-M7, override this.
+        Ok(())
+    }
+
     /// Get the `Architecture` of the Core.
     fn architecture(&self) -> Architecture;
 
@@ -177,6 +291,18 @@ pub trait CoreInterface: MemoryInterface + CoreMemoryInterfaceShim {
     fn is_64_bit(&self) -> bool {
         false
     }
+
+    /// Gets the UART interface of the debug probe, if the probe has one which can be
+    /// used while debugging this core.
+    fn uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        None
+    }
+
+    /// Gets the SWO interface of the debug probe, if the probe has one which can be
+    /// used while debugging this core.
+    fn swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        None
+    }
 }
 
 /// Implementation detail to allow trait upcasting-like behaviour.
@@ -217,6 +343,9 @@ pub struct Core<'probe> {
     memory_regions: &'probe [MemoryRegion],
 
     inner: Box<dyn CoreInterface + 'probe>,
+
+    /// The subscribers of the session, if the core belongs to one.
+    events: Option<Events>,
 }
 
 impl<'probe> CoreMemoryInterface for Core<'probe> {
@@ -249,9 +378,20 @@ impl<'probe> Core<'probe> {
             name,
             memory_regions,
             inner: Box::new(core),
+            events: None,
         }
     }
 
+    /// Reports events of this core to the subscribers in `events`.
+    pub(crate) fn set_events(&mut self, events: Events) {
+        self.events = Some(events);
+    }
+
+    /// The subscribers of the session this core belongs to.
+    pub(crate) fn events(&self) -> Option<&Events> {
+        self.events.as_ref()
+    }
+
     /// Return the memory regions associated with this core.
     pub fn memory_regions(&self) -> impl Iterator<Item = &MemoryRegion> {
         self.memory_regions
@@ -295,13 +435,21 @@ impl<'probe> Core<'probe> {
     /// returns a [`DebugProbeError::Timeout`](crate::probe::DebugProbeError::Timeout) otherwise.
     #[tracing::instrument(skip(self))]
     pub fn halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
-        self.inner.halt(timeout)
+        let info = self.inner.halt(timeout)?;
+        if let Some(events) = &self.events {
+            events.core_halted(self.id, HaltReason::Request);
+        }
+        Ok(info)
     }
 
     /// Continue to execute instructions.
     #[tracing::instrument(skip(self))]
     pub fn run(&mut self) -> Result<(), Error> {
-        self.inner.run()
+        self.inner.run()?;
+        if let Some(events) = &self.events {
+            events.core_running(self.id);
+        }
+        Ok(())
     }
 
     /// Reset the core, and then continue to execute instructions. If the core
@@ -310,7 +458,11 @@ impl<'probe> Core<'probe> {
     /// [`reset_and_halt`]: Core::reset_and_halt
     #[tracing::instrument(skip(self))]
     pub fn reset(&mut self) -> Result<(), Error> {
-        self.inner.reset()
+        self.inner.reset()?;
+        if let Some(events) = &self.events {
+            events.reset_performed(self.id, false);
+        }
+        Ok(())
     }
 
     /// Reset the core, and then immediately halt. To continue execution after
@@ -319,19 +471,117 @@ impl<'probe> Core<'probe> {
     /// [`reset`]: Core::reset
     #[tracing::instrument(skip(self))]
     pub fn reset_and_halt(&mut self, timeout: Duration) -> Result<CoreInformation, Error> {
-        self.inner.reset_and_halt(timeout)
+        let info = self.inner.reset_and_halt(timeout)?;
+        if let Some(events) = &self.events {
+            events.reset_performed(self.id, true);
+            events.core_halted(self.id, HaltReason::Request);
+        }
+        Ok(info)
+    }
+
+    /// Reset the core with the given strategy, and halt it afterwards if `halt` is set.
+    ///
+    /// [`ResetStrategy::Hardware`] needs access to the probe, use
+    /// [`Session::reset_with`](crate::Session::reset_with) for it.
+    #[tracing::instrument(skip(self))]
+    pub fn reset_with(
+        &mut self,
+        strategy: ResetStrategy,
+        halt: bool,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        use crate::architecture::arm::core::armv7m::Aircr;
+
+        let mut aircr = Aircr(0);
+        aircr.vectkey();
+        match strategy {
+            ResetStrategy::Default if halt => return self.reset_and_halt(timeout).map(|_| ()),
+            ResetStrategy::Default => return self.reset(),
+            ResetStrategy::SysResetReq if self.core_type().is_cortex_m() => {
+                aircr.set_sysresetreq(true)
+            }
+            ResetStrategy::SysResetReq => {
+                return Err(Error::NotImplemented(
+                    "SYSRESETREQ resets of cores other than Cortex-M",
+                ))
+            }
+            ResetStrategy::VectReset
+                if matches!(self.core_type(), CoreType::Armv7m | CoreType::Armv7em) =>
+            {
+                aircr.set_vectreset(true)
+            }
+            ResetStrategy::VectReset => {
+                return Err(Error::NotImplemented(
+                    "VECTRESET resets of cores other than ARMv7-M",
+                ))
+            }
+            ResetStrategy::Hardware => {
+                return Err(Error::Other(
+                    "Hardware resets need the probe, use Session::reset_with instead.".to_string(),
+                ))
+            }
+        }
+
+        if halt {
+            self.reset_catch_set()?;
+        }
+
+        // The debug port may not answer while the target is resetting.
+        if let Err(error) = self.write_word_32(Aircr::get_mmio_address(), aircr.into()) {
+            tracing::debug!("Error while writing AIRCR, the target is probably resetting: {error}");
+        }
+
+        if halt {
+            self.wait_for_core_halted(timeout)?;
+            self.reset_catch_clear()?;
+        } else {
+            // Refresh the cached state of the core.
+            std::thread::sleep(Duration::from_millis(10));
+            self.status()?;
+        }
+
+        if let Some(events) = &self.events {
+            events.reset_performed(self.id, halt);
+            if halt {
+                events.core_halted(self.id, HaltReason::Request);
+            }
+        }
+        Ok(())
     }
 
     /// Steps one instruction and then enters halted state again.
     #[tracing::instrument(skip(self))]
     pub fn step(&mut self) -> Result<CoreInformation, Error> {
-        self.inner.step()
+        self.step_with(InterruptMasking::Masked)
+    }
+
+    /// Steps one instruction with the given interrupt masking, and then enters halted state
+    /// again.
+    ///
+    /// With [`InterruptMasking::Unmasked`], pending interrupts are taken, so the core may halt at
+    /// the start of an interrupt handler.
+    #[tracing::instrument(skip(self))]
+    pub fn step_with(&mut self, interrupts: InterruptMasking) -> Result<CoreInformation, Error> {
+        let info = self.inner.step_with(interrupts)?;
+        if let Some(events) = &self.events {
+            events.core_running(self.id);
+            events.core_halted(self.id, HaltReason::Step);
+        }
+        Ok(info)
     }
 
     /// Returns the current status of the core.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn status(&mut self) -> Result<CoreStatus, Error> {
-        self.inner.status()
+        let status = self.inner.status()?;
+        if let Some(events) = &self.events {
+            match status {
+                CoreStatus::Halted(reason) => events.core_halted(self.id, reason),
+                CoreStatus::Running | CoreStatus::Sleeping => events.core_running(self.id),
+                CoreStatus::LockedUp | CoreStatus::Unknown => {}
+            }
+        }
+        Ok(status)
     }
 
     /// Read the value of a core register.
@@ -366,6 +616,16 @@ impl<'probe> Core<'probe> {
         value.try_into().into_crate_error()
     }
 
+    /// Read the values of several core registers.
+    ///
+    /// On most cores, this is considerably faster than reading the registers one by one.
+    pub fn read_core_regs(
+        &mut self,
+        addresses: &[registers::RegisterId],
+    ) -> Result<Vec<registers::RegisterValue>, Error> {
+        self.inner.read_core_regs(addresses)
+    }
+
     /// Write the value of a core register.
     ///
     /// # Errors
@@ -385,6 +645,44 @@ impl<'probe> Core<'probe> {
         self.inner.write_core_reg(address, value.into())
     }
 
+    /// Reads memory at `address` into `data` in chunks, and fails with [`Error::Cancelled`]
+    /// once `cancellation` is cancelled.
+    ///
+    /// Large transfers can take a long time, so this allows aborting them.
+    pub fn read_cancellable(
+        &mut self,
+        address: u64,
+        data: &mut [u8],
+        cancellation: &CancellationToken,
+    ) -> Result<(), Error> {
+        for (index, chunk) in data.chunks_mut(CANCELLABLE_CHUNK_SIZE).enumerate() {
+            if cancellation.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            self.read(address + (index * CANCELLABLE_CHUNK_SIZE) as u64, chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `data` to memory at `address` in chunks, and fails with [`Error::Cancelled`]
+    /// once `cancellation` is cancelled.
+    pub fn write_cancellable(
+        &mut self,
+        address: u64,
+        data: &[u8],
+        cancellation: &CancellationToken,
+    ) -> Result<(), Error> {
+        for (index, chunk) in data.chunks(CANCELLABLE_CHUNK_SIZE).enumerate() {
+            if cancellation.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            self.write(address + (index * CANCELLABLE_CHUNK_SIZE) as u64, chunk)?;
+        }
+
+        Ok(())
+    }
+
     /// Returns all the available breakpoint units of the core.
     pub fn available_breakpoint_units(&mut self) -> Result<u32, Error> {
         self.inner.available_breakpoint_units()
@@ -401,6 +699,12 @@ impl<'probe> Core<'probe> {
         self.inner.debug_on_sw_breakpoint(enabled)
     }
 
+    /// Invalidates the caches of the core for the memory range, after it was changed other than
+    /// by a memory write of this core handle, e.g. by programming flash.
+    pub fn invalidate_caches(&mut self, range: Range<u64>) -> Result<(), Error> {
+        self.inner.invalidate_caches(range)
+    }
+
     /// Returns a list of all the registers of this core.
     pub fn registers(&self) -> &'static registers::CoreRegisters {
         self.inner.registers()
@@ -574,6 +878,23 @@ impl<'probe> Core<'probe> {
     pub fn is_64_bit(&self) -> bool {
         self.inner.is_64_bit()
     }
+
+    /// Gets the UART interface of the debug probe, if the probe has one which can be
+    /// used while debugging this core.
+    ///
+    /// This does not work on all probes.
+    pub fn uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        self.inner.uart_interface_mut()
+    }
+
+    /// Gets the SWO interface of the debug probe, to read the trace data while the core
+    /// is running.
+    ///
+    /// SWO has to be configured with [`Session::setup_tracing`](crate::Session::setup_tracing)
+    /// first. This does not work on all probes.
+    pub fn swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        self.inner.swo_interface_mut()
+    }
 }
 
 impl<'probe> CoreInterface for Core<'probe> {
@@ -609,6 +930,10 @@ impl<'probe> CoreInterface for Core<'probe> {
         self.step()
     }
 
+    fn step_with(&mut self, interrupts: InterruptMasking) -> Result<CoreInformation, Error> {
+        self.step_with(interrupts)
+    }
+
     fn read_core_reg(
         &mut self,
         address: registers::RegisterId,
@@ -616,6 +941,13 @@ impl<'probe> CoreInterface for Core<'probe> {
         self.read_core_reg(address)
     }
 
+    fn read_core_regs(
+        &mut self,
+        addresses: &[registers::RegisterId],
+    ) -> Result<Vec<registers::RegisterValue>, Error> {
+        self.read_core_regs(addresses)
+    }
+
     fn write_core_reg(
         &mut self,
         address: registers::RegisterId,
@@ -636,12 +968,16 @@ impl<'probe> CoreInterface for Core<'probe> {
         self.enable_breakpoints(state)
     }
 
-    fn set_hw_breakpoint(&mut self, _unit_index: usize, addr: u64) -> Result<(), Error> {
-        self.set_hw_breakpoint(addr)
+    fn set_hw_breakpoint(&mut self, unit_index: usize, addr: u64) -> Result<(), Error> {
+        if !self.inner.hw_breakpoints_enabled() {
+            self.enable_breakpoints(true)?;
+        }
+
+        self.inner.set_hw_breakpoint(unit_index, addr)
     }
 
-    fn clear_hw_breakpoint(&mut self, _unit_index: usize) -> Result<(), Error> {
-        self.clear_all_hw_breakpoints()
+    fn clear_hw_breakpoint(&mut self, unit_index: usize) -> Result<(), Error> {
+        self.inner.clear_hw_breakpoint(unit_index)
     }
 
     fn registers(&self) -> &'static registers::CoreRegisters {
@@ -665,7 +1001,11 @@ impl<'probe> CoreInterface for Core<'probe> {
     }
 
     fn hw_breakpoints_enabled(&self) -> bool {
-        todo!()
+        self.inner.hw_breakpoints_enabled()
+    }
+
+    fn invalidate_caches(&mut self, range: Range<u64>) -> Result<(), Error> {
+        self.invalidate_caches(range)
     }
 
     fn architecture(&self) -> Architecture {
@@ -703,6 +1043,14 @@ impl<'probe> CoreInterface for Core<'probe> {
     fn is_64_bit(&self) -> bool {
         self.is_64_bit()
     }
+
+    fn uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        self.uart_interface_mut()
+    }
+
+    fn swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        self.swo_interface_mut()
+    }
 }
 
 pub enum ResolvedCoreOptions {
@@ -768,3 +1116,80 @@ impl std::fmt::Debug for ResolvedCoreOptions {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::probe::fake_probe::FakeProbe;
+
+    #[test]
+    fn cancelled_transfers_stop() {
+        let mut session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        let mut core = session.core(0).unwrap();
+        let cancellation = CancellationToken::new();
+
+        core.write_cancellable(0x2000_0000, &[1, 2, 3, 4], &cancellation)
+            .unwrap();
+        let mut data = [0; 4];
+        core.read_cancellable(0x2000_0000, &mut data, &cancellation)
+            .unwrap();
+        assert_eq!(data, [1, 2, 3, 4]);
+
+        cancellation.cancel();
+        assert!(matches!(
+            core.read_cancellable(0x2000_0000, &mut data, &cancellation),
+            Err(Error::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn unsupported_reset_strategies_are_rejected() {
+        assert_eq!("SYSRESETREQ".parse(), Ok(ResetStrategy::SysResetReq));
+        assert_eq!("nreset".parse(), Ok(ResetStrategy::Hardware));
+        assert!("por".parse::<ResetStrategy>().is_err());
+
+        let mut session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        // The nRF51 has a Cortex-M0, which has no VECTRESET.
+        assert!(matches!(
+            core.reset_with(ResetStrategy::VectReset, false, Duration::from_millis(100)),
+            Err(Error::NotImplemented(_))
+        ));
+        assert!(core
+            .reset_with(ResetStrategy::Hardware, false, Duration::from_millis(100))
+            .is_err());
+    }
+
+    #[test]
+    fn step_masks_interrupts_as_requested() {
+        assert_eq!("Unmasked".parse(), Ok(InterruptMasking::Unmasked));
+        assert_eq!("masked".parse(), Ok(InterruptMasking::Masked));
+        assert!("off".parse::<InterruptMasking>().is_err());
+
+        /// The address of DHCSR, and its C_MASKINTS bit.
+        const DHCSR: u64 = 0xE000_EDF0;
+        const C_MASKINTS: u32 = 1 << 3;
+
+        // A Cortex-M0 and a Cortex-M4.
+        for target in ["nrf51822_xxAC", "STM32F407VGTx"] {
+            let mut session = FakeProbe::attach_mocked_core(target).unwrap();
+            let mut core = session.core(0).unwrap();
+            core.halt(Duration::from_millis(100)).unwrap();
+
+            core.step_with(InterruptMasking::Unmasked).unwrap();
+            assert_eq!(
+                core.read_word_32(DHCSR).unwrap() & C_MASKINTS,
+                0,
+                "{target}"
+            );
+
+            core.step().unwrap();
+            assert_ne!(
+                core.read_word_32(DHCSR).unwrap() & C_MASKINTS,
+                0,
+                "{target}"
+            );
+        }
+    }
+}
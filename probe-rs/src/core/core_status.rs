@@ -75,14 +75,63 @@ pub enum HaltReason {
 /// When a core hits an exception, we halt the core.
 ///
 /// `VectorCatchCondition` describes which event exactly should trigger a halt.
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum VectorCatchCondition {
     /// We encountered a hardfault.
+    ///
+    /// On RISC-V, this catches all synchronous exceptions except for `ecall` and `ebreak`.
     HardFault,
     /// We encountered a local reset.
     CoreReset,
     /// We encountered a SecureFault.
     SecureFault,
+    /// We encountered a BusFault. Only supported on ARMv7-M and ARMv8-M Mainline cores.
+    ///
+    /// On RISC-V, this catches the access fault exceptions.
+    BusFault,
+    /// We encountered a MemManage fault. Only supported on ARMv7-M and ARMv8-M Mainline cores.
+    ///
+    /// On RISC-V, this catches the page fault exceptions.
+    MemManage,
+    /// We encountered a UsageFault, e.g. an undefined instruction or an unaligned access.
+    /// Only supported on ARMv7-M and ARMv8-M Mainline cores.
+    ///
+    /// On RISC-V, this catches the illegal instruction and misaligned access exceptions.
+    UsageFault,
     /// We encountered any exception.
     All,
 }
+
+impl std::str::FromStr for VectorCatchCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "hard-fault" | "hardfault" => Ok(Self::HardFault),
+            "core-reset" | "reset" => Ok(Self::CoreReset),
+            "secure-fault" | "securefault" => Ok(Self::SecureFault),
+            "bus-fault" | "busfault" => Ok(Self::BusFault),
+            "mem-manage" | "memmanage" => Ok(Self::MemManage),
+            "usage-fault" | "usagefault" => Ok(Self::UsageFault),
+            "all" => Ok(Self::All),
+            _ => Err(format!(
+                "'{s}' is not a vector catch condition, expected one of hard-fault, core-reset, secure-fault, bus-fault, mem-manage, usage-fault and all."
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for VectorCatchCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::HardFault => "hard-fault",
+            Self::CoreReset => "core-reset",
+            Self::SecureFault => "secure-fault",
+            Self::BusFault => "bus-fault",
+            Self::MemManage => "mem-manage",
+            Self::UsageFault => "usage-fault",
+            Self::All => "all",
+        })
+    }
+}
@@ -0,0 +1,174 @@
+use super::snapshot::CoreSnapshot;
+use crate::{Core, Error, InstructionSet};
+use std::time::{Duration, Instant};
+
+/// How far the stack pointer is moved down for the call, to leave the current frame untouched.
+const STACK_GAP: u64 = 64;
+
+impl Core<'_> {
+    /// Call the function at `address` on the halted core, and return the value of its first result register.
+    ///
+    /// The `arguments` are passed in the argument registers of the core, stack arguments are not supported.
+    /// The function returns to a hardware breakpoint at the current program counter. Afterwards, all
+    /// registers are restored, even if the call failed, but changes the function made to memory and
+    /// peripherals are kept.
+    ///
+    /// This can be used to call ROM routines of a chip, or test hooks in the firmware.
+    #[tracing::instrument(skip(self))]
+    pub fn call_function(
+        &mut self,
+        address: u64,
+        arguments: &[u64],
+        timeout: Duration,
+    ) -> Result<u64, Error> {
+        if !self.core_halted()? {
+            return Err(Error::Other(
+                "The core must be halted to call a function.".to_string(),
+            ));
+        }
+
+        let registers = self.registers();
+        if let Some(unsupported) = arguments
+            .len()
+            .checked_sub(1)
+            .filter(|&last| registers.get_argument_register(last).is_none())
+        {
+            return Err(Error::Other(format!(
+                "The function can't be called with {} arguments, only arguments in registers are supported.",
+                unsupported + 1
+            )));
+        }
+
+        // Only the registers are saved, the memory written by the function is the point of calling it.
+        let saved = CoreSnapshot::take_ranges(self, vec![])?;
+
+        let return_address: u64 = self.read_core_reg(self.program_counter())?;
+        let breakpoint_exists = self.inner.hw_breakpoints()?.contains(&Some(return_address));
+        if !breakpoint_exists {
+            self.set_hw_breakpoint(return_address)?;
+        }
+
+        let result = self.run_function(address, arguments, return_address, timeout);
+
+        if !self.core_halted()? {
+            self.halt(Duration::from_millis(100))?;
+        }
+        if !breakpoint_exists {
+            self.clear_hw_breakpoint(return_address)?;
+        }
+        saved.restore(self)?;
+
+        result
+    }
+
+    fn run_function(
+        &mut self,
+        address: u64,
+        arguments: &[u64],
+        return_address: u64,
+        timeout: Duration,
+    ) -> Result<u64, Error> {
+        let registers = self.registers();
+        let thumb = self.instruction_set()? == InstructionSet::Thumb2;
+
+        for (index, argument) in arguments.iter().enumerate() {
+            self.write_core_reg(registers.argument_register(index), *argument)?;
+        }
+
+        let stack_pointer: u64 = self.read_core_reg(self.stack_pointer())?;
+        // Both the ARM and RISC-V calling conventions are satisfied by a 16 byte aligned stack.
+        let stack_pointer = stack_pointer.saturating_sub(STACK_GAP) & !0xF;
+        self.write_core_reg(self.stack_pointer(), stack_pointer)?;
+
+        // For ARM Cortex-M cores, the return address needs the Thumb bit set, but the program counter doesn't.
+        let return_register_value = if thumb {
+            return_address | 1
+        } else {
+            return_address
+        };
+        self.write_core_reg(self.return_address(), return_register_value)?;
+        self.write_core_reg(self.program_counter(), address & !1)?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.run()?;
+            self.wait_for_core_halted(deadline.saturating_duration_since(Instant::now()))?;
+
+            let program_counter: u64 = self.read_core_reg(self.program_counter())?;
+            if program_counter != return_address {
+                return Err(Error::Other(format!(
+                    "The called function halted at {program_counter:#010x} before it returned ({:?}).",
+                    self.status()?
+                )));
+            }
+
+            // A deeper call passing the return address doesn't leave the stack where the function started.
+            let current_stack_pointer: u64 = self.read_core_reg(self.stack_pointer())?;
+            if current_stack_pointer == stack_pointer {
+                break;
+            }
+        }
+
+        self.read_core_reg(registers.result_register(0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{probe::fake_probe::FakeProbe, MemoryInterface, RegisterValue};
+
+    /// Where the core is halted when the function is called.
+    const RETURN_ADDRESS: u64 = 0x0800_0100;
+    /// A function which returns right away, with its argument as result.
+    const IDENTITY: u64 = 0x0800_0200;
+    /// A function which halts at a breakpoint instruction.
+    const BREAKPOINT: u64 = 0x0800_0300;
+
+    /// The FPB comparators of the simulated core.
+    const FP_COMP: u64 = 0xE000_2008;
+
+    fn registers(core: &mut Core<'_>) -> Vec<RegisterValue> {
+        let ids: Vec<_> = core.registers().all_registers().map(|r| r.id).collect();
+        ids.into_iter()
+            .map(|id| core.read_core_reg(id).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn function_call_restores_the_core() {
+        let mut session = FakeProbe::attach_mocked_core("STM32F407VGTx").unwrap();
+        let mut core = session.core(0).unwrap();
+        core.halt(Duration::from_millis(100)).unwrap();
+
+        core.write_8(IDENTITY, &[0x70, 0x47]).unwrap(); // bx lr
+        core.write_8(BREAKPOINT, &[0x00, 0xbe]).unwrap(); // bkpt #0
+
+        core.write_core_reg(core.program_counter(), RETURN_ADDRESS)
+            .unwrap();
+        core.write_core_reg(core.stack_pointer(), 0x2000_1000u32)
+            .unwrap();
+        let first_argument = core.registers().argument_register(0);
+        core.write_core_reg(first_argument, 7u32).unwrap();
+        let before = registers(&mut core);
+
+        let error = core
+            .call_function(BREAKPOINT | 1, &[], Duration::from_millis(100))
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("halted at 0x08000300"),
+            "{error}"
+        );
+        assert_eq!(registers(&mut core), before);
+
+        let result = core.call_function(IDENTITY | 1, &[42], Duration::from_millis(100));
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(registers(&mut core), before);
+        assert!(core.core_halted().unwrap());
+
+        // The temporary breakpoint at the return address is cleared again.
+        let mut comparators = [0; 6];
+        core.read_32(FP_COMP, &mut comparators).unwrap();
+        assert_eq!(comparators, [0; 6]);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{Core, Error, MemoryInterface, RegisterId, RegisterValue};
+use probe_rs_target::MemoryRegion;
+use std::ops::Range;
+
+/// The granularity in which RAM is compared when a snapshot is restored.
+const PAGE_SIZE: usize = 256;
+
+/// The registers and RAM contents of a halted core at a point in time, which can be restored later.
+///
+/// This is not full reverse execution: peripherals, flash, and memory outside the saved ranges keep
+/// their current state. It is enough to run a function again, e.g. with modified arguments.
+#[derive(Debug, Clone)]
+pub struct CoreSnapshot {
+    registers: Vec<(RegisterId, RegisterValue)>,
+    memory: Vec<(Range<u64>, Vec<u8>)>,
+}
+
+impl CoreSnapshot {
+    /// Save the registers and all RAM regions of the core.
+    pub fn take(core: &mut Core) -> Result<Self, Error> {
+        let ranges = core
+            .memory_regions()
+            .filter_map(|region| match region {
+                MemoryRegion::Ram(ram) => Some(ram.range.clone()),
+                _ => None,
+            })
+            .collect();
+
+        Self::take_ranges(core, ranges)
+    }
+
+    /// Save the registers of the core, and the memory in `ranges`.
+    pub fn take_ranges(core: &mut Core, ranges: Vec<Range<u64>>) -> Result<Self, Error> {
+        let register_ids = core
+            .registers()
+            .all_registers()
+            .map(|register| register.id())
+            .collect::<Vec<_>>();
+
+        let values = core.read_core_regs(&register_ids)?;
+        let registers = register_ids.into_iter().zip(values).collect();
+
+        let mut memory = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let mut data = vec![0; (range.end - range.start) as usize];
+            core.read(range.start, &mut data)?;
+            memory.push((range, data));
+        }
+
+        Ok(Self { registers, memory })
+    }
+
+    /// The memory ranges saved in this snapshot.
+    pub fn memory_ranges(&self) -> impl Iterator<Item = &Range<u64>> {
+        self.memory.iter().map(|(range, _)| range)
+    }
+
+    /// Restore the registers and memory of the core.
+    ///
+    /// Only the pages of memory which changed since the snapshot was taken are written.
+    /// Returns the number of bytes written.
+    pub fn restore(&self, core: &mut Core) -> Result<usize, Error> {
+        let mut written = 0;
+        for (range, saved) in &self.memory {
+            let mut current = vec![0; saved.len()];
+            core.read(range.start, &mut current)?;
+
+            for dirty in dirty_pages(saved, &current) {
+                core.write(range.start + dirty.start as u64, &saved[dirty.clone()])?;
+                written += dirty.len();
+            }
+        }
+
+        for (id, value) in &self.registers {
+            core.write_core_reg(*id, *value)?;
+        }
+        core.flush()?;
+
+        Ok(written)
+    }
+}
+
+/// Returns the ranges of pages which differ between `saved` and `current`, merging adjacent pages.
+fn dirty_pages<'a>(saved: &'a [u8], current: &'a [u8]) -> impl Iterator<Item = Range<usize>> + 'a {
+    let mut pages = saved
+        .chunks(PAGE_SIZE)
+        .zip(current.chunks(PAGE_SIZE))
+        .enumerate()
+        .peekable();
+
+    std::iter::from_fn(move || {
+        let (first, _) = pages.find(|(_, (saved, current))| saved != current)?;
+        let mut last = first;
+        while let Some((index, _)) = pages.next_if(|(_, (saved, current))| saved != current) {
+            last = index;
+        }
+
+        Some(first * PAGE_SIZE..((last + 1) * PAGE_SIZE).min(saved.len()))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unchanged_memory_has_no_dirty_pages() {
+        let saved = vec![0xAA; 4 * PAGE_SIZE];
+
+        assert_eq!(dirty_pages(&saved, &saved).count(), 0);
+    }
+
+    #[test]
+    fn adjacent_dirty_pages_are_merged() {
+        let saved = vec![0; 5 * PAGE_SIZE + 10];
+        let mut current = saved.clone();
+        current[PAGE_SIZE] = 1;
+        current[2 * PAGE_SIZE + 7] = 1;
+        current[5 * PAGE_SIZE + 3] = 1;
+
+        assert_eq!(
+            dirty_pages(&saved, &current).collect::<Vec<_>>(),
+            vec![PAGE_SIZE..3 * PAGE_SIZE, 5 * PAGE_SIZE..5 * PAGE_SIZE + 10]
+        );
+    }
+}
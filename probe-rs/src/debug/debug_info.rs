@@ -2,9 +2,11 @@ use super::{
     exception_handling::ExceptionInterface,
     function_die::{Die, FunctionDie},
     get_object_reference,
+    pretty_printer::{CollectionKind, CollectionPrinter, PrettyPrinters},
+    prologue::unwind_riscv_prologue,
     unit_info::UnitInfo,
     variable::*,
-    DebugError, DebugRegisters, StackFrame, VariableCache,
+    DebugError, DebugRegisters, ObjectRef, StackFrame, VariableCache,
 };
 use crate::{
     core::{RegisterRole, RegisterValue, UnwindRule},
@@ -17,10 +19,16 @@ use crate::{
 use gimli::{
     BaseAddresses, DebugFrame, DebugInfoOffset, UnwindContext, UnwindSection, UnwindTableRow,
 };
-use object::read::{Object, ObjectSection};
+use object::read::{Object, ObjectSection, ObjectSymbol};
 use probe_rs_target::InstructionSet;
 use std::{
-    borrow, cmp::Ordering, num::NonZeroU64, ops::ControlFlow, path::Path, rc::Rc, str::from_utf8,
+    borrow,
+    cmp::Ordering,
+    num::NonZeroU64,
+    ops::{ControlFlow, Range},
+    path::Path,
+    rc::Rc,
+    str::from_utf8,
 };
 use typed_path::{TypedPath, TypedPathBuf};
 
@@ -36,49 +44,48 @@ pub(crate) type DwarfReader = gimli::read::EndianRcSlice<gimli::LittleEndian>;
 pub struct DebugInfo {
     pub(crate) dwarf: gimli::Dwarf<DwarfReader>,
     pub(crate) frame_section: gimli::DebugFrame<DwarfReader>,
-    pub(crate) locations_section: gimli::LocationLists<DwarfReader>,
-    pub(crate) address_section: gimli::DebugAddr<DwarfReader>,
     pub(crate) debug_line_section: gimli::DebugLine<DwarfReader>,
 
     pub(crate) unit_infos: Vec<UnitInfo>,
+
+    /// The names and address ranges of the functions in the symbol table, which are also known
+    /// without debug information.
+    pub(crate) function_symbols: Vec<(String, Range<u64>)>,
+
+    pub(crate) pretty_printers: PrettyPrinters,
 }
 
 impl DebugInfo {
     /// Read debug info directly from a ELF file.
+    ///
+    /// The debug info of split units is read from the `.dwo` files they name, or from a `.dwp`
+    /// package next to the ELF file.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<DebugInfo, DebugError> {
-        let data = std::fs::read(path)?;
+        let data = std::fs::read(path.as_ref())?;
 
-        DebugInfo::from_raw(&data)
+        DebugInfo::load(&data, Some(path.as_ref()))
     }
 
     /// Parse debug information directly from a buffer containing an ELF file.
+    ///
+    /// The debug info of split units is read from the `.dwo` files they name.
     pub fn from_raw(data: &[u8]) -> Result<Self, DebugError> {
-        let object = object::File::parse(data)?;
+        DebugInfo::load(data, None)
+    }
 
-        // Load a section and return as `Cow<[u8]>`.
-        let load_section = |id: gimli::SectionId| -> Result<DwarfReader, gimli::Error> {
-            let data = object
-                .section_by_name(id.name())
-                .and_then(|section| section.uncompressed_data().ok())
-                .unwrap_or_else(|| borrow::Cow::Borrowed(&[][..]));
-
-            Ok(gimli::read::EndianRcSlice::new(
-                Rc::from(&*data),
-                gimli::LittleEndian,
-            ))
-        };
+    fn load(data: &[u8], path: Option<&Path>) -> Result<Self, DebugError> {
+        let object = object::File::parse(data)?;
+        let load_section = |id: gimli::SectionId| load_section(&object, Some(id.name()));
 
         // Load all of the sections.
         let dwarf_cow = gimli::Dwarf::load(&load_section)?;
 
         use gimli::Section;
         let mut frame_section = gimli::DebugFrame::load(load_section)?;
-        let address_section = gimli::DebugAddr::load(load_section)?;
-        let debug_loc = gimli::DebugLoc::load(load_section)?;
-        let debug_loc_lists = gimli::DebugLocLists::load(load_section)?;
-        let locations_section = gimli::LocationLists::new(debug_loc, debug_loc_lists);
         let debug_line_section = gimli::DebugLine::load(load_section)?;
 
+        let package = path.and_then(load_dwarf_package);
+
         let mut unit_infos = Vec::new();
 
         let mut iter = dwarf_cow.units();
@@ -93,26 +100,59 @@ impl DebugInfo {
                 // The frame section address size is only used for CIE versions before 4.
                 frame_section.set_address_size(unit.encoding().address_size);
 
-                unit_infos.push(UnitInfo::new(unit));
+                let unit_info = match unit.dwo_id {
+                    Some(dwo_id) => {
+                        match load_split_unit(&dwarf_cow, &unit, dwo_id, package.as_ref(), path) {
+                            Ok((split_dwarf, split_unit)) => {
+                                UnitInfo::new_split(split_unit, split_dwarf, unit)
+                            }
+                            Err(error) => {
+                                // The skeleton still has the line table, so stepping through the
+                                // source works, but there are no variables.
+                                tracing::warn!("Failed to load split debug info: {error}");
+                                UnitInfo::new(unit)
+                            }
+                        }
+                    }
+                    None => UnitInfo::new(unit),
+                };
+                unit_infos.push(unit_info);
             };
         }
 
+        let function_symbols = object
+            .symbols()
+            .filter(|symbol| symbol.kind() == object::SymbolKind::Text && symbol.size() > 0)
+            .map(|symbol| {
+                (
+                    symbol.name().unwrap_or_default().to_string(),
+                    symbol.address()..symbol.address() + symbol.size(),
+                )
+            })
+            .collect();
+
         Ok(DebugInfo {
             dwarf: dwarf_cow,
             frame_section,
-            locations_section,
-            address_section,
             debug_line_section,
             unit_infos,
+            function_symbols,
+            pretty_printers: PrettyPrinters::default(),
         })
     }
 
+    /// The pretty-printers used to show the elements of collections, which can be extended with
+    /// printers for other collection types.
+    pub fn pretty_printers_mut(&mut self) -> &mut PrettyPrinters {
+        &mut self.pretty_printers
+    }
+
     /// Try get the [`SourceLocation`] for a given address.
     pub fn get_source_location(&self, address: u64) -> Option<SourceLocation> {
         for unit_info in &self.unit_infos {
             let unit = &unit_info.unit;
 
-            let mut ranges = match self.dwarf.unit_ranges(unit) {
+            let mut ranges = match unit_info.unit_ranges(self) {
                 Ok(ranges) => ranges,
                 Err(error) => {
                     tracing::warn!(
@@ -205,6 +245,31 @@ impl DebugInfo {
         None
     }
 
+    /// Returns the functions containing the address, from the innermost inlined function out to
+    /// the function it is inlined into, like `addr2line --inlines`.
+    ///
+    /// This doesn't need a target, so it can be used to resolve the addresses of crash logs.
+    pub fn get_source_frames(&self, address: u64) -> Vec<SourceFrame> {
+        let Ok((_, functions)) = self.get_function_dies(address) else {
+            return Vec::new();
+        };
+
+        // The location in an inlined function is the address, and the location in the function
+        // it is inlined into is the call site.
+        let mut source_location = self.get_source_location(address);
+        let mut frames = Vec::with_capacity(functions.len());
+        for function in functions.iter().rev() {
+            let call_location = function.inline_call_location(self);
+            frames.push(SourceFrame {
+                function_name: function.function_name(self),
+                source_location: std::mem::replace(&mut source_location, call_location),
+                is_inlined: function.is_inline(),
+            });
+        }
+
+        frames
+    }
+
     /// We do not actually resolve the children of `[VariableName::StaticScope]` automatically,
     /// and only create the necessary header in the `VariableCache`.
     /// This allows us to resolve the `[VariableName::StaticScope]` on demand/lazily, when a user requests it from the debug client.
@@ -244,14 +309,24 @@ impl DebugInfo {
         }
 
         // Only attempt this part if we have not yet resolved the referenced children.
-        if cache.has_children(parent_variable) {
-            return Ok(());
+        if !cache.has_children(parent_variable) {
+            self.cache_children(cache, memory, parent_variable, frame_info)?;
         }
 
+        self.apply_pretty_printer(cache, memory, parent_variable, frame_info)
+    }
+
+    /// Resolves the children of a variable, as they are stored in memory.
+    fn cache_children(
+        &self,
+        cache: &mut VariableCache,
+        memory: &mut dyn MemoryInterface,
+        parent_variable: &mut Variable,
+        frame_info: StackFrameInfo<'_>,
+    ) -> Result<(), DebugError> {
         match parent_variable.variable_node_type {
             VariableNodeType::TypeOffset(header_offset, type_offset) => {
-                let unit_header = self.dwarf.debug_info.header_from_offset(header_offset)?;
-                let unit_info = UnitInfo::new(gimli::Unit::new(&self.dwarf, unit_header)?);
+                let unit_info = self.unit_info_at_offset(header_offset)?;
 
                 // Find the parent node
                 let mut type_tree = unit_info.unit.entries_tree(Some(type_offset))?;
@@ -267,8 +342,7 @@ impl DebugInfo {
                 )?;
             }
             VariableNodeType::DirectLookup(header_offset, unit_offset) => {
-                let unit_header = self.dwarf.debug_info.header_from_offset(header_offset)?;
-                let unit_info = UnitInfo::new(gimli::Unit::new(&self.dwarf, unit_header)?);
+                let unit_info = self.unit_info_at_offset(header_offset)?;
 
                 // Find the parent node
                 let mut type_tree = unit_info.unit.entries_tree(Some(unit_offset))?;
@@ -336,6 +410,131 @@ impl DebugInfo {
         Ok(())
     }
 
+    /// Replaces the children of a collection with its elements, if one of the
+    /// [`PrettyPrinters`] matches its type.
+    fn apply_pretty_printer(
+        &self,
+        cache: &mut VariableCache,
+        memory: &mut dyn MemoryInterface,
+        collection: &mut Variable,
+        frame_info: StackFrameInfo<'_>,
+    ) -> Result<(), DebugError> {
+        // Printed collections have no storage left, so the printers don't match them anymore.
+        let printers: Vec<_> = self.pretty_printers.find(collection).cloned().collect();
+        for printer in printers {
+            if self.print_collection(cache, memory, collection, &printer, frame_info)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the children of a collection with its elements. Returns `false` if the layout of
+    /// the collection doesn't match the printer.
+    fn print_collection(
+        &self,
+        cache: &mut VariableCache,
+        memory: &mut dyn MemoryInterface,
+        collection: &mut Variable,
+        printer: &CollectionPrinter,
+        frame_info: StackFrameInfo<'_>,
+    ) -> Result<bool, DebugError> {
+        let Some(array) =
+            self.find_field(cache, memory, collection, &printer.elements, frame_info)?
+        else {
+            return Ok(false);
+        };
+        let entries: Vec<_> = cache.get_children(array.variable_key).cloned().collect();
+
+        let mut field = |path: &[String]| {
+            self.find_field(cache, memory, collection, path, frame_info)
+                .ok()
+                .flatten()
+                .map(|field| field.to_string(cache))
+        };
+        let Some(indices) = printer.occupancy.indices(&mut field, entries.len()) else {
+            return Ok(false);
+        };
+
+        let mut elements = Vec::with_capacity(indices.len());
+        for index in indices {
+            let Some(element) =
+                self.find_field(cache, memory, &entries[index], &printer.element, frame_info)?
+            else {
+                return Ok(false);
+            };
+
+            let element = match &printer.kind {
+                CollectionKind::Map { key, value } => {
+                    let key = self.find_field(cache, memory, &element, key, frame_info)?;
+                    let value = self.find_field(cache, memory, &element, value, frame_info)?;
+                    let (Some(key), Some(mut value)) = (key, value) else {
+                        return Ok(false);
+                    };
+                    value.name = VariableName::Named(key.to_string(cache));
+                    value
+                }
+                CollectionKind::List | CollectionKind::Text => {
+                    let mut element = element;
+                    element.name = VariableName::Named(format!("__{}", elements.len()));
+                    element
+                }
+            };
+            elements.push(element);
+        }
+
+        // Replace the storage with the elements, which keep the children they already have.
+        let storage: Vec<_> = cache
+            .get_children(collection.variable_key)
+            .map(|child| child.variable_key)
+            .collect();
+        for element in &mut elements {
+            let old_key = element.variable_key;
+            element.variable_key = ObjectRef::Invalid;
+            element.member_index = None;
+            cache.add_variable(collection.variable_key, element)?;
+            cache.move_children(old_key, element.variable_key);
+        }
+        for key in storage {
+            cache.remove_cache_entry(key)?;
+        }
+
+        collection.set_value(VariableValue::Valid(printer.kind.format(&elements, cache)));
+        cache.update_variable(collection)?;
+
+        Ok(true)
+    }
+
+    /// Returns the field at the end of the `path` of field names, resolving the fields on the
+    /// way.
+    fn find_field(
+        &self,
+        cache: &mut VariableCache,
+        memory: &mut dyn MemoryInterface,
+        variable: &Variable,
+        path: &[String],
+        frame_info: StackFrameInfo<'_>,
+    ) -> Result<Option<Variable>, DebugError> {
+        let mut variable = variable.clone();
+        for name in path {
+            let Some(mut field) = cache.get_variable_by_name_and_parent(
+                &VariableName::Named(name.clone()),
+                variable.variable_key,
+            ) else {
+                return Ok(None);
+            };
+
+            // Fields of collections are looked up in their storage, not their elements.
+            if !cache.has_children(&field) {
+                self.cache_children(cache, memory, &mut field, frame_info)?;
+            }
+            variable = field;
+        }
+
+        Ok(Some(variable))
+    }
+
     /// Returns a populated (resolved) [`StackFrame`] struct.
     /// This function will also populate the `DebugInfo::VariableCache` with in scope `Variable`s for each `StackFrame`, while taking into account the appropriate strategy for lazy-loading of variables.
     pub(crate) fn get_stackframe_info(
@@ -681,58 +880,80 @@ impl DebugInfo {
 
             // PART 2-a: get the `gimli::FrameDescriptorEntry` for the program counter
             // and then the unwind info associated with this row.
-            let unwind_info =
-                match get_unwind_info(&mut unwind_context, &self.frame_section, frame_pc) {
-                    Ok(unwind_info) => unwind_info,
-                    Err(_) => {
-                        // For non exception frames, we cannot do stack unwinding if we do not have debug info.
-                        // However, there is one case where we can continue. When the frame registers have a valid
-                        // return address/LR value, we can use the LR value to calculate the PC for the calling frame.
-                        // The current logic will then use that PC to get the next frame's unwind info, and if that exists,
-                        // we will be able to continue unwinding.
-                        // If the calling frame has no debug info, then the unwinding will end with that frame.
-                        let callee_frame_registers = unwind_registers.clone();
-                        let mut unwound_return_address: Option<RegisterValue> = unwind_registers
-                            .get_return_address()
-                            .and_then(|lr| lr.value);
-
-                        // This will update the program counter in the `unwind_registers` with the PC value calculated from the LR value.
-                        if let Some(calling_pc) = unwind_registers.get_program_counter_mut() {
-                            if let ControlFlow::Break(error) = unwind_register(
-                                calling_pc,
-                                &callee_frame_registers,
-                                None,
-                                stack_frames
-                                    .last()
-                                    .and_then(|first_frame| first_frame.canonical_frame_address),
-                                &mut unwound_return_address,
+            let unwind_info = match get_unwind_info(
+                &mut unwind_context,
+                &self.frame_section,
+                frame_pc,
+            ) {
+                Ok(unwind_info) => unwind_info,
+                Err(_) => {
+                    // Without call frame information, the frame of a RISC-V function can
+                    // still be found by analyzing its prologue. This updates the stack pointer
+                    // and the return address the program counter is unwound from below.
+                    if matches!(
+                        instruction_set,
+                        Some(InstructionSet::RV32 | InstructionSet::RV32C)
+                    ) {
+                        if let Some(function_start) = self.function_start(frame_pc) {
+                            if let Err(error) = unwind_riscv_prologue(
                                 memory,
-                                instruction_set,
+                                function_start,
+                                frame_pc,
+                                &mut unwind_registers,
                             ) {
-                                // This is not fatal, but we cannot continue unwinding beyond the current frame.
-                                tracing::error!("{:?}", &error);
-                                if let Some(first_frame) = stack_frames.first_mut() {
-                                    first_frame.function_name =
-                                        format!("{} : ERROR : {error}", first_frame.function_name);
-                                };
-                                break 'unwind;
-                            }
-                            if calling_pc
-                                .value
-                                .map(|calling_pc_value| {
-                                    calling_pc_value.eq(&frame_pc_register_value)
-                                })
-                                .unwrap_or(false)
-                            {
-                                // Typically if we have to infer the PC value, it might happen that we are in
-                                // a function that has no debug info, and the code is in a tight loop (typical of exception handlers).
-                                // In such cases, we will not be able to unwind the stack beyond this frame.
-                                break 'unwind;
+                                tracing::warn!(
+                                        "UNWIND: Failed to analyze the prologue of the function at {function_start:#010x}: {error}"
+                                    );
                             }
                         }
-                        continue 'unwind;
                     }
-                };
+
+                    // For non exception frames, we cannot do stack unwinding if we do not have debug info.
+                    // However, there is one case where we can continue. When the frame registers have a valid
+                    // return address/LR value, we can use the LR value to calculate the PC for the calling frame.
+                    // The current logic will then use that PC to get the next frame's unwind info, and if that exists,
+                    // we will be able to continue unwinding.
+                    // If the calling frame has no debug info, then the unwinding will end with that frame.
+                    let callee_frame_registers = unwind_registers.clone();
+                    let mut unwound_return_address: Option<RegisterValue> = unwind_registers
+                        .get_return_address()
+                        .and_then(|lr| lr.value);
+
+                    // This will update the program counter in the `unwind_registers` with the PC value calculated from the LR value.
+                    if let Some(calling_pc) = unwind_registers.get_program_counter_mut() {
+                        if let ControlFlow::Break(error) = unwind_register(
+                            calling_pc,
+                            &callee_frame_registers,
+                            None,
+                            stack_frames
+                                .last()
+                                .and_then(|first_frame| first_frame.canonical_frame_address),
+                            &mut unwound_return_address,
+                            memory,
+                            instruction_set,
+                        ) {
+                            // This is not fatal, but we cannot continue unwinding beyond the current frame.
+                            tracing::error!("{:?}", &error);
+                            if let Some(first_frame) = stack_frames.first_mut() {
+                                first_frame.function_name =
+                                    format!("{} : ERROR : {error}", first_frame.function_name);
+                            };
+                            break 'unwind;
+                        }
+                        if calling_pc
+                            .value
+                            .map(|calling_pc_value| calling_pc_value.eq(&frame_pc_register_value))
+                            .unwrap_or(false)
+                        {
+                            // Typically if we have to infer the PC value, it might happen that we are in
+                            // a function that has no debug info, and the code is in a tight loop (typical of exception handlers).
+                            // In such cases, we will not be able to unwind the stack beyond this frame.
+                            break 'unwind;
+                        }
+                    }
+                    continue 'unwind;
+                }
+            };
 
             // Because we will be updating the `unwind_registers` with previous frame unwind info, we need to keep a copy of the current frame's registers that can be used to resolve [DWARF](https://dwarfstd.org) expressions.
             let callee_frame_registers = unwind_registers.clone();
@@ -888,7 +1109,7 @@ impl DebugInfo {
         address: u64,
     ) -> Result<&super::unit_info::UnitInfo, DebugError> {
         for header in &self.unit_infos {
-            match self.dwarf.unit_ranges(&header.unit) {
+            match header.unit_ranges(self) {
                 Ok(mut ranges) => {
                     while let Ok(Some(range)) = ranges.next() {
                         if range.contains(address) {
@@ -904,6 +1125,78 @@ impl DebugInfo {
         })
     }
 
+    /// Returns the start address of the function containing `address`, from the debug information
+    /// or from the symbol table.
+    pub(crate) fn function_start(&self, address: u64) -> Option<u64> {
+        if let Some(low_pc) = self
+            .get_function_dies(address)
+            .ok()
+            .and_then(|(_, functions)| functions.first().and_then(|function| function.low_pc()))
+        {
+            return Some(low_pc);
+        }
+
+        self.function_symbols
+            .iter()
+            .find(|(_, range)| range.contains(&address))
+            .map(|(_, range)| range.start)
+    }
+
+    /// Returns the address of the function called `name` in the symbol table.
+    pub fn function_address(&self, name: &str) -> Option<u64> {
+        self.function_symbols
+            .iter()
+            .find(|(symbol, _)| symbol == name)
+            .map(|(_, range)| range.start)
+    }
+
+    /// Check that the program counter can be moved from `program_counter` to `target`.
+    ///
+    /// Only statements in the function that is currently executing are valid targets,
+    /// because jumping into another function would leave the stack frame and registers
+    /// in a state that the target code does not expect.
+    pub fn validate_goto_target(
+        &self,
+        program_counter: u64,
+        target: u64,
+    ) -> Result<(), DebugError> {
+        let Some(current_function) = self.function_start(program_counter) else {
+            return Err(DebugError::Other(format!(
+                "Cannot determine the function containing the program counter {program_counter:#010x}."
+            )));
+        };
+
+        if self.function_start(target) != Some(current_function) {
+            return Err(DebugError::Other(format!(
+                "Address {target:#010x} is not in the current function. Only statements in the current function can be jumped to."
+            )));
+        }
+
+        if self.get_source_location(target).is_none() {
+            return Err(DebugError::Other(format!(
+                "Address {target:#010x} does not correspond to a source statement."
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Find the address of the statement at the given source location, that the program counter
+    /// can be moved to while halted at `program_counter`.
+    ///
+    /// See [`DebugInfo::validate_goto_target`] for the restrictions on valid targets.
+    pub fn get_goto_location(
+        &self,
+        program_counter: u64,
+        path: &TypedPathBuf,
+        line: u64,
+        column: Option<u64>,
+    ) -> Result<VerifiedBreakpoint, DebugError> {
+        let location = self.get_breakpoint_location(path, line, column)?;
+        self.validate_goto_target(program_counter, location.address)?;
+        Ok(location)
+    }
+
     /// Search accross all compilation untis, and retrive the DIEs for the function containing the given address.
     /// This is distinct from [`UnitInfo::get_function_dies`] in that it will search all compilation units.
     /// - The first entry in the vector will be the outermost function containing the address.
@@ -924,9 +1217,23 @@ impl DebugInfo {
         )))
     }
 
+    /// Returns the unit at the given offset into the debug info section.
+    fn unit_info_at_offset(&self, offset: DebugInfoOffset) -> Result<&UnitInfo, DebugError> {
+        self.unit_infos
+            .iter()
+            .find(|unit_info| unit_info.debug_info_offset().ok() == Some(offset))
+            .ok_or_else(|| {
+                DebugError::Other(format!(
+                    "Unit at debug info offset {:#010x} not found",
+                    offset.0
+                ))
+            })
+    }
+
     /// Get the DIE at the given offset into the debug info section.
     pub(crate) fn get_die_at_offset(&self, offset: DebugInfoOffset) -> Result<Die, DebugError> {
-        for unit_info in &self.unit_infos {
+        // The DIEs of split units are in other files.
+        for unit_info in self.unit_infos.iter().filter(|unit| !unit.is_split()) {
             if let Some(unit_offset) = offset.to_unit_offset(&unit_info.unit.header) {
                 return unit_info.unit.entry(unit_offset).map_err(|error| {
                     DebugError::Other(format!(
@@ -961,7 +1268,13 @@ impl DebugInfo {
                 move |reference_attribute| match reference_attribute.value() {
                     gimli::AttributeValue::UnitRef(unit_ref) => unit_info.unit.entry(unit_ref).ok(),
                     gimli::AttributeValue::DebugInfoRef(debug_info_ref) => {
-                        self.get_die_at_offset(debug_info_ref).ok()
+                        match debug_info_ref.to_unit_offset(&unit_info.unit.header) {
+                            // References within split units are relative to their own file.
+                            Some(unit_ref) if unit_info.is_split() => {
+                                unit_info.unit.entry(unit_ref).ok()
+                            }
+                            _ => self.get_die_at_offset(debug_info_ref).ok(),
+                        }
                     }
                     other_value => {
                         tracing::warn!(
@@ -975,6 +1288,110 @@ impl DebugInfo {
     }
 }
 
+/// Loads a section of an object file, or an empty section if it doesn't exist.
+fn load_section(object: &object::File, name: Option<&str>) -> Result<DwarfReader, gimli::Error> {
+    let data = name
+        .and_then(|name| object.section_by_name(name))
+        .and_then(|section| section.uncompressed_data().ok())
+        .unwrap_or_else(|| borrow::Cow::Borrowed(&[][..]));
+
+    Ok(gimli::read::EndianRcSlice::new(
+        Rc::from(&*data),
+        gimli::LittleEndian,
+    ))
+}
+
+/// Loads the `.dwp` package of an ELF file, which is either `<file>.dwp` or `<file>` with the
+/// extension replaced by `.dwp`.
+fn load_dwarf_package(path: &Path) -> Option<gimli::DwarfPackage<DwarfReader>> {
+    let mut package_path = path.as_os_str().to_owned();
+    package_path.push(".dwp");
+
+    let data = [package_path.into(), path.with_extension("dwp")]
+        .iter()
+        .find_map(|path: &std::path::PathBuf| std::fs::read(path).ok())?;
+    let object = object::File::parse(&*data).ok()?;
+    let empty = load_section(&object, None).ok()?;
+
+    gimli::DwarfPackage::load(|id| load_section(&object, id.dwo_name()), empty)
+        .inspect_err(|error| tracing::warn!("Failed to read the DWARF package: {error}"))
+        .ok()
+}
+
+/// Loads the split unit of a `skeleton` unit, from the DWARF package or the `.dwo` file named
+/// by the skeleton.
+fn load_split_unit(
+    dwarf: &gimli::Dwarf<DwarfReader>,
+    skeleton: &gimli::Unit<DwarfReader>,
+    dwo_id: gimli::DwoId,
+    package: Option<&gimli::DwarfPackage<DwarfReader>>,
+    path: Option<&Path>,
+) -> Result<(gimli::Dwarf<DwarfReader>, gimli::Unit<DwarfReader>), DebugError> {
+    let split_dwarf = match package.map(|package| package.find_cu(dwo_id, dwarf)) {
+        Some(Ok(Some(split_dwarf))) => split_dwarf,
+        _ => {
+            let dwo_name = skeleton
+                .dwo_name()?
+                .map(|name| dwarf.attr_string(skeleton, name))
+                .transpose()?
+                .ok_or_else(|| DebugError::Other("The split unit has no .dwo file".to_string()))?;
+            let dwo_name =
+                Path::new(from_utf8(&dwo_name).map_err(|error| {
+                    DebugError::Other(format!("Invalid .dwo file name: {error}"))
+                })?);
+
+            // The name is relative to the compilation directory, which may not exist on this
+            // machine, so the file is also looked for next to the ELF file.
+            let comp_dir = skeleton
+                .comp_dir
+                .as_ref()
+                .and_then(|dir| from_utf8(dir).ok())
+                .map(Path::new);
+            let candidates = [
+                comp_dir.map(|dir| dir.join(dwo_name)),
+                path.and_then(Path::parent)
+                    .zip(dwo_name.file_name())
+                    .map(|(dir, name)| dir.join(name)),
+            ];
+            let data = candidates
+                .iter()
+                .flatten()
+                .find_map(|path| std::fs::read(path).ok())
+                .ok_or_else(|| DebugError::Other(format!("{} not found", dwo_name.display())))?;
+
+            let object = object::File::parse(&*data)?;
+            let mut split_dwarf = gimli::Dwarf::load(|id| load_section(&object, id.dwo_name()))?;
+            split_dwarf.make_dwo(dwarf);
+            split_dwarf
+        }
+    };
+
+    let header = split_dwarf
+        .units()
+        .next()?
+        .ok_or_else(|| DebugError::Other("The split debug info has no unit".to_string()))?;
+    let split_unit = split_dwarf.unit(header)?;
+    if split_unit.dwo_id != Some(dwo_id) {
+        return Err(DebugError::Other(
+            "The split debug info belongs to another build".to_string(),
+        ));
+    }
+
+    Ok((split_dwarf, split_unit))
+}
+
+/// A function containing an address, as returned by [`DebugInfo::get_source_frames`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFrame {
+    /// The name of the function, if the debug info has one.
+    pub function_name: Option<String>,
+    /// The location of the address in the function, or, if another function is inlined at the
+    /// address, the location of its call site.
+    pub source_location: Option<SourceLocation>,
+    /// Whether the function is inlined into the next one.
+    pub is_inlined: bool,
+}
+
 /// Uses the [`TypedPathBuf::normalize`] function to normalize both paths before comparing them
 pub(crate) fn canonical_path_eq(
     primary_path: &TypedPathBuf,
@@ -1487,6 +1904,13 @@ mod test {
         let next_frame = &frames[1];
         assert_eq!(next_frame.function_name, "SVC");
         assert_eq!(next_frame.pc, RegisterValue::U32(0x00000180));
+        assert_eq!(
+            next_frame
+                .registers
+                .get_register_value_by_role(&crate::RegisterRole::StackPointer)
+                .unwrap(),
+            0x2001fff0
+        );
 
         // Expected stack frame(s):
         // Frame 0: __cortex_m_rt_SVCall_trampoline @ 0x00000182
@@ -1759,6 +2183,70 @@ mod test {
         insta::assert_snapshot!(printed_backtrace);
     }
 
+    #[test_case("split-dwarf/dwo/split-dwarf"; "with .dwo file")]
+    #[test_case("split-dwarf/dwp/split-dwarf"; "with .dwp package")]
+    fn split_dwarf(elf_file: &str) {
+        // Built from `split-dwarf/main.c` with `gcc -g -gdwarf-5 -gsplit-dwarf`, in a directory
+        // which no longer exists.
+        let debug_info = load_test_elf_as_debug_info(elf_file);
+
+        let (unit_info, functions) = debug_info.get_function_dies(0x40100a).unwrap();
+        assert!(unit_info.is_split());
+        assert_eq!(
+            functions[0].function_name(&debug_info).as_deref(),
+            Some("add")
+        );
+
+        let location = debug_info.get_source_location(0x40100a).unwrap();
+        assert_eq!(location.file.as_deref(), Some("main.c"));
+        assert_eq!(location.line, Some(5));
+    }
+
+    #[test]
+    fn source_frames_of_inlined_functions() {
+        let debug_info = load_test_elf_as_debug_info("inlined-functions");
+
+        let frames = debug_info.get_source_frames(0x2e4);
+        let frames: Vec<_> = frames
+            .iter()
+            .map(|frame| {
+                let location = frame.source_location.as_ref().unwrap();
+                (
+                    frame.function_name.clone().unwrap(),
+                    location.file.clone().unwrap(),
+                    location.line.unwrap(),
+                    frame.is_inlined,
+                )
+            })
+            .collect();
+
+        let timer = |function: &str, line, inlined| {
+            (
+                format!("{function}<nrf52833_pac::TIMER0, nrf_hal_common::timer::OneShot>"),
+                "timer.rs".to_string(),
+                line,
+                inlined,
+            )
+        };
+        assert_eq!(
+            frames,
+            [
+                timer("wait", 266, true),
+                timer("delay", 145, true),
+                timer("delay_us", 324, true),
+                timer("delay_ms", 297, true),
+                timer("delay_ms", 306, true),
+                (
+                    "__cortex_m_rt_main".to_string(),
+                    "main.rs".to_string(),
+                    20,
+                    false
+                ),
+            ]
+        );
+        assert!(debug_info.get_source_frames(0x1000_0000).is_empty());
+    }
+
     #[test]
     fn unwinding_inlined() {
         let debug_info = load_test_elf_as_debug_info("inlined-functions");
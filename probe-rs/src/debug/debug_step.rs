@@ -1,12 +1,20 @@
-use super::{debug_info::DebugInfo, DebugError, VerifiedBreakpoint};
+use super::{
+    debug_info::{determine_cfa, get_unwind_info, DebugInfo},
+    registers::DebugRegisters,
+    DebugError, VerifiedBreakpoint,
+};
 use crate::{
     architecture::{
         arm::ArmError, riscv::communication_interface::RiscvError,
         xtensa::communication_interface::XtensaError,
     },
-    CoreInterface, CoreStatus, HaltReason,
+    CoreInterface, CoreStatus, HaltReason, InterruptMasking,
+};
+use gimli::RegisterRule;
+use std::{
+    ops::RangeInclusive,
+    time::{Duration, Instant},
 };
-use std::{ops::RangeInclusive, time::Duration};
 
 /// Stepping granularity for stepping through a program during debug.
 #[derive(Clone, Debug)]
@@ -33,13 +41,25 @@ impl SteppingMode {
     /// - If no hardware breakpoints are available, we will do repeated instruction steps until we reach the desired location.
     ///
     /// Usage Note:
-    /// - Currently, no special provision is made for the effect of interrupts that get triggered
-    ///   during stepping. The user must ensure that interrupts are disabled during stepping, or
-    ///   accept that stepping may be diverted by the interrupt processing on the core.
+    /// - Instruction steps are taken with interrupts masked. Use [`SteppingMode::step_with`] to
+    ///   allow pending interrupts to be taken during an instruction step.
+    /// - Statement steps which run to a breakpoint may still be diverted by the interrupt
+    ///   processing on the core.
     pub fn step(
         &self,
         core: &mut impl CoreInterface,
         debug_info: &DebugInfo,
+    ) -> Result<(CoreStatus, u64), DebugError> {
+        self.step_with(core, debug_info, InterruptMasking::Masked)
+    }
+
+    /// Like [`SteppingMode::step`], but `interrupts` selects whether interrupts are masked
+    /// while the core is single stepped.
+    pub fn step_with(
+        &self,
+        core: &mut impl CoreInterface,
+        debug_info: &DebugInfo,
+        interrupts: InterruptMasking,
     ) -> Result<(CoreStatus, u64), DebugError> {
         let mut core_status = core.status()?;
         let mut program_counter = match core_status {
@@ -62,7 +82,7 @@ impl SteppingMode {
             let post_step_target = match self {
                 SteppingMode::StepInstruction => {
                     // First deal with the the fast/easy case.
-                    program_counter = core.step()?.pc;
+                    program_counter = core.step_with(interrupts)?.pc;
                     core_status = core.status()?;
                     return Ok((core_status, program_counter));
                 }
@@ -131,7 +151,11 @@ impl SteppingMode {
                     target_address,
                 );
 
-                run_to_address(program_counter, target_address, core)?
+                if matches!(self, SteppingMode::OverStatement) {
+                    step_over_range(program_counter, target_address, core, debug_info)?
+                } else {
+                    run_to_address(program_counter, target_address, core)?
+                }
             }
             None => {
                 return Err(DebugError::WarnAndContinue {
@@ -281,7 +305,24 @@ impl SteppingMode {
                                     next_instruction_address,
                                     None,
                                 );
-                            } else if let Some(return_address) = return_address {
+                            } else if let Some(caller) =
+                                CallerFrame::of_current_function(core, debug_info)
+                            {
+                                // Run to the exact return address, and only then look for the next statement,
+                                // so a long running or recursive function doesn't end the step early.
+                                if let Some((_, halted_at)) = run_to_any(&[], Some(&caller), core)?
+                                {
+                                    tracing::debug!(
+                                        "Step Out target: returned to {:#010x}, halted at {:#010x}",
+                                        caller.return_address,
+                                        halted_at
+                                    );
+                                    return SteppingMode::BreakPoint
+                                        .get_halt_location(core, debug_info, halted_at, None);
+                                }
+                            }
+
+                            if let Some(return_address) = return_address {
                                 tracing::debug!(
                                         "Step Out target: non-inline function, stepping over return address: {:#010x}",
                                             return_address
@@ -309,6 +350,137 @@ impl SteppingMode {
     }
 }
 
+/// How long a step may run the core, before it is halted again.
+const STEP_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// The frame which the current function returns to.
+struct CallerFrame {
+    /// The address where execution continues when the current function returns.
+    return_address: u64,
+    /// The canonical frame address of the current function, i.e. the value of the stack pointer after it returned.
+    cfa: u64,
+}
+
+impl CallerFrame {
+    /// Determine the caller of the function at the current program counter, using the call frame information.
+    ///
+    /// Unlike the value of the return address register, this is also correct after the current function
+    /// called other functions.
+    fn of_current_function(core: &mut impl CoreInterface, debug_info: &DebugInfo) -> Option<Self> {
+        let registers = DebugRegisters::from_core(core);
+        let program_counter = registers.get_program_counter()?.value?.try_into().ok()?;
+
+        let mut unwind_context = Box::new(gimli::UnwindContext::new());
+        let unwind_info = get_unwind_info(
+            &mut unwind_context,
+            &debug_info.frame_section,
+            program_counter,
+        )
+        .ok()?;
+        if !matches!(unwind_info.cfa(), gimli::CfaRule::RegisterAndOffset { .. }) {
+            return None;
+        }
+        let cfa = determine_cfa(&registers, unwind_info).ok()??;
+
+        let return_register = registers.get_return_address()?;
+        let return_address: u64 = match return_register
+            .dwarf_id
+            .map(|id| unwind_info.register(gimli::Register(id)))
+        {
+            Some(RegisterRule::Offset(offset)) => {
+                let address = cfa.checked_add_signed(offset)?;
+                if registers.get_address_size_bytes() == 8 {
+                    core.read_word_64(address).ok()?
+                } else {
+                    core.read_word_32(address).ok()? as u64
+                }
+            }
+            // The return address has not been saved, so the register still holds it.
+            None | Some(RegisterRule::Undefined | RegisterRule::SameValue) => {
+                return_register.value?.try_into().ok()?
+            }
+            Some(_) => return None,
+        };
+
+        if return_address == 0 || return_address == u64::from(u32::MAX) {
+            return None;
+        }
+
+        Some(Self {
+            // Clear the Thumb bit, so the address can be compared to the program counter.
+            return_address: return_address & !0b1,
+            cfa,
+        })
+    }
+}
+
+/// Hardware breakpoints which are only set for the duration of a step.
+///
+/// Only free breakpoint units are used, so breakpoints set by the user are left untouched.
+#[derive(Default)]
+struct TransientBreakpoints {
+    units: Vec<usize>,
+}
+
+impl TransientBreakpoints {
+    /// Halt the core at `address`. Returns `false` if there is no free breakpoint unit.
+    fn set(&mut self, core: &mut impl CoreInterface, address: u64) -> Result<bool, crate::Error> {
+        let breakpoints = core.hw_breakpoints()?;
+        if breakpoints.contains(&Some(address)) {
+            // The core already halts at this address.
+            return Ok(true);
+        }
+        let Some(unit) = breakpoints.iter().position(Option::is_none) else {
+            return Ok(false);
+        };
+
+        if !core.hw_breakpoints_enabled() {
+            core.enable_breakpoints(true)?;
+        }
+        core.set_hw_breakpoint(unit, address)?;
+        self.units.push(unit);
+        Ok(true)
+    }
+
+    fn clear(self, core: &mut impl CoreInterface) -> Result<(), crate::Error> {
+        for unit in self.units {
+            core.clear_hw_breakpoint(unit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Step over the statement which spans `program_counter..target_address`.
+///
+/// Instead of stepping through the range one instruction at a time, the core runs until it either reaches
+/// `target_address`, or the current function returns, e.g. because the statement contains a `return`.
+/// Without the breakpoint at the return address, such a step would only halt the next time the statement runs.
+fn step_over_range(
+    program_counter: u64,
+    target_address: u64,
+    core: &mut impl CoreInterface,
+    debug_info: &DebugInfo,
+) -> Result<(CoreStatus, u64), DebugError> {
+    if target_address == program_counter {
+        return run_to_address(program_counter, target_address, core);
+    }
+
+    let caller = CallerFrame::of_current_function(core, debug_info);
+    let Some((core_status, new_pc)) = run_to_any(&[target_address], caller.as_ref(), core)? else {
+        return run_to_address(program_counter, target_address, core);
+    };
+
+    match caller {
+        Some(caller) if new_pc == caller.return_address && new_pc != target_address => {
+            // We returned from the function, so continue to the next statement of the caller.
+            let next_statement =
+                SteppingMode::BreakPoint.get_halt_location(core, debug_info, new_pc, None)?;
+            run_to_address(new_pc, next_statement.address, core)
+        }
+        _ => Ok((core_status, new_pc)),
+    }
+}
+
 /// Run the target to the desired address. If available, we will use a breakpoint, otherwise we will use single step.
 /// Returns the program counter at the end of the step, when any of the following conditions are met:
 /// - We reach the `target_address_range.end()` (inclusive)
@@ -316,35 +488,81 @@ impl SteppingMode {
 /// - We encounter an error (e.g. the core locks up, or the USB cable is unplugged, etc.)
 /// - It turns out this step will be long-running, and we do not have to wait any longer for the request to complete.
 fn run_to_address(
-    mut program_counter: u64,
+    program_counter: u64,
     target_address: u64,
     core: &mut impl CoreInterface,
 ) -> Result<(CoreStatus, u64), DebugError> {
-    Ok(if target_address == program_counter {
+    if target_address == program_counter {
         // No need to step further. e.g. For inline functions we have already stepped to the best available target address..
-        (
+        return Ok((
             core.status()?,
             core.read_core_reg(core.program_counter().id())?
                 .try_into()?,
-        )
-    } else if core.set_hw_breakpoint(0, target_address).is_ok() {
+        ));
+    }
+
+    match run_to_any(&[target_address], None, core)? {
+        Some(result) => Ok(result),
+        // If we don't have breakpoints to use, we have to rely on single stepping.
+        // TODO: In theory, this could go on for a long time. Should we consider NOT allowing this kind of stepping if there are no breakpoints available?
+        None => step_to_address(target_address..=u64::MAX, core),
+    }
+}
+
+/// Run the target until it halts at one of the `exits`, or the return address of `caller`, using transient breakpoints.
+///
+/// The core only counts as having returned to `caller` once the stack pointer is back at its frame,
+/// so that a recursive call returning to the same address does not end the step early.
+///
+/// Returns `None` if there are not enough free breakpoint units.
+fn run_to_any(
+    exits: &[u64],
+    caller: Option<&CallerFrame>,
+    core: &mut impl CoreInterface,
+) -> Result<Option<(CoreStatus, u64)>, DebugError> {
+    let mut breakpoints = TransientBreakpoints::default();
+    for &exit in exits
+        .iter()
+        .chain(caller.map(|caller| &caller.return_address))
+    {
+        if !breakpoints.set(core, exit)? {
+            breakpoints.clear(core)?;
+            return Ok(None);
+        }
+    }
+
+    let deadline = Instant::now() + STEP_TIMEOUT;
+    let result = loop {
         core.run()?;
         // It is possible that we are stepping over long running instructions.
-        match core.wait_for_core_halted(Duration::from_millis(1000)) {
+        match core.wait_for_core_halted(deadline.saturating_duration_since(Instant::now())) {
             Ok(()) => {
-                // We have hit the target address, so all is good.
-                // NOTE: It is conceivable that the core has halted, but we have not yet stepped to the target address. (e.g. the user tries to step out of a function, but there is another breakpoint active before the end of the function.)
-                //       This is a legitimate situation, so we clear the breakpoint at the target address, and pass control back to the user
-                core.clear_hw_breakpoint(0)?;
-                (
-                    core.status()?,
-                    core.read_core_reg(core.program_counter().id())?
-                        .try_into()?,
-                )
+                // NOTE: It is conceivable that the core has halted, but we have not yet stepped to an exit. (e.g. the user tries to step out of a function, but there is another breakpoint active before the end of the function.)
+                //       This is a legitimate situation, so we clear the transient breakpoints, and pass control back to the user
+                let program_counter: u64 = core
+                    .read_core_reg(core.program_counter().id())?
+                    .try_into()?;
+                if let Some(caller) = caller {
+                    if program_counter == caller.return_address && !exits.contains(&program_counter)
+                    {
+                        let stack_pointer: u64 =
+                            core.read_core_reg(core.stack_pointer().id())?.try_into()?;
+                        if stack_pointer < caller.cfa {
+                            // A deeper call of the same function returned.
+                            continue;
+                        }
+                    }
+                }
+                break (core.status()?, program_counter);
             }
             Err(error) => {
-                program_counter = core.halt(Duration::from_millis(500))?.pc;
-                core.clear_hw_breakpoint(0)?;
+                let program_counter = core.halt(Duration::from_millis(500))?.pc;
+                breakpoints.clear(core)?;
+                let target_address = exits
+                    .first()
+                    .or(caller.map(|caller| &caller.return_address))
+                    .copied()
+                    .unwrap_or_default();
                 if matches!(
                     error,
                     crate::Error::Arm(ArmError::Timeout)
@@ -357,23 +575,22 @@ fn run_to_address(
                         target_address,
                         program_counter
                     );
-                    (core.status()?, program_counter)
+                    return Ok(Some((core.status()?, program_counter)));
                 } else {
                     // Something else is wrong.
                     return Err(DebugError::Other(format!(
                         "Unexpected error while waiting for the core to halt after stepping to {:#010X}. Forced a halt at {:#010X}. {:?}.",
-                        program_counter,
                         target_address,
+                        program_counter,
                         error
                     )));
                 }
             }
         }
-    } else {
-        // If we don't have breakpoints to use, we have to rely on single stepping.
-        // TODO: In theory, this could go on for a long time. Should we consider NOT allowing this kind of stepping if there are no breakpoints available?
-        step_to_address(target_address..=u64::MAX, core)?
-    })
+    };
+
+    breakpoints.clear(core)?;
+    Ok(Some(result))
 }
 
 /// In some cases, we need to single-step the core, until ONE of the following conditions are met:
@@ -416,3 +633,43 @@ fn step_to_address(
             .try_into()?,
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::probe::fake_probe::FakeProbe;
+
+    #[test]
+    fn transient_breakpoints_keep_user_breakpoints() {
+        let mut session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        let mut core = session.core(0).unwrap();
+        core.set_hw_breakpoint(0x100).unwrap();
+
+        let mut breakpoints = TransientBreakpoints::default();
+        // The user's breakpoint is reused, and only the new one takes a unit.
+        assert!(breakpoints.set(&mut core, 0x100).unwrap());
+        assert!(breakpoints.set(&mut core, 0x200).unwrap());
+        assert_eq!(breakpoints.units.len(), 1);
+
+        let set = CoreInterface::hw_breakpoints(&mut core).unwrap();
+        assert!(set.contains(&Some(0x100)) && set.contains(&Some(0x200)));
+
+        breakpoints.clear(&mut core).unwrap();
+        let set = CoreInterface::hw_breakpoints(&mut core).unwrap();
+        assert!(set.contains(&Some(0x100)) && !set.contains(&Some(0x200)));
+    }
+
+    #[test]
+    fn transient_breakpoints_need_a_free_unit() {
+        let mut session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        let mut core = session.core(0).unwrap();
+        let units = core.available_breakpoint_units().unwrap() as u64;
+        for unit in 0..units {
+            core.set_hw_breakpoint(0x100 + 2 * unit).unwrap();
+        }
+
+        let mut breakpoints = TransientBreakpoints::default();
+        assert!(!breakpoints.set(&mut core, 0x1000).unwrap());
+        assert!(breakpoints.units.is_empty());
+    }
+}
@@ -6,7 +6,7 @@ use crate::{
         determine_cfa, get_object_reference, get_unwind_info, stack_frame::StackFrameInfo,
         unwind_register, DebugError, DebugInfo, DebugRegisters, StackFrame,
     },
-    Error, MemoryInterface, RegisterValue,
+    memory_mapped_bitfield_register, Error, MemoryInterface, MemoryMappedRegister, RegisterValue,
 };
 use bitfield::bitfield;
 use probe_rs_target::InstructionSet;
@@ -56,6 +56,25 @@ bitfield! {
     pub always_0b01, _: 1,0;
 }
 
+memory_mapped_bitfield_register! {
+    /// FPCCR - Floating-point Context Control Register
+    pub struct Fpccr(u32);
+    0xE000_EF34, "FPCCR",
+    impl From;
+    /// S16 to S31 are saved on exception entry from secure code, and cleared (ARMv8-M only).
+    pub ts, _: 26;
+    /// Lazy state preservation is active: space for the floating point registers was reserved
+    /// on the stack, but they were not saved yet.
+    pub lspact, _: 0;
+}
+
+memory_mapped_bitfield_register! {
+    /// FPCAR - Floating-point Context Address Register
+    pub struct Fpcar(u32);
+    0xE000_EF38, "FPCAR",
+    impl From;
+}
+
 bitfield! {
     #[derive(Copy, Clone)]
     /// xPSR - XPSR register is a combined view of APSR, EPSR and IPSR registers.
@@ -166,23 +185,6 @@ pub(crate) fn exception_details(
             handler_frame.function_name =
                 format!("{} : ERROR: {error}", handler_frame.function_name);
         };
-        // Now we can update the stack pointer also, but
-        // first we have to determine the size of the exception data on the stack.
-        // See <https://developer.arm.com/documentation/ddi0403/d/System-Level-Architecture/System-Level-Programmers--Model/ARMv7-M-exception-model/Exception-entry-behavior?lang=en>
-        let frame_size = if ExcReturn(frame_return_address).use_standard_stackframe() {
-            // This is a standard exception frame.
-            0x20usize
-        } else {
-            // This is an extended frame that includes FPU registers.
-            0x68
-        };
-        // // Now we can update the registers with the new stack pointer.
-        let sp = handler_frame
-            .registers
-            .get_register_mut_by_role(&RegisterRole::StackPointer)?;
-        if let Some(sp_value) = sp.value.as_mut() {
-            sp_value.increment_address(frame_size)?;
-        }
     }
 
     Ok(Some(ExceptionInfo {
@@ -240,28 +242,274 @@ pub(crate) fn raw_exception(
 /// The calling frame registers are a predefined set of registers that are stored on the stack when an exception occurs.
 /// The registers are stored in that list in the order they are defined in the `EXCEPTION_STACK_REGISTERS` array.
 /// This function will read the values of the registers from the stack and update the passed `stackframe_registers` with the new values.
-// TODO: probe-rs does not currently do anything with the floating point registers. When support is added, please note that the list of registers to read is different for cores that have the floating point extension.
 pub(crate) fn calling_frame_registers(
     memory: &mut dyn MemoryInterface,
     stackframe_registers: &crate::debug::DebugRegisters,
     _raw_exception: u32,
 ) -> Result<crate::debug::DebugRegisters, crate::Error> {
-    let exception_context_address: u32 =
-        stackframe_registers.get_register_value_by_role(&RegisterRole::StackPointer)? as u32;
-
-    // Get the values of the registers pushed onto the stack.
-    let mut calling_stack_registers = vec![0u32; EXCEPTION_STACK_REGISTERS.len()];
-    memory.read_32(
-        (exception_context_address).into(),
-        &mut calling_stack_registers,
-    )?;
+    let exc_return = ExcReturn(get_stack_frame_return_address(stackframe_registers)?);
+
+    let frame = ExceptionFrame {
+        address: exception_frame_address(stackframe_registers, exc_return.use_process_stack())?,
+        process_stack: exc_return.use_process_stack(),
+        additional_state_context: false,
+        extended: !exc_return.use_standard_stackframe(),
+        secure_fp_context: false,
+    };
+    frame.calling_frame_registers(memory, stackframe_registers)
+}
+
+/// Returns the address of the exception frame, which is on the process stack when the exception
+/// interrupted thread mode code using it, and on the main stack otherwise.
+///
+/// Exception handlers always use the main stack, so the main stack pointer is the current stack
+/// pointer of the handler.
+pub(crate) fn exception_frame_address(
+    stackframe_registers: &crate::debug::DebugRegisters,
+    process_stack: bool,
+) -> Result<u64, crate::Error> {
+    if process_stack {
+        stackframe_registers
+            .get_register_value_by_role(&RegisterRole::ProcessStackPointer)
+            .map_err(|_| {
+                crate::Error::Register(
+                    "The exception frame is on the process stack, but the value of PSP is unknown."
+                        .to_string(),
+                )
+            })
+    } else {
+        stackframe_registers.get_register_value_by_role(&RegisterRole::StackPointer)
+    }
+}
 
-    let mut calling_frame_registers = stackframe_registers.clone();
-    for (i, register_role) in EXCEPTION_STACK_REGISTERS.iter().enumerate() {
+/// The location and layout of the registers which are saved on the stack on exception entry.
+///
+/// See Section B1.5.6 of the ARMv7-M Architecture Reference Manual, and Section B3.19 of the
+/// ARMv8-M Architecture Reference Manual.
+pub(crate) struct ExceptionFrame {
+    /// The address of the first saved register.
+    pub address: u64,
+    /// The frame is on the process stack, instead of the main stack.
+    pub process_stack: bool,
+    /// The frame starts with the additional state context of the ARMv8-M Security Extension,
+    /// an integrity signature followed by R4 to R11. It is saved when a non-secure exception
+    /// interrupts secure code.
+    pub additional_state_context: bool,
+    /// The frame has space for S0 to S15 and FPSCR.
+    pub extended: bool,
+    /// The extended frame also has space for S16 to S31, which are saved for secure code when
+    /// FPCCR.TS is set.
+    pub secure_fp_context: bool,
+}
+
+impl ExceptionFrame {
+    /// Returns the registers of the interrupted code: the saved ones are read from the frame, and
+    /// the stack pointer is set to its value before the exception.
+    pub(crate) fn calling_frame_registers(
+        &self,
+        memory: &mut dyn MemoryInterface,
+        stackframe_registers: &crate::debug::DebugRegisters,
+    ) -> Result<crate::debug::DebugRegisters, crate::Error> {
+        let mut calling_frame_registers = stackframe_registers.clone();
+        let mut address = self.address;
+
+        if self.additional_state_context {
+            // The integrity signature and a reserved word come before R4 to R11.
+            let mut values = [0u32; 8];
+            memory.read_32(address + 0x08, &mut values)?;
+            for (name, value) in ADDITIONAL_STATE_CONTEXT_REGISTERS.iter().zip(values) {
+                calling_frame_registers
+                    .get_register_mut_by_role(&RegisterRole::Core(name))?
+                    .value = Some(RegisterValue::U32(value));
+            }
+            address += 0x28;
+        }
+
+        // Get the values of the registers pushed onto the stack.
+        let mut calling_stack_registers = vec![0u32; EXCEPTION_STACK_REGISTERS.len()];
+        memory.read_32(address, &mut calling_stack_registers)?;
+        for (i, register_role) in EXCEPTION_STACK_REGISTERS.iter().enumerate() {
+            calling_frame_registers
+                .get_register_mut_by_role(register_role)?
+                .value = Some(RegisterValue::U32(calling_stack_registers[i]));
+        }
+
+        let mut frame_size = 0x20;
+        if self.extended {
+            restore_fp_registers(
+                memory,
+                address + 0x20,
+                self.secure_fp_context,
+                &mut calling_frame_registers,
+            )?;
+            frame_size = if self.secure_fp_context { 0xA8 } else { 0x68 };
+        }
+        // The frame is aligned to 8 bytes, and the padding word is recorded in the saved xPSR.
+        if Xpsr(calling_stack_registers[7]).stack_was_realigned() {
+            frame_size += 4;
+        }
+
+        let stack_pointer = RegisterValue::U32((address + frame_size) as u32);
         calling_frame_registers
-            .get_register_mut_by_role(register_role)?
-            .value = Some(RegisterValue::U32(calling_stack_registers[i]));
+            .get_register_mut_by_role(&RegisterRole::StackPointer)?
+            .value = Some(stack_pointer);
+        let banked_stack_pointer = if self.process_stack {
+            RegisterRole::ProcessStackPointer
+        } else {
+            RegisterRole::MainStackPointer
+        };
+        if let Ok(register) =
+            calling_frame_registers.get_register_mut_by_role(&banked_stack_pointer)
+        {
+            register.value = Some(stack_pointer);
+        }
+
+        Ok(calling_frame_registers)
     }
+}
+
+/// Updates the floating point registers with the values of the interrupted code, which are
+/// stored at `fp_context_address` in an extended exception frame.
+///
+/// With lazy floating point state preservation, the space for the registers is reserved on
+/// exception entry, but the registers are only saved when the handler uses the FPU. Until then,
+/// the registers still hold the values of the interrupted code, and are kept.
+fn restore_fp_registers(
+    memory: &mut dyn MemoryInterface,
+    fp_context_address: u64,
+    secure_fp_context: bool,
+    registers: &mut crate::debug::DebugRegisters,
+) -> Result<(), crate::Error> {
+    let fpccr = Fpccr(memory.read_word_32(Fpccr::get_mmio_address())?);
+    let fpcar = Fpcar(memory.read_word_32(Fpcar::get_mmio_address())?);
+    if fpccr.lspact() && u64::from(fpcar.0 & !0b111) == fp_context_address {
+        return Ok(());
+    }
+
+    // S0 to S15, followed by FPSCR and a reserved word, and S16 to S31 for secure code.
+    let mut values = [0u32; 34];
+    let count = if secure_fp_context { 34 } else { 17 };
+    memory.read_32(fp_context_address, &mut values[..count])?;
+
+    let low_registers = FP_CONTEXT_REGISTERS[..16].iter().zip(&values[..16]);
+    let high_values: &[u32] = if secure_fp_context {
+        &values[18..]
+    } else {
+        &[]
+    };
+    let high_registers = FP_CONTEXT_REGISTERS[16..].iter().zip(high_values);
+    let roles = low_registers
+        .chain(high_registers)
+        .map(|(name, value)| (RegisterRole::Core(name), *value))
+        .chain([(RegisterRole::FloatingPointStatus, values[16])]);
+    for (role, value) in roles {
+        if let Ok(register) = registers.get_register_mut_by_role(&role) {
+            register.value = Some(RegisterValue::U32(value));
+        }
+    }
+
+    Ok(())
+}
 
-    Ok(calling_frame_registers)
+/// The floating point registers saved in an extended exception frame.
+static FP_CONTEXT_REGISTERS: [&str; 32] = [
+    "S0", "S1", "S2", "S3", "S4", "S5", "S6", "S7", "S8", "S9", "S10", "S11", "S12", "S13", "S14",
+    "S15", "S16", "S17", "S18", "S19", "S20", "S21", "S22", "S23", "S24", "S25", "S26", "S27",
+    "S28", "S29", "S30", "S31",
+];
+
+/// The registers saved in the additional state context of the ARMv8-M Security Extension.
+static ADDITIONAL_STATE_CONTEXT_REGISTERS: [&str; 8] =
+    ["R4", "R5", "R6", "R7", "R8", "R9", "R10", "R11"];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        architecture::arm::core::registers::cortex_m::CORTEX_M_WITH_FP_CORE_REGISTERS,
+        debug::DebugRegister, test::MockMemory,
+    };
+
+    fn live_registers() -> DebugRegisters {
+        DebugRegisters(
+            CORTEX_M_WITH_FP_CORE_REGISTERS
+                .all_registers()
+                .map(|core_register| DebugRegister {
+                    core_register,
+                    dwarf_id: None,
+                    value: Some(RegisterValue::U32(0xFFFF_FFFF)),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn fp_registers_are_restored_from_the_stack() {
+        let mut memory = MockMemory::new();
+        memory.add_word_range(0x2000_0020, &(0..17).collect::<Vec<_>>());
+        memory.add_word_range(Fpccr::get_mmio_address(), &[0xC000_0000, 0x2000_0020]);
+
+        let mut registers = live_registers();
+        restore_fp_registers(&mut memory, 0x2000_0020, false, &mut registers).unwrap();
+
+        assert_eq!(
+            registers
+                .get_register_value_by_role(&RegisterRole::Core("S15"))
+                .unwrap(),
+            15
+        );
+        assert_eq!(
+            registers
+                .get_register_value_by_role(&RegisterRole::FloatingPointStatus)
+                .unwrap(),
+            16
+        );
+        assert_eq!(
+            registers
+                .get_register_value_by_role(&RegisterRole::Core("S16"))
+                .unwrap(),
+            0xFFFF_FFFF
+        );
+    }
+
+    #[test]
+    fn exception_frames_with_additional_state_context_are_restored() {
+        let mut memory = MockMemory::new();
+        // Integrity signature, reserved, R4 to R11.
+        memory.add_word_range(0x2000_0000, &[0xFEFA_125B, 0, 4, 5, 6, 7, 8, 9, 10, 11]);
+        // R0 to R3, R12, LR, PC, and xPSR with the realignment bit.
+        memory.add_word_range(0x2000_0028, &[0, 1, 2, 3, 12, 0x101, 0x200, 0x0100_0200]);
+
+        let frame = ExceptionFrame {
+            address: 0x2000_0000,
+            process_stack: true,
+            additional_state_context: true,
+            extended: false,
+            secure_fp_context: false,
+        };
+        let registers = frame
+            .calling_frame_registers(&mut memory, &live_registers())
+            .unwrap();
+
+        let value = |role| registers.get_register_value_by_role(&role).unwrap();
+        assert_eq!(value(RegisterRole::Core("R4")), 4);
+        assert_eq!(value(RegisterRole::Core("R11")), 11);
+        assert_eq!(value(RegisterRole::Core("R12")), 12);
+        assert_eq!(value(RegisterRole::ProgramCounter), 0x200);
+        assert_eq!(value(RegisterRole::StackPointer), 0x2000_004C);
+        assert_eq!(value(RegisterRole::ProcessStackPointer), 0x2000_004C);
+        assert_eq!(value(RegisterRole::MainStackPointer), 0xFFFF_FFFF);
+    }
+
+    #[test]
+    fn lazily_stacked_fp_registers_are_kept() {
+        let mut memory = MockMemory::new();
+        // LSPACT is set, and FPCAR points to the reserved space of the frame.
+        memory.add_word_range(Fpccr::get_mmio_address(), &[0xC000_0001, 0x2000_0020]);
+
+        let mut registers = live_registers();
+        restore_fp_registers(&mut memory, 0x2000_0020, false, &mut registers).unwrap();
+
+        assert_eq!(registers, live_registers());
+    }
 }
@@ -6,7 +6,7 @@ use crate::{
 use bitfield::bitfield;
 
 use super::{
-    armv6m_armv7m_shared::{Xpsr, EXCEPTION_STACK_REGISTERS},
+    armv6m_armv7m_shared::{exception_frame_address, ExceptionFrame, Fpccr, Xpsr},
     ExceptionInfo, ExceptionInterface,
 };
 
@@ -394,50 +394,35 @@ impl ExceptionInterface for ArmV8MExceptionHandler {
         stackframe_registers: &DebugRegisters,
         _raw_exception: u32,
     ) -> Result<DebugRegisters, DebugError> {
-        let mut calling_stack_registers = vec![0u32; EXCEPTION_STACK_REGISTERS.len()];
         let stack_frame_return_address: u32 = get_stack_frame_return_address(stackframe_registers)?;
         let exc_return = ExcReturn(stack_frame_return_address);
 
-        let sp_value = if exc_return.is_exception_flag() == 0xFF {
-            let stack_info = (
-                exc_return.use_secure_stack(),
-                exc_return.stack_pointer_selection(),
-            );
-
-            let sp_reg_id = match stack_info {
-                (false, false) => 0b00011000, // non-secure, main stack pointer
-                (false, true) => 0b00011001,  // non-secure, process stack pointer
-                (true, false) => 0b00011010,  // secure, main stack pointer
-                (true, true) => 0b00011011,   // secure, process stack pointer
-            };
-            stackframe_registers
-                .get_register(sp_reg_id.into())
-                .ok_or_else(|| {
-                    Error::Register(
-                        "No Stack Pointer register. Please report this as a bug.".to_string(),
-                    )
-                })?
-                .value
-                .ok_or_else(|| {
-                    Error::Register(
-                        "No value for Stack Pointer register. Please report this as a bug."
-                            .to_string(),
-                    )
-                })?
-                .try_into()?
-        } else {
-            stackframe_registers
-                .get_register_value_by_role(&crate::core::RegisterRole::StackPointer)?
-        };
-
-        memory_interface.read_32(sp_value, &mut calling_stack_registers)?;
-        let mut calling_frame_registers = stackframe_registers.clone();
-        for (i, register_role) in EXCEPTION_STACK_REGISTERS.iter().enumerate() {
-            calling_frame_registers
-                .get_register_mut_by_role(register_role)?
-                .value = Some(RegisterValue::U32(calling_stack_registers[i]));
+        // The core registers only contain the stack pointers of the security state the core is
+        // halted in, so the frame can only be found if the handler runs in the state of the
+        // interrupted code. Without the Security Extension, both bits are always set.
+        if exc_return.use_secure_stack() != exc_return.exception_secure() {
+            let state = |secure| if secure { "secure" } else { "non-secure" };
+            return Err(DebugError::Other(format!(
+                "The {} exception interrupted {} code, whose stack is not accessible.",
+                state(exc_return.exception_secure()),
+                state(exc_return.use_secure_stack()),
+            )));
         }
-        Ok(calling_frame_registers)
+
+        let process_stack = exc_return.stack_pointer_selection();
+        let extended = !exc_return.use_standard_stackframe();
+        let secure_fp_context = extended
+            && exc_return.use_secure_stack()
+            && Fpccr(memory_interface.read_word_32(Fpccr::get_mmio_address())?).ts();
+
+        let frame = ExceptionFrame {
+            address: exception_frame_address(stackframe_registers, process_stack)?,
+            process_stack,
+            additional_state_context: !exc_return.use_default_register_stacking(),
+            extended,
+            secure_fp_context,
+        };
+        Ok(frame.calling_frame_registers(memory_interface, stackframe_registers)?)
     }
 
     fn raw_exception(&self, stackframe_registers: &DebugRegisters) -> Result<u32, DebugError> {
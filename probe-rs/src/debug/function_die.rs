@@ -58,8 +58,8 @@ impl<'abbrev, 'unit> FunctionDie<'abbrev, 'unit> {
         };
 
         //Validate the function DIE ranges, and confirm this DIE applies to the requested address.
-        let mut gimli_ranges = debug_info
-            .dwarf
+        let mut gimli_ranges = unit_info
+            .dwarf(debug_info)
             .die_ranges(&unit_info.unit, &function_die)?;
         let mut die_ranges = Vec::new();
         while let Ok(Some(gimli_range)) = gimli_ranges.next() {
@@ -140,12 +140,11 @@ impl<'abbrev, 'unit> FunctionDie<'abbrev, 'unit> {
             tracing::debug!("DW_AT_name attribute not found, unable to retrieve function name");
             return None;
         };
-        let value = fn_name_attr.value();
-        let gimli::AttributeValue::DebugStrRef(fn_name_ref) = value else {
-            tracing::debug!("Unexpected attribute value for DW_AT_name: {:?}", value);
-            return None;
-        };
-        match debug_info.dwarf.string(fn_name_ref) {
+        match self
+            .unit_info
+            .dwarf(debug_info)
+            .attr_string(&self.unit_info.unit, fn_name_attr.value())
+        {
             Ok(fn_name_raw) => Some(String::from_utf8_lossy(&fn_name_raw).to_string()),
             Err(error) => {
                 tracing::debug!("No value for DW_AT_name: {:?}: error", error);
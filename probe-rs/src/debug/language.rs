@@ -66,6 +66,16 @@ pub trait ProgrammingLanguage {
         false
     }
 
+    /// Formats the value of a variable from its resolved children, if the language has a more
+    /// readable representation than the one stored in the variable.
+    fn format_value_from_children(
+        &self,
+        _variable: &Variable,
+        _variable_cache: &VariableCache,
+    ) -> Option<String> {
+        None
+    }
+
     fn modified_type_name(&self, modifier: &Modifier, name: &str) -> String {
         match modifier {
             Modifier::Const => format!("const {}", name),
@@ -5,7 +5,7 @@ use crate::{
             ProgrammingLanguage,
         },
         DebugError, Variable, VariableCache, VariableLocation, VariableName, VariableType,
-        VariableValue,
+        VariableValue, VariantRole,
     },
     MemoryInterface,
 };
@@ -60,6 +60,12 @@ impl ProgrammingLanguage for Rust {
             VariableType::Struct(name) if name == "&str" => {
                 String::get_value(variable, memory, variable_cache).into()
             }
+            VariableType::Struct(name) if is_slice(name) => {
+                self.read_slice(variable, memory, variable_cache)
+            }
+            VariableType::Struct(name) if name == "String" => {
+                read_string(variable, memory, variable_cache)
+            }
             _other => VariableValue::Empty,
         }
     }
@@ -121,10 +127,389 @@ impl ProgrammingLanguage for Rust {
 
     fn auto_resolve_children(&self, name: &str) -> bool {
         name.starts_with("&str")
+            || is_slice(name)
             || name.starts_with("Option")
             || name.starts_with("Some")
             || name.starts_with("Result")
             || name.starts_with("Ok")
             || name.starts_with("Err")
+            // The layers of a `String`, down to the pointer to its bytes.
+            || name == "String"
+            || name.starts_with("Vec<u8")
+            || name.starts_with("RawVec")
+            || name == "Unique<u8>"
+            || name == "NonNull<u8>"
+    }
+
+    fn format_value_from_children(
+        &self,
+        variable: &Variable,
+        variable_cache: &VariableCache,
+    ) -> Option<String> {
+        // An enum shows its active variant, e.g. `Some(5)`, instead of its type and address.
+        if matches!(variable.role, VariantRole::VariantPart(_)) {
+            let variant = variable_cache.get_children(variable.variable_key).next()?;
+            return format_variant(variant, variable_cache);
+        }
+
+        let parent = variable_cache.get_variable_by_key(variable.parent_key)?;
+        if matches!(parent.role, VariantRole::VariantPart(_)) {
+            return format_variant(variable, variable_cache);
+        }
+
+        None
+    }
+}
+
+impl Rust {
+    /// Reads the elements of a slice of a primitive type, e.g. `&[u8]`.
+    fn read_slice(
+        &self,
+        variable: &Variable,
+        memory: &mut dyn MemoryInterface,
+        variable_cache: &VariableCache,
+    ) -> VariableValue {
+        let child = |name: &str| {
+            variable_cache
+                .get_children(variable.variable_key)
+                .find(|child| matches!(&child.name, VariableName::Named(child) if child == name))
+        };
+
+        let Some(length) = child("length")
+            .and_then(|length| length.to_string(variable_cache).parse::<usize>().ok())
+        else {
+            return VariableValue::Empty;
+        };
+        // The pointee of the data pointer is the first element.
+        let Some(first) = child("data_ptr")
+            .and_then(|data_ptr| variable_cache.get_children(data_ptr.variable_key).next())
+        else {
+            return VariableValue::Empty;
+        };
+        let (VariableLocation::Address(address), Some(size), VariableType::Base(_)) = (
+            &first.memory_location,
+            first.byte_size,
+            first.type_name.inner(),
+        ) else {
+            return VariableValue::Empty;
+        };
+
+        let mut elements = Vec::new();
+        for index in 0..length.min(MAX_SLICE_ELEMENTS) {
+            let mut element = first.clone();
+            element.memory_location = VariableLocation::Address(address + index as u64 * size);
+            match self.read_variable_value(&element, memory, variable_cache) {
+                VariableValue::Valid(value) => elements.push(value),
+                other => return other,
+            }
+        }
+        if length > MAX_SLICE_ELEMENTS {
+            elements.push("..".to_string());
+        }
+
+        VariableValue::Valid(format!("[{}]", elements.join(", ")))
+    }
+}
+
+/// The number of elements of a slice which are shown in its value.
+const MAX_SLICE_ELEMENTS: usize = 32;
+
+/// Returns if the type is a slice, e.g. `&[u8]` or `&mut [u8]`.
+fn is_slice(name: &str) -> bool {
+    name.starts_with("&[") || name.starts_with("&mut [")
+}
+
+/// Formats an enum variant with its fields, e.g. `Some(5)` or `Move { x: 1, y: 2 }`.
+fn format_variant(variant: &Variable, variable_cache: &VariableCache) -> Option<String> {
+    let VariableName::Named(name) = &variant.name else {
+        return None;
+    };
+
+    let fields: Vec<_> = variable_cache.get_children(variant.variable_key).collect();
+    if fields.is_empty() {
+        return Some(name.clone());
+    }
+
+    let value = if fields.iter().all(|field| field.is_indexed()) {
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|field| format_field(field, variable_cache))
+            .collect();
+        format!("{name}({})", fields.join(", "))
+    } else {
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|field| format!("{}: {}", field.name, format_field(field, variable_cache)))
+            .collect();
+        format!("{name} {{ {} }}", fields.join(", "))
+    };
+
+    Some(value)
+}
+
+/// Formats the value of a field, with strings in quotes like in Rust source code.
+fn format_field(field: &Variable, variable_cache: &VariableCache) -> String {
+    let value = field.to_string(variable_cache);
+    match field.type_name.inner() {
+        VariableType::Struct(name) if name == "&str" || name == "String" => format!("{value:?}"),
+        VariableType::Base(name) if name == "char" => format!("{value:?}").replace('"', "'"),
+        _ => value,
+    }
+}
+
+/// Reads a `String`, by finding the length and the pointer to the bytes in its `Vec<u8>`.
+fn read_string(
+    variable: &Variable,
+    memory: &mut dyn MemoryInterface,
+    variable_cache: &VariableCache,
+) -> VariableValue {
+    let Some(length) = find_descendant(
+        variable,
+        variable_cache,
+        &|child| matches!(&child.name, VariableName::Named(name) if name == "len"),
+    )
+    .and_then(|length| length.to_string(variable_cache).parse::<usize>().ok()) else {
+        return VariableValue::Empty;
+    };
+    if length == 0 {
+        return VariableValue::Valid(String::new());
+    }
+
+    let Some(VariableLocation::Address(address)) =
+        find_descendant(variable, variable_cache, &|child| {
+            matches!(child.type_name.inner(), VariableType::Pointer(_))
+        })
+        .and_then(|pointer| variable_cache.get_children(pointer.variable_key).next())
+        .map(|bytes| bytes.memory_location.clone())
+    else {
+        return VariableValue::Empty;
+    };
+
+    // Limit the length like for `&str`, broken debug info could make us read a lot of memory.
+    let mut bytes = vec![0; length.min(200)];
+    match memory.read(address, &mut bytes) {
+        Ok(()) => VariableValue::Valid(String::from_utf8_lossy(&bytes).into_owned()),
+        Err(error) => VariableValue::Error(format!("{error:?}")),
+    }
+}
+
+/// Returns the first descendant of the variable, depth first, which matches the predicate.
+fn find_descendant<'a>(
+    variable: &Variable,
+    variable_cache: &'a VariableCache,
+    predicate: &dyn Fn(&Variable) -> bool,
+) -> Option<&'a Variable> {
+    variable_cache
+        .get_children(variable.variable_key)
+        .find_map(|child| {
+            if predicate(child) {
+                Some(child)
+            } else {
+                find_descendant(child, variable_cache, predicate)
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{debug::ObjectRef, test::MockMemory};
+
+    /// Adds a variable called `name` of type `type_name` to the cache.
+    fn add(
+        cache: &mut VariableCache,
+        parent: ObjectRef,
+        name: &str,
+        type_name: VariableType,
+        configure: impl FnOnce(&mut Variable),
+    ) -> Variable {
+        let mut variable = Variable::new(None);
+        variable.name = VariableName::Named(name.to_string());
+        variable.type_name = type_name;
+        configure(&mut variable);
+        cache.add_variable(parent, &mut variable).unwrap();
+        variable
+    }
+
+    /// Adds a `&[u16]` with `length` elements at `address`.
+    fn add_slice(cache: &mut VariableCache, address: u64, length: usize) -> Variable {
+        let root = cache.root_variable().variable_key;
+        let slice = add(
+            cache,
+            root,
+            "slice",
+            VariableType::Struct("&[u16]".to_string()),
+            |_| {},
+        );
+        let data_ptr = add(
+            cache,
+            slice.variable_key,
+            "data_ptr",
+            VariableType::Pointer(Some("*const u16".to_string())),
+            |_| {},
+        );
+        add(
+            cache,
+            data_ptr.variable_key,
+            "*data_ptr",
+            VariableType::Base("u16".to_string()),
+            |variable| {
+                variable.memory_location = VariableLocation::Address(address);
+                variable.byte_size = Some(2);
+            },
+        );
+        add(
+            cache,
+            slice.variable_key,
+            "length",
+            VariableType::Base("usize".to_string()),
+            |variable| variable.set_value(VariableValue::Valid(length.to_string())),
+        );
+        slice
+    }
+
+    #[test]
+    fn slice_shows_its_elements() {
+        let mut memory = MockMemory::new();
+        memory.add_range(0x2000_0000, (0..40u16).flat_map(u16::to_le_bytes).collect());
+
+        let mut cache = VariableCache::new_static_cache();
+        let slice = add_slice(&mut cache, 0x2000_0000, 3);
+        assert_eq!(
+            Rust.read_variable_value(&slice, &mut memory, &cache),
+            VariableValue::Valid("[0, 1, 2]".to_string())
+        );
+
+        let long_slice = add_slice(&mut cache, 0x2000_0000, 40);
+        let expected = (0..MAX_SLICE_ELEMENTS)
+            .map(|element| element.to_string())
+            .chain([String::from("..")])
+            .collect::<Vec<_>>()
+            .join(", ");
+        assert_eq!(
+            Rust.read_variable_value(&long_slice, &mut memory, &cache),
+            VariableValue::Valid(format!("[{expected}]"))
+        );
+    }
+
+    /// Adds a `String` of `length` bytes at `address`, with the layers of the standard library.
+    fn add_string(cache: &mut VariableCache, address: u64, length: usize) -> Variable {
+        let root = cache.root_variable().variable_key;
+        let string = add(
+            cache,
+            root,
+            "string",
+            VariableType::Struct("String".to_string()),
+            |_| {},
+        );
+        let vec = add(
+            cache,
+            string.variable_key,
+            "vec",
+            VariableType::Struct("Vec<u8, alloc::alloc::Global>".to_string()),
+            |_| {},
+        );
+        let buf = add(
+            cache,
+            vec.variable_key,
+            "buf",
+            VariableType::Struct("RawVec<u8, alloc::alloc::Global>".to_string()),
+            |_| {},
+        );
+        let pointer = add(
+            cache,
+            buf.variable_key,
+            "pointer",
+            VariableType::Pointer(Some("*const u8".to_string())),
+            |_| {},
+        );
+        add(
+            cache,
+            pointer.variable_key,
+            "*pointer",
+            VariableType::Base("u8".to_string()),
+            |variable| variable.memory_location = VariableLocation::Address(address),
+        );
+        add(
+            cache,
+            vec.variable_key,
+            "len",
+            VariableType::Base("usize".to_string()),
+            |variable| variable.set_value(VariableValue::Valid(length.to_string())),
+        );
+        string
+    }
+
+    #[test]
+    fn string_is_read_through_its_vec() {
+        let mut memory = MockMemory::new();
+        memory.add_range(0x2000_0100, b"probe-rs".to_vec());
+
+        let mut cache = VariableCache::new_static_cache();
+        let string = add_string(&mut cache, 0x2000_0100, 5);
+        assert_eq!(
+            Rust.read_variable_value(&string, &mut memory, &cache),
+            VariableValue::Valid("probe".to_string())
+        );
+
+        let empty = add_string(&mut cache, 0x2000_0100, 0);
+        assert_eq!(
+            Rust.read_variable_value(&empty, &mut memory, &cache),
+            VariableValue::Valid(String::new())
+        );
+    }
+
+    #[test]
+    fn enum_shows_its_active_variant() {
+        let mut cache = VariableCache::new_static_cache();
+        let root = cache.root_variable().variable_key;
+
+        let option = add(
+            &mut cache,
+            root,
+            "option",
+            VariableType::Struct("Option<char>".to_string()),
+            |variable| variable.role = VariantRole::VariantPart(1),
+        );
+        let some = add(
+            &mut cache,
+            option.variable_key,
+            "Some",
+            VariableType::Struct("Some".to_string()),
+            |variable| variable.role = VariantRole::Variant(1),
+        );
+        add(
+            &mut cache,
+            some.variable_key,
+            "__0",
+            VariableType::Base("char".to_string()),
+            |variable| variable.set_value(VariableValue::Valid("a".to_string())),
+        );
+        assert_eq!(option.to_string(&cache), "Some('a')");
+
+        let command = add(
+            &mut cache,
+            root,
+            "command",
+            VariableType::Struct("Command".to_string()),
+            |variable| variable.role = VariantRole::VariantPart(0),
+        );
+        let r#move = add(
+            &mut cache,
+            command.variable_key,
+            "Move",
+            VariableType::Struct("Move".to_string()),
+            |variable| variable.role = VariantRole::Variant(0),
+        );
+        for (field, value) in [("x", "1"), ("y", "-2")] {
+            add(
+                &mut cache,
+                r#move.variable_key,
+                field,
+                VariableType::Base("i32".to_string()),
+                |variable| variable.set_value(VariableValue::Valid(value.to_string())),
+            );
+        }
+        assert_eq!(command.to_string(&cache), "Move { x: 1, y: -2 }");
     }
 }
@@ -11,6 +11,10 @@ pub mod debug_step;
 pub mod function_die;
 /// Programming languages
 pub(crate) mod language;
+/// Pretty-printers which show the elements of collection types instead of their storage.
+pub mod pretty_printer;
+/// Unwinding of functions without call frame information.
+pub(crate) mod prologue;
 /// Target Register definitions, expanded from [`crate::core::registers::CoreRegister`] to include unwind specific information.
 pub mod registers;
 /// The source statement information used while identifying haltpoints for debug stepping and breakpoints.
@@ -27,9 +31,9 @@ pub mod variable_cache;
 pub(crate) mod exception_handling;
 
 pub use self::{
-    debug_info::*, debug_step::SteppingMode, registers::*, source_instructions::SourceLocation,
-    source_instructions::VerifiedBreakpoint, stack_frame::StackFrame, variable::*,
-    variable_cache::VariableCache,
+    debug_info::*, debug_step::SteppingMode, pretty_printer::PrettyPrinters, registers::*,
+    source_instructions::SourceLocation, source_instructions::VerifiedBreakpoint,
+    stack_frame::StackFrame, variable::*, variable_cache::VariableCache,
 };
 use crate::{core::Core, MemoryInterface};
 
@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Variable, VariableCache, VariableType};
+
+/// Describes how the logical elements of a collection type are stored, so the debugger can show
+/// them instead of the backing storage.
+///
+/// Collections like `heapless::Vec` store their elements in an array of `MaybeUninit<T>`, of
+/// which only some entries are initialized. A printer names the fields holding the number of
+/// elements and the array, and how to get from an array entry to the element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionPrinter {
+    /// The start of the names of the matching types, e.g. `Vec<` for `heapless::Vec<T, N>`.
+    ///
+    /// Types with the same name, but a different layout, are not affected, because the fields
+    /// of the printer are not found in them.
+    pub type_name_prefix: String,
+    /// The path of fields from the collection to the array with the elements.
+    pub elements: Vec<String>,
+    /// The path of fields from an entry of the array to the element, e.g. through
+    /// `MaybeUninit<T>` and `ManuallyDrop<T>`.
+    pub element: Vec<String>,
+    /// Which entries of the array hold elements.
+    pub occupancy: Occupancy,
+    /// How the elements are shown as value of the collection.
+    pub kind: CollectionKind,
+}
+
+/// Which entries of the array of a collection hold elements.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Occupancy {
+    /// The first entries, with the number of them in the field at the path.
+    Prefix {
+        /// The path of the field with the number of elements.
+        length: Vec<String>,
+    },
+    /// The entries of a ring buffer, from `front` up to `back`, which wrap around the end.
+    Ring {
+        /// The path of the field with the index of the first element.
+        front: Vec<String>,
+        /// The path of the field with the index behind the last element.
+        back: Vec<String>,
+        /// The path of the field which tells a full buffer from an empty one, where `front`
+        /// equals `back`.
+        full: Vec<String>,
+    },
+}
+
+/// How the elements are shown as value of the collection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollectionKind {
+    /// A list of elements, e.g. `[1, 2, 3]`.
+    List,
+    /// The elements are UTF-8 bytes, e.g. `"text"`.
+    Text,
+    /// The elements are entries of a map, e.g. `{1: 2}`.
+    Map {
+        /// The path of fields from an element to its key.
+        key: Vec<String>,
+        /// The path of fields from an element to its value.
+        value: Vec<String>,
+    },
+}
+
+/// The registry of [`CollectionPrinter`]s, which can be extended with printers for other
+/// collection types.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrettyPrinters {
+    printers: Vec<CollectionPrinter>,
+}
+
+impl PrettyPrinters {
+    /// Adds a printer. It takes precedence over the existing printers for the same types.
+    pub fn register(&mut self, printer: CollectionPrinter) {
+        self.printers.insert(0, printer);
+    }
+
+    /// Returns the printers which may match the type of the variable.
+    pub(crate) fn find<'a>(
+        &'a self,
+        variable: &'a Variable,
+    ) -> impl Iterator<Item = &'a CollectionPrinter> + 'a {
+        let name = match variable.type_name.inner() {
+            VariableType::Struct(name) => name.as_str(),
+            _ => "",
+        };
+
+        self.printers
+            .iter()
+            .filter(move |printer| !name.is_empty() && name.starts_with(&printer.type_name_prefix))
+    }
+}
+
+impl Default for PrettyPrinters {
+    /// Returns the printers for the collections covered by the test firmware, which is only
+    /// `heapless::Vec` for now. Printers for other types have to be registered.
+    fn default() -> Self {
+        let path = |fields: &[&str]| fields.iter().map(|field| field.to_string()).collect();
+
+        let printers = vec![
+            // heapless 0.7 and 0.8
+            CollectionPrinter {
+                type_name_prefix: "Vec<".to_string(),
+                elements: path(&["buffer"]),
+                element: path(&["value", "value"]),
+                occupancy: Occupancy::Prefix {
+                    length: path(&["len"]),
+                },
+                kind: CollectionKind::List,
+            },
+        ];
+
+        Self { printers }
+    }
+}
+
+impl Occupancy {
+    /// Returns the indices of the array entries which hold the elements, in order.
+    ///
+    /// `field` returns the value of the field at a path, and `capacity` is the size of the array.
+    pub(crate) fn indices(
+        &self,
+        field: &mut dyn FnMut(&[String]) -> Option<String>,
+        capacity: usize,
+    ) -> Option<Vec<usize>> {
+        match self {
+            Occupancy::Prefix { length } => {
+                let length = field(length)?.parse::<usize>().ok()?;
+                (length <= capacity).then(|| (0..length).collect())
+            }
+            Occupancy::Ring { front, back, full } => {
+                let front = field(front)?.parse::<usize>().ok()?;
+                let back = field(back)?.parse::<usize>().ok()?;
+                let full = field(full)? == "true";
+                if capacity == 0 || front >= capacity || back >= capacity {
+                    return None;
+                }
+
+                let length = if full {
+                    capacity
+                } else {
+                    (back + capacity - front) % capacity
+                };
+                Some(
+                    (0..length)
+                        .map(|index| (front + index) % capacity)
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+impl CollectionKind {
+    /// Formats the elements as value of the collection.
+    pub(crate) fn format(&self, elements: &[Variable], variable_cache: &VariableCache) -> String {
+        match self {
+            CollectionKind::List => {
+                let elements: Vec<_> = elements
+                    .iter()
+                    .map(|element| element.to_string(variable_cache))
+                    .collect();
+                format!("[{}]", elements.join(", "))
+            }
+            CollectionKind::Text => {
+                let bytes: Vec<u8> = elements
+                    .iter()
+                    .filter_map(|element| element.to_string(variable_cache).parse().ok())
+                    .collect();
+                format!("{:?}", String::from_utf8_lossy(&bytes))
+            }
+            CollectionKind::Map { .. } => {
+                let entries: Vec<_> = elements
+                    .iter()
+                    .map(|element| {
+                        format!("{}: {}", element.name, element.to_string(variable_cache))
+                    })
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ring_buffers_wrap_around() {
+        let ring = Occupancy::Ring {
+            front: vec!["front".to_string()],
+            back: vec!["back".to_string()],
+            full: vec!["full".to_string()],
+        };
+        let fields = |front: &'static str, back: &'static str, full: &'static str| {
+            move |path: &[String]| {
+                Some(
+                    match path[0].as_str() {
+                        "front" => front,
+                        "back" => back,
+                        _ => full,
+                    }
+                    .to_string(),
+                )
+            }
+        };
+
+        assert_eq!(
+            ring.indices(&mut fields("3", "1", "false"), 4),
+            Some(vec![3, 0])
+        );
+        assert_eq!(
+            ring.indices(&mut fields("2", "2", "true"), 4),
+            Some(vec![2, 3, 0, 1])
+        );
+        assert_eq!(
+            ring.indices(&mut fields("2", "2", "false"), 4),
+            Some(vec![])
+        );
+        assert_eq!(ring.indices(&mut fields("5", "2", "false"), 4), None);
+
+        let prefix = Occupancy::Prefix {
+            length: vec!["len".to_string()],
+        };
+        assert_eq!(
+            prefix.indices(&mut |_| Some("2".to_string()), 4),
+            Some(vec![0, 1])
+        );
+        assert_eq!(prefix.indices(&mut |_| Some("5".to_string()), 4), None);
+    }
+
+    #[test]
+    fn registered_printers_take_precedence() {
+        let mut printers = PrettyPrinters::default();
+        let mut variable = Variable::new(None);
+
+        variable.type_name = VariableType::Struct("Vec<i8, 10>".to_string());
+        assert_eq!(printers.find(&variable).count(), 1);
+
+        variable.type_name = VariableType::Struct("String<8>".to_string());
+        assert_eq!(printers.find(&variable).count(), 0);
+
+        let printer = CollectionPrinter {
+            type_name_prefix: "String<".to_string(),
+            elements: vec!["vec".to_string(), "buffer".to_string()],
+            element: vec!["value".to_string(), "value".to_string()],
+            occupancy: Occupancy::Prefix {
+                length: vec!["vec".to_string(), "len".to_string()],
+            },
+            kind: CollectionKind::Text,
+        };
+        printers.register(printer.clone());
+        assert_eq!(printers.find(&variable).next(), Some(&printer));
+    }
+}
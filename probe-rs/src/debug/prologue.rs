@@ -0,0 +1,237 @@
+//! Unwinding of functions without call frame information, by decoding the instructions of their
+//! prologue.
+//!
+//! Optimized code usually doesn't keep a frame pointer, so without `.debug_frame` entries the
+//! stack frame of a function can only be found by following the stack pointer adjustments and
+//! register saves the function did before the current instruction.
+
+use crate::{core::RegisterRole, Error, MemoryInterface, RegisterValue};
+
+use super::DebugRegisters;
+
+/// The number of bytes at the start of a function which are searched for prologue instructions.
+const MAX_PROLOGUE_SIZE: u64 = 128;
+
+/// The stack frame set up by the instructions of a prologue.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct PrologueFrame {
+    /// The number of bytes allocated on the stack.
+    pub stack_size: u64,
+    /// Where the return address was saved, relative to the stack pointer on function entry.
+    pub return_address_offset: Option<i64>,
+    /// Where the frame pointer was saved, relative to the stack pointer on function entry.
+    pub frame_pointer_offset: Option<i64>,
+}
+
+/// Decodes the RV32 and RV32C instructions in `code`, which are the instructions of a function
+/// from its start up to, but excluding, the current instruction.
+///
+/// The prologue ends at the first jump or branch, or at the first instruction which frees stack
+/// space again.
+pub(crate) fn analyze_riscv_prologue(code: &[u8]) -> PrologueFrame {
+    const SP: u32 = 2;
+    const RA: u32 = 1;
+    const S0: u32 = 8;
+
+    let mut frame = PrologueFrame::default();
+    let save = |frame: &mut PrologueFrame, register: u32, offset: i64| {
+        let offset = offset - frame.stack_size as i64;
+        match register {
+            RA => frame.return_address_offset = Some(offset),
+            S0 => frame.frame_pointer_offset = Some(offset),
+            _ => {}
+        }
+    };
+
+    let mut position = 0;
+    while position + 2 <= code.len() {
+        let low = u16::from_le_bytes([code[position], code[position + 1]]) as u32;
+
+        let stack_adjustment = if low & 0b11 != 0b11 {
+            // A compressed instruction.
+            position += 2;
+            let funct3 = low >> 13;
+            let rd = (low >> 7) & 0x1F;
+            match (low & 0b11, funct3) {
+                // C.ADDI
+                (0b01, 0b000) if rd == SP => {
+                    sign_extend((low >> 12 & 1) << 5 | (low >> 2 & 0x1F), 6)
+                }
+                // C.ADDI16SP
+                (0b01, 0b011) if rd == SP => sign_extend(
+                    (low >> 12 & 1) << 9
+                        | (low >> 6 & 1) << 4
+                        | (low >> 5 & 1) << 6
+                        | (low >> 3 & 0b11) << 7
+                        | (low >> 2 & 1) << 5,
+                    10,
+                ),
+                // C.JAL, C.J, C.BEQZ, C.BNEZ
+                (0b01, 0b001 | 0b101 | 0b110 | 0b111) => break,
+                // C.JR, C.JALR
+                (0b10, 0b100) if low >> 2 & 0x1F == 0 && rd != 0 => break,
+                // C.SWSP
+                (0b10, 0b110) => {
+                    let offset = (low >> 9 & 0b1111) << 2 | (low >> 7 & 0b11) << 6;
+                    save(&mut frame, low >> 2 & 0x1F, offset as i64);
+                    0
+                }
+                _ => 0,
+            }
+        } else {
+            if position + 4 > code.len() {
+                break;
+            }
+            let instruction = u32::from_le_bytes([
+                code[position],
+                code[position + 1],
+                code[position + 2],
+                code[position + 3],
+            ]);
+            position += 4;
+            let funct3 = (instruction >> 12) & 0b111;
+            let rd = (instruction >> 7) & 0x1F;
+            let rs1 = (instruction >> 15) & 0x1F;
+            match (instruction & 0x7F, funct3) {
+                // ADDI
+                (0x13, 0b000) if rd == SP && rs1 == SP => ((instruction as i32) >> 20) as i64,
+                // SW
+                (0x23, 0b010) if rs1 == SP => {
+                    let offset = ((instruction as i32) >> 25) << 5 | rd as i32;
+                    save(&mut frame, (instruction >> 20) & 0x1F, offset as i64);
+                    0
+                }
+                // BRANCH, JALR, JAL
+                (0x63 | 0x67 | 0x6F, _) => break,
+                _ => 0,
+            }
+        };
+
+        if stack_adjustment > 0 {
+            // This is part of an epilogue.
+            break;
+        }
+        frame.stack_size += stack_adjustment.unsigned_abs();
+    }
+
+    frame
+}
+
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    let shift = 32 - bits;
+    (((value << shift) as i32) >> shift) as i64
+}
+
+/// Unwinds the stack pointer, return address and frame pointer of a RISC-V function which starts
+/// at `function_start`, by analyzing its prologue.
+///
+/// Registers which were not saved yet are kept, e.g. the return address of a leaf function.
+pub(crate) fn unwind_riscv_prologue(
+    memory: &mut dyn MemoryInterface,
+    function_start: u64,
+    program_counter: u64,
+    registers: &mut DebugRegisters,
+) -> Result<(), Error> {
+    let length = program_counter
+        .checked_sub(function_start)
+        .ok_or_else(|| Error::Other("The program counter is before the function.".to_string()))?
+        .min(MAX_PROLOGUE_SIZE);
+    let mut code = vec![0; length as usize];
+    memory.read(function_start, &mut code)?;
+
+    let frame = analyze_riscv_prologue(&code);
+    tracing::trace!("UNWIND: Prologue of function at {function_start:#010x}: {frame:?}");
+
+    let stack_pointer = registers.get_register_value_by_role(&RegisterRole::StackPointer)?;
+    let canonical_frame_address = stack_pointer + frame.stack_size;
+
+    let saved_registers = [
+        (RegisterRole::ReturnAddress, frame.return_address_offset),
+        (RegisterRole::FramePointer, frame.frame_pointer_offset),
+    ];
+    for (role, offset) in saved_registers {
+        if let Some(offset) = offset {
+            let value = memory.read_word_32(canonical_frame_address.wrapping_add_signed(offset))?;
+            registers.get_register_mut_by_role(&role)?.value = Some(RegisterValue::U32(value));
+        }
+    }
+    registers
+        .get_register_mut_by_role(&RegisterRole::StackPointer)?
+        .value = Some(RegisterValue::U32(canonical_frame_address as u32));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn code(instructions: &[u32]) -> Vec<u8> {
+        instructions
+            .iter()
+            .flat_map(|instruction| {
+                if instruction & 0b11 == 0b11 {
+                    instruction.to_le_bytes().to_vec()
+                } else {
+                    (*instruction as u16).to_le_bytes().to_vec()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn uncompressed_prologue() {
+        let code = code(&[
+            0xfe010113, // addi sp, sp, -32
+            0x00112e23, // sw ra, 28(sp)
+            0x00812c23, // sw s0, 24(sp)
+            0x02010413, // addi s0, sp, 32
+            0x008000ef, // jal ra, 8
+            0x01c12083, // lw ra, 28(sp)
+        ]);
+
+        assert_eq!(
+            analyze_riscv_prologue(&code),
+            PrologueFrame {
+                stack_size: 32,
+                return_address_offset: Some(-4),
+                frame_pointer_offset: Some(-8),
+            }
+        );
+    }
+
+    #[test]
+    fn compressed_prologue() {
+        let code = code(&[
+            0x7139, // addi sp, sp, -64 (c.addi16sp)
+            0xde06, // sw ra, 60(sp) (c.swsp)
+            0x1141, // addi sp, sp, -16 (c.addi)
+            0xc422, // sw s0, 8(sp) (c.swsp)
+        ]);
+
+        assert_eq!(
+            analyze_riscv_prologue(&code),
+            PrologueFrame {
+                stack_size: 80,
+                return_address_offset: Some(-4),
+                frame_pointer_offset: Some(-72),
+            }
+        );
+    }
+
+    #[test]
+    fn prologue_ends_at_the_current_instruction() {
+        let code = code(&[
+            0xfe010113, // addi sp, sp, -32
+        ]);
+
+        assert_eq!(
+            analyze_riscv_prologue(&code),
+            PrologueFrame {
+                stack_size: 32,
+                return_address_offset: None,
+                frame_pointer_offset: None,
+            }
+        );
+    }
+}
@@ -103,25 +103,65 @@ impl DebugRegisters {
                     } else {
                         None
                     },
-                    value: match core.read_core_reg(core_register.id()) {
+                    value: None,
+                });
+            } else {
+                tracing::trace!(
+                    "Unwind will use the default rule for this register : {:?}",
+                    core_register
+                );
+            }
+        }
+
+        // The floating point registers don't have DWARF register numbers in the numbering above,
+        // so they are only unwound with their default rules.
+        debug_registers.extend(
+            core.registers()
+                .all_registers()
+                .filter(|core_register| {
+                    core_register.register_has_role(RegisterRole::FloatingPoint)
+                        || core_register.register_has_role(RegisterRole::FloatingPointStatus)
+                })
+                .map(|core_register| DebugRegister {
+                    core_register,
+                    dwarf_id: None,
+                    value: None,
+                }),
+        );
+
+        // Read all registers at once, which is much faster on most probes. If that fails,
+        // read them one by one, so a single unreadable register doesn't hide all others.
+        let ids: Vec<_> = debug_registers
+            .iter()
+            .map(|register| register.core_register.id())
+            .collect();
+        match core.read_core_regs(&ids) {
+            Ok(values) => {
+                for (register, value) in debug_registers.iter_mut().zip(values) {
+                    register.value = Some(value);
+                }
+            }
+            Err(e) => {
+                tracing::debug!(
+                    "Failed to read all registers at once, reading them one by one: {e}"
+                );
+
+                for register in debug_registers.iter_mut() {
+                    register.value = match core.read_core_reg(register.core_register.id()) {
                         Ok::<RegisterValue, _>(register_value) => Some(register_value),
                         Err(e) => {
                             tracing::warn!(
                                 "Failed to read value for register {:?}: {}",
-                                core_register,
+                                register.core_register,
                                 e
                             );
                             None
                         }
-                    },
-                });
-            } else {
-                tracing::trace!(
-                    "Unwind will use the default rule for this register : {:?}",
-                    core_register
-                );
+                    };
+                }
             }
         }
+
         DebugRegisters(debug_registers)
     }
 
@@ -88,7 +88,7 @@ impl VerifiedBreakpoint {
             //
             // For earlier versions, the current compilation file is not included in the file names, but index 0 still refers to it.
             // To get the correct number of files, we have to add 1 here.
-            if program_unit.unit.header.version() <= 4 {
+            if line_program.header().version() <= 4 {
                 num_files += 1;
             }
 
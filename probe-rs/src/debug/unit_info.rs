@@ -19,9 +19,19 @@ pub(crate) enum ExpressionResult {
     Location(VariableLocation),
 }
 
+/// The parts of a split unit, whose debug info is in a `.dwo` or `.dwp` file.
+struct SplitUnit {
+    /// The sections from the `.dwo` or `.dwp` file.
+    dwarf: Box<gimli::Dwarf<DwarfReader>>,
+    /// The unit in the ELF file, which has the address ranges of the unit.
+    skeleton: gimli::Unit<GimliReader, usize>,
+}
+
 /// A struct containing information about a single compilation unit.
 pub struct UnitInfo {
     pub(crate) unit: gimli::Unit<GimliReader, usize>,
+    /// For split units, where the DIEs of the `unit` are.
+    split: Option<SplitUnit>,
     dwarf_language: gimli::DwLang,
     language: Box<dyn language::ProgrammingLanguage>,
 }
@@ -41,11 +51,87 @@ impl UnitInfo {
 
         Self {
             unit,
+            split: None,
             dwarf_language,
             language: language::from_dwarf(dwarf_language),
         }
     }
 
+    /// Create a `UnitInfo` for a split unit, whose debug info is in a `.dwo` or `.dwp` file.
+    ///
+    /// The `split_unit` is read from the `split_dwarf`, and the `skeleton` is the unit in the ELF
+    /// file which refers to it.
+    pub(crate) fn new_split(
+        mut split_unit: gimli::Unit<GimliReader, usize>,
+        split_dwarf: gimli::Dwarf<DwarfReader>,
+        skeleton: gimli::Unit<GimliReader, usize>,
+    ) -> Self {
+        split_unit.copy_relocated_attributes(&skeleton);
+        // The line table of a split unit only names the files, the rows are in the ELF file.
+        split_unit.line_program = skeleton.line_program.clone();
+
+        let mut unit_info = Self::new(split_unit);
+        unit_info.split = Some(SplitUnit {
+            dwarf: Box::new(split_dwarf),
+            skeleton,
+        });
+        unit_info
+    }
+
+    /// Whether the debug info of this unit is in a `.dwo` or `.dwp` file.
+    pub(crate) fn is_split(&self) -> bool {
+        self.split.is_some()
+    }
+
+    /// The DWARF sections the DIEs of this unit refer to.
+    pub(crate) fn dwarf<'a>(&'a self, debug_info: &'a DebugInfo) -> &'a gimli::Dwarf<DwarfReader> {
+        match &self.split {
+            Some(split) => &split.dwarf,
+            None => &debug_info.dwarf,
+        }
+    }
+
+    /// The address ranges of the code of this unit.
+    pub(crate) fn unit_ranges(
+        &self,
+        debug_info: &DebugInfo,
+    ) -> Result<gimli::RangeIter<GimliReader>, gimli::Error> {
+        match &self.split {
+            Some(split) => debug_info.dwarf.unit_ranges(&split.skeleton),
+            None => debug_info.dwarf.unit_ranges(&self.unit),
+        }
+    }
+
+    /// Reads a string attribute, in any of the forms of DWARF 4 and 5.
+    fn attr_string(&self, debug_info: &DebugInfo, value: AttributeValue<GimliReader>) -> String {
+        match self
+            .dwarf(debug_info)
+            .attr_string(&self.unit, value.clone())
+        {
+            Ok(string) => String::from_utf8_lossy(&string).to_string(),
+            Err(_) => match value {
+                AttributeValue::DebugStrRef(_)
+                | AttributeValue::DebugStrOffsetsIndex(_)
+                | AttributeValue::DebugLineStrRef(_) => "Invalid DW_AT_name value".to_string(),
+                other => format!("Unimplemented: Evaluate name from {other:?}"),
+            },
+        }
+    }
+
+    fn extract_name(
+        &self,
+        debug_info: &DebugInfo,
+        entry: &gimli::DebuggingInformationEntry<GimliReader>,
+    ) -> Result<Option<String>, gimli::Error> {
+        let attr = match entry.attr(gimli::DW_AT_name) {
+            Ok(Some(attr)) => attr.value(),
+            Ok(None) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        Ok(Some(self.attr_string(debug_info, attr)))
+    }
+
     /// Retrieve the value of the `DW_AT_language` attribute of the compilation unit.
     ///
     /// In the unlikely event that we are unable to retrieve the language, we assume Rust.
@@ -53,8 +139,16 @@ impl UnitInfo {
         self.dwarf_language
     }
 
+    /// The offset of the unit in the `.debug_info` section of the ELF file, which identifies it.
+    ///
+    /// For split units, this is the offset of the skeleton unit.
     pub(crate) fn debug_info_offset(&self) -> Result<DebugInfoOffset, DebugError> {
-        self.unit.header.offset().as_debug_info_offset().ok_or_else(|| DebugError::Other(
+        let unit = match &self.split {
+            Some(split) => &split.skeleton,
+            None => &self.unit,
+        };
+
+        unit.header.offset().as_debug_info_offset().ok_or_else(|| DebugError::Other(
             "Failed to convert unit header offset to debug info offset. This is a bug, please report it.".to_string()
         ))
     }
@@ -201,7 +295,7 @@ impl UnitInfo {
         // For variable attribute resolution, we need to resolve a few attributes in advance of looping through all the other ones.
         // Try to exact the name first, for easier debugging
         if let Some(entry) = attributes_entry.as_ref() {
-            if let Ok(Some(name)) = extract_name(debug_info, entry) {
+            if let Ok(Some(name)) = self.extract_name(debug_info, entry) {
                 child_variable.name = VariableName::Named(name);
             }
         }
@@ -461,7 +555,7 @@ impl UnitInfo {
             match child_node.entry().tag() {
                 gimli::DW_TAG_namespace => {
                     let variable_name =
-                        if let Ok(Some(name)) = extract_name(debug_info, child_node.entry()) {
+                        if let Ok(Some(name)) = self.extract_name(debug_info, child_node.entry()) {
                             VariableName::Namespace(name)
                         } else {
                             VariableName::AnonymousNamespace
@@ -671,19 +765,25 @@ impl UnitInfo {
                     // specified discreetly, or in ranges.
                     let mut in_scope = false;
                     if let Ok(Some(low_pc_attr)) = child_node.entry().attr(gimli::DW_AT_low_pc) {
-                        let low_pc = match low_pc_attr.value() {
-                            gimli::AttributeValue::Addr(value) => value,
-                            _other => u64::MAX,
-                        };
+                        let low_pc = self
+                            .dwarf(debug_info)
+                            .attr_address(&self.unit, low_pc_attr.value())
+                            .ok()
+                            .flatten()
+                            .unwrap_or(u64::MAX);
                         let high_pc = if let Ok(Some(high_pc_attr)) =
                             child_node.entry().attr(gimli::DW_AT_high_pc)
                         {
                             match high_pc_attr.value() {
-                                gimli::AttributeValue::Addr(addr) => addr,
                                 gimli::AttributeValue::Udata(unsigned_offset) => {
                                     low_pc + unsigned_offset
                                 }
-                                _other => 0_u64,
+                                other => self
+                                    .dwarf(debug_info)
+                                    .attr_address(&self.unit, other)
+                                    .ok()
+                                    .flatten()
+                                    .unwrap_or(0_u64),
                             }
                         } else {
                             0_u64
@@ -705,19 +805,17 @@ impl UnitInfo {
                     // Searching for ranges has a bit more overhead, so ONLY do this if do not have scope confirmed yet.
                     if !in_scope {
                         if let Ok(Some(ranges)) = child_node.entry().attr(gimli::DW_AT_ranges) {
-                            match ranges.value() {
-                                gimli::AttributeValue::RangeListsRef(raw_range_lists_offset) => {
-                                    let range_lists_offset = debug_info
-                                        .dwarf
-                                        .ranges_offset_from_raw(&self.unit, raw_range_lists_offset);
-
+                            let dwarf = self.dwarf(debug_info);
+                            match dwarf.attr_ranges_offset(&self.unit, ranges.value()) {
+                                Ok(Some(range_lists_offset)) => {
                                     if let Ok(mut range_iter) =
-                                        debug_info.dwarf.ranges(&self.unit, range_lists_offset)
+                                        dwarf.ranges(&self.unit, range_lists_offset)
                                     {
                                         in_scope = range_iter.contains(program_counter);
                                     }
                                 }
-                                other_range_attribute => {
+                                _ => {
+                                    let other_range_attribute = ranges.value();
                                     let error = format!(
                                         "Found unexpected scope attribute: {:?} for variable {:?}",
                                         other_range_attribute, parent_variable.name
@@ -1147,7 +1245,7 @@ impl UnitInfo {
                                 self.unit.header.entry(&self.unit.abbreviations, unit_ref)?;
 
                             child_variable.type_name =
-                                match extract_name(debug_info, &subroutine_type_node) {
+                                match self.extract_name(debug_info, &subroutine_type_node) {
                                     Ok(Some(name_attr)) => VariableType::Other(name_attr),
                                     Ok(None) => VariableType::Unknown,
                                     Err(error) => VariableType::Other(format!(
@@ -1393,7 +1491,7 @@ impl UnitInfo {
                 gimli::DW_TAG_enumerator => {
                     let attributes_entry = child_node.entry();
 
-                    let name_result = extract_name(debug_info, attributes_entry);
+                    let name_result = self.extract_name(debug_info, attributes_entry);
 
                     let Some(attr_value) = attributes_entry.attr_value(gimli::DW_AT_const_value)?
                     else {
@@ -1683,14 +1781,25 @@ impl UnitInfo {
                         ExpressionResult::Location(location)
                     }
 
-                    gimli::AttributeValue::LocationListsRef(location_list_offset) => self
-                        .evaluate_location_list_ref(
+                    gimli::AttributeValue::LocationListsRef(_)
+                    | gimli::AttributeValue::DebugLocListsIndex(_) => {
+                        let Ok(Some(location_list_offset)) = self
+                            .dwarf(debug_info)
+                            .attr_locations_offset(&self.unit, attr.value())
+                        else {
+                            return Ok(ExpressionResult::Location(VariableLocation::Error(
+                                "Error: Resolving variable location list".to_string(),
+                            )));
+                        };
+
+                        self.evaluate_location_list_ref(
                             debug_info,
                             location_list_offset,
                             frame_info,
                             memory,
                         )
-                        .convert_incomplete()?,
+                        .convert_incomplete()?
+                    }
 
                     other_attribute_value => {
                         ExpressionResult::Location(VariableLocation::Unsupported(format!(
@@ -1742,13 +1851,10 @@ impl UnitInfo {
         frame_info: StackFrameInfo<'_>,
         memory: &mut dyn MemoryInterface,
     ) -> Result<ExpressionResult, DebugError> {
-        let mut locations = match debug_info.locations_section.locations(
-            location_list_offset,
-            self.unit.header.encoding(),
-            self.unit.low_pc,
-            &debug_info.address_section,
-            self.unit.addr_base,
-        ) {
+        let mut locations = match self
+            .dwarf(debug_info)
+            .locations(&self.unit, location_list_offset)
+        {
             Ok(locations) => locations,
             Err(error) => {
                 return Ok(ExpressionResult::Location(VariableLocation::Error(
@@ -2038,19 +2144,7 @@ impl UnitInfo {
     ) -> Result<Option<String>, gimli::Error> {
         match entry.attr(gimli::DW_AT_name) {
             Ok(Some(attr)) => {
-                let name = match attr.value() {
-                    gimli::AttributeValue::DebugStrRef(name_ref) => {
-                        if let Ok(name_raw) = debug_info.dwarf.string(name_ref) {
-                            String::from_utf8_lossy(&name_raw).to_string()
-                        } else {
-                            "Invalid DW_AT_name value".to_string()
-                        }
-                    }
-                    gimli::AttributeValue::String(name) => {
-                        String::from_utf8_lossy(&name).to_string()
-                    }
-                    other => format!("Unimplemented: Evaluate name from {other:?}"),
-                };
+                let name = self.attr_string(debug_info, attr.value());
 
                 Ok(Some(name))
             }
@@ -2173,31 +2267,6 @@ impl UnitInfo {
     }
 }
 
-fn extract_name(
-    debug_info: &DebugInfo,
-    entry: &gimli::DebuggingInformationEntry<GimliReader>,
-) -> Result<Option<String>, gimli::Error> {
-    let attr = match entry.attr(gimli::DW_AT_name) {
-        Ok(Some(attr)) => attr.value(),
-        Ok(None) => return Ok(None),
-        Err(error) => return Err(error),
-    };
-
-    let name = match attr {
-        gimli::AttributeValue::DebugStrRef(name_ref) => {
-            if let Ok(name_raw) = debug_info.dwarf.string(name_ref) {
-                String::from_utf8_lossy(&name_raw).to_string()
-            } else {
-                "Invalid DW_AT_name value".to_string()
-            }
-        }
-        gimli::AttributeValue::String(name) => String::from_utf8_lossy(&name).to_string(),
-        other => format!("Unimplemented: Evaluate name from {other:?}"),
-    };
-
-    Ok(Some(name))
-}
-
 /// Gets necessary register information for the DWARF resolver.
 fn provide_register(
     stack_frame_registers: &DebugRegisters,
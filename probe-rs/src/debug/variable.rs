@@ -581,6 +581,14 @@ impl Variable {
     /// Implementing get_value(), because Variable.value has to be private (a requirement of
     /// updating the value without overriding earlier values ... see set_value()).
     pub fn to_string(&self, variable_cache: &VariableCache) -> String {
+        if !matches!(self.value, VariableValue::Error(_)) {
+            if let Some(value) =
+                language::from_dwarf(self.language).format_value_from_children(self, variable_cache)
+            {
+                return value;
+            }
+        }
+
         // Allow for chained `if let` without complaining
         if !self.value.is_empty() {
             // The `value` for this `Variable` is non empty because either
@@ -366,6 +366,14 @@ impl VariableCache {
         Ok(())
     }
 
+    /// Moves the children of one variable to another.
+    pub(crate) fn move_children(&mut self, from: ObjectRef, to: ObjectRef) {
+        self.variable_hash_map
+            .values_mut()
+            .filter(|child| child.parent_key == from)
+            .for_each(|child| child.parent_key = to);
+    }
+
     /// Removing an entry's children from the `VariableCache` will recursively remove all their children
     pub fn remove_cache_entry_children(
         &mut self,
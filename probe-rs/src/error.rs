@@ -50,6 +50,8 @@ pub enum Error {
     /// A timeout occurred.
     // TODO: Errors below should be core specific
     Timeout,
+    /// The operation was cancelled.
+    Cancelled,
     /// Memory access to address {0.address:#X?} was not aligned to {0.alignment} bytes.
     #[error(transparent)]
     MemoryNotAligned(#[from] MemoryNotAlignedError),
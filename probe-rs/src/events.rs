@@ -0,0 +1,278 @@
+//! Notifications about things happening in a session.
+//!
+//! Applications embedding probe-rs, like IDEs or test frameworks, often need to react when a
+//! core halts or the target is reset. Instead of polling, they can subscribe to a session with
+//! [`Session::subscribe`](crate::Session::subscribe), and are called for every
+//! [`SessionEvent`].
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex;
+
+use crate::{flashing::ProgressEvent, HaltReason};
+
+/// An event in a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionEvent {
+    /// A core was halted.
+    CoreHalted {
+        /// The index of the core.
+        core: usize,
+        /// Why the core was halted.
+        reason: HaltReason,
+    },
+    /// A core was reset.
+    ResetPerformed {
+        /// The index of the core.
+        core: usize,
+        /// Whether the core was halted after the reset.
+        halted: bool,
+    },
+    /// A flash operation was started.
+    FlashStarted {
+        /// The operation which was started.
+        operation: FlashOperation,
+    },
+    /// A flash operation made progress.
+    FlashProgress {
+        /// The operation which made progress.
+        operation: FlashOperation,
+        /// The number of bytes which were processed since the last event.
+        bytes: u64,
+    },
+    /// A flash operation has finished.
+    FlashFinished {
+        /// The operation which has finished.
+        operation: FlashOperation,
+        /// Whether the operation was successful.
+        success: bool,
+    },
+    /// An RTT channel was found while attaching to RTT.
+    RttChannelDiscovered {
+        /// The index of the core used to attach to RTT.
+        core: usize,
+        /// The direction of the channel.
+        direction: RttChannelDirection,
+        /// The number of the channel.
+        number: usize,
+        /// The name of the channel, if it has one.
+        name: Option<String>,
+    },
+}
+
+/// An operation during flashing, see [`SessionEvent::FlashProgress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashOperation {
+    /// Reading flash contents which have to be restored after erasing.
+    Fill,
+    /// Erasing flash.
+    Erase,
+    /// Programming flash.
+    Program,
+}
+
+/// The direction of an RTT channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RttChannelDirection {
+    /// The channel sends data from the target to the host.
+    Up,
+    /// The channel sends data from the host to the target.
+    Down,
+}
+
+/// Identifies a subscription, to remove it with
+/// [`Session::unsubscribe`](crate::Session::unsubscribe).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Subscriber = Arc<dyn Fn(&SessionEvent) + Send + Sync>;
+
+/// The subscribers of a session, shared with the cores and the flash loader.
+#[derive(Clone, Default)]
+pub(crate) struct Events {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: AtomicU64,
+    subscribers: Mutex<Vec<(SubscriptionId, Subscriber)>>,
+    /// Whether each core was halted when we last knew, to report halts only once.
+    halted: Mutex<HashMap<usize, bool>>,
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Events")
+            .field("subscribers", &self.inner.subscribers.lock().len())
+            .finish()
+    }
+}
+
+impl Events {
+    pub(crate) fn subscribe(
+        &self,
+        subscriber: impl Fn(&SessionEvent) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.inner.next_id.fetch_add(1, Ordering::Relaxed));
+        self.inner
+            .subscribers
+            .lock()
+            .push((id, Arc::new(subscriber)));
+        id
+    }
+
+    pub(crate) fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscribers = self.inner.subscribers.lock();
+        let count = subscribers.len();
+        subscribers.retain(|(subscriber, _)| *subscriber != id);
+        subscribers.len() != count
+    }
+
+    pub(crate) fn emit(&self, event: SessionEvent) {
+        // Subscribers may (un)subscribe themselves, so don't hold the lock while calling them.
+        let subscribers: Vec<_> = self
+            .inner
+            .subscribers
+            .lock()
+            .iter()
+            .map(|(_, subscriber)| subscriber.clone())
+            .collect();
+
+        for subscriber in subscribers {
+            subscriber(&event);
+        }
+    }
+
+    /// Records that a core is halted, and reports it if it was running before.
+    pub(crate) fn core_halted(&self, core: usize, reason: HaltReason) {
+        let was_halted = self.inner.halted.lock().insert(core, true);
+        if was_halted != Some(true) {
+            self.emit(SessionEvent::CoreHalted { core, reason });
+        }
+    }
+
+    /// Records that a core is running.
+    pub(crate) fn core_running(&self, core: usize) {
+        self.inner.halted.lock().insert(core, false);
+    }
+
+    pub(crate) fn reset_performed(&self, core: usize, halted: bool) {
+        self.core_running(core);
+        self.emit(SessionEvent::ResetPerformed { core, halted });
+    }
+
+    /// Reports the flash operations of a flash progress event.
+    pub(crate) fn flash_progress(&self, event: &ProgressEvent) {
+        use FlashOperation::{Erase, Fill, Program};
+
+        let event = match *event {
+            ProgressEvent::StartedFilling => SessionEvent::FlashStarted { operation: Fill },
+            ProgressEvent::StartedErasing => SessionEvent::FlashStarted { operation: Erase },
+            ProgressEvent::StartedProgramming { .. } => {
+                SessionEvent::FlashStarted { operation: Program }
+            }
+            ProgressEvent::PageFilled { size, .. } => SessionEvent::FlashProgress {
+                operation: Fill,
+                bytes: size,
+            },
+            ProgressEvent::SectorErased { size, .. } => SessionEvent::FlashProgress {
+                operation: Erase,
+                bytes: size,
+            },
+            ProgressEvent::PageProgrammed { size, .. } => SessionEvent::FlashProgress {
+                operation: Program,
+                bytes: size.into(),
+            },
+            ProgressEvent::FinishedFilling | ProgressEvent::FailedFilling => {
+                SessionEvent::FlashFinished {
+                    operation: Fill,
+                    success: matches!(event, ProgressEvent::FinishedFilling),
+                }
+            }
+            ProgressEvent::FinishedErasing | ProgressEvent::FailedErasing => {
+                SessionEvent::FlashFinished {
+                    operation: Erase,
+                    success: matches!(event, ProgressEvent::FinishedErasing),
+                }
+            }
+            ProgressEvent::FinishedProgramming | ProgressEvent::FailedProgramming => {
+                SessionEvent::FlashFinished {
+                    operation: Program,
+                    success: matches!(event, ProgressEvent::FinishedProgramming),
+                }
+            }
+            _ => return,
+        };
+
+        self.emit(event);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn halts_are_reported_once() {
+        let events = Events::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let id = events.subscribe(move |event| sink.lock().push(event.clone()));
+
+        events.core_halted(0, HaltReason::Request);
+        events.core_halted(0, HaltReason::Request);
+        events.core_running(0);
+        events.core_halted(0, HaltReason::Step);
+
+        assert!(events.unsubscribe(id));
+        events.core_running(0);
+        events.core_halted(0, HaltReason::Request);
+
+        assert_eq!(
+            *received.lock(),
+            [
+                SessionEvent::CoreHalted {
+                    core: 0,
+                    reason: HaltReason::Request
+                },
+                SessionEvent::CoreHalted {
+                    core: 0,
+                    reason: HaltReason::Step
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn session_reports_halts() {
+        use crate::probe::fake_probe::FakeProbe;
+        use std::time::Duration;
+
+        let mut session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        session.subscribe(move |event| sink.lock().push(event.clone()));
+
+        let mut core = session.core(0).unwrap();
+        core.halt(Duration::from_millis(100)).unwrap();
+        core.status().unwrap();
+        core.run().unwrap();
+
+        assert_eq!(
+            *received.lock(),
+            [SessionEvent::CoreHalted {
+                core: 0,
+                reason: HaltReason::Request
+            }]
+        );
+    }
+}
@@ -166,7 +166,7 @@ impl FlashDataBlockSpan {
 }
 
 /// A helper structure to build a flash layout from a set of data blocks.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub(super) struct FlashBuilder {
     pub(super) data: BTreeMap<u64, Vec<u8>>,
 }
@@ -223,6 +223,43 @@ impl FlashBuilder {
     }
 
     /// Check whether there is staged data for a given address range.
+    /// Overwrites the staged data at `address` with `data`. Parts which are not staged yet are
+    /// added.
+    pub fn patch_data(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
+        let end = address + data.len() as u64;
+        let mut gaps = vec![];
+        let mut cursor = address;
+
+        for (&chunk_address, chunk) in self.data.range_mut(..end) {
+            let chunk_end = chunk_address + chunk.len() as u64;
+            if chunk_end <= address {
+                continue;
+            }
+
+            let start = chunk_address.max(address);
+            let stop = chunk_end.min(end);
+            chunk[(start - chunk_address) as usize..(stop - chunk_address) as usize]
+                .copy_from_slice(&data[(start - address) as usize..(stop - address) as usize]);
+
+            if start > cursor {
+                gaps.push(cursor..start);
+            }
+            cursor = stop;
+        }
+        if cursor < end {
+            gaps.push(cursor..end);
+        }
+
+        for gap in gaps {
+            self.add_data(
+                gap.start,
+                &data[(gap.start - address) as usize..(gap.end - address) as usize],
+            )?;
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn has_data_in_range(&self, range: &Range<u64>) -> bool {
         self.data_in_range(range).next().is_some()
     }
@@ -1147,4 +1184,21 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn patch_data_overwrites_and_fills_gaps() {
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0, &[1, 2, 3, 4]).unwrap();
+        flash_builder.add_data(8, &[9, 10]).unwrap();
+
+        flash_builder.patch_data(2, &[0xAA; 8]).unwrap();
+
+        assert_eq!(
+            flash_builder.data.into_iter().collect::<Vec<_>>(),
+            [
+                (0, vec![1, 2, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]),
+                (8, vec![0xAA, 0xAA])
+            ]
+        );
+    }
 }
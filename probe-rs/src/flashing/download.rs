@@ -13,6 +13,7 @@ use std::{
 
 use super::*;
 use crate::session::Session;
+use crate::CancellationToken;
 
 /// Extended options for flashing a binary file.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
@@ -202,6 +203,17 @@ pub struct DownloadOptions {
     pub verify: bool,
     /// Disable double buffering when loading flash.
     pub disable_double_buffering: bool,
+    /// A token to cancel the download, e.g. when the user presses Ctrl-C.
+    ///
+    /// The download then stops after the current flash sector or page, and fails with
+    /// [`FlashError::Cancelled`](super::FlashError::Cancelled).
+    pub cancellation: Option<CancellationToken>,
+    /// Don't reset the core before flashing, and restore its registers and the RAM used by the
+    /// flash algorithm afterwards, so a halted program can continue where it stopped.
+    ///
+    /// This is used to change the flash while debugging, e.g. for flash breakpoints. Peripherals
+    /// which the flash algorithm reconfigures are not restored.
+    pub preserve_state: bool,
 }
 
 impl DownloadOptions {
@@ -2,9 +2,12 @@ use std::collections::HashMap;
 
 use probe_rs_target::{MemoryRange, MemoryRegion, NvmRegion};
 
-use crate::flashing::{flasher::Flasher, FlashError, FlashLoader};
+use crate::flashing::{
+    flasher::{check_cancelled, Flasher},
+    FlashError, FlashLoader,
+};
 use crate::flashing::{FlashAlgorithm, FlashLayout, FlashSector};
-use crate::Session;
+use crate::{CancellationToken, Session};
 
 use super::FlashProgress;
 
@@ -13,6 +16,18 @@ use super::FlashProgress;
 /// The optional progress will only be used to emit RTT messages.
 /// No actual indication for the state of the erase all operation will be given.
 pub fn erase_all(session: &mut Session, progress: FlashProgress) -> Result<(), FlashError> {
+    erase_all_cancellable(session, progress, CancellationToken::new())
+}
+
+/// Mass-erase all nonvolatile memory, unless `cancellation` is cancelled.
+///
+/// A chip erase can't be interrupted, so it is only cancelled before it starts. If the chip
+/// doesn't support chip erases, cancellation is checked after each sector.
+pub fn erase_all_cancellable(
+    session: &mut Session,
+    progress: FlashProgress,
+    cancellation: CancellationToken,
+) -> Result<(), FlashError> {
     tracing::debug!("Erasing all...");
 
     let mut algos: HashMap<(String, String), Vec<NvmRegion>> = HashMap::new();
@@ -100,7 +115,13 @@ pub fn erase_all(session: &mut Session, progress: FlashProgress) -> Result<(), F
         let algo = algo.unwrap().clone();
 
         let core_index = session.target().core_index_by_name(&core_name).unwrap();
-        let mut flasher = Flasher::new(session, core_index, &algo, progress.clone())?;
+        let mut flasher = Flasher::new(
+            session,
+            core_index,
+            &algo,
+            progress.clone(),
+            cancellation.clone(),
+        )?;
 
         if flasher.is_chip_erase_supported() {
             tracing::debug!("     -- chip erase supported, doing it.");
@@ -121,6 +142,7 @@ pub fn erase_all(session: &mut Session, progress: FlashProgress) -> Result<(), F
 
             flasher.run_erase(|active| {
                 for info in sectors {
+                    check_cancelled(&cancellation)?;
                     tracing::debug!(
                         "    sector: {:#010x}-{:#010x} ({} bytes)",
                         info.base_address,
@@ -197,7 +219,13 @@ pub fn erase_sectors(
         let algo = algo.unwrap().clone();
 
         let core_index = session.target().core_index_by_name(&core_name).unwrap();
-        let mut flasher = Flasher::new(session, core_index, &algo, progress.clone())?;
+        let mut flasher = Flasher::new(
+            session,
+            core_index,
+            &algo,
+            progress.clone(),
+            CancellationToken::new(),
+        )?;
 
         let sectors = flasher
             .flash_algorithm()
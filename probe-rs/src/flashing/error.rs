@@ -184,4 +184,16 @@ pub enum FlashError {
         /// The operation that caused the stack overflow.
         operation: &'static str,
     },
+    /// Initializing the external memory of a RAM region failed.
+    #[error("Failed to initialize the memory at {range:#010x?}.")]
+    MemoryInit {
+        /// The range which was to be loaded.
+        range: Range<u64>,
+        /// The error of the memory init sequence.
+        #[source]
+        source: crate::memory_init::MemoryInitError,
+    },
+    /// The operation was cancelled with a [`CancellationToken`](crate::CancellationToken).
+    #[error("The flash operation was cancelled.")]
+    Cancelled,
 }
@@ -4,7 +4,7 @@ use probe_rs_target::{
     FlashProperties, MemoryRegion, PageInfo, RamRegion, RawFlashAlgorithm, RegionMergeIterator,
     SectorInfo, TransferEncoding,
 };
-use std::mem::size_of_val;
+use std::{mem::size_of_val, ops::Range};
 
 /// A flash algorithm, which has been assembled for a specific
 /// chip.
@@ -59,6 +59,25 @@ pub struct FlashAlgorithm {
 }
 
 impl FlashAlgorithm {
+    /// Returns the ranges of RAM which are overwritten when running the algorithm: its code and
+    /// data, its stack, and its page buffers.
+    pub fn ram_ranges(&self) -> Vec<Range<u64>> {
+        let code_end = self.load_address + size_of_val(self.instructions.as_slice()) as u64;
+        let page_size = self.flash_properties.page_size as u64;
+
+        let mut ranges = vec![
+            self.load_address..code_end,
+            self.stack_top - self.stack_size..self.stack_top,
+        ];
+        ranges.extend(
+            self.page_buffers
+                .iter()
+                .map(|&buffer| buffer..buffer + page_size),
+        );
+
+        ranges
+    }
+
     /// Try to retrieve the information about the flash sector
     /// which contains `address`.
     ///
@@ -467,6 +486,32 @@ mod test {
 
     use crate::flashing::FlashAlgorithm;
 
+    #[test]
+    fn ram_ranges_cover_code_stack_and_buffers() {
+        let algorithm = FlashAlgorithm {
+            load_address: 0x2000_0000,
+            instructions: vec![0; 0x100],
+            stack_top: 0x2000_1000,
+            stack_size: 0x800,
+            page_buffers: vec![0x2000_1000, 0x2000_1400],
+            flash_properties: FlashProperties {
+                page_size: 0x400,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            algorithm.ram_ranges(),
+            [
+                0x2000_0000..0x2000_0400,
+                0x2000_0800..0x2000_1000,
+                0x2000_1000..0x2000_1400,
+                0x2000_1400..0x2000_1800,
+            ]
+        );
+    }
+
     #[test]
     fn flash_sector_single_size() {
         let config = FlashAlgorithm {
@@ -1,16 +1,21 @@
 use probe_rs_target::RawFlashAlgorithm;
 use tracing::Level;
 
-use super::{FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashPage, FlashProgress};
+use super::{
+    AlgorithmState, FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashPage, FlashProgress,
+};
 use crate::config::NvmRegion;
 use crate::error::Error;
 use crate::flashing::encoder::FlashEncoder;
 use crate::flashing::{FlashLayout, FlashSector};
 use crate::memory::MemoryInterface;
+use crate::metrics::FlashPhase;
 use crate::rtt::{self, Rtt, ScanRegion};
-use crate::CoreStatus;
 use crate::{core::CoreRegisters, session::Session, Core, InstructionSet};
+use crate::{CancellationToken, CoreStatus};
+use crate::{RegisterId, RegisterValue};
 use std::marker::PhantomData;
+use std::mem::size_of_val;
 use std::{
     fmt::Debug,
     time::{Duration, Instant},
@@ -54,6 +59,14 @@ pub(super) struct Flasher<'session> {
     flash_algorithm: FlashAlgorithm,
     loaded: bool,
     progress: FlashProgress,
+    cancellation: CancellationToken,
+    reset: bool,
+}
+
+/// The registers and RAM contents of a core, saved before running a flash algorithm on it.
+pub(super) struct SavedState {
+    registers: Vec<(RegisterId, RegisterValue)>,
+    memory: Vec<(u64, Vec<u8>)>,
 }
 
 /// The byte used to fill the stack when checking for stack overflows.
@@ -65,6 +78,7 @@ impl<'session> Flasher<'session> {
         core_index: usize,
         raw_flash_algorithm: &RawFlashAlgorithm,
         progress: FlashProgress,
+        cancellation: CancellationToken,
     ) -> Result<Self, FlashError> {
         let target = session.target();
 
@@ -79,10 +93,68 @@ impl<'session> Flasher<'session> {
             core_index,
             flash_algorithm,
             progress,
+            cancellation,
             loaded: false,
+            reset: true,
         })
     }
 
+    /// Loads the flash algorithm without resetting the core, e.g. to keep a halted program.
+    pub(super) fn skip_reset(&mut self) {
+        self.reset = false;
+    }
+
+    /// Halts the core, and saves its registers and the RAM which the flash algorithm uses.
+    pub(super) fn save_state(&mut self) -> Result<SavedState, FlashError> {
+        let ranges = self.flash_algorithm.ram_ranges();
+        let mut core = self
+            .session
+            .core(self.core_index)
+            .map_err(FlashError::Core)?;
+
+        if !core.core_halted().map_err(FlashError::Core)? {
+            core.halt(Duration::from_millis(500))
+                .map_err(FlashError::Core)?;
+        }
+
+        // Registers which can't be read, like the FPU registers of a core with a disabled FPU,
+        // are not used by the flash algorithm either.
+        let ids: Vec<_> = core.registers().all_registers().map(|r| r.id()).collect();
+        let registers = ids
+            .into_iter()
+            .filter_map(|id| Some((id, core.read_core_reg(id).ok()?)))
+            .collect();
+
+        let mut memory = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let mut data = vec![0; (range.end - range.start) as usize];
+            core.read(range.start, &mut data)
+                .map_err(FlashError::Core)?;
+            memory.push((range.start, data));
+        }
+
+        Ok(SavedState { registers, memory })
+    }
+
+    /// Restores the state saved with [`Flasher::save_state`].
+    pub(super) fn restore_state(&mut self, state: SavedState) -> Result<(), FlashError> {
+        let mut core = self
+            .session
+            .core(self.core_index)
+            .map_err(FlashError::Core)?;
+
+        for (address, data) in state.memory {
+            core.write(address, &data).map_err(FlashError::Core)?;
+        }
+        for (id, value) in state.registers {
+            if let Err(error) = core.write_core_reg(id, value) {
+                tracing::debug!("Failed to restore register {id:?}: {error}");
+            }
+        }
+
+        Ok(())
+    }
+
     fn ensure_loaded(&mut self) -> Result<(), FlashError> {
         if !self.loaded {
             self.load()?;
@@ -109,10 +181,15 @@ impl<'session> Flasher<'session> {
             .core(self.core_index)
             .map_err(FlashError::Core)?;
 
-        // TODO: we probably want a full system reset here to make sure peripherals don't interfere.
-        tracing::debug!("Reset and halt core {}", self.core_index);
-        core.reset_and_halt(Duration::from_millis(500))
-            .map_err(FlashError::ResetAndHalt)?;
+        if self.reset {
+            // TODO: we probably want a full system reset here to make sure peripherals don't interfere.
+            tracing::debug!("Reset and halt core {}", self.core_index);
+            core.reset_and_halt(Duration::from_millis(500))
+                .map_err(FlashError::ResetAndHalt)?;
+        } else if !core.core_halted().map_err(FlashError::Core)? {
+            core.halt(Duration::from_millis(500))
+                .map_err(FlashError::Core)?;
+        }
 
         // TODO: Possible special preparation of the target such as enabling faster clocks for the flash e.g.
 
@@ -179,6 +256,7 @@ impl<'session> Flasher<'session> {
             rtt: None,
             progress: &self.progress,
             flash_algorithm: &self.flash_algorithm,
+            routine: "Init",
             _operation: PhantomData,
         };
 
@@ -189,7 +267,11 @@ impl<'session> Flasher<'session> {
 
     pub(super) fn run_erase_all(&mut self) -> Result<(), FlashError> {
         self.progress.started_erasing();
-        let result = if self.session.has_sequence_erase_all() {
+        // A chip erase can't be interrupted, so it can only be cancelled before it starts.
+        let result = if self.cancellation.is_cancelled() {
+            Err(FlashError::Cancelled)
+        } else if self.session.has_sequence_erase_all() {
+            let start = Instant::now();
             fn run(flasher: &mut Flasher) -> Result<(), FlashError> {
                 flasher
                     .session
@@ -202,7 +284,9 @@ impl<'session> Flasher<'session> {
                 flasher.load()
             }
 
-            run(self)
+            let result = run(self);
+            self.record_phase(FlashPhase::Erase, start);
+            result
         } else {
             self.run_erase(|active| active.erase_all())
         };
@@ -219,35 +303,51 @@ impl<'session> Flasher<'session> {
     where
         F: FnOnce(&mut ActiveFlasher<'_, Erase>) -> Result<T, FlashError> + Sized,
     {
-        // TODO: Fix those values (None, None).
-        let mut active = self.init(None)?;
-        let r = f(&mut active)?;
-        active.uninit()?;
-        Ok(r)
+        let start = Instant::now();
+        let result = self.run_operation(f);
+        self.record_phase(FlashPhase::Erase, start);
+        result
     }
 
     pub(super) fn run_program<T, F>(&mut self, f: F) -> Result<T, FlashError>
     where
         F: FnOnce(&mut ActiveFlasher<'_, Program>) -> Result<T, FlashError> + Sized,
     {
-        // TODO: Fix those values (None, None).
-        let mut active = self.init(None)?;
-        let r = f(&mut active)?;
-        active.uninit()?;
-        Ok(r)
+        let start = Instant::now();
+        let result = self.run_operation(f);
+        self.record_phase(FlashPhase::Program, start);
+        result
     }
 
     pub(super) fn run_verify<T, F>(&mut self, f: F) -> Result<T, FlashError>
     where
         F: FnOnce(&mut ActiveFlasher<'_, Verify>) -> Result<T, FlashError> + Sized,
+    {
+        self.run_operation(f)
+    }
+
+    fn run_operation<O: Operation, T, F>(&mut self, f: F) -> Result<T, FlashError>
+    where
+        F: FnOnce(&mut ActiveFlasher<'_, O>) -> Result<T, FlashError> + Sized,
     {
         // TODO: Fix those values (None, None).
         let mut active = self.init(None)?;
-        let r = f(&mut active)?;
-        active.uninit()?;
+        let r = f(&mut active);
+        // Uninitialize even if the operation failed or was cancelled, to leave the flash
+        // peripheral in a consistent state.
+        let uninit = active.uninit();
+        let r = r?;
+        uninit?;
         Ok(r)
     }
 
+    /// Adds the time since `start` to the flash timings of the session.
+    fn record_phase(&self, phase: FlashPhase, start: Instant) {
+        self.session
+            .metrics_handle()
+            .record_flash_phase(phase, start.elapsed());
+    }
+
     pub(super) fn is_chip_erase_supported(&self) -> bool {
         self.session.has_sequence_erase_all() || self.flash_algorithm().pc_erase_all.is_some()
     }
@@ -277,15 +377,18 @@ impl<'session> Flasher<'session> {
 
         // Read all fill areas from the flash.
         self.progress.started_filling();
+        let fill_start = Instant::now();
 
         if restore_unwritten_bytes {
             for fill in flash_layout.fills.iter() {
                 let t = Instant::now();
                 let page = &mut flash_layout.pages[fill.page_index()];
-                let result = self.fill_page(page, fill);
+                let result =
+                    check_cancelled(&self.cancellation).and_then(|_| self.fill_page(page, fill));
 
                 // If we encounter an error, catch it, gracefully report the failure and return the error.
                 if result.is_err() {
+                    self.record_phase(FlashPhase::Fill, fill_start);
                     self.progress.failed_filling();
                     return result;
                 } else {
@@ -295,6 +398,7 @@ impl<'session> Flasher<'session> {
         }
 
         // We successfully finished filling.
+        self.record_phase(FlashPhase::Fill, fill_start);
         self.progress.finished_filling();
 
         let flash_encoder = FlashEncoder::new(self.flash_algorithm.transfer_encoding, flash_layout);
@@ -340,8 +444,10 @@ impl<'session> Flasher<'session> {
         self.progress
             .started_programming(flash_encoder.program_size());
 
+        let cancellation = self.cancellation.clone();
         let result = self.run_program(|active| {
             for page in flash_encoder.pages() {
+                check_cancelled(&cancellation)?;
                 active
                     .program_page(page)
                     .map_err(|error| FlashError::PageWrite {
@@ -364,8 +470,10 @@ impl<'session> Flasher<'session> {
     fn sector_erase(&mut self, flash_encoder: &FlashEncoder) -> Result<(), FlashError> {
         self.progress.started_erasing();
 
+        let cancellation = self.cancellation.clone();
         let result = self.run_erase(|active| {
             for sector in flash_encoder.sectors() {
+                check_cancelled(&cancellation)?;
                 active
                     .erase_sector(sector)
                     .map_err(|e| FlashError::EraseFailed {
@@ -398,10 +506,18 @@ impl<'session> Flasher<'session> {
         self.progress
             .started_programming(flash_encoder.program_size());
 
+        let cancellation = self.cancellation.clone();
         let result = self.run_program(|active| {
             let mut t = Instant::now();
             let mut last_page_address = 0;
+            let mut cancelled = false;
             for page in flash_encoder.pages() {
+                if cancellation.is_cancelled() {
+                    // The page which is being programmed has to finish first.
+                    cancelled = true;
+                    break;
+                }
+
                 // At the start of each loop cycle load the next page buffer into RAM.
                 let buffer_address = active.load_page_buffer(page.data(), current_buf)?;
 
@@ -429,7 +545,12 @@ impl<'session> Flasher<'session> {
                 }
             }
 
-            active.wait_for_write_end(last_page_address)
+            active.wait_for_write_end(last_page_address)?;
+
+            match cancelled {
+                true => Err(FlashError::Cancelled),
+                false => Ok(()),
+            }
         });
 
         match result.is_ok() {
@@ -454,6 +575,14 @@ impl<'session> Flasher<'session> {
     }
 }
 
+/// Fails with [`FlashError::Cancelled`] if cancellation was requested.
+pub(super) fn check_cancelled(cancellation: &CancellationToken) -> Result<(), FlashError> {
+    match cancellation.is_cancelled() {
+        true => Err(FlashError::Cancelled),
+        false => Ok(()),
+    }
+}
+
 struct Registers {
     pc: u32,
     r0: Option<u32>,
@@ -486,6 +615,8 @@ pub(super) struct ActiveFlasher<'op, O: Operation> {
     rtt: Option<Rtt>,
     progress: &'op FlashProgress,
     flash_algorithm: &'op FlashAlgorithm,
+    /// The routine called last, to report its result when the algorithm is debugged.
+    routine: &'static str,
     _operation: PhantomData<O>,
 }
 
@@ -524,6 +655,7 @@ impl<O: Operation> ActiveFlasher<'_, O> {
         Ok(())
     }
 
+    #[tracing::instrument(name = "Call to flash algorithm uninit", skip(self))]
     pub(super) fn uninit(&mut self) -> Result<(), FlashError> {
         tracing::debug!("Running uninit routine.");
         let algo = &self.flash_algorithm;
@@ -570,6 +702,7 @@ impl<O: Operation> ActiveFlasher<'_, O> {
     fn call_function(&mut self, registers: &Registers, init: bool) -> Result<(), FlashError> {
         tracing::debug!("Calling routine {:?}, init={})", registers, init);
 
+        let entry_point = u64::from(registers.pc);
         let algo = &self.flash_algorithm;
         let regs: &'static CoreRegisters = self.core.registers();
 
@@ -641,6 +774,13 @@ impl<O: Operation> ActiveFlasher<'_, O> {
             .debug_on_sw_breakpoint(true)
             .map_err(FlashError::Core)?;
 
+        if self.progress.debugs_algorithm() {
+            self.routine = self.routine_name(entry_point);
+            let state = self.algorithm_state()?;
+            self.progress
+                .algorithm_called(self.routine, entry_point, state);
+        }
+
         // Resume target operation.
         self.core.run().map_err(FlashError::Run)?;
 
@@ -662,6 +802,16 @@ impl<O: Operation> ActiveFlasher<'_, O> {
     #[tracing::instrument(skip(self))]
     pub(super) fn wait_for_completion(&mut self, timeout: Duration) -> Result<u32, FlashError> {
         tracing::debug!("Waiting for routine call completion.");
+
+        let result = self.poll_until_completion(timeout);
+        if self.progress.debugs_algorithm() {
+            self.report_return(result.as_ref().ok().copied());
+        }
+
+        result
+    }
+
+    fn poll_until_completion(&mut self, timeout: Duration) -> Result<u32, FlashError> {
         let regs = self.core.registers();
 
         // Wait until halted state is active again.
@@ -705,6 +855,77 @@ impl<O: Operation> ActiveFlasher<'_, O> {
             })
     }
 
+    /// Returns the name of the routine at `entry_point`, as in the CMSIS-Pack flash algorithms.
+    fn routine_name(&self, entry_point: u64) -> &'static str {
+        let algo = self.flash_algorithm;
+        if algo.pc_init == Some(entry_point) {
+            "Init"
+        } else if algo.pc_uninit == Some(entry_point) {
+            "UnInit"
+        } else if algo.pc_erase_all == Some(entry_point) {
+            "EraseChip"
+        } else if algo.pc_erase_sector == entry_point {
+            "EraseSector"
+        } else if algo.pc_program_page == entry_point {
+            "ProgramPage"
+        } else {
+            "unknown"
+        }
+    }
+
+    /// Reads the core registers, and the data and stack of the algorithm.
+    fn algorithm_state(&mut self) -> Result<AlgorithmState, FlashError> {
+        let regs: &'static CoreRegisters = self.core.registers();
+        let ids: Vec<_> = regs.core_registers().map(|r| r.id()).collect();
+        let values = self.core.read_core_regs(&ids).map_err(FlashError::Core)?;
+        let registers = regs
+            .core_registers()
+            .map(|r| r.name())
+            .zip(values)
+            .collect();
+
+        let algo = self.flash_algorithm;
+        let code_end = algo.load_address + size_of_val(algo.instructions.as_slice()) as u64;
+        let ranges = [
+            algo.static_base.min(code_end)..code_end,
+            algo.stack_top - algo.stack_size..algo.stack_top,
+        ];
+
+        let mut memory = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let mut data = vec![0; (range.end - range.start) as usize];
+            self.core
+                .read(range.start, &mut data)
+                .map_err(FlashError::Core)?;
+            memory.push((range.start, data));
+        }
+
+        Ok(AlgorithmState { registers, memory })
+    }
+
+    /// Reports the state of the core after the call to the routine, which is halted first if it
+    /// didn't return.
+    fn report_return(&mut self, result: Option<u32>) {
+        let state = self
+            .core
+            .core_halted()
+            .and_then(|halted| match halted {
+                true => Ok(()),
+                false => self.core.halt(Duration::from_millis(100)).map(drop),
+            })
+            .map_err(FlashError::Core)
+            .and_then(|_| self.algorithm_state());
+
+        match state {
+            Ok(state) => self
+                .progress
+                .algorithm_returned(self.routine, result, state),
+            Err(error) => {
+                tracing::warn!("Failed to read the state of the flash algorithm: {error}")
+            }
+        }
+    }
+
     fn read_rtt(&mut self) -> Result<(), FlashError> {
         let Some(rtt) = &mut self.rtt else {
             return Ok(());
@@ -749,6 +970,7 @@ impl<O: Operation> ActiveFlasher<'_, O> {
 }
 
 impl<'probe> ActiveFlasher<'probe, Erase> {
+    #[tracing::instrument(skip(self))]
     pub(super) fn erase_all(&mut self) -> Result<(), FlashError> {
         tracing::debug!("Erasing entire chip.");
         let algo = &self.flash_algorithm;
@@ -785,6 +1007,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
         }
     }
 
+    #[tracing::instrument(skip(self, sector), fields(address = sector.address(), length = sector.size()))]
     pub(super) fn erase_sector(&mut self, sector: &FlashSector) -> Result<(), FlashError> {
         let address = sector.address();
         tracing::info!("Erasing sector at address {:#010x}", address);
@@ -823,6 +1046,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
 
 impl<'p> ActiveFlasher<'p, Program> {
     /// Transfers the buffer bytes to RAM.
+    #[tracing::instrument(skip(self, bytes), fields(length = bytes.len()))]
     fn load_data(&mut self, address: u64, bytes: &[u8]) -> Result<(), FlashError> {
         tracing::debug!(
             "Loading {} bytes of data into RAM at address {:#010x}\n",
@@ -861,6 +1085,7 @@ impl<'p> ActiveFlasher<'p, Program> {
         Ok(())
     }
 
+    #[tracing::instrument(skip(self, page), fields(address = page.address(), length = page.data().len()))]
     pub(super) fn program_page(&mut self, page: &FlashPage) -> Result<(), FlashError> {
         let t1 = Instant::now();
 
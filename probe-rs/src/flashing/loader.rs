@@ -9,9 +9,10 @@ use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom};
 use std::ops::Range;
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use super::builder::FlashBuilder;
+use super::flasher::check_cancelled;
 use super::{
     extract_from_elf, BinOptions, DownloadOptions, FileDownloadError, FlashError, Flasher,
     IdfOptions,
@@ -19,6 +20,8 @@ use super::{
 use crate::config::DebugSequence;
 use crate::flashing::{FlashLayout, FlashProgress, Format};
 use crate::memory::MemoryInterface;
+use crate::memory_init;
+use crate::metrics::FlashPhase;
 use crate::session::Session;
 use crate::Target;
 
@@ -293,6 +296,7 @@ impl ImageLoader for IdfLoader {
 /// Once you are done adding all your data, use `commit()` to flash the data.
 /// The flash loader will make sure to select the appropriate flash region for the right data chunks.
 /// Region crossing data chunks are allowed as long as the regions are contiguous.
+#[derive(Clone)]
 pub struct FlashLoader {
     memory_map: Vec<MemoryRegion>,
     builder: FlashBuilder,
@@ -345,6 +349,14 @@ impl FlashLoader {
         self.builder.add_data(address, data)
     }
 
+    /// Overwrites staged data, e.g. to give every device a unique serial number.
+    ///
+    /// Unlike [add_data()](FlashLoader::add_data), the data may overlap previously added data.
+    pub fn patch_data(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
+        self.check_data_in_memory_map(address..address + data.len() as u64)?;
+        self.builder.patch_data(address, data)
+    }
+
     pub(super) fn get_region_for_address(
         memory_map: &[MemoryRegion],
         address: u64,
@@ -390,6 +402,7 @@ impl FlashLoader {
     /// Writes all the stored data chunks to flash.
     ///
     /// Requires a session with an attached target that has a known flash algorithm.
+    #[tracing::instrument(skip_all)]
     pub fn commit(
         &self,
         session: &mut Session,
@@ -428,7 +441,11 @@ impl FlashLoader {
 
         let mut algos: HashMap<(String, usize), Vec<NvmRegion>> = HashMap::new();
 
-        let progress = options.progress.unwrap_or_else(FlashProgress::empty);
+        let progress = options
+            .progress
+            .unwrap_or_else(FlashProgress::empty)
+            .with_events(session.events_handle().clone());
+        let cancellation = options.cancellation.unwrap_or_default();
 
         // Commit NVM first
 
@@ -506,7 +523,13 @@ impl FlashLoader {
             let algo = session.target().flash_algorithm_by_name(algo_name);
             let algo = algo.unwrap().clone();
 
-            let flasher = Flasher::new(session, *core, &algo, progress.clone())?;
+            let flasher = Flasher::new(
+                session,
+                *core,
+                &algo,
+                progress.clone(),
+                cancellation.clone(),
+            )?;
             // If the first flash algo doesn't support erase all, disable chip erase.
             // TODO: we could sort by support but it's unlikely to make a difference.
             if do_chip_erase && !flasher.is_chip_erase_supported() {
@@ -535,7 +558,15 @@ impl FlashLoader {
             let algo = session.target().flash_algorithm_by_name(&algo_name);
             let algo = algo.unwrap().clone();
 
-            let mut flasher = Flasher::new(session, core, &algo, progress.clone())?;
+            let mut flasher =
+                Flasher::new(session, core, &algo, progress.clone(), cancellation.clone())?;
+
+            let saved_state = if options.preserve_state {
+                flasher.skip_reset();
+                Some(flasher.save_state()?)
+            } else {
+                None
+            };
 
             if do_chip_erase {
                 tracing::debug!("    Doing chip erase...");
@@ -550,6 +581,7 @@ impl FlashLoader {
                 do_use_double_buffering = false;
             }
 
+            let programmed: Vec<_> = regions.iter().map(|region| region.range.clone()).collect();
             for region in regions {
                 tracing::debug!(
                     "    programming region: {:#010X?} ({} bytes)",
@@ -566,6 +598,18 @@ impl FlashLoader {
                     options.skip_erase || did_chip_erase,
                 )?;
             }
+
+            if let Some(state) = saved_state {
+                flasher.restore_state(state)?;
+            }
+            drop(flasher);
+
+            // A core which keeps running the old code from its caches would miss e.g. flash
+            // breakpoints.
+            let mut core = session.core(core).map_err(FlashError::Core)?;
+            for range in programmed {
+                core.invalidate_caches(range).map_err(FlashError::Core)?;
+            }
         }
 
         tracing::debug!("committing RAM!");
@@ -591,6 +635,17 @@ impl FlashLoader {
                         .ok_or_else(|| FlashError::NoRamCoreAccess(region.clone()))?,
                 )
                 .unwrap();
+
+            // External memory, e.g. SDRAM, may have to be initialized before it can be loaded.
+            if self.builder.data_in_range(&region.range).next().is_some() {
+                memory_init::init_memory(session, &region.range).map_err(|source| {
+                    FlashError::MemoryInit {
+                        range: region.range.clone(),
+                        source,
+                    }
+                })?;
+            }
+
             // Attach to memory and core.
             let mut core = session.core(region_core_index).map_err(FlashError::Core)?;
 
@@ -621,7 +676,9 @@ impl FlashLoader {
         }
 
         if options.verify {
+            check_cancelled(&cancellation)?;
             tracing::debug!("Verifying!");
+            let start = Instant::now();
             for (&address, data) in &self.builder.data {
                 tracing::debug!(
                     "    data: {:#010X}..{:#010X} ({} bytes)",
@@ -646,6 +703,9 @@ impl FlashLoader {
                     return Err(FlashError::Verify);
                 }
             }
+            session
+                .metrics_handle()
+                .record_flash_phase(FlashPhase::Verify, start.elapsed());
         }
 
         Ok(())
@@ -1,4 +1,6 @@
 use super::FlashLayout;
+use crate::events::Events;
+use crate::RegisterValue;
 use std::{sync::Arc, time::Duration};
 
 /// A structure to manage the flashing procedure progress reporting.
@@ -17,6 +19,7 @@ use std::{sync::Arc, time::Duration};
 #[derive(Clone)]
 pub struct FlashProgress {
     handler: Arc<dyn Fn(ProgressEvent)>,
+    debug_algorithm: bool,
 }
 
 impl FlashProgress {
@@ -24,6 +27,7 @@ impl FlashProgress {
     pub fn new(handler: impl Fn(ProgressEvent) + 'static) -> Self {
         Self {
             handler: Arc::new(handler),
+            debug_algorithm: false,
         }
     }
 
@@ -31,6 +35,38 @@ impl FlashProgress {
     pub fn empty() -> Self {
         Self {
             handler: Arc::new(|_| {}),
+            debug_algorithm: false,
+        }
+    }
+
+    /// Also reports the registers and the RAM of the core before and after each call to the flash
+    /// algorithm, with [`ProgressEvent::AlgorithmCalled`] and
+    /// [`ProgressEvent::AlgorithmReturned`].
+    ///
+    /// This is meant for developing flash algorithms, and slows down flashing considerably.
+    /// As the events are reported before the algorithm runs, the handler can also stop to step
+    /// through the calls.
+    pub fn with_algorithm_debugging(mut self) -> Self {
+        self.debug_algorithm = true;
+        self
+    }
+
+    /// Whether the calls to the flash algorithm are reported.
+    pub(super) fn debugs_algorithm(&self) -> bool {
+        self.debug_algorithm
+    }
+
+    /// Also reports the flash operations to the subscribers of a session.
+    // The handler is not `Send` either, the `Arc` only makes `FlashProgress` cheap to clone.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub(crate) fn with_events(self, events: Events) -> Self {
+        let handler = self.handler;
+        Self {
+            handler: Arc::new(move |event| {
+                events.flash_progress(&event);
+                handler(event);
+            }),
+            debug_algorithm: self.debug_algorithm,
         }
     }
 
@@ -116,6 +152,46 @@ impl FlashProgress {
     pub(super) fn message(&self, message: String) {
         self.emit(ProgressEvent::DiagnosticMessage { message });
     }
+
+    /// Signalize that a routine of the flash algorithm is about to be called.
+    pub(super) fn algorithm_called(
+        &self,
+        routine: &'static str,
+        entry_point: u64,
+        state: AlgorithmState,
+    ) {
+        self.emit(ProgressEvent::AlgorithmCalled {
+            routine,
+            entry_point,
+            state,
+        });
+    }
+
+    /// Signalize that a routine of the flash algorithm returned, or failed to.
+    pub(super) fn algorithm_returned(
+        &self,
+        routine: &'static str,
+        result: Option<u32>,
+        state: AlgorithmState,
+    ) {
+        self.emit(ProgressEvent::AlgorithmReturned {
+            routine,
+            result,
+            state,
+        });
+    }
+}
+
+/// The state of the core running the flash algorithm, reported when debugging the algorithm.
+///
+/// See [`FlashProgress::with_algorithm_debugging`].
+#[derive(Debug, Clone)]
+pub struct AlgorithmState {
+    /// The names and values of the core registers.
+    pub registers: Vec<(&'static str, RegisterValue)>,
+    /// The RAM of the algorithm, which contains its data and its stack, as start addresses and
+    /// contents.
+    pub memory: Vec<(u64, Vec<u8>)>,
 }
 
 /// Possible events during the flashing process.
@@ -202,4 +278,28 @@ pub enum ProgressEvent {
         /// The message that was emitted.
         message: String,
     },
+    /// A routine of the flash algorithm is about to be called.
+    ///
+    /// Only reported if enabled with [`FlashProgress::with_algorithm_debugging`].
+    AlgorithmCalled {
+        /// The name of the routine, e.g. `ProgramPage`.
+        routine: &'static str,
+        /// The address of the routine in RAM.
+        entry_point: u64,
+        /// The state of the core with the arguments of the call.
+        state: AlgorithmState,
+    },
+    /// A routine of the flash algorithm returned, or failed to return in time.
+    ///
+    /// Only reported if enabled with [`FlashProgress::with_algorithm_debugging`].
+    AlgorithmReturned {
+        /// The name of the routine, e.g. `ProgramPage`.
+        routine: &'static str,
+        /// The value returned by the routine, which is an error code if it's not 0. `None` if
+        /// the routine didn't return, e.g. because it timed out or the core locked up.
+        result: Option<u32>,
+        /// The state of the core after the call. If the routine didn't return, the core was
+        /// halted wherever it was.
+        state: AlgorithmState,
+    },
 }
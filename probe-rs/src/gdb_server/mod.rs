@@ -4,4 +4,4 @@ mod arch;
 mod stub;
 mod target;
 
-pub use stub::{run, GdbInstanceConfiguration};
+pub use stub::{run, GdbInstanceConfiguration, GdbServer};
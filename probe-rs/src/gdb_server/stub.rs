@@ -2,6 +2,7 @@ use crate::{CoreType, Session};
 use parking_lot::FairMutex;
 
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use itertools::Itertools;
@@ -11,6 +12,7 @@ use super::target;
 const CONNECTION_STRING: &str = "127.0.0.1:1337";
 
 /// Configuration for a single GDB endpoint
+#[derive(Debug, Clone)]
 pub struct GdbInstanceConfiguration {
     /// The core type that will be sent to GDB
     pub core_type: CoreType,
@@ -84,28 +86,58 @@ pub fn run<'a>(
     session: &FairMutex<Session>,
     instances: impl Iterator<Item = &'a GdbInstanceConfiguration>,
 ) -> anyhow::Result<()> {
-    // Turn our group list into GDB targets
-    let mut targets = instances
-        .map(|instance| {
-            target::RuntimeTarget::new(session, instance.cores.to_vec(), &instance.socket_addrs[..])
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+    GdbServer::new(session, instances)?.run_until(&AtomicBool::new(false))
+}
+
+/// A GDB server, which listens on the addresses of its instances.
+///
+/// The session is only locked while a GDB request is handled, so it can be shared with other
+/// users, e.g. a DAP server for the same session. Those see the changes made by GDB clients, like
+/// halting a core, and the other way around.
+pub struct GdbServer<'a> {
+    targets: Vec<target::RuntimeTarget<'a>>,
+}
 
-    // Avoid getting stuck in an infinite loop if we have no targets
-    if targets.is_empty() {
-        return Ok(());
+impl<'a> GdbServer<'a> {
+    /// Create a GDB server, and bind the sockets of the instances.
+    pub fn new<'i>(
+        session: &'a FairMutex<Session>,
+        instances: impl Iterator<Item = &'i GdbInstanceConfiguration>,
+    ) -> anyhow::Result<Self> {
+        // Turn our group list into GDB targets
+        let targets = instances
+            .map(|instance| {
+                target::RuntimeTarget::new(
+                    session,
+                    instance.cores.to_vec(),
+                    &instance.socket_addrs[..],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { targets })
     }
 
-    // Process every target in a loop
-    loop {
-        let mut wait_time = Duration::MAX;
+    /// Handle GDB connections and requests until `stop` is set.
+    pub fn run_until(&mut self, stop: &AtomicBool) -> anyhow::Result<()> {
+        // Avoid getting stuck in an infinite loop if we have no targets
+        if self.targets.is_empty() {
+            return Ok(());
+        }
+
+        // Process every target in a loop
+        while !stop.load(Ordering::Relaxed) {
+            let mut wait_time = Duration::MAX;
+
+            for target in self.targets.iter_mut() {
+                wait_time = wait_time.min(target.process()?);
+            }
 
-        for target in targets.iter_mut() {
-            wait_time = wait_time.min(target.process()?);
+            // Wait until we were asked to check again
+            std::thread::sleep(wait_time);
         }
 
-        // Wait until we were asked to check again
-        std::thread::sleep(wait_time);
+        Ok(())
     }
 }
 
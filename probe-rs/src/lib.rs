@@ -69,6 +69,8 @@
 #![cfg_attr(probers_docsrs, feature(doc_cfg))] // Used for docs.rs
 
 pub mod architecture;
+mod async_session;
+mod cancellation;
 pub mod config;
 pub mod vendor;
 
@@ -77,31 +79,42 @@ mod core;
 #[cfg_attr(probers_docsrs, doc(cfg(feature = "debug")))]
 pub mod debug;
 mod error;
+pub mod events;
 pub mod flashing;
 #[cfg(feature = "gdb-server")]
 #[cfg_attr(probers_docsrs, doc(cfg(feature = "gdb-server")))]
 pub mod gdb_server;
 pub mod integration;
+pub mod measurement;
 mod memory;
+pub mod memory_init;
+pub mod metrics;
 pub mod probe;
+pub mod provisioning;
 pub mod rtt;
 mod semihosting;
 mod session;
+mod shared_session;
 #[cfg(test)]
 mod test;
 
+pub use crate::async_session::AsyncSession;
+pub use crate::cancellation::CancellationToken;
 pub use crate::config::{CoreType, InstructionSet, Target};
+pub use crate::core::snapshot::CoreSnapshot;
 pub use crate::core::{
     Architecture, BreakpointCause, Core, CoreInformation, CoreInterface, CoreRegister,
-    CoreRegisters, CoreState, CoreStatus, HaltReason, MemoryMappedRegister, RegisterId,
-    RegisterRole, RegisterValue, SpecificCoreState, VectorCatchCondition,
+    CoreRegisters, CoreState, CoreStatus, HaltReason, InterruptMasking, MemoryMappedRegister,
+    RegisterDataType, RegisterId, RegisterRole, RegisterValue, ResetStrategy, SpecificCoreState,
+    VectorCatchCondition,
 };
 pub use crate::error::Error;
 pub use crate::memory::MemoryInterface;
 pub use crate::semihosting::{
     ExitErrorDetails, GetCommandLineRequest, SemihostingCommand, UnknownCommandDetails,
 };
-pub use crate::session::{Permissions, Session};
+pub use crate::session::{BreakpointPlacement, Permissions, Session};
+pub use crate::shared_session::{CoreHandle, SharedSession};
 
 #[cfg(feature = "debug")]
 pub use crate::core::dump::{CoreDump, CoreDumpError};
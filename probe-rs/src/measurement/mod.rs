@@ -0,0 +1,105 @@
+//! Measuring the current consumption of the target.
+//!
+//! Current meters are separate USB devices which are connected between the power supply and
+//! the target. Use [`open_current_meter`] to open the first one which is connected.
+
+pub mod ppk2;
+
+pub use ppk2::Ppk2;
+
+/// A device which measures the current consumption of the target.
+pub trait CurrentMeter: Send + std::fmt::Debug {
+    /// Start measuring.
+    fn start(&mut self) -> Result<(), MeasurementError>;
+
+    /// Stop measuring.
+    fn stop(&mut self) -> Result<(), MeasurementError>;
+
+    /// Returns the samples measured since the last call, in Ampere.
+    ///
+    /// This has to be called often enough to not lose samples, e.g. from a separate thread.
+    fn read_samples(&mut self) -> Result<Vec<f32>, MeasurementError>;
+
+    /// The number of samples measured per second.
+    fn sample_rate(&self) -> u32;
+}
+
+/// Opens the first supported current meter which is connected.
+pub fn open_current_meter() -> Result<Box<dyn CurrentMeter>, MeasurementError> {
+    Ok(Box::new(Ppk2::open()?))
+}
+
+/// An error which occurred while measuring the current.
+#[derive(Debug, thiserror::Error, docsplay::Display)]
+pub enum MeasurementError {
+    /// No supported current meter was found.
+    NotFound,
+
+    /// The USB communication with the current meter failed.
+    Usb(#[from] std::io::Error),
+
+    /// The current meter sent invalid calibration data: {0}
+    InvalidCalibration(String),
+}
+
+/// Summary statistics of current samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CurrentStatistics {
+    /// The number of samples.
+    pub count: u64,
+    /// The lowest current in Ampere.
+    pub min: f32,
+    /// The highest current in Ampere.
+    pub max: f32,
+    sum: f64,
+}
+
+impl CurrentStatistics {
+    /// Adds samples in Ampere.
+    pub fn add(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            if self.count == 0 {
+                self.min = sample;
+                self.max = sample;
+            } else {
+                self.min = self.min.min(sample);
+                self.max = self.max.max(sample);
+            }
+            self.count += 1;
+            self.sum += f64::from(sample);
+        }
+    }
+
+    /// The average current in Ampere.
+    pub fn average(&self) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        (self.sum / self.count as f64) as f32
+    }
+
+    /// The charge consumed in Coulomb, if the samples were measured at the given sample rate.
+    pub fn charge(&self, sample_rate: u32) -> f64 {
+        self.sum / f64::from(sample_rate)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn statistics() {
+        let mut statistics = CurrentStatistics::default();
+        assert_eq!(statistics.average(), 0.0);
+
+        statistics.add(&[0.002, 0.001]);
+        statistics.add(&[0.003]);
+
+        assert_eq!(statistics.count, 3);
+        assert_eq!(statistics.min, 0.001);
+        assert_eq!(statistics.max, 0.003);
+        assert!((statistics.average() - 0.002).abs() < 1e-9);
+        assert!((statistics.charge(1000) - 0.000_006).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,325 @@
+//! Driver for the Nordic Power Profiler Kit II (PPK2).
+//!
+//! The PPK2 is used in ampere meter mode, where the target is powered externally and the
+//! current flows through the PPK2.
+
+use std::time::Duration;
+
+use nusb::{
+    transfer::{Direction, EndpointType},
+    Interface,
+};
+
+use super::{CurrentMeter, MeasurementError};
+use crate::probe::usb_util::InterfaceExt;
+
+const VENDOR_ID: u16 = 0x1915;
+const PRODUCT_ID: u16 = 0xc00a;
+
+/// USB class of the CDC data interface.
+const CDC_DATA_CLASS: u8 = 0x0a;
+
+const TIMEOUT: Duration = Duration::from_millis(1000);
+const READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+const SAMPLE_RATE: u32 = 100_000;
+
+/// The number of current ranges the PPK2 switches between.
+const RANGES: usize = 5;
+
+/// Volts per ADC step.
+const ADC_MULT: f32 = 1.8 / 163_840.0;
+
+mod commands {
+    pub const AVERAGE_START: u8 = 0x06;
+    pub const AVERAGE_STOP: u8 = 0x07;
+    pub const SET_POWER_MODE: u8 = 0x11;
+    pub const GET_META_DATA: u8 = 0x19;
+
+    pub const POWER_MODE_AMPERE_METER: u8 = 0x01;
+}
+
+/// The calibration data of a PPK2, read from the device.
+#[derive(Debug, Clone, PartialEq)]
+struct Calibration {
+    r: [f32; RANGES],
+    gs: [f32; RANGES],
+    gi: [f32; RANGES],
+    o: [f32; RANGES],
+    s: [f32; RANGES],
+    i: [f32; RANGES],
+    ug: [f32; RANGES],
+    /// The supply voltage in mV.
+    vdd: f32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            r: [1031.64, 101.65, 10.15, 0.94, 0.043],
+            gs: [0.0; RANGES],
+            gi: [1.0; RANGES],
+            o: [0.0; RANGES],
+            s: [0.0; RANGES],
+            i: [0.0; RANGES],
+            ug: [1.0; RANGES],
+            vdd: 3000.0,
+        }
+    }
+}
+
+impl Calibration {
+    /// Parses the metadata, which consists of `key: value` lines.
+    fn parse(metadata: &str) -> Result<Self, MeasurementError> {
+        let mut calibration = Self::default();
+
+        for line in metadata.lines() {
+            let Some((key, value)) = line.split_once(": ") else {
+                continue;
+            };
+
+            if key == "vdd" {
+                calibration.vdd = parse_value(key, value)?;
+                continue;
+            }
+
+            let Some(index) = key.find(|c: char| c.is_ascii_digit()) else {
+                continue;
+            };
+            let Ok(range) = key[index..].parse::<usize>() else {
+                continue;
+            };
+            let field = match &key[..index] {
+                "R" => &mut calibration.r,
+                "GS" => &mut calibration.gs,
+                "GI" => &mut calibration.gi,
+                "O" => &mut calibration.o,
+                "S" => &mut calibration.s,
+                "I" => &mut calibration.i,
+                "UG" => &mut calibration.ug,
+                _ => continue,
+            };
+            if range < RANGES {
+                field[range] = parse_value(key, value)?;
+            }
+        }
+
+        Ok(calibration)
+    }
+
+    /// Converts a raw sample to the current in Ampere.
+    fn current(&self, sample: u32) -> f32 {
+        let adc = (sample & 0x3fff) as f32 * 4.0;
+        let range = ((sample >> 14) & 0x7).min(RANGES as u32 - 1) as usize;
+
+        let without_gain = (adc - self.o[range]) * (ADC_MULT / self.r[range]);
+        self.ug[range]
+            * (without_gain * (self.gs[range] * without_gain + self.gi[range])
+                + (self.s[range] * (self.vdd / 1000.0) + self.i[range]))
+    }
+}
+
+fn parse_value(key: &str, value: &str) -> Result<f32, MeasurementError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| MeasurementError::InvalidCalibration(format!("{key}: {value}")))
+}
+
+/// A Nordic Power Profiler Kit II.
+pub struct Ppk2 {
+    interface: Interface,
+    endpoint_in: u8,
+    endpoint_out: u8,
+    calibration: Calibration,
+    /// Bytes of an incomplete sample.
+    buffer: Vec<u8>,
+}
+
+impl std::fmt::Debug for Ppk2 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ppk2")
+            .field("calibration", &self.calibration)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Ppk2 {
+    /// Opens the first PPK2 and switches it to ampere meter mode.
+    pub fn open() -> Result<Self, MeasurementError> {
+        let device = nusb::list_devices()?
+            .find(|device| device.vendor_id() == VENDOR_ID && device.product_id() == PRODUCT_ID)
+            .ok_or(MeasurementError::NotFound)?;
+
+        let device_handle = device.open()?;
+
+        let mut endpoints = None;
+        if let Some(config) = device_handle.configurations().next() {
+            for altsetting in config.interface_alt_settings() {
+                if altsetting.class() != CDC_DATA_CLASS {
+                    continue;
+                }
+
+                let bulk = |direction| {
+                    altsetting
+                        .endpoints()
+                        .find(|ep| {
+                            ep.transfer_type() == EndpointType::Bulk && ep.direction() == direction
+                        })
+                        .map(|ep| ep.address())
+                };
+
+                if let (Some(endpoint_in), Some(endpoint_out)) =
+                    (bulk(Direction::In), bulk(Direction::Out))
+                {
+                    endpoints = Some((altsetting.interface_number(), endpoint_in, endpoint_out));
+                    break;
+                }
+            }
+        }
+
+        let Some((interface_number, endpoint_in, endpoint_out)) = endpoints else {
+            return Err(MeasurementError::NotFound);
+        };
+
+        // The serial port is usually claimed by the CDC ACM driver of the OS.
+        let interface = device_handle.detach_and_claim_interface(interface_number)?;
+
+        let mut ppk2 = Self {
+            interface,
+            endpoint_in,
+            endpoint_out,
+            calibration: Calibration::default(),
+            buffer: Vec::new(),
+        };
+
+        // Make sure no measurement is running from a previous session.
+        ppk2.command(&[commands::AVERAGE_STOP])?;
+        ppk2.drain();
+
+        ppk2.calibration = Calibration::parse(&ppk2.read_metadata()?)?;
+        tracing::debug!("PPK2 calibration: {:?}", ppk2.calibration);
+
+        ppk2.command(&[commands::SET_POWER_MODE, commands::POWER_MODE_AMPERE_METER])?;
+
+        Ok(ppk2)
+    }
+
+    fn command(&mut self, command: &[u8]) -> Result<(), MeasurementError> {
+        self.interface
+            .write_bulk(self.endpoint_out, command, TIMEOUT)?;
+        Ok(())
+    }
+
+    /// Discards any data which is still in flight.
+    fn drain(&mut self) {
+        let mut buf = [0; 4096];
+        while let Ok(read) = self
+            .interface
+            .read_bulk(self.endpoint_in, &mut buf, READ_TIMEOUT)
+        {
+            if read == 0 {
+                break;
+            }
+        }
+    }
+
+    fn read_metadata(&mut self) -> Result<String, MeasurementError> {
+        self.command(&[commands::GET_META_DATA])?;
+
+        let mut metadata = Vec::new();
+        while !metadata.ends_with(b"END\n") && !metadata.ends_with(b"END") {
+            let mut buf = [0; 64];
+            let read = self
+                .interface
+                .read_bulk(self.endpoint_in, &mut buf, TIMEOUT)?;
+            metadata.extend_from_slice(&buf[..read]);
+        }
+
+        Ok(String::from_utf8_lossy(&metadata).into_owned())
+    }
+}
+
+impl CurrentMeter for Ppk2 {
+    fn start(&mut self) -> Result<(), MeasurementError> {
+        self.buffer.clear();
+        self.command(&[commands::AVERAGE_START])
+    }
+
+    fn stop(&mut self) -> Result<(), MeasurementError> {
+        self.command(&[commands::AVERAGE_STOP])
+    }
+
+    fn read_samples(&mut self) -> Result<Vec<f32>, MeasurementError> {
+        let mut buf = vec![0; 16 * 1024];
+        let read = match self
+            .interface
+            .read_bulk(self.endpoint_in, &mut buf, READ_TIMEOUT)
+        {
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => 0,
+            Err(e) => return Err(e.into()),
+        };
+        self.buffer.extend_from_slice(&buf[..read]);
+
+        let complete = self.buffer.len() / 4 * 4;
+        let samples = self.buffer[..complete]
+            .chunks_exact(4)
+            .map(|sample| {
+                self.calibration
+                    .current(u32::from_le_bytes(sample.try_into().unwrap()))
+            })
+            .collect();
+        self.buffer.drain(..complete);
+
+        Ok(samples)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+}
+
+impl Drop for Ppk2 {
+    fn drop(&mut self) {
+        // We ignore the error as we can't do much about it anyways.
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_calibration() {
+        let metadata = "Calibrated: 0\nR0: 1003.3293\nR1: 101.5\nGS0: 0.0001\nGI0: 1.01\nO0: 112.5\nS0: 0.00002\nI0: -0.0000004\nUG0: 0.99\nvdd: 3300\nHW: 9173\nIA: 56\nEND\n";
+        let calibration = Calibration::parse(metadata).unwrap();
+
+        assert_eq!(calibration.r[0], 1003.3293);
+        assert_eq!(calibration.r[1], 101.5);
+        assert_eq!(calibration.r[2], 10.15);
+        assert_eq!(calibration.gs[0], 0.0001);
+        assert_eq!(calibration.gi[0], 1.01);
+        assert_eq!(calibration.o[0], 112.5);
+        assert_eq!(calibration.ug[0], 0.99);
+        assert_eq!(calibration.vdd, 3300.0);
+
+        assert!(Calibration::parse("R0: abc").is_err());
+    }
+
+    #[test]
+    fn convert_sample() {
+        let calibration = Calibration::default();
+
+        // 1000 ADC steps in the lowest current range.
+        let sample = 1000;
+        let expected = 4000.0 * ADC_MULT / 1031.64;
+        assert!((calibration.current(sample) - expected).abs() < 1e-9);
+
+        // The range is taken from bits 14 to 16.
+        let sample = 1000 | (4 << 14);
+        let expected = 4000.0 * ADC_MULT / 0.043;
+        assert!((calibration.current(sample) - expected).abs() < 1e-6);
+    }
+}
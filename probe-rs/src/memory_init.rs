@@ -0,0 +1,185 @@
+//! Initialization of external memory, like the SDRAM behind the FMC of STM32 chips.
+//!
+//! External memory can only be accessed after its controller was configured, which is usually done
+//! by the firmware. To dump or load such memory without the firmware, the target description can
+//! contain [`MemoryInitSequence`]s, which configure the controller with register writes, or by
+//! running code in internal RAM. [`init_memory`] runs the sequences covering a range, and is
+//! called before RAM is loaded by the [`flashing`](crate::flashing) module.
+//!
+//! ```no_run
+//! # fn example(session: &mut probe_rs::Session) -> Result<(), probe_rs::memory_init::MemoryInitError> {
+//! use probe_rs::{memory_init, MemoryInterface};
+//!
+//! memory_init::init_memory(session, &(0xC000_0000..0xC000_1000))?;
+//!
+//! let mut data = [0; 0x1000];
+//! session.core(0)?.read(0xC000_0000, &mut data)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::{
+    config::{MemoryInitSequence, MemoryInitStep},
+    Core, Error, MemoryInterface, Session,
+};
+
+/// An error while running a memory init sequence.
+#[derive(Debug, thiserror::Error)]
+pub enum MemoryInitError {
+    /// A register did not reach the expected value in time.
+    #[error("Memory init sequence {sequence} timed out waiting for {address:#010x}.")]
+    PollTimeout {
+        /// The name of the sequence.
+        sequence: String,
+        /// The address of the register.
+        address: u64,
+    },
+    /// The code run by the sequence did not halt the core in time.
+    #[error("The code of memory init sequence {0} did not halt the core in time.")]
+    RunTimeout(String),
+    /// Accessing the target failed.
+    #[error("Failed to access the target.")]
+    Probe(#[from] Error),
+}
+
+/// Runs the memory init sequences of the target which cover `range`, so it can be accessed.
+///
+/// The sequences are run on the core which has access to the start of the range. Ranges which
+/// are not covered by any sequence don't need initialization, so nothing is done for them.
+pub fn init_memory(session: &mut Session, range: &Range<u64>) -> Result<(), MemoryInitError> {
+    let sequences: Vec<_> = session
+        .target()
+        .memory_init_sequences
+        .iter()
+        .filter(|sequence| sequence.covers(range))
+        .cloned()
+        .collect();
+    if sequences.is_empty() {
+        return Ok(());
+    }
+
+    let core_index = session
+        .target()
+        .get_memory_region_by_address(range.start)
+        .and_then(|region| region.cores().first())
+        .and_then(|core| session.target().core_index_by_name(core))
+        .unwrap_or(0);
+    let mut core = session.core(core_index)?;
+
+    for sequence in &sequences {
+        run_sequence(&mut core, sequence)?;
+    }
+
+    Ok(())
+}
+
+/// Runs the steps of a memory init sequence on the core.
+pub fn run_sequence(core: &mut Core, sequence: &MemoryInitSequence) -> Result<(), MemoryInitError> {
+    tracing::info!("Running memory init sequence {}", sequence.name);
+
+    for step in &sequence.steps {
+        match *step {
+            MemoryInitStep::Write { address, value } => core.write_word_32(address, value)?,
+            MemoryInitStep::Modify {
+                address,
+                mask,
+                value,
+            } => {
+                let current = core.read_word_32(address)?;
+                core.write_word_32(address, (current & !mask) | (value & mask))?;
+            }
+            MemoryInitStep::Poll {
+                address,
+                mask,
+                value,
+                timeout_ms,
+            } => {
+                let start = Instant::now();
+                while core.read_word_32(address)? & mask != value & mask {
+                    if start.elapsed() > Duration::from_millis(timeout_ms) {
+                        return Err(MemoryInitError::PollTimeout {
+                            sequence: sequence.name.clone(),
+                            address,
+                        });
+                    }
+                }
+            }
+            MemoryInitStep::Delay { us } => std::thread::sleep(Duration::from_micros(us)),
+            MemoryInitStep::Run {
+                load_address,
+                ref instructions,
+                stack_pointer,
+                timeout_ms,
+            } => {
+                if !core.core_halted()? {
+                    core.halt(Duration::from_millis(100))?;
+                }
+                core.write(load_address, instructions)?;
+                core.write_core_reg(core.program_counter(), load_address)?;
+                if let Some(stack_pointer) = stack_pointer {
+                    core.write_core_reg(core.stack_pointer(), stack_pointer)?;
+                }
+
+                // The code ends with a breakpoint instruction, which has to halt the core.
+                core.debug_on_sw_breakpoint(true)?;
+                core.run()?;
+                match core.wait_for_core_halted(Duration::from_millis(timeout_ms)) {
+                    Ok(()) => {}
+                    Err(Error::Timeout) => {
+                        core.halt(Duration::from_millis(100))?;
+                        return Err(MemoryInitError::RunTimeout(sequence.name.clone()));
+                    }
+                    Err(error) => return Err(error.into()),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sequences_are_parsed_from_target_descriptions() {
+        let sequence: MemoryInitSequence = serde_yaml::from_str(
+            "
+name: SDRAM
+ranges:
+  - start: 0xC0000000
+    end: 0xC2000000
+steps:
+  - !Write { address: 0x52004000, value: 0x1 }
+  - !Delay { us: 100 }
+  - !Poll { address: 0x52004158, mask: 0x20, value: 0x0 }
+  - !Run { load_address: 0x20000000, instructions: AL4= }
+",
+        )
+        .unwrap();
+
+        assert!(sequence.covers(&(0xC000_0000..0xC000_1000)));
+        assert_eq!(
+            sequence.steps[2],
+            MemoryInitStep::Poll {
+                address: 0x5200_4158,
+                mask: 0x20,
+                value: 0,
+                timeout_ms: 1000,
+            }
+        );
+        assert_eq!(
+            sequence.steps[3],
+            MemoryInitStep::Run {
+                load_address: 0x2000_0000,
+                instructions: vec![0x00, 0xbe],
+                stack_pointer: None,
+                timeout_ms: 1000,
+            }
+        );
+    }
+}
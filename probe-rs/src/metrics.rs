@@ -0,0 +1,195 @@
+//! Transfer statistics, to diagnose slow setups.
+//!
+//! The probe drivers, the ARM debug interface and the flash loader count what they do in a
+//! [`Metrics`] handle, which is shared by everything using the same probe. The current values
+//! are available from [`Session::metrics`](crate::Session::metrics).
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// A shared handle to the transfer counters of a probe.
+///
+/// Cloning the handle is cheap, all clones count into the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    dap_transactions: AtomicU64,
+    retries: AtomicU64,
+    wait_responses: AtomicU64,
+    probe_round_trips: AtomicU64,
+    flash_erase_us: AtomicU64,
+    flash_fill_us: AtomicU64,
+    flash_program_us: AtomicU64,
+    flash_verify_us: AtomicU64,
+}
+
+/// A phase of the flashing process, timed separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlashPhase {
+    Erase,
+    Fill,
+    Program,
+    Verify,
+}
+
+impl Metrics {
+    /// Creates a new handle with all counters set to zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current values of all counters.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let c = &self.counters;
+        let duration = |us: &AtomicU64| Duration::from_micros(us.load(Ordering::Relaxed));
+
+        MetricsSnapshot {
+            bytes_read: c.bytes_read.load(Ordering::Relaxed),
+            bytes_written: c.bytes_written.load(Ordering::Relaxed),
+            dap_transactions: c.dap_transactions.load(Ordering::Relaxed),
+            retries: c.retries.load(Ordering::Relaxed),
+            wait_responses: c.wait_responses.load(Ordering::Relaxed),
+            probe_round_trips: c.probe_round_trips.load(Ordering::Relaxed),
+            flash_erase_time: duration(&c.flash_erase_us),
+            flash_fill_time: duration(&c.flash_fill_us),
+            flash_program_time: duration(&c.flash_program_us),
+            flash_verify_time: duration(&c.flash_verify_us),
+        }
+    }
+
+    /// Sets all counters back to zero.
+    pub fn reset(&self) {
+        let c = &self.counters;
+        for counter in [
+            &c.bytes_read,
+            &c.bytes_written,
+            &c.dap_transactions,
+            &c.retries,
+            &c.wait_responses,
+            &c.probe_round_trips,
+            &c.flash_erase_us,
+            &c.flash_fill_us,
+            &c.flash_program_us,
+            &c.flash_verify_us,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn add(counter: &AtomicU64, value: usize) {
+        counter.fetch_add(value as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_read(&self, bytes: usize) {
+        Self::add(&self.counters.bytes_read, bytes);
+    }
+
+    pub(crate) fn record_write(&self, bytes: usize) {
+        Self::add(&self.counters.bytes_written, bytes);
+    }
+
+    pub(crate) fn record_dap_transactions(&self, count: usize) {
+        Self::add(&self.counters.dap_transactions, count);
+    }
+
+    pub(crate) fn record_retry(&self) {
+        Self::add(&self.counters.retries, 1);
+    }
+
+    pub(crate) fn record_wait_response(&self) {
+        Self::add(&self.counters.wait_responses, 1);
+    }
+
+    pub(crate) fn record_round_trips(&self, count: usize) {
+        Self::add(&self.counters.probe_round_trips, count);
+    }
+
+    pub(crate) fn record_flash_phase(&self, phase: FlashPhase, duration: Duration) {
+        let counter = match phase {
+            FlashPhase::Erase => &self.counters.flash_erase_us,
+            FlashPhase::Fill => &self.counters.flash_fill_us,
+            FlashPhase::Program => &self.counters.flash_program_us,
+            FlashPhase::Verify => &self.counters.flash_verify_us,
+        };
+        counter.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// The values of the transfer counters at one point in time, see [`Metrics::snapshot`].
+///
+/// Not every probe reports every counter, e.g. probes which handle the DAP protocol on their
+/// own don't report WAIT responses or DAP transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Bytes read through ARM memory APs.
+    pub bytes_read: u64,
+    /// Bytes written through ARM memory APs.
+    pub bytes_written: u64,
+    /// DP and AP register accesses.
+    pub dap_transactions: u64,
+    /// Transfers which were repeated by the probe driver after a WAIT or FAULT response.
+    pub retries: u64,
+    /// WAIT responses received from the target.
+    pub wait_responses: u64,
+    /// Commands sent to the probe, each of which usually needs a USB round trip.
+    pub probe_round_trips: u64,
+    /// Time spent erasing flash.
+    pub flash_erase_time: Duration,
+    /// Time spent reading flash contents which have to be restored after erasing.
+    pub flash_fill_time: Duration,
+    /// Time spent programming flash.
+    pub flash_program_time: Duration,
+    /// Time spent verifying flash contents.
+    pub flash_verify_time: Duration,
+}
+
+impl fmt::Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Bytes read:         {}", self.bytes_read)?;
+        writeln!(f, "Bytes written:      {}", self.bytes_written)?;
+        writeln!(f, "DAP transactions:   {}", self.dap_transactions)?;
+        writeln!(f, "Retries:            {}", self.retries)?;
+        writeln!(f, "WAIT responses:     {}", self.wait_responses)?;
+        writeln!(f, "Probe round trips:  {}", self.probe_round_trips)?;
+        writeln!(f, "Flash erase time:   {:.3?}", self.flash_erase_time)?;
+        writeln!(f, "Flash fill time:    {:.3?}", self.flash_fill_time)?;
+        writeln!(f, "Flash program time: {:.3?}", self.flash_program_time)?;
+        write!(f, "Flash verify time:  {:.3?}", self.flash_verify_time)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clones_share_counters() {
+        let metrics = Metrics::new();
+        let clone = metrics.clone();
+
+        metrics.record_read(16);
+        clone.record_read(4);
+        clone.record_wait_response();
+        clone.record_flash_phase(FlashPhase::Erase, Duration::from_millis(5));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.bytes_read, 20);
+        assert_eq!(snapshot.wait_responses, 1);
+        assert_eq!(snapshot.flash_erase_time, Duration::from_millis(5));
+
+        metrics.reset();
+        assert_eq!(clone.snapshot(), MetricsSnapshot::default());
+    }
+}
@@ -3,14 +3,22 @@ pub(crate) mod arm_debug_interface;
 pub(crate) mod common;
 pub(crate) mod usb_util;
 
+pub mod bitbang;
+pub mod blackmagic;
+pub mod ch347;
 pub mod cmsisdap;
 pub mod espusbjtag;
 pub mod fake_probe;
 pub mod ftdi;
+pub mod gpio;
 pub mod jlink;
 pub mod list;
+pub mod recording;
+pub mod remote;
+pub(crate) mod speed_fallback;
 pub mod stlink;
 pub mod wlink;
+pub mod xds110;
 
 use crate::architecture::arm::sequences::{ArmDebugSequence, DefaultArmSequence};
 use crate::architecture::arm::ArmError;
@@ -24,6 +32,7 @@ use crate::architecture::xtensa::communication_interface::{
 };
 use crate::config::RegistryError;
 use crate::config::TargetSelector;
+use crate::metrics::Metrics;
 use crate::probe::common::IdCode;
 use crate::{Error, Permissions, Session};
 use common::ScanChainError;
@@ -34,6 +43,8 @@ use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 
+pub use speed_fallback::SpeedFallback;
+
 /// Used to log warnings when the measured target voltage is
 /// lower than 1.4V, if at all measurable.
 const LOW_TARGET_VOLTAGE_WARNING_THRESHOLD: f32 = 1.4;
@@ -233,32 +244,57 @@ pub enum ProbeCreationError {
 pub struct Probe {
     inner: Box<dyn DebugProbe>,
     attached: bool,
+    speed_fallback: Option<SpeedFallback>,
+    metrics: Metrics,
 }
 
 impl Probe {
+    /// Below this voltage, the target is considered to be unpowered.
+    const MIN_TARGET_VOLTAGE: f32 = 1.0;
+
     /// Create a new probe from a more specific probe driver.
     pub fn new(probe: impl DebugProbe + 'static) -> Self {
-        Self {
-            inner: Box::new(probe),
-            attached: false,
-        }
+        Self::from_specific_probe(Box::new(probe))
     }
 
     pub(crate) fn from_attached_probe(probe: Box<dyn DebugProbe>) -> Self {
         Self {
-            inner: probe,
             attached: true,
+            ..Self::from_specific_probe(probe)
         }
     }
 
     /// Same as [`Probe::new`] but without automatic boxing in case you already have a box.
-    pub fn from_specific_probe(probe: Box<dyn DebugProbe>) -> Self {
+    pub fn from_specific_probe(mut probe: Box<dyn DebugProbe>) -> Self {
+        let metrics = Metrics::new();
+        probe.set_metrics(metrics.clone());
+
         Probe {
             inner: probe,
             attached: false,
+            speed_fallback: None,
+            metrics,
         }
     }
 
+    /// Wraps the probe in a [`RecordingProbe`](recording::RecordingProbe), which writes
+    /// all probe operations to `recording`.
+    ///
+    /// This is only supported for probes offering DAP access.
+    pub fn record(
+        self,
+        recording: impl std::io::Write + Send + 'static,
+    ) -> Result<Self, DebugProbeError> {
+        let probe = recording::RecordingProbe::new(self.inner, Box::new(recording))?;
+
+        Ok(Probe {
+            inner: Box::new(probe),
+            attached: self.attached,
+            speed_fallback: self.speed_fallback,
+            metrics: self.metrics,
+        })
+    }
+
     /// Get the human readable name for the probe.
     pub fn get_name(&self) -> String {
         self.inner.get_name().to_string()
@@ -375,6 +411,29 @@ impl Probe {
         self.inner.target_reset_deassert()
     }
 
+    /// Drives the pins in `pin_select` to the levels in `pin_out`, waits up to `pin_wait`
+    /// microseconds for them to settle, and returns the levels of all pins, like the
+    /// `DAP_SWJ_Pins` command of CMSIS-DAP.
+    ///
+    /// The bits of the masks are described by [`Pins`](crate::architecture::arm::Pins). With an
+    /// empty `pin_select`, this only reads the pins.
+    ///
+    /// This is not supported on all probes, some only support the nRESET pin.
+    pub fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let dap_probe =
+            self.try_as_dap_probe()
+                .ok_or(DebugProbeError::CommandNotSupportedByProbe {
+                    command_name: "swj_pins",
+                })?;
+
+        dap_probe.swj_pins(pin_out, pin_select, pin_wait)
+    }
+
     /// Configure protocol speed to use in kHz
     pub fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
         if !self.attached {
@@ -458,9 +517,15 @@ impl Probe {
         if !self.attached {
             Err((self, DebugProbeError::NotAttached))
         } else {
-            self.inner
+            let mut interface = self
+                .inner
                 .try_get_arm_interface()
-                .map_err(|(probe, err)| (Probe::from_attached_probe(probe), err))
+                .map_err(|(probe, err)| (Probe::from_attached_probe(probe), err))?;
+            if let Some(fallback) = self.speed_fallback {
+                interface.set_speed_fallback(fallback);
+            }
+            interface.set_metrics(self.metrics);
+            Ok(interface)
         }
     }
 
@@ -500,6 +565,13 @@ impl Probe {
         self.inner.get_swo_interface_mut()
     }
 
+    /// Gets a mutable UART interface from the debug probe.
+    ///
+    /// This does not work on all probes.
+    pub fn get_uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        self.inner.get_uart_interface_mut()
+    }
+
     /// Gets a DAP interface from the debug probe.
     ///
     /// This does not work on all probes.
@@ -514,10 +586,61 @@ impl Probe {
         self.inner.get_target_voltage()
     }
 
+    /// Returns whether the target appears to be unpowered, based on the target voltage.
+    ///
+    /// Returns `false` if the probe can't measure the target voltage.
+    pub fn target_unpowered(&mut self) -> bool {
+        matches!(self.get_target_voltage(), Ok(Some(voltage)) if voltage < Self::MIN_TARGET_VOLTAGE)
+    }
+
     /// Try to get a J-Link interface from the debug probe.
     pub fn try_into_jlink(&mut self) -> Result<&mut jlink::JLink, DebugProbeError> {
         self.inner.try_into_jlink()
     }
+
+    /// Switch the power supply of the target on or off.
+    ///
+    /// This only works with probes which can power the target.
+    pub fn set_target_power(&mut self, enabled: bool) -> Result<(), DebugProbeError> {
+        self.inner.set_target_power(enabled)
+    }
+
+    /// Lower the protocol speed when transfers to an ARM target keep failing, instead of failing
+    /// the operation every time.
+    ///
+    /// This is applied to the ARM interface created by [`Probe::try_into_arm_interface`], and
+    /// only works with probes which can change the speed while attached.
+    pub fn set_speed_fallback(&mut self, fallback: Option<SpeedFallback>) {
+        self.speed_fallback = fallback;
+    }
+
+    /// Returns the handle to the transfer counters of this probe.
+    ///
+    /// The counters keep running when the probe is attached to a [`Session`], see
+    /// [`Session::metrics`].
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Enable or disable adaptive clocking.
+    ///
+    /// With adaptive clocking, the probe waits for the target to return each JTAG clock edge
+    /// on the RTCK pin, so the clock follows the speed of the target. This makes slow targets,
+    /// or targets which change their clock e.g. when waking up from sleep, work reliably.
+    ///
+    /// This only works with JTAG, and requires a probe and target which support RTCK.
+    pub fn set_adaptive_clocking(&mut self, enabled: bool) -> Result<(), DebugProbeError> {
+        self.inner.set_adaptive_clocking(enabled)
+    }
+
+    /// Returns the capabilities and firmware details of the probe, as far as they are known.
+    pub fn details(&mut self) -> ProbeDetails {
+        let mut details = self.inner.details();
+        if details.target_voltage.is_none() {
+            details.target_voltage = self.get_target_voltage().ok().flatten();
+        }
+        details
+    }
 }
 
 /// An abstraction over a probe driver type.
@@ -690,6 +813,13 @@ pub trait DebugProbe: Send + fmt::Debug {
         None
     }
 
+    /// Get a mutable UART interface from the debug probe.
+    ///
+    /// This is not available on all debug probes.
+    fn get_uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        None
+    }
+
     /// Boxes itself.
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe>;
 
@@ -706,6 +836,36 @@ pub trait DebugProbe: Send + fmt::Debug {
         Ok(None)
     }
 
+    /// Switch the power supply of the target on or off.
+    ///
+    /// This is not available on all probes.
+    fn set_target_power(&mut self, _enabled: bool) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe {
+            command_name: "set_target_power",
+        })
+    }
+
+    /// Enable or disable adaptive clocking using the RTCK signal.
+    ///
+    /// This is not available on all probes.
+    fn set_adaptive_clocking(&mut self, _enabled: bool) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe {
+            command_name: "set_adaptive_clocking",
+        })
+    }
+
+    /// Sets the handle which the probe driver counts its transfers in.
+    ///
+    /// Drivers which don't collect any statistics ignore this.
+    fn set_metrics(&mut self, _metrics: Metrics) {}
+
+    /// Returns the capabilities and firmware details of the probe.
+    ///
+    /// The target voltage is filled in by [`Probe::details`] if it is left empty.
+    fn details(&mut self) -> ProbeDetails {
+        ProbeDetails::default()
+    }
+
     /// Try to get a J-Link interface from the debug probe.
     fn try_into_jlink(&mut self) -> Result<&mut jlink::JLink, DebugProbeError> {
         Err(DebugProbeError::Other(
@@ -743,6 +903,12 @@ pub struct DebugProbeInfo {
     /// This is necessary for composite HID devices.
     pub hid_interface: Option<u8>,
 
+    /// The USB port the probe is connected to, e.g. `1-3.2`, if known.
+    ///
+    /// Unlike the serial number, this is unique even for probes with identical or missing
+    /// serial numbers, as long as the probe stays connected to the same port.
+    pub usb_port: Option<String>,
+
     /// A reference to the [`ProbeFactory`] that created this info object.
     probe_factory: &'static dyn ProbeFactory,
 }
@@ -778,9 +944,16 @@ impl DebugProbeInfo {
             serial_number,
             probe_factory,
             hid_interface,
+            usb_port: None,
         }
     }
 
+    /// Records the USB port of the device the probe was found on.
+    pub(crate) fn with_usb_port(mut self, device: &DeviceInfo) -> Self {
+        self.usb_port = usb_port_path(device);
+        self
+    }
+
     /// Open the probe described by this `DebugProbeInfo`.
     pub fn open(&self) -> Result<Probe, DebugProbeError> {
         let selector = DebugProbeSelector::from(self);
@@ -802,13 +975,80 @@ impl DebugProbeInfo {
     }
 }
 
+/// Capabilities and firmware details of a debug probe, see [`Probe::details`].
+///
+/// Fields are empty if the probe does not report them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProbeDetails {
+    /// The firmware version of the probe.
+    pub firmware_version: Option<String>,
+    /// The wire protocols supported by the probe.
+    pub protocols: Vec<WireProtocol>,
+    /// The highest supported speed of the wire protocol in kHz.
+    pub max_speed_khz: Option<u32>,
+    /// The SWO capabilities, or `None` if the probe can't capture SWO data.
+    pub swo: Option<SwoCapabilities>,
+    /// The size of a packet sent to the probe in bytes.
+    pub packet_size: Option<usize>,
+    /// The number of packets the probe can buffer.
+    pub packet_count: Option<usize>,
+    /// The target voltage in Volts.
+    pub target_voltage: Option<f32>,
+    /// The probe supports adaptive clocking, see [`Probe::set_adaptive_clocking`].
+    pub adaptive_clocking: bool,
+}
+
+/// The SWO capture capabilities of a debug probe.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SwoCapabilities {
+    /// UART (NRZ) encoding is supported.
+    pub uart: bool,
+    /// Manchester encoding is supported.
+    pub manchester: bool,
+    /// The highest supported baud rate.
+    pub max_baud: Option<u32>,
+    /// The size of the SWO buffer of the probe in bytes.
+    pub buffer_size: Option<usize>,
+}
+
+/// Returns the path of the USB port the device is connected to, e.g. `1-3.2` for a device on
+/// port 2 of a hub connected to port 3 of bus 1.
+///
+/// This is not available on Windows.
+pub(crate) fn usb_port_path(device: &DeviceInfo) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        device.sysfs_path().file_name()?.to_str().map(String::from)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // The location ID contains the bus number in the top byte, followed by one nibble
+        // for each port on the way to the device.
+        let location = device.location_id();
+        let ports: Vec<_> = (0..6)
+            .rev()
+            .map(|nibble| (location >> (nibble * 4)) & 0xf)
+            .take_while(|&port| port != 0)
+            .map(|port| port.to_string())
+            .collect();
+        Some(format!("{}-{}", location >> 24, ports.join(".")))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = device;
+        None
+    }
+}
+
 /// An error which can occur while parsing a [`DebugProbeSelector`].
 #[derive(thiserror::Error, Debug, docsplay::Display)]
 pub enum DebugProbeSelectorParseError {
     /// Could not parse VID or PID: {0}
     ParseInt(#[from] std::num::ParseIntError),
 
-    /// The format of the selector is invalid. Please use a string in the form `VID:PID:<Serial>`, where Serial is optional.
+    /// The format of the selector is invalid. Please use a string in the form `VID:PID:<Serial>`, where Serial is optional, `usb:<Port>` or `ip:<Address>`.
     Format,
 }
 
@@ -819,6 +1059,14 @@ pub enum DebugProbeSelectorParseError {
 /// where the serial number is optional, and VID and PID are
 /// parsed as hexadecimal numbers.
 ///
+/// Network attached J-Link probes are selected with "ip:ADDRESS",
+/// where the address is a host name or IP address, optionally
+/// followed by a port.
+///
+/// Probes can also be selected by the USB port they are connected to
+/// with "usb:PORT", e.g. "usb:1-3.2", as shown by `probe-rs list`. This
+/// distinguishes probes with identical or missing serial numbers.
+///
 /// ## Example:
 ///
 /// ```
@@ -838,11 +1086,40 @@ pub struct DebugProbeSelector {
     pub product_id: u16,
     /// The the serial number of the debug probe to be used.
     pub serial_number: Option<String>,
+    /// The network address of a probe connected over TCP/IP.
+    ///
+    /// If this is set, the USB vendor and product id are not used.
+    pub ip_address: Option<String>,
+    /// The USB port the probe is connected to, see [`DebugProbeInfo::usb_port`].
+    ///
+    /// If this is set, the USB vendor and product id and the serial number are not used.
+    pub usb_port: Option<String>,
 }
 
 impl DebugProbeSelector {
+    /// Returns whether the selector matches the given probe.
+    pub fn matches_probe(&self, info: &DebugProbeInfo) -> bool {
+        if let Some(port) = &self.usb_port {
+            return info.usb_port.as_ref() == Some(port);
+        }
+
+        self.ip_address.is_none()
+            && info.vendor_id == self.vendor_id
+            && info.product_id == self.product_id
+            && self
+                .serial_number
+                .as_ref()
+                .map(|s| info.serial_number.as_ref() == Some(s))
+                .unwrap_or(true)
+    }
+
     pub(crate) fn matches(&self, info: &DeviceInfo) -> bool {
-        info.vendor_id() == self.vendor_id
+        if let Some(port) = &self.usb_port {
+            return usb_port_path(info).as_ref() == Some(port);
+        }
+
+        self.ip_address.is_none()
+            && info.vendor_id() == self.vendor_id
             && info.product_id() == self.product_id
             && self
                 .serial_number
@@ -855,6 +1132,34 @@ impl DebugProbeSelector {
 impl TryFrom<&str> for DebugProbeSelector {
     type Error = DebugProbeSelectorParseError;
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if let Some(address) = value.strip_prefix("ip:") {
+            if address.is_empty() {
+                return Err(DebugProbeSelectorParseError::Format);
+            }
+
+            return Ok(DebugProbeSelector {
+                vendor_id: 0,
+                product_id: 0,
+                serial_number: None,
+                ip_address: Some(address.to_string()),
+                usb_port: None,
+            });
+        }
+
+        if let Some(port) = value.strip_prefix("usb:") {
+            if port.is_empty() {
+                return Err(DebugProbeSelectorParseError::Format);
+            }
+
+            return Ok(DebugProbeSelector {
+                vendor_id: 0,
+                product_id: 0,
+                serial_number: None,
+                ip_address: None,
+                usb_port: Some(port.to_string()),
+            });
+        }
+
         // Split into at most 3 parts: VID, PID, Serial.
         // We limit the number of splits to allow for colons in the
         // serial number (EspJtag uses MAC address)
@@ -868,6 +1173,8 @@ impl TryFrom<&str> for DebugProbeSelector {
             vendor_id: u16::from_str_radix(vendor_id, 16)?,
             product_id: u16::from_str_radix(product_id, 16)?,
             serial_number,
+            ip_address: None,
+            usb_port: None,
         })
     }
 }
@@ -892,6 +1199,8 @@ impl From<DebugProbeInfo> for DebugProbeSelector {
             vendor_id: selector.vendor_id,
             product_id: selector.product_id,
             serial_number: selector.serial_number,
+            ip_address: None,
+            usb_port: selector.usb_port,
         }
     }
 }
@@ -902,6 +1211,8 @@ impl From<&DebugProbeInfo> for DebugProbeSelector {
             vendor_id: selector.vendor_id,
             product_id: selector.product_id,
             serial_number: selector.serial_number.clone(),
+            ip_address: None,
+            usb_port: selector.usb_port.clone(),
         }
     }
 }
@@ -914,6 +1225,13 @@ impl From<&DebugProbeSelector> for DebugProbeSelector {
 
 impl fmt::Display for DebugProbeSelector {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref address) = self.ip_address {
+            return write!(f, "ip:{address}");
+        }
+        if let Some(ref port) = self.usb_port {
+            return write!(f, "usb:{port}");
+        }
+
         write!(f, "{:04x}:{:04x}", self.vendor_id, self.product_id)?;
         if let Some(ref sn) = self.serial_number {
             write!(f, ":{sn}")?;
@@ -922,6 +1240,28 @@ impl fmt::Display for DebugProbeSelector {
     }
 }
 
+/// Access to a UART of the debug probe which is connected to the target.
+///
+/// This is used for probes which transfer the UART data over the same connection as the
+/// debug commands, so the UART can be used while debugging the target.
+pub trait UartAccess {
+    /// Configures the UART for 8N1 with the given baud rate and starts receiving.
+    ///
+    /// Returns the baud rate which was actually configured.
+    fn enable_uart(&mut self, baud_rate: u32) -> Result<u32, DebugProbeError>;
+
+    /// Stops the UART.
+    fn disable_uart(&mut self) -> Result<(), DebugProbeError>;
+
+    /// Reads the data received since the last call, without waiting.
+    ///
+    /// If no data was received, an empty Vec is returned.
+    fn read_uart(&mut self) -> Result<Vec<u8>, DebugProbeError>;
+
+    /// Sends `data` to the target.
+    fn write_uart(&mut self, data: &[u8]) -> Result<(), DebugProbeError>;
+}
+
 /// Low-Level Access to the JTAG protocol
 ///
 /// This trait should be implemented by all probes which offer low-level access to
@@ -1353,4 +1693,36 @@ mod test {
             Some("DC:DA:0C:D3:FE:D8".to_string())
         );
     }
+
+    #[test]
+    fn test_parsing_ip_address() {
+        let selector: DebugProbeSelector = "ip:192.168.1.10:19020".try_into().unwrap();
+
+        assert_eq!(selector.ip_address, Some("192.168.1.10:19020".to_string()));
+        assert_eq!(selector.to_string(), "ip:192.168.1.10:19020");
+
+        assert!(DebugProbeSelector::try_from("ip:").is_err());
+    }
+
+    #[test]
+    fn test_parsing_usb_port() {
+        let selector: DebugProbeSelector = "usb:1-3.2".try_into().unwrap();
+
+        assert_eq!(selector.usb_port, Some("1-3.2".to_string()));
+        assert_eq!(selector.to_string(), "usb:1-3.2");
+
+        let mut probe_info = DebugProbeInfo::new(
+            "Mock probe",
+            0x12,
+            0x23,
+            None,
+            &ftdi::FtdiProbeFactory,
+            None,
+        );
+        assert!(!selector.matches_probe(&probe_info));
+        probe_info.usb_port = Some("1-3.2".to_string());
+        assert!(selector.matches_probe(&probe_info));
+
+        assert!(DebugProbeSelector::try_from("usb:").is_err());
+    }
 }
@@ -8,8 +8,10 @@ use crate::{
     architecture::arm::{
         ap::AccessPortError,
         dp::{Abort, Ctrl, DebugPortError, DpRegister, RdBuff, DPIDR},
-        ArmError, DapError, FullyQualifiedApAddress, PortType, RawDapAccess, Register,
+        ArmError, DapError, FullyQualifiedApAddress, PortType, RawDapAccess, RawDapTransfer,
+        Register,
     },
+    metrics::Metrics,
     probe::{
         common::bits_to_byte, CommandResult, DebugProbe, DebugProbeError, JTAGAccess,
         JtagCommandQueue, JtagWriteCommand, WireProtocol,
@@ -97,9 +99,16 @@ pub struct ProbeStatistics {
 
     /// Number of SWD FAULT responses encountered.
     num_faults: usize,
+
+    /// The transfer counters of the session, which are updated as well.
+    metrics: Metrics,
 }
 
 impl ProbeStatistics {
+    pub fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = metrics;
+    }
+
     pub fn record_extra_transfer(&mut self) {
         self.num_extra_transfers += 1;
     }
@@ -110,12 +119,20 @@ impl ProbeStatistics {
 
     pub fn report_io(&mut self) {
         self.num_io_calls += 1;
+        self.metrics.record_round_trips(1);
+    }
+
+    pub fn record_retry(&mut self) {
+        self.metrics.record_retry();
     }
 
     pub fn report_swd_response<T>(&mut self, response: &Result<T, DapError>) {
         match response {
             Err(DapError::FaultResponse) => self.num_faults += 1,
-            Err(DapError::WaitResponse) => self.num_wait_resp += 1,
+            Err(DapError::WaitResponse) => {
+                self.num_wait_resp += 1;
+                self.metrics.record_wait_response();
+            }
             // Other errors are not counted right now.
             _ => (),
         }
@@ -561,6 +578,7 @@ fn perform_raw_transfers_retry<P: DebugProbe + RawProtocolIo + JTAGAccess>(
                 TransferStatus::Ok => successful_transfers += 1,
                 TransferStatus::Failed(DapError::WaitResponse) => {
                     tracing::debug!("got WAIT on transfer {}, retrying...", successful_transfers);
+                    probe.probe_statistics().record_retry();
 
                     clear_overrun(probe)?;
 
@@ -1215,6 +1233,46 @@ impl<Probe: DebugProbe + RawProtocolIo + JTAGAccess + 'static> RawDapAccess for
         Ok(())
     }
 
+    fn raw_transfer_batch(&mut self, transfers: &mut [RawDapTransfer]) -> Result<(), ArmError> {
+        if transfers.is_empty() {
+            return Ok(());
+        }
+
+        let mut dap_transfers = transfers
+            .iter()
+            .map(|transfer| match *transfer {
+                RawDapTransfer::Read { port, addr, .. } => DapTransfer::read(port, addr),
+                RawDapTransfer::Write { port, addr, value } => {
+                    DapTransfer::write(port, addr, value)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        perform_transfers(self, &mut dap_transfers)?;
+
+        let count = transfers.len();
+        for (i, (transfer, result)) in transfers.iter_mut().zip(dap_transfers).enumerate() {
+            match result.status {
+                TransferStatus::Ok => {
+                    if let RawDapTransfer::Read { value, .. } = transfer {
+                        *value = result.value;
+                    }
+                }
+                TransferStatus::Failed(err) => {
+                    tracing::debug!("Error in transfer {}/{} of batch: {}", i + 1, count, err);
+
+                    return Err(err.into());
+                }
+                TransferStatus::Pending => {
+                    // This should not happen...
+                    panic!("Error performing transfers. This is a bug, please report it.")
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn swj_pins(
         &mut self,
         pin_out: u32,
@@ -1273,7 +1331,7 @@ mod test {
     use std::iter;
 
     use crate::{
-        architecture::arm::{PortType, RawDapAccess},
+        architecture::arm::{PortType, RawDapAccess, RawDapTransfer},
         error::Error,
         probe::{DebugProbe, DebugProbeError, JTAGAccess, ScanChainElement, WireProtocol},
     };
@@ -1686,6 +1744,28 @@ mod test {
         assert_eq!(result, read_value);
     }
 
+    #[test]
+    fn transfer_batch() {
+        let ap_read_value = 0x123223;
+        let dp_read_value = 0xFFAABB;
+
+        let mut mock = MockJaylink::new();
+
+        mock.add_read_response(DapAcknowledge::Ok, 0);
+        mock.add_read_response(DapAcknowledge::Ok, ap_read_value);
+        mock.add_read_response(DapAcknowledge::Ok, dp_read_value);
+        mock.add_idle_cycles(mock.swd_settings.idle_cycles_after_transfer);
+
+        let mut transfers = [
+            RawDapTransfer::read(PortType::AccessPort, 4),
+            RawDapTransfer::read(PortType::DebugPort, 3),
+        ];
+        mock.raw_transfer_batch(&mut transfers).unwrap();
+
+        assert_eq!(transfers[0].value(), ap_read_value);
+        assert_eq!(transfers[1].value(), dp_read_value);
+    }
+
     #[test]
     fn read_register_jtag() {
         let read_value = 12;
@@ -0,0 +1,623 @@
+//! JTAG over TCP, using the virtual JTAG protocols supported by OpenOCD.
+//!
+//! Simulators like Verilator or Renode, and some FPGA designs, expose the JTAG port of the
+//! simulated target as a TCP server speaking either the `remote_bitbang` or the `jtag_vpi`
+//! protocol. A [`BitbangProbe`] connects to such a server and drives the JTAG signals
+//! through it.
+//!
+//! - `remote_bitbang` sends one ASCII character per change of the JTAG signals, and one
+//!   character per TDO sample.
+//! - `jtag_vpi` sends fixed size messages, each containing a TMS sequence or a scan of up
+//!   to 4096 bits.
+
+use std::{
+    io::{Read, Write},
+    iter,
+    net::{TcpStream, ToSocketAddrs},
+};
+
+use bitvec::prelude::*;
+
+use crate::{
+    architecture::{
+        arm::{
+            communication_interface::{DapProbe, UninitializedArmProbe},
+            ArmCommunicationInterface,
+        },
+        riscv::{communication_interface::RiscvInterfaceBuilder, dtm::jtag_dtm::JtagDtmBuilder},
+        xtensa::communication_interface::{
+            XtensaCommunicationInterface, XtensaDebugInterfaceState,
+        },
+    },
+    metrics::Metrics,
+    probe::{
+        arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
+        common::{JtagDriverState, RawJtagIo},
+        DebugProbe, DebugProbeError, JTAGAccess, ScanChainElement, WireProtocol,
+    },
+};
+
+/// The protocol spoken by a virtual JTAG server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitbangProtocol {
+    /// The `remote_bitbang` protocol, default port 44853 in most simulators.
+    RemoteBitbang,
+    /// The `jtag_vpi` protocol, default port 5555.
+    JtagVpi,
+}
+
+impl std::fmt::Display for BitbangProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitbangProtocol::RemoteBitbang => f.write_str("remote-bitbang"),
+            BitbangProtocol::JtagVpi => f.write_str("jtag-vpi"),
+        }
+    }
+}
+
+impl std::str::FromStr for BitbangProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_ascii_lowercase().replace('_', "-")[..] {
+            "remote-bitbang" => Ok(BitbangProtocol::RemoteBitbang),
+            "jtag-vpi" => Ok(BitbangProtocol::JtagVpi),
+            _ => Err(format!(
+                "'{s}' is not a valid bitbang protocol. Choose from [remote-bitbang, jtag-vpi]."
+            )),
+        }
+    }
+}
+
+/// A JTAG probe connected to a virtual JTAG server.
+///
+/// See the [module level documentation](self) for details.
+#[derive(Debug)]
+pub struct BitbangProbe {
+    stream: TcpStream,
+    protocol: BitbangProtocol,
+    speed_khz: u32,
+
+    /// Buffered `(tms, tdi, capture)` bits, which were not shifted yet.
+    bits: Vec<(bool, bool, bool)>,
+    response: BitVec<u8, Lsb0>,
+    jtag_state: JtagDriverState,
+
+    probe_statistics: ProbeStatistics,
+    swd_settings: SwdSettings,
+}
+
+impl BitbangProbe {
+    /// Connects to the virtual JTAG server at `address`.
+    pub fn connect(
+        address: impl ToSocketAddrs,
+        protocol: BitbangProtocol,
+    ) -> Result<Self, DebugProbeError> {
+        let stream = TcpStream::connect(address).map_err(connection_error)?;
+        stream.set_nodelay(true).map_err(connection_error)?;
+
+        Ok(Self {
+            stream,
+            protocol,
+            // The simulated clock doesn't have a meaningful frequency.
+            speed_khz: 1000,
+            bits: Vec::new(),
+            response: BitVec::new(),
+            jtag_state: JtagDriverState::default(),
+            probe_statistics: ProbeStatistics::default(),
+            swd_settings: SwdSettings::default(),
+        })
+    }
+
+    fn flush(&mut self) -> Result<(), DebugProbeError> {
+        if self.bits.is_empty() {
+            return Ok(());
+        }
+
+        let bits = std::mem::take(&mut self.bits);
+        match self.protocol {
+            BitbangProtocol::RemoteBitbang => self.flush_remote_bitbang(&bits),
+            BitbangProtocol::JtagVpi => self.flush_jtag_vpi(&bits),
+        }
+        .map_err(connection_error)
+    }
+
+    fn flush_remote_bitbang(&mut self, bits: &[(bool, bool, bool)]) -> std::io::Result<()> {
+        let (commands, samples) = remote_bitbang::encode(bits);
+        self.stream.write_all(&commands)?;
+
+        let mut tdo = vec![0; samples];
+        self.stream.read_exact(&mut tdo)?;
+
+        for value in tdo {
+            match value {
+                b'0' => self.response.push(false),
+                b'1' => self.response.push(true),
+                other => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid TDO value {other:#04x}"),
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn flush_jtag_vpi(&mut self, bits: &[(bool, bool, bool)]) -> std::io::Result<()> {
+        for command in jtag_vpi::split(bits) {
+            self.stream.write_all(&command.encode())?;
+
+            if !command.is_scan() {
+                continue;
+            }
+
+            let mut response = [0; jtag_vpi::MESSAGE_SIZE];
+            self.stream.read_exact(&mut response)?;
+            let tdo = jtag_vpi::decode_tdo(&response);
+            for (i, capture) in command.capture.iter().enumerate() {
+                if *capture {
+                    self.response.push(tdo[i]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_reset(&mut self, asserted: bool) -> Result<(), DebugProbeError> {
+        match self.protocol {
+            BitbangProtocol::RemoteBitbang => {
+                self.flush()?;
+                self.stream
+                    .write_all(&[remote_bitbang::reset(false, asserted)])
+                    .map_err(connection_error)
+            }
+            BitbangProtocol::JtagVpi => Err(DebugProbeError::NotImplemented {
+                function_name: "target_reset",
+            }),
+        }
+    }
+}
+
+impl DebugProbe for BitbangProbe {
+    fn get_name(&self) -> &str {
+        match self.protocol {
+            BitbangProtocol::RemoteBitbang => "remote_bitbang",
+            BitbangProtocol::JtagVpi => "jtag_vpi",
+        }
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        // The speed is determined by the simulation, there is nothing to configure.
+        self.speed_khz = speed_khz;
+        Ok(speed_khz)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.jtag_state.expected_scan_chain = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        Ok(self
+            .jtag_state
+            .expected_scan_chain
+            .as_deref()
+            .unwrap_or(&[]))
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        tracing::debug!("Attaching using {}...", self.protocol);
+
+        JTAGAccess::scan_chain(self)?;
+        self.select_target(0)
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.select_target(index)
+    }
+
+    fn detach(&mut self) -> Result<(), crate::Error> {
+        self.flush()?;
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(true)?;
+        self.set_reset(false)
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(true)
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(false)
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        if protocol != WireProtocol::Jtag {
+            Err(DebugProbeError::UnsupportedProtocol(protocol))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        // Only supports JTAG
+        Some(WireProtocol::Jtag)
+    }
+
+    fn try_get_riscv_interface_builder<'probe>(
+        &'probe mut self,
+    ) -> Result<Box<dyn RiscvInterfaceBuilder<'probe> + 'probe>, DebugProbeError> {
+        Ok(Box::new(JtagDtmBuilder::new(self)))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        true
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn set_metrics(&mut self, metrics: Metrics) {
+        self.probe_statistics.set_metrics(metrics);
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        let use_overrun_detect = self.use_overrun_detect();
+        let uninitialized_interface = ArmCommunicationInterface::new(self, use_overrun_detect);
+
+        Ok(Box::new(uninitialized_interface))
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_xtensa_interface<'probe>(
+        &'probe mut self,
+        state: &'probe mut XtensaDebugInterfaceState,
+    ) -> Result<XtensaCommunicationInterface<'probe>, DebugProbeError> {
+        Ok(XtensaCommunicationInterface::new(self, state))
+    }
+
+    fn has_xtensa_interface(&self) -> bool {
+        true
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+}
+
+impl DapProbe for BitbangProbe {
+    fn use_overrun_detect(&self) -> bool {
+        true
+    }
+}
+
+impl RawProtocolIo for BitbangProbe {
+    fn jtag_shift_tms<M>(&mut self, tms: M, tdi: bool) -> Result<(), DebugProbeError>
+    where
+        M: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+        self.shift_bits(tms, iter::repeat(tdi), iter::repeat(false))
+    }
+
+    fn jtag_shift_tdi<I>(&mut self, tms: bool, tdi: I) -> Result<(), DebugProbeError>
+    where
+        I: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+        self.shift_bits(iter::repeat(tms), tdi, iter::repeat(false))
+    }
+
+    fn swd_io<D, S>(&mut self, _dir: D, _swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    where
+        D: IntoIterator<Item = bool>,
+        S: IntoIterator<Item = bool>,
+    {
+        Err(DebugProbeError::NotImplemented {
+            function_name: "swd_io",
+        })
+    }
+
+    fn swj_pins(
+        &mut self,
+        _pin_out: u32,
+        _pin_select: u32,
+        _pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe {
+            command_name: "swj_pins",
+        })
+    }
+
+    fn swd_settings(&self) -> &SwdSettings {
+        &self.swd_settings
+    }
+
+    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
+        &mut self.probe_statistics
+    }
+}
+
+impl RawJtagIo for BitbangProbe {
+    fn shift_bit(&mut self, tms: bool, tdi: bool, capture: bool) -> Result<(), DebugProbeError> {
+        self.jtag_state.state.update(tms);
+        self.bits.push((tms, tdi, capture));
+        Ok(())
+    }
+
+    fn read_captured_bits(&mut self) -> Result<BitVec<u8, Lsb0>, DebugProbeError> {
+        self.flush()?;
+        Ok(std::mem::take(&mut self.response))
+    }
+
+    fn state_mut(&mut self) -> &mut JtagDriverState {
+        &mut self.jtag_state
+    }
+
+    fn state(&self) -> &JtagDriverState {
+        &self.jtag_state
+    }
+}
+
+fn connection_error(error: std::io::Error) -> DebugProbeError {
+    DebugProbeError::Other(format!("Virtual JTAG connection failed: {error}"))
+}
+
+/// Encoding of the `remote_bitbang` protocol.
+mod remote_bitbang {
+    /// Sets the JTAG signals.
+    pub(super) fn write(tck: bool, tms: bool, tdi: bool) -> u8 {
+        b'0' + (u8::from(tck) << 2 | u8::from(tms) << 1 | u8::from(tdi))
+    }
+
+    /// Requests a TDO sample, answered with `0` or `1`.
+    pub(super) const READ: u8 = b'R';
+
+    /// Sets the TRST and SRST signals, `true` asserts the reset.
+    pub(super) fn reset(trst: bool, srst: bool) -> u8 {
+        b'r' + (u8::from(trst) << 1 | u8::from(srst))
+    }
+
+    /// Encodes a sequence of `(tms, tdi, capture)` bits, returning the commands and the
+    /// number of TDO samples the server will answer with.
+    ///
+    /// TDO is sampled before the rising edge of TCK.
+    pub(super) fn encode(bits: &[(bool, bool, bool)]) -> (Vec<u8>, usize) {
+        let mut commands = Vec::with_capacity(bits.len() * 3);
+        let mut samples = 0;
+
+        for &(tms, tdi, capture) in bits {
+            commands.push(write(false, tms, tdi));
+            if capture {
+                commands.push(READ);
+                samples += 1;
+            }
+            commands.push(write(true, tms, tdi));
+        }
+
+        (commands, samples)
+    }
+}
+
+/// Encoding of the `jtag_vpi` protocol.
+mod jtag_vpi {
+    const BUFFER_SIZE: usize = 512;
+
+    /// Maximum number of bits in a single command.
+    pub(super) const MAX_BITS: usize = BUFFER_SIZE * 8;
+
+    /// Size of a message, in both directions.
+    pub(super) const MESSAGE_SIZE: usize = 4 + BUFFER_SIZE + BUFFER_SIZE + 4 + 4;
+
+    const CMD_TMS_SEQ: u32 = 1;
+    const CMD_SCAN_CHAIN: u32 = 2;
+    const CMD_SCAN_CHAIN_FLIP_TMS: u32 = 3;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(super) enum Kind {
+        /// Clocks the bits out on TMS, without capturing TDO.
+        TmsSequence,
+        /// Shifts the bits through TDI with TMS low, capturing TDO.
+        ///
+        /// If `flip_tms` is set, TMS is high during the last bit.
+        Scan { flip_tms: bool },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(super) struct Command {
+        pub kind: Kind,
+        pub bits: Vec<bool>,
+        /// Whether the TDO value of each bit was requested.
+        pub capture: Vec<bool>,
+    }
+
+    impl Command {
+        pub(super) fn is_scan(&self) -> bool {
+            matches!(self.kind, Kind::Scan { .. })
+        }
+
+        /// Encodes the command into a `vpi_cmd` message.
+        pub(super) fn encode(&self) -> [u8; MESSAGE_SIZE] {
+            let cmd = match self.kind {
+                Kind::TmsSequence => CMD_TMS_SEQ,
+                Kind::Scan { flip_tms: false } => CMD_SCAN_CHAIN,
+                Kind::Scan { flip_tms: true } => CMD_SCAN_CHAIN_FLIP_TMS,
+            };
+
+            let mut message = [0; MESSAGE_SIZE];
+            message[..4].copy_from_slice(&cmd.to_le_bytes());
+            for (i, bit) in self.bits.iter().enumerate() {
+                message[4 + i / 8] |= u8::from(*bit) << (i % 8);
+            }
+
+            let nb_bits = self.bits.len() as u32;
+            let length = nb_bits.div_ceil(8);
+            let tail = 4 + 2 * BUFFER_SIZE;
+            message[tail..tail + 4].copy_from_slice(&length.to_le_bytes());
+            message[tail + 4..].copy_from_slice(&nb_bits.to_le_bytes());
+
+            message
+        }
+    }
+
+    /// Extracts the TDO bits from the response to a scan.
+    pub(super) fn decode_tdo(message: &[u8; MESSAGE_SIZE]) -> Vec<bool> {
+        let buffer_in = &message[4 + BUFFER_SIZE..4 + 2 * BUFFER_SIZE];
+        (0..MAX_BITS)
+            .map(|i| buffer_in[i / 8] & (1 << (i % 8)) != 0)
+            .collect()
+    }
+
+    /// Splits a sequence of `(tms, tdi, capture)` bits into commands.
+    ///
+    /// Bits with TMS high, which neither set TDI nor capture TDO, are sent as TMS
+    /// sequences. All other bits are scanned, a scan ends after a bit with TMS high.
+    pub(super) fn split(bits: &[(bool, bool, bool)]) -> Vec<Command> {
+        let mut commands: Vec<Command> = Vec::new();
+
+        for &(tms, tdi, capture) in bits {
+            if tms && !tdi && !capture {
+                match commands.last_mut() {
+                    Some(command)
+                        if command.kind == Kind::TmsSequence && command.bits.len() < MAX_BITS =>
+                    {
+                        command.bits.push(true);
+                        command.capture.push(false);
+                    }
+                    _ => commands.push(Command {
+                        kind: Kind::TmsSequence,
+                        bits: vec![true],
+                        capture: vec![false],
+                    }),
+                }
+                continue;
+            }
+
+            match commands.last_mut() {
+                Some(command)
+                    if command.kind == (Kind::Scan { flip_tms: false })
+                        && command.bits.len() < MAX_BITS =>
+                {
+                    command.kind = Kind::Scan { flip_tms: tms };
+                    command.bits.push(tdi);
+                    command.capture.push(capture);
+                }
+                _ => commands.push(Command {
+                    kind: Kind::Scan { flip_tms: tms },
+                    bits: vec![tdi],
+                    capture: vec![capture],
+                }),
+            }
+        }
+
+        commands
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+
+    #[test]
+    fn encode_remote_bitbang() {
+        let (commands, samples) =
+            remote_bitbang::encode(&[(true, false, false), (false, true, true)]);
+
+        assert_eq!(commands, b"261R5");
+        assert_eq!(samples, 1);
+        assert_eq!(remote_bitbang::reset(false, true), b's');
+    }
+
+    #[test]
+    fn split_jtag_vpi_commands() {
+        // Go to Shift-DR, shift 3 bits exiting on the last one, then go to idle
+        let bits = [
+            (true, false, false),
+            (false, false, false),
+            (false, false, false),
+            (false, true, true),
+            (false, false, true),
+            (true, true, true),
+            (true, false, false),
+            (false, false, false),
+        ];
+
+        let commands = jtag_vpi::split(&bits);
+        let kinds: Vec<_> = commands
+            .iter()
+            .map(|command| (command.kind.clone(), command.bits.len()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (jtag_vpi::Kind::TmsSequence, 1),
+                (jtag_vpi::Kind::Scan { flip_tms: true }, 5),
+                (jtag_vpi::Kind::TmsSequence, 1),
+                (jtag_vpi::Kind::Scan { flip_tms: false }, 1),
+            ]
+        );
+        assert_eq!(commands[1].bits, [false, false, true, false, true]);
+
+        let message = commands[1].encode();
+        assert_eq!(message.len(), jtag_vpi::MESSAGE_SIZE);
+        assert_eq!(message[..4], [3, 0, 0, 0]);
+        assert_eq!(message[4], 0b10100);
+        assert_eq!(
+            message[jtag_vpi::MESSAGE_SIZE - 8..],
+            [1, 0, 0, 0, 5, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn capture_tdo_over_remote_bitbang() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // A server which loops TDI back to TDO.
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut tdi = false;
+            let mut command = [0];
+            while stream.read_exact(&mut command).is_ok() {
+                match command[0] {
+                    c @ b'0'..=b'7' => tdi = (c - b'0') & 1 != 0,
+                    b'R' => stream.write_all(if tdi { b"1" } else { b"0" }).unwrap(),
+                    _ => {}
+                }
+            }
+        });
+
+        let mut probe = BitbangProbe::connect(address, BitbangProtocol::RemoteBitbang).unwrap();
+        probe
+            .shift_bits(
+                iter::repeat(false),
+                [true, false, true, true],
+                [true, true, false, true],
+            )
+            .unwrap();
+        let captured = probe.read_captured_bits().unwrap();
+        assert_eq!(captured, bits![1, 0, 1]);
+
+        drop(probe);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,463 @@
+//! Black Magic Probe support.
+//!
+//! The probe is controlled using the remote protocol of its firmware, which is spoken on the
+//! GDB serial port next to the GDB protocol. Only the low level SWD and JTAG sequences of the
+//! protocol are used, the debug port transfers themselves are assembled by probe-rs.
+//!
+//! For details on the protocol, see <https://github.com/blackmagic-debug/blackmagic>.
+
+mod protocol;
+mod usb_interface;
+
+use std::time::Duration;
+
+use bitvec::prelude::*;
+use nusb::DeviceInfo;
+use probe_rs_target::ScanChainElement;
+
+use self::protocol::{JtagChunk, SwdChunk};
+use self::usb_interface::BlackMagicUsbDevice;
+use crate::{
+    architecture::{
+        arm::{
+            communication_interface::{DapProbe, UninitializedArmProbe},
+            ArmCommunicationInterface, Pins,
+        },
+        riscv::{communication_interface::RiscvInterfaceBuilder, dtm::jtag_dtm::JtagDtmBuilder},
+        xtensa::communication_interface::{
+            XtensaCommunicationInterface, XtensaDebugInterfaceState,
+        },
+    },
+    probe::{
+        arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
+        common::{JtagDriverState, JtagState, RawJtagIo},
+        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, JTAGAccess,
+        ProbeCreationError, ProbeFactory, WireProtocol,
+    },
+};
+
+const VENDOR_ID: u16 = 0x1d50;
+const PRODUCT_ID: u16 = 0x6018;
+
+/// Factory for creating [`BlackMagicProbe`] probes.
+#[derive(Debug)]
+pub struct BlackMagicProbeFactory;
+
+impl std::fmt::Display for BlackMagicProbeFactory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Black Magic Probe")
+    }
+}
+
+impl ProbeFactory for BlackMagicProbeFactory {
+    fn open(&self, selector: &DebugProbeSelector) -> Result<Box<dyn DebugProbe>, DebugProbeError> {
+        let device = BlackMagicUsbDevice::new_from_selector(selector)?;
+        let mut probe = BlackMagicProbe {
+            device,
+            name: "Black Magic Probe".into(),
+            protocol: WireProtocol::Swd,
+            speed_khz: 0,
+            swd_driving: true,
+            jtag_bits: Vec::new(),
+            jtag_response: BitVec::new(),
+            jtag_state: JtagDriverState::default(),
+            probe_statistics: ProbeStatistics::default(),
+            swd_settings: SwdSettings::default(),
+        };
+
+        probe.init()?;
+
+        Ok(Box::new(probe))
+    }
+
+    fn list_probes(&self) -> Vec<DebugProbeInfo> {
+        list_black_magic_probes()
+    }
+}
+
+/// A Black Magic Probe.
+#[derive(Debug)]
+pub struct BlackMagicProbe {
+    device: BlackMagicUsbDevice,
+    name: String,
+    protocol: WireProtocol,
+    speed_khz: u32,
+
+    /// Whether the probe drove SWDIO at the end of the last SWD sequence.
+    swd_driving: bool,
+
+    /// Buffered `(tms, tdi, capture)` JTAG bits, which were not shifted yet.
+    jtag_bits: Vec<(bool, bool, bool)>,
+    jtag_response: BitVec<u8, Lsb0>,
+    jtag_state: JtagDriverState,
+
+    probe_statistics: ProbeStatistics,
+    swd_settings: SwdSettings,
+}
+
+impl BlackMagicProbe {
+    fn init(&mut self) -> Result<(), DebugProbeError> {
+        tracing::debug!("Initializing Black Magic Probe...");
+
+        let version = self.device.command(protocol::START)?;
+        tracing::info!("Black Magic Probe firmware: {version}");
+        self.name = format!("Black Magic Probe {version}");
+
+        let protocol_version = self.device.command(protocol::PROTOCOL_VERSION)?;
+        tracing::debug!("Remote protocol version: {protocol_version}");
+
+        let frequency = self.device.command(protocol::GET_FREQUENCY)?;
+        self.speed_khz = (protocol::parse_hex(&frequency)? / 1000) as u32;
+
+        Ok(())
+    }
+
+    fn set_reset(&mut self, asserted: bool) -> Result<(), DebugProbeError> {
+        self.device.command(&protocol::set_reset(asserted))?;
+        Ok(())
+    }
+
+    fn flush_jtag(&mut self) -> Result<(), DebugProbeError> {
+        if self.jtag_bits.is_empty() {
+            return Ok(());
+        }
+
+        let bits = std::mem::take(&mut self.jtag_bits);
+        let chunks = protocol::jtag_chunks(bits.iter().map(|&(tms, tdi, _)| (tms, tdi)));
+
+        let mut capture = bits.iter().map(|&(_, _, capture)| capture);
+        for JtagChunk {
+            cycles,
+            tdi,
+            final_tms,
+        } in chunks
+        {
+            let response = self
+                .device
+                .command(&protocol::jtag_shift(cycles, tdi, final_tms))?;
+            let tdo = protocol::parse_hex(&response)?;
+
+            for (i, capture) in capture.by_ref().take(cycles as usize).enumerate() {
+                if capture {
+                    self.jtag_response.push(tdo & (1 << i) != 0);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl DebugProbe for BlackMagicProbe {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        let frequency_hz = speed_khz
+            .checked_mul(1000)
+            .ok_or(DebugProbeError::UnsupportedSpeed(speed_khz))?;
+        self.device
+            .command(&protocol::set_frequency(frequency_hz))?;
+
+        // The probe picks the closest frequency it supports.
+        let frequency = self.device.command(protocol::GET_FREQUENCY)?;
+        self.speed_khz = (protocol::parse_hex(&frequency)? / 1000) as u32;
+
+        Ok(self.speed_khz)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.jtag_state.expected_scan_chain = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        match self.protocol {
+            WireProtocol::Jtag => Ok(self
+                .jtag_state
+                .expected_scan_chain
+                .as_deref()
+                .unwrap_or(&[])),
+            WireProtocol::Swd => Err(DebugProbeError::InterfaceNotAvailable {
+                interface_name: "JTAG",
+            }),
+        }
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        tracing::debug!("Attaching with protocol '{}'", self.protocol);
+
+        match self.protocol {
+            WireProtocol::Swd => {
+                self.device.command(protocol::SWD_INIT)?;
+                self.swd_driving = true;
+            }
+            WireProtocol::Jtag => {
+                self.device.command(protocol::JTAG_INIT)?;
+                // Initializing the interface resets the TAP.
+                self.jtag_state.state = JtagState::Reset;
+
+                JTAGAccess::scan_chain(self)?;
+                self.select_target(0)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.select_target(index)
+    }
+
+    fn detach(&mut self) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(true)?;
+        std::thread::sleep(Duration::from_millis(10));
+        self.set_reset(false)
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(true)
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(false)
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        self.protocol = protocol;
+        Ok(())
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        Some(self.protocol)
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        let use_overrun_detect = self.use_overrun_detect();
+        Ok(Box::new(ArmCommunicationInterface::new(
+            self,
+            use_overrun_detect,
+        )))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.protocol == WireProtocol::Jtag
+    }
+
+    fn try_get_riscv_interface_builder<'probe>(
+        &'probe mut self,
+    ) -> Result<Box<dyn RiscvInterfaceBuilder<'probe> + 'probe>, DebugProbeError> {
+        self.select_protocol(WireProtocol::Jtag)?;
+        Ok(Box::new(JtagDtmBuilder::new(self)))
+    }
+
+    fn has_xtensa_interface(&self) -> bool {
+        self.protocol == WireProtocol::Jtag
+    }
+
+    fn try_get_xtensa_interface<'probe>(
+        &'probe mut self,
+        state: &'probe mut XtensaDebugInterfaceState,
+    ) -> Result<XtensaCommunicationInterface<'probe>, DebugProbeError> {
+        self.select_protocol(WireProtocol::Jtag)?;
+        Ok(XtensaCommunicationInterface::new(self, state))
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+
+    fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        // The voltage is reported as text, e.g. `3.3V`.
+        let voltage = self.device.command(protocol::TARGET_VOLTAGE)?;
+        Ok(voltage.trim_end_matches('V').parse().ok())
+    }
+}
+
+impl RawProtocolIo for BlackMagicProbe {
+    fn jtag_shift_tms<M>(&mut self, tms: M, tdi: bool) -> Result<(), DebugProbeError>
+    where
+        M: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+        self.shift_bits(tms, std::iter::repeat(tdi), std::iter::repeat(false))
+    }
+
+    fn jtag_shift_tdi<I>(&mut self, tms: bool, tdi: I) -> Result<(), DebugProbeError>
+    where
+        I: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+        self.shift_bits(std::iter::repeat(tms), tdi, std::iter::repeat(false))
+    }
+
+    fn swd_io<D, S>(&mut self, dir: D, swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    where
+        D: IntoIterator<Item = bool>,
+        S: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+
+        let dir: Vec<bool> = dir.into_iter().collect();
+        let swdio: Vec<bool> = swdio.into_iter().collect();
+
+        let mut output = Vec::with_capacity(dir.len());
+        for chunk in protocol::swd_chunks(&dir, &swdio, self.swd_driving) {
+            match chunk {
+                SwdChunk::Output { bits, len } => {
+                    self.device.command(&protocol::swd_out(bits, len))?;
+                    output.extend((0..len).map(|i| bits & (1 << i) != 0));
+                    self.swd_driving = true;
+                }
+                SwdChunk::Input { len } => {
+                    let response = self.device.command(&protocol::swd_in(len))?;
+                    let bits = protocol::parse_hex(&response)?;
+                    output.extend((0..len).map(|i| bits & (1 << i) != 0));
+                    self.swd_driving = false;
+                }
+                SwdChunk::Turnaround => {
+                    output.push(false);
+                    self.swd_driving = !self.swd_driving;
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let mut nreset = Pins(0);
+        nreset.set_nreset(true);
+        let nreset_mask = nreset.0 as u32;
+
+        // Only the reset pin can be controlled.
+        if pin_select != nreset_mask {
+            return Err(DebugProbeError::CommandNotSupportedByProbe {
+                command_name: "swj_pins",
+            });
+        }
+
+        self.set_reset(!Pins(pin_out as u8).nreset())?;
+
+        // The probe can't wait for the pins to settle, so wait on the host.
+        std::thread::sleep(Duration::from_micros(pin_wait as u64));
+
+        Ok(0xffff_ffff)
+    }
+
+    fn swd_settings(&self) -> &SwdSettings {
+        &self.swd_settings
+    }
+
+    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
+        &mut self.probe_statistics
+    }
+}
+
+impl RawJtagIo for BlackMagicProbe {
+    fn state_mut(&mut self) -> &mut JtagDriverState {
+        &mut self.jtag_state
+    }
+
+    fn state(&self) -> &JtagDriverState {
+        &self.jtag_state
+    }
+
+    fn shift_bit(&mut self, tms: bool, tdi: bool, capture: bool) -> Result<(), DebugProbeError> {
+        self.jtag_state.state.update(tms);
+        self.jtag_bits.push((tms, tdi, capture));
+        Ok(())
+    }
+
+    fn read_captured_bits(&mut self) -> Result<BitVec<u8, Lsb0>, DebugProbeError> {
+        self.flush_jtag()?;
+        Ok(std::mem::take(&mut self.jtag_response))
+    }
+}
+
+impl DapProbe for BlackMagicProbe {
+    fn use_overrun_detect(&self) -> bool {
+        true
+    }
+}
+
+fn is_black_magic_probe(device: &DeviceInfo) -> bool {
+    device.vendor_id() == VENDOR_ID && device.product_id() == PRODUCT_ID
+}
+
+#[tracing::instrument(skip_all)]
+fn list_black_magic_probes() -> Vec<DebugProbeInfo> {
+    tracing::debug!("Searching for Black Magic Probes");
+    let Ok(devices) = nusb::list_devices() else {
+        return vec![];
+    };
+
+    let probes: Vec<_> = devices
+        .filter(is_black_magic_probe)
+        .map(|device| {
+            DebugProbeInfo::new(
+                "Black Magic Probe",
+                VENDOR_ID,
+                PRODUCT_ID,
+                device.serial_number().map(|s| s.to_string()),
+                &BlackMagicProbeFactory,
+                None,
+            )
+            .with_usb_port(&device)
+        })
+        .collect();
+
+    tracing::debug!("Found {} Black Magic Probes total", probes.len());
+    probes
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum BlackMagicError {
+    #[error("Usb endpoint not found.")]
+    EndpointNotFound,
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("The probe reported an error: {0}")]
+    ErrorResponse(String),
+    #[error("The probe rejected a parameter: {0}")]
+    ParameterError(String),
+    #[error("The command is not supported by the probe firmware.")]
+    NotSupported,
+}
+
+impl From<BlackMagicError> for DebugProbeError {
+    fn from(e: BlackMagicError) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(e))
+    }
+}
+
+impl From<BlackMagicError> for ProbeCreationError {
+    fn from(e: BlackMagicError) -> Self {
+        ProbeCreationError::ProbeSpecific(Box::new(e))
+    }
+}
@@ -0,0 +1,275 @@
+//! Encoding of the Black Magic Probe remote protocol.
+//!
+//! Commands are sent as `!<packet>#`, responses are received as `&<status><payload>#`.
+//! Numbers are encoded as hexadecimal strings, most significant digit first.
+
+use super::BlackMagicError;
+
+/// Starts a remote protocol session, answered with the firmware version.
+pub(crate) const START: &str = "+#!GA#";
+/// Requests the version of the remote protocol.
+pub(crate) const PROTOCOL_VERSION: &str = "!HC#";
+/// Requests the target voltage.
+pub(crate) const TARGET_VOLTAGE: &str = "!GV#";
+/// Requests the current SWCLK / TCK frequency in Hz.
+pub(crate) const GET_FREQUENCY: &str = "!Gf#";
+/// Initializes the SWD interface.
+pub(crate) const SWD_INIT: &str = "!SS#";
+/// Initializes the JTAG interface.
+pub(crate) const JTAG_INIT: &str = "!JS#";
+
+/// Sets the SWCLK / TCK frequency in Hz.
+pub(crate) fn set_frequency(frequency_hz: u32) -> String {
+    format!("!GF{frequency_hz:08x}#")
+}
+
+/// Drives the nRST line. `true` asserts the reset.
+pub(crate) fn set_reset(asserted: bool) -> String {
+    format!("!GZ{}#", u8::from(asserted))
+}
+
+/// Drives `len` bits from `bits` on SWDIO, least significant bit first.
+pub(crate) fn swd_out(bits: u32, len: u8) -> String {
+    format!("!So{len:02x}{bits:x}#")
+}
+
+/// Reads `len` bits from SWDIO, the response contains the bits least significant bit first.
+pub(crate) fn swd_in(len: u8) -> String {
+    format!("!Si{len:02x}#")
+}
+
+/// Shifts `cycles` bits from `tdi` through the TAP, least significant bit first.
+///
+/// TMS is low during all cycles, except for the last one if `final_tms` is set. The
+/// response contains the TDO bits, least significant bit first.
+pub(crate) fn jtag_shift(cycles: u8, tdi: u64, final_tms: bool) -> String {
+    let command = if final_tms { 'D' } else { 'd' };
+    format!("!J{command}{cycles:02x}{tdi:x}#")
+}
+
+/// Extracts the payload of a response, checking its status.
+pub(crate) fn parse_response(response: &[u8]) -> Result<&str, BlackMagicError> {
+    let invalid = || BlackMagicError::InvalidResponse(String::from_utf8_lossy(response).into());
+
+    let response = std::str::from_utf8(response).map_err(|_| invalid())?;
+    let response = response
+        .strip_prefix('&')
+        .and_then(|response| response.strip_suffix('#'))
+        .ok_or_else(invalid)?;
+
+    let mut chars = response.chars();
+    let status = chars.next().ok_or_else(invalid)?;
+    let payload = chars.as_str();
+
+    match status {
+        'K' => Ok(payload),
+        'E' => Err(BlackMagicError::ErrorResponse(payload.to_string())),
+        'P' => Err(BlackMagicError::ParameterError(payload.to_string())),
+        'N' => Err(BlackMagicError::NotSupported),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses a hexadecimal number in a response payload.
+pub(crate) fn parse_hex(payload: &str) -> Result<u64, BlackMagicError> {
+    u64::from_str_radix(payload, 16)
+        .map_err(|_| BlackMagicError::InvalidResponse(payload.to_string()))
+}
+
+/// A part of an SWD sequence, executed by a single command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SwdChunk {
+    /// Drive up to 32 bits.
+    Output { bits: u32, len: u8 },
+    /// Read up to 32 bits.
+    Input { len: u8 },
+    /// A turnaround cycle.
+    ///
+    /// The probe inserts these on its own whenever the direction of SWDIO changes, so
+    /// they are not sent.
+    Turnaround,
+}
+
+/// Splits an SWD sequence into the chunks executed by the probe.
+///
+/// `driving` is whether the probe drove SWDIO at the end of the previous sequence. The
+/// turnaround bits of the sequence are the first input bit after driving SWDIO, and the
+/// last input bit before driving SWDIO again or at the end of the sequence.
+pub(crate) fn swd_chunks(dir: &[bool], swdio: &[bool], mut driving: bool) -> Vec<SwdChunk> {
+    let mut chunks = Vec::new();
+
+    for (i, (&output, &bit)) in dir.iter().zip(swdio).enumerate() {
+        if output {
+            driving = true;
+            match chunks.last_mut() {
+                Some(SwdChunk::Output { bits, len }) if *len < 32 => {
+                    *bits |= u32::from(bit) << *len;
+                    *len += 1;
+                }
+                _ => chunks.push(SwdChunk::Output {
+                    bits: u32::from(bit),
+                    len: 1,
+                }),
+            }
+            continue;
+        }
+
+        let next_is_output = dir.get(i + 1).copied().unwrap_or(true);
+        if driving || next_is_output {
+            driving = false;
+            chunks.push(SwdChunk::Turnaround);
+            continue;
+        }
+
+        match chunks.last_mut() {
+            Some(SwdChunk::Input { len }) if *len < 32 => *len += 1,
+            _ => chunks.push(SwdChunk::Input { len: 1 }),
+        }
+    }
+
+    chunks
+}
+
+/// A part of a JTAG sequence, executed by a single command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct JtagChunk {
+    pub cycles: u8,
+    pub tdi: u64,
+    pub final_tms: bool,
+}
+
+/// Splits a JTAG sequence of `(tms, tdi)` bits into the chunks executed by the probe.
+///
+/// The probe can only set TMS in the last cycle of a shift, so every chunk ends at a
+/// bit with TMS set.
+pub(crate) fn jtag_chunks(bits: impl IntoIterator<Item = (bool, bool)>) -> Vec<JtagChunk> {
+    let mut chunks: Vec<JtagChunk> = Vec::new();
+
+    for (tms, tdi) in bits {
+        match chunks.last_mut() {
+            Some(chunk) if !chunk.final_tms && chunk.cycles < 64 => {
+                chunk.tdi |= u64::from(tdi) << chunk.cycles;
+                chunk.cycles += 1;
+                chunk.final_tms = tms;
+            }
+            _ => chunks.push(JtagChunk {
+                cycles: 1,
+                tdi: u64::from(tdi),
+                final_tms: tms,
+            }),
+        }
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_commands() {
+        assert_eq!(swd_out(0x3c, 8), "!So083c#");
+        assert_eq!(swd_in(32), "!Si20#");
+        assert_eq!(jtag_shift(5, 0x1f, true), "!JD051f#");
+        assert_eq!(jtag_shift(3, 0, false), "!Jd030#");
+        assert_eq!(set_frequency(4_000_000), "!GF003d0900#");
+        assert_eq!(set_reset(true), "!GZ1#");
+    }
+
+    #[test]
+    fn parse_responses() {
+        assert_eq!(parse_response(b"&K1.10.0#").unwrap(), "1.10.0");
+        assert_eq!(parse_response(b"&K#").unwrap(), "");
+        assert!(matches!(
+            parse_response(b"&E01#"),
+            Err(BlackMagicError::ErrorResponse(code)) if code == "01"
+        ));
+        assert!(matches!(
+            parse_response(b"&N#"),
+            Err(BlackMagicError::NotSupported)
+        ));
+        assert!(matches!(
+            parse_response(b"K12#"),
+            Err(BlackMagicError::InvalidResponse(_))
+        ));
+
+        assert_eq!(parse_hex("2ba01477").unwrap(), 0x2ba0_1477);
+    }
+
+    #[test]
+    fn split_swd_read() {
+        // Idle, request, turnaround, ACK, data, parity, turnaround
+        let mut dir = vec![true; 10];
+        dir.extend([false; 1 + 3 + 32 + 1 + 1]);
+        let swdio = vec![true; dir.len()];
+
+        assert_eq!(
+            swd_chunks(&dir, &swdio, true),
+            [
+                SwdChunk::Output {
+                    bits: 0x3ff,
+                    len: 10
+                },
+                SwdChunk::Turnaround,
+                SwdChunk::Input { len: 32 },
+                SwdChunk::Input { len: 4 },
+                SwdChunk::Turnaround,
+            ]
+        );
+    }
+
+    #[test]
+    fn split_swd_write() {
+        // Request, turnaround, ACK, turnaround, data, parity, idle
+        let mut dir = vec![true; 8];
+        dir.extend([false; 1 + 3 + 1]);
+        dir.extend([true; 32 + 1 + 2]);
+        let swdio = vec![false; dir.len()];
+
+        assert_eq!(
+            swd_chunks(&dir, &swdio, true),
+            [
+                SwdChunk::Output { bits: 0, len: 8 },
+                SwdChunk::Turnaround,
+                SwdChunk::Input { len: 3 },
+                SwdChunk::Turnaround,
+                SwdChunk::Output { bits: 0, len: 32 },
+                SwdChunk::Output { bits: 0, len: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn split_jtag_sequence() {
+        // Shift 3 bits into DR, exiting on the last one, then go to idle
+        let bits = [
+            (false, true),
+            (false, false),
+            (true, true),
+            (true, false),
+            (false, false),
+        ];
+
+        assert_eq!(
+            jtag_chunks(bits),
+            [
+                JtagChunk {
+                    cycles: 3,
+                    tdi: 0b101,
+                    final_tms: true
+                },
+                JtagChunk {
+                    cycles: 1,
+                    tdi: 0,
+                    final_tms: true
+                },
+                JtagChunk {
+                    cycles: 1,
+                    tdi: 0,
+                    final_tms: false
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use nusb::{
+    transfer::{Direction, EndpointType},
+    Interface,
+};
+
+use crate::probe::{
+    usb_util::InterfaceExt, DebugProbeError, DebugProbeSelector, ProbeCreationError,
+};
+
+use super::{is_black_magic_probe, protocol, BlackMagicError};
+
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// USB class of the CDC data interfaces.
+const CDC_DATA_CLASS: u8 = 0x0a;
+
+/// The GDB serial port of a Black Magic Probe, which also speaks the remote protocol.
+pub struct BlackMagicUsbDevice {
+    interface: Interface,
+    endpoint_in: u8,
+    endpoint_out: u8,
+    /// Received data which was not consumed yet.
+    buffer: Vec<u8>,
+}
+
+impl std::fmt::Debug for BlackMagicUsbDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlackMagicUsbDevice")
+            .finish_non_exhaustive()
+    }
+}
+
+impl BlackMagicUsbDevice {
+    pub fn new_from_selector(selector: &DebugProbeSelector) -> Result<Self, ProbeCreationError> {
+        let device = nusb::list_devices()
+            .map_err(ProbeCreationError::Usb)?
+            .filter(is_black_magic_probe)
+            .find(|device| selector.matches(device))
+            .ok_or(ProbeCreationError::NotFound)?;
+
+        let device_handle = device.open().map_err(ProbeCreationError::Usb)?;
+
+        // The first CDC data interface is the GDB port.
+        let mut endpoints = None;
+        if let Some(config) = device_handle.configurations().next() {
+            for altsetting in config.interface_alt_settings() {
+                if altsetting.class() != CDC_DATA_CLASS {
+                    continue;
+                }
+
+                let bulk = |direction| {
+                    altsetting
+                        .endpoints()
+                        .find(|ep| {
+                            ep.transfer_type() == EndpointType::Bulk && ep.direction() == direction
+                        })
+                        .map(|ep| ep.address())
+                };
+
+                if let (Some(endpoint_in), Some(endpoint_out)) =
+                    (bulk(Direction::In), bulk(Direction::Out))
+                {
+                    endpoints = Some((altsetting.interface_number(), endpoint_in, endpoint_out));
+                    break;
+                }
+            }
+        }
+
+        let Some((interface_number, endpoint_in, endpoint_out)) = endpoints else {
+            return Err(BlackMagicError::EndpointNotFound.into());
+        };
+
+        // The serial port is usually claimed by the CDC ACM driver of the OS.
+        let interface = device_handle
+            .detach_and_claim_interface(interface_number)
+            .map_err(ProbeCreationError::Usb)?;
+        tracing::trace!("Claimed interface {interface_number} of USB device.");
+
+        Ok(Self {
+            interface,
+            endpoint_in,
+            endpoint_out,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Sends a command, and returns the payload of the response.
+    pub(crate) fn command(&mut self, command: &str) -> Result<String, DebugProbeError> {
+        tracing::trace!("Sending command: {command}");
+
+        self.interface
+            .write_bulk(self.endpoint_out, command.as_bytes(), TIMEOUT)
+            .map_err(DebugProbeError::Usb)?;
+
+        let response = self.read_response()?;
+        tracing::trace!("Received response: {}", String::from_utf8_lossy(&response));
+
+        Ok(protocol::parse_response(&response)?.to_string())
+    }
+
+    /// Reads until a complete response was received.
+    fn read_response(&mut self) -> Result<Vec<u8>, DebugProbeError> {
+        loop {
+            // Skip anything before the start of the response, e.g. GDB acknowledgements.
+            if let Some(start) = self.buffer.iter().position(|&b| b == b'&') {
+                if let Some(end) = self.buffer[start..].iter().position(|&b| b == b'#') {
+                    let response = self.buffer[start..=start + end].to_vec();
+                    self.buffer.drain(..=start + end);
+                    return Ok(response);
+                }
+            }
+
+            let mut buf = [0; 64];
+            let read = self
+                .interface
+                .read_bulk(self.endpoint_in, &mut buf, TIMEOUT)
+                .map_err(DebugProbeError::Usb)?;
+            self.buffer.extend_from_slice(&buf[..read]);
+        }
+    }
+}
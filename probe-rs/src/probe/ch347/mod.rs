@@ -0,0 +1,370 @@
+//! Support for probes based on the WCH CH347 USB bridge.
+//!
+//! The CH347 is a cheap USB high-speed bridge with UARTs and a JTAG interface, which is
+//! found on many RISC-V development boards. The chip has to be in a mode with JTAG enabled,
+//! e.g. mode 3 for the CH347T.
+//!
+//! Bits where TMS changes are shifted by setting the pins for every clock edge. Longer runs
+//! with TMS low, like the data of a register scan, are shifted as whole bytes by the SPI
+//! engine of the chip, which is a lot faster.
+//!
+//! Only JTAG is supported, ARM targets can be used through a JTAG-DP.
+
+mod protocol;
+mod usb_interface;
+
+use bitvec::prelude::*;
+use nusb::DeviceInfo;
+
+use self::usb_interface::Ch347UsbDevice;
+use crate::{
+    architecture::{
+        arm::{
+            communication_interface::{DapProbe, UninitializedArmProbe},
+            ArmCommunicationInterface,
+        },
+        riscv::{communication_interface::RiscvInterfaceBuilder, dtm::jtag_dtm::JtagDtmBuilder},
+        xtensa::communication_interface::{
+            XtensaCommunicationInterface, XtensaDebugInterfaceState,
+        },
+    },
+    probe::{
+        arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
+        common::{JtagDriverState, RawJtagIo},
+        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, JTAGAccess,
+        ProbeCreationError, ProbeFactory, ScanChainElement, WireProtocol,
+    },
+};
+
+const VENDOR_ID: u16 = 0x1a86;
+/// Product IDs of the CH347T in mode 3 and the CH347F.
+const PRODUCT_IDS: [u16; 2] = [0x55dd, 0x55de];
+
+/// Number of buffered bits after which they are sent to the probe.
+const MAX_PENDING_BITS: usize = 8192;
+
+/// Factory for creating [`Ch347`] probes.
+#[derive(Debug)]
+pub struct Ch347Factory;
+
+impl std::fmt::Display for Ch347Factory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CH347")
+    }
+}
+
+impl ProbeFactory for Ch347Factory {
+    fn open(&self, selector: &DebugProbeSelector) -> Result<Box<dyn DebugProbe>, DebugProbeError> {
+        let device = Ch347UsbDevice::new_from_selector(selector)?;
+        let mut probe = Ch347 {
+            device,
+            speed_khz: 0,
+            pending: Vec::new(),
+            captured: BitVec::new(),
+            jtag_state: JtagDriverState::default(),
+            probe_statistics: ProbeStatistics::default(),
+            swd_settings: SwdSettings::default(),
+        };
+
+        probe.set_speed(1875)?;
+
+        Ok(Box::new(probe))
+    }
+
+    fn list_probes(&self) -> Vec<DebugProbeInfo> {
+        list_ch347_devices()
+    }
+}
+
+/// A probe based on the WCH CH347.
+///
+/// See the [module level documentation](self) for details.
+#[derive(Debug)]
+pub struct Ch347 {
+    device: Ch347UsbDevice,
+    speed_khz: u32,
+
+    /// Bits which have not been sent yet, as `(tms, tdi, capture)`.
+    pending: Vec<(bool, bool, bool)>,
+    captured: BitVec<u8, Lsb0>,
+    jtag_state: JtagDriverState,
+    probe_statistics: ProbeStatistics,
+    swd_settings: SwdSettings,
+}
+
+impl Ch347 {
+    /// Sends the pending bits, and stores the captured TDO bits.
+    fn flush(&mut self) -> Result<(), DebugProbeError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let commands = protocol::jtag_commands(&self.pending);
+        self.pending.clear();
+
+        let packets: Vec<_> = commands.iter().map(|c| c.packet.clone()).collect();
+        let response_sizes: Vec<_> = commands.iter().map(|c| c.response_size()).collect();
+        let responses = self.device.transfer(&packets, &response_sizes)?;
+
+        for (command, response) in commands.iter().zip(&responses) {
+            self.captured.extend(command.captured_bits(response));
+        }
+
+        Ok(())
+    }
+}
+
+impl DebugProbe for Ch347 {
+    fn get_name(&self) -> &str {
+        "CH347"
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        let (index, actual_speed) = protocol::clock_index(speed_khz);
+
+        let packet = protocol::command(protocol::JTAG_INIT, &[0, index, 0, 0, 0, 0]);
+        let response = self
+            .device
+            .transfer(&[packet], &[protocol::HEADER_SIZE + 1])?;
+        if response[0] != [0] {
+            return Err(Ch347Error::InitFailed.into());
+        }
+
+        self.speed_khz = actual_speed;
+        Ok(actual_speed)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.jtag_state.expected_scan_chain = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        Ok(self
+            .jtag_state
+            .expected_scan_chain
+            .as_deref()
+            .unwrap_or(&[]))
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        tracing::debug!("Attaching with protocol '{}'", WireProtocol::Jtag);
+
+        JTAGAccess::scan_chain(self)?;
+        self.select_target(0)
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.select_target(index)
+    }
+
+    fn detach(&mut self) -> Result<(), crate::Error> {
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::NotImplemented {
+            function_name: "target_reset",
+        })
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::NotImplemented {
+            function_name: "target_reset_assert",
+        })
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        Err(DebugProbeError::NotImplemented {
+            function_name: "target_reset_deassert",
+        })
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        match protocol {
+            WireProtocol::Jtag => Ok(()),
+            WireProtocol::Swd => Err(DebugProbeError::UnsupportedProtocol(protocol)),
+        }
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        Some(WireProtocol::Jtag)
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        let use_overrun_detect = self.use_overrun_detect();
+        Ok(Box::new(ArmCommunicationInterface::new(
+            self,
+            use_overrun_detect,
+        )))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_riscv_interface_builder<'probe>(
+        &'probe mut self,
+    ) -> Result<Box<dyn RiscvInterfaceBuilder<'probe> + 'probe>, DebugProbeError> {
+        Ok(Box::new(JtagDtmBuilder::new(self)))
+    }
+
+    fn has_xtensa_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_xtensa_interface<'probe>(
+        &'probe mut self,
+        state: &'probe mut XtensaDebugInterfaceState,
+    ) -> Result<XtensaCommunicationInterface<'probe>, DebugProbeError> {
+        Ok(XtensaCommunicationInterface::new(self, state))
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+}
+
+impl RawProtocolIo for Ch347 {
+    fn jtag_shift_tms<M>(&mut self, tms: M, tdi: bool) -> Result<(), DebugProbeError>
+    where
+        M: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+        self.shift_bits(tms, std::iter::repeat(tdi), std::iter::repeat(false))
+    }
+
+    fn jtag_shift_tdi<I>(&mut self, tms: bool, tdi: I) -> Result<(), DebugProbeError>
+    where
+        I: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+        self.shift_bits(std::iter::repeat(tms), tdi, std::iter::repeat(false))
+    }
+
+    fn swd_io<D, S>(&mut self, _dir: D, _swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    where
+        D: IntoIterator<Item = bool>,
+        S: IntoIterator<Item = bool>,
+    {
+        Err(DebugProbeError::UnsupportedProtocol(WireProtocol::Swd))
+    }
+
+    fn swj_pins(
+        &mut self,
+        _pin_out: u32,
+        _pin_select: u32,
+        _pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        Err(DebugProbeError::CommandNotSupportedByProbe {
+            command_name: "swj_pins",
+        })
+    }
+
+    fn swd_settings(&self) -> &SwdSettings {
+        &self.swd_settings
+    }
+
+    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
+        &mut self.probe_statistics
+    }
+}
+
+impl RawJtagIo for Ch347 {
+    fn state_mut(&mut self) -> &mut JtagDriverState {
+        &mut self.jtag_state
+    }
+
+    fn state(&self) -> &JtagDriverState {
+        &self.jtag_state
+    }
+
+    fn shift_bit(&mut self, tms: bool, tdi: bool, capture: bool) -> Result<(), DebugProbeError> {
+        self.jtag_state.state.update(tms);
+        self.pending.push((tms, tdi, capture));
+
+        if self.pending.len() >= MAX_PENDING_BITS {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn read_captured_bits(&mut self) -> Result<BitVec<u8, Lsb0>, DebugProbeError> {
+        self.flush()?;
+        Ok(std::mem::take(&mut self.captured))
+    }
+}
+
+impl DapProbe for Ch347 {
+    fn use_overrun_detect(&self) -> bool {
+        true
+    }
+}
+
+fn is_ch347(device: &DeviceInfo) -> bool {
+    device.vendor_id() == VENDOR_ID && PRODUCT_IDS.contains(&device.product_id())
+}
+
+#[tracing::instrument(skip_all)]
+fn list_ch347_devices() -> Vec<DebugProbeInfo> {
+    tracing::debug!("Searching for CH347 probes");
+    let Ok(devices) = nusb::list_devices() else {
+        return vec![];
+    };
+
+    let probes: Vec<_> = devices
+        .filter(is_ch347)
+        .map(|device| {
+            DebugProbeInfo::new(
+                "CH347",
+                VENDOR_ID,
+                device.product_id(),
+                device.serial_number().map(|s| s.to_string()),
+                &Ch347Factory,
+                None,
+            )
+            .with_usb_port(&device)
+        })
+        .collect();
+
+    tracing::debug!("Found {} CH347 probes total", probes.len());
+    probes
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum Ch347Error {
+    #[error("Usb endpoint not found.")]
+    EndpointNotFound,
+    #[error("Invalid response received from the probe.")]
+    InvalidResponse,
+    #[error("Failed to initialize the JTAG interface.")]
+    InitFailed,
+}
+
+impl From<Ch347Error> for DebugProbeError {
+    fn from(e: Ch347Error) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(e))
+    }
+}
+
+impl From<Ch347Error> for ProbeCreationError {
+    fn from(e: Ch347Error) -> Self {
+        ProbeCreationError::ProbeSpecific(Box::new(e))
+    }
+}
@@ -0,0 +1,189 @@
+//! Encoding of the CH347 JTAG commands.
+//!
+//! Every command starts with the command byte, followed by the length of its data as a
+//! little endian `u16`. Responses use the same format.
+//!
+//! Only the reading variants of the shift commands are used, so every command is answered
+//! with the TDO values, and the responses can be matched with the commands.
+
+/// Initializes the JTAG interface, setting the clock speed.
+pub(crate) const JTAG_INIT: u8 = 0xd0;
+/// Sets the pins to the values of each data byte, returning TDO for each rising TCK edge.
+pub(crate) const JTAG_BIT_OP_RD: u8 = 0xd2;
+/// Shifts the data bytes through TDI with TMS low, returning the TDO bytes.
+pub(crate) const JTAG_DATA_SHIFT_RD: u8 = 0xd4;
+
+/// Size of the command and response headers.
+pub(crate) const HEADER_SIZE: usize = 3;
+
+/// Maximum data length of a single command.
+const MAX_DATA: usize = 500;
+
+const TCK: u8 = 0x01;
+const TMS: u8 = 0x02;
+const TDI: u8 = 0x10;
+
+/// The base clock of the CH347, which is multiplied by powers of two.
+const BASE_CLOCK_KHZ: u32 = 1875;
+/// The highest clock index.
+const MAX_CLOCK_INDEX: u8 = 5;
+
+/// Encodes a command with its data.
+pub(crate) fn command(command: u8, data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(HEADER_SIZE + data.len());
+    packet.push(command);
+    packet.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// Returns the highest clock index with a speed of at most `speed_khz`, and its speed.
+///
+/// If the speed is below the lowest supported speed, the lowest speed is used.
+pub(crate) fn clock_index(speed_khz: u32) -> (u8, u32) {
+    let index = (0..=MAX_CLOCK_INDEX)
+        .rev()
+        .find(|index| BASE_CLOCK_KHZ << index <= speed_khz)
+        .unwrap_or(0);
+
+    (index, BASE_CLOCK_KHZ << index)
+}
+
+/// A command of a JTAG sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct JtagCommand {
+    /// The encoded command.
+    pub packet: Vec<u8>,
+    /// Whether the TDO value of each bit was requested.
+    pub capture: Vec<bool>,
+    /// Whether the command shifts whole bytes, in which case the response contains eight
+    /// TDO bits per byte instead of one byte per bit.
+    pub byte_mode: bool,
+}
+
+impl JtagCommand {
+    /// Returns the size of the response to this command.
+    pub fn response_size(&self) -> usize {
+        if self.byte_mode {
+            HEADER_SIZE + self.capture.len() / 8
+        } else {
+            HEADER_SIZE + self.capture.len()
+        }
+    }
+
+    /// Extracts the captured TDO bits from the response data.
+    pub fn captured_bits(&self, data: &[u8]) -> impl Iterator<Item = bool> + '_ {
+        let bits: Vec<bool> = if self.byte_mode {
+            data.iter()
+                .flat_map(|byte| (0..8).map(move |i| byte & (1 << i) != 0))
+                .collect()
+        } else {
+            data.iter().map(|byte| byte & 1 != 0).collect()
+        };
+
+        bits.into_iter()
+            .zip(self.capture.iter())
+            .filter_map(|(bit, capture)| capture.then_some(bit))
+    }
+}
+
+/// Splits a sequence of `(tms, tdi, capture)` bits into commands.
+///
+/// Runs of at least eight bits with TMS low are shifted as whole bytes, which is a lot
+/// faster than setting the pins for every clock edge.
+pub(crate) fn jtag_commands(bits: &[(bool, bool, bool)]) -> Vec<JtagCommand> {
+    let mut commands = Vec::new();
+    let mut pending: Vec<(bool, bool, bool)> = Vec::new();
+
+    let mut i = 0;
+    while i < bits.len() {
+        let run = bits[i..].iter().take_while(|(tms, _, _)| !tms).count();
+        let bytes = run / 8;
+
+        if bytes == 0 {
+            pending.push(bits[i]);
+            i += 1;
+            continue;
+        }
+
+        bit_commands(&pending, &mut commands);
+        pending.clear();
+
+        for chunk in bits[i..i + bytes * 8].chunks(MAX_DATA * 8) {
+            let data: Vec<u8> = chunk
+                .chunks(8)
+                .map(|byte| {
+                    byte.iter()
+                        .enumerate()
+                        .fold(0, |acc, (bit, &(_, tdi, _))| acc | (tdi as u8) << bit)
+                })
+                .collect();
+
+            commands.push(JtagCommand {
+                packet: command(JTAG_DATA_SHIFT_RD, &data),
+                capture: chunk.iter().map(|&(_, _, capture)| capture).collect(),
+                byte_mode: true,
+            });
+        }
+
+        i += bytes * 8;
+    }
+
+    bit_commands(&pending, &mut commands);
+    commands
+}
+
+/// Encodes single bits, setting the pins for both clock edges of every bit.
+fn bit_commands(bits: &[(bool, bool, bool)], commands: &mut Vec<JtagCommand>) {
+    for chunk in bits.chunks(MAX_DATA / 2) {
+        let mut data = Vec::with_capacity(chunk.len() * 2);
+        for &(tms, tdi, _) in chunk {
+            let pins = if tms { TMS } else { 0 } | if tdi { TDI } else { 0 };
+            data.extend_from_slice(&[pins, pins | TCK]);
+        }
+
+        commands.push(JtagCommand {
+            packet: command(JTAG_BIT_OP_RD, &data),
+            capture: chunk.iter().map(|&(_, _, capture)| capture).collect(),
+            byte_mode: false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn select_clock() {
+        assert_eq!(clock_index(100), (0, 1875));
+        assert_eq!(clock_index(4000), (1, 3750));
+        assert_eq!(clock_index(15_000), (3, 15_000));
+        assert_eq!(clock_index(100_000), (5, 60_000));
+    }
+
+    #[test]
+    fn shift_bytes_and_bits() {
+        // Enter Shift-DR, shift 9 bits exiting on the last one
+        let mut bits = vec![(true, false, false), (false, false, false)];
+        bits.extend([(false, true, true); 8]);
+        bits.push((true, false, true));
+
+        let commands = jtag_commands(&bits);
+        assert_eq!(commands.len(), 3);
+
+        assert_eq!(commands[0].packet, [0xd2, 2, 0, 0x02, 0x03]);
+        assert_eq!(commands[0].response_size(), 4);
+
+        // The run of TMS low bits starts with the Capture-DR to Shift-DR transition.
+        assert_eq!(commands[1].packet, [0xd4, 1, 0, 0xfe]);
+        assert!(commands[1].byte_mode);
+        assert_eq!(commands[1].response_size(), 4);
+
+        assert_eq!(commands[2].packet, [0xd2, 4, 0, 0x10, 0x11, 0x02, 0x03]);
+        assert_eq!(commands[2].capture, [true, true]);
+
+        let captured: Vec<bool> = commands[1].captured_bits(&[0x81]).collect();
+        assert_eq!(captured, [false, false, false, false, false, false, true]);
+    }
+}
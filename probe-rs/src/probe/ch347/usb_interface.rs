@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use nusb::{
+    transfer::{Direction, EndpointType},
+    Interface,
+};
+
+use crate::probe::{
+    usb_util::InterfaceExt, DebugProbeError, DebugProbeSelector, ProbeCreationError,
+};
+
+use super::{is_ch347, protocol, Ch347Error};
+
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// USB class of the vendor specific JTAG interface.
+const VENDOR_SPECIFIC_CLASS: u8 = 0xff;
+
+/// Maximum size of a single write, which has to fit into the buffer of the chip.
+const MAX_WRITE_SIZE: usize = 510;
+
+pub struct Ch347UsbDevice {
+    interface: Interface,
+    endpoint_in: u8,
+    endpoint_out: u8,
+}
+
+impl std::fmt::Debug for Ch347UsbDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ch347UsbDevice").finish_non_exhaustive()
+    }
+}
+
+impl Ch347UsbDevice {
+    pub fn new_from_selector(selector: &DebugProbeSelector) -> Result<Self, ProbeCreationError> {
+        let device = nusb::list_devices()
+            .map_err(ProbeCreationError::Usb)?
+            .filter(is_ch347)
+            .find(|device| selector.matches(device))
+            .ok_or(ProbeCreationError::NotFound)?;
+
+        let device_handle = device.open().map_err(ProbeCreationError::Usb)?;
+
+        // Next to the UARTs, the chip has a single vendor specific interface, which is used
+        // for JTAG.
+        let mut endpoints = None;
+        if let Some(config) = device_handle.configurations().next() {
+            for altsetting in config.interface_alt_settings() {
+                if altsetting.class() != VENDOR_SPECIFIC_CLASS {
+                    continue;
+                }
+
+                let bulk = |direction| {
+                    altsetting
+                        .endpoints()
+                        .find(|ep| {
+                            ep.transfer_type() == EndpointType::Bulk && ep.direction() == direction
+                        })
+                        .map(|ep| ep.address())
+                };
+
+                if let (Some(endpoint_in), Some(endpoint_out)) =
+                    (bulk(Direction::In), bulk(Direction::Out))
+                {
+                    endpoints = Some((altsetting.interface_number(), endpoint_in, endpoint_out));
+                    break;
+                }
+            }
+        }
+
+        let Some((interface_number, endpoint_in, endpoint_out)) = endpoints else {
+            return Err(Ch347Error::EndpointNotFound.into());
+        };
+
+        let interface = device_handle
+            .claim_interface(interface_number)
+            .map_err(ProbeCreationError::Usb)?;
+        tracing::trace!("Claimed interface {interface_number} of USB device.");
+
+        Ok(Self {
+            interface,
+            endpoint_in,
+            endpoint_out,
+        })
+    }
+
+    /// Sends the encoded commands, and returns the responses.
+    ///
+    /// Commands are combined into writes of at most [`MAX_WRITE_SIZE`] bytes. After each
+    /// write, the responses of its commands are read back, their sizes given by
+    /// `response_sizes`.
+    pub(crate) fn transfer(
+        &mut self,
+        packets: &[Vec<u8>],
+        response_sizes: &[usize],
+    ) -> Result<Vec<Vec<u8>>, DebugProbeError> {
+        let mut responses = Vec::with_capacity(packets.len());
+
+        let mut start = 0;
+        while start < packets.len() {
+            let mut end = start;
+            let mut write = Vec::new();
+            while end < packets.len() && write.len() + packets[end].len() <= MAX_WRITE_SIZE {
+                write.extend_from_slice(&packets[end]);
+                end += 1;
+            }
+
+            tracing::trace!("Sending commands: {:02x?}", write);
+            self.interface
+                .write_bulk(self.endpoint_out, &write, TIMEOUT)
+                .map_err(DebugProbeError::Usb)?;
+
+            let expected: usize = response_sizes[start..end].iter().sum();
+            let response = self.read_exact(expected)?;
+            tracing::trace!("Received responses: {:02x?}", response);
+
+            let mut offset = 0;
+            for (packet, &size) in packets[start..end].iter().zip(&response_sizes[start..end]) {
+                let data = &response[offset..offset + size];
+                if data[0] != packet[0]
+                    || usize::from(u16::from_le_bytes([data[1], data[2]]))
+                        != size - protocol::HEADER_SIZE
+                {
+                    return Err(Ch347Error::InvalidResponse.into());
+                }
+
+                responses.push(data[protocol::HEADER_SIZE..].to_vec());
+                offset += size;
+            }
+
+            start = end;
+        }
+
+        Ok(responses)
+    }
+
+    fn read_exact(&mut self, len: usize) -> Result<Vec<u8>, DebugProbeError> {
+        let mut response = Vec::with_capacity(len);
+        let mut buf = [0; 512];
+        while response.len() < len {
+            let read = self
+                .interface
+                .read_bulk(self.endpoint_in, &mut buf, TIMEOUT)
+                .map_err(DebugProbeError::Usb)?;
+            response.extend_from_slice(&buf[..read]);
+        }
+
+        if response.len() != len {
+            return Err(Ch347Error::InvalidResponse.into());
+        }
+
+        Ok(response)
+    }
+}
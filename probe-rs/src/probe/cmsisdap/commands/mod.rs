@@ -4,6 +4,7 @@ pub mod swd;
 pub mod swj;
 pub mod swo;
 pub mod transfer;
+pub mod uart;
 
 use crate::probe::cmsisdap::commands::general::info::PacketSizeCommand;
 use crate::probe::usb_util::InterfaceExt;
@@ -31,6 +32,10 @@ pub enum CmsisDapError {
     SwoTraceStreamError,
     #[error("Requested SWO mode is not available on this probe")]
     SwoModeNotAvailable,
+    #[error("The probe does not support UART communication")]
+    UartNotAvailable,
+    #[error("Requested UART baud rate could not be configured")]
+    UartBaudrateNotConfigured,
     #[error("USB Error reading SWO data.")]
     SwoReadError(#[source] std::io::Error),
     #[error("Could not determine a suitable packet size for this probe")]
@@ -91,6 +96,28 @@ pub enum CmsisDapDevice {
         max_packet_size: usize,
         swo_ep: Option<(u8, usize)>,
     },
+
+    /// CMSIS-DAP v2 packets over a transport provided by the application.
+    Custom {
+        transport: Box<dyn CmsisDapTransport>,
+        max_packet_size: usize,
+    },
+}
+
+/// A transport for CMSIS-DAP v2 packets, for probes which probe-rs can't open on its own.
+///
+/// This allows driving a probe over a connection the application manages, e.g. a USB stack of
+/// its own or a network bridge. Packets are the same as on the bulk endpoints of a CMSIS-DAP v2
+/// probe.
+pub trait CmsisDapTransport: Send {
+    /// Reads a single response packet into `buf`, and returns its length.
+    ///
+    /// If no packet arrives within `timeout`, an error of kind [`ErrorKind::TimedOut`] has to
+    /// be returned.
+    fn read(&self, buf: &mut [u8], timeout: Duration) -> std::io::Result<usize>;
+
+    /// Writes a single command packet, and returns the number of bytes written.
+    fn write(&self, buf: &[u8], timeout: Duration) -> std::io::Result<usize>;
 }
 
 impl CmsisDapDevice {
@@ -107,6 +134,14 @@ impl CmsisDapDevice {
             CmsisDapDevice::V2 { handle, in_ep, .. } => handle
                 .read_bulk(*in_ep, buf, USB_TIMEOUT)
                 .map_err(SendError::UsbError),
+            CmsisDapDevice::Custom { transport, .. } => {
+                transport
+                    .read(buf, USB_TIMEOUT)
+                    .map_err(|e| match e.kind() {
+                        ErrorKind::TimedOut => SendError::Timeout,
+                        _ => SendError::UsbError(e),
+                    })
+            }
         }
     }
 
@@ -120,6 +155,9 @@ impl CmsisDapDevice {
                     .write_bulk(*out_ep, &buf[1..], USB_TIMEOUT)
                     .map_err(SendError::UsbError)
             }
+            CmsisDapDevice::Custom { transport, .. } => transport
+                .write(&buf[1..], USB_TIMEOUT)
+                .map_err(SendError::UsbError),
         }
     }
 
@@ -157,6 +195,19 @@ impl CmsisDapDevice {
                     }
                 }
             }
+
+            CmsisDapDevice::Custom {
+                transport,
+                max_packet_size,
+            } => {
+                let timeout = Duration::from_millis(1);
+                let mut discard = vec![0u8; *max_packet_size];
+                while let Ok(n) = transport.read(&mut discard, timeout) {
+                    if n == 0 {
+                        break;
+                    }
+                }
+            }
         }
     }
 
@@ -176,6 +227,10 @@ impl CmsisDapDevice {
             CmsisDapDevice::V2 {
                 ref mut max_packet_size,
                 ..
+            }
+            | CmsisDapDevice::Custom {
+                ref mut max_packet_size,
+                ..
             } => {
                 *max_packet_size = packet_size;
             }
@@ -218,7 +273,7 @@ impl CmsisDapDevice {
     /// Check if SWO streaming is supported by this device.
     pub(super) fn swo_streaming_supported(&self) -> bool {
         match self {
-            CmsisDapDevice::V1 { .. } => false,
+            CmsisDapDevice::V1 { .. } | CmsisDapDevice::Custom { .. } => false,
             CmsisDapDevice::V2 { swo_ep, .. } => swo_ep.is_some(),
         }
     }
@@ -230,7 +285,9 @@ impl CmsisDapDevice {
     /// On timeout, returns a zero-length buffer.
     pub(super) fn read_swo_stream(&self, timeout: Duration) -> Result<Vec<u8>, CmsisDapError> {
         match self {
-            CmsisDapDevice::V1 { .. } => Err(CmsisDapError::SwoModeNotAvailable),
+            CmsisDapDevice::V1 { .. } | CmsisDapDevice::Custom { .. } => {
+                Err(CmsisDapError::SwoModeNotAvailable)
+            }
             CmsisDapDevice::V2 { handle, swo_ep, .. } => match swo_ep {
                 Some((ep, len)) => {
                     let mut buf = vec![0u8; *len];
@@ -332,21 +389,77 @@ pub(crate) fn send_command<Req: Request>(
     })
 }
 
-fn send_command_inner<Req: Request>(
+/// Send several requests of the same kind, and return their responses in order.
+///
+/// Up to `max_in_flight` requests are sent before the first response is read, so the probe
+/// can start on the next request while the previous response is still on its way. This should
+/// not exceed the packet count reported by the probe.
+pub(crate) fn send_commands_pipelined<Req: Request>(
     device: &mut CmsisDapDevice,
-    request: Req,
-) -> Result<Req::Response, SendError> {
-    // Size the buffer for the maximum packet size.
-    // On v1, we always send this full-sized report, while
-    // on v2 we can truncate to just the required data.
-    // Add one byte for HID report ID.
-    let buffer_len: usize = match device {
+    requests: &[Req],
+    max_in_flight: usize,
+) -> Result<Vec<Req::Response>, CmsisDapError> {
+    let max_in_flight = max_in_flight.max(1);
+    let mut responses = Vec::with_capacity(requests.len());
+    let mut sent = 0;
+
+    while responses.len() < requests.len() {
+        while sent < requests.len() && sent - responses.len() < max_in_flight {
+            if let Err(e) = write_request(device, &requests[sent]) {
+                // Responses to the requests which were sent already would
+                // otherwise be mistaken for responses to later requests.
+                device.drain();
+                return Err(CmsisDapError::Send {
+                    command_id: Req::COMMAND_ID,
+                    source: e,
+                });
+            }
+            sent += 1;
+        }
+
+        match read_response(device, &requests[responses.len()]) {
+            Ok(response) => responses.push(response),
+            Err(e) => {
+                device.drain();
+                return Err(CmsisDapError::Send {
+                    command_id: Req::COMMAND_ID,
+                    source: e,
+                });
+            }
+        }
+    }
+
+    Ok(responses)
+}
+
+/// Size the buffer for the maximum packet size, plus one byte for the HID report ID.
+fn buffer_len(device: &CmsisDapDevice) -> usize {
+    match device {
         CmsisDapDevice::V1 { report_size, .. } => *report_size + 1,
         CmsisDapDevice::V2 {
             max_packet_size, ..
+        }
+        | CmsisDapDevice::Custom {
+            max_packet_size, ..
         } => *max_packet_size + 1,
-    };
-    let mut buffer = vec![0; buffer_len];
+    }
+}
+
+fn send_command_inner<Req: Request>(
+    device: &mut CmsisDapDevice,
+    request: Req,
+) -> Result<Req::Response, SendError> {
+    write_request(device, &request)?;
+    read_response(device, &request)
+}
+
+fn write_request<Req: Request>(
+    device: &mut CmsisDapDevice,
+    request: &Req,
+) -> Result<(), SendError> {
+    // On v1, we always send a full-sized report, while
+    // on v2 we can truncate to just the required data.
+    let mut buffer = vec![0; buffer_len(device)];
 
     // Leave byte 0 as the HID report, and write the command and request to the buffer.
     buffer[1] = Req::COMMAND_ID as u8;
@@ -364,6 +477,15 @@ fn send_command_inner<Req: Request>(
     let _ = device.write(&buffer[..size])?;
     trace_buffer("Transmit buffer", &buffer[..size]);
 
+    Ok(())
+}
+
+fn read_response<Req: Request>(
+    device: &mut CmsisDapDevice,
+    request: &Req,
+) -> Result<Req::Response, SendError> {
+    let mut buffer = vec![0; buffer_len(device)];
+
     // Read back response.
     let bytes_read = device.read(&mut buffer)?;
     let response_data = &buffer[..bytes_read];
@@ -0,0 +1,224 @@
+use scroll::{Pread, LE};
+
+use super::{CommandId, Request, SendError, Status};
+
+#[repr(u8)]
+#[allow(unused)]
+#[derive(Copy, Clone, Debug)]
+pub enum TransportRequest {
+    NoTransport = 0,
+    UsbComPort = 1,
+    DapCommand = 2,
+}
+
+impl Request for TransportRequest {
+    const COMMAND_ID: CommandId = CommandId::UartTransport;
+
+    type Response = TransportResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        buffer[0] = *self as u8;
+        Ok(1)
+    }
+
+    fn parse_response(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        Ok(TransportResponse(Status::from_byte(buffer[0])?))
+    }
+}
+
+#[derive(Debug)]
+pub struct TransportResponse(pub(crate) Status);
+
+/// Configures the UART for 8 data bits, no parity, one stop bit and no flow control.
+#[derive(Copy, Clone, Debug)]
+pub struct ConfigureRequest {
+    pub(crate) baud_rate: u32,
+}
+
+impl Request for ConfigureRequest {
+    const COMMAND_ID: CommandId = CommandId::UartConfigure;
+
+    type Response = ConfigureResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        assert!(
+            buffer.len() >= 5,
+            "Buffer for CMSIS-DAP command is too small. This is a bug, please report it."
+        );
+        // All control bits zero select 8N1 without flow control.
+        buffer[0] = 0;
+        buffer[1..5].copy_from_slice(&self.baud_rate.to_le_bytes());
+        Ok(5)
+    }
+
+    fn parse_response(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        if buffer.len() < 5 {
+            return Err(SendError::NotEnoughData);
+        }
+
+        Ok(ConfigureResponse {
+            status: buffer[0],
+            baud_rate: buffer
+                .pread_with(1, LE)
+                .map_err(|_| SendError::NotEnoughData)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigureResponse {
+    /// Bit 0 to 2 are set if the data bits, parity or stop bits are not supported.
+    pub(crate) status: u8,
+    pub(crate) baud_rate: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ControlRequest {
+    pub(crate) rx_enable: bool,
+    pub(crate) rx_disable: bool,
+    pub(crate) rx_flush: bool,
+    pub(crate) tx_enable: bool,
+    pub(crate) tx_disable: bool,
+    pub(crate) tx_flush: bool,
+}
+
+impl Request for ControlRequest {
+    const COMMAND_ID: CommandId = CommandId::UartControl;
+
+    type Response = ControlResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        buffer[0] = self.rx_enable as u8
+            | (self.rx_disable as u8) << 1
+            | (self.rx_flush as u8) << 2
+            | (self.tx_enable as u8) << 4
+            | (self.tx_disable as u8) << 5
+            | (self.tx_flush as u8) << 6;
+        Ok(1)
+    }
+
+    fn parse_response(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        Ok(ControlResponse(Status::from_byte(buffer[0])?))
+    }
+}
+
+#[derive(Debug)]
+pub struct ControlResponse(pub(crate) Status);
+
+/// Sends data to the target and reads the data received by the probe.
+#[derive(Clone, Debug)]
+pub struct TransferRequest<'a> {
+    pub(crate) data: &'a [u8],
+}
+
+impl Request for TransferRequest<'_> {
+    const COMMAND_ID: CommandId = CommandId::UartTransfer;
+
+    type Response = TransferResponse;
+
+    fn to_bytes(&self, buffer: &mut [u8]) -> Result<usize, SendError> {
+        assert!(
+            self.data.len() <= u8::MAX as usize && buffer.len() > self.data.len(),
+            "Buffer for CMSIS-DAP command is too small. This is a bug, please report it."
+        );
+        buffer[0] = self.data.len() as u8;
+        buffer[1..][..self.data.len()].copy_from_slice(self.data);
+        Ok(1 + self.data.len())
+    }
+
+    fn parse_response(&self, buffer: &[u8]) -> Result<Self::Response, SendError> {
+        if buffer.len() < 3 {
+            return Err(SendError::NotEnoughData);
+        }
+
+        let status = UartStatus::from(buffer[0]);
+        let sent = buffer[1] as usize;
+        let received = buffer[2] as usize;
+        let data = buffer
+            .get(3..3 + received)
+            .ok_or(SendError::NotEnoughData)?
+            .to_vec();
+
+        Ok(TransferResponse { status, sent, data })
+    }
+}
+
+#[derive(Debug)]
+pub struct TransferResponse {
+    pub(crate) status: UartStatus,
+    pub(crate) sent: usize,
+    pub(crate) data: Vec<u8>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UartStatus {
+    pub(crate) rx_data_lost: bool,
+    pub(crate) framing_error: bool,
+    pub(crate) parity_error: bool,
+    pub(crate) tx_data_lost: bool,
+}
+
+impl From<u8> for UartStatus {
+    fn from(value: u8) -> Self {
+        Self {
+            rx_data_lost: value & (1 << 0) != 0,
+            framing_error: value & (1 << 1) != 0,
+            parity_error: value & (1 << 2) != 0,
+            tx_data_lost: value & (1 << 3) != 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn control_bits() {
+        let request = ControlRequest {
+            rx_enable: true,
+            rx_flush: true,
+            tx_enable: true,
+            tx_flush: true,
+            ..Default::default()
+        };
+
+        let mut buffer = [0; 1];
+        assert_eq!(request.to_bytes(&mut buffer).unwrap(), 1);
+        assert_eq!(buffer, [0b0101_0101]);
+    }
+
+    #[test]
+    fn configure_8n1() {
+        let mut buffer = [0xff; 8];
+        let request = ConfigureRequest { baud_rate: 115_200 };
+        assert_eq!(request.to_bytes(&mut buffer).unwrap(), 5);
+        assert_eq!(buffer[..5], [0x00, 0x00, 0xc2, 0x01, 0x00]);
+
+        let response = request
+            .parse_response(&[0x00, 0x00, 0xc2, 0x01, 0x00])
+            .unwrap();
+        assert_eq!(response.status, 0);
+        assert_eq!(response.baud_rate, 115_200);
+    }
+
+    #[test]
+    fn transfer() {
+        let mut buffer = [0; 8];
+        let request = TransferRequest { data: b"hi" };
+        assert_eq!(request.to_bytes(&mut buffer).unwrap(), 3);
+        assert_eq!(buffer[..3], [2, b'h', b'i']);
+
+        let response = request
+            .parse_response(&[0x01, 2, 3, b'a', b'b', b'c', 0x00])
+            .unwrap();
+        assert!(response.status.rx_data_lost);
+        assert_eq!(response.sent, 2);
+        assert_eq!(response.data, b"abc");
+
+        assert!(matches!(
+            request.parse_response(&[0x00, 0, 4, b'a']),
+            Err(SendError::NotEnoughData)
+        ));
+    }
+}
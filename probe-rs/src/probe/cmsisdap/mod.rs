@@ -7,20 +7,25 @@ use crate::{
         communication_interface::{DapProbe, UninitializedArmProbe},
         dp::{Abort, Ctrl},
         swo::poll_interval_from_buf_size,
-        ArmCommunicationInterface, ArmError, DapError, Pins, PortType, RawDapAccess, Register,
-        SwoAccess, SwoConfig, SwoMode,
+        ArmCommunicationInterface, ArmError, BlockTransfer, DapError, Pins, PortType, RawDapAccess,
+        RawDapTransfer, Register, SwoAccess, SwoConfig, SwoMode,
     },
+    metrics::Metrics,
     probe::{
         cmsisdap::commands::{
-            general::info::{CapabilitiesCommand, PacketCountCommand, SWOTraceBufferSizeCommand},
+            general::info::{
+                CapabilitiesCommand, FirmwareVersionCommand, PacketCountCommand,
+                SWOTraceBufferSizeCommand,
+            },
             CmsisDapError,
         },
         BatchCommand, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector,
-        JtagChainItem, ProbeFactory, WireProtocol,
+        JtagChainItem, ProbeDetails, ProbeFactory, SwoCapabilities, UartAccess, WireProtocol,
     },
     CoreStatus,
 };
 
+pub use commands::CmsisDapTransport;
 use commands::{
     general::{
         connect::{ConnectRequest, ConnectResponse},
@@ -47,9 +52,9 @@ use commands::{
     swo,
     transfer::{
         configure::{ConfigureRequest, ConfigureResponse},
-        Ack, TransferBlockRequest, TransferBlockResponse, TransferRequest,
+        Ack, TransferBlockRequest, TransferRequest,
     },
-    CmsisDapDevice, Status,
+    uart, CmsisDapDevice, Status,
 };
 use probe_rs_target::ScanChainElement;
 
@@ -86,6 +91,7 @@ pub struct CmsisDap {
     device: CmsisDapDevice,
     _hw_version: u8,
     _jtag_version: u8,
+    firmware_version: Option<String>,
     protocol: Option<WireProtocol>,
 
     packet_size: u16,
@@ -94,6 +100,9 @@ pub struct CmsisDap {
     swo_buffer_size: Option<usize>,
     swo_active: bool,
     swo_streaming: bool,
+    uart_active: bool,
+    /// Data received while sending data over the UART.
+    uart_buffer: Vec<u8>,
     connected: bool,
 
     /// Speed in kHz
@@ -101,6 +110,8 @@ pub struct CmsisDap {
     scan_chain: Option<Vec<ScanChainElement>>,
 
     batch: Vec<BatchCommand>,
+
+    metrics: Metrics,
 }
 
 impl std::fmt::Debug for CmsisDap {
@@ -113,12 +124,26 @@ impl std::fmt::Debug for CmsisDap {
             .field("swo_buffer_size", &self.swo_buffer_size)
             .field("swo_active", &self.swo_active)
             .field("swo_streaming", &self.swo_streaming)
+            .field("uart_active", &self.uart_active)
             .field("speed_khz", &self.speed_khz)
             .finish()
     }
 }
 
 impl CmsisDap {
+    /// Creates a probe which exchanges CMSIS-DAP v2 packets over `transport`.
+    ///
+    /// The probe can be used with [`Probe::from_specific_probe`](crate::probe::Probe::from_specific_probe).
+    pub fn new_from_transport(
+        transport: Box<dyn CmsisDapTransport>,
+    ) -> Result<Self, DebugProbeError> {
+        Self::new_from_device(CmsisDapDevice::Custom {
+            transport,
+            // Updated once the probe reported its packet size.
+            max_packet_size: 64,
+        })
+    }
+
     fn new_from_device(mut device: CmsisDapDevice) -> Result<Self, DebugProbeError> {
         // Discard anything left in buffer, as otherwise
         // we'll get out of sync between requests and responses.
@@ -132,6 +157,9 @@ impl CmsisDap {
         let packet_count = commands::send_command(&mut device, PacketCountCommand {})?;
         let caps: Capabilities = commands::send_command(&mut device, CapabilitiesCommand {})?;
         tracing::debug!("Detected probe capabilities: {:?}", caps);
+        let firmware_version = commands::send_command(&mut device, FirmwareVersionCommand {})
+            .ok()
+            .flatten();
         let mut swo_buffer_size = None;
         if caps.swo_uart_implemented || caps.swo_manchester_implemented {
             let swo_size = commands::send_command(&mut device, SWOTraceBufferSizeCommand {})?;
@@ -143,6 +171,7 @@ impl CmsisDap {
             device,
             _hw_version: 0,
             _jtag_version: 0,
+            firmware_version,
             protocol: None,
             packet_count,
             packet_size,
@@ -150,10 +179,13 @@ impl CmsisDap {
             swo_buffer_size,
             swo_active: false,
             swo_streaming: false,
+            uart_active: false,
+            uart_buffer: Vec::new(),
             connected: false,
             speed_khz: 1_000,
             scan_chain: None,
             batch: Vec::new(),
+            metrics: Metrics::default(),
         })
     }
 
@@ -421,6 +453,7 @@ impl CmsisDap {
         )
         .map_err(CmsisDapError::from)
         .map_err(DebugProbeError::from)?;
+        self.metrics.record_round_trips(1);
 
         // We can assume that the single transfer is always executed,
         // no need to check here.
@@ -443,7 +476,10 @@ impl CmsisDap {
                         "CMSIS-DAP probe should always return data for a read.",
                     )))
                 }
-                Ack::Wait => Err(DapError::WaitResponse.into()),
+                Ack::Wait => {
+                    self.metrics.record_wait_response();
+                    Err(DapError::WaitResponse.into())
+                }
                 Ack::Fault => Err(DapError::FaultResponse.into()),
                 Ack::NoAck => Err(DapError::NoAcknowledge.into()),
             }
@@ -457,6 +493,7 @@ impl CmsisDap {
         )
         .map_err(CmsisDapError::from)
         .map_err(DebugProbeError::from)?;
+        self.metrics.record_round_trips(1);
 
         // We can assume that the single transfer is always executed,
         // no need to check here.
@@ -468,7 +505,10 @@ impl CmsisDap {
         } else {
             match response.last_transfer_response.ack {
                 Ack::Ok => Ok(()),
-                Ack::Wait => Err(DapError::WaitResponse.into()),
+                Ack::Wait => {
+                    self.metrics.record_wait_response();
+                    Err(DapError::WaitResponse.into())
+                }
                 Ack::Fault => Err(DapError::FaultResponse.into()),
                 Ack::NoAck => Err(DapError::NoAcknowledge.into()),
             }
@@ -482,11 +522,27 @@ impl CmsisDap {
     ///
     /// This will ensure any pending writes are processed and errors from them
     /// raised if necessary.
-    #[tracing::instrument(skip(self))]
+    #[tracing::instrument(skip(self), fields(length = self.batch.len()))]
     fn process_batch(&mut self) -> Result<Option<u32>, ArmError> {
-        let mut batch = std::mem::take(&mut self.batch);
+        let batch = std::mem::take(&mut self.batch);
+        let last_is_read = matches!(batch.last(), Some(BatchCommand::Read(..)));
+
+        let reads = self.send_batch(batch)?;
+
+        Ok(if last_is_read {
+            reads.last().copied()
+        } else {
+            None
+        })
+    }
+
+    /// Send the commands in `batch` to the probe, and return the values of all reads.
+    ///
+    /// The batch has to fit into a single packet.
+    fn send_batch(&mut self, mut batch: Vec<BatchCommand>) -> Result<Vec<u32>, ArmError> {
+        let mut reads = vec![];
         if batch.is_empty() {
-            return Ok(None);
+            return Ok(reads);
         }
 
         tracing::debug!("{} items in batch", batch.len());
@@ -512,6 +568,7 @@ impl CmsisDap {
             let response = commands::send_command(&mut self.device, transfers)
                 .map_err(CmsisDapError::from)
                 .map_err(DebugProbeError::from)?;
+            self.metrics.record_round_trips(1);
 
             let count = response.transfers.len();
 
@@ -528,7 +585,8 @@ impl CmsisDap {
             match response.last_transfer_response.ack {
                 Ack::Ok => {
                     tracing::trace!("Transfer status: ACK");
-                    return Ok(response.transfers[count - 1].data);
+                    reads.extend(response.transfers.iter().filter_map(|t| t.data));
+                    return Ok(reads);
                 }
                 Ack::NoAck => {
                     tracing::debug!(
@@ -563,8 +621,14 @@ impl CmsisDap {
                     }
 
                     let successful = count.saturating_sub(1);
+                    reads.extend(
+                        response.transfers[..successful]
+                            .iter()
+                            .filter_map(|t| t.data),
+                    );
                     tracing::trace!("draining {:?} and retries left {:?}", successful, retry);
                     batch.drain(0..successful);
+                    self.metrics.record_retry();
                 }
                 Ack::Wait => {
                     tracing::debug!(
@@ -572,6 +636,7 @@ impl CmsisDap {
                         count,
                         batch.len()
                     );
+                    self.metrics.record_wait_response();
 
                     self.write_abort({
                         let mut abort = Abort(0);
@@ -711,6 +776,30 @@ impl CmsisDap {
         }
     }
 
+    /// Enable or disable UART reception and transmission.
+    fn set_uart_control(&mut self, request: uart::ControlRequest) -> Result<(), DebugProbeError> {
+        let response = commands::send_command(&mut self.device, request)?;
+        match response {
+            uart::ControlResponse(Status::DAPOk) => Ok(()),
+            uart::ControlResponse(Status::DAPError) => Err(CmsisDapError::ErrorResponse.into()),
+        }
+    }
+
+    /// Send data over the UART with a DAP_UART_Transfer request, and
+    /// return the received data.
+    fn uart_transfer(&mut self, data: &[u8]) -> Result<uart::TransferResponse, DebugProbeError> {
+        let response = commands::send_command(&mut self.device, uart::TransferRequest { data })?;
+
+        if response.status.rx_data_lost {
+            tracing::warn!("The probe lost UART data, the receive buffer overflowed");
+        }
+        if response.status.framing_error || response.status.parity_error {
+            tracing::warn!("UART framing or parity error, is the baud rate correct?");
+        }
+
+        Ok(response)
+    }
+
     fn connect_if_needed(&mut self) -> Result<(), DebugProbeError> {
         if self.connected {
             return Ok(());
@@ -828,6 +917,10 @@ impl DebugProbe for CmsisDap {
             self.disable_swo()?;
         }
 
+        if self.uart_active {
+            self.disable_uart()?;
+        }
+
         let response = commands::send_command(&mut self.device, DisconnectRequest {})
             .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
 
@@ -847,11 +940,11 @@ impl DebugProbe for CmsisDap {
 
     fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
         match protocol {
-            WireProtocol::Jtag if self.capabilities._jtag_implemented => {
+            WireProtocol::Jtag if self.capabilities.jtag_implemented => {
                 self.protocol = Some(WireProtocol::Jtag);
                 Ok(())
             }
-            WireProtocol::Swd if self.capabilities._swd_implemented => {
+            WireProtocol::Swd if self.capabilities.swd_implemented => {
                 self.protocol = Some(WireProtocol::Swd);
                 Ok(())
             }
@@ -897,11 +990,23 @@ impl DebugProbe for CmsisDap {
         Some(self as _)
     }
 
+    fn get_uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        if self.capabilities.uart_communication_port_implemented {
+            Some(self as _)
+        } else {
+            None
+        }
+    }
+
     fn try_get_arm_interface<'probe>(
         self: Box<Self>,
     ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
     {
-        Ok(Box::new(ArmCommunicationInterface::new(self, false)))
+        let use_overrun_detect = self.use_overrun_detect();
+        Ok(Box::new(ArmCommunicationInterface::new(
+            self,
+            use_overrun_detect,
+        )))
     }
 
     fn has_arm_interface(&self) -> bool {
@@ -915,6 +1020,47 @@ impl DebugProbe for CmsisDap {
     fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
         Some(self)
     }
+
+    fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        // CMSIS-DAP has no command to read the target voltage.
+        Ok(None)
+    }
+
+    fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = metrics;
+    }
+
+    fn details(&mut self) -> ProbeDetails {
+        let caps = self.capabilities;
+
+        let mut protocols = vec![];
+        if caps.swd_implemented {
+            protocols.push(WireProtocol::Swd);
+        }
+        if caps.jtag_implemented {
+            protocols.push(WireProtocol::Jtag);
+        }
+
+        let swo = (caps.swo_uart_implemented || caps.swo_manchester_implemented).then_some(
+            SwoCapabilities {
+                uart: caps.swo_uart_implemented,
+                manchester: caps.swo_manchester_implemented,
+                max_baud: None,
+                buffer_size: self.swo_buffer_size,
+            },
+        );
+
+        ProbeDetails {
+            firmware_version: self.firmware_version.clone(),
+            protocols,
+            max_speed_khz: None,
+            swo,
+            packet_size: Some(self.packet_size as usize),
+            packet_count: Some(self.packet_count as usize),
+            target_voltage: None,
+            adaptive_clocking: false,
+        }
+    }
 }
 
 impl RawDapAccess for CmsisDap {
@@ -945,37 +1091,13 @@ impl RawDapAccess for CmsisDap {
         register_address: u8,
         values: &[u32],
     ) -> Result<(), ArmError> {
-        self.process_batch()?;
-
-        // the overhead for a single packet is 6 bytes
-        //
-        // [0]: HID overhead
-        // [1]: Category
-        // [2]: DAP Index
-        // [3]: Len 1
-        // [4]: Len 2
-        // [5]: Request type
-        //
-
-        let max_packet_size_words = (self.packet_size - 6) / 4;
-
-        let data_chunk_len = max_packet_size_words as usize;
-
-        for (i, chunk) in values.chunks(data_chunk_len).enumerate() {
-            let request =
-                TransferBlockRequest::write_request(register_address, port, Vec::from(chunk));
-
-            tracing::debug!("Transfer block: chunk={}, len={} bytes", i, chunk.len() * 4);
-
-            let resp: TransferBlockResponse =
-                commands::send_command(&mut self.device, request).map_err(DebugProbeError::from)?;
-
-            if resp.transfer_response != 1 {
-                return Err(DebugProbeError::from(CmsisDapError::ErrorResponse).into());
-            }
-        }
-
-        Ok(())
+        self.raw_block_sequence(
+            port,
+            &mut [BlockTransfer::WriteRepeated {
+                addr: register_address,
+                values,
+            }],
+        )
     }
 
     fn raw_read_block(
@@ -983,37 +1105,170 @@ impl RawDapAccess for CmsisDap {
         port: PortType,
         register_address: u8,
         values: &mut [u32],
+    ) -> Result<(), ArmError> {
+        self.raw_block_sequence(
+            port,
+            &mut [BlockTransfer::ReadRepeated {
+                addr: register_address,
+                values,
+            }],
+        )
+    }
+
+    fn raw_block_sequence(
+        &mut self,
+        port: PortType,
+        steps: &mut [BlockTransfer<'_>],
     ) -> Result<(), ArmError> {
         self.process_batch()?;
 
-        // the overhead for a single packet is 6 bytes
+        // A DAP_TransferBlock request has 5 bytes of overhead:
         //
-        // [0]: HID overhead
-        // [1]: Category
-        // [2]: DAP Index
-        // [3]: Len 1
-        // [4]: Len 2
-        // [5]: Request type
+        // [0]: Command
+        // [1]: DAP Index
+        // [2]: Count 1
+        // [3]: Count 2
+        // [4]: Request type
         //
+        // The response has 4 bytes of overhead: command, count, and response.
+        let max_write_words = (self.packet_size as usize - 5) / 4;
+        let max_read_words = (self.packet_size as usize - 4) / 4;
+
+        let mut requests = vec![];
+        for step in steps.iter() {
+            match step {
+                BlockTransfer::Write { addr, value } => {
+                    requests.push(TransferBlockRequest::write_request(
+                        *addr,
+                        port,
+                        vec![*value],
+                    ));
+                }
+                BlockTransfer::ReadRepeated { addr, values } => {
+                    for chunk in values.chunks(max_read_words) {
+                        requests.push(TransferBlockRequest::read_request(
+                            *addr,
+                            port,
+                            chunk.len() as u16,
+                        ));
+                    }
+                }
+                BlockTransfer::WriteRepeated { addr, values } => {
+                    for chunk in values.chunks(max_write_words) {
+                        requests.push(TransferBlockRequest::write_request(
+                            *addr,
+                            port,
+                            Vec::from(chunk),
+                        ));
+                    }
+                }
+            }
+        }
+
+        tracing::debug!(
+            "Sending {} block transfers, up to {} at once",
+            requests.len(),
+            self.packet_count
+        );
+
+        let responses = commands::send_commands_pipelined(
+            &mut self.device,
+            &requests,
+            self.packet_count as usize,
+        )
+        .map_err(DebugProbeError::from)?;
+        self.metrics.record_round_trips(responses.len());
+
+        if let Some(i) = responses.iter().position(|r| r.transfer_response != 1) {
+            // The lowest bits hold the ACK of the failed transfer.
+            if responses[i].transfer_response & 0b111 == 2 {
+                self.metrics.record_wait_response();
+            }
 
-        let max_packet_size_words = (self.packet_size - 6) / 4;
+            tracing::debug!(
+                "Block transfer {}/{} failed with response {:#x}",
+                i + 1,
+                responses.len(),
+                responses[i].transfer_response
+            );
+            return Err(DebugProbeError::from(CmsisDapError::ErrorResponse).into());
+        }
 
-        let data_chunk_len = max_packet_size_words as usize;
+        let mut responses = responses.into_iter();
+        for step in steps.iter_mut() {
+            match step {
+                BlockTransfer::Write { .. } => {
+                    responses.next();
+                }
+                BlockTransfer::ReadRepeated { values, .. } => {
+                    for chunk in values.chunks_mut(max_read_words) {
+                        // NOTE(unwrap): There is a response for every request.
+                        let response = responses.next().unwrap();
+                        if response.transfer_data.len() != chunk.len() {
+                            return Err(DebugProbeError::from(CmsisDapError::ErrorResponse).into());
+                        }
+                        chunk.copy_from_slice(&response.transfer_data);
+                    }
+                }
+                BlockTransfer::WriteRepeated { values, .. } => {
+                    for _ in values.chunks(max_write_words) {
+                        responses.next();
+                    }
+                }
+            }
+        }
 
-        for (i, chunk) in values.chunks_mut(data_chunk_len).enumerate() {
-            let request =
-                TransferBlockRequest::read_request(register_address, port, chunk.len() as u16);
+        Ok(())
+    }
 
-            tracing::debug!("Transfer block: chunk={}, len={} bytes", i, chunk.len() * 4);
+    fn raw_transfer_batch(&mut self, transfers: &mut [RawDapTransfer]) -> Result<(), ArmError> {
+        self.process_batch()?;
 
-            let resp: TransferBlockResponse =
-                commands::send_command(&mut self.device, request).map_err(DebugProbeError::from)?;
+        // A transfer request has 3 bytes of overhead, and each transfer takes
+        // 1 byte plus 4 bytes of data for writes. The response has 3 bytes of
+        // overhead, plus 4 bytes for each read.
+        let max_bytes = self.packet_size as usize - 3;
+
+        let mut start = 0;
+        while start < transfers.len() {
+            let mut request_bytes = 0;
+            let mut response_bytes = 0;
+            let mut end = start;
+            while end < transfers.len() && end - start < u8::MAX as usize {
+                let (request, response) = match transfers[end] {
+                    RawDapTransfer::Read { .. } => (1, 4),
+                    RawDapTransfer::Write { .. } => (5, 0),
+                };
+                if request_bytes + request > max_bytes || response_bytes + response > max_bytes {
+                    break;
+                }
+                request_bytes += request;
+                response_bytes += response;
+                end += 1;
+            }
 
-            if resp.transfer_response != 1 {
-                return Err(DebugProbeError::from(CmsisDapError::ErrorResponse).into());
+            let chunk = &mut transfers[start..end];
+            let batch = chunk
+                .iter()
+                .map(|transfer| match *transfer {
+                    RawDapTransfer::Read { port, addr, .. } => {
+                        BatchCommand::Read(port, addr as u16)
+                    }
+                    RawDapTransfer::Write { port, addr, value } => {
+                        BatchCommand::Write(port, addr as u16, value)
+                    }
+                })
+                .collect();
+
+            let mut reads = self.send_batch(batch)?.into_iter();
+            for transfer in chunk {
+                if let RawDapTransfer::Read { value, .. } = transfer {
+                    // NOTE(unwrap): A successful batch returns data for every read.
+                    *value = reads.next().unwrap();
+                }
             }
 
-            chunk.clone_from_slice(&resp.transfer_data[..]);
+            start = end;
         }
 
         Ok(())
@@ -1218,6 +1473,97 @@ impl SwoAccess for CmsisDap {
     }
 }
 
+impl UartAccess for CmsisDap {
+    fn enable_uart(&mut self, baud_rate: u32) -> Result<u32, DebugProbeError> {
+        if !self.capabilities.uart_communication_port_implemented {
+            return Err(CmsisDapError::UartNotAvailable.into());
+        }
+
+        tracing::debug!("Starting UART with a baud rate of {}", baud_rate);
+
+        // Route the UART data through DAP_UART_Transfer instead of a USB COM port.
+        let response =
+            commands::send_command(&mut self.device, uart::TransportRequest::DapCommand)?;
+        if let uart::TransportResponse(Status::DAPError) = response {
+            return Err(CmsisDapError::UartNotAvailable.into());
+        }
+
+        let response =
+            commands::send_command(&mut self.device, uart::ConfigureRequest { baud_rate })?;
+        if response.status != 0 || response.baud_rate == 0 {
+            return Err(CmsisDapError::UartBaudrateNotConfigured.into());
+        }
+        if response.baud_rate != baud_rate {
+            tracing::warn!(
+                "Target UART baud rate not met: requested {}, got {}",
+                baud_rate,
+                response.baud_rate
+            );
+        }
+
+        self.set_uart_control(uart::ControlRequest {
+            rx_enable: true,
+            rx_flush: true,
+            tx_enable: true,
+            tx_flush: true,
+            ..Default::default()
+        })?;
+
+        self.uart_buffer.clear();
+        self.uart_active = true;
+        Ok(response.baud_rate)
+    }
+
+    fn disable_uart(&mut self) -> Result<(), DebugProbeError> {
+        tracing::debug!("Stopping UART");
+        self.set_uart_control(uart::ControlRequest {
+            rx_disable: true,
+            tx_disable: true,
+            ..Default::default()
+        })?;
+        self.uart_active = false;
+        Ok(())
+    }
+
+    fn read_uart(&mut self) -> Result<Vec<u8>, DebugProbeError> {
+        if !self.uart_active {
+            return Ok(Vec::new());
+        }
+
+        let mut data = std::mem::take(&mut self.uart_buffer);
+        loop {
+            let response = self.uart_transfer(&[])?;
+            if response.data.is_empty() {
+                break;
+            }
+            data.extend_from_slice(&response.data);
+        }
+
+        tracing::trace!("UART data: {:?}", data);
+        Ok(data)
+    }
+
+    fn write_uart(&mut self, mut data: &[u8]) -> Result<(), DebugProbeError> {
+        if !self.uart_active {
+            return Err(CmsisDapError::UartNotAvailable.into());
+        }
+
+        // The request contains the command ID and the number of bytes.
+        let chunk_size = usize::min(self.packet_size as usize - 2, u8::MAX as usize);
+
+        while !data.is_empty() {
+            let chunk = &data[..usize::min(chunk_size, data.len())];
+            let response = self.uart_transfer(chunk)?;
+            self.uart_buffer.extend_from_slice(&response.data);
+
+            // Bytes which did not fit in the transmit buffer of the probe are sent again.
+            data = &data[usize::min(response.sent, chunk.len())..];
+        }
+
+        Ok(())
+    }
+}
+
 impl Drop for CmsisDap {
     fn drop(&mut self) {
         tracing::debug!("Detaching from CMSIS-DAP probe");
@@ -1230,6 +1576,10 @@ impl Drop for CmsisDap {
             let _ = self.disable_swo();
         }
 
+        if self.uart_active {
+            let _ = self.disable_uart();
+        }
+
         let _ = self.detach();
     }
 }
@@ -1242,3 +1592,238 @@ impl From<ScanChainError> for CmsisDapError {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::VecDeque,
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    /// Answers the info commands sent while opening a probe.
+    #[derive(Default)]
+    struct InfoTransport {
+        responses: Mutex<VecDeque<Vec<u8>>>,
+    }
+
+    impl CmsisDapTransport for InfoTransport {
+        fn read(&self, buf: &mut [u8], _timeout: Duration) -> io::Result<usize> {
+            let response = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or(io::ErrorKind::TimedOut)?;
+            buf[..response.len()].copy_from_slice(&response);
+            Ok(response.len())
+        }
+
+        fn write(&self, buf: &[u8], _timeout: Duration) -> io::Result<usize> {
+            let response = match buf {
+                // Packet size
+                [0x00, 0xFF, ..] => vec![0x00, 2, 0x00, 0x02],
+                // Packet count
+                [0x00, 0xFE, ..] => vec![0x00, 1, 4],
+                // Capabilities: SWD only
+                [0x00, 0xF0, ..] => vec![0x00, 1, 0x01],
+                // Firmware version
+                [0x00, 0x04, ..] => vec![0x00, 6, b'2', b'.', b'1', b'.', b'0', 0],
+                // Commands sent when the probe is dropped
+                [command, ..] => vec![*command, 0x00],
+                [] => return Err(io::ErrorKind::InvalidInput.into()),
+            };
+            self.responses.lock().unwrap().push_back(response);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn open_over_custom_transport() {
+        let probe = CmsisDap::new_from_transport(Box::<InfoTransport>::default()).unwrap();
+
+        assert_eq!(probe.packet_size, 512);
+        assert_eq!(probe.packet_count, 4);
+        assert!(probe.capabilities.swd_implemented);
+        assert!(!probe.capabilities.jtag_implemented);
+        assert!(matches!(
+            probe.device,
+            CmsisDapDevice::Custom {
+                max_packet_size: 512,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn details_report_capabilities() {
+        let mut probe = CmsisDap::new_from_transport(Box::<InfoTransport>::default()).unwrap();
+
+        assert_eq!(
+            probe.details(),
+            ProbeDetails {
+                firmware_version: Some("2.1.0".to_string()),
+                protocols: vec![WireProtocol::Swd],
+                max_speed_khz: None,
+                swo: None,
+                packet_size: Some(512),
+                packet_count: Some(4),
+                target_voltage: None,
+                adaptive_clocking: false,
+            }
+        );
+    }
+
+    /// Answers `DAP_TransferBlock` commands, on top of the info commands.
+    ///
+    /// Reads return consecutive values, so responses which get out of sync with their requests
+    /// show up as wrong data.
+    #[derive(Default)]
+    struct BlockTransport {
+        info: InfoTransport,
+        /// The request byte and transfer count of each block transfer.
+        transfers: Mutex<Vec<(u8, u16)>>,
+        /// The most responses which were pending when a block transfer was sent.
+        max_in_flight: Mutex<usize>,
+        next_value: Mutex<u32>,
+        /// The index of a block transfer which can't be sent.
+        fail_send: Option<usize>,
+        /// The index of a block transfer which is answered with a FAULT.
+        fail_response: Option<usize>,
+    }
+
+    impl CmsisDapTransport for Arc<BlockTransport> {
+        fn read(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+            self.info.read(buf, timeout)
+        }
+
+        fn write(&self, buf: &[u8], timeout: Duration) -> io::Result<usize> {
+            let [0x06, _dap_index, count_lo, count_hi, request, ..] = *buf else {
+                return self.info.write(buf, timeout);
+            };
+
+            let count = u16::from_le_bytes([count_lo, count_hi]);
+            let mut transfers = self.transfers.lock().unwrap();
+            let index = transfers.len();
+            transfers.push((request, count));
+            if self.fail_send == Some(index) {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+
+            let mut responses = self.info.responses.lock().unwrap();
+            let mut max_in_flight = self.max_in_flight.lock().unwrap();
+            *max_in_flight = (*max_in_flight).max(responses.len());
+
+            let response = if self.fail_response == Some(index) {
+                vec![0x06, 0, 0, 0x04]
+            } else {
+                let mut response = vec![0x06, count_lo, count_hi, 0x01];
+                if request & 0x02 != 0 {
+                    let mut next_value = self.next_value.lock().unwrap();
+                    for _ in 0..count {
+                        response.extend_from_slice(&next_value.to_le_bytes());
+                        *next_value += 1;
+                    }
+                }
+                response
+            };
+            responses.push_back(response);
+
+            Ok(buf.len())
+        }
+    }
+
+    fn open_block_transport(transport: BlockTransport) -> (CmsisDap, Arc<BlockTransport>) {
+        let transport = Arc::new(transport);
+        let probe = CmsisDap::new_from_transport(Box::new(transport.clone())).unwrap();
+        (probe, transport)
+    }
+
+    #[test]
+    fn block_sequence_fills_packets() {
+        let (mut probe, transport) = open_block_transport(BlockTransport::default());
+
+        let mut read = vec![0; 300];
+        let written = vec![0; 300];
+        probe
+            .raw_block_sequence(
+                PortType::AccessPort,
+                &mut [
+                    BlockTransfer::Write {
+                        addr: 0x4,
+                        value: 0x2000_0000,
+                    },
+                    BlockTransfer::ReadRepeated {
+                        addr: 0xC,
+                        values: &mut read,
+                    },
+                    BlockTransfer::WriteRepeated {
+                        addr: 0xC,
+                        values: &written,
+                    },
+                ],
+            )
+            .unwrap();
+
+        // 512 byte packets hold 127 words of read data, or 126 words of write data.
+        assert_eq!(
+            *transport.transfers.lock().unwrap(),
+            [
+                (0x05, 1),
+                (0x0F, 127),
+                (0x0F, 127),
+                (0x0F, 46),
+                (0x0D, 126),
+                (0x0D, 126),
+                (0x0D, 48)
+            ]
+        );
+        assert_eq!(read, (0..300).collect::<Vec<_>>());
+        // The probe reports a packet count of 4.
+        assert_eq!(*transport.max_in_flight.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn failed_send_drains_pending_responses() {
+        let (mut probe, transport) = open_block_transport(BlockTransport {
+            fail_send: Some(2),
+            ..Default::default()
+        });
+
+        let mut values = vec![0; 300];
+        probe
+            .raw_read_block(PortType::AccessPort, 0xC, &mut values)
+            .unwrap_err();
+        assert!(transport.info.responses.lock().unwrap().is_empty());
+
+        // The responses to the first two packets were discarded, so the next read is in sync.
+        let mut values = vec![0; 4];
+        probe
+            .raw_read_block(PortType::AccessPort, 0xC, &mut values)
+            .unwrap();
+        assert_eq!(values, [254, 255, 256, 257]);
+    }
+
+    #[test]
+    fn failed_block_transfer_keeps_responses_in_sync() {
+        let (mut probe, transport) = open_block_transport(BlockTransport {
+            fail_response: Some(1),
+            ..Default::default()
+        });
+
+        let mut values = vec![0; 300];
+        probe
+            .raw_read_block(PortType::AccessPort, 0xC, &mut values)
+            .unwrap_err();
+        // All packets were in flight before the failure was seen.
+        assert_eq!(transport.transfers.lock().unwrap().len(), 3);
+
+        let mut values = vec![0; 4];
+        probe
+            .raw_read_block(PortType::AccessPort, 0xC, &mut values)
+            .unwrap();
+        assert_eq!(values, [173, 174, 175, 176]);
+    }
+}
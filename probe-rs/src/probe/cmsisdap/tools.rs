@@ -105,14 +105,17 @@ fn get_cmsisdap_info(device: &DeviceInfo) -> Option<DebugProbeInfo> {
             tracing::trace!("No HID interface for CMSIS-DAP found.")
         }
 
-        Some(DebugProbeInfo::new(
-            prod_str.to_string(),
-            device.vendor_id(),
-            device.product_id(),
-            sn_str.map(Into::into),
-            &CmsisDapFactory,
-            hid_interface,
-        ))
+        Some(
+            DebugProbeInfo::new(
+                prod_str.to_string(),
+                device.vendor_id(),
+                device.product_id(),
+                sn_str.map(Into::into),
+                &CmsisDapFactory,
+                hid_interface,
+            )
+            .with_usb_port(device),
+        )
     } else {
         None
     }
@@ -234,6 +237,11 @@ pub fn open_device_from_selector(
 ) -> Result<CmsisDapDevice, ProbeCreationError> {
     tracing::trace!("Attempting to open device matching {}", selector);
 
+    // CMSIS-DAP probes are only connected over USB.
+    if selector.ip_address.is_some() {
+        return Err(ProbeCreationError::NotFound);
+    }
+
     // We need to use nusb to detect the proper HID interface to use
     // if a probe has multiple HID interfaces. The hidapi lib unfortunately
     // offers no method to get the interface description string directly,
@@ -268,9 +276,20 @@ pub fn open_device_from_selector(
     }
 
     // If nusb failed or the device didn't support v2, try using hidapi to open in v1 mode.
-    let vid = selector.vendor_id;
-    let pid = selector.product_id;
-    let sn = selector.serial_number.as_deref();
+    // A selector for a USB port does not contain the VID and PID, so use those of the
+    // device found on that port.
+    let (vid, pid, sn) = match (&selector.usb_port, &hid_device_info) {
+        (Some(_), Some(info)) => (
+            info.vendor_id,
+            info.product_id,
+            info.serial_number.as_deref(),
+        ),
+        _ => (
+            selector.vendor_id,
+            selector.product_id,
+            selector.serial_number.as_deref(),
+        ),
+    };
 
     tracing::debug!(
         "Attempting to open {:04x}:{:04x} in CMSIS-DAP v1 mode",
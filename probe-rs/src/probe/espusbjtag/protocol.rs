@@ -494,6 +494,7 @@ pub(super) fn list_espjtag_devices() -> Vec<DebugProbeInfo> {
                 &EspUsbJtagFactory,
                 None,
             )
+            .with_usb_port(&device)
         })
         .collect()
 }
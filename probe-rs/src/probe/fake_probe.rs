@@ -1,7 +1,7 @@
 #![allow(missing_docs)] // Don't require docs for test code
 use std::{
     cell::RefCell,
-    collections::{BTreeSet, VecDeque},
+    collections::{BTreeSet, HashMap, VecDeque},
     fmt::Debug,
     sync::Arc,
 };
@@ -11,17 +11,23 @@ use probe_rs_target::ScanChainElement;
 use crate::{
     architecture::arm::{
         ap::memory_ap::{mock::MockMemoryAp, MemoryAp},
-        armv8m::Dhcsr,
+        armv8m::{Dhcsr, FpCtrl},
         communication_interface::{
             ArmDebugState, Initialized, SwdSequence, Uninitialized, UninitializedArmProbe,
         },
+        core::{
+            armv7m::{Aircr, Demcr, FpRev1CompX},
+            cortex_m::{Dcrdr, Dcrsr},
+            Dfsr,
+        },
         memory::{adi_v5_memory_interface::ADIMemoryInterface, ArmMemoryInterface},
         sequences::ArmDebugSequence,
         ArmError, ArmProbeInterface, DapAccess, DpAddress, FullyQualifiedApAddress, PortType,
         RawDapAccess, SwoAccess,
     },
+    config::TargetSelector,
     probe::{DebugProbe, DebugProbeError, Probe, WireProtocol},
-    Error, MemoryInterface, MemoryMappedRegister,
+    Error, MemoryInterface, MemoryMappedRegister, Permissions, Session,
 };
 
 /// This is a mock probe which can be used for mocking things in tests or for dry runs.
@@ -48,18 +54,231 @@ enum MockedAp {
     Core(MockCore),
 }
 
-struct MockCore {
+/// A simulated Cortex-M core.
+///
+/// The core only executes a few instructions, but it keeps its memory and core registers,
+/// and follows the halt, step and run requests written to the debug registers. This is
+/// enough to attach to it and exercise the tools without any hardware.
+pub(crate) struct MockCore {
     dhcsr: Dhcsr,
 
     /// Is the core halted?
     is_halted: bool,
+
+    /// Core registers, indexed by their DCRSR register selector.
+    registers: Box<[u32; 128]>,
+
+    /// Value of the DCRDR register, used to transfer core register values.
+    dcrdr: u32,
+
+    /// Contents of the memory. Memory which was never written reads as zero.
+    memory: HashMap<u64, u8>,
 }
 
 impl MockCore {
+    /// The DCRSR register selectors of the link register and the program counter.
+    const LR: usize = 14;
+    const PC: usize = 15;
+
     pub fn new() -> Self {
-        Self {
+        let mut core = Self {
             dhcsr: Dhcsr(0),
             is_halted: false,
+            registers: Box::new([0; 128]),
+            dcrdr: 0,
+            memory: HashMap::new(),
+        };
+
+        // Report a Cortex-M4 in CPUID, with a FPB providing 6 code comparators.
+        core.write_word(0xE000_ED00, 0x410f_c241);
+        core.write_word(FpCtrl::ADDRESS_OFFSET, 0x0000_0260);
+
+        core
+    }
+
+    fn read_byte(&self, address: u64) -> u8 {
+        self.memory.get(&address).copied().unwrap_or(0)
+    }
+
+    fn write_byte(&mut self, address: u64, value: u8) {
+        self.memory.insert(address, value);
+    }
+
+    fn read_word(&self, address: u64) -> u32 {
+        let bytes = std::array::from_fn(|i| self.read_byte(address + i as u64));
+        u32::from_le_bytes(bytes)
+    }
+
+    fn write_word(&mut self, address: u64, value: u32) {
+        for (i, byte) in value.to_le_bytes().into_iter().enumerate() {
+            self.write_byte(address + i as u64, byte);
+        }
+    }
+
+    /// Executes the instructions from the program counter after the core was started,
+    /// until it halts at a breakpoint.
+    fn run(&mut self) {
+        while !self.is_halted {
+            if self.hw_breakpoint_at(self.registers[Self::PC]) {
+                self.halt_at_breakpoint();
+            } else if !self.step() {
+                // The core keeps running.
+                return;
+            }
+        }
+    }
+
+    /// Executes the instruction at the program counter, and returns if it is supported.
+    ///
+    /// Only `bx lr` and `bkpt` are executed, so that a function returning right away can be
+    /// called. The program counter isn't moved for any other instruction.
+    fn step(&mut self) -> bool {
+        let pc = self.registers[Self::PC] as u64;
+        let instruction = u16::from_le_bytes([self.read_byte(pc), self.read_byte(pc + 1)]);
+
+        match instruction {
+            // bx lr
+            0x4770 => self.registers[Self::PC] = self.registers[Self::LR] & !1,
+            // bkpt #imm
+            0xbe00..=0xbeff => self.halt_at_breakpoint(),
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Is an enabled FPB comparator set to the given address?
+    fn hw_breakpoint_at(&self, address: u32) -> bool {
+        let fp_ctrl = FpCtrl::from(self.read_word(FpCtrl::ADDRESS_OFFSET));
+        if !fp_ctrl.enable() {
+            return false;
+        }
+
+        (0..fp_ctrl.num_code()).any(|index| {
+            let comparator =
+                FpRev1CompX::from(self.read_word(FpRev1CompX::ADDRESS_OFFSET + 4 * index as u64));
+            let half_word = match comparator.replace() {
+                0b01 => 0,
+                0b10 => 2,
+                _ => return false,
+            };
+            comparator.enable() && (comparator.comp() << 2) | half_word == address
+        })
+    }
+
+    fn halt_at_breakpoint(&mut self) {
+        self.is_halted = true;
+
+        let mut dfsr = Dfsr(self.read_word(Dfsr::ADDRESS_OFFSET));
+        dfsr.set_bkpt(true);
+        self.write_word(Dfsr::ADDRESS_OFFSET, dfsr.into());
+    }
+
+    /// Reads a word, emulating the debug registers.
+    fn read_register_or_memory(&self, address: u64) -> u32 {
+        match address {
+            Dhcsr::ADDRESS_OFFSET => {
+                let mut dhcsr: u32 = self.dhcsr.into();
+
+                if self.is_halted {
+                    dhcsr |= 1 << 17;
+                }
+
+                // Always set S_REGRDY, and say that a register value can
+                // be read.
+                dhcsr |= 1 << 16;
+
+                tracing::trace!("MockCore: Read DHCSR = {:#010x}", dhcsr);
+                dhcsr
+            }
+            Dcrdr::ADDRESS_OFFSET => self.dcrdr,
+            _ => {
+                let value = self.read_word(address);
+                tracing::trace!("MockCore: Read {:#010x} = {:#010x}", address, value);
+                value
+            }
+        }
+    }
+
+    /// Writes a word, emulating the debug registers.
+    fn write_register_or_memory(&mut self, address: u64, word: u32) {
+        match address {
+            Dhcsr::ADDRESS_OFFSET => {
+                let dbg_key = (word >> 16) & 0xffff;
+
+                if dbg_key == 0xa05f {
+                    // Mask out dbg key
+                    self.dhcsr = Dhcsr::from(word & 0xffff);
+                    tracing::trace!("MockCore: Write DHCSR = {:#010x}", word);
+
+                    let was_halted = self.is_halted;
+                    self.is_halted = self.dhcsr.c_halt();
+
+                    if !self.dhcsr.c_halt() && self.dhcsr.c_debugen() && self.dhcsr.c_step() {
+                        tracing::debug!("MockCore: Single step requested, setting s_halt");
+                        self.step();
+                        self.is_halted = true;
+                    }
+
+                    if was_halted && !self.is_halted {
+                        self.run();
+                    }
+
+                    if self.is_halted && (!was_halted || self.dhcsr.c_step()) {
+                        // Report the halt request as reason in the DFSR.
+                        let mut dfsr = Dfsr(self.read_word(Dfsr::ADDRESS_OFFSET));
+                        dfsr.set_halted(true);
+                        self.write_word(Dfsr::ADDRESS_OFFSET, dfsr.into());
+                    }
+                }
+            }
+            Dcrsr::ADDRESS_OFFSET => {
+                let register = (word & 0x7f) as usize;
+                let is_write = word & (1 << 16) != 0;
+
+                if is_write {
+                    self.registers[register] = self.dcrdr;
+                } else {
+                    self.dcrdr = self.registers[register];
+                }
+            }
+            Dcrdr::ADDRESS_OFFSET => self.dcrdr = word,
+            Aircr::ADDRESS_OFFSET => {
+                let aircr = Aircr::from(word);
+                let demcr = Demcr::from(self.read_word(Demcr::ADDRESS_OFFSET));
+
+                // A reset halts the core if the reset vector catch is enabled.
+                if word >> 16 == 0x05fa
+                    && (aircr.sysresetreq() || aircr.vectreset())
+                    && demcr.vc_corereset()
+                {
+                    tracing::debug!("MockCore: Reset with vector catch, halting");
+                    self.is_halted = true;
+
+                    let mut dfsr = Dfsr(self.read_word(Dfsr::ADDRESS_OFFSET));
+                    dfsr.set_vcatch(true);
+                    self.write_word(Dfsr::ADDRESS_OFFSET, dfsr.into());
+                }
+
+                // Keep the last request, so the type of reset can be checked.
+                self.write_word(address, word);
+            }
+            Dfsr::ADDRESS_OFFSET => {
+                // The DFSR bits are cleared by writing one to them.
+                let dfsr = self.read_word(address);
+                self.write_word(address, dfsr & !word);
+            }
+            FpCtrl::ADDRESS_OFFSET => {
+                // Only ENABLE can be written, and only together with KEY.
+                if word & 0b10 != 0 {
+                    let fp_ctrl = self.read_word(address);
+                    self.write_word(address, (fp_ctrl & !1) | (word & 1));
+                }
+            }
+            _ => {
+                tracing::trace!("MockCore: Write {:#010x} = {:#010x}", address, word);
+                self.write_word(address, word);
+            }
         }
     }
 }
@@ -80,90 +299,77 @@ impl SwdSequence for &mut MockCore {
 }
 
 impl MemoryInterface<ArmError> for &mut MockCore {
-    fn read_8(&mut self, _address: u64, _data: &mut [u8]) -> Result<(), ArmError> {
-        todo!()
-    }
+    fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), ArmError> {
+        for (i, val) in data.iter_mut().enumerate() {
+            *val = self.read_byte(address + i as u64);
+        }
 
-    fn read_16(&mut self, _address: u64, _data: &mut [u16]) -> Result<(), ArmError> {
-        todo!()
+        Ok(())
     }
 
-    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+    fn read_16(&mut self, address: u64, data: &mut [u16]) -> Result<(), ArmError> {
         for (i, val) in data.iter_mut().enumerate() {
-            let address = address + (i as u64 * 4);
-
-            match address {
-                // DHCSR
-                Dhcsr::ADDRESS_OFFSET => {
-                    let mut dhcsr: u32 = self.dhcsr.into();
+            let address = address + (i as u64 * 2);
+            *val = u16::from_le_bytes([self.read_byte(address), self.read_byte(address + 1)]);
+        }
 
-                    if self.is_halted {
-                        dhcsr |= 1 << 17;
-                    }
+        Ok(())
+    }
 
-                    // Always set S_REGRDY, and say that a register value can
-                    // be read.
-                    dhcsr |= 1 << 16;
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+        for (i, val) in data.iter_mut().enumerate() {
+            *val = self.read_register_or_memory(address + (i as u64 * 4));
+        }
 
-                    *val = dhcsr;
-                    println!("Read  DHCSR: {:#x} = {:#x}", address, val);
-                }
+        Ok(())
+    }
 
-                _ => {
-                    *val = 0;
-                    println!("Read {:#010x} = 0", address);
-                }
-            }
+    fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), ArmError> {
+        for (i, val) in data.iter_mut().enumerate() {
+            let address = address + (i as u64 * 8);
+            let low = self.read_register_or_memory(address) as u64;
+            let high = self.read_register_or_memory(address + 4) as u64;
+            *val = low | (high << 32);
         }
 
         Ok(())
     }
 
-    fn read_64(&mut self, _address: u64, _data: &mut [u64]) -> Result<(), ArmError> {
-        todo!()
-    }
+    fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), ArmError> {
+        for (i, byte) in data.iter().enumerate() {
+            self.write_byte(address + i as u64, *byte);
+        }
 
-    fn write_8(&mut self, _address: u64, _data: &[u8]) -> Result<(), ArmError> {
-        todo!()
+        Ok(())
     }
 
-    fn write_16(&mut self, _address: u64, _data: &[u16]) -> Result<(), ArmError> {
-        todo!()
+    fn write_16(&mut self, address: u64, data: &[u16]) -> Result<(), ArmError> {
+        for (i, half_word) in data.iter().enumerate() {
+            let address = address + (i as u64 * 2);
+            let [low, high] = half_word.to_le_bytes();
+            self.write_byte(address, low);
+            self.write_byte(address + 1, high);
+        }
+
+        Ok(())
     }
 
     fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ArmError> {
         for (i, word) in data.iter().enumerate() {
-            let address = address + (i as u64 * 4);
-
-            match address {
-                // DHCSR
-                Dhcsr::ADDRESS_OFFSET => {
-                    let dbg_key = (*word >> 16) & 0xffff;
-
-                    if dbg_key == 0xa05f {
-                        // Mask out dbg key
-                        self.dhcsr = Dhcsr::from(*word & 0xffff);
-                        println!("Write DHCSR = {:#010x}", word);
-
-                        let request_halt = self.dhcsr.c_halt();
-
-                        self.is_halted = request_halt;
-
-                        if !self.dhcsr.c_halt() && self.dhcsr.c_debugen() && self.dhcsr.c_step() {
-                            tracing::debug!("MockCore: Single step requested, setting s_halt");
-                            self.is_halted = true;
-                        }
-                    }
-                }
-                _ => println!("Write {:#010x} = {:#010x}", address, word),
-            }
+            self.write_register_or_memory(address + (i as u64 * 4), *word);
         }
 
         Ok(())
     }
 
-    fn write_64(&mut self, _address: u64, _data: &[u64]) -> Result<(), ArmError> {
-        todo!()
+    fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), ArmError> {
+        for (i, word) in data.iter().enumerate() {
+            let address = address + (i as u64 * 8);
+            self.write_register_or_memory(address, *word as u32);
+            self.write_register_or_memory(address + 4, (*word >> 32) as u32);
+        }
+
+        Ok(())
     }
 
     fn flush(&mut self) -> Result<(), ArmError> {
@@ -175,7 +381,7 @@ impl MemoryInterface<ArmError> for &mut MockCore {
     }
 
     fn supports_8bit_transfers(&self) -> Result<bool, ArmError> {
-        todo!()
+        Ok(true)
     }
 }
 
@@ -210,6 +416,8 @@ impl ArmMemoryInterface for &mut MockCore {
     }
 
     fn update_core_status(&mut self, _state: crate::CoreStatus) {}
+
+    fn invalidate_register_cache(&mut self) {}
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -263,6 +471,13 @@ impl FakeProbe {
         }
     }
 
+    /// Attaches to `target` through a fake probe with a mocked core.
+    pub fn attach_mocked_core(target: impl Into<TargetSelector>) -> Result<Session, Error> {
+        Self::with_mocked_core()
+            .into_probe()
+            .attach(target, Permissions::default())
+    }
+
     /// This sets the read handler for DAP register reads.
     /// Can be used to hook into the read.
     pub fn set_dap_register_read_handler(
@@ -618,8 +833,10 @@ impl DapAccess for FakeArmInterface<Initialized> {
 
 #[cfg(all(test, feature = "builtin-targets"))]
 mod test {
+    use std::time::Duration;
+
     use super::FakeProbe;
-    use crate::Permissions;
+    use crate::{CoreStatus, HaltReason, MemoryInterface, Permissions};
 
     #[test]
     fn create_session_with_fake_probe() {
@@ -631,4 +848,47 @@ mod test {
             .attach("nrf51822_xxAC", Permissions::default())
             .unwrap();
     }
+
+    #[test]
+    fn simulated_core_run_control() {
+        let mut session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        core.halt(Duration::from_millis(100)).unwrap();
+        assert_eq!(
+            core.status().unwrap(),
+            CoreStatus::Halted(HaltReason::Request)
+        );
+
+        core.run().unwrap();
+        assert_eq!(core.status().unwrap(), CoreStatus::Running);
+    }
+
+    #[test]
+    fn simulated_core_halts_after_reset() {
+        let mut session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        core.run().unwrap();
+        core.reset_and_halt(Duration::from_millis(100)).unwrap();
+        assert!(core.core_halted().unwrap());
+    }
+
+    #[test]
+    fn simulated_core_keeps_memory_and_registers() {
+        let mut session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        let mut core = session.core(0).unwrap();
+
+        core.write_word_32(0x2000_0000, 0xdead_beef).unwrap();
+        core.write_8(0x2000_0004, &[1, 2, 3]).unwrap();
+        assert_eq!(core.read_word_32(0x2000_0000).unwrap(), 0xdead_beef);
+        assert_eq!(core.read_word_8(0x2000_0005).unwrap(), 2);
+        assert_eq!(core.read_word_32(0x2000_1000).unwrap(), 0);
+
+        core.halt(Duration::from_millis(100)).unwrap();
+        let pc = core.program_counter();
+        core.write_core_reg(pc, 0x0000_1234u32).unwrap();
+        let value: u32 = core.read_core_reg(pc).unwrap();
+        assert_eq!(value, 0x0000_1234);
+    }
 }
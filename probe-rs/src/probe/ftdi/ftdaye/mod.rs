@@ -472,6 +472,15 @@ impl Device {
         Ok(self.write_all(&[0x8B])?)
     }
 
+    /// Makes TCK wait for the RTCK signal of the target on GPIOL3.
+    pub fn enable_adaptive_clocking(&mut self) -> Result<()> {
+        Ok(self.write_all(&[0x96])?)
+    }
+
+    pub fn disable_adaptive_clocking(&mut self) -> Result<()> {
+        Ok(self.write_all(&[0x97])?)
+    }
+
     pub fn configure_clock_divider(&mut self, divisor: u16) -> Result<()> {
         let [l, h] = divisor.to_le_bytes();
         Ok(self.write_all(&[0x86, l, h])?)
@@ -1,4 +1,6 @@
 //! FTDI-based debug probes.
+//!
+//! JTAG uses the standard MPSSE pinout. For the SWD wiring, see the [`swd`] module.
 use crate::{
     architecture::{
         arm::{
@@ -13,8 +15,8 @@ use crate::{
     probe::{
         arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
         common::{JtagDriverState, RawJtagIo},
-        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, JTAGAccess,
-        ProbeCreationError, ProbeFactory, ScanChainElement, WireProtocol,
+        usb_port_path, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, JTAGAccess,
+        ProbeCreationError, ProbeDetails, ProbeFactory, ScanChainElement, WireProtocol,
     },
 };
 use bitvec::prelude::*;
@@ -27,9 +29,11 @@ use std::{
 
 mod command_compacter;
 mod ftdaye;
+mod swd;
 
 use command_compacter::Command;
 use ftdaye::{error::FtdiError, ChipType};
+use swd::{SwdPins, SwdSequence};
 
 #[derive(Debug)]
 struct JtagAdapter {
@@ -41,6 +45,12 @@ struct JtagAdapter {
     in_bit_counts: Vec<usize>,
     in_bits: BitVec<u8, Lsb0>,
     ftdi: FtdiProperties,
+
+    protocol: WireProtocol,
+    swd_pins: SwdPins,
+
+    /// Whether TCK follows the RTCK signal of the target, applied when attaching.
+    adaptive_clocking: bool,
 }
 
 impl JtagAdapter {
@@ -61,6 +71,9 @@ impl JtagAdapter {
             in_bit_counts: vec![],
             in_bits: BitVec::new(),
             ftdi,
+            protocol: WireProtocol::Jtag,
+            swd_pins: SwdPins::default(),
+            adaptive_clocking: false,
         })
     }
 
@@ -74,16 +87,28 @@ impl JtagAdapter {
         let mut junk = vec![];
         let _ = self.device.read_to_end(&mut junk);
 
-        // TMS starts high
-        let output = 0x0008;
-
-        // TMS, TDO and TCK are outputs
-        let direction = 0x000b;
+        let (output, direction) = match self.protocol {
+            // TMS starts high, TMS, TDI and TCK are outputs
+            WireProtocol::Jtag => (0x0008, 0x000b),
+            WireProtocol::Swd => {
+                self.swd_pins = SwdPins::default();
+                (swd::INITIAL_LEVEL as u16, swd::INITIAL_DIRECTION as u16)
+            }
+        };
 
         self.device.set_pins(output, direction)?;
 
         self.apply_clock_speed(self.speed_khz)?;
 
+        if self.ftdi.has_adaptive_clocking {
+            if self.adaptive_clocking && self.protocol == WireProtocol::Jtag {
+                tracing::info!("Using adaptive clocking");
+                self.device.enable_adaptive_clocking()?;
+            } else {
+                self.device.disable_adaptive_clocking()?;
+            }
+        }
+
         self.device.disable_loopback()?;
 
         Ok(())
@@ -240,6 +265,34 @@ impl JtagAdapter {
 
         Ok(std::mem::take(&mut self.in_bits))
     }
+
+    fn swd_io(&mut self, dir: &[bool], swdio: &[bool]) -> Result<Vec<bool>, DebugProbeError> {
+        let sequence = SwdSequence::encode(dir, swdio, &mut self.swd_pins);
+
+        // 1 byte is reserved for the send immediate command
+        if self.commands.len() + sequence.commands.len() + 1 >= self.ftdi.buffer_size {
+            self.send_buffer()?;
+        }
+        self.commands.extend_from_slice(&sequence.commands);
+        self.in_bit_counts
+            .extend_from_slice(&sequence.in_bit_counts);
+
+        self.send_buffer()?;
+        self.read_response()?;
+
+        let mut input = std::mem::take(&mut self.in_bits).into_iter();
+        Ok(dir
+            .iter()
+            .zip(swdio)
+            .map(|(&output, &value)| {
+                if output {
+                    value
+                } else {
+                    input.next().unwrap_or(false)
+                }
+            })
+            .collect())
+    }
 }
 
 /// A factory for creating [`FtdiProbe`] instances.
@@ -255,19 +308,15 @@ impl std::fmt::Display for FtdiProbeFactory {
 impl ProbeFactory for FtdiProbeFactory {
     fn open(&self, selector: &DebugProbeSelector) -> Result<Box<dyn DebugProbe>, DebugProbeError> {
         // Only open FTDI-compatible probes
-        let Some(ftdi) = FTDI_COMPAT_DEVICES
-            .iter()
-            .find(|ftdi| ftdi.id == (selector.vendor_id, selector.product_id))
-            .copied()
-        else {
-            return Err(DebugProbeError::ProbeCouldNotBeCreated(
-                ProbeCreationError::NotFound,
-            ));
-        };
-
         let mut probes = nusb::list_devices()
             .map_err(FtdiError::from)?
             .filter(|usb_info| selector.matches(usb_info))
+            .filter_map(|usb_info| {
+                let ftdi = FTDI_COMPAT_DEVICES
+                    .iter()
+                    .find(|ftdi| ftdi.matches(&usb_info))?;
+                Some((*ftdi, usb_info))
+            })
             .collect::<Vec<_>>();
 
         if probes.is_empty() {
@@ -278,8 +327,9 @@ impl ProbeFactory for FtdiProbeFactory {
             tracing::warn!("More than one matching FTDI probe was found. Opening the first one.");
         }
 
+        let (ftdi, usb_info) = probes.pop().unwrap();
         let probe = FtdiProbe {
-            adapter: JtagAdapter::open(ftdi, probes.pop().unwrap())?,
+            adapter: JtagAdapter::open(ftdi, usb_info)?,
             jtag_state: JtagDriverState::default(),
             swd_settings: SwdSettings::default(),
             probe_statistics: ProbeStatistics::default(),
@@ -330,14 +380,19 @@ impl DebugProbe for FtdiProbe {
     }
 
     fn attach(&mut self) -> Result<(), DebugProbeError> {
-        tracing::debug!("Attaching...");
+        tracing::debug!("Attaching with protocol '{}'", self.adapter.protocol);
 
         self.adapter
             .attach()
             .map_err(|e| DebugProbeError::ProbeSpecific(Box::new(e)))?;
 
-        self.scan_chain()?;
-        self.select_target(0)
+        match self.adapter.protocol {
+            WireProtocol::Jtag => {
+                self.scan_chain()?;
+                self.select_target(0)
+            }
+            WireProtocol::Swd => Ok(()),
+        }
     }
 
     fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
@@ -369,26 +424,48 @@ impl DebugProbe for FtdiProbe {
     }
 
     fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
-        if protocol != WireProtocol::Jtag {
-            Err(DebugProbeError::UnsupportedProtocol(protocol))
-        } else {
-            Ok(())
-        }
+        self.adapter.protocol = protocol;
+        Ok(())
     }
 
     fn active_protocol(&self) -> Option<WireProtocol> {
-        // Only supports JTAG
-        Some(WireProtocol::Jtag)
+        Some(self.adapter.protocol)
+    }
+
+    fn set_adaptive_clocking(&mut self, enabled: bool) -> Result<(), DebugProbeError> {
+        if !self.adapter.ftdi.has_adaptive_clocking {
+            return Err(DebugProbeError::CommandNotSupportedByProbe {
+                command_name: "set_adaptive_clocking",
+            });
+        }
+        if enabled && self.adapter.protocol != WireProtocol::Jtag {
+            return Err(DebugProbeError::UnsupportedProtocol(self.adapter.protocol));
+        }
+
+        self.adapter.adaptive_clocking = enabled;
+        Ok(())
+    }
+
+    fn details(&mut self) -> ProbeDetails {
+        ProbeDetails {
+            protocols: vec![WireProtocol::Jtag, WireProtocol::Swd],
+            max_speed_khz: Some(self.adapter.ftdi.max_clock),
+            adaptive_clocking: self.adapter.ftdi.has_adaptive_clocking,
+            ..Default::default()
+        }
     }
 
     fn try_get_riscv_interface_builder<'probe>(
         &'probe mut self,
     ) -> Result<Box<dyn RiscvInterfaceBuilder<'probe> + 'probe>, DebugProbeError> {
+        if self.adapter.protocol != WireProtocol::Jtag {
+            return Err(DebugProbeError::UnsupportedProtocol(self.adapter.protocol));
+        }
         Ok(Box::new(JtagDtmBuilder::new(self)))
     }
 
     fn has_riscv_interface(&self) -> bool {
-        true
+        self.adapter.protocol == WireProtocol::Jtag
     }
 
     fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
@@ -399,7 +476,8 @@ impl DebugProbe for FtdiProbe {
         self: Box<Self>,
     ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
     {
-        let uninitialized_interface = ArmCommunicationInterface::new(self, true);
+        let use_overrun_detect = self.use_overrun_detect();
+        let uninitialized_interface = ArmCommunicationInterface::new(self, use_overrun_detect);
 
         Ok(Box::new(uninitialized_interface))
     }
@@ -412,15 +490,22 @@ impl DebugProbe for FtdiProbe {
         &'probe mut self,
         state: &'probe mut XtensaDebugInterfaceState,
     ) -> Result<XtensaCommunicationInterface<'probe>, DebugProbeError> {
+        if self.adapter.protocol != WireProtocol::Jtag {
+            return Err(DebugProbeError::UnsupportedProtocol(self.adapter.protocol));
+        }
         Ok(XtensaCommunicationInterface::new(self, state))
     }
 
     fn has_xtensa_interface(&self) -> bool {
-        true
+        self.adapter.protocol == WireProtocol::Jtag
     }
 }
 
-impl DapProbe for FtdiProbe {}
+impl DapProbe for FtdiProbe {
+    fn use_overrun_detect(&self) -> bool {
+        true
+    }
+}
 
 impl RawProtocolIo for FtdiProbe {
     fn jtag_shift_tms<M>(&mut self, tms: M, tdi: bool) -> Result<(), DebugProbeError>
@@ -445,14 +530,17 @@ impl RawProtocolIo for FtdiProbe {
         Ok(())
     }
 
-    fn swd_io<D, S>(&mut self, _dir: D, _swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    fn swd_io<D, S>(&mut self, dir: D, swdio: S) -> Result<Vec<bool>, DebugProbeError>
     where
         D: IntoIterator<Item = bool>,
         S: IntoIterator<Item = bool>,
     {
-        Err(DebugProbeError::NotImplemented {
-            function_name: "swd_io",
-        })
+        self.probe_statistics.report_io();
+
+        let dir: Vec<bool> = dir.into_iter().collect();
+        let swdio: Vec<bool> = swdio.into_iter().collect();
+
+        self.adapter.swd_io(&dir, &swdio)
     }
 
     fn swj_pins(
@@ -516,6 +604,9 @@ struct FtdiProperties {
     /// Newer devices have 60MHz internal clocks, instead of 12MHz, however, they still
     /// fall back to 12MHz by default. This flag indicates whether we can disable the clock divider.
     has_divide_by_5: bool,
+
+    /// Whether the device can wait for the RTCK signal of the target before each TCK edge.
+    has_adaptive_clocking: bool,
 }
 
 impl TryFrom<(FtdiDevice, Option<ChipType>)> for FtdiProperties {
@@ -535,16 +626,19 @@ impl TryFrom<(FtdiDevice, Option<ChipType>)> for FtdiProperties {
                 buffer_size: 4096,
                 max_clock: 30_000,
                 has_divide_by_5: true,
+                has_adaptive_clocking: true,
             },
             ChipType::FT232H => Self {
                 buffer_size: 1024,
                 max_clock: 30_000,
                 has_divide_by_5: true,
+                has_adaptive_clocking: true,
             },
             ChipType::FT2232C => Self {
                 buffer_size: 128,
                 max_clock: 6_000,
                 has_divide_by_5: false,
+                has_adaptive_clocking: false,
             },
             not_mpsse => {
                 tracing::warn!("Unsupported FTDI chip: {:?}", not_mpsse);
@@ -630,6 +724,7 @@ fn get_device_info(device: &DeviceInfo) -> Option<DebugProbeInfo> {
             serial_number: device.serial_number().map(|s| s.to_string()),
             probe_factory: &FtdiProbeFactory,
             hid_interface: None,
+            usb_port: usb_port_path(device),
         })
     })
 }
@@ -643,3 +738,24 @@ fn list_ftdi_devices() -> Vec<DebugProbeInfo> {
         Err(_) => vec![],
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn properties(chip_type: ChipType) -> FtdiProperties {
+        let device = FtdiDevice {
+            id: (0x0403, 0x6010),
+            fallback_chip_type: ChipType::FT2232H,
+        };
+        FtdiProperties::try_from((device, Some(chip_type))).unwrap()
+    }
+
+    #[test]
+    fn adaptive_clocking_needs_a_high_speed_chip() {
+        assert!(properties(ChipType::FT2232H).has_adaptive_clocking);
+        assert!(properties(ChipType::FT4232H).has_adaptive_clocking);
+        assert!(properties(ChipType::FT232H).has_adaptive_clocking);
+        assert!(!properties(ChipType::FT2232C).has_adaptive_clocking);
+    }
+}
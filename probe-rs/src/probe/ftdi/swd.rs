@@ -0,0 +1,151 @@
+//! SWD using the MPSSE engine.
+//!
+//! SWCLK is connected to TCK (ADBUS0). SWDIO is driven by TDI (ADBUS1) through a resistor,
+//! and read back by TDO (ADBUS2), which is connected to SWDIO directly. While reading
+//! SWDIO, TDI is switched to an input, so the target can drive the line.
+
+/// SWCLK, TDI and TMS are outputs while driving SWDIO.
+const DIRECTION_OUTPUT: u8 = 0x0b;
+/// SWCLK and TMS are outputs while reading SWDIO.
+const DIRECTION_INPUT: u8 = 0x09;
+
+/// The initial pin levels: SWDIO and TMS high, SWCLK low.
+pub(super) const INITIAL_LEVEL: u8 = 0x0a;
+/// The initial pin directions.
+pub(super) const INITIAL_DIRECTION: u8 = DIRECTION_OUTPUT;
+
+/// The pin state of the MPSSE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct SwdPins {
+    /// Whether TDI currently drives SWDIO.
+    pub driving: bool,
+}
+
+impl Default for SwdPins {
+    fn default() -> Self {
+        Self { driving: true }
+    }
+}
+
+/// MPSSE commands for a SWD sequence.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct SwdSequence {
+    pub commands: Vec<u8>,
+    /// The number of bits in each byte read back.
+    pub in_bit_counts: Vec<usize>,
+}
+
+impl SwdSequence {
+    /// Encodes the SWD sequence, switching the direction of TDI where needed.
+    pub fn encode(dir: &[bool], swdio: &[bool], pins: &mut SwdPins) -> Self {
+        let mut sequence = Self::default();
+
+        let mut start = 0;
+        while start < dir.len() {
+            let output = dir[start];
+            let len = dir[start..]
+                .iter()
+                .take_while(|&&bit| bit == output)
+                .count();
+
+            if pins.driving != output {
+                sequence.set_direction(output);
+                pins.driving = output;
+            }
+
+            for chunk in swdio[start..start + len].chunks(8) {
+                if output {
+                    sequence.write_bits(chunk);
+                } else {
+                    sequence.read_bits(chunk.len());
+                }
+            }
+
+            start += len;
+        }
+
+        sequence
+    }
+
+    fn set_direction(&mut self, output: bool) {
+        // Keep SWDIO high when driving it again.
+        let direction = if output {
+            DIRECTION_OUTPUT
+        } else {
+            DIRECTION_INPUT
+        };
+        self.commands
+            .extend_from_slice(&[0x80, INITIAL_LEVEL, direction]);
+    }
+
+    fn write_bits(&mut self, bits: &[bool]) {
+        let mut byte = bits
+            .iter()
+            .enumerate()
+            .fold(0, |byte, (i, &bit)| byte | (bit as u8) << i);
+        let mut count = bits.len() as u8;
+
+        if count == 7 {
+            // Some FTDI chips have trouble with 7 bits, so output 6 bits first and 1 later.
+            self.commands.extend_from_slice(&[0x1b, 5, byte]);
+            byte >>= 6;
+            count -= 6;
+        }
+        self.commands.extend_from_slice(&[0x1b, count - 1, byte]);
+    }
+
+    fn read_bits(&mut self, count: usize) {
+        if count == 7 {
+            // Some FTDI chips have trouble with 7 bits, so read 6 bits first and 1 later.
+            self.commands.extend_from_slice(&[0x2a, 5, 0x2a, 0]);
+            self.in_bit_counts.extend([6, 1]);
+        } else {
+            self.commands.extend_from_slice(&[0x2a, count as u8 - 1]);
+            self.in_bit_counts.push(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_swd_read() {
+        // Request, turnaround + ACK + 7 data bits, then drive again
+        let mut dir = vec![true; 8];
+        dir.extend([false; 11]);
+        dir.push(true);
+        let mut swdio = vec![true, false, true, false, false, true, false, true];
+        swdio.extend([false; 12]);
+
+        let mut pins = SwdPins::default();
+        let sequence = SwdSequence::encode(&dir, &swdio, &mut pins);
+
+        assert_eq!(
+            sequence.commands,
+            [
+                0x1b, 7, 0xa5, // request
+                0x80, 0x0a, 0x09, // release SWDIO
+                0x2a, 7, // 8 bits in
+                0x2a, 2, // 3 bits in
+                0x80, 0x0a, 0x0b, // drive SWDIO
+                0x1b, 0, 0, // 1 bit out
+            ]
+        );
+        assert_eq!(sequence.in_bit_counts, [8, 3]);
+        assert!(pins.driving);
+    }
+
+    #[test]
+    fn encode_seven_bits() {
+        let mut pins = SwdPins::default();
+        let sequence = SwdSequence::encode(&[true; 7], &[true; 7], &mut pins);
+        assert_eq!(sequence.commands, [0x1b, 5, 0x7f, 0x1b, 0, 0x01]);
+
+        let sequence = SwdSequence::encode(&[false; 7], &[false; 7], &mut pins);
+        assert_eq!(sequence.commands, [0x80, 0x0a, 0x09, 0x2a, 5, 0x2a, 0]);
+        assert_eq!(sequence.in_bit_counts, [6, 1]);
+        assert!(!pins.driving);
+    }
+}
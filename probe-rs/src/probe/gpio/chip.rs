@@ -0,0 +1,242 @@
+//! Access to GPIO lines using the Linux GPIO character device (uAPI v2).
+
+/// Flags of a requested line.
+pub(super) mod flags {
+    pub const INPUT: u64 = 1 << 2;
+    pub const OUTPUT: u64 = 1 << 3;
+    pub const OPEN_DRAIN: u64 = 1 << 6;
+    pub const BIAS_PULL_UP: u64 = 1 << 8;
+}
+
+#[cfg(target_os = "linux")]
+pub(super) use linux::Lines;
+
+#[cfg(not(target_os = "linux"))]
+pub(super) use unsupported::Lines;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        fs::{File, OpenOptions},
+        io,
+        os::fd::{AsRawFd, FromRawFd},
+        path::Path,
+    };
+
+    const LINES_MAX: usize = 64;
+    const NAME_SIZE: usize = 32;
+    const NUM_ATTRS_MAX: usize = 10;
+
+    const ATTR_ID_FLAGS: u32 = 1;
+    const ATTR_ID_OUTPUT_VALUES: u32 = 2;
+
+    /// `_IOWR(0xB4, nr, size)`
+    const fn iowr(nr: u32, size: usize) -> u32 {
+        (3 << 30) | ((size as u32) << 16) | (0xB4 << 8) | nr
+    }
+
+    const GET_LINE_IOCTL: u32 = iowr(0x07, std::mem::size_of::<LineRequest>());
+    const SET_CONFIG_IOCTL: u32 = iowr(0x0D, std::mem::size_of::<LineConfig>());
+    const GET_VALUES_IOCTL: u32 = iowr(0x0E, std::mem::size_of::<LineValues>());
+    const SET_VALUES_IOCTL: u32 = iowr(0x0F, std::mem::size_of::<LineValues>());
+
+    /// `struct gpio_v2_line_attribute`
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct LineAttribute {
+        id: u32,
+        padding: u32,
+        /// Either the flags, or the output values.
+        value: u64,
+    }
+
+    /// `struct gpio_v2_line_config_attribute`
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct LineConfigAttribute {
+        attr: LineAttribute,
+        mask: u64,
+    }
+
+    /// `struct gpio_v2_line_config`
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct LineConfig {
+        flags: u64,
+        num_attrs: u32,
+        padding: [u32; 5],
+        attrs: [LineConfigAttribute; NUM_ATTRS_MAX],
+    }
+
+    /// `struct gpio_v2_line_request`
+    #[repr(C)]
+    struct LineRequest {
+        offsets: [u32; LINES_MAX],
+        consumer: [u8; NAME_SIZE],
+        config: LineConfig,
+        num_lines: u32,
+        event_buffer_size: u32,
+        padding: [u32; 5],
+        fd: i32,
+    }
+
+    /// `struct gpio_v2_line_values`
+    #[repr(C)]
+    #[derive(Default)]
+    struct LineValues {
+        bits: u64,
+        mask: u64,
+    }
+
+    /// Builds a configuration with separate flags for every line.
+    fn line_config(line_flags: &[u64], output_values: u64) -> LineConfig {
+        let mut config = LineConfig::default();
+
+        for (i, flags) in line_flags.iter().enumerate() {
+            config.attrs[i] = LineConfigAttribute {
+                attr: LineAttribute {
+                    id: ATTR_ID_FLAGS,
+                    padding: 0,
+                    value: *flags,
+                },
+                mask: 1 << i,
+            };
+        }
+
+        config.attrs[line_flags.len()] = LineConfigAttribute {
+            attr: LineAttribute {
+                id: ATTR_ID_OUTPUT_VALUES,
+                padding: 0,
+                value: output_values,
+            },
+            mask: (1 << line_flags.len()) - 1,
+        };
+        config.num_attrs = line_flags.len() as u32 + 1;
+
+        config
+    }
+
+    fn ioctl<T>(file: &File, request: u32, data: &mut T) -> io::Result<()> {
+        // SAFETY: `data` is the structure expected by the request, and lives for the whole call.
+        let result = unsafe { libc::ioctl(file.as_raw_fd(), request as _, data as *mut T) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// A set of requested lines, addressed by their index in the request.
+    #[derive(Debug)]
+    pub(in crate::probe::gpio) struct Lines {
+        file: File,
+        count: usize,
+    }
+
+    impl Lines {
+        /// Requests the lines at `offsets` of the GPIO chip at `chip`, with the given flags.
+        pub fn request(
+            chip: &Path,
+            offsets: &[u32],
+            line_flags: &[u64],
+            output_values: u64,
+        ) -> io::Result<Self> {
+            if offsets.len() >= NUM_ATTRS_MAX || offsets.len() != line_flags.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Invalid number of GPIO lines",
+                ));
+            }
+
+            let chip = OpenOptions::new().read(true).write(true).open(chip)?;
+
+            let mut request = LineRequest {
+                offsets: [0; LINES_MAX],
+                consumer: [0; NAME_SIZE],
+                config: line_config(line_flags, output_values),
+                num_lines: offsets.len() as u32,
+                event_buffer_size: 0,
+                padding: [0; 5],
+                fd: -1,
+            };
+            request.offsets[..offsets.len()].copy_from_slice(offsets);
+            let consumer = b"probe-rs";
+            request.consumer[..consumer.len()].copy_from_slice(consumer);
+
+            ioctl(&chip, GET_LINE_IOCTL, &mut request)?;
+
+            Ok(Self {
+                // SAFETY: The kernel returned a new file descriptor for the lines, which is
+                // owned by nothing else.
+                file: unsafe { File::from_raw_fd(request.fd) },
+                count: offsets.len(),
+            })
+        }
+
+        /// Changes the flags of all lines, e.g. to change the direction of a line.
+        pub fn reconfigure(&self, line_flags: &[u64], output_values: u64) -> io::Result<()> {
+            debug_assert_eq!(line_flags.len(), self.count);
+            let mut config = line_config(line_flags, output_values);
+            ioctl(&self.file, SET_CONFIG_IOCTL, &mut config)
+        }
+
+        /// Sets the values of the output lines in `mask`.
+        pub fn set_values(&self, mask: u64, bits: u64) -> io::Result<()> {
+            let mut values = LineValues { bits, mask };
+            ioctl(&self.file, SET_VALUES_IOCTL, &mut values)
+        }
+
+        /// Reads the values of the lines in `mask`.
+        pub fn get_values(&self, mask: u64) -> io::Result<u64> {
+            let mut values = LineValues { bits: 0, mask };
+            ioctl(&self.file, GET_VALUES_IOCTL, &mut values)?;
+            Ok(values.bits)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn uapi_layout() {
+            assert_eq!(std::mem::size_of::<LineConfig>(), 272);
+            assert_eq!(std::mem::size_of::<LineRequest>(), 592);
+            assert_eq!(GET_LINE_IOCTL, 0xC250_B407);
+            assert_eq!(SET_VALUES_IOCTL, 0xC010_B40F);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod unsupported {
+    use std::{io, path::Path};
+
+    #[derive(Debug)]
+    pub(in crate::probe::gpio) struct Lines;
+
+    impl Lines {
+        pub fn request(
+            _chip: &Path,
+            _offsets: &[u32],
+            _line_flags: &[u64],
+            _output_values: u64,
+        ) -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "GPIO probes are only supported on Linux",
+            ))
+        }
+
+        pub fn reconfigure(&self, _line_flags: &[u64], _output_values: u64) -> io::Result<()> {
+            unreachable!()
+        }
+
+        pub fn set_values(&self, _mask: u64, _bits: u64) -> io::Result<()> {
+            unreachable!()
+        }
+
+        pub fn get_values(&self, _mask: u64) -> io::Result<u64> {
+            unreachable!()
+        }
+    }
+}
@@ -0,0 +1,554 @@
+//! A probe bit-banging SWD or JTAG using GPIO lines.
+//!
+//! On Linux, the lines of a GPIO chip, e.g. the header pins of a Raspberry Pi, can be used
+//! to drive the debug interface of a target directly. Every edge of the clock requires a
+//! system call, so this is a lot slower than a real probe, but it doesn't need any
+//! additional hardware.
+//!
+//! SWD needs SWCLK and SWDIO. For JTAG, SWCLK and SWDIO are used as TCK and TMS, and TDI
+//! and TDO have to be given in addition. The reset line is optional.
+
+mod chip;
+
+use std::{
+    iter,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use bitvec::prelude::*;
+
+use self::chip::{flags, Lines};
+use crate::{
+    architecture::{
+        arm::{
+            communication_interface::{DapProbe, UninitializedArmProbe},
+            ArmCommunicationInterface, Pins,
+        },
+        riscv::{communication_interface::RiscvInterfaceBuilder, dtm::jtag_dtm::JtagDtmBuilder},
+        xtensa::communication_interface::{
+            XtensaCommunicationInterface, XtensaDebugInterfaceState,
+        },
+    },
+    metrics::Metrics,
+    probe::{
+        arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
+        common::{JtagDriverState, RawJtagIo},
+        DebugProbe, DebugProbeError, JTAGAccess, ScanChainElement, WireProtocol,
+    },
+};
+
+/// The GPIO lines connected to the target.
+///
+/// Parsed from a comma separated list of `name=value` pairs, e.g.
+/// `swclk=25,swdio=24,nreset=18`. The lines are given as offsets on the GPIO chip, which
+/// defaults to `/dev/gpiochip0` and can be set with `chip=<path>`. `tck` and `tms` are
+/// accepted as aliases of `swclk` and `swdio`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpioPins {
+    /// The GPIO chip device.
+    pub chip: PathBuf,
+    /// The SWCLK / TCK line.
+    pub swclk: u32,
+    /// The SWDIO / TMS line.
+    pub swdio: u32,
+    /// The TDI line, only needed for JTAG.
+    pub tdi: Option<u32>,
+    /// The TDO line, only needed for JTAG.
+    pub tdo: Option<u32>,
+    /// The active low reset line.
+    pub nreset: Option<u32>,
+}
+
+impl std::str::FromStr for GpioPins {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chip = PathBuf::from("/dev/gpiochip0");
+        let mut swclk = None;
+        let mut swdio = None;
+        let mut tdi = None;
+        let mut tdo = None;
+        let mut nreset = None;
+
+        for pair in s.split(',') {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected 'name=value', found '{pair}'"))?;
+
+            if name == "chip" {
+                chip = PathBuf::from(value);
+                continue;
+            }
+
+            let line = value
+                .parse::<u32>()
+                .map_err(|e| format!("Invalid line '{value}' for {name}: {e}"))?;
+            match name {
+                "swclk" | "tck" => swclk = Some(line),
+                "swdio" | "tms" => swdio = Some(line),
+                "tdi" => tdi = Some(line),
+                "tdo" => tdo = Some(line),
+                "nreset" => nreset = Some(line),
+                _ => {
+                    return Err(format!(
+                        "Unknown line '{name}'. Choose from [chip, swclk, swdio, tdi, tdo, nreset]."
+                    ))
+                }
+            }
+        }
+
+        if tdi.is_some() != tdo.is_some() {
+            return Err("JTAG requires both tdi and tdo".to_string());
+        }
+
+        Ok(Self {
+            chip,
+            swclk: swclk.ok_or("The swclk line is required")?,
+            swdio: swdio.ok_or("The swdio line is required")?,
+            tdi,
+            tdo,
+            nreset,
+        })
+    }
+}
+
+/// Index of SWCLK / TCK in the requested lines.
+const SWCLK: usize = 0;
+/// Index of SWDIO / TMS in the requested lines.
+const SWDIO: usize = 1;
+
+/// A probe bit-banging the debug interface using GPIO lines.
+///
+/// See the [module level documentation](self) for details.
+#[derive(Debug)]
+pub struct GpioProbe {
+    lines: Lines,
+    line_flags: Vec<u64>,
+    tdi: Option<usize>,
+    tdo: Option<usize>,
+    nreset: Option<usize>,
+
+    protocol: WireProtocol,
+    speed_khz: u32,
+    /// Whether SWDIO is currently an output.
+    swdio_output: bool,
+    /// The current values of the output lines.
+    output_values: u64,
+    last_edge: Instant,
+
+    captured: BitVec<u8, Lsb0>,
+    jtag_state: JtagDriverState,
+    probe_statistics: ProbeStatistics,
+    swd_settings: SwdSettings,
+}
+
+impl GpioProbe {
+    /// Requests the given GPIO lines.
+    pub fn open(pins: &GpioPins) -> Result<Self, DebugProbeError> {
+        let mut offsets = vec![pins.swclk, pins.swdio];
+        let mut line_flags = vec![flags::OUTPUT, flags::OUTPUT];
+        // SWCLK and SWDIO idle high.
+        let mut output_values = 1 << SWCLK | 1 << SWDIO;
+
+        let mut add_line = |line: Option<u32>, line_flags_value: u64| {
+            line.map(|line| {
+                offsets.push(line);
+                line_flags.push(line_flags_value);
+                offsets.len() - 1
+            })
+        };
+
+        let tdi = add_line(pins.tdi, flags::OUTPUT);
+        let tdo = add_line(pins.tdo, flags::INPUT);
+        let nreset = add_line(pins.nreset, flags::OUTPUT | flags::OPEN_DRAIN);
+        if let Some(nreset) = nreset {
+            output_values |= 1 << nreset;
+        }
+
+        let lines = Lines::request(&pins.chip, &offsets, &line_flags, output_values)
+            .map_err(|e| DebugProbeError::Other(format!("Failed to request GPIO lines: {e}")))?;
+
+        Ok(Self {
+            lines,
+            line_flags,
+            tdi,
+            tdo,
+            nreset,
+            protocol: WireProtocol::Swd,
+            speed_khz: 100,
+            swdio_output: true,
+            output_values,
+            last_edge: Instant::now(),
+            captured: BitVec::new(),
+            jtag_state: JtagDriverState::default(),
+            probe_statistics: ProbeStatistics::default(),
+            swd_settings: SwdSettings::default(),
+        })
+    }
+
+    fn half_period(&self) -> Duration {
+        Duration::from_nanos(500_000 / self.speed_khz as u64)
+    }
+
+    /// Sets the output line `index` to `value`.
+    fn set_line(&mut self, index: usize, value: bool) {
+        if value {
+            self.output_values |= 1 << index;
+        } else {
+            self.output_values &= !(1 << index);
+        }
+    }
+
+    /// Applies the output values, waiting for half a clock period since the last change.
+    fn write_lines(&mut self) -> Result<(), DebugProbeError> {
+        let half_period = self.half_period();
+        while self.last_edge.elapsed() < half_period {
+            std::hint::spin_loop();
+        }
+
+        // Setting the value of an input line is rejected by the kernel.
+        let mask = self
+            .line_flags
+            .iter()
+            .enumerate()
+            .filter(|(_, line_flags)| *line_flags & flags::OUTPUT != 0)
+            .fold(0, |mask, (i, _)| mask | 1 << i);
+        self.lines
+            .set_values(mask, self.output_values)
+            .map_err(gpio_error)?;
+        self.last_edge = Instant::now();
+
+        Ok(())
+    }
+
+    fn read_line(&mut self, index: usize) -> Result<bool, DebugProbeError> {
+        let values = self.lines.get_values(1 << index).map_err(gpio_error)?;
+        Ok(values & (1 << index) != 0)
+    }
+
+    fn set_swdio_direction(&mut self, output: bool) -> Result<(), DebugProbeError> {
+        if self.swdio_output == output {
+            return Ok(());
+        }
+
+        self.line_flags[SWDIO] = if output {
+            flags::OUTPUT
+        } else {
+            flags::INPUT | flags::BIAS_PULL_UP
+        };
+        self.lines
+            .reconfigure(&self.line_flags, self.output_values)
+            .map_err(gpio_error)?;
+        self.swdio_output = output;
+
+        Ok(())
+    }
+
+    /// Clocks a single bit, returning the value of `sample` before the rising edge.
+    fn clock(&mut self, sample: Option<usize>) -> Result<bool, DebugProbeError> {
+        self.set_line(SWCLK, false);
+        self.write_lines()?;
+
+        let value = match sample {
+            Some(index) => self.read_line(index)?,
+            None => false,
+        };
+
+        self.set_line(SWCLK, true);
+        self.write_lines()?;
+
+        Ok(value)
+    }
+
+    fn set_reset(&mut self, asserted: bool) -> Result<(), DebugProbeError> {
+        let Some(nreset) = self.nreset else {
+            return Err(DebugProbeError::NotImplemented {
+                function_name: "target_reset",
+            });
+        };
+
+        self.set_line(nreset, !asserted);
+        self.write_lines()
+    }
+
+    fn has_jtag(&self) -> bool {
+        self.tdi.is_some() && self.tdo.is_some()
+    }
+}
+
+fn gpio_error(error: std::io::Error) -> DebugProbeError {
+    DebugProbeError::Other(format!("GPIO access failed: {error}"))
+}
+
+impl DebugProbe for GpioProbe {
+    fn get_name(&self) -> &str {
+        "GPIO"
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        if speed_khz == 0 {
+            return Err(DebugProbeError::UnsupportedSpeed(speed_khz));
+        }
+
+        // The actual speed is limited by the system calls, this is only an upper bound.
+        self.speed_khz = speed_khz;
+        Ok(speed_khz)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.jtag_state.expected_scan_chain = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        Ok(self
+            .jtag_state
+            .expected_scan_chain
+            .as_deref()
+            .unwrap_or(&[]))
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        tracing::debug!("Attaching with protocol '{}'", self.protocol);
+
+        match self.protocol {
+            WireProtocol::Swd => Ok(()),
+            WireProtocol::Jtag => {
+                JTAGAccess::scan_chain(self)?;
+                self.select_target(0)
+            }
+        }
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.select_target(index)
+    }
+
+    fn detach(&mut self) -> Result<(), crate::Error> {
+        self.set_swdio_direction(true)?;
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(true)?;
+        std::thread::sleep(Duration::from_millis(10));
+        self.set_reset(false)
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(true)
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_reset(false)
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        if protocol == WireProtocol::Jtag && !self.has_jtag() {
+            return Err(DebugProbeError::UnsupportedProtocol(protocol));
+        }
+
+        self.protocol = protocol;
+        Ok(())
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        Some(self.protocol)
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn set_metrics(&mut self, metrics: Metrics) {
+        self.probe_statistics.set_metrics(metrics);
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        let use_overrun_detect = self.use_overrun_detect();
+        Ok(Box::new(ArmCommunicationInterface::new(
+            self,
+            use_overrun_detect,
+        )))
+    }
+
+    fn has_riscv_interface(&self) -> bool {
+        self.has_jtag()
+    }
+
+    fn try_get_riscv_interface_builder<'probe>(
+        &'probe mut self,
+    ) -> Result<Box<dyn RiscvInterfaceBuilder<'probe> + 'probe>, DebugProbeError> {
+        self.select_protocol(WireProtocol::Jtag)?;
+        Ok(Box::new(JtagDtmBuilder::new(self)))
+    }
+
+    fn has_xtensa_interface(&self) -> bool {
+        self.has_jtag()
+    }
+
+    fn try_get_xtensa_interface<'probe>(
+        &'probe mut self,
+        state: &'probe mut XtensaDebugInterfaceState,
+    ) -> Result<XtensaCommunicationInterface<'probe>, DebugProbeError> {
+        self.select_protocol(WireProtocol::Jtag)?;
+        Ok(XtensaCommunicationInterface::new(self, state))
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+}
+
+impl RawProtocolIo for GpioProbe {
+    fn jtag_shift_tms<M>(&mut self, tms: M, tdi: bool) -> Result<(), DebugProbeError>
+    where
+        M: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+        self.shift_bits(tms, iter::repeat(tdi), iter::repeat(false))
+    }
+
+    fn jtag_shift_tdi<I>(&mut self, tms: bool, tdi: I) -> Result<(), DebugProbeError>
+    where
+        I: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+        self.shift_bits(iter::repeat(tms), tdi, iter::repeat(false))
+    }
+
+    fn swd_io<D, S>(&mut self, dir: D, swdio: S) -> Result<Vec<bool>, DebugProbeError>
+    where
+        D: IntoIterator<Item = bool>,
+        S: IntoIterator<Item = bool>,
+    {
+        self.probe_statistics.report_io();
+
+        let mut result = Vec::new();
+        for (output, value) in dir.into_iter().zip(swdio) {
+            self.set_swdio_direction(output)?;
+
+            if output {
+                self.set_line(SWDIO, value);
+                self.clock(None)?;
+                result.push(value);
+            } else {
+                result.push(self.clock(Some(SWDIO))?);
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let mut nreset = Pins(0);
+        nreset.set_nreset(true);
+        let nreset_mask = nreset.0 as u32;
+
+        // Only the reset pin can be controlled.
+        if pin_select != nreset_mask || self.nreset.is_none() {
+            return Err(DebugProbeError::CommandNotSupportedByProbe {
+                command_name: "swj_pins",
+            });
+        }
+
+        self.set_reset(!Pins(pin_out as u8).nreset())?;
+        std::thread::sleep(Duration::from_micros(pin_wait as u64));
+
+        Ok(0xffff_ffff)
+    }
+
+    fn swd_settings(&self) -> &SwdSettings {
+        &self.swd_settings
+    }
+
+    fn probe_statistics(&mut self) -> &mut ProbeStatistics {
+        &mut self.probe_statistics
+    }
+}
+
+impl RawJtagIo for GpioProbe {
+    fn state_mut(&mut self) -> &mut JtagDriverState {
+        &mut self.jtag_state
+    }
+
+    fn state(&self) -> &JtagDriverState {
+        &self.jtag_state
+    }
+
+    fn shift_bit(&mut self, tms: bool, tdi: bool, capture: bool) -> Result<(), DebugProbeError> {
+        let (Some(tdi_line), Some(tdo_line)) = (self.tdi, self.tdo) else {
+            return Err(DebugProbeError::UnsupportedProtocol(WireProtocol::Jtag));
+        };
+
+        self.jtag_state.state.update(tms);
+
+        self.set_line(SWDIO, tms);
+        self.set_line(tdi_line, tdi);
+        let tdo = self.clock(capture.then_some(tdo_line))?;
+        if capture {
+            self.captured.push(tdo);
+        }
+
+        Ok(())
+    }
+
+    fn read_captured_bits(&mut self) -> Result<BitVec<u8, Lsb0>, DebugProbeError> {
+        Ok(std::mem::take(&mut self.captured))
+    }
+}
+
+impl DapProbe for GpioProbe {
+    fn use_overrun_detect(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_pins() {
+        let pins: GpioPins = "swclk=25,swdio=24,nreset=18".parse().unwrap();
+        assert_eq!(
+            pins,
+            GpioPins {
+                chip: PathBuf::from("/dev/gpiochip0"),
+                swclk: 25,
+                swdio: 24,
+                tdi: None,
+                tdo: None,
+                nreset: Some(18),
+            }
+        );
+
+        let pins: GpioPins = "chip=/dev/gpiochip4,tck=11,tms=25,tdi=10,tdo=9"
+            .parse()
+            .unwrap();
+        assert_eq!(pins.chip, PathBuf::from("/dev/gpiochip4"));
+        assert_eq!((pins.swclk, pins.swdio), (11, 25));
+        assert_eq!((pins.tdi, pins.tdo), (Some(10), Some(9)));
+
+        assert!("swclk=25".parse::<GpioPins>().is_err());
+        assert!("swclk=25,swdio=24,tdi=10".parse::<GpioPins>().is_err());
+        assert!("swclk=25,swdio=x".parse::<GpioPins>().is_err());
+    }
+}
@@ -15,6 +15,10 @@ pub enum JlinkError {
     /// permission to access it.
     Usb(#[from] nusb::Error),
 
+    #[error("A network transport error occurred.")]
+    /// An error occurred while talking to a J-Link connected over TCP/IP.
+    Network(#[source] std::io::Error),
+
     #[error("device is missing capabilities ({0:?}) for operation")]
     /// An operation was attempted that is not supported by the probe.
     ///
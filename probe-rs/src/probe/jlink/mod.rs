@@ -6,6 +6,7 @@ mod error;
 mod interface;
 mod speed;
 pub mod swo;
+mod transport;
 
 use std::iter;
 use std::mem::take;
@@ -25,13 +26,13 @@ use self::error::JlinkError;
 use self::interface::{Interface, Interfaces};
 use self::speed::SpeedConfig;
 use self::swo::SwoMode;
+use self::transport::Transport;
 use crate::architecture::arm::{ArmError, Pins};
 use crate::architecture::xtensa::communication_interface::{
     XtensaCommunicationInterface, XtensaDebugInterfaceState,
 };
 use crate::probe::common::{JtagDriverState, RawJtagIo};
 use crate::probe::jlink::bits::IteratorExt;
-use crate::probe::usb_util::InterfaceExt;
 use crate::probe::JTAGAccess;
 use crate::{
     architecture::{
@@ -42,10 +43,11 @@ use crate::{
         },
         riscv::{communication_interface::RiscvInterfaceBuilder, dtm::jtag_dtm::JtagDtmBuilder},
     },
+    metrics::Metrics,
     probe::{
         arm_debug_interface::{ProbeStatistics, RawProtocolIo, SwdSettings},
-        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, ProbeFactory,
-        WireProtocol,
+        DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, ProbeDetails,
+        ProbeFactory, SwoCapabilities, WireProtocol,
     },
 };
 
@@ -76,6 +78,13 @@ impl ProbeFactory for JLinkFactory {
             )))
         }
 
+        if let Some(address) = &selector.ip_address {
+            let transport = Transport::connect_tcp(address)?;
+            // The transfer size limits of network attached probes are unknown, so use the
+            // safe default.
+            return Ok(Box::new(JLink::new(transport, 504)?));
+        }
+
         let mut jlinks = nusb::list_devices()
             .map_err(DebugProbeError::Usb)?
             .filter(is_jlink)
@@ -164,90 +173,12 @@ impl ProbeFactory for JLinkFactory {
             .claim_interface(intf)
             .map_err(|e| open_error(e, "taking control over USB device"))?;
 
-        let mut this = JLink {
-            read_ep,
-            write_ep,
-            caps: Capabilities::from_raw_legacy(0), // dummy value
-            interface: Interface::Spi,              // dummy value, must not be JTAG
-            interfaces: Interfaces::from_bits_warn(0), // dummy value
-            handle,
-
-            supported_protocols: vec![],  // dummy value
-            protocol: WireProtocol::Jtag, // dummy value
-
-            swo_config: None,
-            speed_khz: 0, // default is unknown
-            swd_settings: SwdSettings::default(),
-            probe_statistics: ProbeStatistics::default(),
-            jtag_state: JtagDriverState::default(),
-
-            jtag_tms_bits: vec![],
-            jtag_tdi_bits: vec![],
-            jtag_capture_tdo: vec![],
-            jtag_response: BitVec::new(),
-
-            max_mem_block_size: 0, // dummy value
-            jtag_chunk_size: 0,    // dummy value
-        };
-        this.fill_capabilities()?;
-        this.fill_interfaces()?;
-
-        this.supported_protocols = if this.caps.contains(Capability::SelectIf) {
-            let protocols: Vec<_> = this
-                .interfaces
-                .into_iter()
-                .map(WireProtocol::try_from)
-                .collect();
-
-            protocols
-                .iter()
-                .filter(|p| p.is_err())
-                .for_each(|protocol| {
-                    if let Err(JlinkError::UnknownInterface(interface)) = protocol {
-                        tracing::debug!(
-                            "J-Link returned interface {:?}, which is not supported by probe-rs.",
-                            interface
-                        );
-                    }
-                });
-
-            // We ignore unknown protocols, the chance that this happens is pretty low,
-            // and we can just work with the ones we know and support.
-            protocols.into_iter().filter_map(Result::ok).collect()
-        } else {
-            // The J-Link cannot report which interfaces it supports, and cannot
-            // switch interfaces. We assume it just supports JTAG.
-            vec![WireProtocol::Jtag]
-        };
-
-        this.protocol = if this.supported_protocols.contains(&WireProtocol::Swd) {
-            // Default to SWD if supported, since it's the most commonly used.
-            WireProtocol::Swd
-        } else {
-            // Otherwise just pick the first supported.
-            *this.supported_protocols.first().unwrap()
-        };
-
-        if this.caps.contains(Capability::GetMaxBlockSize) {
-            this.max_mem_block_size = this.read_max_mem_block()?;
-
-            tracing::debug!(
-                "J-Link max mem block size for SWD IO: {} byte",
-                this.max_mem_block_size
-            );
-        } else {
-            tracing::debug!(
-                "J-Link does not support GET_MAX_MEM_BLOCK, using default value of 65535"
-            );
-            this.max_mem_block_size = 65535;
-        }
-
         // Some devices can't handle large transfers, so we limit the chunk size.
         // While it would be nice to read this directly from the device,
         // `read_max_mem_block`'s return value does not directly correspond to the
         // maximum transfer size when performing JTAG IO, and it's not clear how to get the actual value.
         // The number of *bits* is encoded as a u16, so the maximum value is 65535
-        this.jtag_chunk_size = match selector.product_id {
+        let jtag_chunk_size = match selector.product_id {
             // 0x0101: J-Link EDU
             0x0101 => 65535,
             // 0x1051: J-Link OB-K22-SiFive: 504 bits
@@ -256,7 +187,13 @@ impl ProbeFactory for JLinkFactory {
             _ => 504,
         };
 
-        Ok(Box::new(this))
+        let transport = Transport::Usb {
+            handle,
+            read_ep,
+            write_ep,
+        };
+
+        Ok(Box::new(JLink::new(transport, jtag_chunk_size)?))
     }
 
     fn list_probes(&self) -> Vec<DebugProbeInfo> {
@@ -316,10 +253,7 @@ enum Command {
 
 /// A J-Link probe.
 pub struct JLink {
-    handle: nusb::Interface,
-
-    read_ep: u8,
-    write_ep: u8,
+    transport: Transport,
 
     /// The capabilities reported by the device. They're fetched once, when the device is opened.
     caps: Capabilities,
@@ -339,6 +273,8 @@ pub struct JLink {
     protocol: WireProtocol,
 
     speed_khz: u32,
+    /// Whether the clock follows the RTCK signal of the target instead of `speed_khz`.
+    adaptive_clocking: bool,
 
     jtag_tms_bits: Vec<bool>,
     jtag_tdi_bits: Vec<bool>,
@@ -365,6 +301,88 @@ impl fmt::Debug for JLink {
 }
 
 impl JLink {
+    /// Initializes the probe, reading its capabilities and supported interfaces.
+    fn new(transport: Transport, jtag_chunk_size: usize) -> Result<Self, DebugProbeError> {
+        let mut this = JLink {
+            transport,
+            caps: Capabilities::from_raw_legacy(0), // dummy value
+            interface: Interface::Spi,              // dummy value, must not be JTAG
+            interfaces: Interfaces::from_bits_warn(0), // dummy value
+
+            supported_protocols: vec![],  // dummy value
+            protocol: WireProtocol::Jtag, // dummy value
+
+            swo_config: None,
+            speed_khz: 0, // default is unknown
+            adaptive_clocking: false,
+            swd_settings: SwdSettings::default(),
+            probe_statistics: ProbeStatistics::default(),
+            jtag_state: JtagDriverState::default(),
+
+            jtag_tms_bits: vec![],
+            jtag_tdi_bits: vec![],
+            jtag_capture_tdo: vec![],
+            jtag_response: BitVec::new(),
+
+            max_mem_block_size: 0, // dummy value
+            jtag_chunk_size,
+        };
+        this.fill_capabilities()?;
+        this.fill_interfaces()?;
+
+        this.supported_protocols = if this.caps.contains(Capability::SelectIf) {
+            let protocols: Vec<_> = this
+                .interfaces
+                .into_iter()
+                .map(WireProtocol::try_from)
+                .collect();
+
+            protocols
+                .iter()
+                .filter(|p| p.is_err())
+                .for_each(|protocol| {
+                    if let Err(JlinkError::UnknownInterface(interface)) = protocol {
+                        tracing::debug!(
+                            "J-Link returned interface {:?}, which is not supported by probe-rs.",
+                            interface
+                        );
+                    }
+                });
+
+            // We ignore unknown protocols, the chance that this happens is pretty low,
+            // and we can just work with the ones we know and support.
+            protocols.into_iter().filter_map(Result::ok).collect()
+        } else {
+            // The J-Link cannot report which interfaces it supports, and cannot
+            // switch interfaces. We assume it just supports JTAG.
+            vec![WireProtocol::Jtag]
+        };
+
+        this.protocol = if this.supported_protocols.contains(&WireProtocol::Swd) {
+            // Default to SWD if supported, since it's the most commonly used.
+            WireProtocol::Swd
+        } else {
+            // Otherwise just pick the first supported.
+            *this.supported_protocols.first().unwrap()
+        };
+
+        if this.caps.contains(Capability::GetMaxBlockSize) {
+            this.max_mem_block_size = this.read_max_mem_block()?;
+
+            tracing::debug!(
+                "J-Link max mem block size for SWD IO: {} byte",
+                this.max_mem_block_size
+            );
+        } else {
+            tracing::debug!(
+                "J-Link does not support GET_MAX_MEM_BLOCK, using default value of 65535"
+            );
+            this.max_mem_block_size = 65535;
+        }
+
+        Ok(this)
+    }
+
     /// Returns the supported J-Link capabilities.
     pub fn capabilities(&self) -> Capabilities {
         self.caps
@@ -419,9 +437,7 @@ impl JLink {
     fn write_cmd(&self, cmd: &[u8]) -> Result<(), JlinkError> {
         trace!("write {} bytes: {:x?}", cmd.len(), cmd);
 
-        let n = self
-            .handle
-            .write_bulk(self.write_ep, cmd, TIMEOUT_DEFAULT)?;
+        let n = self.transport.write(cmd, TIMEOUT_DEFAULT)?;
 
         if n != cmd.len() {
             return Err(JlinkError::Other(format!(
@@ -437,9 +453,7 @@ impl JLink {
         let mut total = 0;
 
         while total < buf.len() {
-            let n = self
-                .handle
-                .read_bulk(self.read_ep, &mut buf[total..], TIMEOUT_DEFAULT)?;
+            let n = self.transport.read(&mut buf[total..], TIMEOUT_DEFAULT)?;
             total += n;
         }
 
@@ -556,7 +570,10 @@ impl JLink {
 
         self.interface = intf;
 
-        if self.speed_khz != 0 {
+        if self.adaptive_clocking && intf == Interface::Jtag {
+            // SelectIf resets the configured speed. Let's restore it.
+            self.set_interface_clock_speed(SpeedConfig::ADAPTIVE)?;
+        } else if self.speed_khz != 0 {
             // SelectIf resets the configured speed. Let's restore it.
             self.set_interface_clock_speed(SpeedConfig::khz(self.speed_khz as u16).unwrap())?;
         }
@@ -838,6 +855,7 @@ impl DebugProbe for JLink {
         if let Some(expected_speed) = SpeedConfig::khz(speed_khz as u16) {
             self.set_interface_clock_speed(expected_speed)?;
             self.speed_khz = speed_khz;
+            self.adaptive_clocking = false;
         } else {
             return Err(DebugProbeError::UnsupportedSpeed(speed_khz));
         }
@@ -980,16 +998,69 @@ impl DebugProbe for JLink {
         self: Box<Self>,
     ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
     {
-        let uninitialized_interface = ArmCommunicationInterface::new(self, true);
+        let use_overrun_detect = self.use_overrun_detect();
+        let uninitialized_interface = ArmCommunicationInterface::new(self, use_overrun_detect);
 
         Ok(Box::new(uninitialized_interface))
     }
 
     fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
-        // Convert the integer millivolts value from self.handle to volts as an f32.
+        // Convert the integer millivolts value to volts as an f32.
         Ok(Some((self.read_target_voltage()? as f32) / 1000f32))
     }
 
+    fn set_target_power(&mut self, enabled: bool) -> Result<(), DebugProbeError> {
+        Ok(self.set_kickstart_power(enabled)?)
+    }
+
+    fn set_adaptive_clocking(&mut self, enabled: bool) -> Result<(), DebugProbeError> {
+        if enabled {
+            // RTCK is a JTAG signal, SWD has no equivalent.
+            if self.protocol != WireProtocol::Jtag {
+                return Err(DebugProbeError::UnsupportedProtocol(self.protocol));
+            }
+            self.set_interface_clock_speed(SpeedConfig::ADAPTIVE)?;
+        } else if self.speed_khz != 0 {
+            self.set_interface_clock_speed(SpeedConfig::khz(self.speed_khz as u16).unwrap())?;
+        }
+
+        self.adaptive_clocking = enabled;
+        Ok(())
+    }
+
+    fn set_metrics(&mut self, metrics: Metrics) {
+        self.probe_statistics.set_metrics(metrics);
+    }
+
+    fn details(&mut self) -> ProbeDetails {
+        let swo = self
+            .caps
+            .contains(Capability::Swo)
+            .then(|| SwoCapabilities {
+                uart: true,
+                manchester: false,
+                max_baud: self
+                    .read_swo_speeds(SwoMode::Uart)
+                    .ok()
+                    .map(|speeds| speeds.max_speed_hz()),
+                buffer_size: Some(SWO_BUFFER_SIZE.into()),
+            });
+
+        ProbeDetails {
+            firmware_version: self.read_firmware_version().ok(),
+            protocols: self.supported_protocols.clone(),
+            max_speed_khz: self
+                .read_interface_speeds()
+                .ok()
+                .map(|speeds| speeds.max_speed_hz() / 1000),
+            swo,
+            packet_size: None,
+            packet_count: None,
+            target_voltage: None,
+            adaptive_clocking: self.caps.contains(Capability::AdaptiveClocking),
+        }
+    }
+
     fn try_get_xtensa_interface<'probe>(
         &'probe mut self,
         state: &'probe mut XtensaDebugInterfaceState,
@@ -1145,7 +1216,11 @@ impl RawJtagIo for JLink {
     }
 }
 
-impl DapProbe for JLink {}
+impl DapProbe for JLink {
+    fn use_overrun_detect(&self) -> bool {
+        true
+    }
+}
 
 impl SwoAccess for JLink {
     fn enable_swo(&mut self, config: &SwoConfig) -> Result<(), ArmError> {
@@ -1203,6 +1278,7 @@ fn list_jlink_devices() -> Vec<DebugProbeInfo> {
                 &JLinkFactory,
                 None,
             )
+            .with_usb_port(&info)
         })
         .collect()
 }
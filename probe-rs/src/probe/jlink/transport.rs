@@ -0,0 +1,180 @@
+//! The connection to a J-Link, either over USB or over TCP/IP.
+//!
+//! J-Link PRO and J-Link WiFi probes can be reached over the network. After a short
+//! handshake, the TCP connection carries the same commands and responses as the USB
+//! endpoints.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::time::Duration;
+
+use tracing::debug;
+
+use super::error::JlinkError;
+use crate::probe::usb_util::InterfaceExt;
+
+/// The TCP port the J-Link listens on.
+const TCP_PORT: u16 = 19020;
+/// The version of the TCP protocol.
+const TCP_PROTOCOL_VERSION: u16 = 1;
+/// First byte of the hello message sent by the probe.
+const SERVER_HELLO: u8 = 0x00;
+/// First byte of the hello message sent to the probe.
+const CLIENT_HELLO: u8 = 0x07;
+
+/// Network round trips take longer than USB transfers, so use a longer timeout.
+const TCP_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub(super) enum Transport {
+    Usb {
+        handle: nusb::Interface,
+        read_ep: u8,
+        write_ep: u8,
+    },
+    Tcp(TcpStream),
+}
+
+impl Transport {
+    /// Connects to the J-Link at `address`, which is a host name or IP address, optionally
+    /// followed by a port.
+    pub fn connect_tcp(address: &str) -> Result<Self, JlinkError> {
+        let address = with_default_port(address);
+        debug!("connecting to J-Link at {address}");
+
+        let stream = TcpStream::connect(&address).map_err(JlinkError::Network)?;
+        stream.set_nodelay(true).map_err(JlinkError::Network)?;
+        stream
+            .set_read_timeout(Some(TCP_TIMEOUT))
+            .map_err(JlinkError::Network)?;
+        stream
+            .set_write_timeout(Some(TCP_TIMEOUT))
+            .map_err(JlinkError::Network)?;
+
+        handshake(&stream)?;
+
+        Ok(Self::Tcp(stream))
+    }
+
+    /// Writes `data`, returning the number of bytes written.
+    pub fn write(&self, data: &[u8], timeout: Duration) -> Result<usize, JlinkError> {
+        match self {
+            Self::Usb {
+                handle, write_ep, ..
+            } => Ok(handle.write_bulk(*write_ep, data, timeout)?),
+            Self::Tcp(stream) => {
+                let mut stream: &TcpStream = stream;
+                stream.write_all(data).map_err(JlinkError::Network)?;
+                Ok(data.len())
+            }
+        }
+    }
+
+    /// Reads into `buf`, returning the number of bytes read.
+    pub fn read(&self, buf: &mut [u8], timeout: Duration) -> Result<usize, JlinkError> {
+        match self {
+            Self::Usb {
+                handle, read_ep, ..
+            } => Ok(handle.read_bulk(*read_ep, buf, timeout)?),
+            Self::Tcp(stream) => {
+                let mut stream: &TcpStream = stream;
+                match stream.read(buf).map_err(JlinkError::Network)? {
+                    0 => Err(JlinkError::Network(
+                        std::io::ErrorKind::UnexpectedEof.into(),
+                    )),
+                    n => Ok(n),
+                }
+            }
+        }
+    }
+}
+
+/// Appends the default port to `address`, if it has none.
+fn with_default_port(address: &str) -> String {
+    match address.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{ip}]:{TCP_PORT}"),
+        Ok(IpAddr::V4(ip)) => format!("{ip}:{TCP_PORT}"),
+        Err(_) if address.contains(':') => address.to_string(),
+        Err(_) => format!("{address}:{TCP_PORT}"),
+    }
+}
+
+/// Exchanges the hello messages with the probe.
+fn handshake(mut stream: &TcpStream) -> Result<(), JlinkError> {
+    let mut hello = [0; 4];
+    stream.read_exact(&mut hello).map_err(JlinkError::Network)?;
+
+    if hello[0] != SERVER_HELLO {
+        return Err(JlinkError::Other(format!(
+            "invalid hello message received: {hello:x?}"
+        )));
+    }
+
+    let version = u16::from_le_bytes([hello[1], hello[2]]);
+    if version != TCP_PROTOCOL_VERSION {
+        return Err(JlinkError::Other(format!(
+            "unsupported TCP protocol version {version}"
+        )));
+    }
+
+    // The hello message is followed by options, which are not used.
+    let mut options = vec![0; usize::from(hello[3])];
+    stream
+        .read_exact(&mut options)
+        .map_err(JlinkError::Network)?;
+
+    let mut response = vec![CLIENT_HELLO];
+    response.extend_from_slice(&TCP_PROTOCOL_VERSION.to_le_bytes());
+    stream.write_all(&response).map_err(JlinkError::Network)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn default_port() {
+        assert_eq!(with_default_port("192.168.1.10"), "192.168.1.10:19020");
+        assert_eq!(with_default_port("jlink.lab"), "jlink.lab:19020");
+        assert_eq!(with_default_port("jlink.lab:1234"), "jlink.lab:1234");
+        assert_eq!(with_default_port("fe80::1"), "[fe80::1]:19020");
+    }
+
+    #[test]
+    fn tcp_handshake_and_transfer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .write_all(&[0x00, 0x01, 0x00, 0x02, 0xaa, 0xbb])
+                .unwrap();
+
+            let mut hello = [0; 3];
+            stream.read_exact(&mut hello).unwrap();
+            assert_eq!(hello, [0x07, 0x01, 0x00]);
+
+            // Answer a version request with its length.
+            let mut command = [0; 1];
+            stream.read_exact(&mut command).unwrap();
+            assert_eq!(command, [0x01]);
+            stream.write_all(&[0x04, 0x00]).unwrap();
+        });
+
+        let transport = Transport::connect_tcp(&address).unwrap();
+        assert_eq!(transport.write(&[0x01], TCP_TIMEOUT).unwrap(), 1);
+
+        let mut buf = [0; 2];
+        let mut total = 0;
+        while total < buf.len() {
+            total += transport.read(&mut buf[total..], TCP_TIMEOUT).unwrap();
+        }
+        assert_eq!(buf, [0x04, 0x00]);
+
+        server.join().unwrap();
+    }
+}
@@ -1,10 +1,15 @@
 //! Listing probes of various types.
 
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
 use crate::probe::{
     DebugProbeError, DebugProbeInfo, DebugProbeSelector, Probe, ProbeCreationError, ProbeFactory,
 };
 
-use super::{cmsisdap, espusbjtag, ftdi, jlink, stlink, wlink};
+use super::{blackmagic, ch347, cmsisdap, espusbjtag, ftdi, jlink, stlink, wlink, xds110};
 
 /// Struct to list all attached debug probes
 #[derive(Debug)]
@@ -34,6 +39,122 @@ impl Lister {
     pub fn list_all(&self) -> Vec<DebugProbeInfo> {
         self.lister.list_all()
     }
+
+    /// Watch for debug probes being connected or disconnected.
+    ///
+    /// The probes which are already attached are reported as connected first.
+    pub fn watch(&self) -> ProbeWatcher<'_> {
+        ProbeWatcher {
+            lister: self,
+            known: vec![],
+            pending: VecDeque::new(),
+            interval: ProbeWatcher::DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Wait until a probe is connected, and return it.
+    ///
+    /// If a selector is given, only a matching probe is returned. Returns `None` if no probe
+    /// appeared before the timeout expired.
+    pub fn wait_for_probe(
+        &self,
+        selector: Option<&DebugProbeSelector>,
+        timeout: Option<Duration>,
+    ) -> Option<DebugProbeInfo> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut watcher = self.watch();
+
+        loop {
+            for event in watcher.poll() {
+                if let ProbeEvent::Connected(probe) = event {
+                    if selector.map_or(true, |selector| selector.matches_probe(&probe)) {
+                        return Some(probe);
+                    }
+                }
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+
+            std::thread::sleep(watcher.interval);
+        }
+    }
+}
+
+/// A debug probe was connected or disconnected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeEvent {
+    /// The probe was connected.
+    Connected(DebugProbeInfo),
+    /// The probe was disconnected.
+    Disconnected(DebugProbeInfo),
+}
+
+/// Reports debug probes being connected or disconnected, see [`Lister::watch`].
+///
+/// The watcher is an iterator which blocks until the next event. Use [`ProbeWatcher::poll`]
+/// to check for changes without blocking, e.g. from an event loop.
+#[derive(Debug)]
+pub struct ProbeWatcher<'a> {
+    lister: &'a Lister,
+    known: Vec<DebugProbeInfo>,
+    pending: VecDeque<ProbeEvent>,
+    interval: Duration,
+}
+
+impl ProbeWatcher<'_> {
+    const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Set how often the attached probes are scanned.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Scan the attached probes once, and return the changes since the last scan.
+    pub fn poll(&mut self) -> Vec<ProbeEvent> {
+        let current = self.lister.list_all();
+
+        let mut events: Vec<_> = self
+            .known
+            .iter()
+            .filter(|probe| !current.contains(probe))
+            .cloned()
+            .map(ProbeEvent::Disconnected)
+            .collect();
+        events.extend(
+            current
+                .iter()
+                .filter(|probe| !self.known.contains(probe))
+                .cloned()
+                .map(ProbeEvent::Connected),
+        );
+
+        self.known = current;
+        events
+    }
+}
+
+impl Iterator for ProbeWatcher<'_> {
+    type Item = ProbeEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut first = true;
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+
+            if !first {
+                std::thread::sleep(self.interval);
+            }
+            first = false;
+
+            let events = self.poll();
+            self.pending.extend(events);
+        }
+    }
 }
 
 impl Default for Lister {
@@ -81,6 +202,9 @@ impl AllProbesLister {
         &jlink::JLinkFactory,
         &espusbjtag::EspUsbJtagFactory,
         &wlink::WchLinkFactory,
+        &blackmagic::BlackMagicProbeFactory,
+        &xds110::Xds110Factory,
+        &ch347::Ch347Factory,
     ];
 
     /// Create a new lister with all built-in probe drivers.
@@ -114,3 +238,71 @@ impl AllProbesLister {
         list
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct MockLister(Mutex<VecDeque<Vec<DebugProbeInfo>>>);
+
+    impl ProbeLister for MockLister {
+        fn open(&self, _selector: &DebugProbeSelector) -> Result<Probe, DebugProbeError> {
+            Err(DebugProbeError::ProbeCouldNotBeCreated(
+                ProbeCreationError::NotFound,
+            ))
+        }
+
+        fn list_all(&self) -> Vec<DebugProbeInfo> {
+            self.0.lock().unwrap().pop_front().unwrap_or_default()
+        }
+    }
+
+    fn probe(serial: &str) -> DebugProbeInfo {
+        DebugProbeInfo::new(
+            "Mock probe",
+            0x12,
+            0x23,
+            Some(serial.to_owned()),
+            &ftdi::FtdiProbeFactory,
+            None,
+        )
+    }
+
+    fn lister(scans: Vec<Vec<DebugProbeInfo>>) -> Lister {
+        Lister::with_lister(Box::new(MockLister(Mutex::new(scans.into()))))
+    }
+
+    #[test]
+    fn watch_reports_changes() {
+        let lister = lister(vec![
+            vec![probe("a")],
+            vec![probe("a"), probe("b")],
+            vec![probe("b")],
+        ]);
+        let mut watcher = lister.watch();
+
+        assert_eq!(watcher.poll(), vec![ProbeEvent::Connected(probe("a"))]);
+        assert_eq!(watcher.poll(), vec![ProbeEvent::Connected(probe("b"))]);
+        assert_eq!(watcher.poll(), vec![ProbeEvent::Disconnected(probe("a"))]);
+        assert_eq!(watcher.poll(), vec![ProbeEvent::Disconnected(probe("b"))]);
+        assert_eq!(watcher.poll(), vec![]);
+    }
+
+    #[test]
+    fn wait_for_matching_probe() {
+        let lister = lister(vec![vec![], vec![probe("a")], vec![probe("a"), probe("b")]]);
+        let selector = "12:23:b".parse().unwrap();
+
+        let found = lister.wait_for_probe(Some(&selector), None);
+        assert_eq!(found, Some(probe("b")));
+    }
+
+    #[test]
+    fn wait_for_probe_timeout() {
+        let lister = lister(vec![]);
+        assert_eq!(lister.wait_for_probe(None, Some(Duration::ZERO)), None);
+    }
+}
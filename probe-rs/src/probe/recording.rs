@@ -0,0 +1,871 @@
+//! Recording and replaying of probe operations.
+//!
+//! A [`RecordingProbe`] wraps a probe offering DAP access and writes every probe level
+//! operation, together with its result, to a recording. A [`ReplayProbe`] reads such a
+//! recording back and answers the same sequence of operations from it, without any
+//! hardware attached. This allows reproducing a failing attach or flash sequence from
+//! a recording, e.g. in regression tests or when investigating a bug report.
+//!
+//! The recording is a text file containing one JSON object per line. The first line
+//! is a [`RecordingHeader`], each following line is one probe operation.
+//!
+//! Only the probe operations used by the ARM debug interface are recorded, so only
+//! ARM targets can be replayed.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use probe_rs_target::ScanChainElement;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    architecture::arm::{
+        communication_interface::{DapProbe, UninitializedArmProbe},
+        ArmCommunicationInterface, ArmError, DapError, PortType, RawDapAccess, SwoAccess,
+    },
+    probe::{DebugProbe, DebugProbeError, ProbeDetails, UartAccess, WireProtocol},
+    CoreStatus,
+};
+
+/// Version of the recording format.
+const RECORDING_VERSION: u32 = 1;
+
+/// The first line of a recording, describing the recorded probe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    /// Version of the recording format.
+    pub version: u32,
+    /// Name of the recorded probe.
+    pub probe_name: String,
+    /// The speed of the recorded probe when the recording started.
+    pub speed_khz: u32,
+    /// The protocol selected on the recorded probe when the recording started.
+    pub protocol: Option<WireProtocol>,
+    /// Whether the ARM communication interface used overrun detection.
+    pub use_overrun_detect: bool,
+}
+
+/// A probe operation, with all arguments which influence its result.
+///
+/// This is also used as the wire format of the [remote probe protocol](super::remote).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(crate) enum Request {
+    SetSpeed {
+        speed_khz: u32,
+    },
+    Attach,
+    Detach,
+    SelectJtagTap {
+        index: usize,
+    },
+    TargetReset,
+    TargetResetAssert,
+    TargetResetDeassert,
+    SelectProtocol {
+        protocol: WireProtocol,
+    },
+    TargetVoltage,
+    ReadRegister {
+        port: PortType,
+        addr: u8,
+    },
+    ReadBlock {
+        port: PortType,
+        addr: u8,
+        len: usize,
+    },
+    WriteRegister {
+        port: PortType,
+        addr: u8,
+        value: u32,
+    },
+    WriteBlock {
+        port: PortType,
+        addr: u8,
+        values: Vec<u32>,
+    },
+    Flush,
+    ConfigureJtag {
+        skip_scan: bool,
+    },
+    JtagSequence {
+        cycles: u8,
+        tms: bool,
+        tdi: u64,
+    },
+    SwjSequence {
+        bit_len: u8,
+        bits: u64,
+    },
+    SwjPins {
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    },
+}
+
+/// The result of a probe operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Response {
+    Done,
+    Value(u32),
+    Values(Vec<u32>),
+    Voltage(Option<f32>),
+    Error(RecordedError),
+}
+
+/// An error returned by the recorded probe.
+///
+/// DAP errors are kept as they are, because the ARM debug interface handles some of
+/// them. All other errors are only kept as their message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RecordedError {
+    Dap(DapError),
+    Other(String),
+}
+
+impl RecordedError {
+    pub(crate) fn from_arm(error: &ArmError) -> Self {
+        match error {
+            ArmError::Dap(error) => RecordedError::Dap(*error),
+            other => RecordedError::Other(other.to_string()),
+        }
+    }
+
+    pub(crate) fn into_arm(self) -> ArmError {
+        match self {
+            RecordedError::Dap(error) => ArmError::Dap(error),
+            RecordedError::Other(message) => ArmError::Probe(DebugProbeError::Other(message)),
+        }
+    }
+
+    pub(crate) fn into_probe(self) -> DebugProbeError {
+        match self {
+            RecordedError::Dap(error) => DebugProbeError::Other(error.to_string()),
+            RecordedError::Other(message) => DebugProbeError::Other(message),
+        }
+    }
+}
+
+impl Response {
+    pub(crate) fn from_arm<T>(
+        result: &Result<T, ArmError>,
+        ok: impl FnOnce(&T) -> Response,
+    ) -> Self {
+        match result {
+            Ok(value) => ok(value),
+            Err(error) => Response::Error(RecordedError::from_arm(error)),
+        }
+    }
+
+    pub(crate) fn from_probe<T, E: fmt::Display>(
+        result: &Result<T, E>,
+        ok: impl FnOnce(&T) -> Response,
+    ) -> Self {
+        match result {
+            Ok(value) => ok(value),
+            Err(error) => Response::Error(RecordedError::Other(error.to_string())),
+        }
+    }
+}
+
+/// One line of a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Transaction {
+    #[serde(flatten)]
+    request: Request,
+    response: Response,
+}
+
+/// A probe which records all operations of the wrapped probe.
+///
+/// See the [module level documentation](self) for details.
+pub struct RecordingProbe {
+    inner: Box<dyn DebugProbe>,
+    recording: Box<dyn Write + Send>,
+    use_overrun_detect: bool,
+}
+
+impl fmt::Debug for RecordingProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordingProbe")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RecordingProbe {
+    /// Wraps `probe`, writing all its operations to `recording`.
+    ///
+    /// Fails if the probe doesn't offer DAP access, as only those operations can be recorded.
+    pub fn new(
+        mut probe: Box<dyn DebugProbe>,
+        mut recording: Box<dyn Write + Send>,
+    ) -> Result<Self, DebugProbeError> {
+        let Some(dap_probe) = probe.try_as_dap_probe() else {
+            return Err(DebugProbeError::InterfaceNotAvailable {
+                interface_name: "DAP",
+            });
+        };
+
+        let use_overrun_detect = dap_probe.use_overrun_detect();
+        let header = RecordingHeader {
+            version: RECORDING_VERSION,
+            probe_name: dap_probe.get_name().to_string(),
+            speed_khz: dap_probe.speed_khz(),
+            protocol: dap_probe.active_protocol(),
+            use_overrun_detect,
+        };
+        write_line(&mut recording, &header)?;
+
+        Ok(Self {
+            inner: probe,
+            recording,
+            use_overrun_detect,
+        })
+    }
+
+    fn dap(&mut self) -> &mut dyn DapProbe {
+        self.inner
+            .try_as_dap_probe()
+            .expect("DAP access was checked when creating the recording probe")
+    }
+
+    fn record(&mut self, request: Request, response: Response) {
+        let transaction = Transaction { request, response };
+        if let Err(error) = write_line(&mut self.recording, &transaction) {
+            tracing::warn!("Failed to record probe operation: {error}");
+        }
+    }
+}
+
+fn write_line(writer: &mut dyn Write, value: &impl Serialize) -> Result<(), DebugProbeError> {
+    let mut line = serde_json::to_string(value)
+        .map_err(|error| DebugProbeError::Other(format!("Failed to serialize: {error}")))?;
+    line.push('\n');
+
+    // Flush every line, the recording is most useful when the program crashes or hangs.
+    writer
+        .write_all(line.as_bytes())
+        .and_then(|_| writer.flush())
+        .map_err(|error| DebugProbeError::Other(format!("Failed to write recording: {error}")))
+}
+
+impl DebugProbe for RecordingProbe {
+    fn get_name(&self) -> &str {
+        self.inner.get_name()
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.inner.speed_khz()
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        let result = self.inner.set_speed(speed_khz);
+        let response = Response::from_probe(&result, |speed| Response::Value(*speed));
+        self.record(Request::SetSpeed { speed_khz }, response);
+        result
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.inner.set_scan_chain(scan_chain)
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        self.inner.scan_chain()
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        let result = self.inner.attach();
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::Attach, response);
+        result
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        let result = self.inner.select_jtag_tap(index);
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::SelectJtagTap { index }, response);
+        result
+    }
+
+    fn detach(&mut self) -> Result<(), crate::Error> {
+        let result = self.inner.detach();
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::Detach, response);
+        result
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        let result = self.inner.target_reset();
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::TargetReset, response);
+        result
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        let result = self.inner.target_reset_assert();
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::TargetResetAssert, response);
+        result
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        let result = self.inner.target_reset_deassert();
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::TargetResetDeassert, response);
+        result
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        let result = self.inner.select_protocol(protocol);
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::SelectProtocol { protocol }, response);
+        result
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        self.inner.active_protocol()
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        let use_overrun_detect = self.use_overrun_detect;
+        Ok(Box::new(ArmCommunicationInterface::new(
+            self,
+            use_overrun_detect,
+        )))
+    }
+
+    fn get_swo_interface(&self) -> Option<&dyn SwoAccess> {
+        self.inner.get_swo_interface()
+    }
+
+    fn get_swo_interface_mut(&mut self) -> Option<&mut dyn SwoAccess> {
+        self.inner.get_swo_interface_mut()
+    }
+
+    fn get_uart_interface_mut(&mut self) -> Option<&mut dyn UartAccess> {
+        self.inner.get_uart_interface_mut()
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+
+    fn details(&mut self) -> ProbeDetails {
+        self.inner.details()
+    }
+
+    fn set_target_power(&mut self, enabled: bool) -> Result<(), DebugProbeError> {
+        self.inner.set_target_power(enabled)
+    }
+
+    fn set_adaptive_clocking(&mut self, enabled: bool) -> Result<(), DebugProbeError> {
+        self.inner.set_adaptive_clocking(enabled)
+    }
+
+    fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        let result = self.inner.get_target_voltage();
+        let response = Response::from_probe(&result, |voltage| Response::Voltage(*voltage));
+        self.record(Request::TargetVoltage, response);
+        result
+    }
+}
+
+impl RawDapAccess for RecordingProbe {
+    fn raw_read_register(&mut self, port: PortType, addr: u8) -> Result<u32, ArmError> {
+        let result = self.dap().raw_read_register(port, addr);
+        let response = Response::from_arm(&result, |value| Response::Value(*value));
+        self.record(Request::ReadRegister { port, addr }, response);
+        result
+    }
+
+    fn raw_read_block(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        values: &mut [u32],
+    ) -> Result<(), ArmError> {
+        let result = self.dap().raw_read_block(port, addr, values);
+        let response = Response::from_arm(&result, |_| Response::Values(values.to_vec()));
+        let len = values.len();
+        self.record(Request::ReadBlock { port, addr, len }, response);
+        result
+    }
+
+    fn raw_write_register(&mut self, port: PortType, addr: u8, value: u32) -> Result<(), ArmError> {
+        let result = self.dap().raw_write_register(port, addr, value);
+        let response = Response::from_arm(&result, |_| Response::Done);
+        self.record(Request::WriteRegister { port, addr, value }, response);
+        result
+    }
+
+    fn raw_write_block(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        values: &[u32],
+    ) -> Result<(), ArmError> {
+        let result = self.dap().raw_write_block(port, addr, values);
+        let response = Response::from_arm(&result, |_| Response::Done);
+        let values = values.to_vec();
+        self.record(Request::WriteBlock { port, addr, values }, response);
+        result
+    }
+
+    fn raw_flush(&mut self) -> Result<(), ArmError> {
+        let result = self.dap().raw_flush();
+        let response = Response::from_arm(&result, |_| Response::Done);
+        self.record(Request::Flush, response);
+        result
+    }
+
+    fn configure_jtag(&mut self, skip_scan: bool) -> Result<(), DebugProbeError> {
+        let result = self.dap().configure_jtag(skip_scan);
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::ConfigureJtag { skip_scan }, response);
+        result
+    }
+
+    fn jtag_sequence(&mut self, cycles: u8, tms: bool, tdi: u64) -> Result<(), DebugProbeError> {
+        let result = self.dap().jtag_sequence(cycles, tms, tdi);
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::JtagSequence { cycles, tms, tdi }, response);
+        result
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        let result = self.dap().swj_sequence(bit_len, bits);
+        let response = Response::from_probe(&result, |_| Response::Done);
+        self.record(Request::SwjSequence { bit_len, bits }, response);
+        result
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let result = self.dap().swj_pins(pin_out, pin_select, pin_wait);
+        let response = Response::from_probe(&result, |pins| Response::Value(*pins));
+        let request = Request::SwjPins {
+            pin_out,
+            pin_select,
+            pin_wait,
+        };
+        self.record(request, response);
+        result
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn core_status_notification(&mut self, state: CoreStatus) -> Result<(), DebugProbeError> {
+        self.dap().core_status_notification(state)
+    }
+}
+
+impl DapProbe for RecordingProbe {
+    fn use_overrun_detect(&self) -> bool {
+        self.use_overrun_detect
+    }
+}
+
+/// A probe which replays a recording created by a [`RecordingProbe`].
+///
+/// Every operation has to match the next operation in the recording, otherwise an
+/// error is returned. See the [module level documentation](self) for details.
+#[derive(Debug)]
+pub struct ReplayProbe {
+    name: String,
+    header: RecordingHeader,
+    protocol: Option<WireProtocol>,
+    speed_khz: u32,
+    scan_chain: Option<Vec<ScanChainElement>>,
+    transactions: VecDeque<Transaction>,
+}
+
+impl ReplayProbe {
+    /// Loads the recording from the file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, DebugProbeError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| {
+            DebugProbeError::Other(format!(
+                "Failed to open recording {}: {error}",
+                path.display()
+            ))
+        })?;
+
+        Self::new(BufReader::new(file))
+    }
+
+    /// Loads a recording.
+    pub fn new(recording: impl BufRead) -> Result<Self, DebugProbeError> {
+        let mut lines = recording.lines().enumerate();
+
+        let Some((_, header)) = lines.next() else {
+            return Err(DebugProbeError::Other("The recording is empty".to_string()));
+        };
+        let header: RecordingHeader = parse_line(0, header)?;
+        if header.version != RECORDING_VERSION {
+            return Err(DebugProbeError::Other(format!(
+                "Unsupported recording version {}, expected {}",
+                header.version, RECORDING_VERSION
+            )));
+        }
+
+        let transactions = lines
+            .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|(index, line)| parse_line(index, line))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            name: format!("Replay of {}", header.probe_name),
+            protocol: header.protocol,
+            speed_khz: header.speed_khz,
+            header,
+            scan_chain: None,
+            transactions,
+        })
+    }
+
+    /// Returns the header of the replayed recording.
+    pub fn header(&self) -> &RecordingHeader {
+        &self.header
+    }
+
+    /// Returns the number of recorded operations which were not replayed yet.
+    pub fn remaining_operations(&self) -> usize {
+        self.transactions.len()
+    }
+
+    fn replay(&mut self, request: Request) -> Result<Response, RecordedError> {
+        let Some(transaction) = self.transactions.pop_front() else {
+            return Err(RecordedError::Other(format!(
+                "The recording ended, but the operation {request:?} was requested"
+            )));
+        };
+
+        if transaction.request != request {
+            return Err(RecordedError::Other(format!(
+                "Replay diverged from the recording: expected {:?}, but {request:?} was requested",
+                transaction.request
+            )));
+        }
+
+        match transaction.response {
+            Response::Error(error) => Err(error),
+            response => Ok(response),
+        }
+    }
+
+    fn replay_arm<T>(
+        &mut self,
+        request: Request,
+        ok: impl FnOnce(Response) -> Option<T>,
+    ) -> Result<T, ArmError> {
+        let response = self.replay(request).map_err(RecordedError::into_arm)?;
+        ok(response).ok_or_else(|| unexpected_response().into_arm())
+    }
+
+    fn replay_probe<T>(
+        &mut self,
+        request: Request,
+        ok: impl FnOnce(Response) -> Option<T>,
+    ) -> Result<T, DebugProbeError> {
+        let response = self.replay(request).map_err(RecordedError::into_probe)?;
+        ok(response).ok_or_else(|| unexpected_response().into_probe())
+    }
+}
+
+fn unexpected_response() -> RecordedError {
+    RecordedError::Other("The recording contains an unexpected response".to_string())
+}
+
+pub(crate) fn done(response: Response) -> Option<()> {
+    matches!(response, Response::Done).then_some(())
+}
+
+pub(crate) fn value(response: Response) -> Option<u32> {
+    match response {
+        Response::Value(value) => Some(value),
+        _ => None,
+    }
+}
+
+fn parse_line<T: for<'de> Deserialize<'de>>(
+    index: usize,
+    line: std::io::Result<String>,
+) -> Result<T, DebugProbeError> {
+    let line = line.map_err(|error| {
+        DebugProbeError::Other(format!("Failed to read the recording: {error}"))
+    })?;
+
+    serde_json::from_str(&line).map_err(|error| {
+        DebugProbeError::Other(format!("Invalid recording, line {}: {error}", index + 1))
+    })
+}
+
+impl DebugProbe for ReplayProbe {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        let speed = self.replay_probe(Request::SetSpeed { speed_khz }, value)?;
+        self.speed_khz = speed;
+        Ok(speed)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.scan_chain = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        match &self.scan_chain {
+            Some(chain) => Ok(chain),
+            None => Err(DebugProbeError::Other(
+                "No scan chain set for replay probe".to_string(),
+            )),
+        }
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        self.replay_probe(Request::Attach, done)
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.replay_probe(Request::SelectJtagTap { index }, done)
+    }
+
+    fn detach(&mut self) -> Result<(), crate::Error> {
+        Ok(self.replay_probe(Request::Detach, done)?)
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.replay_probe(Request::TargetReset, done)
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.replay_probe(Request::TargetResetAssert, done)
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.replay_probe(Request::TargetResetDeassert, done)
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        self.replay_probe(Request::SelectProtocol { protocol }, done)?;
+        self.protocol = Some(protocol);
+        Ok(())
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        self.protocol
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        let use_overrun_detect = self.header.use_overrun_detect;
+        Ok(Box::new(ArmCommunicationInterface::new(
+            self,
+            use_overrun_detect,
+        )))
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+
+    fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        self.replay_probe(Request::TargetVoltage, |response| match response {
+            Response::Voltage(voltage) => Some(voltage),
+            _ => None,
+        })
+    }
+}
+
+impl RawDapAccess for ReplayProbe {
+    fn raw_read_register(&mut self, port: PortType, addr: u8) -> Result<u32, ArmError> {
+        self.replay_arm(Request::ReadRegister { port, addr }, value)
+    }
+
+    fn raw_read_block(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        values: &mut [u32],
+    ) -> Result<(), ArmError> {
+        let len = values.len();
+        let recorded =
+            self.replay_arm(
+                Request::ReadBlock { port, addr, len },
+                |response| match response {
+                    Response::Values(recorded) if recorded.len() == len => Some(recorded),
+                    _ => None,
+                },
+            )?;
+        values.copy_from_slice(&recorded);
+        Ok(())
+    }
+
+    fn raw_write_register(&mut self, port: PortType, addr: u8, value: u32) -> Result<(), ArmError> {
+        self.replay_arm(Request::WriteRegister { port, addr, value }, done)
+    }
+
+    fn raw_write_block(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        values: &[u32],
+    ) -> Result<(), ArmError> {
+        let values = values.to_vec();
+        self.replay_arm(Request::WriteBlock { port, addr, values }, done)
+    }
+
+    fn raw_flush(&mut self) -> Result<(), ArmError> {
+        self.replay_arm(Request::Flush, done)
+    }
+
+    fn configure_jtag(&mut self, skip_scan: bool) -> Result<(), DebugProbeError> {
+        self.replay_probe(Request::ConfigureJtag { skip_scan }, done)
+    }
+
+    fn jtag_sequence(&mut self, cycles: u8, tms: bool, tdi: u64) -> Result<(), DebugProbeError> {
+        self.replay_probe(Request::JtagSequence { cycles, tms, tdi }, done)
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        self.replay_probe(Request::SwjSequence { bit_len, bits }, done)
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let request = Request::SwjPins {
+            pin_out,
+            pin_select,
+            pin_wait,
+        };
+        self.replay_probe(request, value)
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn core_status_notification(&mut self, _state: CoreStatus) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+}
+
+impl DapProbe for ReplayProbe {
+    fn use_overrun_detect(&self) -> bool {
+        self.header.use_overrun_detect
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RECORDING: &str = r#"{"version":1,"probe_name":"Test probe","speed_khz":1000,"protocol":"Swd","use_overrun_detect":false}
+{"op":"attach","response":"done"}
+{"op":"read_register","port":"DebugPort","addr":0,"response":{"value":731911287}}
+{"op":"write_register","port":"AccessPort","addr":4,"value":1,"response":{"error":{"dap":"FaultResponse"}}}
+{"op":"read_block","port":"AccessPort","addr":12,"len":2,"response":{"values":[1,2]}}
+"#;
+
+    #[test]
+    fn replay_recording() {
+        let mut probe = ReplayProbe::new(RECORDING.as_bytes()).unwrap();
+
+        assert_eq!(probe.get_name(), "Replay of Test probe");
+        assert_eq!(probe.active_protocol(), Some(WireProtocol::Swd));
+        assert_eq!(probe.remaining_operations(), 4);
+
+        probe.attach().unwrap();
+        assert_eq!(
+            probe.raw_read_register(PortType::DebugPort, 0).unwrap(),
+            0x2ba0_1477
+        );
+        assert!(matches!(
+            probe.raw_write_register(PortType::AccessPort, 4, 1),
+            Err(ArmError::Dap(DapError::FaultResponse))
+        ));
+
+        let mut values = [0; 2];
+        probe
+            .raw_read_block(PortType::AccessPort, 12, &mut values)
+            .unwrap();
+        assert_eq!(values, [1, 2]);
+
+        assert_eq!(probe.remaining_operations(), 0);
+        assert!(probe.raw_flush().is_err());
+    }
+
+    #[test]
+    fn replay_fails_on_divergence() {
+        let mut probe = ReplayProbe::new(RECORDING.as_bytes()).unwrap();
+
+        probe.attach().unwrap();
+        // The recording reads from address 0
+        assert!(probe.raw_read_register(PortType::DebugPort, 4).is_err());
+    }
+
+    #[test]
+    fn transaction_round_trip() {
+        let transaction = Transaction {
+            request: Request::WriteBlock {
+                port: PortType::AccessPort,
+                addr: 12,
+                values: vec![1, 2, 3],
+            },
+            response: Response::Error(RecordedError::Other("timeout".to_string())),
+        };
+
+        let line = serde_json::to_string(&transaction).unwrap();
+        let parsed: Transaction = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(parsed, transaction);
+    }
+}
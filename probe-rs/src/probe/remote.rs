@@ -0,0 +1,967 @@
+//! Access to a probe over the network.
+//!
+//! A [`RemoteServer`] exposes a probe offering DAP access to one or more clients.
+//! The client side is a [`RemoteProbe`], which forwards every probe operation to the
+//! server and waits for its result. This way, a probe connected to a machine in a lab
+//! can be used by probe-rs running on a different machine, and multiple probe-rs
+//! processes can share a probe, e.g. to read memory while another one is receiving RTT
+//! messages.
+//!
+//! The protocol exchanges one JSON object per line over a TCP connection. After a client
+//! connects, the server sends a [`ServerInfo`]. Afterwards, the client sends probe
+//! operations, and the server answers each with its result, using the same format as
+//! the [recordings](super::recording).
+//!
+//! The protocol doesn't offer any authentication or encryption, so the server should
+//! only be reachable from trusted networks.
+//!
+//! The server only arbitrates the debug port, not the cores behind it. A client halting,
+//! resuming or resetting a core does so for all clients, so clients controlling the same
+//! core have to coordinate with each other. Sharing works best when only one client
+//! controls the cores, and the others only access memory.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::{BufRead, BufReader, BufWriter, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+use probe_rs_target::ScanChainElement;
+use serde::{Deserialize, Serialize};
+
+use super::recording::{done, value, RecordedError, Request, Response};
+use crate::{
+    architecture::arm::{
+        communication_interface::{DapProbe, UninitializedArmProbe},
+        ArmCommunicationInterface, ArmError, PortType, RawDapAccess,
+    },
+    probe::{DebugProbe, DebugProbeError, Probe, WireProtocol},
+    CoreStatus,
+};
+
+/// Version of the remote probe protocol.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Sent by the server after a client connected, describing the served probe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    /// Version of the protocol spoken by the server.
+    pub version: u32,
+    /// Name of the served probe.
+    pub probe_name: String,
+    /// The current speed of the served probe.
+    pub speed_khz: u32,
+    /// The protocol currently selected on the served probe.
+    pub protocol: Option<WireProtocol>,
+    /// Whether the ARM communication interface should use overrun detection.
+    pub use_overrun_detect: bool,
+}
+
+/// Address of the DP SELECT register.
+const DP_SELECT: u8 = 0x8;
+
+/// Address of the AP TAR register, in bank 0.
+const AP_TAR: u8 = 0x4;
+
+/// Address of the AP DRW register, in bank 0.
+const AP_DRW: u8 = 0xc;
+
+/// Serves a probe to [`RemoteProbe`] clients.
+///
+/// Multiple clients can be connected at the same time, their operations are forwarded to
+/// the probe one at a time. Every client configures the debug port for its own accesses,
+/// so before forwarding a register access of a different client than the previous one, the
+/// server restores the DP SELECT register and the CSW and TAR registers of the access
+/// ports to the values last written by that client. As DRW accesses can increment TAR,
+/// the TAR of an access port is read back when switching away from a client which
+/// accessed its DRW register.
+///
+/// Run control is not arbitrated, see the [module level documentation](self).
+///
+/// The probe is only attached when the first client attaches, and only detached once
+/// the last attached client detached or disconnected.
+///
+/// See the [module level documentation](self) for details.
+#[derive(Clone)]
+pub struct RemoteServer {
+    shared: Arc<Mutex<SharedProbe>>,
+    next_client: Arc<AtomicU64>,
+}
+
+impl fmt::Debug for RemoteServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteServer").finish_non_exhaustive()
+    }
+}
+
+/// The served probe, together with the state of the connected clients.
+struct SharedProbe {
+    probe: Box<dyn DebugProbe>,
+    /// The client whose register access was forwarded last.
+    active_client: Option<u64>,
+    /// Number of clients which currently have the probe attached.
+    attached_clients: usize,
+    /// The state of the connected clients, by client ID.
+    clients: BTreeMap<u64, ClientState>,
+}
+
+/// The debug port state last configured by a client.
+#[derive(Default)]
+struct ClientState {
+    attached: bool,
+    select: Option<u32>,
+    /// The CSW, TAR and TAR2 values of the client, by the SELECT value of the AP register
+    /// bank and the register address. Only bank 0 holds these registers.
+    ap_registers: BTreeMap<(u32, u8), u32>,
+    /// The AP register banks whose TAR may have been incremented by DRW accesses since
+    /// the client wrote it.
+    incremented_tar: BTreeSet<u32>,
+}
+
+impl ClientState {
+    /// The SELECT value of the AP register bank accessed by the client.
+    fn ap_bank(&self) -> u32 {
+        self.select.unwrap_or(0) & 0xff00_00f0
+    }
+
+    fn track(&mut self, request: &Request) {
+        let bank = self.ap_bank();
+        let is_bank_0 = bank & 0xf0 == 0;
+
+        match *request {
+            Request::WriteRegister {
+                port: PortType::DebugPort,
+                addr: DP_SELECT,
+                value,
+            } => self.select = Some(value),
+            Request::WriteRegister {
+                port: PortType::AccessPort,
+                addr,
+                value,
+            } if is_bank_0 && addr < AP_DRW => {
+                self.ap_registers.insert((bank, addr), value);
+                if addr == AP_TAR {
+                    self.incremented_tar.remove(&bank);
+                }
+            }
+            Request::ReadRegister {
+                port: PortType::AccessPort,
+                addr: AP_DRW,
+            }
+            | Request::WriteRegister {
+                port: PortType::AccessPort,
+                addr: AP_DRW,
+                ..
+            }
+            | Request::ReadBlock {
+                port: PortType::AccessPort,
+                addr: AP_DRW,
+                ..
+            }
+            | Request::WriteBlock {
+                port: PortType::AccessPort,
+                addr: AP_DRW,
+                ..
+            } if is_bank_0 => {
+                self.incremented_tar.insert(bank);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl RemoteServer {
+    /// Creates a server for `probe`.
+    ///
+    /// Fails if the probe doesn't offer DAP access, as only those operations can be forwarded.
+    pub fn new(probe: Probe) -> Result<Self, DebugProbeError> {
+        let mut probe = probe.inner;
+        if probe.try_as_dap_probe().is_none() {
+            return Err(DebugProbeError::InterfaceNotAvailable {
+                interface_name: "DAP",
+            });
+        }
+
+        let shared = SharedProbe {
+            probe,
+            active_client: None,
+            attached_clients: 0,
+            clients: BTreeMap::new(),
+        };
+
+        Ok(Self {
+            shared: Arc::new(Mutex::new(shared)),
+            next_client: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Returns the name of the served probe.
+    pub fn probe_name(&self) -> String {
+        self.lock().probe.get_name().to_string()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SharedProbe> {
+        // A panic while forwarding an operation doesn't leave the probe in a state
+        // other clients can't continue to use.
+        self.shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Handles the requests of a connected client, until it disconnects.
+    ///
+    /// This can be called for multiple connections at the same time, from different threads.
+    pub fn handle_connection(&self, stream: TcpStream) -> Result<(), DebugProbeError> {
+        stream.set_nodelay(true).map_err(connection_error)?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(connection_error)?);
+        let mut writer = BufWriter::new(stream);
+
+        let client = self.next_client.fetch_add(1, Ordering::Relaxed);
+
+        let info = {
+            let mut shared = self.lock();
+            shared.clients.insert(client, ClientState::default());
+            let dap = shared.dap();
+            ServerInfo {
+                version: PROTOCOL_VERSION,
+                probe_name: dap.get_name().to_string(),
+                speed_khz: dap.speed_khz(),
+                protocol: dap.active_protocol(),
+                use_overrun_detect: dap.use_overrun_detect(),
+            }
+        };
+
+        let result = send(&mut writer, &info).and_then(|_| {
+            while let Some(request) = receive::<Request>(&mut reader)? {
+                tracing::trace!("Remote request of client {client}: {request:?}");
+                let response = self.lock().execute(client, request);
+                send(&mut writer, &response)?;
+            }
+            Ok(())
+        });
+
+        let mut shared = self.lock();
+        if shared.clients[&client].attached {
+            if let Response::Error(error) = shared.execute(client, Request::Detach) {
+                tracing::warn!("Failed to detach probe: {error:?}");
+            }
+        }
+        shared.clients.remove(&client);
+        if shared.active_client == Some(client) {
+            shared.active_client = None;
+        }
+
+        result
+    }
+}
+
+impl SharedProbe {
+    fn dap(&mut self) -> &mut dyn DapProbe {
+        self.probe
+            .try_as_dap_probe()
+            .expect("DAP access was checked when creating the server")
+    }
+
+    /// Executes `request` for `client`, taking into account the other clients using the probe.
+    fn execute(&mut self, client_id: u64, request: Request) -> Response {
+        let accesses_registers = matches!(
+            request,
+            Request::ReadRegister { .. }
+                | Request::ReadBlock { .. }
+                | Request::WriteRegister { .. }
+                | Request::WriteBlock { .. }
+        );
+
+        if accesses_registers && self.active_client != Some(client_id) {
+            if let Some(previous) = self.active_client {
+                self.save(previous);
+            }
+            if let Err(error) = self.restore(client_id) {
+                return Response::Error(RecordedError::from_arm(&error));
+            }
+            self.active_client = Some(client_id);
+        }
+
+        let client = self
+            .clients
+            .get_mut(&client_id)
+            .expect("clients are registered when they connect");
+
+        let others_attached = self.attached_clients > usize::from(client.attached);
+
+        match request {
+            Request::Attach if others_attached => {
+                // The probe is already attached by another client.
+                if !client.attached {
+                    client.attached = true;
+                    self.attached_clients += 1;
+                }
+                return Response::Done;
+            }
+            Request::Detach if !client.attached => return Response::Done,
+            Request::Detach if others_attached => {
+                client.attached = false;
+                self.attached_clients -= 1;
+                return Response::Done;
+            }
+            Request::SetSpeed { .. } if others_attached => {
+                // Keep the speed used by the other clients.
+                return Response::Value(self.probe.speed_khz());
+            }
+            Request::SelectProtocol { protocol } if others_attached => {
+                return match self.probe.active_protocol() {
+                    Some(active) if active != protocol => Response::Error(RecordedError::Other(
+                        format!("The probe is shared with other clients using {active}"),
+                    )),
+                    _ => Response::Done,
+                };
+            }
+            _ => {}
+        }
+
+        let is_attach = matches!(request, Request::Attach);
+        let is_detach = matches!(request, Request::Detach);
+
+        client.track(&request);
+        let was_attached = client.attached;
+        let response = execute(self.dap(), request);
+
+        let client = self
+            .clients
+            .get_mut(&client_id)
+            .expect("clients are registered when they connect");
+        if !matches!(response, Response::Error(_)) {
+            if is_attach && !was_attached {
+                client.attached = true;
+                self.attached_clients += 1;
+            } else if is_detach {
+                client.attached = false;
+                self.attached_clients -= 1;
+            }
+        }
+
+        response
+    }
+
+    /// Saves the TAR values of `client` which were incremented by its DRW accesses, so they
+    /// can be restored after other clients used the access ports.
+    fn save(&mut self, client_id: u64) {
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return;
+        };
+        let banks = std::mem::take(&mut client.incremented_tar)
+            .into_iter()
+            .filter(|&bank| client.ap_registers.contains_key(&(bank, AP_TAR)))
+            .collect::<Vec<_>>();
+
+        for bank in banks {
+            let dap = self.dap();
+            let tar = dap
+                .raw_write_register(PortType::DebugPort, DP_SELECT, bank)
+                .and_then(|_| dap.raw_read_register(PortType::AccessPort, AP_TAR));
+
+            let registers = &mut self.clients.get_mut(&client_id).unwrap().ap_registers;
+            match tar {
+                Ok(tar) => {
+                    registers.insert((bank, AP_TAR), tar);
+                }
+                Err(error) => {
+                    // Restoring the stale value would make the client access the wrong address.
+                    tracing::warn!("Failed to read back TAR of client {client_id}: {error}");
+                    registers.remove(&(bank, AP_TAR));
+                }
+            }
+        }
+    }
+
+    /// Restores the debug port state last configured by `client`.
+    fn restore(&mut self, client_id: u64) -> Result<(), ArmError> {
+        let Some(client) = self.clients.get(&client_id) else {
+            return Ok(());
+        };
+        let registers = client
+            .ap_registers
+            .iter()
+            .map(|(&key, &value)| (key, value))
+            .collect::<Vec<_>>();
+        let client_select = client.select;
+
+        let dap = self.dap();
+        let mut select = None;
+
+        for ((bank, addr), value) in registers {
+            if select != Some(bank) {
+                dap.raw_write_register(PortType::DebugPort, DP_SELECT, bank)?;
+                select = Some(bank);
+            }
+            dap.raw_write_register(PortType::AccessPort, addr, value)?;
+        }
+
+        if let Some(client_select) = client_select {
+            if select != Some(client_select) {
+                dap.raw_write_register(PortType::DebugPort, DP_SELECT, client_select)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Executes `request` on the probe, and returns its result.
+fn execute(probe: &mut dyn DapProbe, request: Request) -> Response {
+    match request {
+        Request::SetSpeed { speed_khz } => {
+            Response::from_probe(&probe.set_speed(speed_khz), |speed| Response::Value(*speed))
+        }
+        Request::Attach => Response::from_probe(&probe.attach(), |_| Response::Done),
+        Request::Detach => Response::from_probe(&probe.detach(), |_| Response::Done),
+        Request::SelectJtagTap { index } => {
+            Response::from_probe(&probe.select_jtag_tap(index), |_| Response::Done)
+        }
+        Request::TargetReset => Response::from_probe(&probe.target_reset(), |_| Response::Done),
+        Request::TargetResetAssert => {
+            Response::from_probe(&probe.target_reset_assert(), |_| Response::Done)
+        }
+        Request::TargetResetDeassert => {
+            Response::from_probe(&probe.target_reset_deassert(), |_| Response::Done)
+        }
+        Request::SelectProtocol { protocol } => {
+            Response::from_probe(&probe.select_protocol(protocol), |_| Response::Done)
+        }
+        Request::TargetVoltage => Response::from_probe(&probe.get_target_voltage(), |voltage| {
+            Response::Voltage(*voltage)
+        }),
+        Request::ReadRegister { port, addr } => {
+            Response::from_arm(&probe.raw_read_register(port, addr), |value| {
+                Response::Value(*value)
+            })
+        }
+        Request::ReadBlock { port, addr, len } => {
+            let mut values = vec![0; len];
+            let result = probe.raw_read_block(port, addr, &mut values);
+            Response::from_arm(&result, |_| Response::Values(values))
+        }
+        Request::WriteRegister { port, addr, value } => {
+            Response::from_arm(&probe.raw_write_register(port, addr, value), |_| {
+                Response::Done
+            })
+        }
+        Request::WriteBlock { port, addr, values } => {
+            Response::from_arm(&probe.raw_write_block(port, addr, &values), |_| {
+                Response::Done
+            })
+        }
+        Request::Flush => Response::from_arm(&probe.raw_flush(), |_| Response::Done),
+        Request::ConfigureJtag { skip_scan } => {
+            Response::from_probe(&probe.configure_jtag(skip_scan), |_| Response::Done)
+        }
+        Request::JtagSequence { cycles, tms, tdi } => {
+            Response::from_probe(&probe.jtag_sequence(cycles, tms, tdi), |_| Response::Done)
+        }
+        Request::SwjSequence { bit_len, bits } => {
+            Response::from_probe(&probe.swj_sequence(bit_len, bits), |_| Response::Done)
+        }
+        Request::SwjPins {
+            pin_out,
+            pin_select,
+            pin_wait,
+        } => Response::from_probe(&probe.swj_pins(pin_out, pin_select, pin_wait), |pins| {
+            Response::Value(*pins)
+        }),
+    }
+}
+
+fn connection_error(error: std::io::Error) -> DebugProbeError {
+    DebugProbeError::Other(format!("Remote probe connection failed: {error}"))
+}
+
+fn send(writer: &mut impl Write, message: &impl Serialize) -> Result<(), DebugProbeError> {
+    let mut line = serde_json::to_string(message)
+        .map_err(|error| DebugProbeError::Other(format!("Failed to serialize: {error}")))?;
+    line.push('\n');
+
+    writer
+        .write_all(line.as_bytes())
+        .and_then(|_| writer.flush())
+        .map_err(connection_error)
+}
+
+/// Receives the next message, or `None` if the connection was closed.
+fn receive<T: for<'de> Deserialize<'de>>(
+    reader: &mut impl BufRead,
+) -> Result<Option<T>, DebugProbeError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).map_err(connection_error)? == 0 {
+        return Ok(None);
+    }
+
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|error| DebugProbeError::Other(format!("Invalid remote probe message: {error}")))
+}
+
+/// A probe connected to a [`RemoteServer`].
+///
+/// See the [module level documentation](self) for details.
+#[derive(Debug)]
+pub struct RemoteProbe {
+    name: String,
+    info: ServerInfo,
+    protocol: Option<WireProtocol>,
+    speed_khz: u32,
+    scan_chain: Option<Vec<ScanChainElement>>,
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl RemoteProbe {
+    /// Connects to the server at `address`.
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self, DebugProbeError> {
+        let stream = TcpStream::connect(address).map_err(connection_error)?;
+        stream.set_nodelay(true).map_err(connection_error)?;
+
+        let mut reader = BufReader::new(stream.try_clone().map_err(connection_error)?);
+        let writer = BufWriter::new(stream);
+
+        let Some(info) = receive::<ServerInfo>(&mut reader)? else {
+            return Err(DebugProbeError::Other(
+                "The remote probe server closed the connection".to_string(),
+            ));
+        };
+        if info.version != PROTOCOL_VERSION {
+            return Err(DebugProbeError::Other(format!(
+                "Unsupported remote probe protocol version {}, expected {}",
+                info.version, PROTOCOL_VERSION
+            )));
+        }
+
+        Ok(Self {
+            name: format!("Remote {}", info.probe_name),
+            protocol: info.protocol,
+            speed_khz: info.speed_khz,
+            info,
+            scan_chain: None,
+            reader,
+            writer,
+        })
+    }
+
+    /// Returns the information the server sent when connecting.
+    pub fn server_info(&self) -> &ServerInfo {
+        &self.info
+    }
+
+    fn call(&mut self, request: Request) -> Result<Response, RecordedError> {
+        let exchange = |probe: &mut Self| {
+            send(&mut probe.writer, &request)?;
+            receive::<Response>(&mut probe.reader)?.ok_or_else(|| {
+                DebugProbeError::Other("The remote probe server closed the connection".to_string())
+            })
+        };
+
+        match exchange(self) {
+            Ok(Response::Error(error)) => Err(error),
+            Ok(response) => Ok(response),
+            Err(error) => Err(RecordedError::Other(error.to_string())),
+        }
+    }
+
+    fn call_arm<T>(
+        &mut self,
+        request: Request,
+        ok: impl FnOnce(Response) -> Option<T>,
+    ) -> Result<T, ArmError> {
+        let response = self.call(request).map_err(RecordedError::into_arm)?;
+        ok(response).ok_or_else(|| unexpected_response().into_arm())
+    }
+
+    fn call_probe<T>(
+        &mut self,
+        request: Request,
+        ok: impl FnOnce(Response) -> Option<T>,
+    ) -> Result<T, DebugProbeError> {
+        let response = self.call(request).map_err(RecordedError::into_probe)?;
+        ok(response).ok_or_else(|| unexpected_response().into_probe())
+    }
+}
+
+fn unexpected_response() -> RecordedError {
+    RecordedError::Other("The remote probe server sent an unexpected response".to_string())
+}
+
+impl DebugProbe for RemoteProbe {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        let speed = self.call_probe(Request::SetSpeed { speed_khz }, value)?;
+        self.speed_khz = speed;
+        Ok(speed)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.scan_chain = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        match &self.scan_chain {
+            Some(chain) => Ok(chain),
+            None => Err(DebugProbeError::Other(
+                "No scan chain set for remote probe".to_string(),
+            )),
+        }
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        self.call_probe(Request::Attach, done)
+    }
+
+    fn select_jtag_tap(&mut self, index: usize) -> Result<(), DebugProbeError> {
+        self.call_probe(Request::SelectJtagTap { index }, done)
+    }
+
+    fn detach(&mut self) -> Result<(), crate::Error> {
+        Ok(self.call_probe(Request::Detach, done)?)
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.call_probe(Request::TargetReset, done)
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.call_probe(Request::TargetResetAssert, done)
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.call_probe(Request::TargetResetDeassert, done)
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        self.call_probe(Request::SelectProtocol { protocol }, done)?;
+        self.protocol = Some(protocol);
+        Ok(())
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        self.protocol
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        let use_overrun_detect = self.info.use_overrun_detect;
+        Ok(Box::new(ArmCommunicationInterface::new(
+            self,
+            use_overrun_detect,
+        )))
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+
+    fn get_target_voltage(&mut self) -> Result<Option<f32>, DebugProbeError> {
+        self.call_probe(Request::TargetVoltage, |response| match response {
+            Response::Voltage(voltage) => Some(voltage),
+            _ => None,
+        })
+    }
+}
+
+impl RawDapAccess for RemoteProbe {
+    fn raw_read_register(&mut self, port: PortType, addr: u8) -> Result<u32, ArmError> {
+        self.call_arm(Request::ReadRegister { port, addr }, value)
+    }
+
+    fn raw_read_block(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        values: &mut [u32],
+    ) -> Result<(), ArmError> {
+        let len = values.len();
+        let received =
+            self.call_arm(
+                Request::ReadBlock { port, addr, len },
+                |response| match response {
+                    Response::Values(received) if received.len() == len => Some(received),
+                    _ => None,
+                },
+            )?;
+        values.copy_from_slice(&received);
+        Ok(())
+    }
+
+    fn raw_write_register(&mut self, port: PortType, addr: u8, value: u32) -> Result<(), ArmError> {
+        self.call_arm(Request::WriteRegister { port, addr, value }, done)
+    }
+
+    fn raw_write_block(
+        &mut self,
+        port: PortType,
+        addr: u8,
+        values: &[u32],
+    ) -> Result<(), ArmError> {
+        let values = values.to_vec();
+        self.call_arm(Request::WriteBlock { port, addr, values }, done)
+    }
+
+    fn raw_flush(&mut self) -> Result<(), ArmError> {
+        self.call_arm(Request::Flush, done)
+    }
+
+    fn configure_jtag(&mut self, skip_scan: bool) -> Result<(), DebugProbeError> {
+        self.call_probe(Request::ConfigureJtag { skip_scan }, done)
+    }
+
+    fn jtag_sequence(&mut self, cycles: u8, tms: bool, tdi: u64) -> Result<(), DebugProbeError> {
+        self.call_probe(Request::JtagSequence { cycles, tms, tdi }, done)
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        self.call_probe(Request::SwjSequence { bit_len, bits }, done)
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let request = Request::SwjPins {
+            pin_out,
+            pin_select,
+            pin_wait,
+        };
+        self.call_probe(request, value)
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn core_status_notification(&mut self, _state: CoreStatus) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+}
+
+impl DapProbe for RemoteProbe {
+    fn use_overrun_detect(&self) -> bool {
+        self.info.use_overrun_detect
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::{SocketAddr, TcpListener},
+        thread::JoinHandle,
+    };
+
+    use super::*;
+    use crate::{architecture::arm::DapError, probe::recording::ReplayProbe};
+
+    const RECORDING: &str = r#"{"version":1,"probe_name":"Test probe","speed_khz":1000,"protocol":"Swd","use_overrun_detect":true}
+{"op":"attach","response":"done"}
+{"op":"read_register","port":"DebugPort","addr":0,"response":{"value":731911287}}
+{"op":"write_register","port":"AccessPort","addr":4,"value":1,"response":{"error":{"dap":"FaultResponse"}}}
+{"op":"read_block","port":"AccessPort","addr":12,"len":2,"response":{"values":[1,2]}}
+{"op":"detach","response":"done"}
+"#;
+
+    fn serve(recording: &str, connections: usize) -> (SocketAddr, JoinHandle<()>) {
+        let probe =
+            Probe::from_specific_probe(Box::new(ReplayProbe::new(recording.as_bytes()).unwrap()));
+        let server = RemoteServer::new(probe).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server_thread = std::thread::spawn(move || {
+            std::thread::scope(|scope| {
+                for stream in listener.incoming().take(connections) {
+                    let server = &server;
+                    scope.spawn(move || server.handle_connection(stream.unwrap()).unwrap());
+                }
+            })
+        });
+
+        (address, server_thread)
+    }
+
+    #[test]
+    fn forward_operations_to_server() {
+        let (address, server_thread) = serve(RECORDING, 1);
+
+        let mut probe = RemoteProbe::connect(address).unwrap();
+        assert_eq!(probe.get_name(), "Remote Replay of Test probe");
+        assert_eq!(probe.speed_khz(), 1000);
+        assert_eq!(probe.active_protocol(), Some(WireProtocol::Swd));
+        assert!(probe.use_overrun_detect());
+
+        probe.attach().unwrap();
+        assert_eq!(
+            probe.raw_read_register(PortType::DebugPort, 0).unwrap(),
+            0x2ba0_1477
+        );
+        assert!(matches!(
+            probe.raw_write_register(PortType::AccessPort, 4, 1),
+            Err(ArmError::Dap(DapError::FaultResponse))
+        ));
+
+        let mut values = [0; 2];
+        probe
+            .raw_read_block(PortType::AccessPort, 12, &mut values)
+            .unwrap();
+        assert_eq!(values, [1, 2]);
+
+        probe.detach().unwrap();
+        drop(probe);
+        server_thread.join().unwrap();
+    }
+
+    const SHARED_RECORDING: &str = r#"{"version":1,"probe_name":"Test probe","speed_khz":1000,"protocol":"Swd","use_overrun_detect":false}
+{"op":"attach","response":"done"}
+{"op":"write_register","port":"DebugPort","addr":8,"value":16777216,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":4,"value":536870912,"response":"done"}
+{"op":"write_register","port":"DebugPort","addr":8,"value":0,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":4,"value":4096,"response":"done"}
+{"op":"write_register","port":"DebugPort","addr":8,"value":16777216,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":4,"value":536870912,"response":"done"}
+{"op":"read_register","port":"AccessPort","addr":12,"response":{"value":42}}
+{"op":"detach","response":"done"}
+"#;
+
+    #[test]
+    fn share_probe_between_clients() {
+        let (address, server_thread) = serve(SHARED_RECORDING, 2);
+
+        let mut first = RemoteProbe::connect(address).unwrap();
+        first.attach().unwrap();
+        first
+            .raw_write_register(PortType::DebugPort, DP_SELECT, 0x0100_0000)
+            .unwrap();
+        first
+            .raw_write_register(PortType::AccessPort, 4, 0x2000_0000)
+            .unwrap();
+
+        // The probe is already attached, so this is not forwarded.
+        let mut second = RemoteProbe::connect(address).unwrap();
+        second.attach().unwrap();
+        second
+            .raw_write_register(PortType::DebugPort, DP_SELECT, 0)
+            .unwrap();
+        second
+            .raw_write_register(PortType::AccessPort, 4, 0x1000)
+            .unwrap();
+
+        // The SELECT and TAR values of the first client are restored before reading.
+        assert_eq!(
+            first.raw_read_register(PortType::AccessPort, 12).unwrap(),
+            42
+        );
+
+        // Only the last client detaching detaches the probe.
+        second.detach().unwrap();
+        first.detach().unwrap();
+
+        drop(first);
+        drop(second);
+        server_thread.join().unwrap();
+    }
+
+    // SELECT values: AP 1 bank 0, AP 1 bank 1, and AP 0 bank 0.
+    const BANKED_RECORDING: &str = r#"{"version":1,"probe_name":"Test probe","speed_khz":1000,"protocol":"Swd","use_overrun_detect":false}
+{"op":"attach","response":"done"}
+{"op":"write_register","port":"DebugPort","addr":8,"value":16777216,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":4,"value":536870912,"response":"done"}
+{"op":"write_register","port":"DebugPort","addr":8,"value":16777232,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":4,"value":3735928559,"response":"done"}
+{"op":"write_register","port":"DebugPort","addr":8,"value":0,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":4,"value":4096,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":12,"value":7,"response":"done"}
+{"op":"write_register","port":"DebugPort","addr":8,"value":0,"response":"done"}
+{"op":"read_register","port":"AccessPort","addr":4,"response":{"value":4100}}
+{"op":"write_register","port":"DebugPort","addr":8,"value":16777216,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":4,"value":536870912,"response":"done"}
+{"op":"write_register","port":"DebugPort","addr":8,"value":16777232,"response":"done"}
+{"op":"read_register","port":"AccessPort","addr":4,"response":{"value":3735928559}}
+{"op":"write_register","port":"DebugPort","addr":8,"value":0,"response":"done"}
+{"op":"write_register","port":"AccessPort","addr":4,"value":4100,"response":"done"}
+{"op":"read_register","port":"AccessPort","addr":12,"response":{"value":42}}
+{"op":"detach","response":"done"}
+"#;
+
+    #[test]
+    fn share_banked_ap_registers_between_clients() {
+        let (address, server_thread) = serve(BANKED_RECORDING, 2);
+
+        let mut first = RemoteProbe::connect(address).unwrap();
+        first.attach().unwrap();
+        // TAR of AP 1.
+        first
+            .raw_write_register(PortType::DebugPort, DP_SELECT, 0x0100_0000)
+            .unwrap();
+        first
+            .raw_write_register(PortType::AccessPort, 4, 0x2000_0000)
+            .unwrap();
+        // BD1 of AP 1, which must not be restored as TAR.
+        first
+            .raw_write_register(PortType::DebugPort, DP_SELECT, 0x0100_0010)
+            .unwrap();
+        first
+            .raw_write_register(PortType::AccessPort, 4, 0xdead_beef)
+            .unwrap();
+
+        // The DRW write increments TAR of AP 0, which is read back when switching clients.
+        let mut second = RemoteProbe::connect(address).unwrap();
+        second.attach().unwrap();
+        second
+            .raw_write_register(PortType::DebugPort, DP_SELECT, 0)
+            .unwrap();
+        second
+            .raw_write_register(PortType::AccessPort, 4, 0x1000)
+            .unwrap();
+        second
+            .raw_write_register(PortType::AccessPort, 12, 7)
+            .unwrap();
+
+        // Only TAR of AP 1 is restored, and the bank 1 SELECT of the first client.
+        assert_eq!(
+            first.raw_read_register(PortType::AccessPort, 4).unwrap(),
+            0xdead_beef
+        );
+
+        // The incremented TAR is restored for the second client.
+        assert_eq!(
+            second.raw_read_register(PortType::AccessPort, 12).unwrap(),
+            42
+        );
+
+        second.detach().unwrap();
+        first.detach().unwrap();
+
+        drop(first);
+        drop(second);
+        server_thread.join().unwrap();
+    }
+}
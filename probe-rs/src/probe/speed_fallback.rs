@@ -0,0 +1,196 @@
+//! Lowering the protocol speed when transfers to the target keep failing.
+//!
+//! Long or badly shielded wires often work, just not at the speed the probe defaults to. With a
+//! [`SpeedFallback`] configured on the [`Probe`](super::Probe), repeated WAIT, FAULT, parity and
+//! protocol errors halve the speed until transfers succeed, or the minimum speed is reached.
+
+use crate::architecture::arm::{ArmError, DapError};
+
+/// Configures how the protocol speed is lowered on transfer errors, see
+/// [`Probe::set_speed_fallback`](super::Probe::set_speed_fallback).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeedFallback {
+    /// The speed is never lowered below this value, in kHz.
+    pub min_speed_khz: u32,
+    /// The number of consecutive failed transfers after which the speed is lowered.
+    pub error_threshold: u32,
+    /// If set, the speed is doubled again after this many successful transfers, up to the
+    /// speed which was configured initially.
+    pub recover_after: Option<u32>,
+}
+
+impl Default for SpeedFallback {
+    fn default() -> Self {
+        Self {
+            min_speed_khz: 100,
+            error_threshold: 3,
+            recover_after: Some(10_000),
+        }
+    }
+}
+
+/// Tracks the transfer errors of a probe, and decides when the speed is changed.
+#[derive(Debug)]
+pub(crate) struct SpeedFallbackState {
+    config: SpeedFallback,
+    /// The speed the probe was configured with.
+    initial_speed_khz: u32,
+    current_speed_khz: u32,
+    consecutive_errors: u32,
+    successes: u32,
+}
+
+impl SpeedFallbackState {
+    pub(crate) fn new(config: SpeedFallback, speed_khz: u32) -> Self {
+        Self {
+            config,
+            initial_speed_khz: speed_khz,
+            current_speed_khz: speed_khz,
+            consecutive_errors: 0,
+            successes: 0,
+        }
+    }
+
+    /// Returns `true` for errors which can be caused by a protocol speed which is too high.
+    pub(crate) fn is_signal_error(error: &ArmError) -> bool {
+        matches!(
+            error,
+            ArmError::Dap(
+                DapError::WaitResponse
+                    | DapError::FaultResponse
+                    | DapError::IncorrectParity
+                    | DapError::NoAcknowledge
+                    | DapError::SwdProtocol
+            )
+        )
+    }
+
+    /// Records the result of a transfer, and returns the new speed if it should be changed.
+    pub(crate) fn record<T>(&mut self, result: &Result<T, ArmError>) -> Option<u32> {
+        match result {
+            Ok(_) => self.record_success(),
+            Err(error) if Self::is_signal_error(error) => self.record_error(),
+            // Other errors say nothing about the signal quality.
+            Err(_) => None,
+        }
+    }
+
+    fn record_success(&mut self) -> Option<u32> {
+        self.consecutive_errors = 0;
+
+        let recover_after = self.config.recover_after?;
+        if self.current_speed_khz >= self.initial_speed_khz {
+            return None;
+        }
+
+        self.successes += 1;
+        if self.successes < recover_after {
+            return None;
+        }
+
+        self.change_speed((self.current_speed_khz * 2).min(self.initial_speed_khz))
+    }
+
+    fn record_error(&mut self) -> Option<u32> {
+        self.successes = 0;
+        self.consecutive_errors += 1;
+
+        if self.consecutive_errors < self.config.error_threshold {
+            return None;
+        }
+
+        self.step_down()
+    }
+
+    /// Halves the speed, unless the minimum speed was already reached.
+    pub(crate) fn step_down(&mut self) -> Option<u32> {
+        self.successes = 0;
+        self.consecutive_errors = 0;
+
+        let speed = (self.current_speed_khz / 2).max(self.config.min_speed_khz);
+        if speed >= self.current_speed_khz {
+            return None;
+        }
+
+        self.change_speed(speed)
+    }
+
+    fn change_speed(&mut self, speed_khz: u32) -> Option<u32> {
+        self.successes = 0;
+        self.current_speed_khz = speed_khz;
+        Some(speed_khz)
+    }
+
+    /// Updates the current speed with the speed the probe actually selected.
+    pub(crate) fn set_current_speed(&mut self, speed_khz: u32) {
+        self.current_speed_khz = speed_khz;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn wait() -> Result<(), ArmError> {
+        Err(ArmError::Dap(DapError::WaitResponse))
+    }
+
+    #[test]
+    fn steps_down_after_consecutive_errors() {
+        let mut state = SpeedFallbackState::new(SpeedFallback::default(), 4000);
+
+        assert_eq!(state.record(&wait()), None);
+        assert_eq!(state.record(&wait()), None);
+        assert_eq!(state.record(&Ok(())), None);
+        assert_eq!(state.record(&wait()), None);
+        assert_eq!(state.record(&wait()), None);
+        assert_eq!(state.record(&wait()), Some(2000));
+    }
+
+    #[test]
+    fn stops_at_min_speed() {
+        let config = SpeedFallback {
+            min_speed_khz: 300,
+            error_threshold: 1,
+            recover_after: None,
+        };
+        let mut state = SpeedFallbackState::new(config, 1000);
+
+        assert_eq!(state.record(&wait()), Some(500));
+        assert_eq!(state.record(&wait()), Some(300));
+        assert_eq!(state.record(&wait()), None);
+    }
+
+    #[test]
+    fn ignores_other_errors() {
+        let mut state = SpeedFallbackState::new(
+            SpeedFallback {
+                error_threshold: 1,
+                ..Default::default()
+            },
+            1000,
+        );
+
+        assert_eq!(state.record::<()>(&Err(ArmError::Timeout)), None);
+    }
+
+    #[test]
+    fn recovers_to_initial_speed() {
+        let config = SpeedFallback {
+            min_speed_khz: 100,
+            error_threshold: 1,
+            recover_after: Some(2),
+        };
+        let mut state = SpeedFallbackState::new(config, 1000);
+
+        assert_eq!(state.record(&wait()), Some(500));
+        assert_eq!(state.record(&wait()), Some(250));
+
+        assert_eq!(state.record(&Ok(())), None);
+        assert_eq!(state.record(&Ok(())), Some(500));
+        assert_eq!(state.record(&Ok(())), None);
+        assert_eq!(state.record(&Ok(())), Some(1000));
+        assert_eq!(state.record(&Ok(())), None);
+        assert_eq!(state.record(&Ok(())), None);
+    }
+}
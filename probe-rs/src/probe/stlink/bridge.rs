@@ -0,0 +1,528 @@
+//! The bridge interface of the ST-LINK-V3.
+//!
+//! Next to the debug interface, the ST-LINK-V3 has a separate USB interface which gives
+//! access to SPI, I2C and GPIO pins of the probe. As the bridge uses its own interface, it
+//! can be used while another program is debugging the target through the same probe.
+//!
+//! The UARTs of the probe are regular virtual COM ports, which are listed with
+//! [`StLinkBridge::uarts`].
+
+use std::{path::PathBuf, time::Duration};
+
+use nusb::{
+    transfer::{Direction, EndpointType},
+    DeviceInfo, Interface,
+};
+use scroll::{Pread, LE};
+
+use super::{
+    tools::{is_stlink_device, read_serial_number},
+    usb_interface::USB_PID_EP_MAP,
+    StLinkFactory, StlinkError,
+};
+use crate::probe::{
+    usb_util::{self, InterfaceExt},
+    DebugProbeInfo, DebugProbeSelector, ProbeCreationError,
+};
+
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// The USB Command packet size.
+const CMD_LEN: usize = 16;
+
+/// USB class of the vendor specific debug and bridge interfaces.
+const VENDOR_SPECIFIC_CLASS: u8 = 0xff;
+/// USB class of the CDC communication interfaces of the virtual COM ports.
+const CDC_COMMUNICATION_CLASS: u8 = 0x02;
+
+/// Status returned by successful bridge commands.
+const BRIDGE_OK: u16 = 0x80;
+
+mod commands {
+    pub const BRIDGE_COMMAND: u8 = 0xfc;
+
+    pub const GET_RWCMD_STATUS: u8 = 0x02;
+    pub const GET_CLOCK: u8 = 0x03;
+
+    pub const INIT_SPI: u8 = 0x20;
+    pub const WRITE_SPI: u8 = 0x21;
+    pub const READ_SPI: u8 = 0x22;
+    pub const CS_SPI: u8 = 0x23;
+
+    pub const INIT_I2C: u8 = 0x30;
+    pub const WRITE_I2C: u8 = 0x31;
+    pub const READ_I2C: u8 = 0x32;
+
+    pub const INIT_GPIO: u8 = 0x60;
+    pub const SET_RESET_GPIO: u8 = 0x61;
+    pub const READ_GPIO: u8 = 0x62;
+
+    /// Peripheral ids used by `GET_CLOCK`.
+    pub const COM_SPI: u8 = 0x02;
+    pub const COM_I2C: u8 = 0x03;
+}
+
+/// The number of GPIO pins of the bridge.
+pub const BRIDGE_GPIO_COUNT: usize = 4;
+
+/// The SPI clock polarity and phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpiMode {
+    /// Clock idles low, data sampled on the rising edge.
+    #[default]
+    Mode0,
+    /// Clock idles low, data sampled on the falling edge.
+    Mode1,
+    /// Clock idles high, data sampled on the falling edge.
+    Mode2,
+    /// Clock idles high, data sampled on the rising edge.
+    Mode3,
+}
+
+impl SpiMode {
+    fn cpol_cpha(self) -> (u8, u8) {
+        match self {
+            SpiMode::Mode0 => (0, 0),
+            SpiMode::Mode1 => (0, 1),
+            SpiMode::Mode2 => (1, 0),
+            SpiMode::Mode3 => (1, 1),
+        }
+    }
+}
+
+/// The configuration of a bridge GPIO pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeGpioMode {
+    /// Floating input.
+    Input,
+    /// Input with pull-up.
+    InputPullUp,
+    /// Input with pull-down.
+    InputPullDown,
+    /// Push-pull output.
+    Output,
+    /// Open drain output.
+    OutputOpenDrain,
+}
+
+impl BridgeGpioMode {
+    /// Encodes mode, speed, pull and output type.
+    fn encode(self) -> [u8; 4] {
+        match self {
+            BridgeGpioMode::Input => [0, 0, 0, 0],
+            BridgeGpioMode::InputPullUp => [0, 0, 1, 0],
+            BridgeGpioMode::InputPullDown => [0, 0, 2, 0],
+            BridgeGpioMode::Output => [1, 0, 0, 0],
+            BridgeGpioMode::OutputOpenDrain => [1, 0, 0, 1],
+        }
+    }
+}
+
+/// A virtual COM port of an ST-LINK-V3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StLinkUart {
+    /// The name of the port, as reported by the probe.
+    pub name: String,
+    /// The serial port, if it could be determined.
+    pub path: Option<PathBuf>,
+}
+
+/// The bridge interface of an ST-LINK-V3.
+///
+/// See the [module level documentation](self) for details.
+pub struct StLinkBridge {
+    device: DeviceInfo,
+    interface: Interface,
+    endpoint_in: u8,
+    endpoint_out: u8,
+}
+
+impl std::fmt::Debug for StLinkBridge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StLinkBridge").finish_non_exhaustive()
+    }
+}
+
+impl StLinkBridge {
+    /// Lists the connected ST-LINK-V3 probes, which have a bridge interface.
+    pub fn list() -> Vec<DebugProbeInfo> {
+        let Ok(devices) = nusb::list_devices() else {
+            return vec![];
+        };
+
+        devices
+            .filter(is_stlink_v3)
+            .map(|device| {
+                DebugProbeInfo::new(
+                    format!(
+                        "STLink {}",
+                        &USB_PID_EP_MAP[&device.product_id()].version_name
+                    ),
+                    device.vendor_id(),
+                    device.product_id(),
+                    read_serial_number(&device),
+                    &StLinkFactory,
+                    None,
+                )
+                .with_usb_port(&device)
+            })
+            .collect()
+    }
+
+    /// Opens the bridge interface of the ST-LINK-V3 matching `selector`.
+    pub fn open(selector: &DebugProbeSelector) -> Result<Self, ProbeCreationError> {
+        let device = nusb::list_devices()
+            .map_err(ProbeCreationError::Usb)?
+            .filter(is_stlink_v3)
+            .find(|device| {
+                device.vendor_id() == selector.vendor_id
+                    && device.product_id() == selector.product_id
+                    && selector
+                        .serial_number
+                        .as_ref()
+                        .map(|s| read_serial_number(device).as_ref() == Some(s))
+                        .unwrap_or(true)
+            })
+            .ok_or(ProbeCreationError::NotFound)?;
+
+        let device_handle = device.open().map_err(ProbeCreationError::Usb)?;
+
+        // The debug interface is the first vendor specific interface, the bridge is the
+        // other one.
+        let mut endpoints = None;
+        if let Some(config) = device_handle.configurations().next() {
+            for altsetting in config.interface_alt_settings() {
+                if altsetting.class() != VENDOR_SPECIFIC_CLASS || altsetting.interface_number() == 0
+                {
+                    continue;
+                }
+
+                let bulk = |direction| {
+                    altsetting
+                        .endpoints()
+                        .find(|ep| {
+                            ep.transfer_type() == EndpointType::Bulk && ep.direction() == direction
+                        })
+                        .map(|ep| ep.address())
+                };
+
+                if let (Some(endpoint_in), Some(endpoint_out)) =
+                    (bulk(Direction::In), bulk(Direction::Out))
+                {
+                    endpoints = Some((altsetting.interface_number(), endpoint_in, endpoint_out));
+                    break;
+                }
+            }
+        }
+
+        let Some((interface_number, endpoint_in, endpoint_out)) = endpoints else {
+            return Err(StlinkError::BridgeNotSupported.into());
+        };
+
+        let interface = device_handle
+            .claim_interface(interface_number)
+            .map_err(ProbeCreationError::Usb)?;
+        tracing::trace!("Claimed bridge interface {interface_number} of USB device.");
+
+        Ok(Self {
+            device,
+            interface,
+            endpoint_in,
+            endpoint_out,
+        })
+    }
+
+    /// Returns the virtual COM ports of the probe.
+    pub fn uarts(&self) -> Vec<StLinkUart> {
+        self.device
+            .interfaces()
+            .filter(|interface| interface.class() == CDC_COMMUNICATION_CLASS)
+            .map(|interface| StLinkUart {
+                name: interface.interface_string().unwrap_or("VCP").to_string(),
+                path: usb_util::serial_port_path(&self.device, interface.interface_number()),
+            })
+            .collect()
+    }
+
+    /// Sends a command with optional data, and reads the response.
+    fn transfer(
+        &mut self,
+        cmd: &[u8],
+        write_data: &[u8],
+        read_len: usize,
+    ) -> Result<Vec<u8>, StlinkError> {
+        let mut packet = [0; CMD_LEN];
+        packet[0] = commands::BRIDGE_COMMAND;
+        packet[1..=cmd.len()].copy_from_slice(cmd);
+        tracing::trace!("Sending bridge command {:02x?}", packet);
+
+        self.interface
+            .write_bulk(self.endpoint_out, &packet, TIMEOUT)?;
+        if !write_data.is_empty() {
+            self.interface
+                .write_bulk(self.endpoint_out, write_data, TIMEOUT)?;
+        }
+
+        let mut response = vec![0; read_len];
+        read_response(&mut response, |buffer| {
+            self.interface.read_bulk(self.endpoint_in, buffer, TIMEOUT)
+        })?;
+
+        Ok(response)
+    }
+
+    /// Sends a command which only returns a status.
+    fn command(&mut self, cmd: &[u8], write_data: &[u8]) -> Result<(), StlinkError> {
+        let response = self.transfer(cmd, write_data, 2)?;
+        check_status(&response)
+    }
+
+    /// Reads the status of the last read or write command.
+    fn rw_status(&mut self) -> Result<(), StlinkError> {
+        let response = self.transfer(&[commands::GET_RWCMD_STATUS], &[], 8)?;
+        check_status(&response)
+    }
+
+    /// Returns the input clock of the given peripheral in kHz.
+    fn input_clock_khz(&mut self, com: u8) -> Result<u32, StlinkError> {
+        let response = self.transfer(&[commands::GET_CLOCK, com], &[], 12)?;
+        check_status(&response)?;
+        Ok(response.pread_with(4, LE).unwrap())
+    }
+
+    /// Initializes SPI as controller with the given mode and a clock of at most `speed_khz`.
+    ///
+    /// Returns the actual clock speed.
+    pub fn spi_init(&mut self, mode: SpiMode, speed_khz: u32) -> Result<u32, StlinkError> {
+        let input_khz = self.input_clock_khz(commands::COM_SPI)?;
+        let (prescaler, actual_khz) = spi_prescaler(input_khz, speed_khz);
+        let (cpol, cpha) = mode.cpol_cpha();
+
+        // Full duplex, controller, MSB first, 8 bit frames, software chip select
+        self.command(
+            &[commands::INIT_SPI, 0, 0, cpol, cpha, 0, 0, 0, 0, prescaler],
+            &[],
+        )?;
+
+        Ok(actual_khz)
+    }
+
+    /// Sets the level of the chip select line.
+    pub fn spi_set_cs(&mut self, high: bool) -> Result<(), StlinkError> {
+        self.command(&[commands::CS_SPI, high as u8], &[])
+    }
+
+    /// Writes `data` to the SPI bus.
+    pub fn spi_write(&mut self, data: &[u8]) -> Result<(), StlinkError> {
+        let len = (data.len() as u16).to_le_bytes();
+        let response = self.transfer(&[commands::WRITE_SPI, len[0], len[1]], data, 4)?;
+        check_status(&response)
+    }
+
+    /// Reads `len` bytes from the SPI bus.
+    pub fn spi_read(&mut self, len: usize) -> Result<Vec<u8>, StlinkError> {
+        let len_bytes = (len as u16).to_le_bytes();
+        let data = self.transfer(&[commands::READ_SPI, len_bytes[0], len_bytes[1]], &[], len)?;
+        self.rw_status()?;
+        Ok(data)
+    }
+
+    /// Initializes I2C as controller, with a clock of `speed_khz`.
+    pub fn i2c_init(&mut self, speed_khz: u32) -> Result<(), StlinkError> {
+        let input_khz = self.input_clock_khz(commands::COM_I2C)?;
+        let timing = i2c_timing(input_khz, speed_khz)?.to_le_bytes();
+
+        // 7 bit addressing, analog filter enabled, no digital filter
+        self.command(
+            &[
+                commands::INIT_I2C,
+                timing[0],
+                timing[1],
+                timing[2],
+                timing[3],
+                0,
+                0,
+                0,
+                1,
+                0,
+            ],
+            &[],
+        )
+    }
+
+    /// Writes `data` to the device with the 7 bit address `address`.
+    pub fn i2c_write(&mut self, address: u8, data: &[u8]) -> Result<(), StlinkError> {
+        let len = (data.len() as u16).to_le_bytes();
+        let response = self.transfer(&[commands::WRITE_I2C, len[0], len[1], address], data, 4)?;
+        check_status(&response)
+    }
+
+    /// Reads `len` bytes from the device with the 7 bit address `address`.
+    pub fn i2c_read(&mut self, address: u8, len: usize) -> Result<Vec<u8>, StlinkError> {
+        let len_bytes = (len as u16).to_le_bytes();
+        let data = self.transfer(
+            &[commands::READ_I2C, len_bytes[0], len_bytes[1], address],
+            &[],
+            len,
+        )?;
+        self.rw_status()?;
+        Ok(data)
+    }
+
+    /// Configures the GPIO pins in `mask`.
+    pub fn gpio_init(&mut self, mask: u8, mode: BridgeGpioMode) -> Result<(), StlinkError> {
+        let mut cmd = vec![commands::INIT_GPIO, mask];
+        for _ in 0..BRIDGE_GPIO_COUNT {
+            cmd.extend_from_slice(&mode.encode());
+        }
+        self.command(&cmd, &[])
+    }
+
+    /// Sets the output pins in `mask` to the corresponding bits of `values`.
+    pub fn gpio_write(&mut self, mask: u8, values: u8) -> Result<(), StlinkError> {
+        self.command(&[commands::SET_RESET_GPIO, mask, values], &[])
+    }
+
+    /// Reads the pins in `mask`.
+    pub fn gpio_read(&mut self, mask: u8) -> Result<u8, StlinkError> {
+        let response = self.transfer(&[commands::READ_GPIO, mask], &[], 4)?;
+        check_status(&response)?;
+        Ok(response[2] & mask)
+    }
+}
+
+fn is_stlink_v3(device: &DeviceInfo) -> bool {
+    is_stlink_device(device)
+        && USB_PID_EP_MAP[&device.product_id()]
+            .version_name
+            .starts_with("V3")
+}
+
+fn check_status(response: &[u8]) -> Result<(), StlinkError> {
+    let status: u16 = response.pread_with(0, LE).unwrap();
+    if status != BRIDGE_OK {
+        return Err(StlinkError::BridgeCommandFailed(status));
+    }
+    Ok(())
+}
+
+/// Returns the prescaler for the highest SPI clock of at most `speed_khz`, and that clock.
+///
+/// The SPI clock is the input clock divided by `2^(prescaler + 1)`.
+fn spi_prescaler(input_khz: u32, speed_khz: u32) -> (u8, u32) {
+    let prescaler = (0..8u8)
+        .find(|prescaler| input_khz >> (prescaler + 1) <= speed_khz)
+        .unwrap_or(7);
+
+    (prescaler, input_khz >> (prescaler + 1))
+}
+
+/// Calculates the value of the I2C timing register for the given clock speed.
+///
+/// Standard mode uses an equal low and high time, faster modes a 2:1 ratio. Setup and hold
+/// times are kept short, which works for the common EEPROMs and sensors.
+fn i2c_timing(input_khz: u32, speed_khz: u32) -> Result<u32, StlinkError> {
+    if speed_khz == 0 || speed_khz > 1000 {
+        return Err(StlinkError::BridgeUnsupportedSpeed(speed_khz));
+    }
+
+    let period = input_khz / speed_khz;
+    let (presc, low, high) = (0..16u32)
+        .map(|presc| {
+            let cycles = period / (presc + 1);
+            let low = if speed_khz <= 100 {
+                cycles / 2
+            } else {
+                cycles * 2 / 3
+            };
+            (presc, low, cycles - low)
+        })
+        .find(|(_, low, high)| *low <= 256 && *high <= 256)
+        .ok_or(StlinkError::BridgeUnsupportedSpeed(speed_khz))?;
+
+    let scll = low.saturating_sub(1);
+    let sclh = high.saturating_sub(1);
+    let sdadel = 2;
+    let scldel = 4;
+
+    Ok(presc << 28 | scldel << 20 | sdadel << 16 | sclh << 8 | scll)
+}
+
+/// Fills `response` with as many reads as needed, as the probe may split it into multiple
+/// USB packets.
+///
+/// Fails if a read returns no data, instead of waiting for the rest forever.
+fn read_response(
+    response: &mut [u8],
+    mut read: impl FnMut(&mut [u8]) -> std::io::Result<usize>,
+) -> Result<(), StlinkError> {
+    let mut total = 0;
+    while total < response.len() {
+        let count = read(&mut response[total..])?;
+        if count == 0 {
+            return Err(StlinkError::NotEnoughBytesRead {
+                is: total,
+                should: response.len(),
+            });
+        }
+        total += count;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A device which returns `packets` for consecutive reads, and no data afterwards.
+    fn fake_device(packets: &[&[u8]]) -> impl FnMut(&mut [u8]) -> std::io::Result<usize> {
+        let mut packets = packets
+            .iter()
+            .map(|packet| packet.to_vec())
+            .collect::<std::collections::VecDeque<_>>();
+
+        move |buffer| {
+            let packet = packets.pop_front().unwrap_or_default();
+            buffer[..packet.len()].copy_from_slice(&packet);
+            Ok(packet.len())
+        }
+    }
+
+    #[test]
+    fn response_split_into_packets() {
+        let mut response = [0; 6];
+        read_response(&mut response, fake_device(&[&[1, 2], &[3, 4, 5], &[6]])).unwrap();
+        assert_eq!(response, [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn short_response() {
+        let mut response = [0; 8];
+        let result = read_response(&mut response, fake_device(&[&[0x80, 0]]));
+        assert!(matches!(
+            result,
+            Err(StlinkError::NotEnoughBytesRead { is: 2, should: 8 })
+        ));
+    }
+
+    #[test]
+    fn spi_clock() {
+        assert_eq!(spi_prescaler(48_000, 24_000), (0, 24_000));
+        assert_eq!(spi_prescaler(48_000, 1_000), (5, 750));
+        assert_eq!(spi_prescaler(48_000, 1), (7, 187));
+    }
+
+    #[test]
+    fn i2c_timing_register() {
+        // 100 kHz from 48 MHz: 480 cycles, split evenly
+        assert_eq!(i2c_timing(48_000, 100).unwrap(), 0x0042_efef);
+        // 400 kHz from 48 MHz: 120 cycles, 80 low and 40 high
+        assert_eq!(i2c_timing(48_000, 400).unwrap(), 0x0042_274f);
+        // 100 kHz from 192 MHz needs a prescaler
+        assert_eq!(i2c_timing(192_000, 100).unwrap() >> 28, 3);
+
+        assert!(i2c_timing(48_000, 0).is_err());
+        assert!(i2c_timing(48_000, 2000).is_err());
+    }
+}
@@ -64,6 +64,19 @@ pub mod commands {
     // Parameters for SET_COM_FREQ and GET_COM_FREQ.
     pub const JTAG_STLINK_SWD_COM: u8 = 0x00;
     pub const JTAG_STLINK_JTAG_COM: u8 = 0x01;
+
+    // SWIM commands, used for STM8 targets.
+    pub const SWIM_ENTER: u8 = 0x00;
+    pub const SWIM_SPEED: u8 = 0x03;
+    pub const SWIM_ENTER_SEQ: u8 = 0x04;
+    pub const SWIM_GEN_RST: u8 = 0x05;
+    pub const SWIM_RESET: u8 = 0x06;
+    pub const SWIM_ASSERT_RESET: u8 = 0x07;
+    pub const SWIM_DEASSERT_RESET: u8 = 0x08;
+    pub const SWIM_READSTATUS: u8 = 0x09;
+    pub const SWIM_WRITEMEM: u8 = 0x0a;
+    pub const SWIM_READMEM: u8 = 0x0b;
+    pub const SWIM_READBUF: u8 = 0x0c;
 }
 
 /// STLink status codes and messages.
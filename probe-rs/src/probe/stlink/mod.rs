@@ -1,6 +1,8 @@
 //! ST-Link probe implementation.
 
+mod bridge;
 mod constants;
+mod swim;
 mod tools;
 mod usb_interface;
 
@@ -20,7 +22,7 @@ use crate::{
     },
     probe::{
         DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector, Probe, ProbeCreationError,
-        ProbeFactory, WireProtocol,
+        ProbeDetails, ProbeFactory, SwoCapabilities, WireProtocol,
     },
     Error as ProbeRsError, MemoryInterface,
 };
@@ -35,6 +37,9 @@ use std::{cmp::Ordering, sync::Arc, time::Duration};
 use constants::{commands, JTagFrequencyToDivider, Mode, Status, SwdFrequencyToDelayCount};
 use usb_interface::{StLinkUsb, StLinkUsbDevice, TIMEOUT};
 
+pub use bridge::{BridgeGpioMode, SpiMode, StLinkBridge, StLinkUart, BRIDGE_GPIO_COUNT};
+pub use swim::{StLinkSwim, Stm8Family, Stm8Registers};
+
 /// Maximum length of 32 bit reads in bytes.
 ///
 /// Length has been determined by experimenting with
@@ -61,19 +66,7 @@ impl std::fmt::Display for StLinkFactory {
 impl ProbeFactory for StLinkFactory {
     fn open(&self, selector: &DebugProbeSelector) -> Result<Box<dyn DebugProbe>, DebugProbeError> {
         let device = StLinkUsbDevice::new_from_selector(selector)?;
-        let mut stlink = StLink {
-            name: format!("ST-Link {}", &device.info.version_name),
-            device,
-            hw_version: 0,
-            jtag_version: 0,
-            protocol: WireProtocol::Swd,
-            swd_speed_khz: 1_800,
-            jtag_speed_khz: 1_120,
-            swo_enabled: false,
-            scan_chain: None,
-
-            opened_aps: vec![],
-        };
+        let mut stlink = StLink::new(device);
 
         stlink.init()?;
 
@@ -102,6 +95,24 @@ pub struct StLink<D: StLinkUsb> {
     opened_aps: Vec<u8>,
 }
 
+impl StLink<StLinkUsbDevice> {
+    fn new(device: StLinkUsbDevice) -> Self {
+        Self {
+            name: format!("ST-Link {}", &device.info.version_name),
+            device,
+            hw_version: 0,
+            jtag_version: 0,
+            protocol: WireProtocol::Swd,
+            swd_speed_khz: 1_800,
+            jtag_speed_khz: 1_120,
+            swo_enabled: false,
+            scan_chain: None,
+
+            opened_aps: vec![],
+        }
+    }
+}
+
 impl DebugProbe for StLink<StLinkUsbDevice> {
     fn get_name(&self) -> &str {
         &self.name
@@ -350,6 +361,33 @@ impl DebugProbe for StLink<StLinkUsbDevice> {
             })
             .map_err(|e| e.into())
     }
+
+    fn details(&mut self) -> ProbeDetails {
+        let max_speed_khz = if self.hw_version < 3 {
+            Some(match self.protocol {
+                WireProtocol::Swd => SwdFrequencyToDelayCount::Hz4600000.to_khz(),
+                WireProtocol::Jtag => JTagFrequencyToDivider::Hz18000000.to_khz(),
+            })
+        } else {
+            self.get_communication_frequencies(self.protocol)
+                .ok()
+                .and_then(|(speeds, _)| speeds.into_iter().max())
+        };
+
+        ProbeDetails {
+            firmware_version: Some(format!("V{}J{}", self.hw_version, self.jtag_version)),
+            protocols: vec![WireProtocol::Swd, WireProtocol::Jtag],
+            max_speed_khz,
+            swo: Some(SwoCapabilities {
+                uart: true,
+                ..Default::default()
+            }),
+            packet_size: None,
+            packet_count: None,
+            target_voltage: None,
+            adaptive_clocking: false,
+        }
+    }
 }
 
 impl<D: StLinkUsb> Drop for StLink<D> {
@@ -1303,6 +1341,15 @@ pub enum StlinkError {
         should: usize,
     },
 
+    /// Not enough bytes were read.
+    #[error("Not enough bytes read.")]
+    NotEnoughBytesRead {
+        /// The number of bytes actually read
+        is: usize,
+        /// The number of bytes that should have been read
+        should: usize,
+    },
+
     /// USB endpoint not found.
     #[error("Usb endpoint not found.")]
     EndpointNotFound,
@@ -1330,6 +1377,34 @@ pub enum StlinkError {
     /// USB error.
     #[error("USB")]
     Usb(Box<dyn std::error::Error + Sync + Send>),
+
+    /// The probe has no bridge interface.
+    #[error("The probe has no bridge interface. Only ST-LINK-V3 probes support the bridge.")]
+    BridgeNotSupported,
+
+    /// A bridge command failed.
+    #[error("Bridge command failed with status {0:#06x}")]
+    BridgeCommandFailed(u16),
+
+    /// The requested bridge clock speed is not supported.
+    #[error("Bridge clock speed of {0} kHz is not supported")]
+    BridgeUnsupportedSpeed(u32),
+
+    /// A SWIM command failed.
+    #[error("SWIM command failed with status {0:#04x}")]
+    SwimCommandFailed(u8),
+
+    /// The target did not finish a SWIM command in time.
+    #[error("Timeout while waiting for a SWIM command to finish")]
+    SwimTimeout,
+
+    /// The flash of an STM8 target could not be unlocked.
+    #[error("Failed to unlock the flash of the STM8 target")]
+    Stm8FlashUnlockFailed,
+
+    /// The flash of an STM8 target is write protected.
+    #[error("Failed to write to the flash of the STM8 target, the block is write protected")]
+    Stm8FlashWriteProtected,
 }
 
 impl From<nusb::Error> for StlinkError {
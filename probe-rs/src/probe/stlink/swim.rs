@@ -0,0 +1,433 @@
+//! SWIM access to STM8 targets.
+//!
+//! SWIM (single wire interface module) is the debug interface of the STM8. It gives access
+//! to the whole memory space of the target, which includes the debug module used to halt the
+//! CPU and to read its registers. The flash is programmed through the flash controller of
+//! the target, which is driven with plain memory accesses.
+//!
+//! STM8 targets are not part of the target database, so they cannot be used through a
+//! [`Session`](crate::Session). [`StLinkSwim`] offers the basic operations needed to flash
+//! and inspect them instead.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    constants::commands,
+    usb_interface::{StLinkUsb, StLinkUsbDevice, TIMEOUT},
+    StLink, StlinkError,
+};
+use crate::probe::{DebugProbeError, DebugProbeSelector};
+
+/// Largest number of bytes transferred with a single memory access.
+const MAX_TRANSFER_SIZE: usize = 1024;
+
+/// Number of data bytes which fit into a `SWIM_WRITEMEM` command.
+const INLINE_WRITE_SIZE: usize = 8;
+
+/// Time the target gets to finish a SWIM command.
+const SWIM_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Status returned by `SWIM_READSTATUS` when the last command has finished.
+const SWIM_OK: u8 = 0x00;
+/// Status returned by `SWIM_READSTATUS` while the last command is still running.
+const SWIM_BUSY: u8 = 0x01;
+
+/// The SWIM control and status register.
+const SWIM_CSR: u32 = 0x7f80;
+/// Masks the interrupts of the target while the CPU is stalled.
+const SWIM_CSR_SAFE_MASK: u8 = 0x80;
+/// Gives SWIM access to the debug module.
+const SWIM_CSR_SWIM_DM: u8 = 0x20;
+
+/// The CPU registers, as seen by the debug module.
+const DM_REGISTERS: u32 = 0x7f00;
+/// The second control and status register of the debug module.
+const DM_CSR2: u32 = 0x7f99;
+/// Stalls the CPU.
+const DM_CSR2_STALL: u8 = 0x08;
+
+/// Block programming mode in `FLASH_CR2`.
+const FLASH_CR2_PRG: u8 = 0x01;
+/// Write attempted to a protected page, in `FLASH_IAPSR`.
+const FLASH_IAPSR_WR_PG_DIS: u8 = 0x01;
+/// Program memory unlocked, in `FLASH_IAPSR`.
+const FLASH_IAPSR_PUL: u8 = 0x02;
+/// End of programming, in `FLASH_IAPSR`.
+const FLASH_IAPSR_EOP: u8 = 0x04;
+/// Keys to unlock the program memory, written to `FLASH_PUKR`.
+const FLASH_PUKR_KEYS: [u8; 2] = [0x56, 0xae];
+
+/// Time the flash controller gets to program a block.
+const FLASH_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The STM8 product lines, which differ in the location of the flash controller.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stm8Family {
+    /// STM8S and STM8AF devices.
+    Stm8s,
+    /// STM8L and STM8AL devices.
+    Stm8l,
+}
+
+/// The flash controller registers of an STM8 product line.
+struct FlashRegisters {
+    cr2: u32,
+    /// Complementary to `cr2`, only present on STM8S devices.
+    ncr2: Option<u32>,
+    iapsr: u32,
+    pukr: u32,
+}
+
+impl Stm8Family {
+    fn flash_registers(self) -> FlashRegisters {
+        match self {
+            Stm8Family::Stm8s => FlashRegisters {
+                cr2: 0x505b,
+                ncr2: Some(0x505c),
+                iapsr: 0x505f,
+                pukr: 0x5062,
+            },
+            Stm8Family::Stm8l => FlashRegisters {
+                cr2: 0x5051,
+                ncr2: None,
+                iapsr: 0x5054,
+                pukr: 0x5052,
+            },
+        }
+    }
+}
+
+/// The CPU registers of an STM8.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Stm8Registers {
+    /// The accumulator.
+    pub a: u8,
+    /// The 24 bit program counter.
+    pub pc: u32,
+    /// The X index register.
+    pub x: u16,
+    /// The Y index register.
+    pub y: u16,
+    /// The stack pointer.
+    pub sp: u16,
+    /// The condition code register.
+    pub cc: u8,
+}
+
+impl Stm8Registers {
+    fn from_bytes(bytes: [u8; 11]) -> Self {
+        Self {
+            a: bytes[0],
+            pc: u32::from_be_bytes([0, bytes[1], bytes[2], bytes[3]]),
+            x: u16::from_be_bytes([bytes[4], bytes[5]]),
+            y: u16::from_be_bytes([bytes[6], bytes[7]]),
+            sp: u16::from_be_bytes([bytes[8], bytes[9]]),
+            cc: bytes[10],
+        }
+    }
+}
+
+/// An ST-LINK which is connected to an STM8 target using SWIM.
+///
+/// See the [module level documentation](self) for details.
+#[derive(Debug)]
+pub struct StLinkSwim {
+    stlink: StLink<StLinkUsbDevice>,
+}
+
+impl StLinkSwim {
+    /// Opens the ST-LINK matching `selector`, and connects to the target with SWIM.
+    ///
+    /// The target is reset while connecting, and halted afterwards.
+    pub fn open(selector: &DebugProbeSelector) -> Result<Self, DebugProbeError> {
+        let device = StLinkUsbDevice::new_from_selector(selector)?;
+        let mut stlink = StLink::new(device);
+        stlink.init()?;
+
+        let mut swim = Self { stlink };
+        swim.connect()?;
+
+        Ok(swim)
+    }
+
+    /// Runs the SWIM entry sequence and activates the debug module.
+    fn connect(&mut self) -> Result<(), DebugProbeError> {
+        self.command(&[commands::SWIM_COMMAND, commands::SWIM_ENTER])?;
+        // Start with low speed, which is the default of the target after reset.
+        self.command(&[commands::SWIM_COMMAND, commands::SWIM_SPEED, 0])?;
+
+        self.command(&[commands::SWIM_COMMAND, commands::SWIM_ASSERT_RESET])?;
+        self.command(&[commands::SWIM_COMMAND, commands::SWIM_ENTER_SEQ])?;
+        self.write_memory(SWIM_CSR, &[SWIM_CSR_SAFE_MASK | SWIM_CSR_SWIM_DM])?;
+        self.command(&[commands::SWIM_COMMAND, commands::SWIM_DEASSERT_RESET])?;
+        thread::sleep(Duration::from_millis(1));
+
+        self.halt()
+    }
+
+    /// Sends a SWIM command and waits until it has finished.
+    fn command(&mut self, cmd: &[u8]) -> Result<(), StlinkError> {
+        self.stlink.device.write(cmd, &[], &mut [], TIMEOUT)?;
+        self.wait_ready()
+    }
+
+    /// Waits until the last SWIM command has finished.
+    fn wait_ready(&mut self) -> Result<(), StlinkError> {
+        let start = Instant::now();
+
+        loop {
+            let mut status = [0; 4];
+            self.stlink.device.write(
+                &[commands::SWIM_COMMAND, commands::SWIM_READSTATUS],
+                &[],
+                &mut status,
+                TIMEOUT,
+            )?;
+
+            match status[0] {
+                SWIM_OK => return Ok(()),
+                SWIM_BUSY if start.elapsed() < SWIM_TIMEOUT => {}
+                SWIM_BUSY => return Err(StlinkError::SwimTimeout),
+                status => return Err(StlinkError::SwimCommandFailed(status)),
+            }
+        }
+    }
+
+    /// Reads `data.len()` bytes, starting at `address`.
+    pub fn read_memory(&mut self, address: u32, data: &mut [u8]) -> Result<(), DebugProbeError> {
+        for (index, chunk) in data.chunks_mut(MAX_TRANSFER_SIZE).enumerate() {
+            let address = address + (index * MAX_TRANSFER_SIZE) as u32;
+
+            let mut cmd = vec![commands::SWIM_COMMAND, commands::SWIM_READMEM];
+            cmd.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            cmd.extend_from_slice(&address.to_be_bytes());
+            self.command(&cmd)?;
+
+            self.stlink.device.write(
+                &[commands::SWIM_COMMAND, commands::SWIM_READBUF],
+                &[],
+                chunk,
+                TIMEOUT,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `data`, starting at `address`.
+    pub fn write_memory(&mut self, address: u32, data: &[u8]) -> Result<(), DebugProbeError> {
+        for (index, chunk) in data.chunks(MAX_TRANSFER_SIZE).enumerate() {
+            let address = address + (index * MAX_TRANSFER_SIZE) as u32;
+
+            // The first bytes are sent as part of the command, the rest in the data phase.
+            let (inline, rest) = chunk.split_at(chunk.len().min(INLINE_WRITE_SIZE));
+
+            let mut cmd = vec![commands::SWIM_COMMAND, commands::SWIM_WRITEMEM];
+            cmd.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            cmd.extend_from_slice(&address.to_be_bytes());
+            cmd.extend_from_slice(inline);
+
+            self.stlink.device.write(&cmd, rest, &mut [], TIMEOUT)?;
+            self.wait_ready()?;
+        }
+
+        Ok(())
+    }
+
+    /// Halts the CPU.
+    pub fn halt(&mut self) -> Result<(), DebugProbeError> {
+        self.write_memory(DM_CSR2, &[DM_CSR2_STALL])
+    }
+
+    /// Resumes the CPU.
+    pub fn run(&mut self) -> Result<(), DebugProbeError> {
+        self.write_memory(DM_CSR2, &[0])
+    }
+
+    /// Returns `true` if the CPU is halted.
+    pub fn core_halted(&mut self) -> Result<bool, DebugProbeError> {
+        let mut csr2 = [0];
+        self.read_memory(DM_CSR2, &mut csr2)?;
+        Ok(csr2[0] & DM_CSR2_STALL != 0)
+    }
+
+    /// Reads the CPU registers. The CPU should be halted.
+    pub fn read_registers(&mut self) -> Result<Stm8Registers, DebugProbeError> {
+        let mut bytes = [0; 11];
+        self.read_memory(DM_REGISTERS, &mut bytes)?;
+        Ok(Stm8Registers::from_bytes(bytes))
+    }
+
+    /// Resets the target with the reset pin, and halts it afterwards if `halt` is set.
+    pub fn reset(&mut self, halt: bool) -> Result<(), DebugProbeError> {
+        self.command(&[commands::SWIM_COMMAND, commands::SWIM_ASSERT_RESET])?;
+        thread::sleep(Duration::from_millis(1));
+        self.command(&[commands::SWIM_COMMAND, commands::SWIM_DEASSERT_RESET])?;
+
+        // The target drops out of SWIM mode on reset, resynchronize with it.
+        self.command(&[commands::SWIM_COMMAND, commands::SWIM_GEN_RST])?;
+        self.command(&[commands::SWIM_COMMAND, commands::SWIM_RESET])?;
+        self.write_memory(SWIM_CSR, &[SWIM_CSR_SAFE_MASK | SWIM_CSR_SWIM_DM])?;
+
+        if halt {
+            self.halt()
+        } else {
+            self.run()
+        }
+    }
+
+    /// Programs `data` into the program memory, starting at `address`.
+    ///
+    /// The flash is written in blocks of `block_size` bytes, which is 64 or 128 bytes depending
+    /// on the device. Bytes of partially written blocks keep their previous value. The CPU
+    /// should be halted.
+    pub fn write_flash(
+        &mut self,
+        family: Stm8Family,
+        block_size: usize,
+        address: u32,
+        data: &[u8],
+    ) -> Result<(), DebugProbeError> {
+        let registers = family.flash_registers();
+
+        self.unlock_flash(&registers)?;
+        let result = self.write_flash_blocks(&registers, block_size, address, data);
+
+        // Lock the flash again, even if programming failed.
+        let lock_result = self.write_memory(registers.iapsr, &[0]);
+
+        result?;
+        lock_result
+    }
+
+    fn unlock_flash(&mut self, registers: &FlashRegisters) -> Result<(), DebugProbeError> {
+        for key in FLASH_PUKR_KEYS {
+            self.write_memory(registers.pukr, &[key])?;
+        }
+
+        let mut iapsr = [0];
+        self.read_memory(registers.iapsr, &mut iapsr)?;
+        if iapsr[0] & FLASH_IAPSR_PUL == 0 {
+            return Err(StlinkError::Stm8FlashUnlockFailed.into());
+        }
+
+        Ok(())
+    }
+
+    fn write_flash_blocks(
+        &mut self,
+        registers: &FlashRegisters,
+        block_size: usize,
+        address: u32,
+        data: &[u8],
+    ) -> Result<(), DebugProbeError> {
+        for (block_address, range) in flash_blocks(block_size, address, data.len()) {
+            let mut block = vec![0; block_size];
+            if range.len() != block_size {
+                self.read_memory(block_address, &mut block)?;
+            }
+            let offset = (address + range.start as u32 - block_address) as usize;
+            block[offset..][..range.len()].copy_from_slice(&data[range]);
+
+            tracing::debug!("Programming flash block at {:#06x}", block_address);
+
+            self.write_memory(registers.cr2, &[FLASH_CR2_PRG])?;
+            if let Some(ncr2) = registers.ncr2 {
+                self.write_memory(ncr2, &[!FLASH_CR2_PRG])?;
+            }
+            self.write_memory(block_address, &block)?;
+
+            self.wait_for_flash(registers)?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the flash controller has programmed a block.
+    fn wait_for_flash(&mut self, registers: &FlashRegisters) -> Result<(), DebugProbeError> {
+        let start = Instant::now();
+
+        loop {
+            let mut iapsr = [0];
+            self.read_memory(registers.iapsr, &mut iapsr)?;
+
+            if iapsr[0] & FLASH_IAPSR_WR_PG_DIS != 0 {
+                return Err(StlinkError::Stm8FlashWriteProtected.into());
+            }
+            if iapsr[0] & FLASH_IAPSR_EOP != 0 {
+                return Ok(());
+            }
+            if start.elapsed() > FLASH_TIMEOUT {
+                return Err(StlinkError::SwimTimeout.into());
+            }
+        }
+    }
+}
+
+impl Drop for StLinkSwim {
+    fn drop(&mut self) {
+        // Let the target run and leave SWIM mode, errors can't be handled here.
+        let _ = self.run();
+        let _ = self.stlink.enter_idle();
+    }
+}
+
+/// Splits `len` bytes starting at `address` into flash blocks.
+///
+/// Returns the start address of each block, and the range of the data which goes into it.
+fn flash_blocks(block_size: usize, address: u32, len: usize) -> Vec<(u32, std::ops::Range<usize>)> {
+    let mut blocks = vec![];
+    let mut offset = 0;
+
+    while offset < len {
+        let current = address + offset as u32;
+        let block_offset = (current % block_size as u32) as usize;
+        let end = usize::min(len, offset + block_size - block_offset);
+        let block_address = current - block_offset as u32;
+
+        blocks.push((block_address, offset..end));
+        offset = end;
+    }
+
+    blocks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registers() {
+        let bytes = [
+            0x12, 0x00, 0x80, 0x80, 0x34, 0x56, 0x78, 0x9a, 0x03, 0xff, 0x28,
+        ];
+        assert_eq!(
+            Stm8Registers::from_bytes(bytes),
+            Stm8Registers {
+                a: 0x12,
+                pc: 0x8080,
+                x: 0x3456,
+                y: 0x789a,
+                sp: 0x03ff,
+                cc: 0x28,
+            }
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(flash_blocks(64, 0x8000, 0), vec![]);
+        assert_eq!(flash_blocks(64, 0x8000, 64), vec![(0x8000, 0..64)]);
+        assert_eq!(
+            flash_blocks(64, 0x8010, 100),
+            vec![(0x8000, 0..48), (0x8040, 48..100)]
+        );
+        assert_eq!(
+            flash_blocks(128, 0x8000, 130),
+            vec![(0x8000, 0..128), (0x8080, 128..130)]
+        );
+    }
+}
@@ -34,6 +34,7 @@ pub(super) fn list_stlink_devices() -> Vec<DebugProbeInfo> {
                 &StLinkFactory,
                 None,
             )
+            .with_usb_port(&device)
         })
         .collect()
 }
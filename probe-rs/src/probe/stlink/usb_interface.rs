@@ -93,6 +93,10 @@ pub trait StLinkUsb: std::fmt::Debug {
 // Copy of `Selector::matches` except it uses the stlink-specific read_serial_number
 // to handle the broken stlink-v2 serial numbers that need hex-encoding.
 fn selector_matches(selector: &DebugProbeSelector, info: &DeviceInfo) -> bool {
+    if selector.usb_port.is_some() {
+        return selector.matches(info);
+    }
+
     let res = info.vendor_id() == selector.vendor_id
         && info.product_id() == selector.product_id
         && selector
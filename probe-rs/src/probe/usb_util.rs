@@ -1,7 +1,7 @@
 use async_io::{block_on, Timer};
 use futures_lite::FutureExt;
-use nusb::{transfer::RequestBuffer, Interface};
-use std::{io, time::Duration};
+use nusb::{transfer::RequestBuffer, DeviceInfo, Interface};
+use std::{io, path::PathBuf, time::Duration};
 
 pub trait InterfaceExt {
     fn read_bulk(&self, endpoint: u8, buf: &mut [u8], timeout: Duration) -> io::Result<usize>;
@@ -40,3 +40,21 @@ impl InterfaceExt for Interface {
         }))
     }
 }
+
+/// Finds the TTY device of a CDC ACM interface in sysfs.
+#[cfg(target_os = "linux")]
+pub(crate) fn serial_port_path(device: &DeviceInfo, interface_number: u8) -> Option<PathBuf> {
+    let sysfs_path = device.sysfs_path();
+    let device_name = sysfs_path.file_name()?.to_str()?;
+    let tty_dir = sysfs_path
+        .join(format!("{device_name}:1.{interface_number}"))
+        .join("tty");
+
+    let tty = std::fs::read_dir(tty_dir).ok()?.next()?.ok()?;
+    Some(PathBuf::from("/dev").join(tty.file_name()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn serial_port_path(_device: &DeviceInfo, _interface_number: u8) -> Option<PathBuf> {
+    None
+}
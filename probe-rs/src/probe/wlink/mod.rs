@@ -44,6 +44,9 @@ const REG_DMI_ADDRESS: u8 = 0x11;
 const DTMCS_DMIRESET_MASK: u32 = 1 << 16;
 const DTMCS_DMIHARDRESET_MASK: u32 = 1 << 17;
 
+/// Number of attempts to attach to the target for each chip type.
+const ATTACH_RETRIES: usize = 3;
+
 /// All WCH-Link probe variants, see-also: <http://www.wch-ic.com/products/WCH-Link.html>
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
@@ -70,6 +73,11 @@ impl fmt::Display for WchLinkVariant {
 }
 
 impl WchLinkVariant {
+    /// Whether the probe can debug chips with a single wire debug interface (SDI).
+    fn supports_sdi(&self) -> bool {
+        matches!(self, WchLinkVariant::ECh32v305 | WchLinkVariant::WCh32v208)
+    }
+
     fn try_from_u8(value: u8) -> Result<Self, WchLinkError> {
         match value {
             1 => Ok(Self::Ch549),
@@ -134,6 +142,12 @@ impl RiscvChip {
         }
     }
 
+    /// Whether the chip is debugged through the single wire debug interface (SDI) instead
+    /// of two wire debugging.
+    fn is_sdi(&self) -> bool {
+        matches!(self, RiscvChip::CH32V003 | RiscvChip::CH641)
+    }
+
     fn support_flash_protect(&self) -> bool {
         matches!(
             self,
@@ -258,6 +272,37 @@ impl WchLink {
         Ok(())
     }
 
+    /// Attaches to the target chip.
+    ///
+    /// The probe only talks to SDI chips like the CH32V003 once it has been configured for
+    /// them, so attaching is retried with the CH32V003 as chip type if that fails.
+    fn attach_chip(&mut self) -> Result<commands::AttachChipResponse, DebugProbeError> {
+        let mut chip_types = vec![self.chip_family];
+        if self.variant.supports_sdi() && !self.chip_family.is_sdi() {
+            chip_types.push(RiscvChip::CH32V003);
+        }
+
+        let mut last_error = None;
+        for chip_type in chip_types {
+            for _ in 0..ATTACH_RETRIES {
+                self.device
+                    .send_command(commands::SetSpeed(chip_type, self.speed))?;
+
+                match self.device.send_command(commands::AttachChip) {
+                    Ok(resp) => return Ok(resp),
+                    Err(e) => {
+                        tracing::debug!("Failed to attach as {:?}: {}", chip_type, e);
+                        last_error = Some(e);
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                }
+            }
+        }
+
+        // Unwrap: There is at least one attempt.
+        Err(last_error.unwrap())
+    }
+
     fn dmi_op_read(&mut self, addr: u8) -> Result<(u8, u32, u8), DebugProbeError> {
         let resp = self.device.send_command(commands::DmiOp::read(addr))?;
 
@@ -302,14 +347,24 @@ impl DebugProbe for WchLink {
         // second stage of wlink_init
         tracing::trace!("attach to target chip");
 
-        self.device
-            .send_command(commands::SetSpeed(self.chip_family, self.speed))?;
+        let resp = self.attach_chip()?;
 
-        let resp = self.device.send_command(commands::AttachChip)?;
+        if resp.chip_family.is_sdi() && !self.variant.supports_sdi() {
+            return Err(WchLinkError::SdiNotSupported(self.variant).into());
+        }
 
-        self.chip_family = resp.chip_family;
+        if resp.chip_family != self.chip_family {
+            // Configure the probe for the chip which was actually found.
+            self.chip_family = resp.chip_family;
+            self.device
+                .send_command(commands::SetSpeed(self.chip_family, self.speed))?;
+        }
 
-        tracing::info!("attached riscv chip {:?}", self.chip_family);
+        tracing::info!(
+            "attached riscv chip {:?}, chip id {:#010x}",
+            self.chip_family,
+            resp.chip_id
+        );
 
         self.chip_id = resp.chip_id;
 
@@ -516,14 +571,17 @@ impl JTAGAccess for WchLink {
 
 fn get_wlink_info(device: &DeviceInfo) -> Option<DebugProbeInfo> {
     if matches!(device.product_string(), Some("WCH-Link") | Some("WCH_Link")) {
-        Some(DebugProbeInfo::new(
-            "WCH-Link",
-            VENDOR_ID,
-            PRODUCT_ID,
-            device.serial_number().map(|s| s.to_string()),
-            &WchLinkFactory,
-            None,
-        ))
+        Some(
+            DebugProbeInfo::new(
+                "WCH-Link",
+                VENDOR_ID,
+                PRODUCT_ID,
+                device.serial_number().map(|s| s.to_string()),
+                &WchLinkFactory,
+                None,
+            )
+            .with_usb_port(device),
+        )
     } else {
         None
     }
@@ -564,6 +622,8 @@ pub(crate) enum WchLinkError {
     UnknownChip(u8),
     #[error("Unsupported operation.")]
     UnsupportedOperation,
+    #[error("{0} does not support single wire debugging (SDI), use a WCH-LinkE instead.")]
+    SdiNotSupported(WchLinkVariant),
 }
 
 impl From<WchLinkError> for DebugProbeError {
@@ -0,0 +1,128 @@
+//! Commands of the XDS110 firmware.
+//!
+//! Every packet starts with `*`, followed by the length of the payload as a little endian
+//! `u16`. The first byte of a command payload is the command ID, the payload of a response
+//! starts with the result code of the command as a little endian `i32`.
+
+use super::Xds110Error;
+
+/// Start of every packet.
+const PACKET_START: u8 = b'*';
+
+/// Size of the packet header.
+pub(crate) const HEADER_SIZE: usize = 3;
+
+pub(crate) const XDS_CONNECT: u8 = 0x01;
+pub(crate) const XDS_DISCONNECT: u8 = 0x02;
+pub(crate) const XDS_VERSION: u8 = 0x03;
+pub(crate) const XDS_SET_TCK: u8 = 0x04;
+pub(crate) const XDS_SET_SRST: u8 = 0x0e;
+pub(crate) const CMAPI_CONNECT: u8 = 0x0f;
+pub(crate) const CMAPI_DISCONNECT: u8 = 0x10;
+pub(crate) const CMAPI_ACQUIRE: u8 = 0x11;
+pub(crate) const CMAPI_RELEASE: u8 = 0x12;
+pub(crate) const CMAPI_REG_READ: u8 = 0x15;
+pub(crate) const CMAPI_REG_WRITE: u8 = 0x16;
+pub(crate) const SWD_CONNECT: u8 = 0x17;
+pub(crate) const SWD_DISCONNECT: u8 = 0x18;
+
+/// Register type of access port registers in CMAPI register accesses.
+pub(crate) const DAP_AP: u8 = 0;
+/// Register type of debug port registers in CMAPI register accesses.
+pub(crate) const DAP_DP: u8 = 1;
+
+/// The fastest TCK frequency, used with a delay count of 0.
+const MAX_TCK_KHZ: u32 = 2500;
+
+/// Wraps a command payload into a packet.
+pub(crate) fn encode_packet(payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(HEADER_SIZE + payload.len());
+    packet.push(PACKET_START);
+    packet.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Returns the total size of a packet, once its header was received.
+pub(crate) fn packet_size(header: &[u8]) -> Result<usize, Xds110Error> {
+    match header {
+        [PACKET_START, low, high, ..] => {
+            Ok(HEADER_SIZE + u16::from_le_bytes([*low, *high]) as usize)
+        }
+        _ => Err(Xds110Error::InvalidResponse),
+    }
+}
+
+/// Checks the result code of a response packet, and returns the data following it.
+pub(crate) fn decode_response(packet: &[u8]) -> Result<&[u8], Xds110Error> {
+    if packet.len() < HEADER_SIZE + 4 || packet_size(packet)? != packet.len() {
+        return Err(Xds110Error::InvalidResponse);
+    }
+
+    let (result, data) = packet[HEADER_SIZE..].split_at(4);
+    match i32::from_le_bytes(result.try_into().unwrap()) {
+        0 => Ok(data),
+        code => Err(Xds110Error::CommandFailed(code)),
+    }
+}
+
+/// Reads a little endian `u32` at `offset` of the response data.
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Result<u32, Xds110Error> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Xds110Error::InvalidResponse)
+}
+
+/// Reads a little endian `u16` at `offset` of the response data.
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Result<u16, Xds110Error> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Xds110Error::InvalidResponse)
+}
+
+/// Converts a speed into the TCK delay count of the firmware, rounding down the speed.
+pub(crate) fn speed_to_delay(speed_khz: u32) -> u32 {
+    MAX_TCK_KHZ.div_ceil(speed_khz.max(1)) - 1
+}
+
+/// Converts a TCK delay count into the resulting speed.
+pub(crate) fn delay_to_speed(delay: u32) -> u32 {
+    MAX_TCK_KHZ / (delay + 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_and_decode_packets() {
+        assert_eq!(
+            encode_packet(&[CMAPI_REG_READ, DAP_DP, 0, 0]),
+            [b'*', 4, 0, 0x15, 1, 0, 0]
+        );
+
+        let response = [b'*', 8, 0, 0, 0, 0, 0, 0x77, 0x14, 0xa0, 0x2b];
+        assert_eq!(packet_size(&response[..3]).unwrap(), response.len());
+        let data = decode_response(&response).unwrap();
+        assert_eq!(read_u32(data, 0).unwrap(), 0x2ba0_1477);
+        assert!(matches!(
+            read_u16(data, 4),
+            Err(Xds110Error::InvalidResponse)
+        ));
+
+        let failed = [b'*', 4, 0, 0xf6, 0xff, 0xff, 0xff];
+        assert!(matches!(
+            decode_response(&failed),
+            Err(Xds110Error::CommandFailed(-10))
+        ));
+    }
+
+    #[test]
+    fn tck_delay() {
+        assert_eq!(speed_to_delay(10_000), 0);
+        assert_eq!(speed_to_delay(2500), 0);
+        assert_eq!(speed_to_delay(1000), 2);
+        assert_eq!(delay_to_speed(speed_to_delay(1000)), 833);
+        assert_eq!(speed_to_delay(0), 2499);
+    }
+}
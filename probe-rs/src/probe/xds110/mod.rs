@@ -0,0 +1,406 @@
+//! Support for the TI XDS110 debug probe, which is built into the TI LaunchPads.
+//!
+//! Debug port accesses are done using the Cortex-M API (CMAPI) of the probe firmware, which
+//! handles the SWD or JTAG transfers on its own. The firmware tracks the selected access
+//! port and register bank itself, so writes to the DP SELECT register are not forwarded.
+//!
+//! Next to the debug interface, the probe offers two UARTs, which can be found using
+//! [`list_uarts`].
+
+mod commands;
+mod usb_interface;
+
+use std::{path::PathBuf, time::Duration};
+
+use nusb::DeviceInfo;
+use probe_rs_target::ScanChainElement;
+
+use self::usb_interface::Xds110UsbDevice;
+use crate::{
+    architecture::arm::{
+        communication_interface::{DapProbe, UninitializedArmProbe},
+        ArmCommunicationInterface, ArmError, Pins, PortType, RawDapAccess,
+    },
+    probe::{
+        usb_util, DebugProbe, DebugProbeError, DebugProbeInfo, DebugProbeSelector,
+        ProbeCreationError, ProbeFactory, WireProtocol,
+    },
+    CoreStatus,
+};
+
+const VENDOR_ID: u16 = 0x0451;
+const PRODUCT_ID: u16 = 0xbef3;
+
+/// Address of the DP SELECT register.
+const DP_SELECT: u8 = 0x8;
+
+/// USB class of the CDC communication interfaces of the UARTs.
+const CDC_COMMUNICATION_CLASS: u8 = 0x02;
+
+/// Factory for creating [`Xds110`] probes.
+#[derive(Debug)]
+pub struct Xds110Factory;
+
+impl std::fmt::Display for Xds110Factory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("XDS110")
+    }
+}
+
+impl ProbeFactory for Xds110Factory {
+    fn open(&self, selector: &DebugProbeSelector) -> Result<Box<dyn DebugProbe>, DebugProbeError> {
+        let device = Xds110UsbDevice::new_from_selector(selector)?;
+        let mut probe = Xds110 {
+            device,
+            name: "XDS110".into(),
+            protocol: WireProtocol::Swd,
+            speed_khz: 0,
+            select: 0,
+            connected: false,
+            scan_chain: None,
+        };
+
+        probe.init()?;
+
+        Ok(Box::new(probe))
+    }
+
+    fn list_probes(&self) -> Vec<DebugProbeInfo> {
+        list_xds110_devices()
+    }
+}
+
+/// A UART offered by an XDS110 probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xds110Uart {
+    /// The name of the UART, as reported by the probe.
+    pub name: String,
+    /// The serial port of the UART, if it could be determined.
+    pub path: Option<PathBuf>,
+}
+
+/// Lists the UARTs of the XDS110 probe described by `info`.
+pub fn list_uarts(info: &DebugProbeInfo) -> Vec<Xds110Uart> {
+    let Ok(devices) = nusb::list_devices() else {
+        return vec![];
+    };
+
+    let Some(device) = devices
+        .filter(is_xds110)
+        .find(|device| device.serial_number().map(|s| s.to_string()) == info.serial_number)
+    else {
+        return vec![];
+    };
+
+    device
+        .interfaces()
+        .filter(|interface| interface.class() == CDC_COMMUNICATION_CLASS)
+        .map(|interface| Xds110Uart {
+            name: interface
+                .interface_string()
+                .unwrap_or("UART")
+                .trim_start_matches("XDS110 Class ")
+                .to_string(),
+            path: usb_util::serial_port_path(&device, interface.interface_number()),
+        })
+        .collect()
+}
+
+/// A TI XDS110 debug probe.
+#[derive(Debug)]
+pub struct Xds110 {
+    device: Xds110UsbDevice,
+    name: String,
+    protocol: WireProtocol,
+    speed_khz: u32,
+
+    /// The last value written to the DP SELECT register.
+    select: u32,
+    connected: bool,
+    scan_chain: Option<Vec<ScanChainElement>>,
+}
+
+impl Xds110 {
+    fn init(&mut self) -> Result<(), DebugProbeError> {
+        let version = self.device.execute(&[commands::XDS_VERSION])?;
+        let firmware = commands::read_u32(&version, 0)?;
+        let hardware = commands::read_u16(&version, 4)?;
+
+        // The firmware version is encoded as BCD digits, e.g. 0x03000000 is 3.0.0.0.
+        let firmware = format!(
+            "{:x}.{:x}.{:x}.{:x}",
+            firmware >> 24,
+            (firmware >> 16) & 0xff,
+            (firmware >> 8) & 0xff,
+            firmware & 0xff
+        );
+        tracing::info!("XDS110 firmware {firmware}, hardware {hardware:#06x}");
+        self.name = format!("XDS110 (firmware {firmware})");
+
+        self.set_speed(1000)?;
+
+        Ok(())
+    }
+
+    fn connect(&mut self) -> Result<(), DebugProbeError> {
+        let connect = match self.protocol {
+            WireProtocol::Swd => commands::SWD_CONNECT,
+            WireProtocol::Jtag => commands::XDS_CONNECT,
+        };
+        self.device.execute(&[connect])?;
+
+        let response = self.device.execute(&[commands::CMAPI_CONNECT])?;
+        let idcode = commands::read_u32(&response, 0)?;
+        tracing::debug!("Connected to DP with IDCODE {idcode:#010x}");
+
+        self.device.execute(&[commands::CMAPI_ACQUIRE])?;
+        self.connected = true;
+        self.select = 0;
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self) -> Result<(), DebugProbeError> {
+        if !self.connected {
+            return Ok(());
+        }
+        self.connected = false;
+
+        self.device.execute(&[commands::CMAPI_RELEASE])?;
+        self.device.execute(&[commands::CMAPI_DISCONNECT])?;
+
+        let disconnect = match self.protocol {
+            WireProtocol::Swd => commands::SWD_DISCONNECT,
+            WireProtocol::Jtag => commands::XDS_DISCONNECT,
+        };
+        self.device.execute(&[disconnect])?;
+
+        Ok(())
+    }
+
+    fn set_srst(&mut self, asserted: bool) -> Result<(), DebugProbeError> {
+        // The command sets the level of the active low reset line.
+        self.device
+            .execute(&[commands::XDS_SET_SRST, u8::from(!asserted)])?;
+        Ok(())
+    }
+
+    /// Returns the register type, AP number and register address used by CMAPI.
+    fn cmapi_address(&self, port: PortType, addr: u8) -> [u8; 3] {
+        match port {
+            PortType::DebugPort => [commands::DAP_DP, 0, addr & 0xc],
+            PortType::AccessPort => [
+                commands::DAP_AP,
+                (self.select >> 24) as u8,
+                (self.select & 0xf0) as u8 | (addr & 0xc),
+            ],
+        }
+    }
+}
+
+impl DebugProbe for Xds110 {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn speed_khz(&self) -> u32 {
+        self.speed_khz
+    }
+
+    fn set_speed(&mut self, speed_khz: u32) -> Result<u32, DebugProbeError> {
+        let delay = commands::speed_to_delay(speed_khz);
+
+        let mut command = vec![commands::XDS_SET_TCK];
+        command.extend_from_slice(&delay.to_le_bytes());
+        self.device.execute(&command)?;
+
+        self.speed_khz = commands::delay_to_speed(delay);
+        Ok(self.speed_khz)
+    }
+
+    fn set_scan_chain(&mut self, scan_chain: Vec<ScanChainElement>) -> Result<(), DebugProbeError> {
+        self.scan_chain = Some(scan_chain);
+        Ok(())
+    }
+
+    fn scan_chain(&self) -> Result<&[ScanChainElement], DebugProbeError> {
+        Ok(self.scan_chain.as_deref().unwrap_or(&[]))
+    }
+
+    fn attach(&mut self) -> Result<(), DebugProbeError> {
+        tracing::debug!("Attaching with protocol '{}'", self.protocol);
+        self.connect()
+    }
+
+    fn detach(&mut self) -> Result<(), crate::Error> {
+        self.disconnect()?;
+        Ok(())
+    }
+
+    fn target_reset(&mut self) -> Result<(), DebugProbeError> {
+        self.set_srst(true)?;
+        std::thread::sleep(Duration::from_millis(10));
+        self.set_srst(false)
+    }
+
+    fn target_reset_assert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_srst(true)
+    }
+
+    fn target_reset_deassert(&mut self) -> Result<(), DebugProbeError> {
+        self.set_srst(false)
+    }
+
+    fn select_protocol(&mut self, protocol: WireProtocol) -> Result<(), DebugProbeError> {
+        if self.connected {
+            return Err(DebugProbeError::Attached);
+        }
+        self.protocol = protocol;
+        Ok(())
+    }
+
+    fn active_protocol(&self) -> Option<WireProtocol> {
+        Some(self.protocol)
+    }
+
+    fn has_arm_interface(&self) -> bool {
+        true
+    }
+
+    fn try_get_arm_interface<'probe>(
+        self: Box<Self>,
+    ) -> Result<Box<dyn UninitializedArmProbe + 'probe>, (Box<dyn DebugProbe>, DebugProbeError)>
+    {
+        Ok(Box::new(ArmCommunicationInterface::new(self, false)))
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn try_as_dap_probe(&mut self) -> Option<&mut dyn DapProbe> {
+        Some(self)
+    }
+}
+
+impl RawDapAccess for Xds110 {
+    fn raw_read_register(&mut self, port: PortType, addr: u8) -> Result<u32, ArmError> {
+        let mut command = vec![commands::CMAPI_REG_READ];
+        command.extend_from_slice(&self.cmapi_address(port, addr));
+
+        let response = self.device.execute(&command)?;
+        Ok(commands::read_u32(&response, 0).map_err(DebugProbeError::from)?)
+    }
+
+    fn raw_write_register(&mut self, port: PortType, addr: u8, value: u32) -> Result<(), ArmError> {
+        if port == PortType::DebugPort && addr & 0xc == DP_SELECT {
+            // The AP and bank are part of every CMAPI access instead.
+            self.select = value;
+            return Ok(());
+        }
+
+        let mut command = vec![commands::CMAPI_REG_WRITE];
+        command.extend_from_slice(&self.cmapi_address(port, addr));
+        command.extend_from_slice(&value.to_le_bytes());
+
+        self.device.execute(&command)?;
+        Ok(())
+    }
+
+    fn jtag_sequence(&mut self, cycles: u8, tms: bool, _tdi: u64) -> Result<(), DebugProbeError> {
+        // The firmware resets the TAP when connecting.
+        tracing::debug!("Ignoring JTAG sequence of {cycles} cycles with TMS {tms}");
+        Ok(())
+    }
+
+    fn swj_sequence(&mut self, bit_len: u8, bits: u64) -> Result<(), DebugProbeError> {
+        // The firmware sends the line reset and protocol switch sequences when connecting.
+        tracing::debug!("Ignoring SWJ sequence {bits:#x} of {bit_len} bits");
+        Ok(())
+    }
+
+    fn swj_pins(
+        &mut self,
+        pin_out: u32,
+        pin_select: u32,
+        pin_wait: u32,
+    ) -> Result<u32, DebugProbeError> {
+        let mut nreset = Pins(0);
+        nreset.set_nreset(true);
+        let nreset_mask = nreset.0 as u32;
+
+        // Only the reset pin can be controlled.
+        if pin_select != nreset_mask {
+            return Err(DebugProbeError::CommandNotSupportedByProbe {
+                command_name: "swj_pins",
+            });
+        }
+
+        self.set_srst(!Pins(pin_out as u8).nreset())?;
+        std::thread::sleep(Duration::from_micros(pin_wait as u64));
+
+        Ok(0xffff_ffff)
+    }
+
+    fn into_probe(self: Box<Self>) -> Box<dyn DebugProbe> {
+        self
+    }
+
+    fn core_status_notification(&mut self, _state: CoreStatus) -> Result<(), DebugProbeError> {
+        Ok(())
+    }
+}
+
+impl DapProbe for Xds110 {}
+
+fn is_xds110(device: &DeviceInfo) -> bool {
+    device.vendor_id() == VENDOR_ID && device.product_id() == PRODUCT_ID
+}
+
+#[tracing::instrument(skip_all)]
+fn list_xds110_devices() -> Vec<DebugProbeInfo> {
+    tracing::debug!("Searching for XDS110 probes");
+    let Ok(devices) = nusb::list_devices() else {
+        return vec![];
+    };
+
+    let probes: Vec<_> = devices
+        .filter(is_xds110)
+        .map(|device| {
+            DebugProbeInfo::new(
+                "XDS110",
+                VENDOR_ID,
+                PRODUCT_ID,
+                device.serial_number().map(|s| s.to_string()),
+                &Xds110Factory,
+                None,
+            )
+            .with_usb_port(&device)
+        })
+        .collect();
+
+    tracing::debug!("Found {} XDS110 probes total", probes.len());
+    probes
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum Xds110Error {
+    #[error("Usb endpoint not found.")]
+    EndpointNotFound,
+    #[error("Invalid response received from the probe.")]
+    InvalidResponse,
+    #[error("The probe reported error {0}.")]
+    CommandFailed(i32),
+}
+
+impl From<Xds110Error> for DebugProbeError {
+    fn from(e: Xds110Error) -> Self {
+        DebugProbeError::ProbeSpecific(Box::new(e))
+    }
+}
+
+impl From<Xds110Error> for ProbeCreationError {
+    fn from(e: Xds110Error) -> Self {
+        ProbeCreationError::ProbeSpecific(Box::new(e))
+    }
+}
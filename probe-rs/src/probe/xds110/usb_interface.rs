@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use nusb::{
+    transfer::{Direction, EndpointType},
+    Interface,
+};
+
+use crate::probe::{
+    usb_util::InterfaceExt, DebugProbeError, DebugProbeSelector, ProbeCreationError,
+};
+
+use super::{commands, is_xds110, Xds110Error};
+
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// USB class of the vendor specific debug interface.
+const VENDOR_SPECIFIC_CLASS: u8 = 0xff;
+
+pub struct Xds110UsbDevice {
+    interface: Interface,
+    endpoint_in: u8,
+    endpoint_out: u8,
+}
+
+impl std::fmt::Debug for Xds110UsbDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Xds110UsbDevice").finish_non_exhaustive()
+    }
+}
+
+impl Xds110UsbDevice {
+    pub fn new_from_selector(selector: &DebugProbeSelector) -> Result<Self, ProbeCreationError> {
+        let device = nusb::list_devices()
+            .map_err(ProbeCreationError::Usb)?
+            .filter(is_xds110)
+            .find(|device| selector.matches(device))
+            .ok_or(ProbeCreationError::NotFound)?;
+
+        let device_handle = device.open().map_err(ProbeCreationError::Usb)?;
+
+        // The debug interface is the only vendor specific interface, next to the UARTs.
+        let mut endpoints = None;
+        if let Some(config) = device_handle.configurations().next() {
+            for altsetting in config.interface_alt_settings() {
+                if altsetting.class() != VENDOR_SPECIFIC_CLASS {
+                    continue;
+                }
+
+                let bulk = |direction| {
+                    altsetting
+                        .endpoints()
+                        .find(|ep| {
+                            ep.transfer_type() == EndpointType::Bulk && ep.direction() == direction
+                        })
+                        .map(|ep| ep.address())
+                };
+
+                if let (Some(endpoint_in), Some(endpoint_out)) =
+                    (bulk(Direction::In), bulk(Direction::Out))
+                {
+                    endpoints = Some((altsetting.interface_number(), endpoint_in, endpoint_out));
+                    break;
+                }
+            }
+        }
+
+        let Some((interface_number, endpoint_in, endpoint_out)) = endpoints else {
+            return Err(Xds110Error::EndpointNotFound.into());
+        };
+
+        let interface = device_handle
+            .claim_interface(interface_number)
+            .map_err(ProbeCreationError::Usb)?;
+        tracing::trace!("Claimed interface {interface_number} of USB device.");
+
+        Ok(Self {
+            interface,
+            endpoint_in,
+            endpoint_out,
+        })
+    }
+
+    /// Executes a command, and returns the response data following the result code.
+    pub(crate) fn execute(&mut self, payload: &[u8]) -> Result<Vec<u8>, DebugProbeError> {
+        tracing::trace!("Sending command: {:02x?}", payload);
+
+        let packet = commands::encode_packet(payload);
+        self.interface
+            .write_bulk(self.endpoint_out, &packet, TIMEOUT)
+            .map_err(DebugProbeError::Usb)?;
+
+        let mut response = Vec::new();
+        let mut buf = [0; 512];
+        loop {
+            let read = self
+                .interface
+                .read_bulk(self.endpoint_in, &mut buf, TIMEOUT)
+                .map_err(DebugProbeError::Usb)?;
+            response.extend_from_slice(&buf[..read]);
+
+            if response.len() >= commands::HEADER_SIZE
+                && response.len() >= commands::packet_size(&response)?
+            {
+                break;
+            }
+        }
+        tracing::trace!("Received response: {:02x?}", response);
+
+        Ok(commands::decode_response(&response)?.to_vec())
+    }
+}
@@ -0,0 +1,303 @@
+//! Writing per-device configuration to provisioning regions.
+//!
+//! Provisioning regions, like the UICR of nRF chips or the OTP area of STM32 chips, are described
+//! in the target description (see [`ProvisioningRegion`]). Programmed bits in these regions can't
+//! be reset, or only by erasing the whole chip, so a mistake can brick a device. Writes are
+//! therefore planned first with [`plan`], which reads the current contents and checks whether
+//! the data can be written, and only then performed with [`provision`].
+//!
+//! ```no_run
+//! # fn example(session: &mut probe_rs::Session) -> Result<(), probe_rs::provisioning::ProvisioningError> {
+//! use probe_rs::provisioning::{self, ProvisioningState};
+//!
+//! let plan = provisioning::plan(session, 0x1000_1080, &[0x12, 0x34, 0x56, 0x78])?;
+//! if plan.state != ProvisioningState::AlreadyProgrammed {
+//!     provisioning::provision(session, &plan)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Range;
+
+use crate::{
+    config::{MemoryRange, MemoryRegion, NvmRegion, ProvisioningRegion},
+    flashing::{DownloadOptions, FlashError},
+    MemoryInterface, Session,
+};
+
+/// An error while provisioning a device.
+#[derive(Debug, thiserror::Error)]
+pub enum ProvisioningError {
+    /// The target has no provisioning region covering the given range.
+    #[error("The range {0:#010x?} is not inside of a provisioning region of the target.")]
+    NotInRegion(Range<u64>),
+    /// The write does not start or end at a multiple of the write size of the region.
+    #[error("The range {range:#010x?} is not aligned to the write size of {write_size} bytes of region {region}.")]
+    Unaligned {
+        /// The name of the region.
+        region: String,
+        /// The range which was to be written.
+        range: Range<u64>,
+        /// The write size of the region.
+        write_size: u32,
+    },
+    /// The region was already programmed with different data, which can't be overwritten.
+    #[error("Region {region} was already programmed with different data at {address:#010x}, which can't be changed without erasing it.")]
+    Conflict {
+        /// The name of the region.
+        region: String,
+        /// The address of the first conflicting byte.
+        address: u64,
+    },
+    /// None of the flash algorithms of the target can write the region.
+    #[error("Region {0} can't be written, because no flash algorithm of the target covers it.")]
+    NoFlashAlgorithm(String),
+    /// Reading back the data after writing returned different data.
+    #[error(
+        "Reading back region {region} after writing returned different data at {address:#010x}."
+    )]
+    ReadBackMismatch {
+        /// The name of the region.
+        region: String,
+        /// The address of the first differing byte.
+        address: u64,
+    },
+    /// Writing the region failed.
+    #[error("Failed to write the provisioning region.")]
+    Flash(#[from] FlashError),
+    /// Accessing the target failed.
+    #[error("Failed to access the target.")]
+    Probe(#[from] crate::Error),
+}
+
+/// The current contents of a range, compared to the data which should be written to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProvisioningState {
+    /// Nothing was programmed in the range yet.
+    Erased,
+    /// The range already contains the data.
+    AlreadyProgrammed,
+    /// Something was programmed in the range, but the data can be written over it.
+    Programmable,
+    /// The range contains different data, which can't be changed without erasing it.
+    Conflict,
+}
+
+/// A planned write to a provisioning region, created by [`plan`].
+#[derive(Debug, Clone)]
+pub struct ProvisioningPlan {
+    /// The region which is written.
+    pub region: ProvisioningRegion,
+    /// The address of the data.
+    pub address: u64,
+    /// The data to write.
+    pub data: Vec<u8>,
+    /// The current contents of the range.
+    pub current: Vec<u8>,
+    /// Whether the data can be written.
+    pub state: ProvisioningState,
+}
+
+impl ProvisioningPlan {
+    fn range(&self) -> Range<u64> {
+        self.address..self.address + self.data.len() as u64
+    }
+}
+
+/// Returns the provisioning region of the target containing `range`.
+pub fn find_region<'a>(
+    session: &'a Session,
+    range: &Range<u64>,
+) -> Result<&'a ProvisioningRegion, ProvisioningError> {
+    session
+        .target()
+        .provisioning_regions
+        .iter()
+        .find(|region| region.contains_range(range))
+        .ok_or_else(|| ProvisioningError::NotInRegion(range.clone()))
+}
+
+/// Plans writing `data` to `address`, by reading the current contents of the range.
+///
+/// Nothing is written to the target, so this can be used for a dry run.
+pub fn plan(
+    session: &mut Session,
+    address: u64,
+    data: &[u8],
+) -> Result<ProvisioningPlan, ProvisioningError> {
+    let range = address..address + data.len() as u64;
+    let region = find_region(session, &range)?.clone();
+
+    if !region.is_aligned(&range) {
+        return Err(ProvisioningError::Unaligned {
+            region: region.name,
+            range,
+            write_size: region.write_size,
+        });
+    }
+
+    let mut current = vec![0; data.len()];
+    session.core(0)?.read(address, &mut current)?;
+
+    let state = if current == data {
+        ProvisioningState::AlreadyProgrammed
+    } else if region.is_erased(&current) {
+        ProvisioningState::Erased
+    } else if region.can_program(&current, data) {
+        ProvisioningState::Programmable
+    } else {
+        ProvisioningState::Conflict
+    };
+
+    Ok(ProvisioningPlan {
+        region,
+        address,
+        data: data.to_vec(),
+        current,
+        state,
+    })
+}
+
+/// Performs a planned write, and reads the data back to check it.
+///
+/// The region is written with the flash algorithm of the target covering it, without erasing.
+/// Nothing is written if the data was already programmed.
+pub fn provision(session: &mut Session, plan: &ProvisioningPlan) -> Result<(), ProvisioningError> {
+    match plan.state {
+        ProvisioningState::AlreadyProgrammed => return Ok(()),
+        ProvisioningState::Conflict => {
+            let offset = conflict_offset(plan).unwrap_or_default();
+            return Err(ProvisioningError::Conflict {
+                region: plan.region.name.clone(),
+                address: plan.address + offset as u64,
+            });
+        }
+        ProvisioningState::Erased | ProvisioningState::Programmable => {}
+    }
+
+    let range = plan.range();
+    let target = session.target();
+    if !target.flash_algorithms.iter().any(|algorithm| {
+        algorithm
+            .flash_properties
+            .address_range
+            .contains_range(&range)
+    }) {
+        return Err(ProvisioningError::NoFlashAlgorithm(
+            plan.region.name.clone(),
+        ));
+    }
+
+    // OTP areas are often not part of the memory map, but the loader only writes NVM regions.
+    let mut memory_map = target.memory_map.clone();
+    let covered = memory_map
+        .iter()
+        .filter_map(MemoryRegion::as_nvm_region)
+        .any(|region| region.range.contains_range(&range));
+    if !covered {
+        memory_map.push(MemoryRegion::Nvm(NvmRegion {
+            name: Some(plan.region.name.clone()),
+            range: plan.region.range.clone(),
+            cores: vec![target.cores[0].name.clone()],
+            is_alias: false,
+            access: None,
+        }));
+    }
+
+    let mut loader = crate::flashing::FlashLoader::new(memory_map, target.source().clone());
+    loader.add_data(plan.address, &plan.data)?;
+
+    // Erasing would destroy the other contents, or is not possible at all.
+    let options = DownloadOptions {
+        skip_erase: true,
+        keep_unwritten_bytes: true,
+        ..Default::default()
+    };
+    loader.commit(session, options)?;
+
+    let mut written = vec![0; plan.data.len()];
+    session.core(0)?.read(plan.address, &mut written)?;
+    if let Some(offset) = written.iter().zip(&plan.data).position(|(a, b)| a != b) {
+        return Err(ProvisioningError::ReadBackMismatch {
+            region: plan.region.name.clone(),
+            address: plan.address + offset as u64,
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the offset of the first byte which can't be programmed.
+fn conflict_offset(plan: &ProvisioningPlan) -> Option<usize> {
+    plan.current
+        .iter()
+        .zip(&plan.data)
+        .position(|(&current, &data)| !plan.region.can_program(&[current], &[data]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::probe::fake_probe::FakeProbe;
+
+    #[test]
+    fn writes_are_checked_against_the_current_contents() {
+        let mut target = crate::config::get_target_by_name("nrf51822_xxAC").unwrap();
+        // The fake probe has no flash, so use a RAM area as provisioning region.
+        target.provisioning_regions = vec![ProvisioningRegion {
+            name: "TEST".to_string(),
+            range: 0x2000_0000..0x2000_0010,
+            one_time: true,
+            erased_byte_value: 0xFF,
+            write_size: 4,
+        }];
+        let mut session = FakeProbe::attach_mocked_core(target).unwrap();
+        session
+            .core(0)
+            .unwrap()
+            .write(0x2000_0000, &[0xFF; 16])
+            .unwrap();
+
+        let plan = plan(&mut session, 0x2000_0000, &[0x12, 0x34, 0x56, 0x78]).unwrap();
+        assert_eq!(plan.state, ProvisioningState::Erased);
+
+        session
+            .core(0)
+            .unwrap()
+            .write(0x2000_0000, &[0x12, 0x34, 0x56, 0x78])
+            .unwrap();
+        let plan = super::plan(&mut session, 0x2000_0000, &[0x12, 0x34, 0x56, 0x78]).unwrap();
+        assert_eq!(plan.state, ProvisioningState::AlreadyProgrammed);
+        assert!(provision(&mut session, &plan).is_ok());
+
+        let plan = super::plan(&mut session, 0x2000_0000, &[0xFF, 0x34, 0x56, 0x78]).unwrap();
+        assert_eq!(plan.state, ProvisioningState::Conflict);
+        assert!(matches!(
+            provision(&mut session, &plan),
+            Err(ProvisioningError::Conflict {
+                address: 0x2000_0000,
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            super::plan(&mut session, 0x2000_0002, &[0; 4]),
+            Err(ProvisioningError::Unaligned { .. })
+        ));
+        assert!(matches!(
+            super::plan(&mut session, 0x3000_0000, &[0; 4]),
+            Err(ProvisioningError::NotInRegion(_))
+        ));
+    }
+    #[test]
+    fn family_regions_are_shared_by_all_variants() {
+        let target = crate::config::get_target_by_name("STM32F401CBUx").unwrap();
+        let names: Vec<_> = target
+            .provisioning_regions
+            .iter()
+            .map(|region| region.name.as_str())
+            .collect();
+        assert_eq!(names, ["OTP", "OTP_LOCK"]);
+    }
+}
@@ -48,7 +48,11 @@ mod channel;
 pub use channel::*;
 
 use crate::Session;
-use crate::{config::MemoryRegion, Core, MemoryInterface};
+use crate::{
+    config::MemoryRegion,
+    events::{RttChannelDirection, SessionEvent},
+    Core, MemoryInterface,
+};
 use std::ops::Range;
 use std::thread;
 use std::time::Instant;
@@ -339,6 +343,32 @@ impl Rtt {
     ///
     /// `core` can be e.g. an owned `Core` or a shared `Rc<Core>`.
     pub fn attach_region(core: &mut Core, region: &ScanRegion) -> Result<Rtt, Error> {
+        let rtt = Self::find(core, region)?;
+
+        if let Some(events) = core.events() {
+            let up = rtt
+                .up_channels
+                .iter()
+                .map(|channel| (RttChannelDirection::Up, channel.number(), channel.name()));
+            let down = rtt
+                .down_channels
+                .iter()
+                .map(|channel| (RttChannelDirection::Down, channel.number(), channel.name()));
+
+            for (direction, number, name) in up.chain(down) {
+                events.emit(SessionEvent::RttChannelDiscovered {
+                    core: core.id(),
+                    direction,
+                    number,
+                    name: name.map(str::to_string),
+                });
+            }
+        }
+
+        Ok(rtt)
+    }
+
+    fn find(core: &mut Core, region: &ScanRegion) -> Result<Rtt, Error> {
         let ranges = match region.clone() {
             ScanRegion::Exact(addr) => {
                 tracing::debug!("Scanning at exact address: {:#010x}", addr);
@@ -1,3 +1,5 @@
+use crate::flashing::{DownloadOptions, FlashError};
+use crate::CoreInterface;
 use crate::{
     architecture::{
         arm::{
@@ -5,7 +7,7 @@ use crate::{
             component::{get_arm_components, TraceSink},
             memory::CoresightComponent,
             sequences::{ArmDebugSequence, DefaultArmSequence},
-            ArmError, DpAddress, SwoReader,
+            ArmError, DpAddress, Pins, SwoReader,
         },
         riscv::communication_interface::{
             RiscvCommunicationInterface, RiscvDebugInterfaceState, RiscvError,
@@ -14,17 +16,24 @@ use crate::{
             XtensaCommunicationInterface, XtensaDebugInterfaceState, XtensaError,
         },
     },
-    config::{CoreExt, DebugSequence, RegistryError, Target, TargetSelector},
+    config::{CoreExt, DebugSequence, MemoryRegion, RegistryError, Target, TargetSelector},
     core::{Architecture, CombinedCoreState},
+    events::{Events, SessionEvent, SubscriptionId},
+    metrics::{Metrics, MetricsSnapshot},
     probe::{
         fake_probe::FakeProbe, list::Lister, AttachMethod, DebugProbeError, Probe,
         ProbeCreationError,
     },
-    Core, CoreType, Error,
+    Core, CoreType, Error, HaltReason, MemoryInterface, ResetStrategy,
 };
+use breakpoints::assign_hardware_units;
+pub use breakpoints::BreakpointPlacement;
+use std::collections::BTreeMap;
 use std::ops::DerefMut;
 use std::{fmt, sync::Arc, time::Duration};
 
+mod breakpoints;
+
 /// The `Session` struct represents an active debug session.
 ///
 /// ## Creating a session
@@ -49,6 +58,12 @@ pub struct Session {
     interfaces: ArchitectureInterface,
     cores: Vec<CombinedCoreState>,
     configured_trace_sink: Option<TraceSink>,
+    metrics: Metrics,
+    events: Events,
+    /// The original contents of the flash where breakpoints were programmed.
+    flash_breakpoints: BTreeMap<u64, [u8; 2]>,
+    /// The breakpoints requested through [`Session::set_breakpoint`], by core and address.
+    breakpoints: BTreeMap<(usize, u64), BreakpointPlacement>,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -172,6 +187,7 @@ impl Session {
         permissions: Permissions,
         cores: Vec<CombinedCoreState>,
     ) -> Result<Self, Error> {
+        let metrics = probe.metrics().clone();
         let default_core = target.default_core();
 
         let default_memory_ap = default_core.memory_ap().ok_or_else(|| {
@@ -262,6 +278,10 @@ impl Session {
                 interfaces: ArchitectureInterface::Arm(interface),
                 cores,
                 configured_trace_sink: None,
+                metrics,
+                events: Events::default(),
+                flash_breakpoints: BTreeMap::new(),
+                breakpoints: BTreeMap::new(),
             };
 
             {
@@ -285,6 +305,10 @@ impl Session {
                 interfaces: ArchitectureInterface::Arm(interface),
                 cores,
                 configured_trace_sink: None,
+                metrics,
+                events: Events::default(),
+                flash_breakpoints: BTreeMap::new(),
+                breakpoints: BTreeMap::new(),
             })
         }
     }
@@ -361,6 +385,7 @@ impl Session {
             };
         }
 
+        let metrics = probe.metrics().clone();
         let interfaces = ArchitectureInterface::Jtag(probe, interfaces);
 
         let mut session = Session {
@@ -368,6 +393,10 @@ impl Session {
             interfaces,
             cores,
             configured_trace_sink: None,
+            metrics,
+            events: Events::default(),
+            flash_breakpoints: BTreeMap::new(),
+            breakpoints: BTreeMap::new(),
         };
 
         // Wait for the cores to be halted.
@@ -487,6 +516,10 @@ impl Session {
             .ok_or(Error::CoreNotFound(core_index))?;
 
         match self.interfaces.attach(&self.target, combined_state) {
+            Ok(mut core) => {
+                core.set_events(self.events.clone());
+                Ok(core)
+            }
             Err(Error::Xtensa(XtensaError::CoreDisabled)) => {
                 // If the core is disabled, we can't attach to it.
                 // We can't do anything about it, so we just translate
@@ -669,6 +702,347 @@ impl Session {
         Ok(())
     }
 
+    /// Reset the core `core_index` with the given strategy, and halt it afterwards if `halt` is set.
+    ///
+    /// Unlike [`Core::reset_with`], this supports [`ResetStrategy::Hardware`], which pulses the
+    /// nRESET pin of the probe. This resets the whole chip, not only the given core.
+    pub fn reset_with(
+        &mut self,
+        core_index: usize,
+        strategy: ResetStrategy,
+        halt: bool,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        if strategy != ResetStrategy::Hardware {
+            return self.core(core_index)?.reset_with(strategy, halt, timeout);
+        }
+
+        let DebugSequence::Arm(sequence) = self.target.debug_sequence.clone() else {
+            return Err(Error::NotImplemented(
+                "Hardware resets are not implemented for non-ARM targets.",
+            ));
+        };
+        let memory_ap = self
+            .target
+            .cores
+            .get(core_index)
+            .ok_or(Error::CoreNotFound(core_index))?
+            .memory_ap()
+            .ok_or_else(|| Error::Other(format!("Core {core_index} has no memory AP")))?;
+
+        if halt {
+            self.core(core_index)?.reset_catch_set()?;
+        }
+
+        {
+            let interface = self.get_arm_interface()?;
+
+            let mut n_reset = Pins(0);
+            n_reset.set_nreset(true);
+            interface.swj_pins(0, n_reset.0 as u32, 0)?;
+            std::thread::sleep(Duration::from_millis(10));
+
+            let mut memory = interface.memory_interface(&memory_ap)?;
+            sequence.reset_hardware_deassert(&mut *memory)?;
+        }
+
+        {
+            let mut core = self.core(core_index)?;
+            if halt {
+                core.wait_for_core_halted(timeout)?;
+                core.reset_catch_clear()?;
+            } else {
+                core.status()?;
+            }
+        }
+
+        self.events.reset_performed(core_index, halt);
+        if halt {
+            self.events.core_halted(core_index, HaltReason::Request);
+        }
+        Ok(())
+    }
+
+    /// Sets a hardware breakpoint at `address` on every core which can access it.
+    ///
+    /// Hardware breakpoints only stop the core they are set on, so code shared by several cores,
+    /// like the flash of the RP2040, needs a breakpoint on every core. If the breakpoint can't be
+    /// set on one of the cores, it is removed from the others again, so the cores stay consistent.
+    pub fn set_shared_hw_breakpoint(&mut self, address: u64) -> Result<(), Error> {
+        let cores = self.target.cores_accessing(address);
+
+        for (i, &core_index) in cores.iter().enumerate() {
+            let result = self
+                .core(core_index)
+                .and_then(|mut core| core.set_hw_breakpoint(address));
+            if let Err(error) = result {
+                for &core_index in &cores[..i] {
+                    if let Err(error) = self.core(core_index)?.clear_hw_breakpoint(address) {
+                        tracing::warn!(
+                            "Failed to remove breakpoint from core {core_index}: {error}"
+                        );
+                    }
+                }
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Clears the hardware breakpoint at `address` from every core which can access it, see
+    /// [`Session::set_shared_hw_breakpoint`].
+    pub fn clear_shared_hw_breakpoint(&mut self, address: u64) -> Result<(), Error> {
+        for core_index in self.target.cores_accessing(address) {
+            self.core(core_index)?.clear_hw_breakpoint(address)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets a breakpoint at `address` on a core, with whatever mechanism is available.
+    ///
+    /// Unlike [`Core::set_hw_breakpoint`], this doesn't fail when the hardware breakpoint units
+    /// are exhausted. The units go to the breakpoints closest to the program counter, which are
+    /// the most likely to be hit next. Breakpoints in flash which don't get a unit are programmed
+    /// into flash, see [`Session::set_flash_breakpoint`]. Others stay pending, until
+    /// [`Session::rebalance_breakpoints`] assigns a unit to them.
+    ///
+    /// Returns how the breakpoint was placed.
+    pub fn set_breakpoint(
+        &mut self,
+        core_index: usize,
+        address: u64,
+    ) -> Result<BreakpointPlacement, Error> {
+        self.breakpoints
+            .entry((core_index, address))
+            .or_insert(BreakpointPlacement::Pending);
+        self.rebalance_breakpoints(core_index)?;
+
+        Ok(self.breakpoints[&(core_index, address)])
+    }
+
+    /// Removes a breakpoint set with [`Session::set_breakpoint`].
+    pub fn clear_breakpoint(&mut self, core_index: usize, address: u64) -> Result<(), Error> {
+        let Some(placement) = self.breakpoints.remove(&(core_index, address)) else {
+            return Err(Error::Other(format!(
+                "No breakpoint found at address {address:#010x}"
+            )));
+        };
+
+        match placement {
+            BreakpointPlacement::Hardware => self.core(core_index)?.clear_hw_breakpoint(address)?,
+            BreakpointPlacement::Flash => {
+                // Flash breakpoints stop every core, so keep it if another core still uses it.
+                let shared = self.breakpoints.iter().any(|(&(_, other), &placement)| {
+                    other == address && placement == BreakpointPlacement::Flash
+                });
+                if !shared {
+                    self.clear_flash_breakpoint(address)
+                        .map_err(|error| Error::Other(error.to_string()))?;
+                }
+            }
+            BreakpointPlacement::Pending => {}
+        }
+
+        // The freed unit can go to a pending breakpoint.
+        self.rebalance_breakpoints(core_index)
+    }
+
+    /// Returns the breakpoints of a core which were set with [`Session::set_breakpoint`].
+    pub fn breakpoints(
+        &self,
+        core_index: usize,
+    ) -> impl Iterator<Item = (u64, BreakpointPlacement)> + '_ {
+        self.breakpoints
+            .range((core_index, 0)..=(core_index, u64::MAX))
+            .map(|(&(_, address), &placement)| (address, placement))
+    }
+
+    /// Assigns the hardware breakpoint units of a core to the breakpoints closest to its program
+    /// counter, see [`Session::set_breakpoint`].
+    ///
+    /// This should be called whenever the core halted, so pending breakpoints close to the new
+    /// program counter get a unit.
+    pub fn rebalance_breakpoints(&mut self, core_index: usize) -> Result<(), Error> {
+        let mut core = self.core(core_index)?;
+        let pc = if core.core_halted()? {
+            Some(core.read_core_reg::<u64>(core.program_counter())?)
+        } else {
+            None
+        };
+        let units = core.available_breakpoint_units()? as usize;
+        let in_use = core.hw_breakpoints()?;
+        drop(core);
+
+        let candidates: Vec<(u64, bool)> = self
+            .breakpoints(core_index)
+            .filter(|&(_, placement)| placement != BreakpointPlacement::Flash)
+            .map(|(address, placement)| (address, placement == BreakpointPlacement::Hardware))
+            .collect();
+
+        // Units used by breakpoints set directly on the core are not available.
+        let unmanaged = in_use
+            .iter()
+            .flatten()
+            .filter(|&&address| !candidates.contains(&(address, true)))
+            .count();
+        let hardware = assign_hardware_units(&candidates, pc, units.saturating_sub(unmanaged));
+
+        // Free the units first, so they can be assigned to other breakpoints.
+        for &(address, is_hardware) in &candidates {
+            if is_hardware && !hardware.contains(&address) {
+                self.core(core_index)?.clear_hw_breakpoint(address)?;
+                self.breakpoints
+                    .insert((core_index, address), BreakpointPlacement::Pending);
+            }
+        }
+
+        for &(address, is_hardware) in &candidates {
+            let placement = if hardware.contains(&address) {
+                if !is_hardware {
+                    self.core(core_index)?.set_hw_breakpoint(address)?;
+                }
+                BreakpointPlacement::Hardware
+            } else if self.is_in_flash(address) {
+                match self.set_flash_breakpoint(address) {
+                    Ok(()) => BreakpointPlacement::Flash,
+                    Err(error) => {
+                        tracing::warn!(
+                            "Failed to set a flash breakpoint at {address:#010x}: {error}"
+                        );
+                        BreakpointPlacement::Pending
+                    }
+                }
+            } else {
+                BreakpointPlacement::Pending
+            };
+            self.breakpoints.insert((core_index, address), placement);
+        }
+
+        Ok(())
+    }
+
+    fn is_in_flash(&self, address: u64) -> bool {
+        self.target
+            .memory_map
+            .iter()
+            .filter_map(MemoryRegion::as_nvm_region)
+            .any(|region| region.range.contains(&address))
+    }
+
+    /// Sets a breakpoint at `address` in flash, by programming a `BKPT` instruction into it.
+    ///
+    /// This needs no hardware breakpoint units, which makes it useful once they are exhausted,
+    /// but is slow, as the flash sector has to be erased and programmed again. The program
+    /// stays halted where it was, see [`DownloadOptions::preserve_state`]. The original
+    /// instruction is restored by [`Session::clear_flash_breakpoint`], or when the session is
+    /// dropped.
+    ///
+    /// Only Cortex-M cores are supported.
+    pub fn set_flash_breakpoint(&mut self, address: u64) -> Result<(), FlashError> {
+        let address = address & !1;
+        if self.flash_breakpoints.contains_key(&address) {
+            return Ok(());
+        }
+
+        let cores = self.target.cores_accessing(address);
+        let Some(&core_index) = cores.first() else {
+            return Err(FlashError::NoSuitableNvm {
+                range: address..address + 2,
+                description_source: self.target.source().clone(),
+            });
+        };
+        if !cores
+            .iter()
+            .all(|&core| self.target.cores[core].core_type.is_cortex_m())
+        {
+            return Err(FlashError::Core(Error::NotImplemented(
+                "Flash breakpoints on cores other than Cortex-M",
+            )));
+        }
+
+        let mut original = [0; 2];
+        self.core(core_index)
+            .and_then(|mut core| core.read(address, &mut original))
+            .map_err(FlashError::Core)?;
+
+        self.write_flash(address, &BKPT)?;
+        self.flash_breakpoints.insert(address, original);
+
+        Ok(())
+    }
+
+    /// Removes a breakpoint set with [`Session::set_flash_breakpoint`], by programming the
+    /// original instruction again.
+    pub fn clear_flash_breakpoint(&mut self, address: u64) -> Result<(), FlashError> {
+        let address = address & !1;
+        if let Some(original) = self.flash_breakpoints.get(&address).copied() {
+            self.write_flash(address, &original)?;
+            self.flash_breakpoints.remove(&address);
+        }
+
+        Ok(())
+    }
+
+    /// Removes all breakpoints set with [`Session::set_flash_breakpoint`].
+    pub fn clear_all_flash_breakpoints(&mut self) -> Result<(), FlashError> {
+        if self.flash_breakpoints.is_empty() {
+            return Ok(());
+        }
+
+        let mut loader = self.target.flash_loader();
+        for (&address, original) in &self.flash_breakpoints {
+            loader.add_data(address, original)?;
+        }
+        loader.commit(self, Self::flash_breakpoint_options())?;
+        self.flash_breakpoints.clear();
+
+        Ok(())
+    }
+
+    /// Returns the addresses of the breakpoints set with [`Session::set_flash_breakpoint`].
+    pub fn flash_breakpoints(&self) -> impl Iterator<Item = u64> + '_ {
+        self.flash_breakpoints.keys().copied()
+    }
+
+    /// Executes the original instruction at a flash breakpoint the core is halted at.
+    ///
+    /// Stepping or running the core would otherwise hit the breakpoint again, or skip the
+    /// instruction. The breakpoint is removed for the step, and programmed again afterwards.
+    /// Returns `false` without stepping if the core is not halted at a flash breakpoint.
+    pub fn step_over_flash_breakpoint(&mut self, core_index: usize) -> Result<bool, FlashError> {
+        let pc = self
+            .core(core_index)
+            .and_then(|mut core| core.read_core_reg::<u64>(core.program_counter()))
+            .map_err(FlashError::Core)?;
+        if !self.flash_breakpoints.contains_key(&pc) {
+            return Ok(false);
+        }
+
+        self.clear_flash_breakpoint(pc)?;
+        self.core(core_index)
+            .and_then(|mut core| core.step())
+            .map_err(FlashError::Core)?;
+        self.set_flash_breakpoint(pc)?;
+
+        Ok(true)
+    }
+
+    fn write_flash(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
+        let mut loader = self.target.flash_loader();
+        loader.add_data(address, data)?;
+        loader.commit(self, Self::flash_breakpoint_options())
+    }
+
+    fn flash_breakpoint_options() -> DownloadOptions {
+        DownloadOptions {
+            keep_unwritten_bytes: true,
+            preserve_state: true,
+            ..Default::default()
+        }
+    }
+
     /// Reads all the available ARM CoresightComponents of the currently attached target.
     ///
     /// This will recursively parse the Romtable of the attached target
@@ -687,6 +1061,52 @@ impl Session {
         &self.target
     }
 
+    /// Returns the transfer statistics collected since the probe was opened, or since the
+    /// last call to [`Session::reset_metrics`].
+    ///
+    /// This helps to find out why operations on a setup are slow, e.g. because of many WAIT
+    /// responses, or because the probe needs many round trips for each memory access.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Sets all transfer statistics back to zero.
+    pub fn reset_metrics(&self) {
+        self.metrics.reset();
+    }
+
+    /// The handle to the transfer statistics, to record into.
+    pub(crate) fn metrics_handle(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Calls `subscriber` for every event in this session, until it is removed with
+    /// [`Session::unsubscribe`].
+    ///
+    /// Subscribers are called on the thread which caused the event, while the session is
+    /// in use, so they should return quickly, e.g. by sending the event to a channel.
+    ///
+    /// Halts are reported when probe-rs halts a core, or when it notices that a core was halted,
+    /// e.g. by a breakpoint, while checking its status.
+    pub fn subscribe(
+        &self,
+        subscriber: impl Fn(&SessionEvent) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        self.events.subscribe(subscriber)
+    }
+
+    /// Removes a subscriber added with [`Session::subscribe`].
+    ///
+    /// Returns `false` if there was no such subscriber.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        self.events.unsubscribe(id)
+    }
+
+    /// The subscribers of this session, to report events to.
+    pub(crate) fn events_handle(&self) -> &Events {
+        &self.events
+    }
+
     /// Configure the target and probe for serial wire view (SWV) tracing.
     pub fn setup_tracing(
         &mut self,
@@ -792,6 +1212,10 @@ impl Drop for Session {
             tracing::warn!("Could not clear all hardware breakpoints: {:?}", err);
         }
 
+        if let Err(err) = self.clear_all_flash_breakpoints() {
+            tracing::warn!("Could not restore the flash at breakpoints: {:?}", err);
+        }
+
         // Call any necessary deconfiguration/shutdown hooks.
         if let Err(err) = { 0..self.cores.len() }.try_for_each(|core| match self.core(core) {
             Ok(mut core) => core.debug_core_stop(),
@@ -803,6 +1227,9 @@ impl Drop for Session {
     }
 }
 
+/// The `BKPT #0` Thumb instruction.
+const BKPT: [u8; 2] = [0x00, 0xBE];
+
 /// Determine the [Target] from a [TargetSelector].
 ///
 /// If the selector is [TargetSelector::Unspecified], the target will be looked up in the registry.
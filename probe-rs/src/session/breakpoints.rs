@@ -0,0 +1,96 @@
+//! Assignment of the hardware breakpoint units to the breakpoints requested through
+//! [`Session::set_breakpoint`](crate::Session::set_breakpoint).
+
+/// How a breakpoint requested through [`Session::set_breakpoint`](crate::Session::set_breakpoint)
+/// is currently implemented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointPlacement {
+    /// The breakpoint uses a hardware breakpoint unit of the core.
+    Hardware,
+    /// The breakpoint is a `BKPT` instruction programmed into flash, see
+    /// [`Session::set_flash_breakpoint`](crate::Session::set_flash_breakpoint).
+    Flash,
+    /// No hardware breakpoint unit is free, and the breakpoint can't be programmed into flash.
+    ///
+    /// The breakpoint is not hit, until a unit is assigned to it by
+    /// [`Session::rebalance_breakpoints`](crate::Session::rebalance_breakpoints).
+    Pending,
+}
+
+/// Returns the breakpoints which get one of `units` hardware breakpoint units.
+///
+/// `candidates` are the addresses of the breakpoints, and whether they currently have a unit.
+/// The breakpoints closest to the program counter `pc` are the most likely to be hit next, so
+/// they get the units. If the program counter is not known, e.g. because the core is running,
+/// breakpoints keep their units, so nothing has to be changed on the running core.
+pub(crate) fn assign_hardware_units(
+    candidates: &[(u64, bool)],
+    pc: Option<u64>,
+    units: usize,
+) -> Vec<u64> {
+    let mut candidates = candidates.to_vec();
+    match pc {
+        Some(pc) => candidates.sort_by_key(|&(address, _)| address.abs_diff(pc)),
+        None => candidates.sort_by_key(|&(_, is_hardware)| !is_hardware),
+    }
+
+    candidates
+        .into_iter()
+        .take(units)
+        .map(|(address, _)| address)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::probe::fake_probe::FakeProbe;
+
+    #[test]
+    fn units_go_to_breakpoints_close_to_pc() {
+        let candidates = [
+            (0x1000, true),
+            (0x2000, false),
+            (0x3000, true),
+            (0x8000, false),
+        ];
+
+        assert_eq!(
+            assign_hardware_units(&candidates, Some(0x7000), 2),
+            [0x8000, 0x3000]
+        );
+        assert_eq!(
+            assign_hardware_units(&candidates, None, 2),
+            [0x1000, 0x3000]
+        );
+        assert_eq!(
+            assign_hardware_units(&candidates, Some(0), 0),
+            [] as [u64; 0]
+        );
+    }
+
+    #[test]
+    fn breakpoints_beyond_the_units_are_pending() {
+        let mut session = FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap();
+        let units = session
+            .core(0)
+            .unwrap()
+            .available_breakpoint_units()
+            .unwrap() as u64;
+
+        // Breakpoints outside of the flash can't be programmed into it.
+        for i in 0..=units {
+            session.set_breakpoint(0, 0x1000_0000 + 2 * i).unwrap();
+        }
+        let pending = session
+            .breakpoints(0)
+            .filter(|&(_, placement)| placement == BreakpointPlacement::Pending)
+            .count();
+        assert_eq!(pending, 1);
+
+        session.clear_breakpoint(0, 0x1000_0000).unwrap();
+        assert!(session
+            .breakpoints(0)
+            .all(|(_, placement)| placement == BreakpointPlacement::Hardware));
+    }
+}
@@ -0,0 +1,286 @@
+//! A [`Session`] which can be used from multiple threads at the same time.
+//!
+//! A [`Core`] borrows its session mutably, so only one core can be used at a time, and tools
+//! which e.g. poll RTT in one thread while a debugger runs in another had to put the whole
+//! session behind a mutex and hold it for as long as they used a core. [`SharedSession`]
+//! instead hands out [`CoreHandle`]s, which lock the session only for a single operation.
+
+use std::{sync::Arc, time::Duration};
+
+use parking_lot::{Mutex, MutexGuard};
+
+use crate::{
+    core::RegisterValueResultExt, memory::MemoryInterface, Core, CoreInformation, CoreStatus,
+    Error, RegisterId, RegisterValue, Session,
+};
+
+/// A [`Session`] which can be shared between threads.
+///
+/// Cloning a `SharedSession` returns another handle to the same session.
+///
+/// ```no_run
+/// # fn example() -> anyhow::Result<()> {
+/// use probe_rs::{probe::list::Lister, MemoryInterface, Permissions, SharedSession};
+///
+/// let lister = Lister::new();
+/// let probe = lister.list_all()[0].open()?;
+/// let session = SharedSession::new(probe.attach("nrf51822_xxAC", Permissions::default())?);
+///
+/// let mut poller = session.core(0)?;
+/// let thread = std::thread::spawn(move || poller.read_word_32(0x2000_0000));
+///
+/// // The other thread can access memory in between these operations.
+/// let core = session.core(0)?;
+/// core.halt(std::time::Duration::from_millis(100))?;
+/// core.run()?;
+///
+/// thread.join().unwrap()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedSession {
+    session: Arc<Mutex<Session>>,
+}
+
+impl SharedSession {
+    /// Makes `session` shareable.
+    pub fn new(session: Session) -> Self {
+        Self {
+            session: Arc::new(Mutex::new(session)),
+        }
+    }
+
+    /// Locks the session, for operations which are not available on a [`CoreHandle`].
+    ///
+    /// Other threads block until the returned guard is dropped, so it should not be held longer
+    /// than necessary.
+    pub fn lock(&self) -> MutexGuard<'_, Session> {
+        self.session.lock()
+    }
+
+    /// Returns a handle to the core with index `core_index`.
+    pub fn core(&self, core_index: usize) -> Result<CoreHandle, Error> {
+        if core_index >= self.lock().list_cores().len() {
+            return Err(Error::CoreNotFound(core_index));
+        }
+
+        Ok(CoreHandle {
+            session: self.clone(),
+            core_index,
+        })
+    }
+
+    /// Returns the session, if this is the last handle to it.
+    ///
+    /// Otherwise, `self` is returned as the error.
+    pub fn into_inner(self) -> Result<Session, Self> {
+        Arc::try_unwrap(self.session)
+            .map(Mutex::into_inner)
+            .map_err(|session| Self { session })
+    }
+}
+
+impl From<Session> for SharedSession {
+    fn from(session: Session) -> Self {
+        Self::new(session)
+    }
+}
+
+/// A handle to a core of a [`SharedSession`].
+///
+/// Every method locks the session only while the operation is performed, so operations from
+/// different threads are interleaved. Use [`CoreHandle::with`] to perform several operations
+/// without being interrupted, e.g. to read registers while the core is known to be halted.
+#[derive(Debug, Clone)]
+pub struct CoreHandle {
+    session: SharedSession,
+    core_index: usize,
+}
+
+impl CoreHandle {
+    /// The index of the core.
+    pub fn core_index(&self) -> usize {
+        self.core_index
+    }
+
+    /// The session this core belongs to.
+    pub fn session(&self) -> &SharedSession {
+        &self.session
+    }
+
+    /// Locks the session, and runs `f` with the core.
+    pub fn with<T>(&self, f: impl FnOnce(&mut Core<'_>) -> Result<T, Error>) -> Result<T, Error> {
+        let mut session = self.session.lock();
+        let mut core = session.core(self.core_index)?;
+        f(&mut core)
+    }
+
+    /// Returns the current status of the core.
+    pub fn status(&self) -> Result<CoreStatus, Error> {
+        self.with(|core| core.status())
+    }
+
+    /// Halts the core, waiting up to `timeout` for it to halt.
+    pub fn halt(&self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.with(|core| core.halt(timeout))
+    }
+
+    /// Resumes the core.
+    pub fn run(&self) -> Result<(), Error> {
+        self.with(|core| core.run())
+    }
+
+    /// Executes a single instruction.
+    pub fn step(&self) -> Result<CoreInformation, Error> {
+        self.with(|core| core.step())
+    }
+
+    /// Resets the core, and lets it run.
+    pub fn reset(&self) -> Result<(), Error> {
+        self.with(|core| core.reset())
+    }
+
+    /// Resets the core, and halts it before the first instruction.
+    pub fn reset_and_halt(&self, timeout: Duration) -> Result<CoreInformation, Error> {
+        self.with(|core| core.reset_and_halt(timeout))
+    }
+
+    /// Reads the core register `address`. The core has to be halted.
+    pub fn read_core_reg<T>(&self, address: impl Into<RegisterId>) -> Result<T, Error>
+    where
+        RegisterValue: TryInto<T>,
+        Result<T, <RegisterValue as TryInto<T>>::Error>: RegisterValueResultExt<T>,
+    {
+        let address = address.into();
+        self.with(|core| core.read_core_reg(address))
+    }
+
+    /// Writes `value` to the core register `address`. The core has to be halted.
+    pub fn write_core_reg(
+        &self,
+        address: impl Into<RegisterId>,
+        value: impl Into<RegisterValue>,
+    ) -> Result<(), Error> {
+        let address = address.into();
+        let value = value.into();
+        self.with(|core| core.write_core_reg(address, value))
+    }
+}
+
+impl MemoryInterface for CoreHandle {
+    fn supports_native_64bit_access(&mut self) -> bool {
+        self.with(|core| Ok(core.supports_native_64bit_access()))
+            .unwrap_or(false)
+    }
+
+    fn read_word_64(&mut self, address: u64) -> Result<u64, Error> {
+        self.with(|core| core.read_word_64(address))
+    }
+
+    fn read_word_32(&mut self, address: u64) -> Result<u32, Error> {
+        self.with(|core| core.read_word_32(address))
+    }
+
+    fn read_word_16(&mut self, address: u64) -> Result<u16, Error> {
+        self.with(|core| core.read_word_16(address))
+    }
+
+    fn read_word_8(&mut self, address: u64) -> Result<u8, Error> {
+        self.with(|core| core.read_word_8(address))
+    }
+
+    fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), Error> {
+        self.with(|core| core.read_64(address, data))
+    }
+
+    fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), Error> {
+        self.with(|core| core.read_32(address, data))
+    }
+
+    fn read_16(&mut self, address: u64, data: &mut [u16]) -> Result<(), Error> {
+        self.with(|core| core.read_16(address, data))
+    }
+
+    fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+        self.with(|core| core.read_8(address, data))
+    }
+
+    fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), Error> {
+        self.with(|core| core.read(address, data))
+    }
+
+    fn write_word_64(&mut self, address: u64, data: u64) -> Result<(), Error> {
+        self.with(|core| core.write_word_64(address, data))
+    }
+
+    fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), Error> {
+        self.with(|core| core.write_word_32(address, data))
+    }
+
+    fn write_word_16(&mut self, address: u64, data: u16) -> Result<(), Error> {
+        self.with(|core| core.write_word_16(address, data))
+    }
+
+    fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), Error> {
+        self.with(|core| core.write_word_8(address, data))
+    }
+
+    fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), Error> {
+        self.with(|core| core.write_64(address, data))
+    }
+
+    fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), Error> {
+        self.with(|core| core.write_32(address, data))
+    }
+
+    fn write_16(&mut self, address: u64, data: &[u16]) -> Result<(), Error> {
+        self.with(|core| core.write_16(address, data))
+    }
+
+    fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+        self.with(|core| core.write_8(address, data))
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), Error> {
+        self.with(|core| core.write(address, data))
+    }
+
+    fn supports_8bit_transfers(&self) -> Result<bool, Error> {
+        self.with(|core| core.supports_8bit_transfers())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.with(|core| core.flush())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::probe::fake_probe::FakeProbe;
+
+    #[test]
+    fn cores_are_usable_from_multiple_threads() {
+        let session = SharedSession::new(FakeProbe::attach_mocked_core("nrf51822_xxAC").unwrap());
+        assert!(matches!(session.core(1), Err(Error::CoreNotFound(1))));
+
+        let mut memory = session.core(0).unwrap();
+        let writer = std::thread::spawn(move || {
+            for value in 0..16 {
+                memory.write_word_32(0x2000_0000, value)?;
+            }
+            memory.read_word_32(0x2000_0000)
+        });
+
+        let core = session.core(0).unwrap();
+        for _ in 0..16 {
+            core.halt(Duration::from_millis(100)).unwrap();
+            core.run().unwrap();
+        }
+
+        assert_eq!(writer.join().unwrap().unwrap(), 15);
+        drop(core);
+        assert!(session.into_inner().is_ok());
+    }
+}
@@ -126,3 +126,35 @@ fn regression_pr2324() {
 
     assert_eq!(addr.address, 0x2e4);
 }
+
+#[test]
+fn goto_location_in_current_function() {
+    let di = DebugInfo::from_file("tests/probe-rs-debugger-test").unwrap();
+
+    let path = UnixPathBuf::from("/Users/jacknoppe/dev/probe-rs-debugger-test/src/main.rs")
+        .to_typed_path_buf();
+    let (program_counter, ..) = TEST_DATA[0];
+
+    // All the test locations are statements in `main`.
+    for (addr, line, _) in TEST_DATA.iter() {
+        assert_eq!(
+            di.get_goto_location(program_counter, &path, *line, None)
+                .unwrap()
+                .address,
+            *addr
+        );
+    }
+
+    let reset = di.function_address("Reset").unwrap() & !1;
+    assert!(di
+        .validate_goto_target(program_counter, reset)
+        .unwrap_err()
+        .to_string()
+        .contains("not in the current function"));
+
+    assert!(di
+        .validate_goto_target(0x7000_0000, program_counter)
+        .unwrap_err()
+        .to_string()
+        .contains("Cannot determine the function"));
+}
@@ -0,0 +1,13 @@
+static volatile int counter;
+
+static int add(int a, int b)
+{
+    int sum = a + b;
+    return sum;
+}
+
+int main(void)
+{
+    counter = add(counter, 2);
+    return counter;
+}
@@ -1,2 +1,3 @@
+pub mod algorithm_debug;
 pub mod elf;
 pub mod test;
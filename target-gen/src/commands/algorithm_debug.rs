@@ -0,0 +1,211 @@
+//! Prints the calls to a flash algorithm, to debug it while it runs on the target.
+//!
+//! The registers are resolved to the functions of the flash algorithm ELF, and the RAM of the
+//! algorithm is compared before and after each call, to show what the algorithm changed.
+
+use std::cell::RefCell;
+use std::io::BufRead;
+
+use anyhow::Result;
+use colored::Colorize;
+use probe_rs::flashing::AlgorithmState;
+
+/// The number of bytes per line of the RAM dumps.
+const LINE_LEN: usize = 16;
+
+/// A function of the flash algorithm.
+struct Function {
+    name: String,
+    address: u64,
+    size: u64,
+}
+
+/// Prints the [`probe_rs::flashing::ProgressEvent::AlgorithmCalled`] and
+/// [`probe_rs::flashing::ProgressEvent::AlgorithmReturned`] events.
+pub struct AlgorithmDebugger {
+    functions: Vec<Function>,
+    /// Whether to wait for the user before each call.
+    step: bool,
+    /// The difference between the addresses in RAM and in the ELF, once it is known.
+    load_offset: RefCell<Option<u64>>,
+    /// The state of the core at the start of the current call.
+    called: RefCell<Option<AlgorithmState>>,
+}
+
+impl AlgorithmDebugger {
+    /// Reads the function symbols of the flash algorithm ELF.
+    pub fn new(elf: &[u8], step: bool) -> Result<Self> {
+        let elf = goblin::elf::Elf::parse(elf)?;
+        let functions = elf
+            .syms
+            .iter()
+            .filter(|sym| sym.is_function() && sym.st_value != 0)
+            .filter_map(|sym| {
+                Some(Function {
+                    name: elf.strtab.get_at(sym.st_name)?.to_string(),
+                    // The lowest bit of Thumb function symbols is set, but it's not part of the
+                    // address.
+                    address: sym.st_value & !1,
+                    size: sym.st_size,
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            functions,
+            step,
+            load_offset: RefCell::new(None),
+            called: RefCell::new(None),
+        })
+    }
+
+    /// Prints the arguments of a call, and waits for the user if stepping.
+    pub fn called(&self, routine: &str, entry_point: u64, state: &AlgorithmState) {
+        if self.load_offset.borrow().is_none() {
+            let offset = self
+                .functions
+                .iter()
+                .find(|function| function.name == routine)
+                .map(|function| (entry_point & !1).wrapping_sub(function.address));
+            *self.load_offset.borrow_mut() = offset;
+        }
+
+        println!("{}: {routine} at {entry_point:#010x}", "Call".cyan().bold());
+        self.print_registers(state);
+        *self.called.borrow_mut() = Some(state.clone());
+
+        if self.step {
+            println!("Press Enter to run {routine}...");
+            let mut line = String::new();
+            if let Err(error) = std::io::stdin().lock().read_line(&mut line) {
+                println!("Failed to read from stdin: {error}");
+            }
+        }
+    }
+
+    /// Prints the result of a call, and the RAM it changed.
+    pub fn returned(&self, routine: &str, result: Option<u32>, state: &AlgorithmState) {
+        let result = match result {
+            Some(0) => "returned 0".green(),
+            Some(error_code) => format!("failed with error code {error_code:#x}").red(),
+            None => "did not return, halted".red(),
+        };
+        println!("{}: {routine} {result}", "Return".cyan().bold());
+        self.print_registers(state);
+
+        let Some(called) = self.called.borrow_mut().take() else {
+            return;
+        };
+        for ((address, before), (_, after)) in called.memory.iter().zip(&state.memory) {
+            for line in changed_lines(*address, before, after) {
+                println!("  {line}");
+            }
+        }
+    }
+
+    fn print_registers(&self, state: &AlgorithmState) {
+        for registers in state.registers.chunks(4) {
+            let line = registers
+                .iter()
+                .map(|(name, value)| format!("{name:>4} {value}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            println!("  {line}");
+        }
+
+        // Show which registers point into the algorithm, like the program counter and the
+        // return address.
+        for (name, value) in &state.registers {
+            let Ok(address) = TryInto::<u64>::try_into(*value) else {
+                continue;
+            };
+            if let Some(location) = self.symbolize(address) {
+                println!("  {name:>4} is in {location}");
+            }
+        }
+    }
+
+    /// Returns the function of the algorithm at `address` in RAM, with the offset into it.
+    fn symbolize(&self, address: u64) -> Option<String> {
+        let offset = (*self.load_offset.borrow())?;
+        let address = (address & !1).wrapping_sub(offset);
+        let function = self.functions.iter().find(|function| {
+            function.address <= address && address < function.address + function.size.max(1)
+        })?;
+
+        Some(format!(
+            "{}+{:#x}",
+            function.name,
+            address - function.address
+        ))
+    }
+}
+
+/// Returns the hex dump of the lines of the RAM at `address` which differ between `before` and
+/// `after`.
+fn changed_lines(address: u64, before: &[u8], after: &[u8]) -> Vec<String> {
+    before
+        .chunks(LINE_LEN)
+        .zip(after.chunks(LINE_LEN))
+        .enumerate()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(i, (_, after))| {
+            let bytes = after
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("{:#010x}: {bytes}", address + (i * LINE_LEN) as u64)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_changed_lines_are_dumped() {
+        let before = [0; 40];
+        let mut after = before;
+        after[17] = 0xAB;
+
+        assert_eq!(
+            changed_lines(0x2000_0000, &before, &after),
+            vec!["0x20000010: 00 ab 00 00 00 00 00 00 00 00 00 00 00 00 00 00"]
+        );
+    }
+
+    #[test]
+    fn addresses_are_resolved_relative_to_the_entry_point() {
+        let debugger = AlgorithmDebugger {
+            functions: vec![
+                Function {
+                    name: "Init".to_string(),
+                    address: 0x100,
+                    size: 0x20,
+                },
+                Function {
+                    name: "wait_ready".to_string(),
+                    address: 0x120,
+                    size: 0x10,
+                },
+            ],
+            step: false,
+            load_offset: RefCell::new(None),
+            called: RefCell::new(None),
+        };
+        let state = AlgorithmState {
+            registers: vec![],
+            memory: vec![],
+        };
+
+        debugger.called("Init", 0x2000_0101, &state);
+
+        assert_eq!(
+            debugger.symbolize(0x2000_0127).as_deref(),
+            Some("wait_ready+0x6")
+        );
+        assert_eq!(debugger.symbolize(0x2000_0200), None);
+    }
+}
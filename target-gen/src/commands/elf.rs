@@ -77,6 +77,7 @@ pub fn cmd_elf(
             generated_from_pack: false,
             chip_detection: vec![],
             pack_file_release: None,
+            provisioning_regions: vec![],
             variants: vec![Chip {
                 cores: vec![Core {
                     name: "main".to_owned(),
@@ -114,6 +115,8 @@ pub fn cmd_elf(
                 rtt_scan_ranges: None,
                 jtag: None,
                 default_binary_format: None,
+                provisioning_regions: vec![],
+                memory_init_sequences: vec![],
             }],
             flash_algorithms: vec![algorithm],
             source: TargetDescriptionSource::BuiltIn,
@@ -334,6 +337,7 @@ mod test {
             generated_from_pack: false,
             chip_detection: vec![],
             pack_file_release: None,
+            provisioning_regions: vec![],
             variants: vec![chip],
             flash_algorithms: vec![],
             source: TargetDescriptionSource::BuiltIn,
@@ -15,8 +15,13 @@ use probe_rs::{
 use probe_rs_target::RawFlashAlgorithm;
 use xshell::{cmd, Shell};
 
+use crate::commands::algorithm_debug::AlgorithmDebugger;
 use crate::commands::elf::cmd_elf;
 
+/// Tests the flash algorithm in `target_artifact` on the attached target.
+///
+/// If `debug_algorithm` is `Some`, the calls to the algorithm are printed, and if it is
+/// `Some(true)`, the user steps through them.
 pub fn cmd_test(
     target_artifact: &Path,
     template_path: &Path,
@@ -24,6 +29,7 @@ pub fn cmd_test(
     test_start_sector_address: Option<u64>,
     chip: Option<String>,
     name: Option<String>,
+    debug_algorithm: Option<bool>,
 ) -> Result<()> {
     ensure_is_file(target_artifact)?;
     ensure_is_file(template_path)?;
@@ -85,6 +91,14 @@ pub fn cmd_test(
     let mut session =
         probe_rs::Session::auto_attach(target_name, Permissions::new().allow_erase_all())?;
 
+    let debugger = match debug_algorithm {
+        Some(step) => Some(AlgorithmDebugger::new(
+            &std::fs::read(target_artifact)?,
+            step,
+        )?),
+        None => None,
+    };
+
     // Register callback to update the progress.
     let t = Rc::new(RefCell::new(Instant::now()));
     let progress = FlashProgress::new(move |event| match event {
@@ -116,8 +130,30 @@ pub fn cmd_test(
                 println!("{prefix}: {message}");
             }
         }
+        ProgressEvent::AlgorithmCalled {
+            routine,
+            entry_point,
+            state,
+        } => {
+            if let Some(debugger) = &debugger {
+                debugger.called(routine, entry_point, &state);
+            }
+        }
+        ProgressEvent::AlgorithmReturned {
+            routine,
+            result,
+            state,
+        } => {
+            if let Some(debugger) = &debugger {
+                debugger.returned(routine, result, &state);
+            }
+        }
         _ => (),
     });
+    let progress = match debug_algorithm {
+        Some(_) => progress.with_algorithm_debugging(),
+        None => progress,
+    };
 
     let flash_algorithm = if let Some(test_start_sector_address) = test_start_sector_address {
         let predicate = |x: &&RawFlashAlgorithm| {
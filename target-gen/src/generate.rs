@@ -106,6 +106,7 @@ where
                 generated_from_pack: true,
                 chip_detection: vec![],
                 pack_file_release: Some(pdsc.releases.latest_release().version.clone()),
+                provisioning_regions: vec![],
                 variants: Vec::new(),
                 flash_algorithms: Vec::new(),
                 source: TargetDescriptionSource::BuiltIn,
@@ -171,6 +172,8 @@ where
             rtt_scan_ranges: None,
             jtag: None, // TODO, parse scan chain from sdf
             default_binary_format: None,
+            provisioning_regions: vec![],
+            memory_init_sequences: vec![],
         });
     }
 
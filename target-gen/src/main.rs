@@ -104,6 +104,13 @@ enum TargetGen {
         /// Name of the flash algorithm to test
         #[clap(long = "name", short = 'n')]
         name: Option<String>,
+        /// Print the registers and the RAM of the flash algorithm before and after each call,
+        /// with the error codes it returns.
+        #[clap(long)]
+        debug_algorithm: bool,
+        /// Wait for Enter before each call to the flash algorithm.
+        #[clap(long, requires = "debug_algorithm")]
+        step: bool,
     },
     /// Loads and updates target description from YAML files.
     Reformat {
@@ -158,6 +165,8 @@ async fn main() -> Result<()> {
             test_start_sector_address,
             chip,
             name,
+            debug_algorithm,
+            step,
         } => cmd_test(
             target_artifact.as_path(),
             template_path.as_path(),
@@ -165,6 +174,7 @@ async fn main() -> Result<()> {
             test_start_sector_address,
             chip,
             name,
+            debug_algorithm.then_some(step),
         )?,
         TargetGen::Reformat { yaml_path } => {
             if yaml_path.is_dir() {